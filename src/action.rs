@@ -0,0 +1,560 @@
+use anyhow::{Context, Result};
+
+use crate::app::{App, FocusedPanel, ManagedFile, Modal};
+
+const ACTION_HISTORY_LIMIT: usize = 100;
+
+/// A state change requested by the UI.
+///
+/// Key handlers translate input into actions and hand them to [`App::apply`],
+/// which is the only place that runs side effects such as `op` calls, config
+/// writes, and clipboard access.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    FocusPanel(FocusedPanel),
+    SelectAccount(Option<usize>),
+    SelectVault(Option<usize>),
+    SelectItem(Option<usize>),
+    /// Re-runs the `op ... list` behind the focused panel.
+    RefreshListing,
+    /// Opens an entry of the Recent panel in the Items and Details panels.
+    OpenRecentItem(usize),
+    SetDefaultAccount {
+        account_id: String,
+    },
+    SetDefaultVault {
+        account_id: String,
+        vault_id: String,
+    },
+    SaveVar {
+        env_var_name: String,
+        account_id: String,
+        op_reference: String,
+        prefix: Option<String>,
+    },
+    /// Renames and/or re-points an existing managed var.
+    UpdateVar {
+        original_name: String,
+        env_var_name: String,
+        account_id: String,
+        op_reference: String,
+        prefix: Option<String>,
+    },
+    /// Saves several `(var_name, op_reference)` mappings in one config write.
+    SaveVars {
+        account_id: String,
+        vars: Vec<(String, String)>,
+    },
+    RemoveVars {
+        vars: Vec<String>,
+    },
+    /// Puts back the vars removed by the last `RemoveVars`.
+    UndoVarDeletion,
+    /// Resolves every managed var and shows what `env inject` would print.
+    PreviewEnv,
+    /// Fetches the vault's items that were never opened so search can match
+    /// their field labels.
+    IndexItemFields,
+    /// Downloads a document item to `path` and keeps it as a managed file.
+    PlaceFile {
+        path: String,
+        file: ManagedFile,
+    },
+    /// Runs `op read` for a managed var and logs whether it resolved.
+    TestVar {
+        var: String,
+    },
+    CopyVarNames {
+        vars: Vec<String>,
+    },
+    CopyOpCommand {
+        command: String,
+    },
+    /// Copies a field's full `op://` reference, however wide.
+    CopyReference {
+        reference: String,
+    },
+    CopyErrorDetails {
+        text: String,
+    },
+    /// Copies a field's value. The value itself is never part of the action,
+    /// so it stays out of the action history and logs.
+    CopyFieldValue {
+        reference: String,
+    },
+    /// Clears the clipboard if it still holds the copied field value.
+    ClearClipboard,
+    /// Writes a redacted state snapshot for bug reports.
+    DumpState,
+    /// Reveals a concealed field's value, or masks it again if it's shown.
+    ToggleReveal {
+        reference: String,
+    },
+    CreateVault {
+        name: String,
+        description: String,
+    },
+    /// Creates the item described by the open form. Like `CopyFieldValue`,
+    /// the values stay in the form rather than the action.
+    CreateItem,
+    /// Saves the value typed into the field editor, which also keeps it.
+    EditField,
+    ShowVaultDetails {
+        vault_id: String,
+    },
+    DeleteItem {
+        item_id: String,
+        archive: bool,
+        affected_vars: Vec<String>,
+    },
+}
+
+impl App {
+    pub fn apply(&mut self, action: Action) {
+        log::debug!("Applying action: {action:?}");
+        self.record_action(action.clone());
+        let retry = action.clone();
+
+        match action {
+            Action::Quit => self.should_quit = true,
+            Action::FocusPanel(panel) => {
+                self.focused_panel = panel;
+                if panel == FocusedPanel::VarsList
+                    && self.managed_vars_list_state.selected().is_none()
+                    && !self.managed_vars.is_empty()
+                {
+                    self.managed_vars_list_state.select(Some(0));
+                }
+            }
+            Action::SelectAccount(idx) => {
+                self.pending_recent_jump = None;
+                self.select_account(idx);
+            }
+            Action::SelectVault(idx) => {
+                self.pending_recent_jump = None;
+                self.selected_vault_idx = idx;
+                self.items_marked.clear();
+                self.clear_search();
+                if let Err(e) = self.load_vault_items() {
+                    self.error_message = Some(e.to_string());
+                }
+                self.focused_panel = FocusedPanel::VaultItemList;
+            }
+            Action::SelectItem(list_idx) => {
+                self.pending_recent_jump = None;
+                self.select_item(list_idx);
+            }
+            Action::RefreshListing => {
+                if let Err(e) = self.refresh_focused_listing() {
+                    self.error_message = Some(e.to_string());
+                }
+            }
+            Action::OpenRecentItem(idx) => {
+                if let Err(e) = self.open_recent_item(idx) {
+                    self.error_message = Some(e.to_string());
+                }
+            }
+            Action::SetDefaultAccount { account_id } => {
+                if let Err(e) = self.set_default_account(&account_id) {
+                    self.command_log.log_failure(
+                        "Failed to save default account configuration",
+                        e.to_string(),
+                    );
+                } else {
+                    self.command_log
+                        .log_success("Saved default account configuration", None);
+                    self.apply(Action::SelectAccount(self.account_list_state.selected()));
+                }
+            }
+            Action::SetDefaultVault {
+                account_id,
+                vault_id,
+            } => {
+                if let Err(e) = self.set_default_vault(&account_id, &vault_id) {
+                    self.command_log
+                        .log_failure("Failed to save default vault configuration", e.to_string());
+                } else {
+                    self.command_log
+                        .log_success("Saved default vault configuration", None);
+                    self.apply(Action::SelectVault(self.vault_list_state.selected()));
+                }
+            }
+            Action::SaveVar {
+                env_var_name,
+                account_id,
+                op_reference,
+                prefix,
+            } => match self.save_op_item_config(
+                &env_var_name,
+                &account_id,
+                &op_reference,
+                prefix.as_deref(),
+            ) {
+                Ok(()) => {
+                    self.command_log
+                        .log_success(format!("Saved {env_var_name} to config"), None);
+                    if let Some((item_id, title)) = self
+                        .selected_item_details
+                        .as_ref()
+                        .map(|details| (details.id.clone(), details.title.clone()))
+                    {
+                        self.record_recent_item(&item_id, &title);
+                    }
+                    self.load_managed_vars();
+                    if self.managed_vars_list_state.selected().is_none()
+                        && !self.managed_vars.is_empty()
+                    {
+                        self.managed_vars_list_state.select(Some(0));
+                    }
+                    self.close_modal();
+                }
+                Err(e) => self.error_message = Some(e.to_string()),
+            },
+            Action::UpdateVar {
+                original_name,
+                env_var_name,
+                account_id,
+                op_reference,
+                prefix,
+            } => match self.update_managed_var(
+                &original_name,
+                &env_var_name,
+                &account_id,
+                &op_reference,
+                prefix.as_deref(),
+            ) {
+                Ok(()) => {
+                    self.command_log
+                        .log_success(format!("Updated {env_var_name} in config"), None);
+                    self.close_modal();
+                }
+                Err(e) => self.error_message = Some(e.to_string()),
+            },
+            Action::SaveVars { account_id, vars } => {
+                match self.save_op_item_configs(&account_id, &vars) {
+                    Ok(()) => {
+                        self.command_log
+                            .log_success(format!("Saved {} vars to config", vars.len()), None);
+                        if let Some(Modal::BulkMap { rows, .. }) = self.modal.clone() {
+                            for row in rows.iter().rev() {
+                                self.record_recent_item(&row.item_id, &row.item_title);
+                            }
+                        }
+                        self.items_marked.clear();
+                        self.load_managed_vars();
+                        if self.managed_vars_list_state.selected().is_none()
+                            && !self.managed_vars.is_empty()
+                        {
+                            self.managed_vars_list_state.select(Some(0));
+                        }
+                        self.close_modal();
+                    }
+                    Err(e) => self.error_message = Some(e.to_string()),
+                }
+            }
+            Action::PreviewEnv => {
+                if let Err(err) = self.open_env_preview() {
+                    self.error_message = Some(err.to_string());
+                }
+            }
+            Action::IndexItemFields => {
+                if let Err(err) = self.index_item_fields() {
+                    self.error_message = Some(err.to_string());
+                }
+            }
+            Action::PlaceFile { path, file } => {
+                let title = file.title.clone();
+                match self.place_managed_file(&path, file) {
+                    Ok(target) => {
+                        self.command_log
+                            .log_success(format!("Placed {title} at {}", target.display()), None);
+                        self.close_modal();
+                    }
+                    Err(err) => self.error_message = Some(err.to_string()),
+                }
+            }
+            Action::TestVar { var } => {
+                // Failures are already in the command log with op's stderr.
+                if let Err(err) = self.test_managed_var(&var) {
+                    log::debug!("Test-resolving {var} failed: {err:#}");
+                }
+            }
+            Action::RemoveVars { vars } => match self.remove_managed_vars(&vars) {
+                Ok(()) => {
+                    self.command_log
+                        .log_success("Vars removed ([u] in Vars to undo)", None);
+                    self.close_modal();
+                }
+                Err(err) => self.error_message = Some(err.to_string()),
+            },
+            Action::UndoVarDeletion => match self.restore_deleted_vars() {
+                Ok(restored) => self
+                    .command_log
+                    .log_success(format!("Vars restored ({restored})"), None),
+                Err(err) => self.error_message = Some(err.to_string()),
+            },
+            Action::CopyVarNames { vars } => match copy_to_clipboard(&vars.join(", ")) {
+                Ok(()) => self.command_log.log_success("Vars copied", None),
+                Err(err) => self.command_log.log_failure("Vars copy", err.to_string()),
+            },
+            Action::CopyReference { reference } => match copy_to_clipboard(&reference) {
+                Ok(()) => self
+                    .command_log
+                    .log_success(format!("Copied {reference}"), None),
+                Err(err) => self
+                    .command_log
+                    .log_failure("Reference copy", err.to_string()),
+            },
+            Action::CopyOpCommand { command } => match copy_to_clipboard(&command) {
+                Ok(()) => self
+                    .command_log
+                    .log_success(format!("Copied `{command}`"), None),
+                Err(err) => self
+                    .command_log
+                    .log_failure("op command copy", err.to_string()),
+            },
+            Action::CopyErrorDetails { text } => match copy_to_clipboard(&text) {
+                Ok(()) => self.command_log.log_success("Copied error details", None),
+                Err(err) => self
+                    .command_log
+                    .log_failure("Error details copy", err.to_string()),
+            },
+            Action::CopyFieldValue { reference } => {
+                let result = self
+                    .field_value(&reference)
+                    .and_then(|value| copy_to_clipboard(&value).map(|()| value));
+                match result {
+                    Ok(value) => {
+                        self.command_log
+                            .log_success(format!("Copied value of {reference}"), None);
+                        self.schedule_clipboard_clear(&value);
+                    }
+                    Err(err) => self
+                        .command_log
+                        .log_failure("Field value copy", err.to_string()),
+                }
+            }
+            Action::ClearClipboard => {
+                if let Some(pending) = self.clipboard_clear.take() {
+                    match clear_clipboard_if_unchanged(&pending.hash) {
+                        Ok(true) => self.command_log.log_success("Cleared clipboard", None),
+                        Ok(false) => {}
+                        Err(err) => self
+                            .command_log
+                            .log_failure("Clipboard clear", err.to_string()),
+                    }
+                }
+            }
+            Action::DumpState => {
+                let snapshot = crate::debug_state::collect(Some(self));
+                match crate::debug_state::write_snapshot(&snapshot, None) {
+                    Ok(path) => self
+                        .command_log
+                        .log_success(format!("Wrote state snapshot to {}", path.display()), None),
+                    Err(err) => self
+                        .command_log
+                        .log_failure("State snapshot", err.to_string()),
+                }
+            }
+            Action::ToggleReveal { reference } => {
+                if self
+                    .revealed_field
+                    .as_ref()
+                    .is_some_and(|revealed| revealed.reference == reference)
+                {
+                    self.revealed_field = None;
+                } else if let Err(err) = self.reveal_field(&reference) {
+                    self.command_log
+                        .log_failure("Field value reveal", err.to_string());
+                }
+            }
+            Action::EditField => match self.edit_field_from_modal() {
+                Ok(()) => self.close_modal(),
+                Err(err) => self.error_message = Some(err.to_string()),
+            },
+            Action::CreateItem => match self.create_item_from_modal() {
+                Ok(()) => self.close_modal(),
+                Err(err) => self.error_message = Some(err.to_string()),
+            },
+            Action::CreateVault { name, description } => {
+                match self.create_vault(&name, &description) {
+                    Ok(()) => self.close_modal(),
+                    Err(err) => self.error_message = Some(err.to_string()),
+                }
+            }
+            Action::ShowVaultDetails { vault_id } => match self.load_vault_details(&vault_id) {
+                Ok(details) => self.modal = Some(Modal::VaultDetails { details }),
+                Err(err) => self.error_message = Some(err.to_string()),
+            },
+            Action::DeleteItem {
+                item_id,
+                archive,
+                affected_vars,
+            } => match self.delete_vault_item(&item_id, archive) {
+                Ok(()) => {
+                    self.close_modal();
+                    if !affected_vars.is_empty() {
+                        self.command_log
+                            .log_failure("Broken references", affected_vars.join(", "));
+                        self.open_vars_delete_modal(affected_vars);
+                    }
+                }
+                Err(err) => self.error_message = Some(err.to_string()),
+            },
+        }
+
+        self.set_signin_retry(retry);
+    }
+
+    fn record_action(&mut self, action: Action) {
+        self.action_history.push(action);
+        if self.action_history.len() > ACTION_HISTORY_LIMIT {
+            self.action_history
+                .drain(0..self.action_history.len() - ACTION_HISTORY_LIMIT);
+        }
+    }
+
+    fn select_account(&mut self, idx: Option<usize>) {
+        self.selected_account_idx = idx;
+
+        // Picking an account is an explicit retry, so end any cool-down.
+        if let Some(account_id) = self.selected_account().map(|a| a.account_uuid.clone()) {
+            self.account_backoff.reset(&account_id);
+        }
+
+        self.clear_search();
+        self.items_marked.clear();
+        self.vault_items.clear();
+        self.filtered_item_indices.clear();
+        self.selected_item_details = None;
+        self.totp = None;
+
+        if let Err(e) = self.load_vaults() {
+            self.error_message = Some(e.to_string());
+        }
+
+        self.focused_panel = FocusedPanel::VaultList;
+    }
+
+    /// Opens a recent item: selects its account and vault, then the item
+    /// itself once the listings are in. The listings may load in the
+    /// background, so the target is kept in `pending_recent_jump` until then.
+    fn open_recent_item(&mut self, idx: usize) -> Result<()> {
+        let recent = self
+            .recent_items
+            .items
+            .get(idx)
+            .cloned()
+            .context("No recent item selected")?;
+        let account_idx = self
+            .accounts
+            .iter()
+            .position(|a| a.account_uuid == recent.account_id)
+            .with_context(|| {
+                format!(
+                    "The account holding {} is no longer signed in",
+                    recent.title
+                )
+            })?;
+
+        self.pending_recent_jump = Some(recent);
+        if self.selected_account_idx == Some(account_idx) && !self.vaults.is_empty() {
+            return self.select_recent_vault();
+        }
+        self.account_list_state.select(Some(account_idx));
+        self.select_account(Some(account_idx));
+        Ok(())
+    }
+
+    fn select_item(&mut self, list_idx: Option<usize>) {
+        self.selected_vault_item_idx = list_idx;
+
+        if let Some(list_idx) = list_idx
+            && let Some(&real_idx) = self.filtered_item_indices.get(list_idx)
+            && let Some(item) = self.vault_items.get(real_idx)
+        {
+            let item_id = item.id.clone();
+            if let Err(e) = self.load_item_details(&item_id) {
+                self.error_message = Some(e.to_string());
+            }
+        }
+    }
+}
+
+/// Empties the clipboard unless something else was copied since.
+fn clear_clipboard_if_unchanged(hash: &str) -> Result<bool> {
+    let output = std::process::Command::new("pbpaste")
+        .output()
+        .context("Failed to launch pbpaste")?;
+    let current = String::from_utf8_lossy(&output.stdout);
+    if crate::cache::config_hash(&current) != hash {
+        return Ok(false);
+    }
+    copy_to_clipboard("")?;
+    Ok(true)
+}
+
+fn copy_to_clipboard(value: &str) -> Result<()> {
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new("pbcopy")
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Failed to launch pbcopy")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        use std::io::Write;
+        stdin
+            .write_all(value.as_bytes())
+            .context("Failed to write to pbcopy")?;
+    }
+
+    let status = child.wait().context("Failed to wait for pbcopy")?;
+    if status.success() {
+        Ok(())
+    } else {
+        anyhow::bail!("pbcopy exited with status {status}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn focus_vars_panel_selects_first_var() {
+        let mut app = App::new();
+        app.managed_vars = vec!["A".to_string(), "B".to_string()];
+
+        app.apply(Action::FocusPanel(FocusedPanel::VarsList));
+
+        assert!(app.focused_panel == FocusedPanel::VarsList);
+        assert_eq!(app.managed_vars_list_state.selected(), Some(0));
+    }
+
+    #[test]
+    fn applied_actions_are_recorded() {
+        let mut app = App::new();
+
+        app.apply(Action::FocusPanel(FocusedPanel::AccountList));
+        app.apply(Action::Quit);
+
+        assert_eq!(
+            app.action_history,
+            vec![Action::FocusPanel(FocusedPanel::AccountList), Action::Quit]
+        );
+        assert!(app.should_quit);
+    }
+
+    #[test]
+    fn action_history_is_bounded() {
+        let mut app = App::new();
+
+        for _ in 0..ACTION_HISTORY_LIMIT + 10 {
+            app.apply(Action::FocusPanel(FocusedPanel::VaultList));
+        }
+
+        assert_eq!(app.action_history.len(), ACTION_HISTORY_LIMIT);
+    }
+}
@@ -0,0 +1,570 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::cache::{CacheEntry, cache_dir};
+
+pub type ResolvedVarsByAccount = HashMap<String, HashMap<String, String>>;
+
+#[cfg_attr(not(unix), allow(dead_code))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+enum AgentRequest {
+    Get,
+    /// Answered from memory, so it never waits on 1Password.
+    Health,
+    Stop,
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum AgentResponse {
+    Vars { vars: ResolvedVarsByAccount },
+    Health { health: AgentHealth },
+    Stopped,
+    Error { message: String },
+}
+
+/// What a running agent reports about itself, for `status` and prompts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentHealth {
+    pub uptime_secs: u64,
+    /// Accounts whose secrets are held in memory, sorted.
+    pub accounts_warmed: Vec<String>,
+    /// Seconds since secrets were last resolved successfully.
+    pub last_refresh_secs: u64,
+    /// The last refresh failed (1Password locked or signed out, for
+    /// example), so the agent serves errors until a refresh succeeds.
+    pub locked: bool,
+}
+
+/// The socket lives in its own `0700` directory under the cache dir, so only
+/// the owning user can connect.
+pub fn socket_path() -> Result<PathBuf> {
+    Ok(cache_dir()?.join("agent").join("agent.sock"))
+}
+
+/// Resolved secrets held in memory, along with the mappings they came from.
+#[cfg(unix)]
+struct AgentState {
+    /// When the secrets were resolved and from which mappings; the payload
+    /// stays empty since the secrets never leave memory.
+    resolved: CacheEntry,
+    cache_ttl: Option<Duration>,
+    vars: ResolvedVarsByAccount,
+    started: Instant,
+    last_refresh: Instant,
+    locked: bool,
+}
+
+/// Whether the secrets held by the agent should be resolved again.
+#[cfg_attr(not(unix), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Refresh {
+    /// Still fresh.
+    NotDue,
+    /// In the last 10% of the TTL: try again, but keep serving the held
+    /// secrets if that fails.
+    Soon,
+    /// The mappings changed or the TTL passed.
+    Now,
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+fn refresh_due(
+    resolved: &CacheEntry,
+    mappings_hash: &str,
+    cache_ttl: Option<Duration>,
+    now: SystemTime,
+) -> Refresh {
+    if resolved.config_hash != mappings_hash {
+        return Refresh::Now;
+    }
+    match cache_ttl {
+        Some(ttl) if !resolved.is_fresh(ttl, now) => Refresh::Now,
+        Some(ttl) if resolved.expires_soon(ttl, now) => Refresh::Soon,
+        _ => Refresh::NotDue,
+    }
+}
+
+#[cfg(unix)]
+impl AgentState {
+    fn load(cache_ttl: Option<Duration>) -> Result<Self> {
+        let config: crate::app::OpLoadConfig =
+            confy::load("op_loader", None).context("Failed to load configuration")?;
+        let now = Instant::now();
+        Ok(Self {
+            resolved: resolved_entry(&config, cache_ttl),
+            cache_ttl,
+            vars: crate::cli::resolve_all_vars(&config)?,
+            started: now,
+            last_refresh: now,
+            locked: false,
+        })
+    }
+
+    /// Re-resolves when the configured mappings changed since the last load
+    /// or the held secrets are older than the cache TTL.
+    fn refresh_if_changed(&mut self) -> Result<()> {
+        let config: crate::app::OpLoadConfig =
+            confy::load("op_loader", None).context("Failed to load configuration")?;
+        let refresh = refresh_due(
+            &self.resolved,
+            &mappings_hash(&config),
+            self.cache_ttl,
+            SystemTime::now(),
+        );
+        if refresh == Refresh::NotDue {
+            return Ok(());
+        }
+
+        log::info!("Mappings changed or secrets expiring, re-resolving secrets");
+        match crate::cli::resolve_all_vars(&config) {
+            Ok(vars) => {
+                self.vars = vars;
+                self.resolved = resolved_entry(&config, self.cache_ttl);
+                self.last_refresh = Instant::now();
+                self.locked = false;
+                Ok(())
+            }
+            Err(err) if refresh == Refresh::Soon => {
+                log::warn!("Failed to re-resolve secrets, serving the held ones: {err:#}");
+                Ok(())
+            }
+            Err(err) => {
+                self.locked = true;
+                Err(err)
+            }
+        }
+    }
+
+    fn health(&self) -> AgentHealth {
+        let mut accounts_warmed: Vec<String> = self.vars.keys().cloned().collect();
+        accounts_warmed.sort();
+        AgentHealth {
+            uptime_secs: self.started.elapsed().as_secs(),
+            accounts_warmed,
+            last_refresh_secs: self.last_refresh.elapsed().as_secs(),
+            locked: self.locked,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn resolved_entry(config: &crate::app::OpLoadConfig, cache_ttl: Option<Duration>) -> CacheEntry {
+    CacheEntry::new(
+        String::new(),
+        cache_ttl.unwrap_or(Duration::MAX),
+        mappings_hash(config),
+    )
+}
+
+#[cfg_attr(not(unix), allow(dead_code))]
+fn mappings_hash(config: &crate::app::OpLoadConfig) -> String {
+    let mut lines: Vec<String> = config
+        .inject_vars
+        .iter()
+        .map(|(name, var)| format!("{name}={}:{}", var.account_id, var.op_reference))
+        .collect();
+    lines.sort();
+    crate::cache::config_hash(&lines.join("\n"))
+}
+
+/// Runs the agent in the foreground until `agent stop` or the idle timeout.
+#[cfg(unix)]
+pub fn run_agent(idle_timeout: Option<Duration>, cache_ttl: Option<Duration>) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let path = socket_path()?;
+    let dir = path
+        .parent()
+        .context("Socket path has no parent directory")?;
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .with_context(|| format!("Failed to create agent directory: {}", dir.display()))?;
+    // The mode above only applies to a new directory.
+    let mode = std::fs::metadata(dir)
+        .with_context(|| format!("Failed to read agent directory: {}", dir.display()))?
+        .permissions()
+        .mode();
+    if mode & 0o077 != 0 {
+        log::info!(
+            "Restricting agent directory {} from {:o} to 700",
+            dir.display(),
+            mode & 0o777
+        );
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+            .with_context(|| format!("Failed to restrict agent directory: {}", dir.display()))?;
+    }
+
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            bail!("An agent is already running at {}", path.display());
+        }
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove stale socket: {}", path.display()))?;
+    }
+
+    let mut state = AgentState::load(cache_ttl)?;
+
+    // Created owner-only, so there's no window in which others can connect.
+    let listener = {
+        // SAFETY: umask only swaps the process's file mode mask, and no other
+        // thread creates files while the agent starts up.
+        let previous = unsafe { libc::umask(0o177) };
+        let bound = UnixListener::bind(&path);
+        unsafe { libc::umask(previous) };
+        bound.with_context(|| format!("Failed to bind agent socket: {}", path.display()))?
+    };
+
+    eprintln!(
+        "op-loader agent listening on {} ({} account(s) resolved)",
+        path.display(),
+        state.vars.len()
+    );
+
+    let result = serve(&listener, idle_timeout, |request| match request {
+        AgentRequest::Get => match state.refresh_if_changed() {
+            Ok(()) => AgentResponse::Vars {
+                vars: state.vars.clone(),
+            },
+            Err(err) => {
+                crate::desktop_notification::notify(
+                    "op-loader agent failed to resolve secrets",
+                    &err.to_string(),
+                );
+                AgentResponse::Error {
+                    message: err.to_string(),
+                }
+            }
+        },
+        AgentRequest::Health => AgentResponse::Health {
+            health: state.health(),
+        },
+        AgentRequest::Stop => AgentResponse::Stopped,
+    });
+
+    if let Err(err) = std::fs::remove_file(&path) {
+        eprintln!("Warning: Failed to remove agent socket: {err}");
+    }
+    result
+}
+
+#[cfg(not(unix))]
+pub fn run_agent(_idle_timeout: Option<Duration>, _cache_ttl: Option<Duration>) -> Result<()> {
+    bail!("The agent is only supported on Unix-like systems.");
+}
+
+#[cfg(unix)]
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Answers one JSON request per connection until a stop request arrives or
+/// no request has been seen for `idle_timeout`.
+#[cfg(unix)]
+fn serve(
+    listener: &std::os::unix::net::UnixListener,
+    idle_timeout: Option<Duration>,
+    mut respond: impl FnMut(AgentRequest) -> AgentResponse,
+) -> Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+
+    listener
+        .set_nonblocking(true)
+        .context("Failed to configure agent socket")?;
+    let mut last_request = std::time::Instant::now();
+
+    loop {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                if idle_timeout.is_some_and(|timeout| last_request.elapsed() >= timeout) {
+                    log::info!("Agent idle timeout reached, shutting down");
+                    return Ok(());
+                }
+                std::thread::sleep(ACCEPT_POLL_INTERVAL);
+                continue;
+            }
+            Err(err) => return Err(err).context("Failed to accept agent connection"),
+        };
+
+        // Accepted sockets inherit non-blocking mode on some platforms.
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+        let mut line = String::new();
+        let response = match BufReader::new(&stream).read_line(&mut line) {
+            Ok(_) => match serde_json::from_str::<AgentRequest>(line.trim()) {
+                Ok(request) => {
+                    // Only requests for secrets keep the agent alive, so a
+                    // prompt polling its health doesn't hold them forever.
+                    if request == AgentRequest::Get {
+                        last_request = std::time::Instant::now();
+                    }
+                    respond(request)
+                }
+                Err(err) => AgentResponse::Error {
+                    message: format!("Invalid request: {err}"),
+                },
+            },
+            Err(err) => AgentResponse::Error {
+                message: format!("Failed to read request: {err}"),
+            },
+        };
+
+        let stopping = response == AgentResponse::Stopped;
+        let mut payload = serde_json::to_vec(&response).context("Failed to serialize response")?;
+        payload.push(b'\n');
+        if let Err(err) = (&stream).write_all(&payload) {
+            eprintln!("Warning: Failed to answer agent request: {err}");
+        }
+
+        if stopping {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(unix)]
+fn send_request(path: &std::path::Path, request: &AgentRequest) -> Result<AgentResponse> {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(path).with_context(|| {
+        format!(
+            "No agent is running at {}. Start one with `op-loader agent`.",
+            path.display()
+        )
+    })?;
+
+    let mut payload = serde_json::to_vec(request).context("Failed to serialize request")?;
+    payload.push(b'\n');
+    stream
+        .write_all(&payload)
+        .context("Failed to send request to agent")?;
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .context("Failed to read agent response")?;
+    serde_json::from_str(response.trim()).context("Failed to parse agent response")
+}
+
+/// Fetches the resolved secrets from a running agent.
+#[cfg(unix)]
+pub fn request_vars() -> Result<ResolvedVarsByAccount> {
+    match send_request(&socket_path()?, &AgentRequest::Get)? {
+        AgentResponse::Vars { vars } => Ok(vars),
+        AgentResponse::Error { message } => bail!("Agent failed to resolve secrets: {message}"),
+        AgentResponse::Stopped => bail!("Agent is shutting down"),
+        AgentResponse::Health { .. } => bail!("Unexpected agent response"),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn request_vars() -> Result<ResolvedVarsByAccount> {
+    bail!("The agent is only supported on Unix-like systems.");
+}
+
+#[cfg(unix)]
+pub fn request_health() -> Result<AgentHealth> {
+    match send_request(&socket_path()?, &AgentRequest::Health)? {
+        AgentResponse::Health { health } => Ok(health),
+        AgentResponse::Error { message } => bail!("Agent health check failed: {message}"),
+        AgentResponse::Vars { .. } | AgentResponse::Stopped => {
+            bail!("Unexpected agent response")
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn request_health() -> Result<AgentHealth> {
+    bail!("The agent is only supported on Unix-like systems.");
+}
+
+/// Whether an agent is accepting connections on the socket.
+#[cfg(unix)]
+pub fn is_running() -> bool {
+    socket_path().is_ok_and(|path| std::os::unix::net::UnixStream::connect(path).is_ok())
+}
+
+#[cfg(not(unix))]
+pub fn is_running() -> bool {
+    false
+}
+
+#[cfg(unix)]
+pub fn stop_agent() -> Result<()> {
+    match send_request(&socket_path()?, &AgentRequest::Stop)? {
+        AgentResponse::Stopped => Ok(()),
+        AgentResponse::Error { message } => bail!("Agent refused to stop: {message}"),
+        AgentResponse::Vars { .. } | AgentResponse::Health { .. } => {
+            bail!("Unexpected agent response")
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn stop_agent() -> Result<()> {
+    bail!("The agent is only supported on Unix-like systems.");
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    fn sample_vars() -> ResolvedVarsByAccount {
+        HashMap::from([(
+            "acct".to_string(),
+            HashMap::from([("TOKEN".to_string(), "secret".to_string())]),
+        )])
+    }
+
+    fn sample_health() -> AgentHealth {
+        AgentHealth {
+            uptime_secs: 120,
+            accounts_warmed: vec!["acct".to_string()],
+            last_refresh_secs: 30,
+            locked: false,
+        }
+    }
+
+    #[test]
+    fn serves_vars_until_stopped() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("agent.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let server = std::thread::spawn(move || {
+            serve(&listener, None, |request| match request {
+                AgentRequest::Get => AgentResponse::Vars {
+                    vars: sample_vars(),
+                },
+                AgentRequest::Health => AgentResponse::Health {
+                    health: sample_health(),
+                },
+                AgentRequest::Stop => AgentResponse::Stopped,
+            })
+        });
+
+        assert_eq!(
+            send_request(&path, &AgentRequest::Get).unwrap(),
+            AgentResponse::Vars {
+                vars: sample_vars()
+            }
+        );
+        assert_eq!(
+            send_request(&path, &AgentRequest::Health).unwrap(),
+            AgentResponse::Health {
+                health: sample_health()
+            }
+        );
+        assert_eq!(
+            send_request(&path, &AgentRequest::Stop).unwrap(),
+            AgentResponse::Stopped
+        );
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn exits_after_idle_timeout() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let listener = UnixListener::bind(dir.path().join("agent.sock")).unwrap();
+
+        serve(&listener, Some(Duration::from_millis(50)), |_| {
+            AgentResponse::Stopped
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn health_requests_do_not_keep_the_agent_alive() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("agent.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = std::thread::spawn(move || {
+            serve(&listener, Some(Duration::from_millis(500)), |_| {
+                AgentResponse::Health {
+                    health: sample_health(),
+                }
+            })
+        });
+
+        let started = Instant::now();
+        while !server.is_finished() {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "agent kept running"
+            );
+            let _ = send_request(&path, &AgentRequest::Health);
+            std::thread::sleep(Duration::from_millis(50));
+        }
+
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn refreshes_on_changed_mappings_and_cache_ttl() {
+        let ttl = Duration::from_secs(100);
+        let resolved = CacheEntry::new(String::new(), ttl, "hash".to_string());
+        let at = |secs| {
+            std::time::UNIX_EPOCH
+                + Duration::from_secs(resolved.created_at)
+                + Duration::from_secs(secs)
+        };
+
+        assert_eq!(
+            refresh_due(&resolved, "hash", Some(ttl), at(10)),
+            Refresh::NotDue
+        );
+        assert_eq!(
+            refresh_due(&resolved, "other", Some(ttl), at(10)),
+            Refresh::Now
+        );
+        assert_eq!(
+            refresh_due(&resolved, "hash", Some(ttl), at(95)),
+            Refresh::Soon
+        );
+        assert_eq!(
+            refresh_due(&resolved, "hash", Some(ttl), at(101)),
+            Refresh::Now
+        );
+        assert_eq!(
+            refresh_due(&resolved, "hash", None, at(10_000)),
+            Refresh::NotDue
+        );
+    }
+
+    #[test]
+    fn mappings_hash_ignores_order_but_not_references() {
+        use crate::app::{InjectVarConfig, OpLoadConfig};
+
+        let var = |reference: &str| InjectVarConfig {
+            account_id: "acct".to_string(),
+            op_reference: reference.to_string(),
+            prefix: None,
+        };
+        let config = |reference: &str| OpLoadConfig {
+            inject_vars: HashMap::from([
+                ("A".to_string(), var("op://v/a/f")),
+                ("B".to_string(), var(reference)),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            mappings_hash(&config("op://v/b/f")),
+            mappings_hash(&config("op://v/b/f"))
+        );
+        assert_ne!(
+            mappings_hash(&config("op://v/b/f")),
+            mappings_hash(&config("op://v/b/other"))
+        );
+    }
+}
@@ -3,43 +3,484 @@ use fuzzy_matcher::FuzzyMatcher;
 use fuzzy_matcher::skim::SkimMatcherV2;
 use ratatui::widgets::ListState;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, collections::HashSet, process::Command};
-
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::{
+    collections::HashMap,
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::action::Action;
+use crate::backoff::FailureBackoff;
 use crate::cache::{CacheRemoval, remove_cache_for_account};
-use crate::command_log::CommandLog;
+use crate::command_log::{CommandLog, ErrorDetails};
+use crate::listing::{ListingRefresh, ListingRequest, run_op, spawn_refresh};
+use crate::op_client::{SharedOpClient, SubprocessOpClient};
+use crate::op_error::{annotate, classify};
+use crate::op_version::{OpFeature, OpVersion};
+use crate::password_gen::Recipe;
+use crate::policy::load_policy;
+use crate::recent_items::{RecentItem, RecentItems};
+use crate::template_naming::TemplateNaming;
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct TemplatedFile {
     pub template_name: String,
+    /// The only managed vars the template can use, when set. Without it
+    /// every managed var is available.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vars: Option<Vec<String>>,
+    /// Render into the state directory and make the target a symlink to
+    /// it, instead of writing the target itself.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub symlink: bool,
+}
+
+impl TemplatedFile {
+    /// Whether the template may use the managed var `name`.
+    pub fn allows(&self, name: &str) -> bool {
+        self.vars
+            .as_ref()
+            .is_none_or(|vars| vars.iter().any(|var| var == name))
+    }
+}
+
+/// A document item written to disk, keyed by its target path in
+/// `managed_files`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagedFile {
+    pub account_id: String,
+    pub vault_id: String,
+    pub item_id: String,
+    /// The item's title when the file was added, for listings.
+    #[serde(default)]
+    pub title: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InjectVarConfig {
     pub account_id: String,
     pub op_reference: String,
+    /// Prepended to the name when exporting, e.g. `PROD_`, so one item can
+    /// back differently named vars.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+}
+
+impl InjectVarConfig {
+    /// The name the var is exported under: `PROD_` + `TOKEN` is
+    /// `PROD_TOKEN`. Templates still use the unprefixed name.
+    pub fn exported_name(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix.as_deref().unwrap_or_default())
+    }
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct OpLoadConfig {
     #[serde(default)]
     pub inject_vars: HashMap<String, InjectVarConfig>,
+    /// Prefixes offered with Ctrl+P when saving a var, e.g. `["PROD_"]`.
+    #[serde(default)]
+    pub var_prefixes: Vec<String>,
     #[serde(default)]
     pub default_account_id: Option<String>,
     #[serde(default)]
     pub default_vault_per_account: HashMap<String, String>,
     #[serde(default)]
     pub templated_files: HashMap<String, TemplatedFile>,
+    /// Document items placed on disk by `env inject` and `bootstrap`.
+    #[serde(default)]
+    pub managed_files: HashMap<String, ManagedFile>,
+    #[serde(default)]
+    pub allowed_sync_paths: Vec<String>,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub template_history: TemplateHistoryConfig,
+    #[serde(default)]
+    pub tui: TuiConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub clipboard: ClipboardConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub templates: TemplatesConfig,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TemplatesConfig {
+    /// How new template files are named; `template migrate-names` renames
+    /// existing ones to match.
+    #[serde(default)]
+    pub naming: TemplateNaming,
+    /// Backups kept per target, taken before a render changes it (default
+    /// 5; 0 turns them off).
+    #[serde(default)]
+    pub backups: Option<usize>,
+}
+
+impl TemplatesConfig {
+    pub fn backups(&self) -> usize {
+        self.backups.unwrap_or(5)
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct SecurityConfig {
+    /// Tighten the config, templates, and cache permissions to owner-only
+    /// before and after every run, instead of only warning about them.
+    #[serde(default)]
+    pub strict_permissions: bool,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ClipboardConfig {
+    /// Clear copied field values after this long, e.g. `45s` (default `30s`,
+    /// `0s` disables).
+    #[serde(default)]
+    pub clear_after: Option<String>,
+}
+
+const DEFAULT_CLIPBOARD_CLEAR_AFTER: Duration = Duration::from_secs(30);
+
+impl ClipboardConfig {
+    pub fn clear_after(&self) -> Option<Duration> {
+        match self.clear_after.as_deref() {
+            None => Some(DEFAULT_CLIPBOARD_CLEAR_AFTER),
+            Some(value) => crate::cli::parse_duration(value)
+                .ok()
+                .flatten()
+                .filter(|duration| !duration.is_zero()),
+        }
+    }
+}
+
+/// A copied secret to wipe from the clipboard. Only its hash is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingClipboardClear {
+    pub at: std::time::Instant,
+    pub hash: String,
+}
+
+/// Anonymous usage counters are only recorded after an explicit opt-in.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// URL that `telemetry send` posts the counters to.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TuiConfig {
+    /// Mask field values and close secret-bearing modals while the terminal
+    /// is unfocused (default true).
+    #[serde(default)]
+    pub lock_on_focus_loss: Option<bool>,
+    /// How long a concealed value revealed with `r` stays visible, e.g. `5s`
+    /// (default `10s`).
+    #[serde(default)]
+    pub reveal_for: Option<String>,
+    /// How many recently used items the Recent panel lists (default 10).
+    /// `0` stops recording them.
+    #[serde(default)]
+    pub recent_items: Option<usize>,
+    /// How much of the width, in percent, the left column of panels takes
+    /// on terminals wide enough for two columns (default 30).
+    #[serde(default)]
+    pub left_pane_percent: Option<u16>,
+}
+
+pub const DEFAULT_LEFT_PANE_PERCENT: u16 = 30;
+
+const DEFAULT_REVEAL_FOR: Duration = Duration::from_secs(10);
+
+impl TuiConfig {
+    pub fn lock_on_focus_loss(&self) -> bool {
+        self.lock_on_focus_loss.unwrap_or(true)
+    }
+
+    pub fn reveal_for(&self) -> Duration {
+        self.reveal_for
+            .as_deref()
+            .and_then(|value| crate::cli::parse_duration(value).ok().flatten())
+            .filter(|duration| !duration.is_zero())
+            .unwrap_or(DEFAULT_REVEAL_FOR)
+    }
+
+    pub fn recent_items(&self) -> usize {
+        self.recent_items
+            .unwrap_or(crate::recent_items::DEFAULT_LIMIT)
+    }
+
+    /// Kept between 15 and 60 so neither column disappears.
+    pub fn left_pane_percent(&self) -> u16 {
+        self.left_pane_percent
+            .unwrap_or(DEFAULT_LEFT_PANE_PERCENT)
+            .clamp(15, 60)
+    }
+}
+
+/// The one-time code shown for the selected item's OTP field. `window` is
+/// the rotation window the code belongs to; a new code is fetched once the
+/// window has passed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TotpCode {
+    pub item_id: String,
+    pub code: Option<String>,
+    pub period: u64,
+    pub window: Option<u64>,
+    pub refreshing: bool,
+}
+
+/// A concealed value shown in the details panel until `until`, or until the
+/// highlight moves off its field.
+pub struct RevealedField {
+    pub reference: String,
+    pub value: String,
+    pub until: std::time::Instant,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TemplateHistoryConfig {
+    /// Number of renders to remember per template (default 5).
+    #[serde(default)]
+    pub keep: Option<usize>,
+    /// Also keep the rendered output so renders can be diffed and rolled
+    /// back: encrypted on macOS and Windows, plain text on Linux.
+    #[serde(default)]
+    pub store_content: bool,
 }
 
+impl TemplateHistoryConfig {
+    pub fn keep(&self) -> usize {
+        self.keep.unwrap_or(5)
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// Upper bound on the total size of cache files, e.g. `10MB`.
+    #[serde(default)]
+    pub max_size: Option<String>,
+}
+
+/// How the TUI signals that a slow `op` call has finished.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct NotificationConfig {
+    /// Ring the terminal bell.
+    #[serde(default)]
+    pub bell: bool,
+    /// Highlight the command log until the next key press.
+    #[serde(default)]
+    pub flash: bool,
+    /// Only notify for calls at least this long, e.g. `3s` (the default).
+    #[serde(default)]
+    pub min_duration: Option<String>,
+    /// Send a desktop notification when a cache warm finishes, a background
+    /// refresh or the agent fails to resolve, or a template render fails.
+    #[serde(default)]
+    pub desktop: bool,
+}
+
+const DEFAULT_NOTIFY_MIN_DURATION: Duration = Duration::from_secs(3);
+
 #[derive(Debug, Clone)]
 pub enum Modal {
     EnvVar {
         env_var_name: String,
         field_reference: String,
+        /// Set when editing a managed var rather than mapping a new field.
+        editing: Option<VarEdit>,
+        /// One of `var_prefixes`, chosen with Ctrl+P.
+        prefix: Option<String>,
+    },
+    /// The name entered in `EnvVar` already maps something else; offers to
+    /// overwrite that mapping or go back and rename.
+    VarCollision {
+        env_var_name: String,
+        account_id: String,
+        op_reference: String,
+        prefix: Option<String>,
+        existing: InjectVarConfig,
+        return_to: Box<Modal>,
     },
     VarDeleteConfirm {
         vars: Vec<String>,
     },
+    VaultCreate {
+        name: String,
+        description: String,
+        active_field: VaultCreateField,
+    },
+    VaultDetails {
+        details: VaultDetails,
+    },
+    ItemDeleteConfirm {
+        item_id: String,
+        item_title: String,
+        archive: bool,
+        confirmed_once: bool,
+        affected_vars: Vec<String>,
+    },
+    ErrorDetails {
+        details: ErrorDetails,
+        scroll: u16,
+    },
+    Help {
+        scroll: u16,
+    },
+    /// Every command log entry with failures' full stderr, newest first.
+    CommandLog {
+        scroll: u16,
+    },
+    BulkMap {
+        field: String,
+        rows: Vec<BulkMapRow>,
+        focus: BulkMapFocus,
+    },
+    FieldEdit {
+        item_id: String,
+        item_title: String,
+        field_label: String,
+        /// `[section.]field` as `op item edit` assignments expect it.
+        assignment_target: String,
+        value: String,
+        /// Enter was pressed once; the next `y` saves.
+        confirming: bool,
+    },
+    /// Opened from the item creation form or field editor; the generated
+    /// password is inserted into whichever opened it.
+    PasswordGenerator {
+        recipe: Recipe,
+        password: String,
+        return_to: Box<Modal>,
+    },
+    /// What `env inject` would print, with values masked until `revealed`.
+    EnvPreview {
+        vars: Vec<(String, String)>,
+        /// Accounts whose vars couldn't be resolved.
+        warnings: Vec<String>,
+        revealed: bool,
+        scroll: u16,
+    },
+    /// Where to place the selected document item's file.
+    PlaceFile {
+        file: ManagedFile,
+        path: String,
+    },
+    /// `q` was pressed while something would be lost by quitting.
+    QuitConfirm {
+        reasons: Vec<String>,
+    },
+    /// `op` said the session expired; Enter runs `op signin`.
+    SignIn {
+        account_id: Option<String>,
+        retry: SigninRetry,
+        return_to: Option<Box<Modal>>,
+    },
+    ItemCreate {
+        title: String,
+        /// Index into `ITEM_CREATE_CATEGORIES`.
+        category_idx: usize,
+        /// `label=value` rows; the last one is always empty so there's
+        /// somewhere to type the next field.
+        fields: Vec<String>,
+        focus: ItemCreateFocus,
+    },
+}
+
+/// The env preview's lines: a `# Warning:` comment per failed account, as
+/// `env inject` prints them, then an `export` per var.
+pub fn env_preview_lines(
+    vars: &[(String, String)],
+    warnings: &[String],
+    revealed: bool,
+) -> Vec<String> {
+    warnings
+        .iter()
+        .map(|warning| format!("# Warning: {warning}"))
+        .chain(vars.iter().map(|(name, value)| {
+            let value = if revealed { value.as_str() } else { "********" };
+            crate::shell::Shell::Posix
+                .export(name, value)
+                .trim_end()
+                .to_string()
+        }))
+        .collect()
+}
+
+/// What to run again once `op signin` succeeds.
+#[derive(Debug, Clone)]
+pub enum SigninRetry {
+    Listing {
+        request: ListingRequest,
+        /// Whether the listing had never loaded, so it loads as on startup.
+        first_load: bool,
+    },
+    ItemDetails(String),
+    Action(Box<Action>),
+    /// Set when a synchronous `op` call fails; the action that made it
+    /// replaces this once it returns.
+    Pending,
+}
+
+/// Which input of the item creation form is being edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemCreateFocus {
+    Title,
+    Category,
+    Field(usize),
+}
+
+/// Categories offered when creating an item: display name, `op` category
+/// id, and the fields the form starts with.
+pub const ITEM_CREATE_CATEGORIES: &[(&str, &str, &[&str])] = &[
+    ("API Credential", "API_CREDENTIAL", &["credential"]),
+    ("Password", "PASSWORD", &["password"]),
+    ("Login", "LOGIN", &["username", "password"]),
+    ("Secure Note", "SECURE_NOTE", &["notesPlain"]),
+];
+
+/// The managed var the env var modal is editing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarEdit {
+    pub original_name: String,
+    pub account_id: String,
+    pub active_field: EnvVarField,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvVarField {
+    Name,
+    Reference,
+}
+
+/// A marked item and the variable its field will be saved as.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkMapRow {
+    pub item_id: String,
+    pub item_title: String,
+    pub env_var_name: String,
+}
+
+/// Which input of the bulk mapping modal is being edited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkMapFocus {
+    Field,
+    Row(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VaultCreateField {
+    Name,
+    Description,
 }
 
 pub struct App {
@@ -49,6 +490,7 @@ pub struct App {
     pub focused_panel: FocusedPanel,
     pub error_message: Option<String>,
     pub command_log: CommandLog,
+    pub command_log_size: CommandLogSize,
 
     pub accounts: Vec<Account>,
     pub account_list_state: ListState,
@@ -65,6 +507,12 @@ pub struct App {
 
     pub managed_vars: Vec<String>,
     pub managed_vars_selected: HashSet<String>,
+    /// The vars removed by the last confirmed delete, for `u` to put back.
+    pub deleted_vars: Vec<(String, InjectVarConfig)>,
+    /// Managed vars with a `{{VAR}}` placeholder in a managed template.
+    pub managed_vars_in_templates: HashSet<String>,
+    /// Items marked with Space in the Items panel for bulk mapping.
+    pub items_marked: HashSet<String>,
     pub managed_vars_list_state: ListState,
 
     pub item_detail_list_state: ListState,
@@ -72,13 +520,125 @@ pub struct App {
 
     pub search_query: String,
     pub search_active: bool,
+    /// Set by a lone `g`, so a second one jumps to the top of the list.
+    pub pending_g: bool,
+    /// The row number typed after `:`, jumped to on Enter.
+    pub jump_input: Option<String>,
+    /// Enter was pressed in the sign-in prompt; the main loop hands the
+    /// terminal to `op signin`.
+    pub signin_requested: bool,
     pub filtered_item_indices: Vec<usize>,
 
     pub modal: Option<Modal>,
+
+    pub action_history: Vec<Action>,
+
+    pub bell_pending: bool,
+    pub flash_pending: bool,
+
+    /// Set while the terminal is unfocused, so field values render masked.
+    pub secrets_hidden: bool,
+
+    pub clipboard_clear: Option<PendingClipboardClear>,
+
+    pub revealed_field: Option<RevealedField>,
+
+    pub totp: Option<TotpCode>,
+
+    /// Whether the Details panel shows the whole of the item's notes.
+    pub notes_expanded: bool,
+
+    /// Field labels of every item whose details were loaded this session, so
+    /// search can find items by what they hold.
+    pub item_field_labels: HashMap<String, Vec<String>>,
+
+    /// Accounts whose `op` calls keep failing are left alone for a while.
+    pub account_backoff: FailureBackoff,
+
+    /// Items opened or mapped from, most recent first.
+    pub recent_items: RecentItems,
+    pub recent_list_state: ListState,
+    /// Where the recent items are persisted; unset until
+    /// [`App::load_recent_items`], so tests never touch the real cache.
+    recent_items_dir: Option<PathBuf>,
+    /// A recent item being opened while its vault and item listings load.
+    pub pending_recent_jump: Option<RecentItem>,
+
+    /// Listings being fetched with nothing cached to show in the meantime.
+    loading_listings: Vec<ListingRequest>,
+    /// The item whose details are being fetched.
+    pub item_details_loading: Option<String>,
+    /// How many items are being fetched to index their field labels.
+    pub field_index_loading: Option<usize>,
+
+    /// Every `op` call goes through this, so tests can stand in for `op`.
+    op: SharedOpClient,
+    /// From `op --version` at startup; `None` until probed or if it failed.
+    pub op_version: Option<OpVersion>,
+    listing_tx: Sender<ListingRefresh>,
+    listing_rx: Receiver<ListingRefresh>,
+    details_tx: Sender<ItemDetailsLoad>,
+    details_rx: Receiver<ItemDetailsLoad>,
+    totp_tx: Sender<TotpLoad>,
+    totp_rx: Receiver<TotpLoad>,
+    /// `op whoami` for each account it has been run for, by account UUID.
+    pub whoami: HashMap<String, WhoAmI>,
+    whoami_tx: Sender<WhoAmILoad>,
+    whoami_rx: Receiver<WhoAmILoad>,
+    field_index_tx: Sender<FieldIndexLoad>,
+    field_index_rx: Receiver<FieldIndexLoad>,
+}
+
+/// The outcome of a batch of `op item get` runs for field search.
+#[derive(Debug)]
+struct FieldIndexLoad {
+    account_id: String,
+    gets: Vec<crate::item_batch::ItemGet>,
+}
+
+/// How a field index batch appears in the command log.
+fn field_index_command(items: usize) -> String {
+    format!("op item get ({items} items, for field search)")
+}
+
+/// The outcome of an `op whoami` run on a worker thread.
+#[derive(Debug)]
+struct WhoAmILoad {
+    account_id: String,
+    result: Result<Vec<u8>, String>,
+}
+
+/// The outcome of an `op item get --otp` run on a worker thread.
+#[derive(Debug)]
+struct TotpLoad {
+    account_id: String,
+    item_id: String,
+    result: Result<Vec<u8>, String>,
+}
+
+/// The outcome of an `op item get` run on a worker thread.
+#[derive(Debug)]
+struct ItemDetailsLoad {
+    account_id: String,
+    item_id: String,
+    result: Result<Vec<u8>, String>,
+    elapsed: Duration,
 }
 
 impl App {
+    /// An app that runs `op` through the given client, for tests.
+    #[cfg(test)]
+    pub fn with_op_client(op: SharedOpClient) -> Self {
+        Self { op, ..Self::new() }
+    }
+
     pub fn new() -> Self {
+        let (listing_tx, listing_rx) = channel();
+        let (details_tx, details_rx) = channel();
+        let (totp_tx, totp_rx) = channel();
+        let (whoami_tx, whoami_rx) = channel();
+        let (field_index_tx, field_index_rx) = channel();
+
         Self {
             config: None,
 
@@ -86,6 +646,7 @@ impl App {
             focused_panel: FocusedPanel::VaultList,
             error_message: None,
             command_log: CommandLog::default(),
+            command_log_size: CommandLogSize::default(),
 
             vaults: Vec::new(),
             vault_list_state: ListState::default(),
@@ -102,6 +663,9 @@ impl App {
 
             managed_vars: Vec::new(),
             managed_vars_selected: HashSet::new(),
+            deleted_vars: Vec::new(),
+            managed_vars_in_templates: HashSet::new(),
+            items_marked: HashSet::new(),
             managed_vars_list_state: ListState::default(),
 
             item_detail_list_state: ListState::default(),
@@ -109,9 +673,53 @@ impl App {
 
             search_query: String::new(),
             search_active: false,
+            pending_g: false,
+            jump_input: None,
+            signin_requested: false,
             filtered_item_indices: Vec::new(),
 
             modal: None,
+
+            action_history: Vec::new(),
+
+            bell_pending: false,
+            flash_pending: false,
+
+            secrets_hidden: false,
+
+            clipboard_clear: None,
+
+            revealed_field: None,
+
+            totp: None,
+
+            notes_expanded: false,
+            item_field_labels: HashMap::new(),
+
+            account_backoff: FailureBackoff::default(),
+
+            recent_items: RecentItems::default(),
+            recent_list_state: ListState::default(),
+            recent_items_dir: None,
+            pending_recent_jump: None,
+
+            loading_listings: Vec::new(),
+            item_details_loading: None,
+            field_index_loading: None,
+
+            op: std::sync::Arc::new(SubprocessOpClient),
+            op_version: None,
+            listing_tx,
+            listing_rx,
+            details_tx,
+            details_rx,
+            totp_tx,
+            totp_rx,
+            whoami: HashMap::new(),
+            whoami_tx,
+            whoami_rx,
+            field_index_tx,
+            field_index_rx,
         }
     }
 
@@ -128,23 +736,183 @@ impl App {
         Ok(())
     }
 
+    /// Loads the recent items history from the cache directory, which is
+    /// also where later changes are saved.
+    pub fn load_recent_items(&mut self) {
+        let Ok(dir) = crate::cache::cache_dir() else {
+            return;
+        };
+        self.recent_items = crate::recent_items::load_recent_items(&dir);
+        self.recent_items_dir = Some(dir);
+        if !self.recent_items.items.is_empty() {
+            self.recent_list_state.select(Some(0));
+        }
+    }
+
+    fn recent_items_limit(&self) -> usize {
+        self.config
+            .as_ref()
+            .map_or(crate::recent_items::DEFAULT_LIMIT, |config| {
+                config.tui.recent_items()
+            })
+    }
+
+    /// Moves an item of the selected vault to the top of the recent items.
+    pub fn record_recent_item(&mut self, item_id: &str, title: &str) {
+        let limit = self.recent_items_limit();
+        let (Some(account), Some(vault)) = (self.selected_account(), self.selected_vault()) else {
+            return;
+        };
+        if limit == 0 {
+            return;
+        }
+
+        let used_at = SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        self.recent_items.record(
+            RecentItem {
+                account_id: account.account_uuid.clone(),
+                vault_id: vault.id.clone(),
+                vault_name: vault.name.clone(),
+                item_id: item_id.to_string(),
+                title: title.to_string(),
+                used_at,
+            },
+            limit,
+        );
+        self.recent_list_state.select(Some(0));
+        self.save_recent_items();
+    }
+
+    /// Drops a deleted item from the recent items.
+    pub fn forget_recent_item(&mut self, account_id: &str, item_id: &str) {
+        self.recent_items.forget(account_id, item_id);
+        if self.recent_items.items.is_empty() {
+            self.recent_list_state.select(None);
+        } else if self
+            .recent_list_state
+            .selected()
+            .is_some_and(|idx| idx >= self.recent_items.items.len())
+        {
+            self.recent_list_state
+                .select(Some(self.recent_items.items.len() - 1));
+        }
+        self.save_recent_items();
+    }
+
+    fn save_recent_items(&mut self) {
+        let Some(dir) = &self.recent_items_dir else {
+            return;
+        };
+        if let Err(err) = crate::recent_items::save_recent_items(dir, &self.recent_items) {
+            self.command_log
+                .log_failure("Recent items save", err.to_string());
+        }
+    }
+
+    /// The vault step of [`App::open_recent_item`].
+    pub fn select_recent_vault(&mut self) -> Result<()> {
+        let Some(recent) = self.pending_recent_jump.clone() else {
+            return Ok(());
+        };
+        let Some(vault_idx) = self.vaults.iter().position(|v| v.id == recent.vault_id) else {
+            self.pending_recent_jump = None;
+            bail!("Vault {} no longer exists", recent.vault_name);
+        };
+
+        if self.selected_vault_idx == Some(vault_idx) && !self.vault_items.is_empty() {
+            return self.select_recent_vault_item();
+        }
+        self.selected_vault_idx = Some(vault_idx);
+        self.vault_list_state.select(Some(vault_idx));
+        self.items_marked.clear();
+        self.clear_search();
+        self.load_vault_items()
+    }
+
+    /// The item step of [`App::open_recent_item`], once the vault's items
+    /// are listed.
+    fn select_recent_vault_item(&mut self) -> Result<()> {
+        if self.pending_recent_jump.as_ref().map(|r| &r.vault_id)
+            != self.selected_vault().map(|v| &v.id)
+        {
+            return Ok(());
+        }
+        let Some(recent) = self.pending_recent_jump.take() else {
+            return Ok(());
+        };
+
+        self.clear_search();
+        let Some(list_idx) = self
+            .filtered_item_indices
+            .iter()
+            .position(|&real_idx| self.vault_items[real_idx].id == recent.item_id)
+        else {
+            bail!("{} is no longer in {}", recent.title, recent.vault_name);
+        };
+
+        self.selected_vault_item_idx = Some(list_idx);
+        self.vault_item_list_state.select(Some(list_idx));
+        self.focused_panel = FocusedPanel::VaultItemList;
+        self.load_item_details(&recent.item_id)
+    }
+
     pub fn save_op_item_config(
         &mut self,
         var_name: &str,
         account_id: &str,
         op_reference: &str,
+        prefix: Option<&str>,
+    ) -> Result<()> {
+        self.store_var_configs(
+            account_id,
+            &[(var_name.to_string(), op_reference.to_string())],
+            prefix,
+        )
+    }
+
+    /// Saves `(var_name, op_reference)` mappings for one account with a
+    /// single config write. Nothing is saved if any mapping breaks policy.
+    pub fn save_op_item_configs(
+        &mut self,
+        account_id: &str,
+        vars: &[(String, String)],
+    ) -> Result<()> {
+        self.store_var_configs(account_id, vars, None)
+    }
+
+    fn store_var_configs(
+        &mut self,
+        account_id: &str,
+        vars: &[(String, String)],
+        prefix: Option<&str>,
     ) -> Result<()> {
+        let var_config = |op_reference: &String| InjectVarConfig {
+            account_id: account_id.to_string(),
+            op_reference: op_reference.clone(),
+            prefix: prefix.map(str::to_string),
+        };
+
+        if let Some(policy) = load_policy()? {
+            for (var_name, op_reference) in vars {
+                let mapping = var_config(op_reference);
+                if let Some(violation) = policy.check_mapping(var_name, &mapping).first() {
+                    bail!("Blocked by policy: {}", violation.message);
+                }
+            }
+        }
+
         if let Some(config) = &mut self.config {
-            config.inject_vars.insert(
-                var_name.to_string(),
-                InjectVarConfig {
-                    account_id: account_id.to_string(),
-                    op_reference: op_reference.to_string(),
-                },
-            );
+            for (var_name, op_reference) in vars {
+                config
+                    .inject_vars
+                    .insert(var_name.clone(), var_config(op_reference));
+            }
             confy::store("op_loader", None, &*config).context("Failed to save configuration")?;
 
-            match remove_cache_for_account(account_id) {
+            match remove_cache_for_account(account_id, false) {
                 Ok(CacheRemoval::Removed) => {
                     self.command_log
                         .log_success(format!("cache clear {account_id}"), None);
@@ -165,6 +933,50 @@ impl App {
         Ok(())
     }
 
+    /// Renames and/or re-points a managed var, keeping its account. The old
+    /// name is only dropped if the new mapping saves.
+    pub fn update_managed_var(
+        &mut self,
+        original_name: &str,
+        var_name: &str,
+        account_id: &str,
+        op_reference: &str,
+        prefix: Option<&str>,
+    ) -> Result<()> {
+        let config = self
+            .config
+            .as_mut()
+            .context("Configuration can't be saved because it is not loaded")?;
+        if var_name != original_name && config.inject_vars.contains_key(var_name) {
+            bail!("{var_name} is already mapped");
+        }
+        if !op_reference.starts_with("op://") {
+            bail!("Reference must start with op://");
+        }
+
+        let previous = if var_name == original_name {
+            None
+        } else {
+            config.inject_vars.remove(original_name)
+        };
+        if let Err(err) = self.save_op_item_config(var_name, account_id, op_reference, prefix) {
+            if let (Some(previous), Some(config)) = (previous, self.config.as_mut()) {
+                config
+                    .inject_vars
+                    .insert(original_name.to_string(), previous);
+            }
+            return Err(err);
+        }
+
+        if self.managed_vars_selected.remove(original_name) {
+            self.managed_vars_selected.insert(var_name.to_string());
+        }
+        self.load_managed_vars();
+        let idx = self.managed_vars.iter().position(|var| var == var_name);
+        self.managed_vars_list_state.select(idx);
+        Ok(())
+    }
+
     pub fn set_default_vault(&mut self, account_id: &str, vault_id: &str) -> Result<()> {
         if let Some(config) = &mut self.config {
             config
@@ -190,47 +1002,73 @@ impl App {
     }
 
     fn run_op_command(&mut self, args: &[&str]) -> Result<Vec<u8>> {
-        let cmd_str = format!("op {}", args.join(" "));
+        self.run_op_command_with_input(args, None, None)
+    }
 
-        let output = Command::new("op")
-            .args(args)
-            .output()
+    /// Runs `op`, optionally feeding `input` on stdin. `shown_as` replaces
+    /// the command in the log and errors when the arguments hold a secret.
+    fn run_op_command_with_input(
+        &mut self,
+        args: &[&str],
+        input: Option<&[u8]>,
+        shown_as: Option<&str>,
+    ) -> Result<Vec<u8>> {
+        let cmd_str = shown_as.map_or_else(|| format!("op {}", args.join(" ")), String::from);
+
+        let started = std::time::Instant::now();
+        let output = self
+            .op
+            .run(args, input)
             .context("Failed to execute op command")?;
+        self.notify_if_slow(started.elapsed());
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        if !output.success {
+            let stderr = annotate(&output.stderr);
             self.command_log.log_failure(&cmd_str, &stderr);
+            if crate::signin::is_session_error(&stderr) {
+                // `App::apply` fills in the action to retry.
+                self.prompt_signin(crate::signin::account_from_args(args), SigninRetry::Pending);
+            }
             bail!("`{cmd_str}` failed: {stderr}");
         }
 
         Ok(output.stdout)
     }
 
-    pub fn load_vaults(&mut self) -> Result<()> {
-        let account_uuid = self.selected_account().map(|a| a.account_uuid.clone());
-
-        let stdout = if let Some(ref uuid) = account_uuid {
-            self.run_op_command(&["vault", "list", "--account", uuid, "--format", "json"])?
-        } else {
-            self.run_op_command(&["vault", "list", "--format", "json"])?
+    fn notify_if_slow(&mut self, elapsed: Duration) {
+        let Some(notifications) = self.config.as_ref().map(|c| &c.notifications) else {
+            return;
         };
+        if !notifications.bell && !notifications.flash {
+            return;
+        }
 
-        let vaults: Vec<Vault> =
-            serde_json::from_slice(&stdout).context("Failed to parse vault list JSON")?;
-
-        self.command_log
-            .log_success("op vault list", Some(vaults.len()));
+        let min_duration = notifications
+            .min_duration
+            .as_deref()
+            .and_then(|d| crate::cli::parse_duration(d).ok().flatten())
+            .unwrap_or(DEFAULT_NOTIFY_MIN_DURATION);
+        if elapsed < min_duration {
+            return;
+        }
 
-        self.vaults = vaults;
-        self.selected_vault_idx = None;
+        self.bell_pending |= notifications.bell;
+        self.flash_pending |= notifications.flash;
+    }
 
-        if self.vaults.is_empty() {
-            self.vault_list_state.select(None);
-        } else {
-            self.vault_list_state.select(Some(0));
+    pub fn load_vaults(&mut self) -> Result<()> {
+        let account_uuid = self.selected_account().map(|a| a.account_uuid.clone());
+        if let Some(account_id) = &account_uuid {
+            self.load_whoami(account_id);
         }
 
-        Ok(())
+        self.vaults.clear();
+        self.selected_vault_idx = None;
+        self.vault_list_state.select(None);
+
+        self.load_listing(ListingRequest::Vaults {
+            account_id: account_uuid,
+        })
     }
 
     pub fn selected_vault(&self) -> Option<&Vault> {
@@ -242,56 +1080,532 @@ impl App {
             .and_then(|idx| self.accounts.get(idx))
     }
 
+    /// Loads accounts, then selects the default account and loads its vaults.
     pub fn load_accounts(&mut self) -> Result<()> {
-        let stdout = self.run_op_command(&["account", "list", "--format", "json"])?;
-
-        let accounts: Vec<Account> =
-            serde_json::from_slice(&stdout).context("Failed to parse account list JSON")?;
-
-        self.command_log
-            .log_success("op account list", Some(accounts.len()));
+        self.load_listing(ListingRequest::Accounts)
+    }
 
-        self.accounts = accounts;
+    pub fn load_vault_items(&mut self) -> Result<()> {
+        if self.selected_account_idx.is_none() || self.selected_vault_idx.is_none() {
+            bail!("Cannot list vault items when account/vault are not selected");
+        }
+
+        let account_id = self.selected_account().unwrap().account_uuid.clone();
+        let vault_id = self.selected_vault().unwrap().id.clone();
+
+        self.vault_items.clear();
+        self.update_filtered_items();
+
+        self.load_listing(ListingRequest::Items {
+            account_id,
+            vault_id,
+        })
+    }
+
+    pub const fn loading_listing_count(&self) -> usize {
+        self.loading_listings.len()
+    }
+
+    pub fn accounts_loading(&self) -> bool {
+        self.loading_listings.contains(&ListingRequest::Accounts)
+    }
+
+    pub fn vaults_loading(&self) -> bool {
+        self.loading_listings.contains(&ListingRequest::Vaults {
+            account_id: self.selected_account().map(|a| a.account_uuid.clone()),
+        })
+    }
+
+    pub fn vault_items_loading(&self) -> bool {
+        let (Some(account), Some(vault)) = (self.selected_account(), self.selected_vault()) else {
+            return false;
+        };
+        self.loading_listings.contains(&ListingRequest::Items {
+            account_id: account.account_uuid.clone(),
+            vault_id: vault.id.clone(),
+        })
+    }
+
+    /// The selected account, vault, and item, outermost first.
+    pub fn breadcrumb(&self) -> Vec<String> {
+        let item_title = self
+            .selected_item_details
+            .as_ref()
+            .map(|details| details.title.clone())
+            .or_else(|| {
+                self.selected_vault_item_idx
+                    .and_then(|idx| self.filtered_item_indices.get(idx))
+                    .and_then(|&real_idx| self.vault_items.get(real_idx))
+                    .map(|item| item.title.clone())
+            });
+
+        self.selected_account()
+            .map(|account| account.email.clone())
+            .into_iter()
+            .chain(self.selected_vault().map(|vault| vault.name.clone()))
+            .chain(item_title)
+            .collect()
+    }
+
+    /// Runs `op whoami` for `account_id` in the background, for the status
+    /// bar. Failures are only logged; the listings prompt to sign in.
+    fn load_whoami(&mut self, account_id: &str) {
+        if self.require_op(OpFeature::JsonOutput).is_err() {
+            return;
+        }
+        let args: Vec<String> = ["whoami", "--account", account_id, "--format", "json"]
+            .map(String::from)
+            .to_vec();
+        let account_id = account_id.to_string();
+        let tx = self.whoami_tx.clone();
+        let op = self.op.clone();
+        std::thread::spawn(move || {
+            let (result, _) = run_op(op.as_ref(), &args);
+            let _ = tx.send(WhoAmILoad { account_id, result });
+        });
+    }
+
+    fn apply_whoami_loads(&mut self) {
+        while let Ok(load) = self.whoami_rx.try_recv() {
+            let command = format!("op whoami --account {}", load.account_id);
+            let parsed = load.result.and_then(|stdout| {
+                serde_json::from_slice::<WhoAmI>(&stdout)
+                    .map_err(|err| format!("Failed to parse whoami JSON: {err}"))
+            });
+            match parsed {
+                Ok(whoami) => {
+                    self.command_log.log_success(&command, None);
+                    self.whoami.insert(load.account_id, whoami);
+                }
+                Err(stderr) => {
+                    self.command_log.log_failure(&command, annotate(&stderr));
+                    self.whoami.remove(&load.account_id);
+                }
+            }
+        }
+    }
+
+    /// `op whoami` for the selected account, once it has come back.
+    pub fn selected_whoami(&self) -> Option<&WhoAmI> {
+        self.selected_account()
+            .and_then(|account| self.whoami.get(&account.account_uuid))
+    }
+
+    /// Whether `op` works for the selected account, as far as the TUI knows.
+    pub fn signin_status(&self, now: SystemTime) -> SigninStatus {
+        if self.accounts_loading() {
+            return SigninStatus::Loading;
+        }
+        let Some(account) = self.selected_account() else {
+            return SigninStatus::SignedOut;
+        };
+        self.account_backoff
+            .remaining(&account.account_uuid, now)
+            .map_or(SigninStatus::SignedIn, SigninStatus::CoolingDown)
+    }
+
+    /// Re-runs the `op ... list` behind the focused panel, for changes made
+    /// outside the TUI. Selections are kept; a list that never loaded is
+    /// loaded as on startup. Like picking an account, this is an explicit
+    /// retry, so the account's cool-down ends.
+    pub fn refresh_focused_listing(&mut self) -> Result<()> {
+        let (request, loaded) = match self.focused_panel {
+            FocusedPanel::AccountList => (ListingRequest::Accounts, !self.accounts.is_empty()),
+            FocusedPanel::VaultList => (
+                ListingRequest::Vaults {
+                    account_id: self.selected_account().map(|a| a.account_uuid.clone()),
+                },
+                !self.vaults.is_empty(),
+            ),
+            FocusedPanel::VaultItemList | FocusedPanel::VaultItemDetail => {
+                let (Some(account), Some(vault)) = (self.selected_account(), self.selected_vault())
+                else {
+                    bail!("Select a vault to refresh its items");
+                };
+                (
+                    ListingRequest::Items {
+                        account_id: account.account_uuid.clone(),
+                        vault_id: vault.id.clone(),
+                    },
+                    !self.vault_items.is_empty(),
+                )
+            }
+            FocusedPanel::VarsList | FocusedPanel::RecentList => return Ok(()),
+        };
 
-        if !self.accounts.is_empty() {
-            self.account_list_state.select(Some(0));
+        if let Some(account_id) = request.account_id() {
+            self.account_backoff.reset(account_id);
+        }
+        if self.loading_listings.contains(&request) {
+            return Ok(());
         }
+        if !loaded {
+            self.loading_listings.push(request.clone());
+        }
+        spawn_refresh(self.op.clone(), request, self.listing_tx.clone());
 
+        if self.focused_panel == FocusedPanel::VaultItemDetail
+            && let Some(item_id) = self.selected_item_details.as_ref().map(|d| d.id.clone())
+        {
+            self.load_item_details(&item_id)?;
+        }
         Ok(())
     }
 
-    pub fn load_vault_items(&mut self) -> Result<()> {
-        if self.selected_account_idx.is_none() || self.selected_vault_idx.is_none() {
-            bail!("Cannot list vault items when account/vault are not selected");
+    /// Serves a fresh cached listing and refreshes it in the background, or
+    /// fetches it on a worker thread when nothing usable is cached. Fetched
+    /// listings are applied by [`App::apply_background_results`].
+    /// Runs `op --version` so features the CLI is too old for fail with a
+    /// clear message rather than `op`'s own usage errors.
+    pub fn detect_op_version(&mut self) {
+        self.op_version = self.op.version();
+        match self.op_version {
+            Some(version) => self
+                .command_log
+                .log_success(format!("op --version ({version})"), None),
+            None => self.command_log.log_failure(
+                "op --version",
+                "Couldn't determine the 1Password CLI version",
+            ),
         }
+    }
 
-        let account_id = self.selected_account().unwrap().account_uuid.clone();
-        let vault_id = self.selected_vault().unwrap().id.clone();
+    fn require_op(&self, feature: OpFeature) -> Result<()> {
+        crate::op_version::require(self.op_version, feature)
+    }
 
-        let stdout = self.run_op_command(&[
-            "item",
-            "list",
-            "--account",
-            &account_id,
-            "--vault",
-            &vault_id,
-            "--format",
-            "json",
-        ])?;
+    fn load_listing(&mut self, request: ListingRequest) -> Result<()> {
+        self.require_op(OpFeature::JsonOutput)?;
+        let cooling_down = request
+            .account_id()
+            .is_some_and(|account_id| self.cooling_down(account_id, &request.command()));
+
+        if let Some(cached) = request.cached() {
+            if !cooling_down {
+                spawn_refresh(self.op.clone(), request.clone(), self.listing_tx.clone());
+            }
+            return self.finish_listing_load(&request, &cached);
+        }
 
-        let vault_items: Vec<VaultItem> =
-            serde_json::from_slice(&stdout).context("Failed to parse vault items JSON")?;
+        if cooling_down {
+            return Ok(());
+        }
 
-        self.command_log.log_success(
-            format!("op item list --vault {vault_id}"),
-            Some(vault_items.len()),
+        if !self.loading_listings.contains(&request) {
+            self.loading_listings.push(request.clone());
+            spawn_refresh(self.op.clone(), request, self.listing_tx.clone());
+        }
+        Ok(())
+    }
+
+    /// Shows a freshly loaded listing and loads whatever depends on it.
+    /// Listings for an account or vault that is no longer selected are
+    /// dropped.
+    fn finish_listing_load(&mut self, request: &ListingRequest, stdout: &[u8]) -> Result<()> {
+        match request {
+            ListingRequest::Accounts => {
+                let accounts: Vec<Account> =
+                    serde_json::from_slice(stdout).context("Failed to parse account list JSON")?;
+
+                self.command_log
+                    .log_success("op account list", Some(accounts.len()));
+
+                self.accounts = accounts;
+
+                if !self.accounts.is_empty() {
+                    self.account_list_state.select(Some(0));
+                }
+
+                if self.selected_account_idx.is_none() {
+                    self.select_default_account();
+                    self.load_vaults()?;
+                }
+            }
+            ListingRequest::Vaults { account_id } => {
+                if self.selected_account().map(|a| &a.account_uuid) != account_id.as_ref() {
+                    return Ok(());
+                }
+
+                let vaults: Vec<Vault> =
+                    serde_json::from_slice(stdout).context("Failed to parse vault list JSON")?;
+
+                self.command_log
+                    .log_success("op vault list", Some(vaults.len()));
+
+                self.vaults = vaults;
+                self.selected_vault_idx = None;
+
+                if self.vaults.is_empty() {
+                    self.vault_list_state.select(None);
+                } else {
+                    self.vault_list_state.select(Some(0));
+                }
+
+                if self
+                    .pending_recent_jump
+                    .as_ref()
+                    .is_some_and(|recent| Some(&recent.account_id) == account_id.as_ref())
+                {
+                    self.select_recent_vault()?;
+                } else if self.select_default_vault() {
+                    self.load_vault_items()?;
+                }
+            }
+            ListingRequest::Items {
+                account_id,
+                vault_id,
+            } => {
+                if self.selected_account().map(|a| &a.account_uuid) != Some(account_id)
+                    || self.selected_vault().map(|v| &v.id) != Some(vault_id)
+                {
+                    return Ok(());
+                }
+
+                let vault_items: Vec<VaultItem> =
+                    serde_json::from_slice(stdout).context("Failed to parse vault items JSON")?;
+
+                self.command_log.log_success(
+                    format!("op item list --vault {vault_id}"),
+                    Some(vault_items.len()),
+                );
+
+                self.vault_items = vault_items;
+                self.update_filtered_items();
+
+                if !self.filtered_item_indices.is_empty() {
+                    self.vault_item_list_state.select(Some(0));
+                }
+                self.select_recent_vault_item()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Selects the configured default account, falling back to the first.
+    fn select_default_account(&mut self) {
+        let idx = self
+            .config
+            .as_ref()
+            .and_then(|c| c.default_account_id.as_ref())
+            .and_then(|account_id| {
+                self.accounts
+                    .iter()
+                    .position(|a| &a.account_uuid == account_id)
+            })
+            .or_else(|| (!self.accounts.is_empty()).then_some(0));
+
+        self.selected_account_idx = idx;
+        if idx.is_some() {
+            self.account_list_state.select(idx);
+        }
+    }
+
+    /// Selects the selected account's default vault, if one is configured and
+    /// still exists.
+    fn select_default_vault(&mut self) -> bool {
+        let Some(vault_idx) = self
+            .selected_account()
+            .and_then(|account| {
+                self.config
+                    .as_ref()
+                    .and_then(|c| c.default_vault_per_account.get(&account.account_uuid))
+            })
+            .and_then(|vault_id| self.vaults.iter().position(|v| &v.id == vault_id))
+        else {
+            return false;
+        };
+
+        self.selected_vault_idx = Some(vault_idx);
+        self.vault_list_state.select(Some(vault_idx));
+        true
+    }
+
+    /// Whether `account_id` is in a cool-down after failed `op` calls. If it
+    /// is, the skipped `command` is logged and the wait is shown as an error.
+    fn cooling_down(&mut self, account_id: &str, command: &str) -> bool {
+        let Some(remaining) = self
+            .account_backoff
+            .remaining(account_id, SystemTime::now())
+        else {
+            return false;
+        };
+
+        let message = format!(
+            "Skipped after {} failed op call(s) for this account; retrying in {}s. Press Enter on the account to retry now.",
+            self.account_backoff.failure_count(account_id),
+            remaining.as_secs().max(1)
         );
+        self.command_log.log_failure(command, &message);
+        self.error_message = Some(message);
+        true
+    }
 
-        self.vault_items = vault_items;
-        self.update_filtered_items();
+    /// Starts or extends an account's cool-down after a failed call, or ends
+    /// it after a successful one. A missing vault or item leaves the account
+    /// alone, since other calls for it still work.
+    fn record_op_result<T>(&mut self, account_id: Option<&str>, result: &Result<T, String>) {
+        let Some(account_id) = account_id else {
+            return;
+        };
+        match result {
+            Ok(_) => self.account_backoff.reset(account_id),
+            Err(stderr) if classify(stderr).pauses_account() => {
+                self.account_backoff
+                    .record_failure(account_id, SystemTime::now());
+            }
+            Err(_) => {}
+        }
+    }
 
-        if !self.filtered_item_indices.is_empty() {
-            self.vault_item_list_state.select(Some(0));
+    /// Applies everything the worker threads finished since the last frame.
+    pub fn apply_background_results(&mut self) {
+        self.apply_listing_refreshes();
+        self.apply_item_details_loads();
+        self.apply_totp_loads();
+        self.apply_whoami_loads();
+        self.apply_field_index_loads();
+    }
+
+    /// Applies finished background refreshes, keeping the current selections
+    /// where the selected entries still exist.
+    fn apply_listing_refreshes(&mut self) {
+        while let Ok(refresh) = self.listing_rx.try_recv() {
+            let command = refresh.request.command();
+            self.record_op_result(refresh.request.account_id(), &refresh.result);
+
+            let loading = self
+                .loading_listings
+                .iter()
+                .position(|request| request == &refresh.request);
+            if let Err(stderr) = &refresh.result
+                && crate::signin::is_session_error(stderr)
+            {
+                self.prompt_signin(
+                    refresh.request.account_id().map(String::from),
+                    SigninRetry::Listing {
+                        request: refresh.request.clone(),
+                        first_load: loading.is_some(),
+                    },
+                );
+            }
+
+            if let Some(pos) = loading {
+                self.loading_listings.remove(pos);
+                self.notify_if_slow(refresh.elapsed);
+
+                let result = refresh
+                    .result
+                    .map_err(|stderr| {
+                        let stderr = annotate(&stderr);
+                        self.command_log.log_failure(&command, &stderr);
+                        anyhow::anyhow!("`{command}` failed: {stderr}")
+                    })
+                    .and_then(|stdout| self.finish_listing_load(&refresh.request, &stdout));
+                if let Err(err) = result {
+                    self.error_message = Some(err.to_string());
+                }
+                continue;
+            }
+
+            let result = refresh
+                .result
+                .map_err(|stderr| anyhow::Error::msg(annotate(&stderr)))
+                .and_then(|stdout| self.apply_listing_refresh(&refresh.request, &stdout));
+            if let Err(err) = result {
+                self.command_log.log_failure(&command, err.to_string());
+            }
+        }
+    }
+
+    fn apply_listing_refresh(&mut self, request: &ListingRequest, stdout: &[u8]) -> Result<()> {
+        match request {
+            ListingRequest::Accounts => {
+                let accounts: Vec<Account> =
+                    serde_json::from_slice(stdout).context("Failed to parse account list JSON")?;
+                let selected = self.selected_account().map(|a| a.account_uuid.clone());
+                let highlighted = self
+                    .account_list_state
+                    .selected()
+                    .and_then(|idx| self.accounts.get(idx))
+                    .map(|a| a.account_uuid.clone());
+
+                self.command_log
+                    .log_success("op account list (refreshed)", Some(accounts.len()));
+                self.accounts = accounts;
+                self.selected_account_idx =
+                    selected.and_then(|id| self.accounts.iter().position(|a| a.account_uuid == id));
+                self.account_list_state.select(
+                    highlighted
+                        .and_then(|id| self.accounts.iter().position(|a| a.account_uuid == id))
+                        .or_else(|| (!self.accounts.is_empty()).then_some(0)),
+                );
+            }
+            ListingRequest::Vaults { account_id } => {
+                if self.selected_account().map(|a| &a.account_uuid) != account_id.as_ref() {
+                    return Ok(());
+                }
+
+                let vaults: Vec<Vault> =
+                    serde_json::from_slice(stdout).context("Failed to parse vault list JSON")?;
+                let selected = self.selected_vault().map(|v| v.id.clone());
+                let highlighted = self
+                    .vault_list_state
+                    .selected()
+                    .and_then(|idx| self.vaults.get(idx))
+                    .map(|v| v.id.clone());
+
+                self.command_log
+                    .log_success("op vault list (refreshed)", Some(vaults.len()));
+                self.vaults = vaults;
+                self.selected_vault_idx =
+                    selected.and_then(|id| self.vaults.iter().position(|v| v.id == id));
+                self.vault_list_state.select(
+                    highlighted
+                        .and_then(|id| self.vaults.iter().position(|v| v.id == id))
+                        .or_else(|| (!self.vaults.is_empty()).then_some(0)),
+                );
+            }
+            ListingRequest::Items {
+                account_id,
+                vault_id,
+            } => {
+                if self.selected_account().map(|a| &a.account_uuid) != Some(account_id)
+                    || self.selected_vault().map(|v| &v.id) != Some(vault_id)
+                {
+                    return Ok(());
+                }
+
+                let vault_items: Vec<VaultItem> =
+                    serde_json::from_slice(stdout).context("Failed to parse vault items JSON")?;
+                let item_id_at = |app: &Self, list_idx: Option<usize>| {
+                    list_idx
+                        .and_then(|idx| app.filtered_item_indices.get(idx))
+                        .and_then(|&real_idx| app.vault_items.get(real_idx))
+                        .map(|item| item.id.clone())
+                };
+                let selected = item_id_at(self, self.selected_vault_item_idx);
+                let highlighted = item_id_at(self, self.vault_item_list_state.selected());
+
+                self.command_log.log_success(
+                    format!("op item list --vault {vault_id} (refreshed)"),
+                    Some(vault_items.len()),
+                );
+                self.vault_items = vault_items;
+                self.update_filtered_items();
+
+                let list_idx_of = |app: &Self, id: Option<String>| {
+                    id.and_then(|id| {
+                        app.filtered_item_indices
+                            .iter()
+                            .position(|&real_idx| app.vault_items[real_idx].id == id)
+                    })
+                };
+                self.selected_vault_item_idx = list_idx_of(self, selected);
+                let highlighted = list_idx_of(self, highlighted)
+                    .or_else(|| (!self.filtered_item_indices.is_empty()).then_some(0));
+                self.vault_item_list_state.select(highlighted);
+            }
         }
 
         Ok(())
@@ -307,12 +1621,10 @@ impl App {
                 .iter()
                 .enumerate()
                 .filter_map(|(idx, item)| {
-                    matcher
-                        .fuzzy_match(&item.title, &self.search_query)
-                        .map(|score| (idx, score))
+                    self.search_score(&matcher, item).map(|score| (idx, score))
                 })
                 .collect();
-            scored.sort_by(|a, b| b.1.cmp(&a.1)); // highest score first
+            scored.sort_by_key(|b| std::cmp::Reverse(b.1)); // highest score first
             self.filtered_item_indices = scored.into_iter().map(|(idx, _)| idx).collect();
         }
 
@@ -323,6 +1635,41 @@ impl App {
         }
         self.selected_vault_item_idx = None;
         self.selected_item_details = None;
+        self.item_details_loading = None;
+        self.totp = None;
+    }
+
+    /// The best match against the item's title, category, additional
+    /// information, and any field labels seen so far. Matches outside the
+    /// title count for half, so title matches rank first.
+    fn search_score(&self, matcher: &SkimMatcherV2, item: &VaultItem) -> Option<i64> {
+        let title = matcher.fuzzy_match(&item.title, &self.search_query);
+        let labels = self
+            .item_field_labels
+            .get(&item.id)
+            .into_iter()
+            .flatten()
+            .map(String::as_str);
+        let other = [item.category.as_str()]
+            .into_iter()
+            .chain(item.additional_information.as_deref())
+            .chain(labels)
+            .filter_map(|text| matcher.fuzzy_match(text, &self.search_query))
+            .max()
+            .map(|score| score / 2);
+        title.max(other)
+    }
+
+    /// Which characters of an item title the search query matched, as char
+    /// indices, so the Items panel can show why a result ranks where it does.
+    pub fn title_match_positions(&self, title: &str) -> Vec<usize> {
+        if self.search_query.is_empty() {
+            return Vec::new();
+        }
+        SkimMatcherV2::default()
+            .fuzzy_indices(title, &self.search_query)
+            .map(|(_, positions)| positions)
+            .unwrap_or_default()
     }
 
     pub fn clear_search(&mut self) {
@@ -331,559 +1678,4239 @@ impl App {
         self.update_filtered_items();
     }
 
+    /// Fetches an item's details on a worker thread; they're shown once
+    /// [`App::apply_background_results`] picks them up.
     pub fn load_item_details(&mut self, item_id: &str) -> Result<()> {
-        let account_id = self.selected_account().unwrap().account_uuid.clone();
-        let vault_id = self.selected_vault().unwrap().id.clone();
+        let account_id = self
+            .selected_account()
+            .map(|a| a.account_uuid.clone())
+            .context("No account selected")?;
+        let vault_id = self
+            .selected_vault()
+            .map(|v| v.id.clone())
+            .context("No vault selected")?;
+
+        self.selected_item_details = None;
+        self.totp = None;
+        if self.cooling_down(&account_id, &format!("op item get {item_id}")) {
+            return Ok(());
+        }
+        self.item_details_loading = Some(item_id.to_string());
+
+        let args = crate::item_batch::item_get_args(item_id, &account_id, &vault_id);
+        let item_id = item_id.to_string();
+        let tx = self.details_tx.clone();
+        let op = self.op.clone();
+        std::thread::spawn(move || {
+            let (result, elapsed) = run_op(op.as_ref(), &args);
+            let _ = tx.send(ItemDetailsLoad {
+                account_id,
+                item_id,
+                result,
+                elapsed,
+            });
+        });
+
+        Ok(())
+    }
+
+    /// Shows fetched item details, unless another item was picked meanwhile.
+    fn apply_item_details_loads(&mut self) {
+        while let Ok(load) = self.details_rx.try_recv() {
+            self.record_op_result(Some(&load.account_id), &load.result);
+            if self.item_details_loading.as_deref() != Some(load.item_id.as_str()) {
+                continue;
+            }
+            self.item_details_loading = None;
+            self.notify_if_slow(load.elapsed);
+
+            let command = format!("op item get {}", load.item_id);
+            if let Err(stderr) = &load.result
+                && crate::signin::is_session_error(stderr)
+            {
+                self.prompt_signin(
+                    Some(load.account_id.clone()),
+                    SigninRetry::ItemDetails(load.item_id.clone()),
+                );
+            }
+            let result = load
+                .result
+                .map_err(|stderr| {
+                    let stderr = annotate(&stderr);
+                    self.command_log.log_failure(&command, &stderr);
+                    anyhow::anyhow!("`{command}` failed: {stderr}")
+                })
+                .and_then(|stdout| {
+                    serde_json::from_slice::<VaultItemDetails>(&stdout)
+                        .context("Failed to parse item details JSON")
+                });
+
+            match result {
+                Ok(details) => {
+                    self.command_log
+                        .log_success(&command, Some(details.fields.len()));
+                    self.totp = totp_for_details(&details, SystemTime::now());
+                    self.notes_expanded = false;
+                    self.record_recent_item(&details.id, &details.title);
+                    self.item_field_labels.insert(
+                        details.id.clone(),
+                        details
+                            .detail_fields()
+                            .iter()
+                            .map(|f| f.label.clone())
+                            .collect(),
+                    );
+                    self.selected_item_details = Some(details);
+                    self.item_detail_list_state.select(Some(0));
+                    self.selected_field_idx = None;
+                    if self.focused_panel == FocusedPanel::VaultItemList {
+                        self.focused_panel = FocusedPanel::VaultItemDetail;
+                    }
+                }
+                Err(err) => self.error_message = Some(err.to_string()),
+            }
+        }
+    }
+
+    /// Fetches every item in the vault whose field labels aren't known yet,
+    /// a few at a time on a worker thread, so search can match fields of
+    /// items that were never opened.
+    pub fn index_item_fields(&mut self) -> Result<()> {
+        if self.field_index_loading.is_some() {
+            return Ok(());
+        }
+        self.require_op(OpFeature::JsonOutput)?;
+        let account_id = self
+            .selected_account()
+            .map(|a| a.account_uuid.clone())
+            .context("No account selected")?;
+        let vault_id = self
+            .selected_vault()
+            .map(|v| v.id.clone())
+            .context("No vault selected")?;
+
+        let item_ids: Vec<String> = self
+            .vault_items
+            .iter()
+            .filter(|item| !self.item_field_labels.contains_key(&item.id))
+            .map(|item| item.id.clone())
+            .collect();
+        if item_ids.is_empty() {
+            return Ok(());
+        }
+        if self.cooling_down(&account_id, &field_index_command(item_ids.len())) {
+            return Ok(());
+        }
+        self.field_index_loading = Some(item_ids.len());
+
+        let tx = self.field_index_tx.clone();
+        let op = self.op.clone();
+        std::thread::spawn(move || {
+            let gets = crate::item_batch::get_items(
+                op.as_ref(),
+                &account_id,
+                &vault_id,
+                &item_ids,
+                crate::item_batch::WORKERS,
+            );
+            let _ = tx.send(FieldIndexLoad { account_id, gets });
+        });
+        Ok(())
+    }
+
+    /// Stores the field labels of a finished batch and searches again with
+    /// them while the search box is still open.
+    fn apply_field_index_loads(&mut self) {
+        while let Ok(load) = self.field_index_rx.try_recv() {
+            let requested = self.field_index_loading.take().unwrap_or(load.gets.len());
+            let command = field_index_command(requested);
+
+            let mut indexed = 0;
+            let mut failure = None;
+            for get in load.gets {
+                match get.result {
+                    Ok(stdout) => {
+                        let Ok(details) = serde_json::from_slice::<VaultItemDetails>(&stdout)
+                        else {
+                            continue;
+                        };
+                        self.item_field_labels.insert(
+                            get.item_id,
+                            details
+                                .detail_fields()
+                                .iter()
+                                .map(|f| f.label.clone())
+                                .collect(),
+                        );
+                        indexed += 1;
+                    }
+                    Err(stderr) => {
+                        failure.get_or_insert(stderr);
+                    }
+                }
+            }
+
+            let outcome: Result<(), String> = failure.map_or(Ok(()), Err);
+            self.record_op_result(Some(&load.account_id), &outcome);
+            match outcome {
+                Ok(()) => self.command_log.log_success(&command, Some(indexed)),
+                Err(stderr) => {
+                    if crate::signin::is_session_error(&stderr) {
+                        self.prompt_signin(
+                            Some(load.account_id.clone()),
+                            SigninRetry::Action(Box::new(Action::IndexItemFields)),
+                        );
+                    }
+                    self.command_log.log_failure(
+                        &command,
+                        format!("{indexed}/{requested} indexed\n{}", annotate(&stderr)),
+                    );
+                }
+            }
+            if self.search_active {
+                self.update_filtered_items();
+            }
+        }
+    }
+
+    /// Fetches a new one-time code once the shown one has rotated out, while
+    /// the details panel is focused.
+    pub fn refresh_totp_if_due(&mut self, now: SystemTime) {
+        if self.focused_panel != FocusedPanel::VaultItemDetail {
+            return;
+        }
+        let Some(totp) = &self.totp else {
+            return;
+        };
+        if totp.refreshing || totp.window == Some(crate::totp::window(now, totp.period)) {
+            return;
+        }
+        if let Err(err) = self.require_op(OpFeature::Otp) {
+            self.totp = None;
+            self.error_message = Some(err.to_string());
+            return;
+        }
+        let (Some(account_id), Some(vault_id)) = (
+            self.selected_account().map(|a| a.account_uuid.clone()),
+            self.selected_vault().map(|v| v.id.clone()),
+        ) else {
+            return;
+        };
+
+        let item_id = totp.item_id.clone();
+        if self.cooling_down(&account_id, &format!("op item get {item_id} --otp")) {
+            if let Some(totp) = &mut self.totp {
+                totp.window = Some(crate::totp::window(now, totp.period));
+            }
+            return;
+        }
+        if let Some(totp) = &mut self.totp {
+            totp.refreshing = true;
+        }
 
-        let stdout = self.run_op_command(&[
+        let args: Vec<String> = [
             "item",
             "get",
-            item_id,
+            &item_id,
+            "--otp",
             "--account",
             &account_id,
             "--vault",
             &vault_id,
-            "--format",
-            "json",
-        ])?;
-
-        let details: VaultItemDetails =
-            serde_json::from_slice(&stdout).context("Failed to parse item details JSON")?;
+        ]
+        .map(String::from)
+        .to_vec();
+        let tx = self.totp_tx.clone();
+        let op = self.op.clone();
+        std::thread::spawn(move || {
+            let (result, _) = run_op(op.as_ref(), &args);
+            let _ = tx.send(TotpLoad {
+                account_id,
+                item_id,
+                result,
+            });
+        });
+    }
 
-        self.command_log
-            .log_success(format!("op item get {item_id}"), Some(details.fields.len()));
+    /// The selected item's one-time code, if it's still in its window.
+    pub fn current_totp_code(&self, now: SystemTime) -> Option<&str> {
+        let totp = self.totp.as_ref()?;
+        let selected = self.selected_item_details.as_ref()?;
+        (selected.id == totp.item_id
+            && !totp.refreshing
+            && totp.window == Some(crate::totp::window(now, totp.period)))
+        .then_some(totp.code.as_deref())
+        .flatten()
+    }
 
-        self.selected_item_details = Some(details);
-        Ok(())
+    /// Shows freshly fetched one-time codes for the item still selected.
+    fn apply_totp_loads(&mut self) {
+        while let Ok(load) = self.totp_rx.try_recv() {
+            self.record_op_result(Some(&load.account_id), &load.result);
+            let Some(totp) = self.totp.as_mut().filter(|t| t.item_id == load.item_id) else {
+                continue;
+            };
+            totp.refreshing = false;
+            totp.window = Some(crate::totp::window(SystemTime::now(), totp.period));
+            match load.result {
+                Ok(stdout) => {
+                    totp.code = Some(String::from_utf8_lossy(&stdout).trim().to_string());
+                }
+                Err(stderr) => {
+                    totp.code = None;
+                    self.command_log.log_failure(
+                        format!("op item get {} --otp", load.item_id),
+                        annotate(&stderr),
+                    );
+                }
+            }
+        }
     }
 
     pub fn open_modal(&mut self, field_reference: String) {
         self.modal = Some(Modal::EnvVar {
             env_var_name: String::new(),
             field_reference,
+            editing: None,
+            prefix: None,
         });
     }
 
-    pub fn open_vars_delete_modal(&mut self, vars: Vec<String>) {
-        self.modal = Some(Modal::VarDeleteConfirm { vars });
+    /// Opens the env var modal for a field of the item in the Details panel,
+    /// with a name suggested from the item title and field label.
+    pub fn open_modal_for_field(&mut self, field_idx: usize) {
+        let Some(details) = &self.selected_item_details else {
+            return;
+        };
+        let fields = details.detail_fields();
+        let Some(field) = fields.get(field_idx) else {
+            return;
+        };
+        let reference = field.reference.clone();
+        let suggestion = suggest_field_env_var_name(&details.title, &field.label);
+
+        self.open_modal(reference);
+        if let Some(Modal::EnvVar { env_var_name, .. }) = self.modal.as_mut() {
+            *env_var_name = suggestion;
+        }
     }
 
-    pub fn close_modal(&mut self) {
-        self.modal = None;
+    /// Asks whether to overwrite when `env_var_name` already maps a
+    /// different reference or account, returning whether it asked. Saving
+    /// the same mapping again needs no confirmation.
+    pub fn confirm_var_collision(
+        &mut self,
+        env_var_name: &str,
+        account_id: &str,
+        op_reference: &str,
+    ) -> bool {
+        let Some(existing) = self
+            .config
+            .as_ref()
+            .and_then(|config| config.inject_vars.get(env_var_name))
+        else {
+            return false;
+        };
+        if existing.account_id == account_id && existing.op_reference == op_reference {
+            return false;
+        }
+        let existing = existing.clone();
+        let Some(return_to) = self.modal.take() else {
+            return false;
+        };
+        let prefix = match &return_to {
+            Modal::EnvVar { prefix, .. } => prefix.clone(),
+            _ => None,
+        };
+
         self.error_message = None;
+        self.modal = Some(Modal::VarCollision {
+            env_var_name: env_var_name.to_string(),
+            account_id: account_id.to_string(),
+            op_reference: op_reference.to_string(),
+            prefix,
+            existing,
+            return_to: Box::new(return_to),
+        });
+        true
     }
 
-    pub fn modal_selected_field(&self) -> Option<&ItemField> {
-        let details = self.selected_item_details.as_ref()?;
-        let Modal::EnvVar {
-            field_reference, ..
-        } = self.modal.as_ref()?
+    /// Goes back from the collision prompt to the env var modal to pick
+    /// another name.
+    pub fn rename_colliding_var(&mut self) {
+        if let Some(Modal::VarCollision { return_to, .. }) = self.modal.take() {
+            self.modal = Some(*return_to);
+        }
+    }
+
+    /// Opens the env var modal pre-filled with a managed var so it can be
+    /// renamed or pointed at another reference.
+    pub fn open_var_edit_modal(&mut self, var_name: &str) {
+        let Some(var) = self
+            .config
+            .as_ref()
+            .and_then(|config| config.inject_vars.get(var_name))
         else {
-            return None;
+            return;
         };
-        details
-            .fields
-            .iter()
-            .find(|f| &f.reference == field_reference)
+        self.modal = Some(Modal::EnvVar {
+            env_var_name: var_name.to_string(),
+            field_reference: var.op_reference.clone(),
+            editing: Some(VarEdit {
+                original_name: var_name.to_string(),
+                account_id: var.account_id.clone(),
+                active_field: EnvVarField::Name,
+            }),
+            prefix: var.prefix.clone(),
+        });
     }
 
-    pub const fn modal_env_var_name_mut(&mut self) -> Option<&mut String> {
-        match self.modal {
-            Some(Modal::EnvVar {
-                ref mut env_var_name,
-                ..
-            }) => Some(env_var_name),
-            _ => None,
+    pub fn toggle_item_mark(&mut self, item_id: &str) {
+        if !self.items_marked.remove(item_id) {
+            self.items_marked.insert(item_id.to_string());
         }
     }
 
-    pub fn modal_env_var_name(&self) -> Option<&str> {
-        match self.modal.as_ref()? {
-            Modal::EnvVar { env_var_name, .. } => Some(env_var_name.as_str()),
-            Modal::VarDeleteConfirm { .. } => None,
+    /// Opens the bulk mapping modal for the marked items, or the highlighted
+    /// item if none are marked.
+    pub fn open_bulk_map_modal(&mut self) {
+        let items: Vec<&VaultItem> = if self.items_marked.is_empty() {
+            self.selected_list_item().into_iter().collect()
+        } else {
+            self.vault_items
+                .iter()
+                .filter(|item| self.items_marked.contains(&item.id))
+                .collect()
+        };
+        let Some(first) = items.first() else {
+            self.command_log
+                .log_failure("Bulk map", "No items marked".to_string());
+            return;
+        };
+
+        let field = default_field_for_category(&first.category).to_string();
+        let mut rows: Vec<BulkMapRow> = Vec::with_capacity(items.len());
+        for item in &items {
+            let base = suggest_env_var_name(&item.title);
+            let mut env_var_name = base.clone();
+            let mut n = 2;
+            while rows.iter().any(|row| row.env_var_name == env_var_name) {
+                env_var_name = format!("{base}_{n}");
+                n += 1;
+            }
+            rows.push(BulkMapRow {
+                item_id: item.id.clone(),
+                item_title: item.title.clone(),
+                env_var_name,
+            });
         }
+
+        self.modal = Some(Modal::BulkMap {
+            field,
+            rows,
+            focus: BulkMapFocus::Row(0),
+        });
     }
 
-    pub fn modal_field_reference(&self) -> Option<&str> {
-        match self.modal.as_ref()? {
-            Modal::EnvVar {
-                field_reference, ..
-            } => Some(field_reference.as_str()),
-            Modal::VarDeleteConfirm { .. } => None,
+    /// The text input the bulk mapping modal is editing.
+    pub fn bulk_map_input_mut(&mut self) -> Option<&mut String> {
+        match self.modal.as_mut()? {
+            Modal::BulkMap {
+                focus: BulkMapFocus::Field,
+                field,
+                ..
+            } => Some(field),
+            Modal::BulkMap {
+                focus: BulkMapFocus::Row(idx),
+                rows,
+                ..
+            } => rows.get_mut(*idx).map(|row| &mut row.env_var_name),
+            _ => None,
         }
     }
 
-    pub fn modal_vars_delete_targets(&self) -> Option<&[String]> {
-        match self.modal.as_ref()? {
-            Modal::VarDeleteConfirm { vars } => Some(vars.as_slice()),
-            Modal::EnvVar { .. } => None,
+    /// Moves between the field input and the rows, wrapping around.
+    pub fn move_bulk_map_focus(&mut self, forward: bool) {
+        if let Some(Modal::BulkMap { rows, focus, .. }) = self.modal.as_mut() {
+            let stops = rows.len() + 1;
+            let current = match focus {
+                BulkMapFocus::Field => 0,
+                BulkMapFocus::Row(idx) => *idx + 1,
+            };
+            let next = if forward {
+                (current + 1) % stops
+            } else {
+                (current + stops - 1) % stops
+            };
+            *focus = match next {
+                0 => BulkMapFocus::Field,
+                idx => BulkMapFocus::Row(idx - 1),
+            };
         }
     }
 
-    pub fn load_managed_vars(&mut self) {
-        if let Some(config) = self.config.as_ref() {
-            self.managed_vars = config.inject_vars.keys().cloned().collect();
-            self.managed_vars.sort();
-        } else {
-            self.managed_vars.clear();
+    /// The mappings the bulk modal would save, as `(var_name, op_reference)`.
+    pub fn bulk_map_vars(&self) -> Result<Vec<(String, String)>> {
+        let Some(Modal::BulkMap { field, rows, .. }) = self.modal.as_ref() else {
+            bail!("Bulk mapping is not open");
+        };
+        let vault = self.selected_vault().context("No vault selected")?;
+        let field = field.trim();
+        if field.is_empty() {
+            bail!("Field name cannot be empty");
         }
+
+        let mut vars: Vec<(String, String)> = Vec::with_capacity(rows.len());
+        for row in rows {
+            if row.env_var_name.is_empty() {
+                bail!(
+                    "Environment variable name for {} cannot be empty",
+                    row.item_title
+                );
+            }
+            if vars.iter().any(|(name, _)| *name == row.env_var_name) {
+                bail!("{} is used more than once", row.env_var_name);
+            }
+            vars.push((
+                row.env_var_name.clone(),
+                format!("op://{}/{}/{field}", vault.name, row.item_id),
+            ));
+        }
+        Ok(vars)
     }
 
-    pub fn selected_managed_var(&self) -> Option<&String> {
-        self.managed_vars_list_state
-            .selected()
-            .and_then(|idx| self.managed_vars.get(idx))
+    pub fn open_vars_delete_modal(&mut self, vars: Vec<String>) {
+        self.modal = Some(Modal::VarDeleteConfirm { vars });
     }
 
-    pub fn toggle_managed_var_selection(&mut self, var: &str) {
-        if self.managed_vars_selected.contains(var) {
-            self.managed_vars_selected.remove(var);
+    /// What quitting now would throw away: `op` calls still running and
+    /// typed input.
+    pub fn quit_blockers(&self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if !self.loading_listings.is_empty() {
+            reasons.push(format!(
+                "{} list(s) still loading from op",
+                self.loading_listings.len()
+            ));
+        }
+        if self.item_details_loading.is_some() {
+            reasons.push("Item details still loading from op".to_string());
+        }
+        if let Some(items) = self.field_index_loading {
+            reasons.push(format!("{items} item(s) still loading for field search"));
+        }
+        if !self.search_query.is_empty() {
+            reasons.push(format!("The search \"{}\"", self.search_query));
+        }
+        reasons
+    }
+
+    /// Ends the `:` prompt, returning the zero-based row it named, if any.
+    pub fn take_jump_row(&mut self) -> Option<usize> {
+        self.jump_input
+            .take()?
+            .parse::<usize>()
+            .ok()?
+            .checked_sub(1)
+    }
+
+    /// The 1-based number shown before row `idx` of the focused list while
+    /// the `:` prompt is open, padded to the widest number.
+    pub fn row_number(&self, idx: usize, len: usize, focused: bool) -> String {
+        if self.jump_input.is_none() || !focused {
+            return String::new();
+        }
+        let width = len.to_string().len();
+        format!("{:>width$} ", idx + 1)
+    }
+
+    /// Quits, or asks first if [`App::quit_blockers`] finds anything.
+    pub fn request_quit(&mut self) {
+        let reasons = self.quit_blockers();
+        if reasons.is_empty() {
+            self.apply(Action::Quit);
         } else {
-            self.managed_vars_selected.insert(var.to_string());
+            self.modal = Some(Modal::QuitConfirm { reasons });
         }
     }
 
-    pub fn remove_managed_vars(&mut self, vars: &[String]) -> Result<()> {
-        let config = self
+    pub fn on_focus_lost(&mut self) {
+        if !self
             .config
-            .as_mut()
-            .context("Configuration can't be saved because it is not loaded")?;
+            .as_ref()
+            .is_none_or(|config| config.tui.lock_on_focus_loss())
+        {
+            return;
+        }
 
-        for var in vars {
-            if let Some(entry) = config.inject_vars.remove(var) {
-                match remove_cache_for_account(&entry.account_id) {
-                    Ok(CacheRemoval::Removed) => {
-                        self.command_log
-                            .log_success(format!("cache clear {}", entry.account_id), None);
-                    }
-                    Ok(CacheRemoval::NotFound) => {
-                        self.command_log
-                            .log_success(format!("cache miss {}", entry.account_id), None);
-                    }
-                    Err(err) => {
-                        self.command_log.log_failure(
-                            format!("cache clear {}", entry.account_id),
-                            err.to_string(),
-                        );
-                    }
-                }
-            }
+        self.secrets_hidden = true;
+        self.revealed_field = None;
+        if matches!(
+            self.modal,
+            Some(Modal::EnvVar { .. } | Modal::VarCollision { .. } | Modal::EnvPreview { .. })
+        ) {
+            self.close_modal();
         }
+    }
 
-        confy::store("op_loader", None, &*config).context("Failed to save configuration")?;
-        self.managed_vars_selected.retain(|var| !vars.contains(var));
-        self.load_managed_vars();
-        Ok(())
+    pub const fn on_focus_gained(&mut self) {
+        self.secrets_hidden = false;
     }
-}
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct Vault {
-    pub id: String,
-    pub name: String,
-}
+    /// Asks to sign in again after `op` reported an expired session, unless
+    /// the prompt is already up. Whatever modal was open comes back after.
+    pub fn prompt_signin(&mut self, account_id: Option<String>, retry: SigninRetry) {
+        if matches!(self.modal, Some(Modal::SignIn { .. })) {
+            return;
+        }
+        let return_to = self.modal.take().map(Box::new);
+        self.modal = Some(Modal::SignIn {
+            account_id,
+            retry,
+            return_to,
+        });
+    }
 
-#[derive(Debug, Clone, Deserialize)]
-#[allow(clippy::struct_field_names)]
-pub struct Account {
-    pub email: String,
-    #[allow(dead_code)]
-    pub user_uuid: String,
-    pub account_uuid: String,
-}
+    /// Makes `action` the retry of a sign-in prompt it triggered.
+    pub fn set_signin_retry(&mut self, action: Action) {
+        if let Some(Modal::SignIn { retry, .. }) = self.modal.as_mut()
+            && matches!(retry, SigninRetry::Pending)
+        {
+            *retry = SigninRetry::Action(Box::new(action));
+        }
+    }
 
-#[derive(Debug, Clone, Deserialize)]
-#[allow(dead_code)]
-pub struct ItemUrl {
-    #[serde(default)]
-    pub label: Option<String>,
-    #[serde(default)]
-    pub primary: bool,
-    pub href: String,
-}
+    /// The account the sign-in prompt is for; `None` is `op`'s default.
+    pub fn signin_account(&self) -> Option<String> {
+        match self.modal.as_ref() {
+            Some(Modal::SignIn { account_id, .. }) => account_id.clone(),
+            _ => None,
+        }
+    }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct VaultItem {
-    pub id: String,
-    pub title: String,
-    #[allow(dead_code)]
-    pub category: String,
-    #[serde(default)]
-    #[allow(dead_code)]
-    pub additional_information: Option<String>,
-    #[serde(default)]
-    #[allow(dead_code)]
-    pub urls: Vec<ItemUrl>,
-}
+    pub fn cancel_signin(&mut self) {
+        if let Some(Modal::SignIn { return_to, .. }) = self.modal.take() {
+            self.modal = return_to.map(|modal| *modal);
+        }
+    }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct VaultItemDetails {
-    #[allow(dead_code)]
-    pub id: String,
-    #[allow(dead_code)]
-    pub title: String,
-    #[allow(dead_code)]
-    pub category: String,
-    #[serde(default)]
-    pub fields: Vec<ItemField>,
-}
+    /// Closes the sign-in prompt after `op signin` ran and, if it worked,
+    /// retries what failed.
+    pub fn finish_signin(&mut self, result: Result<()>) {
+        let Some(Modal::SignIn {
+            account_id,
+            retry,
+            return_to,
+        }) = self.modal.take()
+        else {
+            return;
+        };
+        self.modal = return_to.map(|modal| *modal);
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct ItemField {
-    pub label: String,
-    #[serde(default)]
-    pub value: Option<String>,
-    #[serde(rename = "type")]
-    pub field_type: String,
-    pub reference: String,
-    #[serde(default)]
-    #[allow(dead_code)]
-    pub section: Option<FieldSection>,
-}
+        let command = account_id.as_ref().map_or_else(
+            || "op signin".to_string(),
+            |account_id| format!("op signin --account {account_id}"),
+        );
+        if let Err(err) = result {
+            self.command_log.log_failure(&command, err.to_string());
+            self.error_message = Some(err.to_string());
+            return;
+        }
+        self.command_log.log_success(&command, None);
+        self.error_message = None;
+        if let Some(account_id) = &account_id {
+            self.account_backoff.reset(account_id);
+        }
 
-#[derive(Debug, Clone, Deserialize)]
-#[allow(dead_code)]
-pub struct FieldSection {
-    pub id: String,
-    #[serde(default)]
-    pub label: Option<String>,
-}
+        match retry {
+            SigninRetry::Listing {
+                request,
+                first_load,
+            } => {
+                if self.loading_listings.contains(&request) {
+                    return;
+                }
+                if first_load {
+                    self.loading_listings.push(request.clone());
+                }
+                spawn_refresh(self.op.clone(), request, self.listing_tx.clone());
+            }
+            SigninRetry::ItemDetails(item_id) => {
+                if let Err(err) = self.load_item_details(&item_id) {
+                    self.error_message = Some(err.to_string());
+                }
+            }
+            SigninRetry::Action(action) => self.apply(*action),
+            SigninRetry::Pending => {}
+        }
+    }
 
-#[derive(PartialEq, Eq)]
-pub enum FocusedPanel {
-    AccountList,
-    VaultList,
-    VaultItemList,
-    VaultItemDetail,
-    VarsList,
-}
+    pub fn close_modal(&mut self) {
+        self.modal = None;
+        self.error_message = None;
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// The error to show in the error pane: the current error message if
+    /// there is one, otherwise the most recent failed command.
+    pub fn latest_error(&self) -> Option<ErrorDetails> {
+        self.error_message
+            .as_ref()
+            .map(|message| ErrorDetails {
+                source: "op-loader".to_string(),
+                message: message.clone(),
+            })
+            .or_else(|| self.command_log.last_failure())
+    }
 
-    fn make_vault_item(id: &str, title: &str) -> VaultItem {
-        VaultItem {
-            id: id.to_string(),
-            title: title.to_string(),
-            category: "LOGIN".to_string(),
-            additional_information: None,
-            urls: vec![],
+    pub fn open_error_pane(&mut self) {
+        if let Some(details) = self.latest_error() {
+            self.modal = Some(Modal::ErrorDetails { details, scroll: 0 });
         }
     }
 
-    fn make_item_field(label: &str, reference: &str) -> ItemField {
-        ItemField {
-            label: label.to_string(),
-            value: Some("secret-value".to_string()),
-            field_type: "CONCEALED".to_string(),
-            reference: reference.to_string(),
-            section: None,
+    pub fn scroll_error_pane(&mut self, delta: i16) {
+        if let Some(Modal::ErrorDetails { details, scroll }) = self.modal.as_mut() {
+            let max_scroll = details.message.lines().count().saturating_sub(1);
+            let max_scroll = u16::try_from(max_scroll).unwrap_or(u16::MAX);
+            *scroll = scroll.saturating_add_signed(delta).min(max_scroll);
         }
     }
 
-    mod update_filtered_items {
-        use super::*;
+    /// Resolves the managed vars like `env inject` and shows the export
+    /// statements it would print.
+    pub fn open_env_preview(&mut self) -> Result<()> {
+        let config = self.config.as_ref().context("No configuration loaded")?;
+        if config.inject_vars.is_empty() {
+            bail!("There are no managed vars to preview");
+        }
+        self.require_op(OpFeature::Inject)?;
+        let results = crate::cli::resolve_vars_per_account(self.op.as_ref(), config)?;
+
+        let mut vars = Vec::new();
+        let mut warnings = Vec::new();
+        let mut expired_account = None;
+        for (account_id, result) in results {
+            let command = format!("op inject --account {account_id}");
+            match result {
+                Ok(resolved) => {
+                    self.command_log.log_success(&command, Some(resolved.len()));
+                    vars.extend(crate::cli::with_prefixes(config, &resolved));
+                }
+                Err(err) => {
+                    let message = format!("{err:#}");
+                    self.command_log.log_failure(&command, &message);
+                    if crate::signin::is_session_error(&message) {
+                        expired_account = Some(account_id.clone());
+                    }
+                    warnings.push(format!(
+                        "Failed to resolve secrets for account {account_id}: {}",
+                        message.lines().next().unwrap_or_default()
+                    ));
+                }
+            }
+        }
+        vars.sort();
 
-        #[test]
-        fn empty_query_returns_all_items() {
-            let mut app = App::new();
-            app.vault_items = vec![
-                make_vault_item("1", "GitHub Token"),
-                make_vault_item("2", "AWS Secret"),
-                make_vault_item("3", "Database Password"),
-            ];
-            app.search_query = String::new();
+        self.modal = Some(Modal::EnvPreview {
+            vars,
+            warnings,
+            revealed: false,
+            scroll: 0,
+        });
+        if let Some(account_id) = expired_account {
+            self.prompt_signin(Some(account_id), SigninRetry::Pending);
+        }
+        Ok(())
+    }
 
-            app.update_filtered_items();
+    pub fn toggle_env_preview_reveal(&mut self) {
+        if let Some(Modal::EnvPreview { revealed, .. }) = self.modal.as_mut() {
+            *revealed = !*revealed;
+        }
+    }
 
-            assert_eq!(app.filtered_item_indices, vec![0, 1, 2]);
+    pub fn scroll_env_preview(&mut self, delta: i16) {
+        if let Some(Modal::EnvPreview {
+            vars,
+            warnings,
+            scroll,
+            ..
+        }) = self.modal.as_mut()
+        {
+            let line_count = vars.len() + warnings.len();
+            let max_scroll = u16::try_from(line_count.saturating_sub(1)).unwrap_or(u16::MAX);
+            *scroll = scroll.saturating_add_signed(delta).min(max_scroll);
+        }
+    }
+
+    pub fn open_command_log_view(&mut self) {
+        self.modal = Some(Modal::CommandLog { scroll: 0 });
+    }
+
+    pub fn scroll_command_log_view(&mut self, delta: i16) {
+        let line_count = self.command_log.full_lines().len();
+        if let Some(Modal::CommandLog { scroll }) = self.modal.as_mut() {
+            let max_scroll = u16::try_from(line_count.saturating_sub(1)).unwrap_or(u16::MAX);
+            *scroll = scroll.saturating_add_signed(delta).min(max_scroll);
+        }
+    }
+
+    pub fn open_help(&mut self) {
+        self.modal = Some(Modal::Help { scroll: 0 });
+    }
+
+    pub fn scroll_help(&mut self, delta: i16) {
+        if let Some(Modal::Help { scroll }) = self.modal.as_mut() {
+            let max_scroll = crate::help::help_line_count().saturating_sub(1);
+            let max_scroll = u16::try_from(max_scroll).unwrap_or(u16::MAX);
+            *scroll = scroll.saturating_add_signed(delta).min(max_scroll);
+        }
+    }
+
+    pub fn modal_selected_field(&self) -> Option<&ItemField> {
+        let details = self.selected_item_details.as_ref()?;
+        let Modal::EnvVar {
+            field_reference, ..
+        } = self.modal.as_ref()?
+        else {
+            return None;
+        };
+        details
+            .fields
+            .iter()
+            .find(|f| &f.reference == field_reference)
+    }
+
+    pub fn modal_env_var_name(&self) -> Option<&str> {
+        match self.modal.as_ref()? {
+            Modal::EnvVar { env_var_name, .. } => Some(env_var_name.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The input the env var modal is editing: the name, or the reference
+    /// when a managed var is being edited and its reference is active.
+    pub fn env_var_modal_input_mut(&mut self) -> Option<(&mut String, EnvVarField)> {
+        match self.modal.as_mut()? {
+            Modal::EnvVar {
+                field_reference,
+                editing:
+                    Some(VarEdit {
+                        active_field: EnvVarField::Reference,
+                        ..
+                    }),
+                ..
+            } => Some((field_reference, EnvVarField::Reference)),
+            Modal::EnvVar { env_var_name, .. } => Some((env_var_name, EnvVarField::Name)),
+            _ => None,
+        }
+    }
+
+    pub fn toggle_env_var_modal_field(&mut self) {
+        if let Some(Modal::EnvVar {
+            editing: Some(edit),
+            ..
+        }) = self.modal.as_mut()
+        {
+            edit.active_field = match edit.active_field {
+                EnvVarField::Name => EnvVarField::Reference,
+                EnvVarField::Reference => EnvVarField::Name,
+            };
+        }
+    }
+
+    /// Steps the env var modal's prefix through `var_prefixes`, then back to
+    /// none.
+    pub fn cycle_env_var_prefix(&mut self) {
+        let prefixes = self
+            .config
+            .as_ref()
+            .map(|config| config.var_prefixes.clone())
+            .unwrap_or_default();
+        let Some(Modal::EnvVar { prefix, .. }) = self.modal.as_mut() else {
+            return;
+        };
+        if prefixes.is_empty() {
+            self.error_message =
+                Some("Add prefixes to `var_prefixes` in the config to choose one".to_string());
+            return;
+        }
+
+        let next = match prefix.as_ref() {
+            None => 0,
+            Some(current) => prefixes
+                .iter()
+                .position(|candidate| candidate == current)
+                .map_or(0, |idx| idx + 1),
+        };
+        *prefix = prefixes.get(next).cloned();
+    }
+
+    pub fn modal_env_var_prefix(&self) -> Option<&str> {
+        match self.modal.as_ref()? {
+            Modal::EnvVar { prefix, .. } => prefix.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn modal_field_reference(&self) -> Option<&str> {
+        match self.modal.as_ref()? {
+            Modal::EnvVar {
+                field_reference, ..
+            } => Some(field_reference.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn modal_vars_delete_targets(&self) -> Option<&[String]> {
+        match self.modal.as_ref()? {
+            Modal::VarDeleteConfirm { vars } => Some(vars.as_slice()),
+            _ => None,
+        }
+    }
+
+    /// Asks where to place the selected document item, starting from where
+    /// it's already placed if it's a managed file.
+    pub fn open_place_file_modal(&mut self) -> Result<()> {
+        let item = self.selected_list_item().context("No item selected")?;
+        if item.category != "DOCUMENT" {
+            bail!(
+                "{} is a {} item; only document items can be placed as files",
+                item.title,
+                category_label(&item.category)
+            );
+        }
+        let account_id = self
+            .selected_account()
+            .map(|a| a.account_uuid.clone())
+            .context("No account selected")?;
+        let vault_id = self
+            .selected_vault()
+            .map(|v| v.id.clone())
+            .context("No vault selected")?;
+        let file = ManagedFile {
+            account_id,
+            vault_id,
+            item_id: item.id.clone(),
+            title: item.title.clone(),
+        };
+        let path = self
+            .config
+            .as_ref()
+            .and_then(|config| {
+                config
+                    .managed_files
+                    .iter()
+                    .find(|(_, existing)| existing.item_id == file.item_id)
+            })
+            .map(|(target, _)| target.clone())
+            .unwrap_or_default();
+
+        self.modal = Some(Modal::PlaceFile { file, path });
+        Ok(())
+    }
+
+    pub const fn modal_place_file_path_mut(&mut self) -> Option<&mut String> {
+        match self.modal {
+            Some(Modal::PlaceFile { ref mut path, .. }) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Downloads `file` to `path` with mode 0600 and keeps it as a managed
+    /// file, so `env inject` and `bootstrap` place it again. Returns the
+    /// expanded target.
+    pub fn place_managed_file(&mut self, path: &str, file: ManagedFile) -> Result<PathBuf> {
+        let path = path.trim();
+        if path.is_empty() {
+            bail!("Enter a path to place the file at");
+        }
+        let target = crate::cli::expand_path(path)?;
+        if let Some(config) = &self.config {
+            crate::sync_guard::ensure_allowed(&target, &config.allowed_sync_paths)?;
+        }
+        self.require_op(OpFeature::Document)?;
+
+        let contents = self.run_op_command(&crate::managed_files::document_get_args(&file))?;
+        crate::managed_files::place(&target, &contents)?;
+
+        let config = self.config.as_mut().context("No configuration loaded")?;
+        config
+            .managed_files
+            .insert(target.to_string_lossy().to_string(), file);
+        confy::store("op_loader", None, &*config).context("Failed to save configuration")?;
+        Ok(target)
+    }
+
+    pub fn open_vault_create_modal(&mut self) {
+        self.modal = Some(Modal::VaultCreate {
+            name: String::new(),
+            description: String::new(),
+            active_field: VaultCreateField::Name,
+        });
+    }
+
+    pub const fn modal_vault_create_input_mut(&mut self) -> Option<&mut String> {
+        match self.modal {
+            Some(Modal::VaultCreate {
+                ref mut name,
+                active_field: VaultCreateField::Name,
+                ..
+            }) => Some(name),
+            Some(Modal::VaultCreate {
+                ref mut description,
+                active_field: VaultCreateField::Description,
+                ..
+            }) => Some(description),
+            _ => None,
+        }
+    }
+
+    pub const fn toggle_vault_create_field(&mut self) {
+        if let Some(Modal::VaultCreate {
+            ref mut active_field,
+            ..
+        }) = self.modal
+        {
+            *active_field = match active_field {
+                VaultCreateField::Name => VaultCreateField::Description,
+                VaultCreateField::Description => VaultCreateField::Name,
+            };
+        }
+    }
+
+    /// Opens the value editor for the highlighted Details field. The new
+    /// value starts empty rather than exposing the current one.
+    pub fn open_field_edit_modal(&mut self) {
+        let Some(details) = self.selected_item_details.as_ref() else {
+            return;
+        };
+        let Some(field) = self.highlighted_detail_field() else {
+            self.command_log
+                .log_failure("Field edit", "No field selected".to_string());
+            return;
+        };
+        let assignment_target = match field.section_label() {
+            Some(section) => format!(
+                "{}.{}",
+                escape_assignment_name(section),
+                escape_assignment_name(&field.label)
+            ),
+            None => escape_assignment_name(&field.label),
+        };
+        self.modal = Some(Modal::FieldEdit {
+            item_id: details.id.clone(),
+            item_title: details.title.clone(),
+            field_label: field.label.clone(),
+            assignment_target,
+            value: String::new(),
+            confirming: false,
+        });
+    }
+
+    /// Opens the generator over the field editor, or over the item creation
+    /// form when a field row is active.
+    pub fn open_password_generator(&mut self) {
+        let opens = matches!(
+            self.modal,
+            Some(
+                Modal::FieldEdit {
+                    confirming: false,
+                    ..
+                } | Modal::ItemCreate {
+                    focus: ItemCreateFocus::Field(_),
+                    ..
+                }
+            )
+        );
+        if !opens {
+            return;
+        }
+        let Some(return_to) = self.modal.take() else {
+            return;
+        };
+        let recipe = Recipe::default();
+        self.modal = Some(Modal::PasswordGenerator {
+            recipe,
+            password: crate::password_gen::generate(recipe),
+            return_to: Box::new(return_to),
+        });
+    }
+
+    /// Applies a recipe change and generates a fresh password for it.
+    pub fn update_password_recipe(&mut self, change: impl FnOnce(&mut Recipe)) {
+        if let Some(Modal::PasswordGenerator {
+            recipe, password, ..
+        }) = self.modal.as_mut()
+        {
+            change(recipe);
+            *password = crate::password_gen::generate(*recipe);
+        }
+    }
+
+    /// Goes back to the form the generator was opened from, with the
+    /// password in the active field when `insert` is set.
+    pub fn close_password_generator(&mut self, insert: bool) {
+        let Some(Modal::PasswordGenerator {
+            password,
+            return_to,
+            ..
+        }) = self.modal.take()
+        else {
+            return;
+        };
+        let mut previous = *return_to;
+        if insert {
+            match &mut previous {
+                Modal::FieldEdit { value, .. } => *value = password,
+                Modal::ItemCreate {
+                    fields,
+                    focus: ItemCreateFocus::Field(idx),
+                    ..
+                } => {
+                    if let Some(field) = fields.get_mut(*idx) {
+                        let label = field
+                            .split_once('=')
+                            .map_or("password", |(label, _)| label)
+                            .to_string();
+                        *field = format!("{label}={password}");
+                    }
+                    if fields.last().is_some_and(|field| !field.is_empty()) {
+                        fields.push(String::new());
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.modal = Some(previous);
+    }
+
+    pub fn set_field_edit_confirming(&mut self, value: bool) {
+        if let Some(Modal::FieldEdit { confirming, .. }) = self.modal.as_mut() {
+            *confirming = value;
+        }
+    }
+
+    /// Sets the field value from the open editor with `op item edit`. op
+    /// only takes edits as assignment arguments, so unlike item creation the
+    /// value is briefly visible in the process list.
+    pub fn edit_field_from_modal(&mut self) -> Result<()> {
+        let Some(Modal::FieldEdit {
+            item_id,
+            field_label,
+            assignment_target,
+            value,
+            ..
+        }) = self.modal.clone()
+        else {
+            bail!("Field editing is not open");
+        };
+        let account_id = self
+            .selected_account()
+            .map(|a| a.account_uuid.clone())
+            .context("No account selected")?;
+        let vault_id = self
+            .selected_vault()
+            .map(|v| v.id.clone())
+            .context("No vault selected")?;
+
+        let assignment = format!("{assignment_target}={value}");
+        let shown_as = format!("op item edit {item_id} {assignment_target}=********");
+        self.run_op_command_with_input(
+            &[
+                "item",
+                "edit",
+                &item_id,
+                "--account",
+                &account_id,
+                "--vault",
+                &vault_id,
+                &assignment,
+            ],
+            None,
+            Some(&shown_as),
+        )?;
+        self.command_log
+            .log_success(format!("op item edit {item_id} ({field_label})"), None);
+
+        // Resolved vars from this account may hold the old value.
+        if let Err(err) = remove_cache_for_account(&account_id, false) {
+            self.command_log
+                .log_failure(format!("cache clear {account_id}"), err.to_string());
+        }
+        self.revealed_field = None;
+        self.load_item_details(&item_id)
+    }
+
+    pub fn open_item_create_modal(&mut self) {
+        self.modal = Some(Modal::ItemCreate {
+            title: String::new(),
+            category_idx: 0,
+            fields: default_item_fields(0),
+            focus: ItemCreateFocus::Title,
+        });
+    }
+
+    /// The text input the item creation form is editing, if any.
+    pub fn item_create_input_mut(&mut self) -> Option<&mut String> {
+        match self.modal.as_mut()? {
+            Modal::ItemCreate {
+                title,
+                focus: ItemCreateFocus::Title,
+                ..
+            } => Some(title),
+            Modal::ItemCreate {
+                fields,
+                focus: ItemCreateFocus::Field(idx),
+                ..
+            } => {
+                // Keep one empty row at the end to type the next field into.
+                if *idx + 1 == fields.len() {
+                    fields.push(String::new());
+                }
+                fields.get_mut(*idx)
+            }
+            _ => None,
+        }
+    }
+
+    /// Moves between the title, category, and field rows, wrapping around.
+    pub fn move_item_create_focus(&mut self, forward: bool) {
+        if let Some(Modal::ItemCreate { fields, focus, .. }) = self.modal.as_mut() {
+            let stops = fields.len() + 2;
+            let current = match focus {
+                ItemCreateFocus::Title => 0,
+                ItemCreateFocus::Category => 1,
+                ItemCreateFocus::Field(idx) => *idx + 2,
+            };
+            let next = if forward {
+                (current + 1) % stops
+            } else {
+                (current + stops - 1) % stops
+            };
+            *focus = match next {
+                0 => ItemCreateFocus::Title,
+                1 => ItemCreateFocus::Category,
+                idx => ItemCreateFocus::Field(idx - 2),
+            };
+        }
+    }
+
+    /// Switches category. The field rows follow the category's defaults as
+    /// long as no value has been typed yet.
+    pub fn cycle_item_create_category(&mut self, forward: bool) {
+        if let Some(Modal::ItemCreate {
+            category_idx,
+            fields,
+            ..
+        }) = self.modal.as_mut()
+        {
+            let count = ITEM_CREATE_CATEGORIES.len();
+            *category_idx = if forward {
+                (*category_idx + 1) % count
+            } else {
+                (*category_idx + count - 1) % count
+            };
+            let untouched = fields.iter().all(|field| {
+                field
+                    .split_once('=')
+                    .is_none_or(|(_, value)| value.is_empty())
+            });
+            if untouched {
+                *fields = default_item_fields(*category_idx);
+            }
+        }
+    }
+
+    /// Creates the item described by the open form in the selected vault.
+    /// Values go to `op` on stdin, so they never show up in the process list.
+    pub fn create_item_from_modal(&mut self) -> Result<()> {
+        let Some(Modal::ItemCreate {
+            title,
+            category_idx,
+            fields,
+            ..
+        }) = self.modal.as_ref()
+        else {
+            bail!("Item creation is not open");
+        };
+        let template = item_create_template(title, *category_idx, fields)?;
+        let title = title.trim().to_string();
+
+        let account_id = self
+            .selected_account()
+            .map(|a| a.account_uuid.clone())
+            .context("No account selected")?;
+        let vault_id = self
+            .selected_vault()
+            .map(|v| v.id.clone())
+            .context("No vault selected")?;
+
+        let payload = serde_json::to_vec(&template).context("Failed to build item JSON")?;
+        self.run_op_command_with_input(
+            &[
+                "item",
+                "create",
+                "--account",
+                &account_id,
+                "--vault",
+                &vault_id,
+                "--format",
+                "json",
+            ],
+            Some(&payload),
+            None,
+        )?;
+        self.command_log
+            .log_success(format!("op item create {title}"), None);
+
+        ListingRequest::Items {
+            account_id,
+            vault_id,
+        }
+        .invalidate();
+        self.load_vault_items()
+    }
+
+    pub fn create_vault(&mut self, name: &str, description: &str) -> Result<()> {
+        let account_id = self
+            .selected_account()
+            .map(|a| a.account_uuid.clone())
+            .context("No account selected")?;
+
+        let mut args = vec![
+            "vault",
+            "create",
+            name,
+            "--account",
+            &account_id,
+            "--format",
+            "json",
+        ];
+        if !description.is_empty() {
+            args.extend(["--description", description]);
+        }
+
+        let stdout = self.run_op_command(&args)?;
+        let vault: Vault =
+            serde_json::from_slice(&stdout).context("Failed to parse created vault JSON")?;
+
+        self.command_log
+            .log_success(format!("op vault create {name}"), None);
+
+        ListingRequest::Vaults {
+            account_id: Some(account_id.clone()),
+        }
+        .invalidate();
+
+        // Show the new vault right away; the refreshed listing keeps it
+        // highlighted by id.
+        self.vault_list_state.select(Some(self.vaults.len()));
+        self.vaults.push(vault);
+        spawn_refresh(
+            self.op.clone(),
+            ListingRequest::Vaults {
+                account_id: Some(account_id),
+            },
+            self.listing_tx.clone(),
+        );
+
+        Ok(())
+    }
+
+    pub fn load_vault_details(&mut self, vault_id: &str) -> Result<VaultDetails> {
+        let account = self
+            .selected_account()
+            .map(|a| (a.account_uuid.clone(), a.user_uuid.clone()));
+
+        let mut args = vec!["vault", "get", vault_id, "--format", "json"];
+        if let Some((account_id, _)) = &account {
+            args.extend(["--account", account_id.as_str()]);
+        }
+        let stdout = self.run_op_command(&args)?;
+        let mut details: VaultDetails =
+            serde_json::from_slice(&stdout).context("Failed to parse vault details JSON")?;
+
+        self.command_log
+            .log_success(format!("op vault get {vault_id}"), None);
+
+        // Listing vault users needs manage permissions, so a failure here
+        // just leaves the permissions unknown (run_op_command logs it).
+        if let Some((account_id, user_uuid)) = &account {
+            let users_stdout = self.run_op_command(&[
+                "vault",
+                "user",
+                "list",
+                vault_id,
+                "--account",
+                account_id,
+                "--format",
+                "json",
+            ]);
+            if let Ok(users_stdout) = users_stdout {
+                let users: Vec<VaultUser> =
+                    serde_json::from_slice(&users_stdout).unwrap_or_default();
+                details.permissions = users
+                    .into_iter()
+                    .find(|u| &u.id == user_uuid)
+                    .map(|u| u.permissions);
+            }
+        }
+
+        Ok(details)
+    }
+
+    pub fn selected_list_item(&self) -> Option<&VaultItem> {
+        self.vault_item_list_state
+            .selected()
+            .and_then(|idx| self.filtered_item_indices.get(idx))
+            .and_then(|&real_idx| self.vault_items.get(real_idx))
+    }
+
+    pub fn highlighted_detail_field(&self) -> Option<&ItemField> {
+        let idx = self.item_detail_list_state.selected()?;
+        self.selected_item_details
+            .as_ref()?
+            .detail_fields()
+            .get(idx)
+            .copied()
+    }
+
+    /// The `op` command that reproduces the current selection: `op read` for a
+    /// field in the Details panel, `op item get` for an item in the Items panel.
+    pub fn op_command_for_selection(&self) -> Option<String> {
+        let account_id = &self.selected_account()?.account_uuid;
+        match self.focused_panel {
+            FocusedPanel::VaultItemDetail => self
+                .highlighted_detail_field()
+                .map(|field| op_read_command(&field.reference, account_id)),
+            FocusedPanel::VaultItemList => {
+                let item = self.selected_list_item()?;
+                let vault = self.selected_vault()?;
+                Some(op_item_get_command(&item.id, &vault.id, account_id))
+            }
+            _ => None,
+        }
+    }
+
+    /// The value of a field in the selected item. Concealed values aren't
+    /// kept in the item details, so they're fetched with `op read`.
+    pub fn field_value(&mut self, reference: &str) -> Result<String> {
+        let field = self
+            .selected_item_details
+            .as_ref()
+            .and_then(|details| details.fields.iter().find(|f| f.reference == reference))
+            .with_context(|| format!("Field {reference} is not in the selected item"))?;
+
+        if field.is_otp() {
+            return self
+                .current_totp_code(SystemTime::now())
+                .map(str::to_string)
+                .context("No current one-time code yet; focus the details panel to fetch one");
+        }
+        if field.field_type != "CONCEALED" {
+            return Ok(field.value.clone().unwrap_or_default());
+        }
+
+        let account_id = self
+            .selected_account()
+            .map(|a| a.account_uuid.clone())
+            .context("No account selected")?;
+        self.require_op(OpFeature::Read)?;
+        let stdout =
+            self.run_op_command(&["read", "--no-newline", reference, "--account", &account_id])?;
+        String::from_utf8(stdout).context("Field value is not valid UTF-8")
+    }
+
+    /// Fetches `reference` and shows it unmasked for `tui.reveal_for`.
+    pub fn reveal_field(&mut self, reference: &str) -> Result<()> {
+        let value = self.field_value(reference)?;
+        let reveal_for = self
+            .config
+            .as_ref()
+            .map_or(DEFAULT_REVEAL_FOR, |config| config.tui.reveal_for());
+        self.revealed_field = Some(RevealedField {
+            reference: reference.to_string(),
+            value,
+            until: std::time::Instant::now() + reveal_for,
+        });
+        Ok(())
+    }
+
+    /// The revealed value for `field`, while it is still the highlighted one.
+    pub fn revealed_value(&self, field: &ItemField) -> Option<&str> {
+        let revealed = self.revealed_field.as_ref()?;
+        let highlighted = self.highlighted_detail_field()?;
+        (!self.secrets_hidden
+            && revealed.reference == field.reference
+            && highlighted.reference == field.reference)
+            .then_some(revealed.value.as_str())
+    }
+
+    /// Drops a revealed value once it expires or the highlight has moved to
+    /// another field or item.
+    pub fn remask_if_stale(&mut self, now: std::time::Instant) {
+        let Some(revealed) = &self.revealed_field else {
+            return;
+        };
+        let still_highlighted = self
+            .highlighted_detail_field()
+            .is_some_and(|field| field.reference == revealed.reference);
+        if now >= revealed.until || !still_highlighted || self.secrets_hidden {
+            self.revealed_field = None;
+        }
+    }
+
+    /// Remembers to clear `value` from the clipboard once the configured
+    /// delay has passed.
+    pub fn schedule_clipboard_clear(&mut self, value: &str) {
+        let clear_after = self
+            .config
+            .as_ref()
+            .map_or(Some(DEFAULT_CLIPBOARD_CLEAR_AFTER), |config| {
+                config.clipboard.clear_after()
+            });
+        self.clipboard_clear = clear_after.map(|delay| PendingClipboardClear {
+            at: std::time::Instant::now() + delay,
+            hash: crate::cache::config_hash(value),
+        });
+    }
+
+    pub fn clipboard_clear_due(&self, now: std::time::Instant) -> bool {
+        self.clipboard_clear
+            .as_ref()
+            .is_some_and(|pending| pending.at <= now)
+    }
+
+    pub fn open_item_delete_modal(&mut self, item: &VaultItem, archive: bool) {
+        let affected_vars = self.managed_vars_referencing_item(item);
+        self.modal = Some(Modal::ItemDeleteConfirm {
+            item_id: item.id.clone(),
+            item_title: item.title.clone(),
+            archive,
+            confirmed_once: false,
+            affected_vars,
+        });
+    }
+
+    /// Managed vars whose `op://vault/item/...` reference points at `item`
+    /// in the currently selected vault, by either ID or name.
+    pub fn managed_vars_referencing_item(&self, item: &VaultItem) -> Vec<String> {
+        let Some(config) = self.config.as_ref() else {
+            return Vec::new();
+        };
+        let Some(vault) = self.selected_vault() else {
+            return Vec::new();
+        };
+
+        let mut vars: Vec<String> = config
+            .inject_vars
+            .iter()
+            .filter(|(_, var)| {
+                reference_targets_item(&var.op_reference, vault, &item.id, &item.title)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        vars.sort();
+        vars
+    }
+
+    pub fn delete_vault_item(&mut self, item_id: &str, archive: bool) -> Result<()> {
+        let account_id = self
+            .selected_account()
+            .map(|a| a.account_uuid.clone())
+            .context("No account selected")?;
+        let vault_id = self
+            .selected_vault()
+            .map(|v| v.id.clone())
+            .context("No vault selected")?;
+
+        let mut args = vec![
+            "item",
+            "delete",
+            item_id,
+            "--account",
+            &account_id,
+            "--vault",
+            &vault_id,
+        ];
+        if archive {
+            args.push("--archive");
+        }
+        self.run_op_command(&args)?;
+
+        let verb = if archive { "archive" } else { "delete" };
+        self.command_log
+            .log_success(format!("op item {verb} {item_id}"), None);
+        self.forget_recent_item(&account_id, item_id);
+
+        ListingRequest::Items {
+            account_id: account_id.clone(),
+            vault_id: vault_id.clone(),
+        }
+        .invalidate();
+
+        self.load_vault_items()
+    }
+
+    pub fn load_managed_vars(&mut self) {
+        if let Some(config) = self.config.as_ref() {
+            self.managed_vars = config.inject_vars.keys().cloned().collect();
+            self.managed_vars.sort();
+            self.managed_vars_in_templates = crate::cli::get_templates_dir()
+                .map(|dir| vars_used_in_templates(config, &dir))
+                .unwrap_or_default();
+        } else {
+            self.managed_vars.clear();
+            self.managed_vars_in_templates.clear();
+        }
+    }
+
+    /// Resolves a managed var with `op read` and logs the outcome. The value
+    /// is dropped right away; the log only shows its length.
+    pub fn test_managed_var(&mut self, var_name: &str) -> Result<()> {
+        let var = self
+            .config
+            .as_ref()
+            .and_then(|config| config.inject_vars.get(var_name))
+            .cloned()
+            .with_context(|| format!("{var_name} is not a managed var"))?;
+        self.require_op(OpFeature::Read)?;
+
+        let value = self.run_op_command(&[
+            "read",
+            &var.op_reference,
+            "--account",
+            &var.account_id,
+            "--no-newline",
+        ])?;
+        let length = String::from_utf8_lossy(&value).chars().count();
+        self.command_log.log_success(
+            format!(
+                "op read {} ({var_name}: {})",
+                var.op_reference,
+                masked_length(length)
+            ),
+            None,
+        );
+        Ok(())
+    }
+
+    /// The email of the account a managed var belongs to, falling back to
+    /// the account UUID before accounts have loaded.
+    pub fn managed_var_account_label(&self, var: &InjectVarConfig) -> String {
+        self.accounts
+            .iter()
+            .find(|account| account.account_uuid == var.account_id)
+            .map_or_else(|| var.account_id.clone(), |account| account.email.clone())
+    }
+
+    pub fn selected_managed_var(&self) -> Option<&String> {
+        self.managed_vars_list_state
+            .selected()
+            .and_then(|idx| self.managed_vars.get(idx))
+    }
+
+    pub fn toggle_managed_var_selection(&mut self, var: &str) {
+        if self.managed_vars_selected.contains(var) {
+            self.managed_vars_selected.remove(var);
+        } else {
+            self.managed_vars_selected.insert(var.to_string());
+        }
+    }
+
+    pub fn remove_managed_vars(&mut self, vars: &[String]) -> Result<()> {
+        let config = self
+            .config
+            .as_mut()
+            .context("Configuration can't be saved because it is not loaded")?;
+
+        let mut removed = Vec::new();
+        for var in vars {
+            if let Some(entry) = config.inject_vars.remove(var) {
+                removed.push((var.clone(), entry.clone()));
+                match remove_cache_for_account(&entry.account_id, false) {
+                    Ok(CacheRemoval::Removed) => {
+                        self.command_log
+                            .log_success(format!("cache clear {}", entry.account_id), None);
+                    }
+                    Ok(CacheRemoval::NotFound) => {
+                        self.command_log
+                            .log_success(format!("cache miss {}", entry.account_id), None);
+                    }
+                    Err(err) => {
+                        self.command_log.log_failure(
+                            format!("cache clear {}", entry.account_id),
+                            err.to_string(),
+                        );
+                    }
+                }
+            }
+        }
+
+        confy::store("op_loader", None, &*config).context("Failed to save configuration")?;
+        self.managed_vars_selected.retain(|var| !vars.contains(var));
+        self.deleted_vars = removed;
+        self.load_managed_vars();
+        Ok(())
+    }
+
+    /// Puts back the vars removed by the last delete, returning how many
+    /// were restored. A name mapped again since the delete keeps its new
+    /// mapping.
+    pub fn restore_deleted_vars(&mut self) -> Result<usize> {
+        if self.deleted_vars.is_empty() {
+            bail!("There is no var deletion to undo");
+        }
+        let config = self
+            .config
+            .as_mut()
+            .context("Configuration can't be saved because it is not loaded")?;
+
+        let mut restored = 0;
+        for (var, entry) in &self.deleted_vars {
+            if !config.inject_vars.contains_key(var) {
+                config.inject_vars.insert(var.clone(), entry.clone());
+                restored += 1;
+            }
+        }
+
+        confy::store("op_loader", None, &*config).context("Failed to save configuration")?;
+        self.deleted_vars.clear();
+        self.load_managed_vars();
+        Ok(restored)
+    }
+}
+
+/// Backslash-escapes the characters `op` assignments treat specially in
+/// section and field names.
+fn escape_assignment_name(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        if matches!(c, '.' | '=' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn default_item_fields(category_idx: usize) -> Vec<String> {
+    let mut fields: Vec<String> = ITEM_CREATE_CATEGORIES[category_idx]
+        .2
+        .iter()
+        .map(|label| format!("{label}="))
+        .collect();
+    fields.push(String::new());
+    fields
+}
+
+/// Fields whose values are concealed in 1Password, by built-in id or by
+/// a label that looks like a secret.
+fn is_secret_label(label: &str) -> bool {
+    let label = label.to_ascii_lowercase();
+    ["password", "credential", "secret", "token", "key"]
+        .iter()
+        .any(|word| label.contains(word))
+}
+
+/// The JSON item `op item create` reads from stdin. Empty rows are skipped;
+/// built-in fields keep their purpose so 1Password shows them in place.
+pub fn item_create_template(
+    title: &str,
+    category_idx: usize,
+    fields: &[String],
+) -> Result<serde_json::Value> {
+    let title = title.trim();
+    if title.is_empty() {
+        bail!("Item title cannot be empty");
+    }
+    let (_, category, _) = ITEM_CREATE_CATEGORIES
+        .get(category_idx)
+        .context("Unknown item category")?;
+
+    let mut json_fields = Vec::new();
+    for field in fields.iter().filter(|field| !field.trim().is_empty()) {
+        let (label, value) = field
+            .split_once('=')
+            .with_context(|| format!("Expected label=value, got '{field}'"))?;
+        let label = label.trim();
+        if label.is_empty() {
+            bail!("Field label cannot be empty");
+        }
+        let purpose = match label {
+            "username" => Some("USERNAME"),
+            "password" => Some("PASSWORD"),
+            "notesPlain" => Some("NOTES"),
+            _ => None,
+        };
+        let field_type = if is_secret_label(label) {
+            "CONCEALED"
+        } else {
+            "STRING"
+        };
+        let mut json = serde_json::json!({
+            "id": label,
+            "label": label,
+            "type": field_type,
+            "value": value,
+        });
+        if let Some(purpose) = purpose {
+            json["purpose"] = purpose.into();
+        }
+        json_fields.push(json);
+    }
+
+    Ok(serde_json::json!({
+        "title": title,
+        "category": category,
+        "fields": json_fields,
+    }))
+}
+
+/// `********, 24 chars`, or a warning when op returned nothing.
+pub fn masked_length(length: usize) -> String {
+    match length {
+        0 => "empty value".to_string(),
+        1 => "********, 1 char".to_string(),
+        n => format!("********, {n} chars"),
+    }
+}
+
+/// The managed vars that any managed template refers to, in a `{{VAR}}`
+/// placeholder or a tag, and is allowed to use. Templates that can't be read
+/// are skipped.
+pub fn vars_used_in_templates(config: &OpLoadConfig, templates_dir: &Path) -> HashSet<String> {
+    let templates: Vec<(&TemplatedFile, String)> = config
+        .templated_files
+        .values()
+        .filter_map(|template| {
+            crate::template_engine::read_for_references(
+                &templates_dir.join(&template.template_name),
+                templates_dir,
+            )
+            .map(|content| (template, content))
+        })
+        .collect();
+
+    config
+        .inject_vars
+        .keys()
+        .filter(|name| {
+            templates.iter().any(|(template, content)| {
+                template.allows(name) && crate::template_engine::references(content, name)
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// An env var name from an item title: `SendGrid API (prod)` becomes
+/// `SENDGRID_API_PROD`.
+pub fn suggest_env_var_name(title: &str) -> String {
+    let mut name = String::with_capacity(title.len());
+    for c in title.chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_uppercase());
+        } else if !name.is_empty() && !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    while name.ends_with('_') {
+        name.pop();
+    }
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+/// An env var name for one field of an item. The label is appended unless
+/// it's a generic secret label and the title already names the secret:
+/// `GitHub Token` + `credential` becomes `GITHUB_TOKEN`, `Stripe` +
+/// `credential` becomes `STRIPE_TOKEN`, and `AWS` + `access key id` becomes
+/// `AWS_ACCESS_KEY_ID`.
+pub fn suggest_field_env_var_name(title: &str, label: &str) -> String {
+    const GENERIC_LABELS: &[&str] = &["CREDENTIAL", "PASSWORD", "SECRET", "TOKEN"];
+    const SECRET_WORDS: &[&str] = &["KEY", "PASSWORD", "PAT", "SECRET", "TOKEN"];
+
+    let base = suggest_env_var_name(title);
+    let label = suggest_env_var_name(label);
+    let is_generic = GENERIC_LABELS.contains(&label.as_str());
+    let suffix = if label == "CREDENTIAL" {
+        "TOKEN".to_string()
+    } else {
+        label
+    };
+
+    let last_word = base.rsplit('_').next().unwrap_or_default();
+    if suffix.is_empty()
+        || base == suffix
+        || base.ends_with(&format!("_{suffix}"))
+        || (is_generic && SECRET_WORDS.contains(&last_word))
+    {
+        return base;
+    }
+    if base.is_empty() {
+        return suffix;
+    }
+    format!("{base}_{suffix}")
+}
+
+/// The field most items of a category keep their secret in.
+pub fn default_field_for_category(category: &str) -> &'static str {
+    match category {
+        "API_CREDENTIAL" => "credential",
+        "SSH_KEY" => "private key",
+        _ => "password",
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Vault {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VaultDetails {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, rename = "type")]
+    pub vault_type: Option<String>,
+    #[serde(default)]
+    pub items: Option<u64>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+    /// The signed-in user's permissions, when `op vault user list` allows it.
+    #[serde(skip)]
+    pub permissions: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VaultUser {
+    pub id: String,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[allow(clippy::struct_field_names)]
+pub struct Account {
+    pub email: String,
+    pub user_uuid: String,
+    pub account_uuid: String,
+}
+
+/// What `op whoami --format json` says about a signed-in account.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WhoAmI {
+    pub url: String,
+    pub email: String,
+    #[serde(default)]
+    pub user_type: Option<String>,
+}
+
+impl WhoAmI {
+    pub fn unlock_method(&self, has_session_token: bool) -> UnlockMethod {
+        if self.user_type.as_deref() == Some("SERVICE_ACCOUNT") {
+            UnlockMethod::ServiceAccount
+        } else if has_session_token {
+            UnlockMethod::SessionToken
+        } else {
+            UnlockMethod::AppIntegration
+        }
+    }
+}
+
+/// How `op` is unlocked for an account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnlockMethod {
+    /// `OP_SERVICE_ACCOUNT_TOKEN`.
+    ServiceAccount,
+    /// An `OP_SESSION_*` token from a password sign-in.
+    SessionToken,
+    /// No token: the 1Password app unlocks `op`, usually with biometrics.
+    AppIntegration,
+}
+
+impl UnlockMethod {
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::ServiceAccount => "service account",
+            Self::SessionToken => "session token",
+            Self::AppIntegration => "biometric unlock",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ItemUrl {
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub primary: bool,
+    pub href: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VaultItem {
+    pub id: String,
+    pub title: String,
+    pub category: String,
+    #[serde(default)]
+    pub additional_information: Option<String>,
+    #[serde(default)]
+    #[allow(dead_code)]
+    pub urls: Vec<ItemUrl>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+impl VaultItem {
+    /// How long ago the item was last edited, when `op` reported it.
+    pub fn age(&self, now: SystemTime) -> Option<Duration> {
+        let updated = crate::timestamp::parse_rfc3339(self.updated_at.as_deref()?)?;
+        Some(now.duration_since(updated).unwrap_or_default())
+    }
+}
+
+/// `API_CREDENTIAL` as 1Password shows it: `API Credential`.
+pub fn category_label(category: &str) -> String {
+    category
+        .split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| match word {
+            "API" | "SSH" => word.to_string(),
+            _ => {
+                let lower = word.to_ascii_lowercase();
+                let mut chars = lower.chars();
+                chars.next().map_or_else(String::new, |first| {
+                    first.to_ascii_uppercase().to_string() + chars.as_str()
+                })
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VaultItemDetails {
+    pub id: String,
+    pub title: String,
+    #[allow(dead_code)]
+    pub category: String,
+    #[serde(default)]
+    pub fields: Vec<ItemField>,
+    #[serde(default)]
+    pub urls: Vec<ItemUrl>,
+}
+
+/// The built-in notes field, shown in its own block rather than as a field.
+const NOTES_FIELD: &str = "notesPlain";
+
+impl VaultItemDetails {
+    /// Fields in the order the Details panel lists them: grouped by section,
+    /// sections in the order they first appear, fields without a section
+    /// first. Notes are left out.
+    pub fn detail_fields(&self) -> Vec<&ItemField> {
+        let mut section_order: Vec<Option<&str>> = vec![None];
+        for field in &self.fields {
+            let section = field.section_id();
+            if !section_order.contains(&section) {
+                section_order.push(section);
+            }
+        }
+
+        let mut fields: Vec<&ItemField> = self
+            .fields
+            .iter()
+            .filter(|f| f.label != NOTES_FIELD)
+            .collect();
+        fields.sort_by_key(|field| {
+            section_order
+                .iter()
+                .position(|section| *section == field.section_id())
+        });
+        fields
+    }
+
+    pub fn notes(&self) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|f| f.label == NOTES_FIELD)
+            .and_then(|f| f.value.as_deref())
+            .filter(|notes| !notes.trim().is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ItemField {
+    pub label: String,
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(rename = "type")]
+    pub field_type: String,
+    pub reference: String,
+    #[serde(default)]
+    pub section: Option<FieldSection>,
+    /// The current code of an OTP field, as of when the item was fetched.
+    #[serde(default)]
+    pub totp: Option<String>,
+}
+
+impl ItemField {
+    pub fn is_otp(&self) -> bool {
+        self.field_type == "OTP"
+    }
+
+    fn section_id(&self) -> Option<&str> {
+        self.section.as_ref().map(|section| section.id.as_str())
+    }
+
+    /// The heading of the field's section, when it has a visible one.
+    pub fn section_label(&self) -> Option<&str> {
+        self.section
+            .as_ref()
+            .and_then(|section| section.label.as_deref())
+            .filter(|label| !label.is_empty())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldSection {
+    pub id: String,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// The code to show for an item's OTP field, if it has one. The code that
+/// came with the details is used until its window passes.
+fn totp_for_details(details: &VaultItemDetails, now: SystemTime) -> Option<TotpCode> {
+    let field = details.fields.iter().find(|field| field.is_otp())?;
+    let period = field
+        .value
+        .as_deref()
+        .map_or(crate::totp::DEFAULT_PERIOD, crate::totp::period_from_uri);
+    let code = field.totp.clone();
+    Some(TotpCode {
+        item_id: details.id.clone(),
+        window: code.as_ref().map(|_| crate::totp::window(now, period)),
+        code,
+        period,
+        refreshing: false,
+    })
+}
+
+fn reference_targets_item(reference: &str, vault: &Vault, item_id: &str, item_title: &str) -> bool {
+    let Some(path) = reference.strip_prefix("op://") else {
+        return false;
+    };
+    let mut segments = path.split('/');
+    let (Some(vault_segment), Some(item_segment)) = (segments.next(), segments.next()) else {
+        return false;
+    };
+
+    (vault_segment == vault.id || vault_segment == vault.name)
+        && (item_segment == item_id || item_segment == item_title)
+}
+
+pub fn op_read_command(reference: &str, account_id: &str) -> String {
+    format!(
+        "op read {} --account {}",
+        shell_quote(reference),
+        shell_quote(account_id)
+    )
+}
+
+pub fn op_item_get_command(item_id: &str, vault_id: &str, account_id: &str) -> String {
+    format!(
+        "op item get {} --vault {} --account {}",
+        shell_quote(item_id),
+        shell_quote(vault_id),
+        shell_quote(account_id)
+    )
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// How much of the left column the command log pane takes; `l` cycles it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CommandLogSize {
+    /// Just the latest entry.
+    Collapsed,
+    #[default]
+    Normal,
+    Tall,
+}
+
+impl CommandLogSize {
+    /// Rows including the border.
+    pub const fn height(self) -> u16 {
+        match self {
+            Self::Collapsed => 3,
+            Self::Normal => 8,
+            Self::Tall => 16,
+        }
+    }
+
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Collapsed => Self::Normal,
+            Self::Normal => Self::Tall,
+            Self::Tall => Self::Collapsed,
+        }
+    }
+}
+
+/// What the breadcrumb bar says about the selected account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigninStatus {
+    Loading,
+    SignedIn,
+    /// `op` calls for the account failed and are paused for this long.
+    CoolingDown(Duration),
+    /// No account is listed, usually because `op` isn't signed in.
+    SignedOut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusedPanel {
+    AccountList,
+    VaultList,
+    VaultItemList,
+    VaultItemDetail,
+    VarsList,
+    RecentList,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_vault_item(id: &str, title: &str) -> VaultItem {
+        VaultItem {
+            id: id.to_string(),
+            title: title.to_string(),
+            category: "LOGIN".to_string(),
+            additional_information: None,
+            urls: vec![],
+            updated_at: None,
+        }
+    }
+
+    fn make_item_field(label: &str, reference: &str) -> ItemField {
+        ItemField {
+            label: label.to_string(),
+            value: Some("secret-value".to_string()),
+            field_type: "CONCEALED".to_string(),
+            reference: reference.to_string(),
+            section: None,
+            totp: None,
+        }
+    }
+
+    mod update_filtered_items {
+        use super::*;
+
+        #[test]
+        fn empty_query_returns_all_items() {
+            let mut app = App::new();
+            app.vault_items = vec![
+                make_vault_item("1", "GitHub Token"),
+                make_vault_item("2", "AWS Secret"),
+                make_vault_item("3", "Database Password"),
+            ];
+            app.search_query = String::new();
+
+            app.update_filtered_items();
+
+            assert_eq!(app.filtered_item_indices, vec![0, 1, 2]);
+        }
+
+        #[test]
+        fn title_match_positions_follow_the_query() {
+            let mut app = App::new();
+            assert!(app.title_match_positions("GitHub").is_empty());
+
+            app.search_query = "gh".to_string();
+
+            assert_eq!(app.title_match_positions("GitHub"), [0, 3]);
+            assert!(app.title_match_positions("AWS").is_empty());
+        }
+
+        #[test]
+        fn filters_by_fuzzy_match() {
+            let mut app = App::new();
+            app.vault_items = vec![
+                make_vault_item("1", "GitHub Token"),
+                make_vault_item("2", "AWS Secret"),
+                make_vault_item("3", "GitLab Token"),
+            ];
+            app.search_query = "git".to_string();
+
+            app.update_filtered_items();
+
+            assert_eq!(app.filtered_item_indices.len(), 2);
+            assert!(app.filtered_item_indices.contains(&0)); // GitHub
+            assert!(app.filtered_item_indices.contains(&2)); // GitLab
+        }
+
+        #[test]
+        fn matches_details_beyond_the_title() {
+            let mut app = App::new();
+            let mut mailer = make_vault_item("1", "Mailer");
+            mailer.additional_information = Some("ops@example.com".to_string());
+            app.vault_items = vec![
+                make_vault_item("2", "Sendgrid"),
+                mailer,
+                make_vault_item("3", "Billing"),
+            ];
+            app.item_field_labels
+                .insert("3".to_string(), vec!["SENDGRID_KEY".to_string()]);
+
+            app.search_query = "sendgrid".to_string();
+            app.update_filtered_items();
+            assert_eq!(app.filtered_item_indices, vec![0, 2]);
+
+            app.search_query = "ops@".to_string();
+            app.update_filtered_items();
+            assert_eq!(app.filtered_item_indices, vec![1]);
+        }
+
+        #[test]
+        fn no_matches_returns_empty() {
+            let mut app = App::new();
+            app.vault_items = vec![
+                make_vault_item("1", "GitHub Token"),
+                make_vault_item("2", "AWS Secret"),
+            ];
+            app.search_query = "zzzzz".to_string();
+
+            app.update_filtered_items();
+
+            assert!(app.filtered_item_indices.is_empty());
+            assert!(app.vault_item_list_state.selected().is_none());
+        }
+
+        #[test]
+        fn selects_first_item_when_results_exist() {
+            let mut app = App::new();
+            app.vault_items = vec![
+                make_vault_item("1", "GitHub Token"),
+                make_vault_item("2", "AWS Secret"),
+            ];
+            app.search_query = String::new();
+
+            app.update_filtered_items();
+
+            assert_eq!(app.vault_item_list_state.selected(), Some(0));
+        }
+
+        #[test]
+        fn clears_selected_item_details() {
+            let mut app = App::new();
+            app.vault_items = vec![make_vault_item("1", "GitHub Token")];
+            app.selected_vault_item_idx = Some(0);
+            app.selected_item_details = Some(VaultItemDetails {
+                id: "1".to_string(),
+                title: "GitHub Token".to_string(),
+                category: "LOGIN".to_string(),
+                urls: vec![],
+                fields: vec![],
+            });
+
+            app.update_filtered_items();
+
+            assert!(app.selected_vault_item_idx.is_none());
+            assert!(app.selected_item_details.is_none());
+        }
+
+        #[test]
+        fn empty_vault_items_returns_empty() {
+            let mut app = App::new();
+            app.vault_items = vec![];
+            app.search_query = "test".to_string();
+
+            app.update_filtered_items();
+
+            assert!(app.filtered_item_indices.is_empty());
+        }
+    }
+
+    mod clear_search {
+        use super::*;
+
+        #[test]
+        fn clears_query_and_deactivates() {
+            let mut app = App::new();
+            app.search_query = "some search".to_string();
+            app.search_active = true;
+
+            app.clear_search();
+
+            assert!(app.search_query.is_empty());
+            assert!(!app.search_active);
+        }
+
+        #[test]
+        fn resets_filtered_items_to_all() {
+            let mut app = App::new();
+            app.vault_items = vec![
+                make_vault_item("1", "GitHub Token"),
+                make_vault_item("2", "AWS Secret"),
+            ];
+            app.search_query = "git".to_string();
+            app.update_filtered_items();
+
+            app.clear_search();
+
+            assert_eq!(app.filtered_item_indices, vec![0, 1]);
+        }
+    }
+
+    mod open_modal {
+        use super::*;
+
+        #[test]
+        fn sets_modal_state() {
+            let mut app = App::new();
+            let reference = "op://vault/item/field".to_string();
+
+            app.open_modal(reference.clone());
+
+            let Modal::EnvVar {
+                env_var_name,
+                field_reference,
+                ..
+            } = app.modal.as_ref().expect("modal should be set")
+            else {
+                panic!("expected EnvVar modal");
+            };
+
+            assert!(env_var_name.is_empty());
+            assert_eq!(field_reference, &reference);
+        }
+
+        #[test]
+        fn prefills_a_name_for_a_details_field() {
+            let mut app = App::new();
+            app.selected_item_details = Some(VaultItemDetails {
+                id: "item".to_string(),
+                title: "GitHub Token".to_string(),
+                category: "API_CREDENTIAL".to_string(),
+                fields: vec![ItemField {
+                    label: "credential".to_string(),
+                    value: None,
+                    field_type: "CONCEALED".to_string(),
+                    reference: "op://Private/GitHub Token/credential".to_string(),
+                    section: None,
+                    totp: None,
+                }],
+                urls: Vec::new(),
+            });
+
+            app.open_modal_for_field(0);
+
+            let Some(Modal::EnvVar {
+                env_var_name,
+                field_reference,
+                ..
+            }) = &app.modal
+            else {
+                panic!("expected EnvVar modal");
+            };
+            assert_eq!(env_var_name, "GITHUB_TOKEN");
+            assert_eq!(field_reference, "op://Private/GitHub Token/credential");
+        }
+
+        #[test]
+        fn asks_before_overwriting_another_mapping() {
+            let mut app = App::new();
+            let mut config = OpLoadConfig::default();
+            config.inject_vars.insert(
+                "TOKEN".to_string(),
+                InjectVarConfig {
+                    account_id: "acct".to_string(),
+                    op_reference: "op://Private/Old/credential".to_string(),
+                    prefix: None,
+                },
+            );
+            app.config = Some(config);
+            app.open_modal("op://Private/New/credential".to_string());
+
+            assert!(!app.confirm_var_collision("OTHER", "acct", "op://Private/New/credential"));
+            assert!(!app.confirm_var_collision("TOKEN", "acct", "op://Private/Old/credential"));
+            assert!(app.confirm_var_collision("TOKEN", "acct", "op://Private/New/credential"));
+            let Some(Modal::VarCollision { existing, .. }) = &app.modal else {
+                panic!("expected VarCollision modal");
+            };
+            assert_eq!(existing.op_reference, "op://Private/Old/credential");
+
+            app.rename_colliding_var();
+
+            assert!(matches!(app.modal, Some(Modal::EnvVar { .. })));
+        }
+
+        #[test]
+        fn cycles_through_configured_prefixes() {
+            let mut app = App::new();
+            app.config = Some(OpLoadConfig {
+                var_prefixes: vec!["PROD_".to_string(), "DEV_".to_string()],
+                ..Default::default()
+            });
+            app.open_modal("op://vault/item/field".to_string());
+
+            let mut seen = Vec::new();
+            for _ in 0..3 {
+                app.cycle_env_var_prefix();
+                seen.push(app.modal_env_var_prefix().map(str::to_string));
+            }
+
+            assert_eq!(
+                seen,
+                [Some("PROD_".to_string()), Some("DEV_".to_string()), None]
+            );
+            assert_eq!(
+                InjectVarConfig {
+                    account_id: "acct".to_string(),
+                    op_reference: "op://vault/item/field".to_string(),
+                    prefix: Some("PROD_".to_string()),
+                }
+                .exported_name("TOKEN"),
+                "PROD_TOKEN"
+            );
+        }
+
+        #[test]
+        fn clears_previous_env_var_name() {
+            let mut app = App::new();
+            app.modal = Some(Modal::EnvVar {
+                env_var_name: "OLD_VAR".to_string(),
+                field_reference: "op://vault/item/old".to_string(),
+                editing: None,
+                prefix: None,
+            });
+
+            app.open_modal("op://vault/item/field".to_string());
+
+            let Modal::EnvVar { env_var_name, .. } =
+                app.modal.as_ref().expect("modal should be set")
+            else {
+                panic!("expected EnvVar modal");
+            };
+            assert!(env_var_name.is_empty());
+        }
+    }
+
+    mod close_modal {
+        use super::*;
+
+        #[test]
+        fn resets_all_modal_state() {
+            let mut app = App::new();
+            app.modal = Some(Modal::EnvVar {
+                env_var_name: "MY_VAR".to_string(),
+                field_reference: "op://vault/item/field".to_string(),
+                editing: None,
+                prefix: None,
+            });
+            app.error_message = Some("some error".to_string());
+
+            app.close_modal();
+
+            assert!(app.modal.is_none());
+            assert!(app.error_message.is_none());
+        }
+    }
+
+    mod modal_selected_field {
+        use super::*;
+
+        #[test]
+        fn returns_matching_field() {
+            let mut app = App::new();
+            let reference = "op://vault/item/password".to_string();
+            app.selected_item_details = Some(VaultItemDetails {
+                id: "1".to_string(),
+                title: "Test Item".to_string(),
+                category: "LOGIN".to_string(),
+                urls: vec![],
+                fields: vec![
+                    make_item_field("username", "op://vault/item/username"),
+                    make_item_field("password", "op://vault/item/password"),
+                ],
+            });
+            app.modal = Some(Modal::EnvVar {
+                env_var_name: String::new(),
+                field_reference: reference,
+                editing: None,
+                prefix: None,
+            });
+
+            let field = app.modal_selected_field();
+
+            assert!(field.is_some());
+            assert_eq!(field.unwrap().label, "password");
+        }
+
+        #[test]
+        fn returns_none_when_no_details() {
+            let mut app = App::new();
+            app.selected_item_details = None;
+            app.modal = Some(Modal::EnvVar {
+                env_var_name: String::new(),
+                field_reference: "op://vault/item/field".to_string(),
+                editing: None,
+                prefix: None,
+            });
+
+            assert!(app.modal_selected_field().is_none());
+        }
+
+        #[test]
+        fn returns_none_when_no_reference() {
+            let mut app = App::new();
+            app.selected_item_details = Some(VaultItemDetails {
+                id: "1".to_string(),
+                title: "Test Item".to_string(),
+                category: "LOGIN".to_string(),
+                urls: vec![],
+                fields: vec![make_item_field("password", "op://vault/item/password")],
+            });
+            app.modal = None;
+
+            assert!(app.modal_selected_field().is_none());
+        }
+
+        #[test]
+        fn returns_none_when_reference_not_found() {
+            let mut app = App::new();
+            app.selected_item_details = Some(VaultItemDetails {
+                id: "1".to_string(),
+                title: "Test Item".to_string(),
+                category: "LOGIN".to_string(),
+                urls: vec![],
+                fields: vec![make_item_field("password", "op://vault/item/password")],
+            });
+            app.modal = Some(Modal::EnvVar {
+                env_var_name: String::new(),
+                field_reference: "op://vault/item/nonexistent".to_string(),
+                editing: None,
+                prefix: None,
+            });
+
+            assert!(app.modal_selected_field().is_none());
+        }
+    }
+
+    mod vault_create_modal {
+        use super::*;
+
+        #[test]
+        fn input_follows_active_field() {
+            let mut app = App::new();
+            app.open_vault_create_modal();
+
+            app.modal_vault_create_input_mut()
+                .unwrap()
+                .push_str("Infra");
+            app.toggle_vault_create_field();
+            app.modal_vault_create_input_mut()
+                .unwrap()
+                .push_str("Shared infra secrets");
+
+            let Some(Modal::VaultCreate {
+                name,
+                description,
+                active_field,
+            }) = app.modal.as_ref()
+            else {
+                panic!("expected VaultCreate modal");
+            };
+            assert_eq!(name, "Infra");
+            assert_eq!(description, "Shared infra secrets");
+            assert_eq!(*active_field, VaultCreateField::Description);
+        }
+
+        #[test]
+        fn input_is_none_for_other_modals() {
+            let mut app = App::new();
+            app.open_modal("op://vault/item/field".to_string());
+
+            assert!(app.modal_vault_create_input_mut().is_none());
+        }
+    }
+
+    mod managed_vars_referencing_item {
+        use super::*;
+
+        fn app_with_vars(vars: &[(&str, &str)]) -> App {
+            let mut app = App::new();
+            app.config = Some(OpLoadConfig {
+                inject_vars: vars
+                    .iter()
+                    .map(|(name, reference)| {
+                        (
+                            (*name).to_string(),
+                            InjectVarConfig {
+                                account_id: "acct".to_string(),
+                                op_reference: (*reference).to_string(),
+                                prefix: None,
+                            },
+                        )
+                    })
+                    .collect(),
+                ..Default::default()
+            });
+            app.vaults = vec![Vault {
+                id: "v1".to_string(),
+                name: "Personal".to_string(),
+            }];
+            app.selected_vault_idx = Some(0);
+            app
+        }
+
+        #[test]
+        fn matches_by_id_or_name() {
+            let app = app_with_vars(&[
+                ("BY_ID", "op://v1/item-1/password"),
+                ("BY_NAME", "op://Personal/GitHub Token/credential"),
+                ("OTHER_ITEM", "op://Personal/AWS Secret/password"),
+                ("OTHER_VAULT", "op://Work/GitHub Token/credential"),
+            ]);
+
+            let vars =
+                app.managed_vars_referencing_item(&make_vault_item("item-1", "GitHub Token"));
+
+            assert_eq!(vars, vec!["BY_ID".to_string(), "BY_NAME".to_string()]);
+        }
+
+        #[test]
+        fn returns_empty_without_selected_vault() {
+            let mut app = app_with_vars(&[("BY_ID", "op://v1/item-1/password")]);
+            app.selected_vault_idx = None;
+
+            let vars =
+                app.managed_vars_referencing_item(&make_vault_item("item-1", "GitHub Token"));
+
+            assert!(vars.is_empty());
+        }
+    }
+
+    mod selected_vault {
+        use super::*;
+
+        #[test]
+        fn returns_vault_at_index() {
+            let mut app = App::new();
+            app.vaults = vec![
+                Vault {
+                    id: "v1".to_string(),
+                    name: "Personal".to_string(),
+                },
+                Vault {
+                    id: "v2".to_string(),
+                    name: "Work".to_string(),
+                },
+            ];
+            app.selected_vault_idx = Some(1);
+
+            let vault = app.selected_vault();
+
+            assert!(vault.is_some());
+            assert_eq!(vault.unwrap().name, "Work");
+        }
+
+        #[test]
+        fn returns_none_when_no_selection() {
+            let mut app = App::new();
+            app.vaults = vec![Vault {
+                id: "v1".to_string(),
+                name: "Personal".to_string(),
+            }];
+            app.selected_vault_idx = None;
+
+            assert!(app.selected_vault().is_none());
+        }
+
+        #[test]
+        fn returns_none_when_index_out_of_bounds() {
+            let mut app = App::new();
+            app.vaults = vec![Vault {
+                id: "v1".to_string(),
+                name: "Personal".to_string(),
+            }];
+            app.selected_vault_idx = Some(5);
+
+            assert!(app.selected_vault().is_none());
+        }
+    }
+
+    mod op_command_snippets {
+        use super::*;
+
+        #[test]
+        fn op_read_quotes_reference_and_account() {
+            assert_eq!(
+                op_read_command("op://Personal/GitHub Token/credential", "acct-1"),
+                "op read 'op://Personal/GitHub Token/credential' --account 'acct-1'"
+            );
+        }
+
+        #[test]
+        fn op_item_get_includes_vault_and_account() {
+            assert_eq!(
+                op_item_get_command("item-1", "v1", "acct-1"),
+                "op item get 'item-1' --vault 'v1' --account 'acct-1'"
+            );
+        }
+
+        #[test]
+        fn escapes_single_quotes() {
+            assert_eq!(
+                op_read_command("op://Bob's/item/field", "a"),
+                "op read 'op://Bob'\\''s/item/field' --account 'a'"
+            );
+        }
+    }
+
+    mod notify_if_slow {
+        use super::*;
+
+        fn app_with_notifications(notifications: NotificationConfig) -> App {
+            let mut app = App::new();
+            app.config = Some(OpLoadConfig {
+                notifications,
+                ..Default::default()
+            });
+            app
+        }
+
+        #[test]
+        fn notifies_for_slow_calls() {
+            let mut app = app_with_notifications(NotificationConfig {
+                bell: true,
+                flash: true,
+                min_duration: Some("2s".to_string()),
+                desktop: false,
+            });
+
+            app.notify_if_slow(Duration::from_secs(1));
+            assert!(!app.bell_pending && !app.flash_pending);
+
+            app.notify_if_slow(Duration::from_secs(2));
+            assert!(app.bell_pending && app.flash_pending);
+        }
+
+        #[test]
+        fn stays_quiet_when_disabled() {
+            let mut app = app_with_notifications(NotificationConfig::default());
+
+            app.notify_if_slow(Duration::from_secs(60));
+
+            assert!(!app.bell_pending && !app.flash_pending);
+        }
+
+        #[test]
+        fn uses_default_threshold() {
+            let mut app = app_with_notifications(NotificationConfig {
+                flash: true,
+                ..Default::default()
+            });
+
+            app.notify_if_slow(DEFAULT_NOTIFY_MIN_DURATION);
+
+            assert!(app.flash_pending);
+            assert!(!app.bell_pending);
+        }
+    }
+
+    mod listing_refresh {
+        use super::*;
+        use crate::listing::ListingRefresh;
+
+        fn vault(id: &str) -> Vault {
+            Vault {
+                id: id.to_string(),
+                name: id.to_string(),
+            }
+        }
+
+        #[test]
+        fn refreshed_vaults_keep_selection_by_id() {
+            let mut app = App::new();
+            app.vaults = vec![vault("a"), vault("b")];
+            app.selected_vault_idx = Some(1);
+            app.vault_list_state.select(Some(1));
+
+            app.listing_tx
+                .send(ListingRefresh {
+                    request: ListingRequest::Vaults { account_id: None },
+                    result: Ok(br#"[{"id":"new","name":"new"},{"id":"b","name":"b"}]"#.to_vec()),
+                    elapsed: Duration::ZERO,
+                })
+                .unwrap();
+            app.apply_listing_refreshes();
+
+            assert_eq!(app.vaults.len(), 2);
+            assert_eq!(app.selected_vault_idx, Some(1));
+            assert_eq!(app.vault_list_state.selected(), Some(1));
+        }
+
+        #[test]
+        fn refresh_for_another_account_is_ignored() {
+            let mut app = App::new();
+            app.vaults = vec![vault("a")];
+
+            app.listing_tx
+                .send(ListingRefresh {
+                    request: ListingRequest::Vaults {
+                        account_id: Some("other".to_string()),
+                    },
+                    result: Ok(b"[]".to_vec()),
+                    elapsed: Duration::ZERO,
+                })
+                .unwrap();
+            app.apply_listing_refreshes();
+
+            assert_eq!(app.vaults.len(), 1);
+        }
+
+        #[test]
+        fn failed_refresh_is_logged() {
+            let mut app = App::new();
+
+            app.listing_tx
+                .send(ListingRefresh {
+                    request: ListingRequest::Accounts,
+                    result: Err("not signed in".to_string()),
+                    elapsed: Duration::ZERO,
+                })
+                .unwrap();
+            app.apply_listing_refreshes();
+
+            assert!(app.accounts.is_empty());
+            assert_eq!(app.command_log.recent(10).len(), 1);
+        }
+
+        #[test]
+        fn loaded_items_replace_loading_state() {
+            let mut app = App::new();
+            app.accounts = vec![Account {
+                email: "a@example.com".to_string(),
+                user_uuid: "user".to_string(),
+                account_uuid: "acct".to_string(),
+            }];
+            app.selected_account_idx = Some(0);
+            app.vaults = vec![vault("v")];
+            app.selected_vault_idx = Some(0);
+            let request = ListingRequest::Items {
+                account_id: "acct".to_string(),
+                vault_id: "v".to_string(),
+            };
+            app.loading_listings.push(request.clone());
+            assert!(app.vault_items_loading());
+
+            app.listing_tx
+                .send(ListingRefresh {
+                    request,
+                    result: Ok(br#"[{"id":"i","title":"Item","category":"LOGIN"}]"#.to_vec()),
+                    elapsed: Duration::ZERO,
+                })
+                .unwrap();
+            app.apply_background_results();
+
+            assert!(!app.vault_items_loading());
+            assert_eq!(app.vault_items.len(), 1);
+            assert_eq!(app.vault_item_list_state.selected(), Some(0));
+        }
+
+        #[test]
+        fn failed_load_sets_error() {
+            let mut app = App::new();
+            app.loading_listings.push(ListingRequest::Accounts);
+
+            app.listing_tx
+                .send(ListingRefresh {
+                    request: ListingRequest::Accounts,
+                    result: Err("not signed in".to_string()),
+                    elapsed: Duration::ZERO,
+                })
+                .unwrap();
+            app.apply_background_results();
+
+            assert!(!app.accounts_loading());
+            assert!(app.error_message.unwrap().contains("not signed in"));
+        }
+
+        #[test]
+        fn failing_account_cools_down_until_selected() {
+            let mut app = App::new();
+            let request = ListingRequest::Items {
+                account_id: "acct".to_string(),
+                vault_id: "vault".to_string(),
+            };
+            app.loading_listings.push(request.clone());
+
+            app.listing_tx
+                .send(ListingRefresh {
+                    request: request.clone(),
+                    result: Err("network down".to_string()),
+                    elapsed: Duration::ZERO,
+                })
+                .unwrap();
+            app.apply_background_results();
+            assert!(
+                app.account_backoff
+                    .remaining("acct", SystemTime::now())
+                    .is_some()
+            );
+
+            app.load_listing(request.clone()).unwrap();
+            assert!(!app.loading_listings.contains(&request));
+            assert!(app.error_message.unwrap().contains("retrying in"));
+        }
+
+        #[test]
+        fn details_for_another_item_are_ignored() {
+            let mut app = App::new();
+            app.item_details_loading = Some("current".to_string());
+
+            app.details_tx
+                .send(ItemDetailsLoad {
+                    account_id: "acct".to_string(),
+                    item_id: "previous".to_string(),
+                    result: Ok(
+                        br#"{"id":"previous","title":"t","category":"LOGIN","fields":[]}"#.to_vec(),
+                    ),
+                    elapsed: Duration::ZERO,
+                })
+                .unwrap();
+            app.apply_background_results();
+
+            assert!(app.selected_item_details.is_none());
+            assert_eq!(app.item_details_loading.as_deref(), Some("current"));
+        }
+    }
+
+    mod left_pane_percent {
+        use super::*;
+
+        #[test]
+        fn defaults_and_clamps_the_split() {
+            let mut tui = TuiConfig::default();
+            assert_eq!(tui.left_pane_percent(), DEFAULT_LEFT_PANE_PERCENT);
+
+            tui.left_pane_percent = Some(45);
+            assert_eq!(tui.left_pane_percent(), 45);
+
+            tui.left_pane_percent = Some(95);
+            assert_eq!(tui.left_pane_percent(), 60);
+        }
+    }
+
+    mod breadcrumb {
+        use super::*;
+
+        #[test]
+        fn shows_selected_context_and_signin_status() {
+            let mut app = App::new();
+            assert!(app.breadcrumb().is_empty());
+            assert_eq!(
+                app.signin_status(SystemTime::now()),
+                SigninStatus::SignedOut
+            );
+
+            app.accounts = vec![Account {
+                email: "a@example.com".to_string(),
+                user_uuid: "user".to_string(),
+                account_uuid: "acct".to_string(),
+            }];
+            app.selected_account_idx = Some(0);
+            app.vaults = vec![Vault {
+                id: "v".to_string(),
+                name: "Private".to_string(),
+            }];
+            app.selected_vault_idx = Some(0);
+            app.vault_items = vec![make_vault_item("i", "GitHub")];
+            app.update_filtered_items();
+            app.selected_vault_item_idx = Some(0);
+
+            assert_eq!(app.breadcrumb(), ["a@example.com", "Private", "GitHub"]);
+            assert_eq!(app.signin_status(SystemTime::now()), SigninStatus::SignedIn);
+
+            let now = SystemTime::now();
+            app.account_backoff.record_failure("acct", now);
+            assert!(matches!(
+                app.signin_status(now),
+                SigninStatus::CoolingDown(_)
+            ));
+        }
+    }
+
+    mod refresh {
+        use super::*;
+
+        #[test]
+        fn unloaded_list_refreshes_like_a_first_load() {
+            let mut app = App::new();
+            app.focused_panel = FocusedPanel::AccountList;
+
+            app.refresh_focused_listing().unwrap();
+
+            assert!(app.accounts_loading());
+        }
+
+        #[test]
+        fn refresh_ends_the_account_cool_down() {
+            let mut app = App::new();
+            app.accounts = vec![Account {
+                email: "a@example.com".to_string(),
+                user_uuid: "user".to_string(),
+                account_uuid: "acct".to_string(),
+            }];
+            app.selected_account_idx = Some(0);
+            app.account_backoff
+                .record_failure("acct", SystemTime::now());
+            app.focused_panel = FocusedPanel::VaultList;
+
+            app.refresh_focused_listing().unwrap();
+
+            assert_eq!(app.account_backoff.failure_count("acct"), 0);
+            assert!(app.vaults_loading());
+        }
+
+        #[test]
+        fn items_need_a_selected_vault() {
+            let mut app = App::new();
+            app.focused_panel = FocusedPanel::VaultItemList;
+
+            assert!(app.refresh_focused_listing().is_err());
+        }
+    }
+
+    mod signin {
+        use super::*;
+
+        fn selected_app() -> App {
+            let mut app = App::new();
+            app.accounts = vec![Account {
+                email: "a@example.com".to_string(),
+                user_uuid: "user".to_string(),
+                account_uuid: "acct".to_string(),
+            }];
+            app.selected_account_idx = Some(0);
+            app.vaults = vec![Vault {
+                id: "v".to_string(),
+                name: "Private".to_string(),
+            }];
+            app.selected_vault_idx = Some(0);
+            app
+        }
+
+        #[test]
+        fn expired_session_prompts_and_retries_after_signin() {
+            let mut app = selected_app();
+            app.item_details_loading = Some("i".to_string());
+
+            app.details_tx
+                .send(ItemDetailsLoad {
+                    account_id: "acct".to_string(),
+                    item_id: "i".to_string(),
+                    result: Err("[ERROR] You are not currently signed in.".to_string()),
+                    elapsed: Duration::ZERO,
+                })
+                .unwrap();
+            app.apply_background_results();
+
+            assert_eq!(app.signin_account().as_deref(), Some("acct"));
+            assert!(app.account_backoff.failure_count("acct") > 0);
+
+            app.finish_signin(Ok(()));
+
+            assert!(app.modal.is_none());
+            assert_eq!(app.account_backoff.failure_count("acct"), 0);
+            assert_eq!(app.item_details_loading.as_deref(), Some("i"));
+        }
+
+        #[test]
+        fn other_failures_do_not_prompt() {
+            let mut app = selected_app();
+            app.item_details_loading = Some("i".to_string());
+
+            app.details_tx
+                .send(ItemDetailsLoad {
+                    account_id: "acct".to_string(),
+                    item_id: "i".to_string(),
+                    result: Err("[ERROR] item not found".to_string()),
+                    elapsed: Duration::ZERO,
+                })
+                .unwrap();
+            app.apply_background_results();
+
+            assert!(app.modal.is_none());
+        }
+
+        #[test]
+        fn prompt_returns_to_the_open_modal() {
+            let mut app = selected_app();
+            app.open_help();
+            app.prompt_signin(Some("acct".to_string()), SigninRetry::Pending);
+            app.set_signin_retry(Action::CreateItem);
+
+            assert!(matches!(
+                app.modal,
+                Some(Modal::SignIn {
+                    retry: SigninRetry::Action(_),
+                    ..
+                })
+            ));
+
+            app.cancel_signin();
+            assert!(matches!(app.modal, Some(Modal::Help { .. })));
+
+            app.prompt_signin(None, SigninRetry::Pending);
+            app.finish_signin(Err(anyhow::anyhow!("`op signin` exited with 1")));
+            assert!(matches!(app.modal, Some(Modal::Help { .. })));
+            assert!(app.error_message.is_some());
+        }
+    }
+
+    mod whoami {
+        use super::*;
+
+        #[test]
+        fn shows_who_is_signed_in_to_the_selected_account() {
+            let mut app = App::new();
+            app.accounts = vec![Account {
+                email: "me@example.com".to_string(),
+                user_uuid: "user".to_string(),
+                account_uuid: "acct".to_string(),
+            }];
+            app.selected_account_idx = Some(0);
+
+            app.whoami_tx
+                .send(WhoAmILoad {
+                    account_id: "acct".to_string(),
+                    result: Ok(br#"{"url":"my.1password.com","email":"me@example.com","user_uuid":"user","account_uuid":"acct","user_type":"HUMAN"}"#.to_vec()),
+                })
+                .unwrap();
+            app.apply_background_results();
+
+            let whoami = app.selected_whoami().unwrap();
+            assert_eq!(whoami.url, "my.1password.com");
+            assert_eq!(whoami.unlock_method(false), UnlockMethod::AppIntegration);
+            assert_eq!(whoami.unlock_method(true), UnlockMethod::SessionToken);
+
+            app.whoami_tx
+                .send(WhoAmILoad {
+                    account_id: "acct".to_string(),
+                    result: Err("[ERROR] You are not currently signed in".to_string()),
+                })
+                .unwrap();
+            app.apply_background_results();
+
+            assert!(app.selected_whoami().is_none());
+        }
+    }
+
+    mod op_client {
+        use super::*;
+        use crate::op_client::MockOpClient;
+        use std::sync::Arc;
+
+        fn app_with_token(op: MockOpClient) -> App {
+            let mut app = App::with_op_client(Arc::new(op));
+            let mut config = OpLoadConfig::default();
+            config.inject_vars.insert(
+                "TOKEN".to_string(),
+                InjectVarConfig {
+                    account_id: "acct".to_string(),
+                    op_reference: "op://v/i/f".to_string(),
+                    prefix: None,
+                },
+            );
+            app.config = Some(config);
+            app
+        }
+
+        #[test]
+        fn test_var_logs_the_length_but_not_the_value() {
+            let op = MockOpClient::default()
+                .succeed("read op://v/i/f --account acct --no-newline", "hunter2");
+            let mut app = app_with_token(op);
+
+            app.apply(Action::TestVar {
+                var: "TOKEN".to_string(),
+            });
+
+            let logged = app.command_log.recent(1)[0].display();
+            assert!(logged.starts_with("✓ op read op://v/i/f"), "{logged}");
+            assert!(!logged.contains("hunter2"));
+        }
+
+        #[test]
+        fn old_op_fails_with_the_required_version() {
+            let mut app = app_with_token(MockOpClient::default().succeed("--version", "1.12.4"));
+            app.detect_op_version();
+
+            let err = app.load_accounts().unwrap_err().to_string();
+
+            assert!(err.starts_with("op >= 2.0 required for `--format json` listings"));
+            assert_eq!(app.loading_listing_count(), 0);
+        }
+
+        #[test]
+        fn indexed_fields_join_an_open_search() {
+            let mut app = App::new();
+            app.vault_items = vec![
+                make_vault_item("i1", "Staging"),
+                make_vault_item("i2", "Production"),
+            ];
+            app.search_active = true;
+            app.search_query = "hostname".to_string();
+            app.update_filtered_items();
+            assert!(app.filtered_item_indices.is_empty());
+
+            app.field_index_loading = Some(2);
+            app.field_index_tx
+                .send(FieldIndexLoad {
+                    account_id: "acct".to_string(),
+                    gets: vec![
+                        crate::item_batch::ItemGet {
+                            item_id: "i1".to_string(),
+                            result: Err("[ERROR] \"i1\" isn't an item".to_string()),
+                        },
+                        crate::item_batch::ItemGet {
+                            item_id: "i2".to_string(),
+                            result: Ok(br#"{"id":"i2","title":"Production","category":"SERVER","fields":[
+                                {"label":"hostname","type":"STRING","reference":"op://v/i2/hostname"}
+                            ]}"#
+                            .to_vec()),
+                        },
+                    ],
+                })
+                .unwrap();
+            app.apply_background_results();
+
+            assert_eq!(app.field_index_loading, None);
+            assert_eq!(app.filtered_item_indices, [1]);
+            assert!(
+                app.account_backoff
+                    .remaining("acct", SystemTime::now())
+                    .is_none()
+            );
+            let logged = app.command_log.recent(1)[0].display();
+            assert!(logged.contains("1/2 indexed"), "{logged}");
+        }
+
+        #[test]
+        fn missing_item_is_explained_without_pausing_the_account() {
+            let mut app = App::new();
+            app.item_details_loading = Some("item".to_string());
+            app.details_tx
+                .send(ItemDetailsLoad {
+                    account_id: "acct".to_string(),
+                    item_id: "item".to_string(),
+                    result: Err(
+                        "[ERROR] \"item\" isn't an item in the \"Private\" vault".to_string()
+                    ),
+                    elapsed: Duration::ZERO,
+                })
+                .unwrap();
+
+            app.apply_background_results();
+
+            let logged = app.command_log.recent(1)[0].display();
+            assert_eq!(
+                logged,
+                "✗ op item get item: Item not found; it may have been renamed, moved, or deleted"
+            );
+            assert!(
+                app.account_backoff
+                    .remaining("acct", SystemTime::now())
+                    .is_none()
+            );
+        }
+
+        #[test]
+        fn expired_session_from_op_prompts_to_sign_in() {
+            let op = MockOpClient::default().fail(
+                "read op://v/i/f --account acct --no-newline",
+                "[ERROR] You are not currently signed in",
+            );
+            let mut app = app_with_token(op);
+
+            app.apply(Action::TestVar {
+                var: "TOKEN".to_string(),
+            });
+
+            assert!(matches!(app.modal, Some(Modal::SignIn { .. })));
+        }
+    }
+
+    mod quick_jump {
+        use super::*;
+
+        #[test]
+        fn typed_rows_are_one_based() {
+            let mut app = App::new();
+            app.jump_input = Some("12".to_string());
+
+            assert_eq!(app.take_jump_row(), Some(11));
+            assert!(app.jump_input.is_none());
+
+            app.jump_input = Some("0".to_string());
+            assert_eq!(app.take_jump_row(), None);
+            app.jump_input = Some(String::new());
+            assert_eq!(app.take_jump_row(), None);
+        }
+
+        #[test]
+        fn row_numbers_show_only_while_jumping() {
+            let mut app = App::new();
+            assert_eq!(app.row_number(2, 120, true), "");
+
+            app.jump_input = Some(String::new());
+
+            assert_eq!(app.row_number(2, 120, true), "  3 ");
+            assert_eq!(app.row_number(2, 120, false), "");
+        }
+    }
+
+    mod quit_confirm {
+        use super::*;
+
+        #[test]
+        fn quits_straight_away_when_idle() {
+            let mut app = App::new();
+
+            app.request_quit();
+
+            assert!(app.should_quit);
+        }
+
+        #[test]
+        fn asks_first_while_loading_or_searching() {
+            let mut app = App::new();
+            app.item_details_loading = Some("item".to_string());
+            app.search_query = "git".to_string();
+
+            app.request_quit();
+
+            assert!(!app.should_quit);
+            let Some(Modal::QuitConfirm { reasons }) = &app.modal else {
+                panic!("expected QuitConfirm modal");
+            };
+            assert_eq!(
+                reasons,
+                &[
+                    "Item details still loading from op".to_string(),
+                    "The search \"git\"".to_string(),
+                ]
+            );
+        }
+    }
+
+    mod undo_var_deletion {
+        use super::*;
+
+        #[test]
+        fn nothing_to_undo_without_a_delete() {
+            let mut app = App::new();
+            app.config = Some(OpLoadConfig::default());
+
+            let err = app.restore_deleted_vars().unwrap_err();
+
+            assert!(err.to_string().contains("no var deletion"));
+        }
+
+        #[test]
+        fn needs_a_loaded_config() {
+            let mut app = App::new();
+            app.deleted_vars = vec![(
+                "TOKEN".to_string(),
+                InjectVarConfig {
+                    account_id: "acct".to_string(),
+                    op_reference: "op://Private/GitHub/token".to_string(),
+                    prefix: None,
+                },
+            )];
+
+            assert!(app.restore_deleted_vars().is_err());
+            assert_eq!(app.deleted_vars.len(), 1);
+        }
+    }
+
+    mod env_preview {
+        use super::*;
+
+        #[test]
+        fn values_are_masked_until_revealed() {
+            let vars = vec![("TOKEN".to_string(), "s3cret".to_string())];
+            let warnings = vec!["Failed to resolve secrets for account acct: boom".to_string()];
+
+            assert_eq!(
+                env_preview_lines(&vars, &warnings, false),
+                [
+                    "# Warning: Failed to resolve secrets for account acct: boom",
+                    "export TOKEN='********'",
+                ]
+            );
+            assert_eq!(
+                env_preview_lines(&vars, &[], true),
+                ["export TOKEN='s3cret'"]
+            );
+        }
+
+        #[test]
+        fn focus_loss_closes_the_preview() {
+            let mut app = App::new();
+            app.modal = Some(Modal::EnvPreview {
+                vars: vec![("TOKEN".to_string(), "s3cret".to_string())],
+                warnings: Vec::new(),
+                revealed: true,
+                scroll: 0,
+            });
+
+            app.on_focus_lost();
+
+            assert!(app.modal.is_none());
+        }
+
+        #[test]
+        fn nothing_to_preview_without_vars() {
+            let mut app = App::new();
+            app.config = Some(OpLoadConfig::default());
+
+            assert!(app.open_env_preview().is_err());
+        }
+    }
+
+    mod recent_items {
+        use super::*;
+
+        fn app_in_vaults(vault_ids: &[&str]) -> App {
+            let mut app = App::new();
+            app.accounts = vec![Account {
+                email: "a@example.com".to_string(),
+                user_uuid: "user".to_string(),
+                account_uuid: "acct".to_string(),
+            }];
+            app.selected_account_idx = Some(0);
+            app.vaults = vault_ids
+                .iter()
+                .map(|id| Vault {
+                    id: (*id).to_string(),
+                    name: id.to_uppercase(),
+                })
+                .collect();
+            app.selected_vault_idx = Some(0);
+            app
+        }
+
+        #[test]
+        fn opened_items_are_recorded() {
+            let mut app = app_in_vaults(&["v"]);
+            app.item_details_loading = Some("i".to_string());
+
+            app.details_tx
+                .send(ItemDetailsLoad {
+                    account_id: "acct".to_string(),
+                    item_id: "i".to_string(),
+                    result: Ok(
+                        br#"{"id":"i","title":"GitHub","category":"LOGIN","fields":[]}"#.to_vec(),
+                    ),
+                    elapsed: Duration::ZERO,
+                })
+                .unwrap();
+            app.apply_background_results();
+
+            let recent = &app.recent_items.items[0];
+            assert_eq!(recent.title, "GitHub");
+            assert_eq!(recent.vault_name, "V");
+            assert_eq!(app.recent_list_state.selected(), Some(0));
+        }
+
+        #[test]
+        fn opening_selects_vault_then_item() {
+            let mut app = app_in_vaults(&["a", "b"]);
+            app.recent_items.items.push(RecentItem {
+                account_id: "acct".to_string(),
+                vault_id: "b".to_string(),
+                vault_name: "B".to_string(),
+                item_id: "i2".to_string(),
+                title: "Stripe".to_string(),
+                used_at: 0,
+            });
+            let request = ListingRequest::Items {
+                account_id: "acct".to_string(),
+                vault_id: "b".to_string(),
+            };
+            // Keeps `load_listing` from fetching the listing itself.
+            app.loading_listings.push(request.clone());
+
+            app.apply(Action::OpenRecentItem(0));
+            assert_eq!(app.selected_vault_idx, Some(1));
+
+            app.listing_tx
+                .send(ListingRefresh {
+                    request,
+                    result: Ok(br#"[{"id":"i1","title":"AWS","category":"LOGIN"},{"id":"i2","title":"Stripe","category":"LOGIN"}]"#.to_vec()),
+                    elapsed: Duration::ZERO,
+                })
+                .unwrap();
+            app.apply_listing_refreshes();
+
+            assert_eq!(app.selected_vault_item_idx, Some(1));
+            assert_eq!(app.item_details_loading.as_deref(), Some("i2"));
+            assert!(app.pending_recent_jump.is_none());
+        }
+
+        #[test]
+        fn missing_account_is_an_error() {
+            let mut app = app_in_vaults(&["v"]);
+            app.recent_items.items.push(RecentItem {
+                account_id: "gone".to_string(),
+                vault_id: "v".to_string(),
+                vault_name: "V".to_string(),
+                item_id: "i".to_string(),
+                title: "Old".to_string(),
+                used_at: 0,
+            });
+
+            app.apply(Action::OpenRecentItem(0));
+
+            assert!(app.error_message.unwrap().contains("no longer signed in"));
+            assert_eq!(app.selected_vault_idx, Some(0));
+        }
+    }
+
+    mod error_pane {
+        use super::*;
+
+        #[test]
+        fn prefers_current_error_over_logged_failure() {
+            let mut app = App::new();
+            app.command_log
+                .log_failure("op vault list", "line one\nline two\n");
+
+            assert_eq!(
+                app.latest_error(),
+                Some(ErrorDetails {
+                    source: "op vault list".to_string(),
+                    message: "line one\nline two".to_string(),
+                })
+            );
+
+            app.error_message = Some("Vault name cannot be empty".to_string());
+            assert_eq!(app.latest_error().unwrap().source, "op-loader");
+        }
+
+        #[test]
+        fn scroll_is_clamped_to_message() {
+            let mut app = App::new();
+            app.error_message = Some("a\nb\nc".to_string());
+            app.open_error_pane();
+
+            app.scroll_error_pane(10);
+            assert!(matches!(
+                app.modal,
+                Some(Modal::ErrorDetails { scroll: 2, .. })
+            ));
+
+            app.scroll_error_pane(-10);
+            assert!(matches!(
+                app.modal,
+                Some(Modal::ErrorDetails { scroll: 0, .. })
+            ));
+        }
+
+        #[test]
+        fn nothing_to_show_without_errors() {
+            let mut app = App::new();
+            app.command_log.log_success("op account list", Some(1));
+
+            app.open_error_pane();
+
+            assert!(app.modal.is_none());
+        }
+    }
+
+    mod focus_lock {
+        use super::*;
+
+        fn env_var_modal() -> Modal {
+            Modal::EnvVar {
+                env_var_name: "TOKEN".to_string(),
+                field_reference: "op://v/i/f".to_string(),
+                editing: None,
+                prefix: None,
+            }
+        }
+
+        #[test]
+        fn focus_loss_hides_values_and_closes_env_var_modal() {
+            let mut app = App::new();
+            app.modal = Some(env_var_modal());
+
+            app.on_focus_lost();
+            assert!(app.secrets_hidden);
+            assert!(app.modal.is_none());
+
+            app.on_focus_gained();
+            assert!(!app.secrets_hidden);
+        }
+
+        #[test]
+        fn focus_loss_respects_config() {
+            let mut app = App::new();
+            app.config = Some(OpLoadConfig {
+                tui: TuiConfig {
+                    lock_on_focus_loss: Some(false),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+            app.modal = Some(env_var_modal());
+
+            app.on_focus_lost();
+
+            assert!(!app.secrets_hidden);
+            assert!(app.modal.is_some());
+        }
+    }
+
+    mod item_details_layout {
+        use super::*;
+
+        fn details() -> VaultItemDetails {
+            serde_json::from_str(
+                r#"{
+                    "id": "1",
+                    "title": "Server",
+                    "category": "LOGIN",
+                    "fields": [
+                        {"label": "username", "type": "STRING", "reference": "op://v/i/username"},
+                        {"label": "host", "type": "STRING", "reference": "op://v/i/ssh/host",
+                         "section": {"id": "ssh", "label": "SSH"}},
+                        {"label": "notesPlain", "type": "STRING", "value": "rotate monthly\nowner: ops",
+                         "reference": "op://v/i/notesPlain"},
+                        {"label": "db", "type": "STRING", "reference": "op://v/i/db/name",
+                         "section": {"id": "db", "label": "Database"}},
+                        {"label": "port", "type": "STRING", "reference": "op://v/i/ssh/port",
+                         "section": {"id": "ssh", "label": "SSH"}},
+                        {"label": "password", "type": "CONCEALED", "reference": "op://v/i/password"}
+                    ],
+                    "urls": [{"label": "website", "primary": true, "href": "https://example.com"}]
+                }"#,
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn groups_fields_by_section_without_notes() {
+            let details = details();
+
+            let labels: Vec<&str> = details
+                .detail_fields()
+                .iter()
+                .map(|f| f.label.as_str())
+                .collect();
+
+            assert_eq!(labels, ["username", "password", "host", "port", "db"]);
+            assert_eq!(details.notes(), Some("rotate monthly\nowner: ops"));
+            assert_eq!(details.urls[0].href, "https://example.com");
         }
 
         #[test]
-        fn filters_by_fuzzy_match() {
+        fn highlighted_field_follows_grouped_order() {
             let mut app = App::new();
-            app.vault_items = vec![
-                make_vault_item("1", "GitHub Token"),
-                make_vault_item("2", "AWS Secret"),
-                make_vault_item("3", "GitLab Token"),
-            ];
-            app.search_query = "git".to_string();
+            app.selected_item_details = Some(details());
+            app.item_detail_list_state.select(Some(2));
 
-            app.update_filtered_items();
+            let field = app.highlighted_detail_field().unwrap();
 
-            assert_eq!(app.filtered_item_indices.len(), 2);
-            assert!(app.filtered_item_indices.contains(&0)); // GitHub
-            assert!(app.filtered_item_indices.contains(&2)); // GitLab
+            assert_eq!(field.label, "host");
         }
+    }
 
-        #[test]
-        fn no_matches_returns_empty() {
-            let mut app = App::new();
-            app.vault_items = vec![
-                make_vault_item("1", "GitHub Token"),
-                make_vault_item("2", "AWS Secret"),
-            ];
-            app.search_query = "zzzzz".to_string();
-
-            app.update_filtered_items();
+    mod var_edit {
+        use super::*;
 
-            assert!(app.filtered_item_indices.is_empty());
-            assert!(app.vault_item_list_state.selected().is_none());
+        fn app_with_vars() -> App {
+            let mut app = App::new();
+            let mut config = OpLoadConfig::default();
+            for (name, reference) in [("TOKEN", "op://v/i/f"), ("OTHER", "op://v/j/f")] {
+                config.inject_vars.insert(
+                    name.to_string(),
+                    InjectVarConfig {
+                        account_id: "acct".to_string(),
+                        op_reference: reference.to_string(),
+                        prefix: None,
+                    },
+                );
+            }
+            app.config = Some(config);
+            app
         }
 
         #[test]
-        fn selects_first_item_when_results_exist() {
-            let mut app = App::new();
-            app.vault_items = vec![
-                make_vault_item("1", "GitHub Token"),
-                make_vault_item("2", "AWS Secret"),
-            ];
-            app.search_query = String::new();
+        fn prefills_and_switches_inputs() {
+            let mut app = app_with_vars();
 
-            app.update_filtered_items();
+            app.open_var_edit_modal("TOKEN");
 
-            assert_eq!(app.vault_item_list_state.selected(), Some(0));
+            assert_eq!(app.modal_env_var_name(), Some("TOKEN"));
+            assert_eq!(app.modal_field_reference(), Some("op://v/i/f"));
+            let (input, field) = app.env_var_modal_input_mut().unwrap();
+            assert_eq!(field, EnvVarField::Name);
+            input.push_str("_2");
+
+            app.toggle_env_var_modal_field();
+            let (input, field) = app.env_var_modal_input_mut().unwrap();
+            assert_eq!(field, EnvVarField::Reference);
+            input.push_str("ield");
+
+            assert_eq!(app.modal_env_var_name(), Some("TOKEN_2"));
+            assert_eq!(app.modal_field_reference(), Some("op://v/i/field"));
         }
 
         #[test]
-        fn clears_selected_item_details() {
-            let mut app = App::new();
-            app.vault_items = vec![make_vault_item("1", "GitHub Token")];
-            app.selected_vault_item_idx = Some(0);
-            app.selected_item_details = Some(VaultItemDetails {
-                id: "1".to_string(),
-                title: "GitHub Token".to_string(),
-                category: "LOGIN".to_string(),
-                fields: vec![],
-            });
+        fn rejects_renaming_onto_another_var() {
+            let mut app = app_with_vars();
 
-            app.update_filtered_items();
+            let err = app
+                .update_managed_var("TOKEN", "OTHER", "acct", "op://v/i/f", None)
+                .unwrap_err();
 
-            assert!(app.selected_vault_item_idx.is_none());
-            assert!(app.selected_item_details.is_none());
+            assert_eq!(err.to_string(), "OTHER is already mapped");
+            assert!(app.config.unwrap().inject_vars.contains_key("TOKEN"));
         }
+    }
+
+    mod field_edit {
+        use super::*;
 
         #[test]
-        fn empty_vault_items_returns_empty() {
+        fn targets_the_field_by_section_and_escaped_label() {
             let mut app = App::new();
-            app.vault_items = vec![];
-            app.search_query = "test".to_string();
+            let mut field = make_item_field("api.key", "op://v/i/s/api.key");
+            field.section = Some(FieldSection {
+                id: "s".to_string(),
+                label: Some("Prod".to_string()),
+            });
+            app.selected_item_details = Some(VaultItemDetails {
+                id: "i".to_string(),
+                title: "Stripe".to_string(),
+                category: "API_CREDENTIAL".to_string(),
+                urls: vec![],
+                fields: vec![field],
+            });
+            app.item_detail_list_state.select(Some(0));
 
-            app.update_filtered_items();
+            app.open_field_edit_modal();
 
-            assert!(app.filtered_item_indices.is_empty());
+            let Some(Modal::FieldEdit {
+                assignment_target,
+                value,
+                confirming,
+                ..
+            }) = app.modal.as_ref()
+            else {
+                panic!("expected FieldEdit modal");
+            };
+            assert_eq!(assignment_target, r"Prod.api\.key");
+            assert!(value.is_empty());
+            assert!(!confirming);
+            assert_eq!(escape_assignment_name(r"a=b\c"), r"a\=b\\c");
         }
     }
 
-    mod clear_search {
+    mod item_create {
         use super::*;
 
         #[test]
-        fn clears_query_and_deactivates() {
-            let mut app = App::new();
-            app.search_query = "some search".to_string();
-            app.search_active = true;
+        fn builds_item_json_from_form_rows() {
+            let fields = vec![
+                "username=me".to_string(),
+                "password=hunter2".to_string(),
+                "api token=abc=def".to_string(),
+                String::new(),
+            ];
 
-            app.clear_search();
+            let template = item_create_template("GitHub", 2, &fields).unwrap();
+
+            assert_eq!(template["category"], "LOGIN");
+            assert_eq!(template["fields"].as_array().unwrap().len(), 3);
+            assert_eq!(template["fields"][0]["purpose"], "USERNAME");
+            assert_eq!(template["fields"][0]["type"], "STRING");
+            assert_eq!(template["fields"][1]["type"], "CONCEALED");
+            assert_eq!(template["fields"][2]["label"], "api token");
+            assert_eq!(template["fields"][2]["value"], "abc=def");
+            assert_eq!(template["fields"][2]["type"], "CONCEALED");
+            assert!(item_create_template(" ", 0, &fields).is_err());
+            assert!(item_create_template("x", 0, &["novalue".to_string()]).is_err());
+        }
 
-            assert!(app.search_query.is_empty());
-            assert!(!app.search_active);
+        #[test]
+        fn category_change_resets_untouched_fields() {
+            let mut app = App::new();
+            app.open_item_create_modal();
+            app.cycle_item_create_category(true);
+            let Some(Modal::ItemCreate { fields, .. }) = app.modal.as_ref() else {
+                panic!("expected ItemCreate modal");
+            };
+            assert_eq!(fields, &["password=".to_string(), String::new()]);
+
+            app.move_item_create_focus(true);
+            app.move_item_create_focus(true);
+            app.item_create_input_mut().unwrap().push_str("secret");
+            app.cycle_item_create_category(true);
+            let Some(Modal::ItemCreate {
+                fields,
+                category_idx,
+                ..
+            }) = app.modal.as_ref()
+            else {
+                panic!("expected ItemCreate modal");
+            };
+            assert_eq!(*category_idx, 2);
+            assert_eq!(fields[0], "password=secret");
         }
 
         #[test]
-        fn resets_filtered_items_to_all() {
+        fn generated_password_fills_the_active_field() {
             let mut app = App::new();
-            app.vault_items = vec![
-                make_vault_item("1", "GitHub Token"),
-                make_vault_item("2", "AWS Secret"),
-            ];
-            app.search_query = "git".to_string();
-            app.update_filtered_items();
+            app.open_item_create_modal();
+            app.move_item_create_focus(true);
+            app.move_item_create_focus(true);
 
-            app.clear_search();
+            app.open_password_generator();
+            let Some(Modal::PasswordGenerator { password, .. }) = app.modal.clone() else {
+                panic!("expected PasswordGenerator modal");
+            };
+            app.close_password_generator(true);
 
-            assert_eq!(app.filtered_item_indices, vec![0, 1]);
+            let Some(Modal::ItemCreate { fields, .. }) = app.modal.as_ref() else {
+                panic!("expected ItemCreate modal");
+            };
+            assert_eq!(fields[0], format!("credential={password}"));
         }
     }
 
-    mod open_modal {
+    mod item_columns {
         use super::*;
 
         #[test]
-        fn sets_modal_state() {
-            let mut app = App::new();
-            let reference = "op://vault/item/field".to_string();
+        fn labels_categories_like_1password() {
+            assert_eq!(category_label("API_CREDENTIAL"), "API Credential");
+            assert_eq!(category_label("SECURE_NOTE"), "Secure Note");
+            assert_eq!(category_label("LOGIN"), "Login");
+        }
 
-            app.open_modal(reference.clone());
+        #[test]
+        fn age_comes_from_updated_at() {
+            let mut item = make_vault_item("1", "GitHub");
+            assert_eq!(item.age(SystemTime::now()), None);
 
-            let Modal::EnvVar {
-                env_var_name,
-                field_reference,
-            } = app.modal.as_ref().expect("modal should be set")
-            else {
-                panic!("expected EnvVar modal");
-            };
+            item.updated_at = Some("2024-01-01T00:00:00Z".to_string());
+            let now = crate::timestamp::parse_rfc3339("2024-01-03T00:00:00Z").unwrap();
+            assert_eq!(item.age(now), Some(Duration::from_secs(2 * 86_400)));
+        }
+    }
 
-            assert!(env_var_name.is_empty());
-            assert_eq!(field_reference, &reference);
+    mod masked_length {
+        use super::*;
+
+        #[test]
+        fn reports_length_without_the_value() {
+            assert_eq!(masked_length(0), "empty value");
+            assert_eq!(masked_length(1), "********, 1 char");
+            assert_eq!(masked_length(24), "********, 24 chars");
         }
+    }
+
+    mod template_usage {
+        use super::*;
 
         #[test]
-        fn clears_previous_env_var_name() {
-            let mut app = App::new();
-            app.modal = Some(Modal::EnvVar {
-                env_var_name: "OLD_VAR".to_string(),
-                field_reference: "op://vault/item/old".to_string(),
-            });
+        fn finds_placeholders_in_managed_templates() {
+            let dir = assert_fs::TempDir::new().unwrap();
+            std::fs::write(dir.path().join("npmrc.tmpl"), "token={{NPM_TOKEN}}\n").unwrap();
+            let mut config = OpLoadConfig::default();
+            for name in ["NPM_TOKEN", "NPM_TOKEN_2", "UNUSED"] {
+                config.inject_vars.insert(
+                    name.to_string(),
+                    InjectVarConfig {
+                        account_id: "acct".to_string(),
+                        op_reference: "op://v/i/f".to_string(),
+                        prefix: None,
+                    },
+                );
+            }
+            for (target, template_name) in [("/x/.npmrc", "npmrc.tmpl"), ("/x/.env", "gone.tmpl")] {
+                config.templated_files.insert(
+                    target.to_string(),
+                    TemplatedFile {
+                        template_name: template_name.to_string(),
+                        vars: None,
+                        symlink: false,
+                    },
+                );
+            }
 
-            app.open_modal("op://vault/item/field".to_string());
+            let used = vars_used_in_templates(&config, dir.path());
 
-            let Modal::EnvVar { env_var_name, .. } =
-                app.modal.as_ref().expect("modal should be set")
-            else {
-                panic!("expected EnvVar modal");
-            };
-            assert!(env_var_name.is_empty());
+            assert_eq!(used, HashSet::from(["NPM_TOKEN".to_string()]));
         }
     }
 
-    mod close_modal {
+    mod bulk_map {
         use super::*;
 
         #[test]
-        fn resets_all_modal_state() {
-            let mut app = App::new();
-            app.modal = Some(Modal::EnvVar {
-                env_var_name: "MY_VAR".to_string(),
-                field_reference: "op://vault/item/field".to_string(),
-            });
-            app.error_message = Some("some error".to_string());
+        fn suggests_names_from_title_and_field() {
+            assert_eq!(
+                suggest_field_env_var_name("GitHub Token", "credential"),
+                "GITHUB_TOKEN"
+            );
+            assert_eq!(
+                suggest_field_env_var_name("Stripe", "credential"),
+                "STRIPE_TOKEN"
+            );
+            assert_eq!(
+                suggest_field_env_var_name("AWS", "access key id"),
+                "AWS_ACCESS_KEY_ID"
+            );
+            assert_eq!(
+                suggest_field_env_var_name("Postgres Password", "password"),
+                "POSTGRES_PASSWORD"
+            );
+        }
 
-            app.close_modal();
+        #[test]
+        fn suggests_names_from_titles() {
+            assert_eq!(
+                suggest_env_var_name("SendGrid API (prod)"),
+                "SENDGRID_API_PROD"
+            );
+            assert_eq!(suggest_env_var_name("  stripe-key "), "STRIPE_KEY");
+            assert_eq!(suggest_env_var_name("1Password"), "_1PASSWORD");
+        }
 
-            assert!(app.modal.is_none());
-            assert!(app.error_message.is_none());
+        #[test]
+        fn maps_marked_items_with_unique_names() {
+            let mut app = App::new();
+            app.vaults = vec![Vault {
+                id: "v1".to_string(),
+                name: "Work".to_string(),
+            }];
+            app.selected_vault_idx = Some(0);
+            let mut api = make_vault_item("i1", "GitHub");
+            api.category = "API_CREDENTIAL".to_string();
+            app.vault_items = vec![
+                api,
+                make_vault_item("i2", "Unmarked"),
+                make_vault_item("i3", "github"),
+            ];
+            app.toggle_item_mark("i3");
+            app.toggle_item_mark("i1");
+
+            app.open_bulk_map_modal();
+
+            assert_eq!(
+                app.bulk_map_vars().unwrap(),
+                vec![
+                    ("GITHUB".to_string(), "op://Work/i1/credential".to_string()),
+                    (
+                        "GITHUB_2".to_string(),
+                        "op://Work/i3/credential".to_string()
+                    ),
+                ]
+            );
+
+            app.bulk_map_input_mut().unwrap().truncate(0);
+            assert!(app.bulk_map_vars().is_err());
         }
     }
 
-    mod modal_selected_field {
+    mod totp {
         use super::*;
 
+        fn details_with_otp() -> VaultItemDetails {
+            VaultItemDetails {
+                id: "item".to_string(),
+                title: "GitHub".to_string(),
+                category: "LOGIN".to_string(),
+                urls: vec![],
+                fields: vec![ItemField {
+                    label: "one-time password".to_string(),
+                    value: Some("otpauth://totp/GitHub?secret=SEED&period=30".to_string()),
+                    field_type: "OTP".to_string(),
+                    reference: "op://v/item/otp".to_string(),
+                    section: None,
+                    totp: Some("123456".to_string()),
+                }],
+            }
+        }
+
         #[test]
-        fn returns_matching_field() {
+        fn code_from_details_is_used_until_it_rotates() {
             let mut app = App::new();
-            let reference = "op://vault/item/password".to_string();
-            app.selected_item_details = Some(VaultItemDetails {
-                id: "1".to_string(),
-                title: "Test Item".to_string(),
-                category: "LOGIN".to_string(),
-                fields: vec![
-                    make_item_field("username", "op://vault/item/username"),
-                    make_item_field("password", "op://vault/item/password"),
-                ],
-            });
-            app.modal = Some(Modal::EnvVar {
-                env_var_name: String::new(),
-                field_reference: reference,
-            });
+            let now = SystemTime::now();
+            let details = details_with_otp();
+            app.totp = totp_for_details(&details, now);
+            app.selected_item_details = Some(details);
+
+            assert_eq!(app.current_totp_code(now), Some("123456"));
+            assert_eq!(app.field_value("op://v/item/otp").unwrap(), "123456");
+            assert_eq!(app.current_totp_code(now + Duration::from_secs(30)), None);
+        }
 
-            let field = app.modal_selected_field();
+        #[test]
+        fn refresh_waits_for_the_details_panel() {
+            let mut app = App::new();
+            app.focused_panel = FocusedPanel::VaultItemList;
+            let details = details_with_otp();
+            app.totp = totp_for_details(&details, SystemTime::UNIX_EPOCH);
+            app.selected_item_details = Some(details);
 
-            assert!(field.is_some());
-            assert_eq!(field.unwrap().label, "password");
+            app.refresh_totp_if_due(SystemTime::now());
+
+            assert!(!app.totp.unwrap().refreshing);
         }
 
         #[test]
-        fn returns_none_when_no_details() {
+        fn fetched_code_replaces_the_old_one() {
             let mut app = App::new();
-            app.selected_item_details = None;
-            app.modal = Some(Modal::EnvVar {
-                env_var_name: String::new(),
-                field_reference: "op://vault/item/field".to_string(),
-            });
+            let details = details_with_otp();
+            app.totp = totp_for_details(&details, SystemTime::UNIX_EPOCH);
+            app.totp.as_mut().unwrap().refreshing = true;
+            app.selected_item_details = Some(details);
+
+            app.totp_tx
+                .send(TotpLoad {
+                    account_id: "acct".to_string(),
+                    item_id: "item".to_string(),
+                    result: Ok(b"654321\n".to_vec()),
+                })
+                .unwrap();
+            app.apply_background_results();
 
-            assert!(app.modal_selected_field().is_none());
+            assert_eq!(app.current_totp_code(SystemTime::now()), Some("654321"));
         }
+    }
+
+    mod clipboard {
+        use super::*;
 
         #[test]
-        fn returns_none_when_no_reference() {
+        fn plain_field_value_comes_from_details() {
             let mut app = App::new();
             app.selected_item_details = Some(VaultItemDetails {
-                id: "1".to_string(),
-                title: "Test Item".to_string(),
+                id: "i".to_string(),
+                title: "t".to_string(),
                 category: "LOGIN".to_string(),
-                fields: vec![make_item_field("password", "op://vault/item/password")],
+                urls: vec![],
+                fields: vec![ItemField {
+                    label: "username".to_string(),
+                    value: Some("alice".to_string()),
+                    field_type: "STRING".to_string(),
+                    reference: "op://v/i/username".to_string(),
+                    section: None,
+                    totp: None,
+                }],
             });
-            app.modal = None;
 
-            assert!(app.modal_selected_field().is_none());
+            assert_eq!(app.field_value("op://v/i/username").unwrap(), "alice");
+            assert!(app.field_value("op://v/i/missing").is_err());
         }
 
         #[test]
-        fn returns_none_when_reference_not_found() {
+        fn revealed_value_is_masked_after_moving_or_expiring() {
             let mut app = App::new();
+            let field = |label: &str| ItemField {
+                label: label.to_string(),
+                value: Some(format!("{label}-value")),
+                field_type: "CONCEALED".to_string(),
+                reference: format!("op://v/i/{label}"),
+                section: None,
+                totp: None,
+            };
             app.selected_item_details = Some(VaultItemDetails {
-                id: "1".to_string(),
-                title: "Test Item".to_string(),
+                id: "i".to_string(),
+                title: "t".to_string(),
                 category: "LOGIN".to_string(),
-                fields: vec![make_item_field("password", "op://vault/item/password")],
+                urls: vec![],
+                fields: vec![field("password"), field("pin")],
             });
-            app.modal = Some(Modal::EnvVar {
-                env_var_name: String::new(),
-                field_reference: "op://vault/item/nonexistent".to_string(),
+            app.item_detail_list_state.select(Some(0));
+            let now = std::time::Instant::now();
+            app.revealed_field = Some(RevealedField {
+                reference: "op://v/i/password".to_string(),
+                value: "hunter22".to_string(),
+                until: now + Duration::from_secs(10),
             });
 
-            assert!(app.modal_selected_field().is_none());
-        }
-    }
-
-    mod selected_vault {
-        use super::*;
-
-        #[test]
-        fn returns_vault_at_index() {
-            let mut app = App::new();
-            app.vaults = vec![
-                Vault {
-                    id: "v1".to_string(),
-                    name: "Personal".to_string(),
-                },
-                Vault {
-                    id: "v2".to_string(),
-                    name: "Work".to_string(),
-                },
-            ];
-            app.selected_vault_idx = Some(1);
+            assert_eq!(app.revealed_value(&field("password")), Some("hunter22"));
+            assert_eq!(app.revealed_value(&field("pin")), None);
 
-            let vault = app.selected_vault();
+            app.remask_if_stale(now);
+            assert!(app.revealed_field.is_some());
+            app.remask_if_stale(now + Duration::from_secs(10));
+            assert!(app.revealed_field.is_none());
 
-            assert!(vault.is_some());
-            assert_eq!(vault.unwrap().name, "Work");
+            app.revealed_field = Some(RevealedField {
+                reference: "op://v/i/password".to_string(),
+                value: "hunter22".to_string(),
+                until: now + Duration::from_secs(10),
+            });
+            app.item_detail_list_state.select(Some(1));
+            app.remask_if_stale(now);
+            assert!(app.revealed_field.is_none());
         }
 
         #[test]
-        fn returns_none_when_no_selection() {
+        fn reveal_toggles_off() {
             let mut app = App::new();
-            app.vaults = vec![Vault {
-                id: "v1".to_string(),
-                name: "Personal".to_string(),
-            }];
-            app.selected_vault_idx = None;
+            app.revealed_field = Some(RevealedField {
+                reference: "op://v/i/password".to_string(),
+                value: "hunter22".to_string(),
+                until: std::time::Instant::now() + Duration::from_secs(10),
+            });
 
-            assert!(app.selected_vault().is_none());
+            app.apply(Action::ToggleReveal {
+                reference: "op://v/i/password".to_string(),
+            });
+
+            assert!(app.revealed_field.is_none());
         }
 
         #[test]
-        fn returns_none_when_index_out_of_bounds() {
+        fn clear_is_scheduled_unless_disabled() {
             let mut app = App::new();
-            app.vaults = vec![Vault {
-                id: "v1".to_string(),
-                name: "Personal".to_string(),
-            }];
-            app.selected_vault_idx = Some(5);
-
-            assert!(app.selected_vault().is_none());
+            app.schedule_clipboard_clear("secret");
+            let pending = app.clipboard_clear.clone().unwrap();
+            assert!(!app.clipboard_clear_due(std::time::Instant::now()));
+            assert!(app.clipboard_clear_due(pending.at));
+            assert_ne!(pending.hash, "secret");
+
+            app.config = Some(OpLoadConfig {
+                clipboard: ClipboardConfig {
+                    clear_after: Some("0s".to_string()),
+                },
+                ..Default::default()
+            });
+            app.schedule_clipboard_clear("secret");
+            assert!(app.clipboard_clear.is_none());
         }
     }
 }
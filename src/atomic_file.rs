@@ -0,0 +1,134 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Replaces `target` with `contents` so that readers see either the old file
+/// or the new one, never a partial write: the contents go to a temporary
+/// file next to it, are flushed to disk, and the temporary file is renamed
+/// over the target. An existing target keeps its mode and, where allowed,
+/// its owner; a new one is created readable only by the current user. A
+/// symlinked target is followed, so the file it points to is replaced.
+pub fn write(target: &Path, contents: &[u8]) -> Result<()> {
+    let target = resolve_symlink(target)?;
+    let dir = match target.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let original = std::fs::metadata(&target).ok();
+
+    let temp = temp_path(&dir, &target);
+    let result = write_temp(&temp, contents, original.as_ref()).and_then(|()| {
+        std::fs::rename(&temp, &target).with_context(|| {
+            format!(
+                "Failed to move {} into place at {}",
+                temp.display(),
+                target.display()
+            )
+        })
+    });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+        return result;
+    }
+
+    // The rename itself is only durable once the directory is flushed too.
+    #[cfg(unix)]
+    if let Ok(dir) = std::fs::File::open(&dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+fn resolve_symlink(target: &Path) -> Result<PathBuf> {
+    if target.is_symlink() {
+        std::fs::canonicalize(target)
+            .with_context(|| format!("Failed to resolve symlink {}", target.display()))
+    } else {
+        Ok(target.to_path_buf())
+    }
+}
+
+/// `.<name>.op-loader-<pid>.tmp` in `dir`, so the rename stays on one
+/// filesystem.
+fn temp_path(dir: &Path, target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    dir.join(format!(".{name}.op-loader-{}.tmp", std::process::id()))
+}
+
+fn write_temp(temp: &Path, contents: &[u8], original: Option<&std::fs::Metadata>) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(temp)
+        .with_context(|| format!("Failed to create {}", temp.display()))?;
+    file.write_all(contents)
+        .and_then(|()| file.sync_all())
+        .with_context(|| format!("Failed to write {}", temp.display()))?;
+
+    if let Some(original) = original {
+        std::fs::set_permissions(temp, original.permissions())
+            .with_context(|| format!("Failed to set permissions on {}", temp.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            // Only root can give a file away; anyone else keeps their own.
+            if let Err(err) =
+                std::os::unix::fs::chown(temp, Some(original.uid()), Some(original.gid()))
+            {
+                log::debug!("Keeping the current owner of {}: {err}", temp.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    fn mode(path: &Path) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn replaces_files_without_leaving_temp_files() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let target = dir.path().join(".netrc");
+
+        write(&target, b"first").unwrap();
+        write(&target, b"second").unwrap();
+
+        assert_eq!(std::fs::read(&target).unwrap(), b"second");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+        #[cfg(unix)]
+        assert_eq!(mode(&target), 0o600);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_the_mode_and_follows_symlinks() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = assert_fs::TempDir::new().unwrap();
+        let real = dir.path().join("npmrc");
+        let link = dir.path().join(".npmrc");
+        std::fs::write(&real, "old").unwrap();
+        std::fs::set_permissions(&real, std::fs::Permissions::from_mode(0o640)).unwrap();
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        write(&link, b"new").unwrap();
+
+        assert!(link.is_symlink());
+        assert_eq!(std::fs::read(&real).unwrap(), b"new");
+        assert_eq!(mode(&real), 0o640);
+    }
+}
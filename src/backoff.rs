@@ -0,0 +1,144 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::cache::{unix_secs, update_locked_json};
+
+/// Cool-down after the first failure; it doubles with each further failure.
+const BASE_DELAY: Duration = Duration::from_secs(2);
+const MAX_DELAY: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct AccountFailures {
+    count: u32,
+    /// Unix seconds until which calls for the account are skipped.
+    retry_at: u64,
+}
+
+/// Per-account cool-downs after failed `op` calls, so a revoked session or a
+/// dropped network isn't retried on every keypress or shell start. A success
+/// or an explicit retry clears an account's failures.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureBackoff {
+    #[serde(default)]
+    accounts: BTreeMap<String, AccountFailures>,
+}
+
+/// How long to wait after `count` consecutive failures.
+pub fn delay_after(count: u32) -> Duration {
+    BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(count.saturating_sub(1)))
+        .min(MAX_DELAY)
+}
+
+impl FailureBackoff {
+    /// Records a failure and returns how long the account now cools down.
+    pub fn record_failure(&mut self, account_id: &str, now: SystemTime) -> Duration {
+        let entry = self
+            .accounts
+            .entry(account_id.to_string())
+            .or_insert(AccountFailures {
+                count: 0,
+                retry_at: 0,
+            });
+        entry.count = entry.count.saturating_add(1);
+        let delay = delay_after(entry.count);
+        entry.retry_at = unix_secs(now) + delay.as_secs();
+        delay
+    }
+
+    pub fn reset(&mut self, account_id: &str) {
+        self.accounts.remove(account_id);
+    }
+
+    /// Time left before `account_id` may be called again, if it's cooling
+    /// down.
+    pub fn remaining(&self, account_id: &str, now: SystemTime) -> Option<Duration> {
+        let failures = self.accounts.get(account_id)?;
+        let now = unix_secs(now);
+        (failures.retry_at > now).then(|| Duration::from_secs(failures.retry_at - now))
+    }
+
+    pub fn failing_accounts(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn failure_count(&self, account_id: &str) -> u32 {
+        self.accounts.get(account_id).map_or(0, |f| f.count)
+    }
+}
+
+/// Background cache refreshes run as separate processes, so their backoff is
+/// kept next to the cache files.
+pub fn backoff_path(cache_root: &Path) -> PathBuf {
+    cache_root.join("refresh_backoff.json")
+}
+
+pub fn load_backoff(cache_root: &Path) -> FailureBackoff {
+    std::fs::read_to_string(backoff_path(cache_root))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Rewrites the backoff file under an exclusive lock, so concurrent
+/// refreshes for different accounts don't clobber each other. Returns the
+/// state after the update.
+pub fn update_backoff(
+    cache_root: &Path,
+    update: impl FnOnce(&mut FailureBackoff),
+) -> Result<FailureBackoff> {
+    update_locked_json(&backoff_path(cache_root), "backoff state", update)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_doubles_up_to_the_cap() {
+        assert_eq!(delay_after(1), Duration::from_secs(2));
+        assert_eq!(delay_after(2), Duration::from_secs(4));
+        assert_eq!(delay_after(5), Duration::from_secs(32));
+        assert_eq!(delay_after(40), MAX_DELAY);
+    }
+
+    #[test]
+    fn cools_down_until_reset() {
+        let now = SystemTime::now();
+        let mut backoff = FailureBackoff::default();
+
+        backoff.record_failure("acct", now);
+        backoff.record_failure("acct", now);
+
+        assert_eq!(backoff.remaining("acct", now), Some(Duration::from_secs(4)));
+        assert_eq!(
+            backoff.remaining("acct", now + Duration::from_secs(5)),
+            None
+        );
+        assert_eq!(backoff.remaining("other", now), None);
+
+        backoff.reset("acct");
+        assert_eq!(backoff.remaining("acct", now), None);
+        assert_eq!(backoff.failure_count("acct"), 0);
+    }
+
+    #[test]
+    fn persisted_state_accumulates() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let now = SystemTime::now();
+
+        update_backoff(dir.path(), |b| {
+            b.record_failure("acct", now);
+        })
+        .unwrap();
+        let backoff = update_backoff(dir.path(), |b| {
+            b.record_failure("acct", now);
+        })
+        .unwrap();
+
+        assert_eq!(backoff.failure_count("acct"), 2);
+    }
+}
@@ -1,5 +1,9 @@
 use anyhow::{Context, Result};
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CacheRemoval {
@@ -10,6 +14,167 @@ pub enum CacheRemoval {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CacheKind {
     ResolvedVars,
+    AccountList,
+    VaultList,
+    ItemList,
+    /// Hashes of the files placed from document items, not their contents.
+    Documents,
+}
+
+impl CacheKind {
+    pub const ALL: [Self; 5] = [
+        Self::ResolvedVars,
+        Self::AccountList,
+        Self::VaultList,
+        Self::ItemList,
+        Self::Documents,
+    ];
+
+    /// Stable name used in `--porcelain` output.
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::ResolvedVars => "resolved_vars",
+            Self::AccountList => "accounts",
+            Self::VaultList => "vaults",
+            Self::ItemList => "items",
+            Self::Documents => "documents",
+        }
+    }
+
+    /// The kind and sanitized account id encoded in a cache file name.
+    pub fn parse_file_name(name: &str) -> Option<(Self, &str)> {
+        let stem = name.strip_suffix(".cache")?;
+        Self::ALL.into_iter().find_map(|kind| {
+            stem.strip_prefix(kind.file_prefix())
+                .and_then(|rest| rest.strip_prefix('_'))
+                .map(|account| (kind, account))
+        })
+    }
+
+    pub const fn file_prefix(self) -> &'static str {
+        match self {
+            Self::ResolvedVars => "op_inject_vars",
+            Self::AccountList => "op_accounts",
+            Self::VaultList => "op_vaults",
+            Self::ItemList => "op_items",
+            Self::Documents => "op_documents",
+        }
+    }
+}
+
+/// The plaintext stored inside an encrypted cache file.
+///
+/// Expiry is decided from `created_at` and `ttl_secs` rather than the file's
+/// mtime, so `touch` or a backup restore can't extend a cache's lifetime.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub created_at: u64,
+    pub ttl_secs: u64,
+    pub config_hash: String,
+    pub op_loader_version: String,
+    pub payload: String,
+}
+
+impl CacheEntry {
+    pub fn new(payload: String, ttl: Duration, config_hash: String) -> Self {
+        Self {
+            created_at: unix_secs(SystemTime::now()),
+            ttl_secs: ttl.as_secs(),
+            config_hash,
+            op_loader_version: env!("CARGO_PKG_VERSION").to_string(),
+            payload,
+        }
+    }
+
+    /// An entry is fresh when it was written by this op-loader version and is
+    /// younger than both the TTL it was written with and the caller's TTL.
+    /// Entries dated in the future are treated as stale.
+    #[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+    pub fn is_fresh(&self, ttl: Duration, now: SystemTime) -> bool {
+        if self.op_loader_version != env!("CARGO_PKG_VERSION") {
+            return false;
+        }
+
+        let now = unix_secs(now);
+        if self.created_at > now {
+            return false;
+        }
+
+        now - self.created_at <= self.ttl_secs.min(ttl.as_secs())
+    }
+
+    /// A fresh entry within the last tenth of its lifetime, which is worth
+    /// refreshing in the background before it expires.
+    #[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+    pub fn expires_soon(&self, ttl: Duration, now: SystemTime) -> bool {
+        if !self.is_fresh(ttl, now) {
+            return false;
+        }
+
+        let lifetime = self.ttl_secs.min(ttl.as_secs());
+        let remaining = lifetime - (unix_secs(now) - self.created_at);
+        remaining.saturating_mul(10) <= lifetime
+    }
+}
+
+/// Hex-encoded SHA-256 of the `op inject` input a cache entry was built from.
+pub fn config_hash(input: &str) -> String {
+    Sha256::digest(input.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Whole seconds since the Unix epoch; times before it count as 0.
+pub(crate) fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Applies `update` to the JSON state at `path` under an exclusive lock, so
+/// processes updating it at the same time don't lose each other's changes.
+/// A missing, empty, or unreadable file starts from the default. `what`
+/// names the state in errors. Returns the state after the update.
+pub(crate) fn update_locked_json<T>(
+    path: &Path,
+    what: &str,
+    update: impl FnOnce(&mut T),
+) -> Result<T>
+where
+    T: Default + Serialize + serde::de::DeserializeOwned,
+{
+    use fs2::FileExt;
+    use std::io::{Read, Seek, Write};
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .read(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {what}: {}", path.display()))?;
+    file.lock_exclusive()
+        .with_context(|| format!("Failed to lock {what}: {}", path.display()))?;
+
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)
+        .with_context(|| format!("Failed to read {what}: {}", path.display()))?;
+    let mut state: T = serde_json::from_str(&contents).unwrap_or_default();
+
+    update(&mut state);
+
+    let json =
+        serde_json::to_vec_pretty(&state).with_context(|| format!("Failed to serialize {what}"))?;
+    file.set_len(0)?;
+    file.rewind()?;
+    file.write_all(&json)
+        .with_context(|| format!("Failed to write {what}: {}", path.display()))?;
+    let _ = file.unlock();
+    Ok(state)
 }
 
 pub fn cache_dir() -> Result<PathBuf> {
@@ -17,6 +182,11 @@ pub fn cache_dir() -> Result<PathBuf> {
         return Ok(PathBuf::from(dir).join("op_loader"));
     }
 
+    #[cfg(target_os = "windows")]
+    if let Some(dir) = std::env::var_os("LOCALAPPDATA") {
+        return Ok(PathBuf::from(dir).join("op_loader").join("cache"));
+    }
+
     let home = std::env::var_os("HOME").context("HOME environment variable not set")?;
     Ok(PathBuf::from(home).join(".cache").join("op_loader"))
 }
@@ -33,10 +203,11 @@ pub fn cache_path_for_account(
     account_id: &str,
     kind: CacheKind,
 ) -> PathBuf {
-    let prefix = match kind {
-        CacheKind::ResolvedVars => "op_inject_vars",
-    };
-    let filename = format!("{}_{}.cache", prefix, sanitize_account_id(account_id));
+    let filename = format!(
+        "{}_{}.cache",
+        kind.file_prefix(),
+        sanitize_account_id(account_id)
+    );
     cache_root.join(filename)
 }
 
@@ -51,12 +222,11 @@ pub fn lock_path_for_account(account_id: &str) -> Result<PathBuf> {
     )))
 }
 
-pub fn remove_cache_for_account(account_id: &str) -> Result<CacheRemoval> {
+pub fn remove_cache_for_account(account_id: &str, secure: bool) -> Result<CacheRemoval> {
     let mut removed_any = false;
     let path = cache_file_for_account(account_id, CacheKind::ResolvedVars)?;
     if path.exists() {
-        std::fs::remove_file(&path)
-            .with_context(|| format!("Failed to remove cache file: {}", path.display()))?;
+        remove_cache_file(&path, secure)?;
         removed_any = true;
     }
 
@@ -67,6 +237,204 @@ pub fn remove_cache_for_account(account_id: &str) -> Result<CacheRemoval> {
     }
 }
 
+/// Removes a cache file. With `secure`, the contents are overwritten with
+/// zeros and flushed to disk before unlinking. Copy-on-write filesystems and
+/// SSD wear leveling may still keep old blocks around, so this narrows the
+/// window for recovery rather than guaranteeing it.
+pub fn remove_cache_file(path: &Path, secure: bool) -> Result<()> {
+    if secure {
+        overwrite_with_zeros(path)?;
+    }
+    std::fs::remove_file(path)
+        .with_context(|| format!("Failed to remove cache file: {}", path.display()))
+}
+
+pub(crate) fn overwrite_with_zeros(path: &Path) -> Result<()> {
+    use std::io::Write;
+
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open cache file for wiping: {}", path.display()))?;
+    let len = file
+        .metadata()
+        .with_context(|| format!("Failed to read cache file metadata: {}", path.display()))?
+        .len();
+
+    let zeros = [0u8; 8192];
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..chunk])
+            .with_context(|| format!("Failed to wipe cache file: {}", path.display()))?;
+        remaining -= chunk as u64;
+    }
+    file.sync_all()
+        .with_context(|| format!("Failed to sync wiped cache file: {}", path.display()))
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheFileInfo {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// Cache entry files under `cache_root`, oldest first. Lock and temp files
+/// are not included.
+pub fn list_cache_files(cache_root: &Path) -> Result<Vec<CacheFileInfo>> {
+    if !cache_root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(cache_root)
+        .with_context(|| format!("Failed to read cache directory: {}", cache_root.display()))?
+    {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "cache") {
+            continue;
+        }
+
+        let metadata = std::fs::metadata(&path)
+            .with_context(|| format!("Failed to read cache metadata: {}", path.display()))?;
+        files.push(CacheFileInfo {
+            path,
+            size: metadata.len(),
+            modified: metadata.modified().unwrap_or(UNIX_EPOCH),
+        });
+    }
+
+    files.sort_by_key(|file| file.modified);
+    Ok(files)
+}
+
+/// Per-account counts of how `env inject` lookups were answered.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    #[serde(default)]
+    pub hits: u64,
+    #[serde(default)]
+    pub misses: u64,
+    #[serde(default)]
+    pub expired: u64,
+    #[serde(default)]
+    pub stale: u64,
+}
+
+impl CacheStats {
+    pub const fn lookups(&self) -> u64 {
+        self.hits + self.misses + self.expired + self.stale
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheEvent {
+    Hit,
+    Miss,
+    Expired,
+    Stale,
+}
+
+/// Stats live next to the cache files but don't use the `.cache` extension,
+/// so listing and eviction leave them alone.
+pub fn stats_path(cache_root: &Path) -> PathBuf {
+    cache_root.join("stats.json")
+}
+
+pub fn load_cache_stats(cache_root: &Path) -> Result<BTreeMap<String, CacheStats>> {
+    let path = stats_path(cache_root);
+    match std::fs::read_to_string(&path) {
+        Ok(contents) if contents.trim().is_empty() => Ok(BTreeMap::new()),
+        Ok(contents) => serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse cache stats: {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(err) => {
+            Err(err).with_context(|| format!("Failed to read cache stats: {}", path.display()))
+        }
+    }
+}
+
+/// Adds one lookup to the stats file. Shells starting in parallel update it
+/// under an exclusive lock so counts aren't lost.
+pub fn record_cache_event(cache_root: &Path, account_id: &str, event: CacheEvent) -> Result<()> {
+    update_locked_json(
+        &stats_path(cache_root),
+        "cache stats",
+        |stats: &mut BTreeMap<String, CacheStats>| {
+            let entry = stats.entry(account_id.to_string()).or_default();
+            match event {
+                CacheEvent::Hit => entry.hits += 1,
+                CacheEvent::Miss => entry.misses += 1,
+                CacheEvent::Expired => entry.expired += 1,
+                CacheEvent::Stale => entry.stale += 1,
+            }
+        },
+    )?;
+    Ok(())
+}
+
+/// Removes the oldest cache files until the total size is at most
+/// `max_bytes`. Files in `keep` are never evicted, so fresh writes always
+/// survive even if they alone exceed the limit.
+pub fn evict_to_size(cache_root: &Path, max_bytes: u64, keep: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let files = list_cache_files(cache_root)?;
+    let mut total: u64 = files.iter().map(|file| file.size).sum();
+    let mut evicted = Vec::new();
+
+    for file in files {
+        if total <= max_bytes {
+            break;
+        }
+        if keep.contains(&file.path) {
+            continue;
+        }
+
+        std::fs::remove_file(&file.path)
+            .with_context(|| format!("Failed to evict cache file: {}", file.path.display()))?;
+        total -= file.size;
+        evicted.push(file.path);
+    }
+
+    Ok(evicted)
+}
+
+/// Parses sizes like `512`, `64KB`, `10MB`, or `1GB` (1024-based) into bytes.
+pub fn parse_size(input: &str) -> Result<u64> {
+    let trimmed = input.trim();
+    let upper = trimmed.to_ascii_uppercase();
+    let (number, multiplier) = if let Some(n) = upper.strip_suffix("GB") {
+        (n, 1024 * 1024 * 1024)
+    } else if let Some(n) = upper.strip_suffix("MB") {
+        (n, 1024 * 1024)
+    } else if let Some(n) = upper.strip_suffix("KB") {
+        (n, 1024)
+    } else if let Some(n) = upper.strip_suffix('B') {
+        (n, 1)
+    } else {
+        (upper.as_str(), 1)
+    };
+
+    let value: u64 = number
+        .trim()
+        .parse()
+        .with_context(|| format!("Invalid size: {trimmed}"))?;
+    value
+        .checked_mul(multiplier)
+        .with_context(|| format!("Size too large: {trimmed}"))
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [(&str, u64); 3] = [("GB", 1 << 30), ("MB", 1 << 20), ("KB", 1 << 10)];
+    for (unit, scale) in UNITS {
+        if bytes >= scale {
+            #[allow(clippy::cast_precision_loss)]
+            return format!("{:.1} {unit}", bytes as f64 / scale as f64);
+        }
+    }
+    format!("{bytes} B")
+}
+
 fn sanitize_account_id(account_id: &str) -> String {
     let mut sanitized = String::with_capacity(account_id.len());
     for ch in account_id.chars() {
@@ -83,3 +451,205 @@ fn sanitize_account_id(account_id: &str) -> String {
         sanitized
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secure_wipe_zeroes_contents_before_removal() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("op_inject_vars_acct.cache");
+        std::fs::write(&path, vec![0xAB; 20_000]).unwrap();
+
+        overwrite_with_zeros(&path).unwrap();
+        let wiped = std::fs::read(&path).unwrap();
+        assert_eq!(wiped.len(), 20_000);
+        assert!(wiped.iter().all(|byte| *byte == 0));
+
+        remove_cache_file(&path, true).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn file_names_round_trip_to_kind_and_account() {
+        for kind in CacheKind::ALL {
+            let path = cache_path_for_account(Path::new("/cache"), "team@example.com", kind);
+            let name = path.file_name().unwrap().to_str().unwrap();
+
+            assert_eq!(
+                CacheKind::parse_file_name(name),
+                Some((kind, "team_example.com"))
+            );
+        }
+        assert_eq!(CacheKind::parse_file_name("telemetry.json"), None);
+    }
+
+    fn entry_created_at(created_at: SystemTime, ttl: Duration) -> CacheEntry {
+        CacheEntry {
+            created_at: unix_secs(created_at),
+            ..CacheEntry::new("{}".to_string(), ttl, config_hash("A: op://v/i/f\n"))
+        }
+    }
+
+    #[test]
+    fn fresh_within_ttl() {
+        let now = SystemTime::now();
+        let entry = entry_created_at(now - Duration::from_secs(30), Duration::from_secs(60));
+
+        assert!(entry.is_fresh(Duration::from_secs(60), now));
+    }
+
+    #[test]
+    fn stale_after_ttl() {
+        let now = SystemTime::now();
+        let entry = entry_created_at(now - Duration::from_secs(120), Duration::from_secs(60));
+
+        assert!(!entry.is_fresh(Duration::from_secs(60), now));
+    }
+
+    #[test]
+    fn caller_ttl_cannot_extend_stored_ttl() {
+        let now = SystemTime::now();
+        let entry = entry_created_at(now - Duration::from_secs(120), Duration::from_secs(60));
+
+        assert!(!entry.is_fresh(Duration::from_secs(600), now));
+    }
+
+    #[test]
+    fn stale_when_created_in_the_future() {
+        let now = SystemTime::now();
+        let entry = entry_created_at(now + Duration::from_secs(60), Duration::from_secs(600));
+
+        assert!(!entry.is_fresh(Duration::from_secs(600), now));
+    }
+
+    #[test]
+    fn stats_accumulate_per_account() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let root = dir.path().join("op_loader");
+
+        record_cache_event(&root, "a", CacheEvent::Hit).unwrap();
+        record_cache_event(&root, "a", CacheEvent::Hit).unwrap();
+        record_cache_event(&root, "a", CacheEvent::Expired).unwrap();
+        record_cache_event(&root, "b", CacheEvent::Miss).unwrap();
+
+        let stats = load_cache_stats(&root).unwrap();
+        assert_eq!(
+            stats["a"],
+            CacheStats {
+                hits: 2,
+                expired: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(stats["b"].lookups(), 1);
+        assert!(list_cache_files(&root).unwrap().is_empty());
+    }
+
+    #[test]
+    fn expires_soon_in_last_tenth_of_ttl() {
+        let now = SystemTime::now();
+        let ttl = Duration::from_secs(100);
+
+        let young = entry_created_at(now - Duration::from_secs(50), ttl);
+        let old = entry_created_at(now - Duration::from_secs(95), ttl);
+        let expired = entry_created_at(now - Duration::from_secs(150), ttl);
+
+        assert!(!young.expires_soon(ttl, now));
+        assert!(old.expires_soon(ttl, now));
+        assert!(!expired.expires_soon(ttl, now));
+    }
+
+    #[test]
+    fn stale_when_written_by_another_version() {
+        let now = SystemTime::now();
+        let entry = CacheEntry {
+            op_loader_version: "0.0.0-other".to_string(),
+            ..entry_created_at(now, Duration::from_secs(60))
+        };
+
+        assert!(!entry.is_fresh(Duration::from_secs(60), now));
+    }
+
+    fn write_file(path: &Path, size: usize, age: Duration) {
+        std::fs::write(path, vec![b'x'; size]).unwrap();
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn evicts_oldest_files_until_under_limit() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let oldest = dir.path().join("op_inject_vars_a.cache");
+        let middle = dir.path().join("op_inject_vars_b.cache");
+        let newest = dir.path().join("op_inject_vars_c.cache");
+        write_file(&oldest, 100, Duration::from_secs(300));
+        write_file(&middle, 100, Duration::from_secs(200));
+        write_file(&newest, 100, Duration::from_secs(100));
+        std::fs::write(dir.path().join("op_inject_a.lock"), vec![b'x'; 1000]).unwrap();
+
+        let evicted = evict_to_size(dir.path(), 200, &[newest]).unwrap();
+
+        assert_eq!(evicted, vec![oldest.clone()]);
+        assert!(!oldest.exists());
+        assert!(middle.exists());
+        assert!(dir.path().join("op_inject_a.lock").exists());
+    }
+
+    #[test]
+    fn never_evicts_the_kept_files() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let kept = dir.path().join("op_inject_vars_a.cache");
+        let also_kept = dir.path().join("op_inject_vars_b.cache");
+        let other = dir.path().join("op_inject_vars_c.cache");
+        write_file(&kept, 500, Duration::from_secs(300));
+        write_file(&also_kept, 100, Duration::from_secs(250));
+        write_file(&other, 100, Duration::from_secs(100));
+
+        let evicted = evict_to_size(dir.path(), 100, &[kept.clone(), also_kept.clone()]).unwrap();
+
+        assert_eq!(evicted, vec![other]);
+        assert!(kept.exists());
+        assert!(also_kept.exists());
+    }
+
+    #[test]
+    fn locked_json_updates_start_from_default_on_bad_state() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("nested").join("counts.json");
+
+        let first: BTreeMap<String, u32> =
+            update_locked_json(&path, "counts", |counts: &mut BTreeMap<String, u32>| {
+                *counts.entry("a".to_string()).or_default() += 1;
+            })
+            .unwrap();
+        assert_eq!(first["a"], 1);
+
+        std::fs::write(&path, "not json").unwrap();
+        let reset = update_locked_json(&path, "counts", |counts: &mut BTreeMap<String, u32>| {
+            *counts.entry("a".to_string()).or_default() += 1;
+        })
+        .unwrap();
+        assert_eq!(reset["a"], 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\n  \"a\": 1\n}");
+    }
+
+    #[test]
+    fn parses_sizes_with_units() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("64KB").unwrap(), 64 * 1024);
+        assert_eq!(parse_size("10 mb").unwrap(), 10 * 1024 * 1024);
+        assert_eq!(parse_size("1GB").unwrap(), 1024 * 1024 * 1024);
+        assert!(parse_size("lots").is_err());
+    }
+
+    #[test]
+    fn config_hash_is_stable_and_input_sensitive() {
+        let a = config_hash("A: op://v/i/f\n");
+
+        assert_eq!(a, config_hash("A: op://v/i/f\n"));
+        assert_ne!(a, config_hash("A: op://v/i/f\nB: op://v/i/g\n"));
+        assert_eq!(a.len(), 64);
+    }
+}
@@ -1,24 +1,44 @@
 use anyhow::{Context, Result};
-#[cfg(target_os = "macos")]
 use base64::Engine;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-#[cfg(target_os = "macos")]
 use rand_core::RngCore;
 
-use crate::app::{InjectVarConfig, OpLoadConfig, TemplatedFile};
-#[cfg(target_os = "macos")]
-use crate::cache::cache_file_for_account;
+use crate::app::{Account, InjectVarConfig, ManagedFile, OpLoadConfig, TemplatedFile};
+use crate::backoff::{load_backoff, update_backoff};
 use crate::cache::{
-    CacheKind, CacheRemoval, cache_dir, ensure_cache_dir, lock_path_for_account,
+    CacheEntry, CacheEvent, CacheKind, CacheRemoval, cache_dir, cache_file_for_account,
+    config_hash, ensure_cache_dir, evict_to_size, format_size, list_cache_files, load_cache_stats,
+    lock_path_for_account, parse_size, record_cache_event, remove_cache_file,
     remove_cache_for_account,
 };
-#[cfg(target_os = "macos")]
+use crate::config_import::{EntryKind, apply_merge, load_import, plan_merge, run_merge_screen};
+use crate::config_migration::{backup_path, plan_migrations};
+use crate::desktop_notification::notify;
+use crate::git_hook::{SecretIndex, check_staged, install_hook, repo_root, staged_files};
+use crate::inject_timing::Batching;
+use crate::inventory::{ExportFormat, export, parse_item_list};
+#[cfg(any(target_os = "macos", target_os = "windows"))]
 use crate::keychain::{assert_keychain_available, delete_key, get_or_create_key};
+use crate::make_env::{MakeFormat, format_assignments};
+use crate::op_client::{OpClient, SubprocessOpClient};
+use crate::op_error::annotate;
+use crate::op_version::OpFeature;
+use crate::output_sink::OutputSink;
+use crate::policy::{load_policy, policy_path};
+use crate::porcelain;
+use crate::shell::{
+    ApplyTarget, Shell, fish_universal_erase_script, fish_universal_script, run_fish,
+};
+use crate::telemetry::{TelemetryReport, load_report, send_report, update_report};
+use crate::template_history::{
+    DiffLine, RenderRecord, history_path, line_diff, load_history, push_record, save_history,
+};
+use crate::template_naming::{TemplateNaming, TemplateRename, plan_renames};
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 struct LegacyOpLoadConfig {
@@ -60,6 +80,257 @@ pub enum Command {
         #[command(subcommand)]
         action: TemplateAction,
     },
+    Item {
+        #[command(subcommand)]
+        action: ItemAction,
+    },
+    /// Set up this machine from an existing config: check accounts, sign in, warm caches, and render templates
+    Bootstrap {
+        /// Cache op inject output per account for this duration (e.g. 30s, 10m, 1h, 2d)
+        #[arg(long, value_name = "DURATION")]
+        cache_ttl: Option<String>,
+        /// Max time to wait on another process populating the cache (e.g. 5s, 30s, 1m)
+        #[arg(long, value_name = "DURATION", default_value = "5s")]
+        cache_lock_wait: String,
+    },
+    /// Keep resolved secrets in memory and serve them to `env inject --from-agent` over a unix socket
+    Agent {
+        #[command(subcommand)]
+        action: Option<AgentAction>,
+        /// Shut down after this long without requests (e.g. 30m, 8h); 0s keeps it running
+        #[arg(long, value_name = "DURATION", default_value = "1h")]
+        idle_timeout: String,
+        /// Resolve secrets again once they're this old (e.g. 15m, 8h); 0s keeps them until the mappings change
+        #[arg(long, value_name = "DURATION", default_value = "1h")]
+        cache_ttl: String,
+    },
+    /// Inspect or send anonymous usage counters (opt in with `config set telemetry.enabled true`)
+    Telemetry {
+        #[command(subcommand)]
+        action: TelemetryAction,
+    },
+    /// Migrate the config file from older layouts, keeping a backup of the original
+    UpgradeConfig {
+        /// Print the changes and backup location without touching the file
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Git hooks that keep rendered secrets out of commits
+    Hook {
+        #[command(subcommand)]
+        action: HookAction,
+    },
+    /// Managed environment variables
+    Var {
+        #[command(subcommand)]
+        action: VarAction,
+    },
+    /// Files placed from 1Password document items, such as certificates or kubeconfigs
+    File {
+        #[command(subcommand)]
+        action: FileAction,
+    },
+    /// SSH Key items and the 1Password SSH agent
+    Ssh {
+        #[command(subcommand)]
+        action: SshAction,
+    },
+    /// Troubleshooting helpers for bug reports
+    #[command(hide = true)]
+    Debug {
+        #[command(subcommand)]
+        action: DebugAction,
+    },
+    /// Print managed variables as make or just assignments, e.g. for an `include`d .mk file
+    MakeEnv {
+        /// Assignment syntax
+        #[arg(long, value_enum, default_value_t)]
+        format: MakeFormat,
+        /// Also export the variables to every recipe's environment
+        #[arg(long)]
+        export: bool,
+        /// Cache op inject output per account for this duration (e.g. 30s, 10m, 1h, 2d)
+        #[arg(long, value_name = "DURATION")]
+        cache_ttl: Option<String>,
+        /// Max time to wait on another process populating the cache (e.g. 5s, 30s, 1m)
+        #[arg(long, value_name = "DURATION", default_value = "5s")]
+        cache_lock_wait: String,
+        #[command(flatten)]
+        sink: SinkArgs,
+    },
+    /// Check the local setup for problems, such as config or cache files other users can read
+    Doctor,
+    /// Restrict the config file, templates, and cache to your user (700 for directories, 600 for files)
+    FixPerms {
+        /// List what would change without changing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Summarize variables, templates, caches, and the agent
+    Status {
+        /// Stable tab-separated `key<TAB>value` lines for scripts
+        #[arg(long)]
+        porcelain: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DebugAction {
+    /// Write a redacted snapshot of config and cache state to a JSON file
+    State {
+        /// Where to write the snapshot (default: ./op-loader-state-<timestamp>.json)
+        #[arg(long, value_name = "PATH")]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VarAction {
+    /// List managed variables with their account and reference
+    List {
+        /// Stable tab-separated `name<TAB>account_id<TAB>op_reference` lines for scripts
+        #[arg(long)]
+        porcelain: bool,
+    },
+    /// Resolve one managed variable with `op read` and report its length, not its value
+    Test {
+        /// The variable's name, as shown by `var list`
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum FileAction {
+    /// Place a document item's file at PATH now and on every `env inject` and `bootstrap`
+    Add {
+        path: String,
+        /// The document item's ID or name
+        #[arg(long)]
+        item: String,
+        /// The vault holding the item
+        #[arg(long)]
+        vault: String,
+        /// The account holding the vault (default: the configured default account)
+        #[arg(long)]
+        account: Option<String>,
+    },
+    /// List managed files with their document items
+    List,
+    /// Stop managing the file at PATH; the file itself is left in place
+    Remove { path: String },
+    /// Download every managed file again
+    Sync,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SshAction {
+    /// Export an SSH Key item's public key to ~/.ssh and add a Host block for it to ~/.ssh/config
+    Setup {
+        /// The SSH Key item's ID or name
+        #[arg(long)]
+        item: String,
+        /// The vault holding the item
+        #[arg(long)]
+        vault: String,
+        /// The account holding the vault (default: the configured default account)
+        #[arg(long)]
+        account: Option<String>,
+        /// Host pattern for the config block (default: the key's file name)
+        #[arg(long)]
+        host: Option<String>,
+        /// HostName to connect to, if it differs from the host pattern
+        #[arg(long)]
+        hostname: Option<String>,
+        /// User to log in as
+        #[arg(long)]
+        user: Option<String>,
+        /// Only export the public key; leave ~/.ssh/config alone
+        #[arg(long)]
+        no_config: bool,
+    },
+    /// Print commands that point ssh-add and ssh at the 1Password SSH agent
+    Agent {
+        /// Shell syntax for the printed statements
+        #[arg(long, value_enum, default_value_t)]
+        shell: Shell,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HookAction {
+    /// Fail if staged files are template targets or contain rendered secrets
+    PreCommit {
+        /// Also resolve managed variables and look for their values (calls op)
+        #[arg(long)]
+        check_values: bool,
+    },
+    /// Install the pre-commit hook in the current repository
+    Install {
+        /// Make the installed hook pass --check-values
+        #[arg(long)]
+        check_values: bool,
+        /// Replace an existing pre-commit hook
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+impl Command {
+    /// The subcommand name recorded by telemetry.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Config { .. } => "config",
+            Self::Env { .. } => "env",
+            Self::Cache { .. } => "cache",
+            Self::Template { .. } => "template",
+            Self::Item { .. } => "item",
+            Self::Bootstrap { .. } => "bootstrap",
+            Self::Agent { .. } => "agent",
+            Self::Telemetry { .. } => "telemetry",
+            Self::UpgradeConfig { .. } => "upgrade-config",
+            Self::Hook { .. } => "hook",
+            Self::Var { .. } => "var",
+            Self::File { .. } => "file",
+            Self::Ssh { .. } => "ssh",
+            Self::Status { .. } => "status",
+            Self::MakeEnv { .. } => "make-env",
+            Self::Doctor => "doctor",
+            Self::FixPerms { .. } => "fix-perms",
+            Self::Debug { .. } => "debug",
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TelemetryAction {
+    /// Print the recorded counters
+    Show,
+    /// Post the recorded counters to `telemetry.endpoint`, then reset them
+    Send,
+    /// Discard the recorded counters
+    Reset,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ItemAction {
+    /// List item metadata in a vault (titles, categories, tags, dates; never values)
+    List {
+        /// Vault name or ID
+        #[arg(long)]
+        vault: String,
+        /// Account ID (defaults to op's current account)
+        #[arg(long)]
+        account: Option<String>,
+        /// Write an inventory in this format instead of a table
+        #[arg(long, value_enum)]
+        export: Option<ExportFormat>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AgentAction {
+    /// Stop the running agent
+    Stop,
 }
 
 #[derive(Subcommand, Debug)]
@@ -71,9 +342,68 @@ pub enum EnvAction {
         /// Max time to wait on another process populating the cache (e.g. 5s, 30s, 1m)
         #[arg(long, value_name = "DURATION", default_value = "5s")]
         cache_lock_wait: String,
+        /// Fetch secrets from a running `op-loader agent` instead of calling op
+        #[arg(long, conflicts_with = "cache_ttl")]
+        from_agent: bool,
+        /// Shell syntax for the printed statements
+        #[arg(long, value_enum, default_value_t)]
+        shell: Shell,
+        /// Set the variables directly instead of printing statements
+        #[arg(long, value_enum, conflicts_with_all = ["shell", "fd", "out"])]
+        apply: Option<ApplyTarget>,
+        /// Add or override a mapping for this run only (repeatable; not saved to config)
+        #[arg(
+            long = "set",
+            value_name = "NAME=op://REF",
+            conflicts_with = "from_agent"
+        )]
+        scratch: Vec<String>,
+        /// Account for `--set` mappings (defaults to the config's default account)
+        #[arg(long, value_name = "ACCOUNT_ID", requires = "scratch")]
+        set_account: Option<String>,
+        #[command(flatten)]
+        sink: SinkArgs,
     },
     /// Unset all managed environment variables
-    Unset,
+    Unset {
+        /// Shell syntax for the printed statements
+        #[arg(long, value_enum, default_value_t)]
+        shell: Shell,
+        /// Erase the variables where `env inject --apply` set them instead of printing statements
+        #[arg(long, value_enum, conflicts_with_all = ["shell", "fd", "out"])]
+        apply: Option<ApplyTarget>,
+        #[command(flatten)]
+        sink: SinkArgs,
+    },
+    /// Print the lines that load managed variables from a shell's startup file
+    Profile {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct SinkArgs {
+    /// Write shell statements to this open file descriptor instead of stdout
+    #[arg(long, value_name = "FD", conflicts_with = "out")]
+    fd: Option<u32>,
+    /// Write shell statements to this fifo or file (created with mode 600) instead of stdout
+    #[arg(long, value_name = "PATH")]
+    out: Option<PathBuf>,
+}
+
+impl SinkArgs {
+    /// Only loads the config for `--out`, which needs `allowed_sync_paths`.
+    fn into_sink(self) -> Result<OutputSink> {
+        let allowed_sync_paths = if self.out.is_some() {
+            let config: OpLoadConfig =
+                confy::load("op_loader", None).context("Failed to load configuration")?;
+            config.allowed_sync_paths
+        } else {
+            Vec::new()
+        };
+        Ok(OutputSink::new(self.fd, self.out, allowed_sync_paths))
+    }
 }
 
 #[derive(Subcommand, Debug)]
@@ -82,7 +412,20 @@ pub enum ConfigAction {
         #[arg(short, long)]
         key: String,
     },
+    Set {
+        #[arg(short, long)]
+        key: String,
+        /// New value; an empty string unsets optional keys
+        value: String,
+    },
     Path,
+    /// Check the config against the organization policy file
+    Validate,
+    /// Merge variables and templates from another config file, choosing per entry
+    Import {
+        /// Config file to import (e.g. a teammate's default-config.toml)
+        path: PathBuf,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -91,16 +434,123 @@ pub enum TemplateAction {
     Add {
         /// Path to the file to manage (e.g., ~/.npmrc)
         path: String,
+        /// Copy the file as it is, without looking for secrets to replace
+        #[arg(long)]
+        no_scan: bool,
+    },
+    /// Open a file's template in $VISUAL or $EDITOR, then check its placeholders
+    Edit {
+        /// Path to the managed file
+        path: String,
+    },
+    /// Convert a file containing resolved secrets into a template
+    Adopt {
+        /// Path to the file to adopt (e.g., ~/.npmrc)
+        path: String,
+        /// Reuse cached op inject output younger than this duration (e.g. 10m)
+        #[arg(long, value_name = "DURATION")]
+        cache_ttl: Option<String>,
+        /// Max time to wait on another process populating the cache (e.g. 5s, 30s, 1m)
+        #[arg(long, value_name = "DURATION", default_value = "5s")]
+        cache_lock_wait: String,
     },
     /// List all managed template files
-    List,
+    List {
+        /// Stable tab-separated `target<TAB>template_path<TAB>ok|missing` lines for scripts
+        #[arg(long)]
+        porcelain: bool,
+    },
     /// Stop managing a file as a template
     Remove {
         /// Path to the managed file
         path: String,
     },
-    /// Render all templates (substituting variables)
-    Render,
+    /// Limit which variables a template can use, or lift the limit with --all
+    Scope {
+        /// Path to the managed file
+        path: String,
+        /// Variables the template may use
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        vars: Vec<String>,
+        /// Let the template use every variable again
+        #[arg(long)]
+        all: bool,
+    },
+    /// Render a file into op-loader's state directory and make the file a symlink to it
+    Link {
+        /// Path to the managed file
+        path: String,
+    },
+    /// Turn a linked file back into a regular one, restoring what was there before linking
+    Unlink {
+        /// Path to the managed file
+        path: String,
+    },
+    /// Render templates (substituting variables); all of them unless paths are given
+    Render {
+        /// Only render the template for this file
+        path: Option<String>,
+        /// Only render the template for this file (repeatable)
+        #[arg(long, value_name = "PATH")]
+        only: Vec<String>,
+        /// Overwrite files edited by hand since their last render instead of asking or skipping them
+        #[arg(long, conflicts_with = "stdout")]
+        force: bool,
+        /// Print the rendered file instead of writing it
+        #[arg(long)]
+        stdout: bool,
+        /// With --stdout, print variable values as ********
+        #[arg(long, requires = "stdout")]
+        mask: bool,
+    },
+    /// Render templates in memory and fail if any rendered file differs, for CI and dotfile repos
+    Check {
+        /// Only check the templates for these files
+        paths: Vec<String>,
+    },
+    /// Re-render templates whenever they or the config change, until interrupted
+    Watch {
+        /// Only watch the templates for these files
+        paths: Vec<String>,
+    },
+    /// Put back a rendered file as it was before a render, from its backups
+    Restore {
+        /// Path to the managed file
+        path: String,
+        /// List the backups, newest first, instead of restoring
+        #[arg(long)]
+        list: bool,
+        /// Which backup to restore, counting from 1 for the newest
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        backup: usize,
+    },
+    /// Show previous renders of a template
+    History {
+        /// Path to the managed file
+        path: String,
+    },
+    /// Show what changed between the latest render and an earlier one
+    Diff {
+        /// Path to the managed file
+        path: String,
+        /// Earlier render to compare against: `previous` or how many renders back (1 = previous)
+        #[arg(long, default_value = "previous")]
+        against: String,
+    },
+    /// Put back an earlier render of a file from its render history
+    Rollback {
+        /// Path to the managed file
+        path: String,
+        /// Which render to put back, counting back from the latest as `template history` does
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        to: usize,
+    },
+    /// Rename template files to follow `templates.naming`, so targets with the same file name stop sharing one
+    MigrateNames {
+        /// Print the renames without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -110,6 +560,36 @@ pub enum CacheAction {
         /// Clear cached output for a specific account ID
         #[arg(long)]
         account: Option<String>,
+        /// Overwrite cache files with zeros before deleting them
+        #[arg(long)]
+        secure: bool,
+    },
+    /// List cache files with their sizes
+    List,
+    /// Show per-account cache hit/miss counts
+    Stats,
+    /// Show each cache's kind, account, size, and age
+    Status {
+        /// Stable tab-separated `kind<TAB>account<TAB>size_bytes<TAB>age_secs` lines for scripts
+        #[arg(long)]
+        porcelain: bool,
+    },
+    /// Re-resolve one account's cache (started in the background when a cache nears expiry)
+    #[command(hide = true)]
+    Refresh {
+        #[arg(long)]
+        account: String,
+        #[arg(long, value_name = "DURATION")]
+        ttl: String,
+    },
+    /// Resolve every account and write fresh caches without printing secrets
+    Warm {
+        /// How long the warmed caches stay valid (e.g. 30s, 10m, 1h, 2d)
+        #[arg(long, value_name = "DURATION", default_value = "1h")]
+        ttl: String,
+        /// Max time to wait on another process populating the cache (e.g. 5s, 30s, 1m)
+        #[arg(long, value_name = "DURATION", default_value = "5s")]
+        cache_lock_wait: String,
     },
 }
 
@@ -136,8 +616,69 @@ fn handle_config_action_with_path(action: ConfigAction, config_path: Option<&Pat
                     Some(preferred_account) => println!("{preferred_account}"),
                     None => println!("(not set)"),
                 },
+                "cache.max_size" => match &config.cache.max_size {
+                    Some(max_size) => println!("{max_size}"),
+                    None => println!("(not set)"),
+                },
+                "telemetry.enabled" => println!("{}", config.telemetry.enabled),
+                "security.strict_permissions" => {
+                    println!("{}", config.security.strict_permissions);
+                }
+                "templates.naming" => println!("{}", config.templates.naming.as_str()),
+                "telemetry.endpoint" => match &config.telemetry.endpoint {
+                    Some(endpoint) => println!("{endpoint}"),
+                    None => println!("(not set)"),
+                },
+                _ => anyhow::bail!("Unknown config key: '{key}'."),
+            }
+            Ok(())
+        }
+        ConfigAction::Set { key, value } => {
+            info!("Setting config key: {key}");
+
+            let mut config: OpLoadConfig = if let Some(path) = config_path {
+                confy::load_path(path).context("Failed to load configuration")?
+            } else {
+                confy::load("op_loader", None).context("Failed to load configuration")?
+            };
+
+            let optional = |value: String| (!value.is_empty()).then_some(value);
+            match key.as_str() {
+                "default_account_id" => config.default_account_id = optional(value),
+                "cache.max_size" => {
+                    if !value.is_empty() {
+                        parse_size(&value)?;
+                    }
+                    config.cache.max_size = optional(value);
+                }
+                "telemetry.enabled" => {
+                    config.telemetry.enabled = value.parse().with_context(|| {
+                        format!("Invalid value '{value}' for telemetry.enabled; use true or false")
+                    })?;
+                }
+                "telemetry.endpoint" => config.telemetry.endpoint = optional(value),
+                "security.strict_permissions" => {
+                    config.security.strict_permissions = value.parse().with_context(|| {
+                        format!(
+                            "Invalid value '{value}' for security.strict_permissions; use true or false"
+                        )
+                    })?;
+                }
+                "templates.naming" => {
+                    config.templates.naming = TemplateNaming::parse(&value).with_context(|| {
+                        format!(
+                            "Invalid value '{value}' for templates.naming; use hashed or mirrored"
+                        )
+                    })?;
+                }
                 _ => anyhow::bail!("Unknown config key: '{key}'."),
             }
+
+            if let Some(path) = config_path {
+                confy::store_path(path, &config).context("Failed to save configuration")?;
+            } else {
+                confy::store("op_loader", None, &config).context("Failed to save configuration")?;
+            }
             Ok(())
         }
         ConfigAction::Path => {
@@ -157,6 +698,90 @@ fn handle_config_action_with_path(action: ConfigAction, config_path: Option<&Pat
             }
             Ok(())
         }
+        ConfigAction::Validate => {
+            info!("Validating config against policy");
+
+            let Some(policy) = load_policy()? else {
+                println!(
+                    "No policy file found at {}; nothing to validate.",
+                    policy_path()?.display()
+                );
+                return Ok(());
+            };
+
+            let config: OpLoadConfig = if let Some(path) = config_path {
+                confy::load_path(path).context("Failed to load configuration")?
+            } else {
+                confy::load("op_loader", None).context("Failed to load configuration")?
+            };
+
+            let violations = policy.check_config(&config);
+            if violations.is_empty() {
+                println!("Config satisfies policy.");
+                return Ok(());
+            }
+
+            for violation in &violations {
+                println!("{violation}");
+            }
+            anyhow::bail!("Config violates policy: {} issue(s)", violations.len());
+        }
+        ConfigAction::Import { path } => {
+            info!("Importing config from {}", path.display());
+
+            let theirs = load_import(&path)?;
+            let mut config: OpLoadConfig = if let Some(path) = config_path {
+                confy::load_path(path).context("Failed to load configuration")?
+            } else {
+                confy::load("op_loader", None).context("Failed to load configuration")?
+            };
+
+            let entries = plan_merge(&config, &theirs);
+            if entries.is_empty() {
+                println!("Nothing to import: {} matches your config.", path.display());
+                return Ok(());
+            }
+
+            let Some(entries) = run_merge_screen(entries)? else {
+                println!("Import cancelled; config unchanged.");
+                return Ok(());
+            };
+
+            let taken = apply_merge(&mut config, &theirs, &entries);
+            if taken == 0 {
+                println!("No entries selected; config unchanged.");
+                return Ok(());
+            }
+            if let Some(policy) = load_policy()? {
+                crate::config_import::ensure_within_policy(&policy, &config)?;
+            }
+
+            if let Some(path) = config_path {
+                confy::store_path(path, &config).context("Failed to save configuration")?;
+            } else {
+                confy::store("op_loader", None, &config).context("Failed to save configuration")?;
+            }
+            println!(
+                "Imported {taken} entr{}.",
+                if taken == 1 { "y" } else { "ies" }
+            );
+
+            let templates_dir = get_templates_dir()?;
+            for entry in entries
+                .iter()
+                .filter(|entry| entry.take_theirs && entry.kind == EntryKind::Template)
+            {
+                if !templates_dir.join(&entry.theirs).exists() {
+                    eprintln!(
+                        "Warning: template '{}' for {} is not in {}; copy it from your teammate.",
+                        entry.theirs,
+                        entry.key,
+                        templates_dir.display()
+                    );
+                }
+            }
+            Ok(())
+        }
     }
 }
 
@@ -165,12 +790,35 @@ pub fn handle_env_action(action: EnvAction) -> Result<()> {
         EnvAction::Inject {
             cache_ttl,
             cache_lock_wait,
-        } => handle_env_injection(cache_ttl.as_deref(), Some(cache_lock_wait.as_str())),
-        EnvAction::Unset => handle_env_unset(),
+            from_agent,
+            shell,
+            apply,
+            scratch,
+            set_account,
+            sink,
+        } => handle_env_injection(
+            cache_ttl.as_deref(),
+            Some(cache_lock_wait.as_str()),
+            from_agent,
+            shell,
+            apply,
+            &ScratchVars {
+                mappings: scratch,
+                account_id: set_account,
+            },
+            &sink.into_sink()?,
+        ),
+        EnvAction::Unset { shell, apply, sink } => {
+            handle_env_unset(shell, apply, &sink.into_sink()?)
+        }
+        EnvAction::Profile { shell } => {
+            print!("{}", shell.profile_snippet());
+            Ok(())
+        }
     }
 }
 
-pub fn handle_env_unset() -> Result<()> {
+pub fn handle_env_unset(shell: Shell, apply: Option<ApplyTarget>, sink: &OutputSink) -> Result<()> {
     info!("Unsetting managed environment variables");
 
     let config: OpLoadConfig =
@@ -187,42 +835,128 @@ pub fn handle_env_unset() -> Result<()> {
         config.inject_vars.len()
     );
 
-    let keys: Vec<&String> = config.inject_vars.keys().collect();
+    let names = exported_names(&config);
 
-    let output = format_unsets(keys);
-
-    print!("{output}");
+    match apply {
+        Some(ApplyTarget::FishUniversal) => {
+            run_fish(&fish_universal_erase_script(
+                names.iter().map(String::as_str),
+            ))?;
+        }
+        None => sink.write(&format_unsets(&names, shell))?,
+    }
 
     info!("Finished unsetting env var mappings");
 
     Ok(())
 }
 
-fn format_unsets(keys: Vec<&String>) -> String {
-    let mut output = String::new();
-    for key in keys {
-        output.push_str("unset ");
-        output.push_str(key);
-        output.push('\n');
-    }
-    output
+/// The names `env inject` exports the configured vars under, prefixes
+/// included, in sorted order.
+fn exported_names(config: &OpLoadConfig) -> Vec<String> {
+    let mut names: Vec<String> = config
+        .inject_vars
+        .iter()
+        .map(|(name, var)| var.exported_name(name))
+        .collect();
+    names.sort();
+    names
 }
 
-pub fn handle_env_injection(cache_ttl: Option<&str>, cache_lock_wait: Option<&str>) -> Result<()> {
-    info!("Loading environment variable mappings");
-
-    let mut config: OpLoadConfig =
-        confy::load("op_loader", None).context("Failed to load configuration")?;
-    debug!("Config loaded successfully");
+fn format_unsets(names: &[String], shell: Shell) -> String {
+    names.iter().map(|name| shell.unset(name)).collect()
+}
 
-    if config.inject_vars.is_empty() {
-        let legacy: LegacyOpLoadConfig =
-            confy::load("op_loader", None).context("Failed to load configuration")?;
+/// `env inject --set` mappings, which join the configured ones for a single
+/// run and are never written back to the config.
+#[derive(Debug, Default)]
+pub struct ScratchVars {
+    pub mappings: Vec<String>,
+    pub account_id: Option<String>,
+}
 
-        if legacy.inject_vars.is_empty() {
-            info!("No environment variables configured");
-            eprintln!("No environment variables configured. Use the TUI to add mappings.");
-            return Ok(());
+impl ScratchVars {
+    /// Adds the mappings to `config.inject_vars`, replacing configured
+    /// variables of the same name. Nothing is added if any mapping breaks
+    /// `policy`. Returns how many were added.
+    fn merge_into(
+        &self,
+        config: &mut OpLoadConfig,
+        policy: Option<&crate::policy::Policy>,
+    ) -> Result<usize> {
+        if self.mappings.is_empty() {
+            return Ok(0);
+        }
+        let account_id = self
+            .account_id
+            .clone()
+            .or_else(|| config.default_account_id.clone())
+            .context("No account for --set mappings. Pass --set-account or pick a default account in the TUI.")?;
+
+        let mut vars = Vec::with_capacity(self.mappings.len());
+        for mapping in &self.mappings {
+            let (name, op_reference) = parse_scratch_mapping(mapping)?;
+            let var = InjectVarConfig {
+                account_id: account_id.clone(),
+                op_reference: op_reference.to_string(),
+                prefix: None,
+            };
+            if let Some(violation) =
+                policy.and_then(|policy| policy.check_mapping(name, &var).into_iter().next())
+            {
+                anyhow::bail!("--set {name} is blocked by policy: {}", violation.message);
+            }
+            vars.push((name, var));
+        }
+
+        for (name, var) in vars {
+            if config.inject_vars.contains_key(name) {
+                info!("--set overrides the configured mapping for {name}");
+            }
+            config.inject_vars.insert(name.to_string(), var);
+        }
+        Ok(self.mappings.len())
+    }
+}
+
+fn parse_scratch_mapping(mapping: &str) -> Result<(&str, &str)> {
+    let (name, op_reference) = mapping
+        .split_once('=')
+        .with_context(|| format!("Expected NAME=op://REF, got '{mapping}'"))?;
+    let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_name {
+        anyhow::bail!("'{name}' is not a valid environment variable name");
+    }
+    if !op_reference.starts_with("op://") {
+        anyhow::bail!("The reference for {name} must start with op://, got '{op_reference}'");
+    }
+    Ok((name, op_reference))
+}
+
+pub fn handle_env_injection(
+    cache_ttl: Option<&str>,
+    cache_lock_wait: Option<&str>,
+    from_agent: bool,
+    shell: Shell,
+    apply: Option<ApplyTarget>,
+    scratch: &ScratchVars,
+    sink: &OutputSink,
+) -> Result<()> {
+    info!("Loading environment variable mappings");
+
+    let mut config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    debug!("Config loaded successfully");
+
+    if config.inject_vars.is_empty() {
+        let legacy: LegacyOpLoadConfig =
+            confy::load("op_loader", None).context("Failed to load configuration")?;
+
+        if legacy.inject_vars.is_empty() && scratch.mappings.is_empty() {
+            info!("No environment variables configured");
+            eprintln!("No environment variables configured. Use the TUI to add mappings.");
+            return Ok(());
         }
 
         eprintln!(
@@ -232,54 +966,43 @@ pub fn handle_env_injection(cache_ttl: Option<&str>, cache_lock_wait: Option<&st
         confy::store("op_loader", None, &config).context("Failed to save configuration")?;
     }
 
+    // Merged after the legacy migration above, which stores the config.
+    let scratch_count = scratch.merge_into(&mut config, load_policy()?.as_ref())?;
+
     if config.inject_vars.is_empty() {
         return Ok(());
     }
 
     info!("Processing {} env var mappings", config.inject_vars.len());
 
-    let vars_by_account = group_vars_by_account(&config.inject_vars);
-
-    #[cfg(not(target_os = "macos"))]
-    if cache_ttl.is_some() {
-        anyhow::bail!("Cache is only supported on macOS.");
-    }
+    let file_cache_ttl = cache_ttl.map(parse_duration).transpose()?.flatten();
+    let results = if from_agent {
+        let mut results: Vec<AccountResolution> = crate::agent::request_vars()?
+            .into_iter()
+            .map(|(account_id, vars)| (account_id, Ok(vars)))
+            .collect();
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        results
+    } else {
+        let vars_by_account = group_vars_by_account(&config.inject_vars);
 
-    let cache_ttl = cache_ttl.map(parse_duration).transpose()?.unwrap_or(None);
-    let cache_lock_wait =
-        parse_duration(cache_lock_wait.unwrap_or("5s"))?.unwrap_or_else(|| Duration::from_secs(5));
+        #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+        if cache_ttl.is_some() {
+            anyhow::bail!("Cache is only supported on macOS and Windows.");
+        }
 
-    // Build the input string for each account up front (cheap, no I/O).
-    let account_inputs: Vec<(&str, String)> = vars_by_account
-        .into_iter()
-        .map(|(account_id, vars)| {
-            let mut input = String::new();
-            for (env_var_name, var_config) in vars {
-                use std::fmt::Write;
-                writeln!(input, "{env_var_name}: {}", var_config.op_reference)
-                    .expect("write to String cannot fail");
-            }
-            (account_id, input)
-        })
-        .collect();
+        let cache_ttl = cache_ttl.map(parse_duration).transpose()?.unwrap_or(None);
+        let cache_lock_wait = parse_duration(cache_lock_wait.unwrap_or("5s"))?
+            .unwrap_or_else(|| Duration::from_secs(5));
 
-    // Resolve all accounts in parallel — each thread acquires its own
-    // per-account lock, so different accounts never block each other.
-    let results: Vec<(String, Result<std::collections::HashMap<String, String>>)> =
-        std::thread::scope(|s| {
-            account_inputs
-                .iter()
-                .map(|(account_id, input)| {
-                    let account_id = *account_id;
-                    s.spawn(move || {
-                        let result =
-                            load_resolved_vars(account_id, input, cache_ttl, cache_lock_wait);
-                        (account_id.to_string(), result)
-                    })
-                })
-                .map(|h| h.join().expect("account resolver thread panicked"))
-                .collect()
-        });
+        resolve_vars_by_account(
+            &SubprocessOpClient,
+            vars_by_account,
+            cache_ttl,
+            CacheRead::Use,
+            cache_lock_wait,
+        )?
+    };
 
     let mut combined_output = String::new();
     let mut resolved_vars_by_account: std::collections::HashMap<
@@ -290,7 +1013,18 @@ pub fn handle_env_injection(cache_ttl: Option<&str>, cache_lock_wait: Option<&st
     for (account_id, result) in results {
         match result {
             Ok(resolved) => {
-                combined_output.push_str(&format_exports(&resolved));
+                let exported = with_prefixes(&config, &resolved);
+                combined_output.push_str(&match apply {
+                    Some(ApplyTarget::FishUniversal) => {
+                        let mut vars: Vec<(&str, &str)> = exported
+                            .iter()
+                            .map(|(k, v)| (k.as_str(), v.as_str()))
+                            .collect();
+                        vars.sort_unstable();
+                        fish_universal_script(vars)
+                    }
+                    None => format_exports(&exported, shell),
+                });
                 resolved_vars_by_account.insert(account_id, resolved);
             }
             Err(err) => {
@@ -299,1106 +1033,5452 @@ pub fn handle_env_injection(cache_ttl: Option<&str>, cache_lock_wait: Option<&st
         }
     }
 
-    print!("{combined_output}");
+    match apply {
+        Some(ApplyTarget::FishUniversal) => run_fish(&combined_output)?,
+        None => sink.write(&combined_output)?,
+    }
 
     info!("Finished processing env var mappings");
 
-    if !config.templated_files.is_empty() {
-        info!("Rendering {} template files", config.templated_files.len());
-        render_templates(&config, &resolved_vars_by_account)?;
+    if scratch_count > 0 {
+        // Scratch values are for trying a reference out; keep them out of
+        // rendered files.
+        info!("Skipping template rendering because --set mappings were given");
+    } else {
+        if !config.templated_files.is_empty() {
+            info!("Rendering {} template files", config.templated_files.len());
+            render_templates(
+                &SubprocessOpClient,
+                &config,
+                &resolved_vars_by_account,
+                ManualEdits::Skip,
+            )?;
+        }
+        sync_managed_files(&SubprocessOpClient, &config, file_cache_ttl);
     }
 
     Ok(())
 }
 
-fn run_op_inject(account_id: &str, input: &str) -> Result<String> {
-    use std::process::{Command, Stdio};
-
-    let mut child = Command::new("op")
-        .args(["inject", "--account", account_id])
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .with_context(|| format!("Failed to run `op inject --account {account_id}`"))?;
-
-    if let Some(mut stdin) = child.stdin.take() {
-        use std::io::Write;
-        stdin
-            .write_all(input.as_bytes())
-            .with_context(|| "Failed to write to op inject stdin")?;
+pub fn handle_make_env(
+    format: MakeFormat,
+    export: bool,
+    cache_ttl: Option<&str>,
+    cache_lock_wait: &str,
+    sink: SinkArgs,
+) -> Result<()> {
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    if config.inject_vars.is_empty() {
+        eprintln!("No environment variables configured. Use the TUI to add mappings.");
+        return Ok(());
     }
 
-    let output = child
-        .wait_with_output()
-        .with_context(|| "Failed to read op inject output")?;
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    if cache_ttl.is_some() {
+        anyhow::bail!("Cache is only supported on macOS and Windows.");
+    }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!("op inject failed: {stderr}");
+    let cache_ttl = cache_ttl.map(parse_duration).transpose()?.unwrap_or(None);
+    let cache_lock_wait =
+        parse_duration(cache_lock_wait)?.unwrap_or_else(|| Duration::from_secs(5));
+    let results = resolve_vars_by_account(
+        &SubprocessOpClient,
+        group_vars_by_account(&config.inject_vars),
+        cache_ttl,
+        CacheRead::Use,
+        cache_lock_wait,
+    )?;
+
+    let mut resolved = std::collections::BTreeMap::new();
+    for (account_id, result) in results {
+        match result {
+            Ok(vars) => resolved.extend(with_prefixes(&config, &vars)),
+            Err(err) => {
+                eprintln!("# Warning: Failed to resolve secrets for account {account_id}: {err}");
+            }
+        }
     }
 
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    let vars: Vec<(&str, &str)> = resolved
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    sink.into_sink()?
+        .write(&format_assignments(&vars, format, export)?)
 }
 
-fn parse_duration(input: &str) -> Result<Option<Duration>> {
-    let trimmed = input.trim();
-    if trimmed.is_empty() {
-        return Ok(None);
+pub fn handle_bootstrap(cache_ttl: Option<&str>, cache_lock_wait: &str) -> Result<()> {
+    info!("Bootstrapping from existing configuration");
+
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+
+    if config.inject_vars.is_empty()
+        && config.templated_files.is_empty()
+        && config.managed_files.is_empty()
+    {
+        println!("Nothing to bootstrap: no variables, templates, or files are configured.");
+        return Ok(());
     }
 
-    if trimmed.len() < 2 {
-        anyhow::bail!("Invalid duration '{input}'. Use a number followed by s, m, h, or d.");
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    if cache_ttl.is_some() {
+        anyhow::bail!("Cache is only supported on macOS and Windows.");
     }
 
-    let (value, unit) = trimmed.split_at(trimmed.len().saturating_sub(1));
-    let amount: u64 = value
-        .parse()
-        .with_context(|| format!("Invalid duration value: {input}"))?;
+    let cache_ttl = cache_ttl.map(parse_duration).transpose()?.unwrap_or(None);
+    let cache_lock_wait =
+        parse_duration(cache_lock_wait)?.unwrap_or_else(|| Duration::from_secs(5));
 
-    let seconds = match unit {
-        "s" => amount,
-        "m" => amount.saturating_mul(60),
-        "h" => amount.saturating_mul(60 * 60),
-        "d" => amount.saturating_mul(60 * 60 * 24),
-        _ => anyhow::bail!("Invalid duration unit in '{input}'. Use s, m, h, or d."),
+    let known_accounts = list_op_accounts(&SubprocessOpClient)?;
+    let missing = missing_accounts(&config, &known_accounts);
+    for account_id in &missing {
+        eprintln!(
+            "Account {account_id} is referenced by your config but not added to op. Run `op account add` and re-run bootstrap."
+        );
+    }
+
+    let mut vars_by_account = group_vars_by_account(&config.inject_vars);
+    vars_by_account.retain(|account_id, _| !missing.iter().any(|m| m == account_id));
+
+    let mut signed_in = Vec::new();
+    for account_id in vars_by_account.keys() {
+        match ensure_signed_in(&SubprocessOpClient, account_id) {
+            Ok(()) => signed_in.push(account_id.to_string()),
+            Err(err) => eprintln!("Failed to sign in to account {account_id}: {err}"),
+        }
+    }
+    vars_by_account.retain(|account_id, _| signed_in.iter().any(|s| s == account_id));
+
+    let results = resolve_vars_by_account(
+        &SubprocessOpClient,
+        vars_by_account,
+        cache_ttl,
+        CacheRead::Use,
+        cache_lock_wait,
+    )?;
+
+    let mut resolved_vars_by_account = std::collections::HashMap::new();
+    let mut failed_accounts = Vec::new();
+    for (account_id, result) in results {
+        match result {
+            Ok(resolved) => {
+                resolved_vars_by_account.insert(account_id, resolved);
+            }
+            Err(err) => {
+                eprintln!("Failed to resolve secrets for account {account_id}: {err}");
+                failed_accounts.push(account_id);
+            }
+        }
+    }
+
+    let rendered_templates = if config.templated_files.is_empty() {
+        0
+    } else {
+        render_templates(
+            &SubprocessOpClient,
+            &config,
+            &resolved_vars_by_account,
+            ManualEdits::Skip,
+        )?
     };
+    let placed_files = sync_managed_files(&SubprocessOpClient, &config, cache_ttl);
 
-    Ok(Some(Duration::from_secs(seconds)))
-}
+    let resolved_count: usize = resolved_vars_by_account
+        .values()
+        .map(|vars| vars.len())
+        .sum();
+    println!("Bootstrap summary:");
+    println!("  Accounts ready: {}", resolved_vars_by_account.len());
+    if !missing.is_empty() {
+        println!("  Accounts missing from op: {}", missing.join(", "));
+    }
+    if !failed_accounts.is_empty() {
+        println!("  Accounts that failed: {}", failed_accounts.join(", "));
+    }
+    println!(
+        "  Variables resolved: {resolved_count}/{}",
+        config.inject_vars.len()
+    );
+    println!(
+        "  Caches warmed: {}",
+        if cache_ttl.is_some() {
+            "yes"
+        } else {
+            "no (pass --cache-ttl to warm)"
+        }
+    );
+    println!(
+        "  Templates rendered: {rendered_templates}/{}",
+        config.templated_files.len()
+    );
+    println!(
+        "  Files placed: {placed_files}/{}",
+        config.managed_files.len()
+    );
 
-#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
-enum CacheReadOutcome {
-    Hit(String),
-    Miss,
-    Expired,
+    Ok(())
 }
 
-#[cfg(not(target_os = "macos"))]
-fn read_cached_output(
-    _account_id: &str,
-    _kind: CacheKind,
-    _ttl: Duration,
-) -> Result<CacheReadOutcome> {
-    anyhow::bail!("Cache is only supported on macOS.");
-}
+fn list_op_accounts(op: &dyn OpClient) -> Result<Vec<Account>> {
+    crate::op_version::require(op.version(), OpFeature::JsonOutput)?;
+    let output = op
+        .run(&["account", "list", "--format", "json"], None)
+        .context("Failed to run `op account list`")?;
 
-#[cfg(target_os = "macos")]
-fn read_cached_output(
-    account_id: &str,
-    kind: CacheKind,
-    ttl: Duration,
-) -> Result<CacheReadOutcome> {
-    read_cached_output_macos(account_id, kind, ttl)
+    if !output.success {
+        anyhow::bail!("op account list failed: {}", annotate(&output.stderr));
+    }
+
+    serde_json::from_slice(&output.stdout).context("Failed to parse account list JSON")
 }
 
-#[cfg(target_os = "macos")]
-fn read_cached_output_macos(
-    account_id: &str,
-    kind: CacheKind,
-    ttl: Duration,
-) -> Result<CacheReadOutcome> {
-    let path = cache_file_for_account(account_id, kind)?;
-    let metadata = match std::fs::metadata(&path) {
-        Ok(meta) => meta,
-        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
-            return Ok(CacheReadOutcome::Miss);
-        }
-        Err(err) => {
-            return Err(err)
-                .with_context(|| format!("Failed to read cache metadata: {}", path.display()));
-        }
-    };
+/// Accounts referenced by mappings or the default account that `op` doesn't know about.
+fn missing_accounts(config: &OpLoadConfig, known_accounts: &[Account]) -> Vec<String> {
+    let referenced: std::collections::BTreeSet<&str> = config
+        .inject_vars
+        .values()
+        .map(|var| var.account_id.as_str())
+        .chain(config.default_account_id.as_deref())
+        .collect();
 
-    let modified = metadata
-        .modified()
-        .with_context(|| format!("Failed to read cache mtime: {}", path.display()))?;
+    referenced
+        .into_iter()
+        .filter(|account_id| {
+            !known_accounts
+                .iter()
+                .any(|account| account.account_uuid == *account_id)
+        })
+        .map(str::to_string)
+        .collect()
+}
 
-    let age = modified
-        .elapsed()
-        .unwrap_or_else(|_| Duration::from_secs(0));
-    if age > ttl {
-        return Ok(CacheReadOutcome::Expired);
+/// Check the session with `op whoami`, falling back to an interactive
+/// `op signin` that shares this terminal so the user can answer its prompts.
+fn ensure_signed_in(op: &dyn OpClient, account_id: &str) -> Result<()> {
+    let whoami = op
+        .run(&["whoami", "--account", account_id], None)
+        .context("Failed to run `op whoami`")?;
+    if whoami.success {
+        return Ok(());
     }
 
-    let contents = std::fs::read_to_string(&path)
-        .with_context(|| format!("Failed to read cache file: {}", path.display()))?;
-    match decrypt_cache(&contents) {
-        Ok(decrypted) => {
-            let rendered = String::from_utf8_lossy(&decrypted).to_string();
-            Ok(CacheReadOutcome::Hit(rendered))
-        }
-        Err(err) => {
-            eprintln!("# Warning: Failed to decrypt cache for account {account_id}: {err}");
-            if let Err(remove_err) = std::fs::remove_file(&path) {
-                eprintln!(
-                    "# Warning: Failed to remove corrupt cache file {}: {remove_err}",
-                    path.display()
-                );
+    eprintln!("Signing in to account {account_id}...");
+    crate::signin::sign_in(op, Some(account_id))
+}
+
+pub fn handle_item_action(action: ItemAction) -> Result<()> {
+    match action {
+        ItemAction::List {
+            vault,
+            account,
+            export: format,
+        } => {
+            let items = parse_item_list(&list_op_items(
+                &SubprocessOpClient,
+                &vault,
+                account.as_deref(),
+            )?)?;
+            match format {
+                Some(format) => print!("{}", export(&items, format)?),
+                None => {
+                    for item in &items {
+                        println!(
+                            "{:<40}  {:<20}  {}",
+                            item.title,
+                            item.category,
+                            item.updated_at.as_deref().unwrap_or("-")
+                        );
+                    }
+                }
             }
-            Ok(CacheReadOutcome::Miss)
         }
     }
+    Ok(())
 }
 
-fn read_cached_output_if_fresh(
-    account_id: &str,
-    kind: CacheKind,
-    ttl: Duration,
-) -> Result<Option<String>> {
-    match read_cached_output(account_id, kind, ttl)? {
-        CacheReadOutcome::Hit(cached) => Ok(Some(cached)),
-        CacheReadOutcome::Expired | CacheReadOutcome::Miss => Ok(None),
+fn list_op_items(op: &dyn OpClient, vault: &str, account: Option<&str>) -> Result<Vec<u8>> {
+    crate::op_version::require(op.version(), OpFeature::JsonOutput)?;
+    let mut args = vec!["item", "list", "--vault", vault, "--format", "json"];
+    if let Some(account) = account {
+        args.extend(["--account", account]);
+    }
+
+    let output = op
+        .run(&args, None)
+        .context("Failed to run `op item list`")?;
+
+    if !output.success {
+        anyhow::bail!("op item list failed: {}", annotate(&output.stderr));
     }
+
+    Ok(output.stdout)
 }
 
-fn try_log_cache_state(account_id: &str, kind: CacheKind, ttl: Duration) {
-    let prefix = match kind {
-        CacheKind::ResolvedVars => "Cache",
-    };
+pub fn handle_agent(
+    action: Option<AgentAction>,
+    idle_timeout: &str,
+    cache_ttl: &str,
+) -> Result<()> {
+    match action {
+        Some(AgentAction::Stop) => {
+            crate::agent::stop_agent()?;
+            println!("Agent stopped.");
+        }
+        None => {
+            let idle_timeout = parse_duration(idle_timeout)?.filter(|timeout| !timeout.is_zero());
+            let cache_ttl = parse_duration(cache_ttl)?.filter(|ttl| !ttl.is_zero());
+            crate::agent::run_agent(idle_timeout, cache_ttl)?;
+        }
+    }
+    Ok(())
+}
 
-    match read_cached_output(account_id, kind, ttl) {
-        Ok(CacheReadOutcome::Hit(_)) => info!("{prefix} hit for account {account_id}"),
-        Ok(CacheReadOutcome::Expired) => info!("{prefix} expired for account {account_id}"),
-        Ok(CacheReadOutcome::Miss) => info!("{prefix} miss for account {account_id}"),
-        Err(err) => eprintln!("# Warning: Failed to read cache for account {account_id}: {err}"),
+pub fn handle_hook_action(action: HookAction) -> Result<()> {
+    match action {
+        HookAction::PreCommit { check_values } => hook_pre_commit(check_values),
+        HookAction::Install {
+            check_values,
+            force,
+        } => {
+            let path = install_hook(check_values, force)?;
+            println!("Installed pre-commit hook at {}", path.display());
+            Ok(())
+        }
     }
 }
 
-#[cfg(target_os = "macos")]
-fn encrypt_cache(plaintext: &[u8]) -> Result<String> {
-    use aes_gcm::aead::{Aead, KeyInit};
-    use aes_gcm::{Aes256Gcm, Key, Nonce};
+fn hook_pre_commit(check_values: bool) -> Result<()> {
+    let staged = staged_files()?;
+    if staged.is_empty() {
+        return Ok(());
+    }
 
-    assert_keychain_available()?;
-    let key = get_or_create_key()?;
-    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
 
-    let mut nonce_bytes = [0u8; 12];
-    rand_core::OsRng.fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
+    let mut index = SecretIndex::default();
+    for (target, template) in &config.templated_files {
+        let target = PathBuf::from(target);
+        index.targets.push(target.canonicalize().unwrap_or(target));
 
-    let ciphertext = cipher
-        .encrypt(nonce, plaintext)
-        .map_err(|err| anyhow::anyhow!("Failed to encrypt cache: {err}"))?;
+        for record in load_history(&history_path(&template.template_name)?)? {
+            index
+                .render_hashes
+                .insert(record.hash, template.template_name.clone());
+        }
+    }
+    if check_values {
+        index.values = resolve_all_vars(&config)?.into_values().flatten().collect();
+    }
 
-    let mut payload = Vec::with_capacity(1 + nonce_bytes.len() + ciphertext.len());
-    payload.push(1u8);
-    payload.extend_from_slice(&nonce_bytes);
-    payload.extend_from_slice(&ciphertext);
+    let findings = check_staged(&repo_root()?, &staged, &index);
+    if findings.is_empty() {
+        return Ok(());
+    }
 
-    Ok(base64::engine::general_purpose::STANDARD.encode(payload))
+    eprintln!("op-loader: refusing to commit files that contain secrets:");
+    for finding in &findings {
+        eprintln!("  {finding}");
+    }
+    eprintln!();
+    eprintln!("Unstage them with `git restore --staged <file>` and add them to .gitignore.");
+    eprintln!("If this is a false positive, commit with `git commit --no-verify`.");
+    anyhow::bail!("{} staged file(s) contain secrets", findings.len());
 }
 
-#[cfg(target_os = "macos")]
-fn decrypt_cache(encoded: &str) -> Result<Vec<u8>> {
-    use aes_gcm::aead::{Aead, KeyInit};
-    use aes_gcm::{Aes256Gcm, Key, Nonce};
-
-    assert_keychain_available()?;
-    let key = get_or_create_key()?;
-    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+pub fn handle_upgrade_config(dry_run: bool) -> Result<()> {
+    let config_path = confy::get_configuration_file_path("op_loader", None)
+        .context("Failed to get config path")?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    upgrade_config_at(&config_path, dry_run, now)
+}
 
-    let payload = base64::engine::general_purpose::STANDARD
-        .decode(encoded)
-        .context("Failed to decode cache base64")?;
+fn upgrade_config_at(config_path: &Path, dry_run: bool, timestamp: u64) -> Result<()> {
+    let original = std::fs::read_to_string(config_path)
+        .with_context(|| format!("Failed to read config: {}", config_path.display()))?;
+    let plan = plan_migrations(&original)?;
 
-    if payload.len() < 1 + 12 {
-        anyhow::bail!("Invalid cache payload length");
+    if plan.is_empty() {
+        println!("Config is up to date: {}", config_path.display());
+        return Ok(());
     }
 
-    if payload[0] != 1u8 {
-        anyhow::bail!("Unsupported cache payload version");
+    let backup = backup_path(config_path, timestamp);
+    println!("Migrations:");
+    for description in &plan.applied {
+        println!("  - {description}");
     }
 
-    let nonce = Nonce::from_slice(&payload[1..13]);
-    let ciphertext = &payload[13..];
+    if dry_run {
+        println!();
+        for line in line_diff(&plan.original, &plan.migrated) {
+            match line {
+                DiffLine::Same(text) => println!("  {text}"),
+                DiffLine::Removed(text) => println!("- {text}"),
+                DiffLine::Added(text) => println!("+ {text}"),
+            }
+        }
+        println!();
+        println!("Dry run: {} was not changed.", config_path.display());
+        println!("The original would be backed up to {}", backup.display());
+        return Ok(());
+    }
 
-    cipher
-        .decrypt(nonce, ciphertext)
-        .map_err(|err| anyhow::anyhow!("Failed to decrypt cache: {err}"))
+    std::fs::copy(config_path, &backup)
+        .with_context(|| format!("Failed to back up config to {}", backup.display()))?;
+    std::fs::write(config_path, &plan.migrated)
+        .with_context(|| format!("Failed to write config: {}", config_path.display()))?;
+    println!("Upgraded {}", config_path.display());
+    println!("Backup: {}", backup.display());
+    Ok(())
 }
 
-#[cfg(not(target_os = "macos"))]
-fn write_cached_output(_account_id: &str, _kind: CacheKind, _output: &str) -> Result<()> {
-    anyhow::bail!("Cache is only supported on macOS.");
-}
-
-#[cfg(target_os = "macos")]
-fn write_cached_output(account_id: &str, kind: CacheKind, output: &str) -> Result<()> {
-    write_cached_output_macos(account_id, kind, output)
-}
-
-fn load_resolved_vars(
-    account_id: &str,
-    input: &str,
-    cache_ttl: Option<Duration>,
-    cache_lock_wait: Duration,
-) -> Result<std::collections::HashMap<String, String>> {
-    if let Some(ttl) = cache_ttl {
-        // Fast path: check cache before acquiring any lock.
-        if let Ok(Some(cached)) =
-            read_cached_output_if_fresh(account_id, CacheKind::ResolvedVars, ttl)
-        {
-            info!("Cache hit for account {account_id}");
-            return parse_cached_vars(&cached);
-        }
-
-        try_log_cache_state(account_id, CacheKind::ResolvedVars, ttl);
+pub fn handle_telemetry_action(action: TelemetryAction) -> Result<()> {
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let root = cache_dir()?;
 
-        // Acquire per-account exclusive lock with timeout.
-        let lock_file = open_lock_file_for_account(account_id)?;
-        let acquired = lock_exclusive_with_timeout(&lock_file, cache_lock_wait)?;
-        if !acquired {
-            anyhow::bail!(
-                "Cache lock for account {account_id} not acquired within {}s",
-                cache_lock_wait.as_secs()
+    match action {
+        TelemetryAction::Show => {
+            if !config.telemetry.enabled {
+                println!(
+                    "Telemetry is disabled. Enable it with `op-loader config set -k telemetry.enabled true`."
+                );
+            }
+            let report = load_report(&root)?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&report).context("Failed to serialize telemetry")?
             );
         }
-
-        // Double-check: another process may have populated the cache while
-        // we were waiting on the lock.
-        if let Ok(Some(cached)) =
-            read_cached_output_if_fresh(account_id, CacheKind::ResolvedVars, ttl)
-        {
-            info!("Cache hit (after lock) for account {account_id}");
-            let _ = lock_file.unlock();
-            return parse_cached_vars(&cached);
+        TelemetryAction::Send => {
+            if !config.telemetry.enabled {
+                anyhow::bail!(
+                    "Telemetry is disabled. Enable it with `op-loader config set -k telemetry.enabled true`."
+                );
+            }
+            let endpoint = config
+                .telemetry
+                .endpoint
+                .as_deref()
+                .context("No telemetry.endpoint configured")?;
+            let report = load_report(&root)?;
+            if report.is_empty() {
+                println!("No telemetry recorded yet.");
+                return Ok(());
+            }
+            send_report(endpoint, &report)?;
+            update_report(&root, |report| *report = TelemetryReport::default())?;
+            println!("Sent telemetry to {endpoint}.");
         }
-
-        // Cache is stale/missing and we hold the lock — resolve via op inject.
-        let resolved_json = resolve_vars_json(account_id, input)?;
-        if let Err(err) = write_cached_output(account_id, CacheKind::ResolvedVars, &resolved_json) {
-            eprintln!("# Warning: Failed to write cache for account {account_id}: {err}");
+        TelemetryAction::Reset => {
+            update_report(&root, |report| *report = TelemetryReport::default())?;
+            println!("Telemetry counters reset.");
         }
-        let _ = lock_file.unlock();
-        return parse_cached_vars(&resolved_json);
     }
-
-    let resolved_json = resolve_vars_json(account_id, input)?;
-    parse_cached_vars(&resolved_json)
+    Ok(())
 }
 
-/// Attempt to acquire an exclusive lock on `file`, blocking up to `timeout`.
-///
-/// Returns `Ok(true)` if the lock was acquired, `Ok(false)` if the timeout
-/// elapsed. Uses a background thread so the caller's thread can enforce
-/// the deadline.
-fn lock_exclusive_with_timeout(file: &std::fs::File, timeout: Duration) -> Result<bool> {
-    use fs2::FileExt;
-    use std::sync::mpsc;
+/// Resolves every configured variable straight from `op`, bypassing the disk
+/// cache. Accounts that fail are reported and left out.
+pub fn resolve_all_vars(config: &OpLoadConfig) -> Result<crate::agent::ResolvedVarsByAccount> {
+    let results = resolve_vars_per_account(&SubprocessOpClient, config)?;
 
-    // First try a non-blocking acquire — avoids spawning a thread when
-    // the lock is uncontended (the common case).
-    if file.try_lock_exclusive().is_ok() {
-        return Ok(true);
+    let mut resolved = std::collections::HashMap::new();
+    for (account_id, result) in results {
+        match result {
+            Ok(vars) => {
+                resolved.insert(account_id, vars);
+            }
+            Err(err) => {
+                eprintln!("# Warning: Failed to resolve secrets for account {account_id}: {err}");
+            }
+        }
     }
+    Ok(resolved)
+}
 
-    info!("Lock contended, waiting up to {}s", timeout.as_secs());
+pub type AccountResolution = (String, Result<std::collections::HashMap<String, String>>);
 
-    // Clone the file descriptor so the background thread can call the
-    // blocking lock_exclusive() without borrowing from the caller.
-    let file_dup = file.try_clone().context("Failed to duplicate lock fd")?;
-    let (tx, rx) = mpsc::channel();
+/// Resolved var values, by account ID.
+type ResolvedVarsByAccount =
+    std::collections::HashMap<String, std::collections::HashMap<String, String>>;
 
-    std::thread::spawn(move || {
-        let result = file_dup.lock_exclusive();
-        // If the receiver has been dropped (timeout elapsed), release the
-        // lock we just acquired so we don't hold it indefinitely.
-        if tx.send(result).is_err() {
-            let _ = file_dup.unlock();
-        }
-    });
+/// Resolves every managed var with `op inject`, one account at a time in
+/// parallel and without the cache, leaving failures for the caller to report.
+pub fn resolve_vars_per_account(
+    op: &dyn OpClient,
+    config: &OpLoadConfig,
+) -> Result<Vec<AccountResolution>> {
+    resolve_vars_by_account(
+        op,
+        group_vars_by_account(&config.inject_vars),
+        None,
+        CacheRead::Use,
+        Duration::from_secs(5),
+    )
+}
 
-    match rx.recv_timeout(timeout) {
-        Ok(Ok(())) => Ok(true),
-        Ok(Err(err)) => Err(err).context("Failed to acquire exclusive lock"),
-        Err(mpsc::RecvTimeoutError::Timeout) => Ok(false),
-        Err(mpsc::RecvTimeoutError::Disconnected) => {
-            anyhow::bail!("Lock thread terminated unexpectedly")
+fn resolve_vars_by_account(
+    op: &dyn OpClient,
+    vars_by_account: std::collections::BTreeMap<&str, Vec<(&str, &InjectVarConfig)>>,
+    cache_ttl: Option<Duration>,
+    cache_read: CacheRead,
+    cache_lock_wait: Duration,
+) -> Result<Vec<AccountResolution>> {
+    let policy = load_policy()?.unwrap_or_default();
+    let timings = cache_dir()
+        .map(|dir| crate::inject_timing::load_timings(&dir))
+        .unwrap_or_default();
+
+    // Build the input string for each account up front (cheap, no I/O).
+    let account_inputs: Vec<(&str, String, Option<Duration>, Batching)> = vars_by_account
+        .into_iter()
+        .map(|(account_id, vars)| {
+            let (input, account_ttl) = account_inject_input(account_id, &vars, &policy, cache_ttl);
+            let batching = timings.batching(account_id, vars.len());
+            (account_id, input, account_ttl, batching)
+        })
+        .collect();
+
+    // Resolve all accounts in parallel — each thread acquires its own
+    // per-account lock, so different accounts never block each other.
+    std::thread::scope(|s| {
+        // Spawn every resolver before joining any of them; joining inside
+        // the same iterator chain would run the accounts one at a time.
+        let handles: Vec<_> = account_inputs
+            .iter()
+            .map(|(account_id, input, account_ttl, batching)| {
+                let account_id = *account_id;
+                let account_ttl = *account_ttl;
+                let batching = *batching;
+                s.spawn(move || {
+                    let result = load_resolved_vars(
+                        op,
+                        account_id,
+                        input,
+                        batching,
+                        account_ttl,
+                        cache_read,
+                        cache_lock_wait,
+                    );
+                    (account_id, result)
+                })
+            })
+            .collect();
+
+        let results: Vec<_> = handles
+            .into_iter()
+            .map(|h| h.join().expect("account resolver thread panicked"))
+            .collect();
+
+        // Evict once every account is done, keeping all the files this run
+        // wrote, so one account's eviction can't take another's fresh cache.
+        let written: Vec<PathBuf> = results
+            .iter()
+            .filter(|(_, result)| matches!(result, Ok((_, true))))
+            .filter_map(|(account_id, _)| {
+                cache_file_for_account(account_id, CacheKind::ResolvedVars).ok()
+            })
+            .collect();
+        if !written.is_empty()
+            && let Err(err) = enforce_cache_max_size(&written)
+        {
+            eprintln!("# Warning: Failed to enforce cache size limit: {err}");
         }
-    }
+
+        Ok(results
+            .into_iter()
+            .map(|(account_id, result)| (account_id.to_string(), result.map(|(vars, _)| vars)))
+            .collect())
+    })
 }
 
-fn resolve_vars_json(account_id: &str, input: &str) -> Result<String> {
-    let output = run_op_inject(account_id, input)?;
-    let mut vars = std::collections::HashMap::new();
-    for line in output.lines() {
-        if let Some((var_name, value)) = line.split_once(": ") {
-            vars.insert(var_name.to_string(), value.to_string());
+/// The `op inject` input for one account, and the TTL to cache it with once
+/// the organization policy is applied.
+fn account_inject_input(
+    account_id: &str,
+    vars: &[(&str, &InjectVarConfig)],
+    policy: &crate::policy::Policy,
+    cache_ttl: Option<Duration>,
+) -> (String, Option<Duration>) {
+    let mut account_ttl = cache_ttl;
+    for (env_var_name, var_config) in vars {
+        if account_ttl.is_some() && policy.forbids_caching(env_var_name, var_config) {
+            info!("Policy forbids caching {env_var_name}; not caching account {account_id}");
+            account_ttl = None;
         }
     }
-    serde_json::to_string(&vars).context("Failed to serialize resolved vars")
+    let references: Vec<(&str, &str)> = vars
+        .iter()
+        .map(|(env_var_name, var_config)| (*env_var_name, var_config.op_reference.as_str()))
+        .collect();
+    (inject_blocks(&references), account_ttl)
 }
 
-fn parse_cached_vars(cached_json: &str) -> Result<std::collections::HashMap<String, String>> {
-    serde_json::from_str(cached_json).context("Failed to parse cached vars")
-}
+/// `op inject` input with each `(name, reference)` between boundary lines,
+/// so the values can be told apart in the output even when they span lines.
+fn inject_blocks(references: &[(&str, &str)]) -> String {
+    use std::fmt::Write;
 
-fn format_exports(vars: &std::collections::HashMap<String, String>) -> String {
-    let mut lines: Vec<(&String, &String)> = vars.iter().collect();
-    lines.sort_by(|a, b| a.0.cmp(b.0));
+    let joined: String = references
+        .iter()
+        .map(|(_, reference)| format!("{reference}\n"))
+        .collect();
+    let boundary = format!("--op-loader-{}--", &config_hash(&joined)[..16]);
 
-    let mut output = String::new();
-    for (key, value) in lines {
-        let escaped = escape_shell_single_quotes(value);
-        output.push_str("export ");
-        output.push_str(key);
-        output.push_str("='");
-        output.push_str(&escaped);
-        output.push_str("'\n");
+    let mut input = String::new();
+    for (name, reference) in references {
+        writeln!(input, "{boundary} {name}\n{reference}").expect("write to String cannot fail");
     }
-    output
+    writeln!(input, "{boundary}").expect("write to String cannot fail");
+    input
 }
 
-fn escape_shell_single_quotes(value: &str) -> String {
-    value.replace('\'', "'\\''")
+/// Splits `op inject` input or output into `(name, value)` pairs. Each var
+/// is a `<boundary> NAME` line followed by its reference, or once injected
+/// its value, and a final `<boundary>` line closes the last one. Values
+/// are taken verbatim up to the next boundary, so secrets with newlines or
+/// `: ` come through intact.
+fn split_inject_blocks(text: &str) -> Result<Vec<(String, String)>> {
+    let boundary = text
+        .split_once(' ')
+        .map(|(boundary, _)| boundary)
+        .filter(|boundary| boundary.starts_with("--op-loader-"))
+        .context("op inject output doesn't start with a var boundary")?;
+    let mut blocks = text.split(boundary).skip(1).peekable();
+    let mut vars = Vec::new();
+    while let Some(block) = blocks.next() {
+        if blocks.peek().is_none() {
+            if block.trim_end_matches('\n').is_empty() {
+                return Ok(vars);
+            }
+            break;
+        }
+        let (name, value) = block
+            .strip_prefix(' ')
+            .and_then(|block| block.split_once('\n'))
+            .context("op inject output has a malformed var boundary")?;
+        let value = value.strip_suffix('\n').unwrap_or(value);
+        vars.push((name.to_string(), value.to_string()));
+    }
+    anyhow::bail!("op inject output ended before the last var")
 }
 
-#[cfg(target_os = "macos")]
-fn write_cached_output_macos(account_id: &str, kind: CacheKind, output: &str) -> Result<()> {
-    use std::fs::OpenOptions;
-    use std::io::Write;
-
-    ensure_cache_dir()?;
-    let path = cache_file_for_account(account_id, kind)?;
-    let tmp_path = path.with_extension("cache.tmp");
+/// Starts a detached `op-loader cache refresh` for `account_id` so the next
+/// shell finds a fresh cache. Failures only cost the speedup.
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+fn spawn_background_refresh(account_id: &str, ttl: Duration) {
+    use std::process::{Command, Stdio};
 
-    let encrypted = encrypt_cache(output.as_bytes())?;
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => {
+            debug!("Skipping background refresh: {err}");
+            return;
+        }
+    };
 
-    let mut file = OpenOptions::new()
-        .create(true)
-        .write(true)
-        .truncate(true)
-        .open(&tmp_path)
-        .with_context(|| {
-            format!(
-                "Failed to open temp cache file for writing: {}",
-                tmp_path.display()
-            )
-        })?;
+    let mut command = Command::new(exe);
+    command
+        .args(["cache", "refresh", "--account", account_id, "--ttl"])
+        .arg(format!("{}s", ttl.as_secs()))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
 
+    // Detach from the shell's process group so Ctrl-C or closing the
+    // terminal doesn't interrupt a refresh halfway through.
     #[cfg(unix)]
     {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = file.metadata()?.permissions();
-        perms.set_mode(0o600);
-        std::fs::set_permissions(&tmp_path, perms).with_context(|| {
-            format!(
-                "Failed to set cache file permissions: {}",
-                tmp_path.display()
-            )
-        })?;
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
     }
 
-    file.write_all(encrypted.as_bytes())
-        .with_context(|| format!("Failed to write temp cache file: {}", tmp_path.display()))?;
-
-    // Flush to disk before rename to ensure readers see complete data.
-    file.sync_all()
-        .with_context(|| format!("Failed to sync temp cache file: {}", tmp_path.display()))?;
-    drop(file);
+    match command.spawn() {
+        Ok(child) => info!(
+            "Started background refresh for account {account_id} (pid {})",
+            child.id()
+        ),
+        Err(err) => debug!("Failed to start background refresh: {err}"),
+    }
+}
 
-    // Atomic rename: readers either see the old file or the new complete file.
-    std::fs::rename(&tmp_path, &path)
-        .with_context(|| format!("Failed to rename temp cache to {}", path.display()))?;
+/// Re-resolves one account's cache. Runs in the detached child started by
+/// [`spawn_background_refresh`]; if another process holds the account lock,
+/// it is already refreshing and this is a no-op.
+fn cache_refresh(account_id: &str, ttl: &str) -> Result<()> {
+    use fs2::FileExt;
 
-    Ok(())
-}
+    let ttl = parse_duration(ttl)?
+        .filter(|ttl| !ttl.is_zero())
+        .context("--ttl must be greater than zero")?;
 
-fn open_lock_file_for_account(account_id: &str) -> Result<std::fs::File> {
-    use std::fs::OpenOptions;
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let vars_by_account = group_vars_by_account(&config.inject_vars);
+    let Some(vars) = vars_by_account.get(account_id) else {
+        info!("Account {account_id} has no mappings; nothing to refresh");
+        return Ok(());
+    };
 
-    ensure_cache_dir()?;
-    let lock_path = lock_path_for_account(account_id)?;
-    let lock_file = OpenOptions::new()
-        .create(true)
-        .read(true)
-        .write(true)
-        .truncate(false)
-        .open(&lock_path)
-        .with_context(|| format!("Failed to open cache lock: {}", lock_path.display()))?;
+    let policy = load_policy()?.unwrap_or_default();
+    let (input, Some(ttl)) = account_inject_input(account_id, vars, &policy, Some(ttl)) else {
+        return Ok(());
+    };
+    let input_hash = config_hash(&input);
+    let batching =
+        crate::inject_timing::load_timings(&cache_dir()?).batching(account_id, vars.len());
 
-    #[cfg(unix)]
+    if let Some(remaining) =
+        load_backoff(&cache_dir()?).remaining(account_id, std::time::SystemTime::now())
     {
-        use std::os::unix::fs::PermissionsExt;
-        let mut perms = lock_file.metadata()?.permissions();
-        perms.set_mode(0o600);
-        std::fs::set_permissions(&lock_path, perms).with_context(|| {
-            format!(
-                "Failed to set lock file permissions: {}",
-                lock_path.display()
-            )
-        })?;
+        info!(
+            "Skipping refresh for account {account_id}: cooling down for {}s after failed op calls",
+            remaining.as_secs()
+        );
+        return Ok(());
     }
 
-    Ok(lock_file)
-}
+    let lock_file = open_lock_file_for_account(account_id)?;
+    if lock_file.try_lock_exclusive().is_err() {
+        info!("Account {account_id} is already being refreshed");
+        return Ok(());
+    }
 
-fn get_templates_dir() -> Result<PathBuf> {
-    let config_path = confy::get_configuration_file_path("op_loader", None)
-        .context("Failed to get config path")?;
-    let config_dir = config_path
-        .parent()
-        .context("Config path has no parent directory")?;
-    Ok(config_dir.join("templates"))
-}
+    if matches!(
+        read_cached_output(account_id, CacheKind::ResolvedVars, ttl, &input_hash)?,
+        CacheReadOutcome::Hit(_)
+    ) {
+        let _ = lock_file.unlock();
+        return Ok(());
+    }
 
-fn expand_path(path: &str) -> Result<PathBuf> {
-    let expanded = if let Some(suffix) = path.strip_prefix("~/") {
-        let home = std::env::var("HOME").context("HOME environment variable not set")?;
-        PathBuf::from(home).join(suffix)
-    } else {
-        PathBuf::from(path)
-    };
+    let resolved_json = resolve_account_json(&SubprocessOpClient, account_id, &input, batching);
+    record_refresh_result(account_id, resolved_json.is_ok());
+    let entry = CacheEntry::new(resolved_json?, ttl, input_hash);
+    write_cached_output(account_id, CacheKind::ResolvedVars, &entry)?;
+    enforce_cache_max_size(&[cache_file_for_account(account_id, CacheKind::ResolvedVars)?])?;
+    let _ = lock_file.unlock();
 
-    if expanded.exists() {
-        expanded
-            .canonicalize()
-            .with_context(|| format!("Failed to canonicalize path: {}", expanded.display()))
-    } else {
-        Ok(expanded)
-    }
+    info!("Refreshed cache for account {account_id}");
+    Ok(())
 }
 
-fn path_to_template_name(path: &Path) -> String {
-    let filename = path.file_name().map_or_else(
-        || "template".to_string(),
-        |s| s.to_string_lossy().to_string(),
-    );
-    format!("{filename}.tmpl")
+/// Updates the cool-down background refreshes use for `account_id`.
+fn record_refresh_result(account_id: &str, succeeded: bool) {
+    let result = cache_dir().and_then(|dir| {
+        update_backoff(&dir, |backoff| {
+            if succeeded {
+                backoff.reset(account_id);
+            } else {
+                let delay = backoff.record_failure(account_id, std::time::SystemTime::now());
+                info!(
+                    "Account {account_id} failed; background refreshes pause for {}s",
+                    delay.as_secs()
+                );
+            }
+        })
+    });
+    if let Err(err) = result {
+        debug!("Failed to record refresh backoff: {err}");
+    }
 }
 
-pub fn handle_template_action(action: TemplateAction) -> Result<()> {
-    debug!("Handling template action: {action:?}");
+fn run_op_inject(op: &dyn OpClient, account_id: &str, input: &str) -> Result<String> {
+    crate::op_version::require(op.version(), OpFeature::Inject)?;
+    let output = op
+        .run(&["inject", "--account", account_id], Some(input.as_bytes()))
+        .with_context(|| format!("Failed to run `op inject --account {account_id}`"))?;
 
-    match action {
-        TemplateAction::Add { path } => template_add(&path),
-        TemplateAction::List => template_list(),
-        TemplateAction::Remove { path } => template_remove(&path),
-        TemplateAction::Render => {
-            let config: OpLoadConfig =
-                confy::load("op_loader", None).context("Failed to load configuration")?;
-            let resolved_vars_by_account = std::collections::HashMap::new();
-            render_templates(&config, &resolved_vars_by_account)
-        }
+    if !output.success {
+        anyhow::bail!("op inject failed: {}", annotate(&output.stderr));
     }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
-pub fn handle_cache_action(action: CacheAction) -> Result<()> {
-    debug!("Handling cache action: {action:?}");
+/// Resolves one reference. Cheaper than `op inject` and keeps values with
+/// newlines intact, since there's no output to split back into vars.
+fn run_op_read(op: &dyn OpClient, account_id: &str, reference: &str) -> Result<String> {
+    crate::op_version::require(op.version(), OpFeature::Read)?;
+    let output = op
+        .run(
+            &["read", reference, "--account", account_id, "--no-newline"],
+            None,
+        )
+        .with_context(|| format!("Failed to run `op read --account {account_id}`"))?;
 
-    match action {
-        CacheAction::Clear { account } => {
-            if let Some(account_id) = account {
-                match remove_cache_for_account(&account_id) {
-                    Ok(CacheRemoval::Removed) => {
-                        println!("Cleared cache for account {account_id}");
-                    }
-                    Ok(CacheRemoval::NotFound) => {
-                        println!("No cache found for account {account_id}");
-                    }
-                    Err(err) => {
-                        eprintln!("Warning: Failed to clear cache for account {account_id}: {err}");
-                    }
-                }
-            } else {
-                clear_all_caches()?;
-                #[cfg(target_os = "macos")]
-                {
-                    if let Err(err) = delete_key() {
-                        eprintln!("Warning: Failed to delete cache key from Keychain: {err}");
-                    }
-                }
-            }
-        }
+    if !output.success {
+        anyhow::bail!("op read failed: {}", annotate(&output.stderr));
     }
 
-    Ok(())
+    String::from_utf8(output.stdout).context("op read returned a value that isn't UTF-8")
 }
 
-fn clear_all_caches() -> Result<()> {
-    let dir = cache_dir()?;
-    if !dir.exists() {
-        println!("No cache directory found.");
-        return Ok(());
+pub fn parse_duration(input: &str) -> Result<Option<Duration>> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Ok(None);
     }
 
-    let mut removed = 0usize;
-    let mut failed = 0usize;
-    let mut saw_file = false;
-    for entry in std::fs::read_dir(&dir)
-        .with_context(|| format!("Failed to read cache directory: {}", dir.display()))?
-    {
-        let entry = entry?;
-        let path = entry.path();
-        if !path.is_file() {
-            continue;
+    if trimmed.len() < 2 {
+        anyhow::bail!("Invalid duration '{input}'. Use a number followed by s, m, h, or d.");
+    }
+
+    let (value, unit) = trimmed.split_at(trimmed.len().saturating_sub(1));
+    let amount: u64 = value
+        .parse()
+        .with_context(|| format!("Invalid duration value: {input}"))?;
+
+    let seconds = match unit {
+        "s" => amount,
+        "m" => amount.saturating_mul(60),
+        "h" => amount.saturating_mul(60 * 60),
+        "d" => amount.saturating_mul(60 * 60 * 24),
+        _ => anyhow::bail!("Invalid duration unit in '{input}'. Use s, m, h, or d."),
+    };
+
+    Ok(Some(Duration::from_secs(seconds)))
+}
+
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+enum CacheReadOutcome {
+    Hit(String),
+    /// Still fresh, but close enough to expiry to refresh in the background.
+    ExpiringSoon(String),
+    Miss,
+    Expired,
+    /// The entry was built from a different set of mappings.
+    Stale,
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn read_cached_output(
+    _account_id: &str,
+    _kind: CacheKind,
+    _ttl: Duration,
+    _config_hash: &str,
+) -> Result<CacheReadOutcome> {
+    anyhow::bail!("Cache is only supported on macOS and Windows.");
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn read_cached_output(
+    account_id: &str,
+    kind: CacheKind,
+    ttl: Duration,
+    config_hash: &str,
+) -> Result<CacheReadOutcome> {
+    read_cached_output_encrypted(account_id, kind, ttl, config_hash)
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn read_cached_output_encrypted(
+    account_id: &str,
+    kind: CacheKind,
+    ttl: Duration,
+    config_hash: &str,
+) -> Result<CacheReadOutcome> {
+    let path = cache_file_for_account(account_id, kind)?;
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(CacheReadOutcome::Miss);
         }
-        match std::fs::remove_file(&path) {
-            Ok(()) => removed += 1,
-            Err(err) => {
-                failed += 1;
-                eprintln!("Warning: Failed to remove {}: {err}", path.display());
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("Failed to read cache file: {}", path.display()));
+        }
+    };
+
+    let entry = decrypt_cache(account_id, kind, &contents).and_then(|decrypted| {
+        serde_json::from_slice::<CacheEntry>(&decrypted).context("Failed to parse cache entry")
+    });
+    match entry {
+        Ok(entry) if entry.config_hash != config_hash => Ok(CacheReadOutcome::Stale),
+        Ok(entry) if entry.expires_soon(ttl, std::time::SystemTime::now()) => {
+            Ok(CacheReadOutcome::ExpiringSoon(entry.payload))
+        }
+        Ok(entry) if entry.is_fresh(ttl, std::time::SystemTime::now()) => {
+            Ok(CacheReadOutcome::Hit(entry.payload))
+        }
+        Ok(_) => Ok(CacheReadOutcome::Expired),
+        Err(err) => {
+            eprintln!("# Warning: Failed to decrypt cache for account {account_id}: {err}");
+            if let Err(remove_err) = std::fs::remove_file(&path) {
+                eprintln!(
+                    "# Warning: Failed to remove corrupt cache file {}: {remove_err}",
+                    path.display()
+                );
             }
+            Ok(CacheReadOutcome::Miss)
         }
-        saw_file = true;
     }
+}
 
-    if !saw_file {
-        println!("No cache files found.");
-        return Ok(());
+fn read_cached_output_if_fresh(
+    account_id: &str,
+    kind: CacheKind,
+    ttl: Duration,
+    config_hash: &str,
+) -> Result<Option<String>> {
+    match read_cached_output(account_id, kind, ttl, config_hash)? {
+        CacheReadOutcome::Hit(cached) | CacheReadOutcome::ExpiringSoon(cached) => Ok(Some(cached)),
+        CacheReadOutcome::Expired | CacheReadOutcome::Miss | CacheReadOutcome::Stale => Ok(None),
     }
-
-    println!(
-        "Cleared {removed} cache file(s).{suffix}",
-        suffix = if failed > 0 { " (some failures)" } else { "" }
-    );
-    Ok(())
 }
 
-fn template_add(path: &str) -> Result<()> {
-    info!("Adding template for: {path}");
-
-    let target_path = expand_path(path)?;
-    let target_key = target_path.to_string_lossy().to_string();
+fn try_log_cache_state(account_id: &str, kind: CacheKind, ttl: Duration, config_hash: &str) {
+    let prefix = match kind {
+        CacheKind::ResolvedVars => "Cache",
+        CacheKind::AccountList | CacheKind::VaultList | CacheKind::ItemList => "Listing cache",
+        CacheKind::Documents => "Documents cache",
+    };
 
-    if !target_path.exists() {
-        anyhow::bail!("File does not exist: {}", target_path.display());
+    match read_cached_output(account_id, kind, ttl, config_hash) {
+        Ok(CacheReadOutcome::Hit(_)) => info!("{prefix} hit for account {account_id}"),
+        Ok(CacheReadOutcome::ExpiringSoon(_)) => {
+            info!("{prefix} hit for account {account_id} (expiring soon)");
+        }
+        Ok(CacheReadOutcome::Expired) => info!("{prefix} expired for account {account_id}"),
+        Ok(CacheReadOutcome::Miss) => info!("{prefix} miss for account {account_id}"),
+        Ok(CacheReadOutcome::Stale) => {
+            info!("{prefix} stale for account {account_id} (mappings changed)");
+        }
+        Err(err) => eprintln!("# Warning: Failed to read cache for account {account_id}: {err}"),
     }
+}
 
-    let mut config: OpLoadConfig =
-        confy::load("op_loader", None).context("Failed to load configuration")?;
+/// How long `op account/vault/item list` output is served from the cache
+/// before the TUI waits on `op` again.
+const LISTING_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
 
-    if config.templated_files.contains_key(&target_key) {
-        anyhow::bail!(
-            "File is already managed as a template: {}",
-            target_path.display()
-        );
+/// Cached output of an `op ... list` command, if fresh. Errors (including
+/// platforms without a cache) are treated as a miss.
+pub fn read_listing_cache(key: &str, kind: CacheKind, command: &str) -> Option<String> {
+    match read_cached_output(key, kind, LISTING_CACHE_TTL, &config_hash(command)) {
+        Ok(CacheReadOutcome::Hit(payload) | CacheReadOutcome::ExpiringSoon(payload)) => {
+            Some(payload)
+        }
+        Ok(CacheReadOutcome::Expired | CacheReadOutcome::Miss | CacheReadOutcome::Stale) => None,
+        Err(err) => {
+            debug!("Listing cache unavailable: {err}");
+            None
+        }
     }
+}
 
-    let templates_dir = get_templates_dir()?;
-    std::fs::create_dir_all(&templates_dir).with_context(|| {
-        format!(
-            "Failed to create templates directory: {}",
-            templates_dir.display()
-        )
-    })?;
+pub fn write_listing_cache(
+    key: &str,
+    kind: CacheKind,
+    command: &str,
+    payload: String,
+) -> Result<()> {
+    let entry = CacheEntry::new(payload, LISTING_CACHE_TTL, config_hash(command));
+    write_cached_output(key, kind, &entry)
+}
 
-    let template_name = path_to_template_name(&target_path);
-    let template_path = templates_dir.join(&template_name);
+/// Version 2 payloads bind the ciphertext to the account and cache kind via
+/// AEAD associated data, so a cache file copied between accounts fails to
+/// decrypt instead of leaking one account's secrets into another's shell.
+/// Version 3 wraps the plaintext in a [`CacheEntry`] carrying its own expiry.
+const CACHE_PAYLOAD_VERSION: u8 = 3;
 
-    let original_content =
-        std::fs::read_to_string(&target_path).context("Failed to read source file")?;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn encrypt_cache(account_id: &str, kind: CacheKind, plaintext: &[u8]) -> Result<String> {
+    assert_keychain_available()?;
+    let key = get_or_create_key()?;
+    seal_cache_payload(&key, &cache_aad(account_id, kind), plaintext)
+}
 
-    let var_names: Vec<String> = config
-        .inject_vars
-        .keys()
-        .map(|k| format!("{{{{{k}}}}}"))
-        .collect();
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn decrypt_cache(account_id: &str, kind: CacheKind, encoded: &str) -> Result<Vec<u8>> {
+    assert_keychain_available()?;
+    let key = get_or_create_key()?;
+    open_cache_payload(&key, &cache_aad(account_id, kind), encoded)
+}
 
-    let vars_comment = if var_names.is_empty() {
-        "# op-loader: No variables configured yet. Use the TUI to add variables.\n".to_string()
-    } else {
-        format!(
-            "# op-loader: Available variables: {}\n",
-            var_names.join(", ")
-        )
-    };
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+fn cache_aad(account_id: &str, kind: CacheKind) -> Vec<u8> {
+    format!("op-loader:{}:{account_id}", kind.file_prefix()).into_bytes()
+}
 
-    let template_content = format!("{vars_comment}{original_content}");
-    std::fs::write(&template_path, &template_content)
-        .with_context(|| format!("Failed to write template to {}", template_path.display()))?;
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+fn seal_cache_payload(key: &[u8; 32], aad: &[u8], plaintext: &[u8]) -> Result<String> {
+    use aes_gcm::aead::{Aead, KeyInit, Payload};
+    use aes_gcm::{Aes256Gcm, Key, Nonce};
 
-    config
-        .templated_files
-        .insert(target_key, TemplatedFile { template_name });
-    confy::store("op_loader", None, &config).context("Failed to save configuration")?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
 
-    println!("Added template for: {}", target_path.display());
-    println!("Template stored at: {}", template_path.display());
-    println!("\nAdd {{VAR_NAME}} placeholders to the template file.");
-    println!("Use `op-loader template list` to see configured variables.");
+    let mut nonce_bytes = [0u8; 12];
+    rand_core::OsRng.fill_bytes(&mut nonce_bytes);
+    let nonce = Nonce::from_slice(&nonce_bytes);
 
-    Ok(())
+    let ciphertext = cipher
+        .encrypt(
+            nonce,
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|err| anyhow::anyhow!("Failed to encrypt cache: {err}"))?;
+
+    let mut payload = Vec::with_capacity(1 + nonce_bytes.len() + ciphertext.len());
+    payload.push(CACHE_PAYLOAD_VERSION);
+    payload.extend_from_slice(&nonce_bytes);
+    payload.extend_from_slice(&ciphertext);
+
+    Ok(base64::engine::general_purpose::STANDARD.encode(payload))
 }
 
-fn template_list() -> Result<()> {
-    info!("Listing templates");
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+fn open_cache_payload(key: &[u8; 32], aad: &[u8], encoded: &str) -> Result<Vec<u8>> {
+    use aes_gcm::aead::{Aead, KeyInit, Payload};
+    use aes_gcm::{Aes256Gcm, Key, Nonce};
 
-    let config: OpLoadConfig =
-        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
 
-    if config.templated_files.is_empty() {
-        println!("No template files configured.");
-        println!("\nAdd a template with: op-loader template add <path>");
-        return Ok(());
+    let payload = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .context("Failed to decode cache base64")?;
+
+    if payload.len() < 1 + 12 {
+        anyhow::bail!("Invalid cache payload length");
     }
 
-    let templates_dir = get_templates_dir()?;
+    if payload[0] != CACHE_PAYLOAD_VERSION {
+        anyhow::bail!("Unsupported cache payload version");
+    }
 
-    println!("Managed template files:\n");
-    for (target_path, template_config) in &config.templated_files {
-        let template_path = templates_dir.join(&template_config.template_name);
-        let status = if template_path.exists() {
-            "✓"
-        } else {
-            "✗ (missing)"
+    let nonce = Nonce::from_slice(&payload[1..13]);
+    let ciphertext = &payload[13..];
+
+    cipher
+        .decrypt(
+            nonce,
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map_err(|err| anyhow::anyhow!("Failed to decrypt cache: {err}"))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn write_cached_output(_account_id: &str, _kind: CacheKind, _entry: &CacheEntry) -> Result<()> {
+    anyhow::bail!("Cache is only supported on macOS and Windows.");
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn write_cached_output(account_id: &str, kind: CacheKind, entry: &CacheEntry) -> Result<()> {
+    write_cached_output_encrypted(account_id, kind, entry)
+}
+
+/// Whether a resolve may be answered from a valid cache entry, or must call
+/// `op` and overwrite the entry with a full TTL (as `cache warm` does).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CacheRead {
+    Use,
+    Overwrite,
+}
+
+/// Resolves one account's vars, from the cache where allowed, and says
+/// whether the account's cache file was written.
+fn load_resolved_vars(
+    op: &dyn OpClient,
+    account_id: &str,
+    input: &str,
+    batching: Batching,
+    cache_ttl: Option<Duration>,
+    cache_read: CacheRead,
+    cache_lock_wait: Duration,
+) -> Result<(std::collections::HashMap<String, String>, bool)> {
+    if let Some(ttl) = cache_ttl {
+        let input_hash = config_hash(input);
+
+        // Fast path: check cache before acquiring any lock.
+        let cached = match cache_read {
+            CacheRead::Use => {
+                read_cached_output(account_id, CacheKind::ResolvedVars, ttl, &input_hash)
+            }
+            CacheRead::Overwrite => Ok(CacheReadOutcome::Miss),
         };
-        println!("  {status} {target_path}");
-        println!("    └─ {}", template_path.display());
+        match cached {
+            Ok(CacheReadOutcome::Hit(cached)) => {
+                info!("Cache hit for account {account_id}");
+                track_cache_event(account_id, CacheEvent::Hit);
+                return Ok((parse_cached_vars(&cached)?, false));
+            }
+            Ok(CacheReadOutcome::ExpiringSoon(cached)) => {
+                info!("Cache hit for account {account_id}, refreshing in the background");
+                track_cache_event(account_id, CacheEvent::Hit);
+                spawn_background_refresh(account_id, ttl);
+                return Ok((parse_cached_vars(&cached)?, false));
+            }
+            Ok(CacheReadOutcome::Miss) if cache_read == CacheRead::Overwrite => {}
+            Ok(CacheReadOutcome::Miss) => track_cache_event(account_id, CacheEvent::Miss),
+            Ok(CacheReadOutcome::Expired) => track_cache_event(account_id, CacheEvent::Expired),
+            Ok(CacheReadOutcome::Stale) => track_cache_event(account_id, CacheEvent::Stale),
+            Err(_) => {}
+        }
+
+        try_log_cache_state(account_id, CacheKind::ResolvedVars, ttl, &input_hash);
+
+        // Acquire per-account exclusive lock with timeout.
+        let lock_file = open_lock_file_for_account(account_id)?;
+        let acquired = lock_exclusive_with_timeout(&lock_file, cache_lock_wait)?;
+        if !acquired {
+            anyhow::bail!(
+                "Cache lock for account {account_id} not acquired within {}s",
+                cache_lock_wait.as_secs()
+            );
+        }
+
+        // Double-check: another process may have populated the cache while
+        // we were waiting on the lock.
+        if cache_read == CacheRead::Use
+            && let Ok(Some(cached)) =
+                read_cached_output_if_fresh(account_id, CacheKind::ResolvedVars, ttl, &input_hash)
+        {
+            info!("Cache hit (after lock) for account {account_id}");
+            let _ = lock_file.unlock();
+            return Ok((parse_cached_vars(&cached)?, false));
+        }
+
+        // Cache is stale/missing and we hold the lock — resolve via op inject.
+        let resolved_json = resolve_account_json(op, account_id, input, batching)?;
+        let entry = CacheEntry::new(resolved_json.clone(), ttl, input_hash);
+        let written = match write_cached_output(account_id, CacheKind::ResolvedVars, &entry) {
+            Ok(()) => true,
+            Err(err) => {
+                eprintln!("# Warning: Failed to write cache for account {account_id}: {err}");
+                false
+            }
+        };
+        let _ = lock_file.unlock();
+        return Ok((parse_cached_vars(&resolved_json)?, written));
     }
 
-    Ok(())
+    let resolved_json = resolve_account_json(op, account_id, input, batching)?;
+    Ok((parse_cached_vars(&resolved_json)?, false))
 }
 
-fn template_remove(path: &str) -> Result<()> {
-    info!("Removing template for: {path}");
+/// Attempt to acquire an exclusive lock on `file`, blocking up to `timeout`.
+///
+/// Returns `Ok(true)` if the lock was acquired, `Ok(false)` if the timeout
+/// elapsed. Uses a background thread so the caller's thread can enforce
+/// the deadline.
+fn lock_exclusive_with_timeout(file: &std::fs::File, timeout: Duration) -> Result<bool> {
+    use fs2::FileExt;
+    use std::sync::mpsc;
+
+    // First try a non-blocking acquire — avoids spawning a thread when
+    // the lock is uncontended (the common case).
+    if file.try_lock_exclusive().is_ok() {
+        return Ok(true);
+    }
+
+    info!("Lock contended, waiting up to {}s", timeout.as_secs());
+
+    // Clone the file descriptor so the background thread can call the
+    // blocking lock_exclusive() without borrowing from the caller.
+    let file_dup = file.try_clone().context("Failed to duplicate lock fd")?;
+    let (tx, rx) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let result = file_dup.lock_exclusive();
+        // If the receiver has been dropped (timeout elapsed), release the
+        // lock we just acquired so we don't hold it indefinitely.
+        if tx.send(result).is_err() {
+            let _ = file_dup.unlock();
+        }
+    });
+
+    match rx.recv_timeout(timeout) {
+        Ok(Ok(())) => Ok(true),
+        Ok(Err(err)) => Err(err).context("Failed to acquire exclusive lock"),
+        Err(mpsc::RecvTimeoutError::Timeout) => Ok(false),
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            anyhow::bail!("Lock thread terminated unexpectedly")
+        }
+    }
+}
+
+/// Resolves one account's vars with a single `op inject` call, or with one
+/// call per var run in parallel, and records how long it took.
+fn resolve_account_json(
+    op: &dyn OpClient,
+    account_id: &str,
+    input: &str,
+    batching: Batching,
+) -> Result<String> {
+    let started = std::time::Instant::now();
+    let resolved_json = match batching {
+        Batching::Batched => resolve_vars_json(op, account_id, input)?,
+        Batching::PerVar => {
+            let references = split_inject_blocks(input)?;
+            let vars = std::thread::scope(|s| {
+                let handles: Vec<_> = references
+                    .iter()
+                    .map(|(var_name, reference)| {
+                        s.spawn(move || {
+                            run_op_read(op, account_id, reference).map(|value| (var_name, value))
+                        })
+                    })
+                    .collect();
+                let mut vars = std::collections::HashMap::new();
+                for handle in handles {
+                    let (var_name, value) = handle.join().expect("var resolver thread panicked")?;
+                    vars.insert(var_name, value);
+                }
+                anyhow::Ok(vars)
+            })?;
+            serde_json::to_string(&vars).context("Failed to serialize resolved vars")?
+        }
+    };
+    crate::inject_timing::record(account_id, batching, started.elapsed());
+    Ok(resolved_json)
+}
+
+fn resolve_vars_json(op: &dyn OpClient, account_id: &str, input: &str) -> Result<String> {
+    let references = split_inject_blocks(input)?;
+    if let [(var_name, reference)] = references.as_slice() {
+        let value = run_op_read(op, account_id, reference)?;
+        let vars = std::collections::HashMap::from([(var_name, value)]);
+        return serde_json::to_string(&vars).context("Failed to serialize resolved vars");
+    }
+
+    let output = run_op_inject(op, account_id, input)?;
+    let resolved = split_inject_blocks(&output)?;
+    let expected: Vec<&String> = references.iter().map(|(name, _)| name).collect();
+    let found: Vec<&String> = resolved.iter().map(|(name, _)| name).collect();
+    if found != expected {
+        anyhow::bail!("op inject returned vars {found:?}, expected {expected:?}");
+    }
+    let vars: std::collections::HashMap<String, String> = resolved.into_iter().collect();
+    serde_json::to_string(&vars).context("Failed to serialize resolved vars")
+}
+
+fn parse_cached_vars(cached_json: &str) -> Result<std::collections::HashMap<String, String>> {
+    serde_json::from_str(cached_json).context("Failed to parse cached vars")
+}
+
+fn format_exports(vars: &std::collections::HashMap<String, String>, shell: Shell) -> String {
+    let mut lines: Vec<(&String, &String)> = vars.iter().collect();
+    lines.sort_by(|a, b| a.0.cmp(b.0));
+
+    lines
+        .into_iter()
+        .map(|(key, value)| shell.export(key, value))
+        .collect()
+}
+
+/// Renames resolved vars to the names they're exported under, applying each
+/// mapping's `prefix`.
+pub fn with_prefixes(
+    config: &OpLoadConfig,
+    vars: &std::collections::HashMap<String, String>,
+) -> std::collections::HashMap<String, String> {
+    vars.iter()
+        .map(|(name, value)| {
+            let name = config
+                .inject_vars
+                .get(name)
+                .map_or_else(|| name.clone(), |var| var.exported_name(name));
+            (name, value.clone())
+        })
+        .collect()
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn write_cached_output_encrypted(
+    account_id: &str,
+    kind: CacheKind,
+    entry: &CacheEntry,
+) -> Result<()> {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    ensure_cache_dir()?;
+    let path = cache_file_for_account(account_id, kind)?;
+    let tmp_path = path.with_extension("cache.tmp");
+
+    let plaintext = serde_json::to_vec(entry).context("Failed to serialize cache entry")?;
+    let encrypted = encrypt_cache(account_id, kind, &plaintext)?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&tmp_path)
+        .with_context(|| {
+            format!(
+                "Failed to open temp cache file for writing: {}",
+                tmp_path.display()
+            )
+        })?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = file.metadata()?.permissions();
+        perms.set_mode(0o600);
+        std::fs::set_permissions(&tmp_path, perms).with_context(|| {
+            format!(
+                "Failed to set cache file permissions: {}",
+                tmp_path.display()
+            )
+        })?;
+    }
+
+    file.write_all(encrypted.as_bytes())
+        .with_context(|| format!("Failed to write temp cache file: {}", tmp_path.display()))?;
+
+    // Flush to disk before rename to ensure readers see complete data.
+    file.sync_all()
+        .with_context(|| format!("Failed to sync temp cache file: {}", tmp_path.display()))?;
+    drop(file);
+
+    // Atomic rename: readers either see the old file or the new complete file.
+    std::fs::rename(&tmp_path, &path)
+        .with_context(|| format!("Failed to rename temp cache to {}", path.display()))?;
+
+    Ok(())
+}
+
+fn open_lock_file_for_account(account_id: &str) -> Result<std::fs::File> {
+    use std::fs::OpenOptions;
+
+    ensure_cache_dir()?;
+    let lock_path = lock_path_for_account(account_id)?;
+    let lock_file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .write(true)
+        .truncate(false)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open cache lock: {}", lock_path.display()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = lock_file.metadata()?.permissions();
+        perms.set_mode(0o600);
+        std::fs::set_permissions(&lock_path, perms).with_context(|| {
+            format!(
+                "Failed to set lock file permissions: {}",
+                lock_path.display()
+            )
+        })?;
+    }
+
+    Ok(lock_file)
+}
+
+pub fn get_templates_dir() -> Result<PathBuf> {
+    let config_path = confy::get_configuration_file_path("op_loader", None)
+        .context("Failed to get config path")?;
+    let config_dir = config_path
+        .parent()
+        .context("Config path has no parent directory")?;
+    Ok(config_dir.join("templates"))
+}
+
+pub fn expand_path(path: &str) -> Result<PathBuf> {
+    let expanded = expand_home(path)?;
+    if expanded.exists() {
+        expanded
+            .canonicalize()
+            .with_context(|| format!("Failed to canonicalize path: {}", expanded.display()))
+    } else {
+        Ok(expanded)
+    }
+}
+
+/// Expands a leading `~/` without resolving symlinks.
+pub fn expand_home(path: &str) -> Result<PathBuf> {
+    if let Some(suffix) = path.strip_prefix("~/") {
+        let home = std::env::var("HOME").context("HOME environment variable not set")?;
+        Ok(PathBuf::from(home).join(suffix))
+    } else {
+        Ok(PathBuf::from(path))
+    }
+}
+
+pub fn handle_template_action(action: TemplateAction) -> Result<()> {
+    debug!("Handling template action: {action:?}");
+
+    match action {
+        TemplateAction::Add { path, no_scan } => template_add(&path, no_scan),
+        TemplateAction::Adopt {
+            path,
+            cache_ttl,
+            cache_lock_wait,
+        } => template_adopt(&path, cache_ttl.as_deref(), &cache_lock_wait),
+        TemplateAction::List { porcelain } => template_list(porcelain),
+        TemplateAction::Edit { path } => template_edit(&path),
+        TemplateAction::Remove { path } => template_remove(&path),
+        TemplateAction::Link { path } => template_link(&path),
+        TemplateAction::Unlink { path } => template_unlink(&path),
+        TemplateAction::Scope { path, vars, all } => template_scope(&path, (!all).then_some(vars)),
+        TemplateAction::History { path } => template_history(&path),
+        TemplateAction::Diff { path, against } => template_diff(&path, &against),
+        TemplateAction::Rollback { path, to } => template_rollback(&path, to),
+        TemplateAction::MigrateNames { dry_run } => template_migrate_names(dry_run),
+        TemplateAction::Check { paths } => template_check(&paths),
+        TemplateAction::Watch { paths } => template_watch(&paths),
+        TemplateAction::Restore { path, list, backup } => template_restore(&path, list, backup),
+        TemplateAction::Render {
+            path,
+            only,
+            force,
+            stdout,
+            mask,
+        } => {
+            let paths: Vec<String> = path.into_iter().chain(only).collect();
+            if stdout {
+                template_render_stdout(&paths, mask)
+            } else {
+                template_render(&paths, force)
+            }
+        }
+    }
+}
+
+pub fn handle_debug_action(action: DebugAction) -> Result<()> {
+    match action {
+        DebugAction::State { out } => {
+            let path = crate::debug_state::write_snapshot(&crate::debug_state::collect(None), out)?;
+            println!("Wrote state snapshot to {}", path.display());
+            println!("It contains no secret values or op:// references; review it before sharing.");
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_file_action(action: FileAction) -> Result<()> {
+    debug!("Handling file action: {action:?}");
+
+    let mut config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    match action {
+        FileAction::Add {
+            path,
+            item,
+            vault,
+            account,
+        } => {
+            let target = expand_path(&path)?;
+            let account_id = account
+                .or_else(|| config.default_account_id.clone())
+                .context("Pass --account or set a default account in the TUI")?;
+            let file = document_item(&SubprocessOpClient, &account_id, &vault, &item)?;
+            let contents = crate::managed_files::fetch(&SubprocessOpClient, &file)?;
+            crate::managed_files::place(&target, &contents)?;
+
+            println!("Placed {} at {}", file.title, target.display());
+            config
+                .managed_files
+                .insert(target.to_string_lossy().to_string(), file);
+            confy::store("op_loader", None, &config).context("Failed to save configuration")?;
+        }
+        FileAction::List => file_list(&config),
+        FileAction::Remove { path } => {
+            let target = expand_path(&path)?;
+            let target_key = target.to_string_lossy().to_string();
+            if config.managed_files.remove(&target_key).is_none() {
+                anyhow::bail!("Not a managed file: {}", target.display());
+            }
+            confy::store("op_loader", None, &config).context("Failed to save configuration")?;
+            println!(
+                "Stopped managing {}; the file itself was left in place.",
+                target.display()
+            );
+        }
+        FileAction::Sync => {
+            let placed = sync_managed_files(&SubprocessOpClient, &config, None);
+            println!("Placed {placed}/{} file(s)", config.managed_files.len());
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_ssh_action(action: SshAction) -> Result<()> {
+    debug!("Handling ssh action: {action:?}");
+
+    match action {
+        SshAction::Setup {
+            item,
+            vault,
+            account,
+            host,
+            hostname,
+            user,
+            no_config,
+        } => {
+            let mut config: OpLoadConfig =
+                confy::load("op_loader", None).context("Failed to load configuration")?;
+            let account_id = account
+                .or_else(|| config.default_account_id.clone())
+                .context("Pass --account or set a default account in the TUI")?;
+            let key = crate::ssh_keys::lookup(&SubprocessOpClient, &account_id, &vault, &item)?;
+            let stem = crate::ssh_keys::key_file_stem(&key.title);
+            let identity_file = format!("~/.ssh/{stem}.pub");
+            let public_key_path = expand_path(&identity_file)?;
+            crate::ssh_keys::export_public_key(&public_key_path, &key, &config.allowed_sync_paths)?;
+            println!(
+                "Exported the public key of {} to {}",
+                key.title,
+                public_key_path.display()
+            );
+            if let Some(fingerprint) = &key.fingerprint {
+                println!("Fingerprint: {fingerprint}");
+            }
+
+            if !no_config {
+                let host = host.unwrap_or(stem);
+                let stanza = crate::ssh_keys::config_stanza(
+                    &host,
+                    &identity_file,
+                    &crate::ssh_keys::HostOptions {
+                        hostname: hostname.as_deref(),
+                        user: user.as_deref(),
+                    },
+                );
+                add_ssh_host(&mut config, &host, &stanza)?;
+            }
+            println!(
+                "\nRun `op-loader ssh agent` to check that the 1Password SSH agent offers the key."
+            );
+        }
+        SshAction::Agent { shell } => print!("{}", crate::ssh_keys::agent_instructions(shell)?),
+    }
+    Ok(())
+}
+
+/// Adds a `Host` block to `~/.ssh/config` through its template, adopting the
+/// file as a template first if it isn't one, so later renders keep the block.
+fn add_ssh_host(config: &mut OpLoadConfig, host: &str, stanza: &str) -> Result<()> {
+    let ssh_config = expand_path("~/.ssh/config")?;
+    let target_key = ssh_config.to_string_lossy().to_string();
+    let current = match std::fs::read_to_string(&ssh_config) {
+        Ok(current) => current,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", ssh_config.display()));
+        }
+    };
+
+    match config.templated_files.get(&target_key) {
+        Some(templated) => {
+            let template_path = get_templates_dir()?.join(&templated.template_name);
+            let template = std::fs::read_to_string(&template_path)
+                .with_context(|| format!("Failed to read template {}", template_path.display()))?;
+            if crate::ssh_keys::has_host(&template, host) {
+                println!("{} already has a Host {host} block", ssh_config.display());
+                return Ok(());
+            }
+            std::fs::write(
+                &template_path,
+                crate::ssh_keys::append_stanza(&template, stanza),
+            )
+            .with_context(|| format!("Failed to write template to {}", template_path.display()))?;
+        }
+        None => {
+            if crate::ssh_keys::has_host(&current, host) {
+                println!("{} already has a Host {host} block", ssh_config.display());
+                return Ok(());
+            }
+            let template_path = store_template(
+                config,
+                &ssh_config,
+                &crate::ssh_keys::append_stanza(&current, stanza),
+            )?;
+            println!("Template stored at: {}", template_path.display());
+        }
+    }
+
+    // Written straight to the file too: rendering every template now would
+    // need their secrets resolved first.
+    if !crate::ssh_keys::has_host(&current, host) {
+        std::fs::write(
+            &ssh_config,
+            crate::ssh_keys::append_stanza(&current, stanza),
+        )
+        .with_context(|| format!("Failed to write {}", ssh_config.display()))?;
+    }
+    println!("Added Host {host} to {}", ssh_config.display());
+    Ok(())
+}
+
+/// Looks up `item` and checks that it's a document, since `op document get`
+/// only works for those.
+fn document_item(
+    op: &dyn OpClient,
+    account_id: &str,
+    vault: &str,
+    item: &str,
+) -> Result<ManagedFile> {
+    #[derive(serde::Deserialize)]
+    struct ItemSummary {
+        id: String,
+        title: String,
+        category: String,
+        vault: VaultRef,
+    }
+    #[derive(serde::Deserialize)]
+    struct VaultRef {
+        id: String,
+    }
+
+    let output = op
+        .run(
+            &[
+                "item",
+                "get",
+                item,
+                "--vault",
+                vault,
+                "--account",
+                account_id,
+                "--format",
+                "json",
+            ],
+            None,
+        )
+        .context("Failed to run `op item get`")?;
+    if !output.success {
+        anyhow::bail!("op item get failed: {}", annotate(&output.stderr));
+    }
+    let summary: ItemSummary =
+        serde_json::from_slice(&output.stdout).context("Failed to parse item JSON")?;
+    if summary.category != "DOCUMENT" {
+        anyhow::bail!(
+            "{} is a {} item; only document items can be placed as files",
+            summary.title,
+            crate::app::category_label(&summary.category)
+        );
+    }
+
+    Ok(ManagedFile {
+        account_id: account_id.to_string(),
+        vault_id: summary.vault.id,
+        item_id: summary.id,
+        title: summary.title,
+    })
+}
+
+fn file_list(config: &OpLoadConfig) {
+    if config.managed_files.is_empty() {
+        println!("No managed files.");
+        println!("\nAdd one with: op-loader file add <path> --item <document> --vault <vault>");
+        return;
+    }
+
+    let mut files: Vec<_> = config.managed_files.iter().collect();
+    files.sort_by(|a, b| a.0.cmp(b.0));
+    for (target, file) in files {
+        let state = if Path::new(target).exists() {
+            ""
+        } else {
+            "  (not placed yet)"
+        };
+        println!("{target}  <-  {} ({}){state}", file.title, file.account_id);
+    }
+}
+
+/// Downloads each managed file and writes it to its target with mode 0600,
+/// warning about failures instead of stopping. With a TTL, an account's
+/// files are left alone while its documents cache is fresh and the files
+/// still match what was placed. Returns how many files are in place.
+fn sync_managed_files(
+    op: &dyn OpClient,
+    config: &OpLoadConfig,
+    cache_ttl: Option<Duration>,
+) -> usize {
+    let mut by_account: std::collections::BTreeMap<&str, Vec<(&str, &ManagedFile)>> =
+        std::collections::BTreeMap::new();
+    for (target, file) in &config.managed_files {
+        by_account
+            .entry(file.account_id.as_str())
+            .or_default()
+            .push((target.as_str(), file));
+    }
+
+    let mut placed_count = 0;
+    for (account_id, files) in by_account {
+        let spec_hash = config_hash(&crate::managed_files::spec(&files));
+        if let Some(ttl) = cache_ttl
+            && let Ok(Some(cached)) =
+                read_cached_output_if_fresh(account_id, CacheKind::Documents, ttl, &spec_hash)
+            && let Ok(hashes) = serde_json::from_str::<crate::managed_files::PlacedHashes>(&cached)
+            && files.iter().all(|(target, _)| {
+                hashes
+                    .get(*target)
+                    .is_some_and(|hash| crate::managed_files::is_current(Path::new(target), hash))
+            })
+        {
+            info!("Documents cache hit for account {account_id}");
+            placed_count += files.len();
+            continue;
+        }
+
+        let mut hashes = crate::managed_files::PlacedHashes::new();
+        for (target, file) in &files {
+            if let Err(err) =
+                crate::sync_guard::ensure_allowed(Path::new(target), &config.allowed_sync_paths)
+            {
+                eprintln!("# Warning: Skipping {target}: {err:#}");
+                continue;
+            }
+            let result = crate::managed_files::fetch(op, file).and_then(|contents| {
+                crate::managed_files::place(Path::new(target), &contents)?;
+                Ok(crate::managed_files::content_hash(&contents))
+            });
+            match result {
+                Ok(hash) => {
+                    info!("Placed {} at {target}", file.title);
+                    hashes.insert((*target).to_string(), hash);
+                }
+                Err(err) => eprintln!("# Warning: Failed to place {target}: {err:#}"),
+            }
+        }
+        placed_count += hashes.len();
+
+        if let Some(ttl) = cache_ttl
+            && hashes.len() == files.len()
+        {
+            let payload = serde_json::to_string(&hashes).unwrap_or_default();
+            let entry = CacheEntry::new(payload, ttl, spec_hash);
+            if let Err(err) = write_cached_output(account_id, CacheKind::Documents, &entry) {
+                debug!("Failed to write documents cache for account {account_id}: {err}");
+            }
+        }
+    }
+    placed_count
+}
+
+pub fn handle_var_action(action: VarAction) -> Result<()> {
+    debug!("Handling var action: {action:?}");
+
+    match action {
+        VarAction::List { porcelain } => {
+            let config: OpLoadConfig =
+                confy::load("op_loader", None).context("Failed to load configuration")?;
+            if porcelain {
+                print!("{}", var_list_porcelain(&config));
+            } else {
+                var_list(&config);
+            }
+        }
+        VarAction::Test { name } => {
+            let config: OpLoadConfig =
+                confy::load("op_loader", None).context("Failed to load configuration")?;
+            println!("{}", var_test(&SubprocessOpClient, &config, &name)?);
+        }
+    }
+
+    Ok(())
+}
+
+/// `✓ op read op://... (NAME: ********, 24 chars)`, like the TUI's test.
+fn var_test(op: &dyn OpClient, config: &OpLoadConfig, name: &str) -> Result<String> {
+    let var = config
+        .inject_vars
+        .get(name)
+        .with_context(|| format!("{name} is not a managed var"))?;
+    let value = run_op_read(op, &var.account_id, &var.op_reference)?;
+    Ok(format!(
+        "✓ op read {} ({name}: {})",
+        var.op_reference,
+        crate::app::masked_length(value.chars().count())
+    ))
+}
+
+fn sorted_vars(config: &OpLoadConfig) -> Vec<(&String, &InjectVarConfig)> {
+    let mut vars: Vec<_> = config.inject_vars.iter().collect();
+    vars.sort_by(|a, b| a.0.cmp(b.0));
+    vars
+}
+
+fn var_list_porcelain(config: &OpLoadConfig) -> String {
+    porcelain::records(sorted_vars(config).into_iter().map(|(name, var)| {
+        vec![
+            name.as_str(),
+            var.account_id.as_str(),
+            var.op_reference.as_str(),
+        ]
+    }))
+}
+
+fn var_list(config: &OpLoadConfig) {
+    let vars = sorted_vars(config);
+    if vars.is_empty() {
+        println!("No variables configured.");
+        println!("\nMap a field to a variable in the TUI: op-loader");
+        return;
+    }
+
+    let name_width = vars
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    let account_width = vars
+        .iter()
+        .map(|(_, var)| var.account_id.len())
+        .max()
+        .unwrap_or(0)
+        .max(7);
+    println!(
+        "{:<name_width$}  {:<account_width$}  REFERENCE",
+        "NAME", "ACCOUNT"
+    );
+    for (name, var) in vars {
+        println!(
+            "{name:<name_width$}  {:<account_width$}  {}",
+            var.account_id, var.op_reference
+        );
+    }
+}
+
+/// A snapshot of everything op-loader manages on this machine.
+#[derive(Debug, PartialEq, Eq)]
+struct StatusSummary {
+    vars: usize,
+    accounts: usize,
+    templates: usize,
+    templates_missing: usize,
+    cache_files: usize,
+    cache_bytes: u64,
+    agent_running: bool,
+    agent_health: Option<crate::agent::AgentHealth>,
+    telemetry_enabled: bool,
+    default_account: Option<String>,
+    slow_accounts: Vec<crate::inject_timing::SlowAccount>,
+}
+
+impl StatusSummary {
+    fn collect(
+        config: &OpLoadConfig,
+        templates_dir: &Path,
+        cache_files: &[crate::cache::CacheFileInfo],
+        agent_running: bool,
+        agent_health: Option<crate::agent::AgentHealth>,
+        slow_accounts: Vec<crate::inject_timing::SlowAccount>,
+    ) -> Self {
+        Self {
+            vars: config.inject_vars.len(),
+            accounts: group_vars_by_account(&config.inject_vars).len(),
+            templates: config.templated_files.len(),
+            templates_missing: template_rows(config, templates_dir)
+                .iter()
+                .filter(|(_, _, exists)| !exists)
+                .count(),
+            cache_files: cache_files.len(),
+            cache_bytes: cache_files.iter().map(|file| file.size).sum(),
+            agent_running,
+            agent_health,
+            telemetry_enabled: config.telemetry.enabled,
+            default_account: config.default_account_id.clone(),
+            slow_accounts,
+        }
+    }
+
+    /// `key<TAB>value` records in a fixed order. Booleans are `yes`/`no`;
+    /// an unset default account and the `agent_*` health keys of an agent
+    /// that isn't running (or didn't answer) are empty. `slow_accounts` is
+    /// a comma-separated list of account ids.
+    fn porcelain(&self) -> String {
+        let yes_no = |flag: bool| if flag { "yes" } else { "no" }.to_string();
+        let health = |value: fn(&crate::agent::AgentHealth) -> String| {
+            self.agent_health.as_ref().map(value).unwrap_or_default()
+        };
+        porcelain::records([
+            vec!["vars".to_string(), self.vars.to_string()],
+            vec!["accounts".to_string(), self.accounts.to_string()],
+            vec!["templates".to_string(), self.templates.to_string()],
+            vec![
+                "templates_missing".to_string(),
+                self.templates_missing.to_string(),
+            ],
+            vec!["cache_files".to_string(), self.cache_files.to_string()],
+            vec!["cache_bytes".to_string(), self.cache_bytes.to_string()],
+            vec!["agent_running".to_string(), yes_no(self.agent_running)],
+            vec![
+                "telemetry_enabled".to_string(),
+                yes_no(self.telemetry_enabled),
+            ],
+            vec![
+                "default_account".to_string(),
+                self.default_account.clone().unwrap_or_default(),
+            ],
+            vec![
+                "agent_uptime_secs".to_string(),
+                health(|h| h.uptime_secs.to_string()),
+            ],
+            vec![
+                "agent_accounts_warmed".to_string(),
+                health(|h| h.accounts_warmed.len().to_string()),
+            ],
+            vec![
+                "agent_last_refresh_secs".to_string(),
+                health(|h| h.last_refresh_secs.to_string()),
+            ],
+            vec![
+                "agent_locked".to_string(),
+                health(|h| if h.locked { "yes" } else { "no" }.to_string()),
+            ],
+            vec![
+                "slow_accounts".to_string(),
+                self.slow_accounts
+                    .iter()
+                    .map(|slow| slow.account_id.as_str())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ],
+        ])
+    }
+}
+
+pub fn handle_doctor() -> Result<()> {
+    let mut problems = 0;
+
+    match SubprocessOpClient.version() {
+        Some(version) => {
+            let unsupported: Vec<&str> = OpFeature::ALL
+                .into_iter()
+                .filter(|feature| version < feature.min_version())
+                .map(OpFeature::describe)
+                .collect();
+            if unsupported.is_empty() {
+                println!("✓ op {version} supports everything op-loader uses");
+            } else {
+                problems += 1;
+                println!("✗ op {version} is too old for {}", unsupported.join(", "));
+                println!("  Update the 1Password CLI to 2.0 or later.");
+            }
+        }
+        None => {
+            problems += 1;
+            println!("✗ Couldn't run `op --version`; is the 1Password CLI on PATH?");
+        }
+    }
+
+    let issues = crate::permissions::audit(&crate::permissions::audited_paths()?)?;
+    if issues.is_empty() {
+        println!("✓ Config, templates, and cache are only accessible to you");
+    } else {
+        problems += issues.len();
+        println!("✗ {} path(s) are accessible to other users:", issues.len());
+        for issue in &issues {
+            println!("  {issue}");
+        }
+        println!(
+            "  Run `op-loader fix-perms` to tighten them, or set `security.strict_permissions` to do it on every run."
+        );
+    }
+
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let shared: Vec<TemplateRename> = plan_renames(&config, config.templates.naming)
+        .into_iter()
+        .filter(|rename| !rename.shared_with.is_empty())
+        .collect();
+    if shared.is_empty() {
+        println!("✓ Every managed file has its own template");
+    } else {
+        problems += shared.len();
+        println!("✗ {} managed file(s) share a template:", shared.len());
+        for rename in &shared {
+            println!(
+                "  {} uses {} with {}",
+                rename.target,
+                rename.from,
+                rename.shared_with.join(", ")
+            );
+        }
+        println!("  Run `op-loader template migrate-names` to give each its own.");
+    }
+
+    // Slowness is advice rather than a problem, so it doesn't fail doctor.
+    let slow = crate::inject_timing::load_timings(&cache_dir()?).slow_accounts();
+    if slow.is_empty() {
+        println!("✓ op inject has been quick for every account");
+    }
+    for account in &slow {
+        println!(
+            "! op inject for account {} typically takes {:.1}s",
+            account.account_id,
+            account.median.as_secs_f64()
+        );
+        println!("  To skip it on most shell starts, {}.", account.suggestion);
+    }
+
+    if problems > 0 {
+        anyhow::bail!("doctor found {problems} problem(s)");
+    }
+    Ok(())
+}
+
+pub fn handle_fix_perms(dry_run: bool) -> Result<()> {
+    let roots = crate::permissions::audited_paths()?;
+    let issues = if dry_run {
+        crate::permissions::audit(&roots)?
+    } else {
+        crate::permissions::fix_all(&roots)?
+    };
+
+    if issues.is_empty() {
+        println!("Permissions are already owner-only.");
+    }
+    for issue in &issues {
+        if dry_run {
+            println!(
+                "Would set {:o} on {}",
+                issue.expected_mode(),
+                issue.path.display()
+            );
+        } else {
+            println!(
+                "Set {:o} on {} (was {:o})",
+                issue.expected_mode(),
+                issue.path.display(),
+                issue.mode
+            );
+        }
+    }
+    Ok(())
+}
+
+pub fn handle_status(porcelain: bool) -> Result<()> {
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let agent_running = crate::agent::is_running();
+    // Older agents don't know the health request; they still count as running.
+    let agent_health = agent_running
+        .then(crate::agent::request_health)
+        .and_then(Result::ok);
+    let summary = StatusSummary::collect(
+        &config,
+        &get_templates_dir()?,
+        &list_cache_files(&cache_dir()?)?,
+        agent_running,
+        agent_health,
+        crate::inject_timing::load_timings(&cache_dir()?).slow_accounts(),
+    );
+
+    if porcelain {
+        print!("{}", summary.porcelain());
+        return Ok(());
+    }
+
+    println!(
+        "Variables:  {} across {} account(s)",
+        summary.vars, summary.accounts
+    );
+    match summary.templates_missing {
+        0 => println!("Templates:  {}", summary.templates),
+        missing => println!("Templates:  {} ({missing} missing)", summary.templates),
+    }
+    println!(
+        "Cache:      {} file(s), {}",
+        summary.cache_files,
+        format_size(summary.cache_bytes)
+    );
+    match (&summary.agent_health, summary.agent_running) {
+        (Some(health), _) => println!(
+            "Agent:      {} for {}, {} account(s) warmed, refreshed {} ago",
+            if health.locked { "locked" } else { "running" },
+            format_age(Duration::from_secs(health.uptime_secs)),
+            health.accounts_warmed.len(),
+            format_age(Duration::from_secs(health.last_refresh_secs))
+        ),
+        (None, true) => println!("Agent:      running"),
+        (None, false) => println!("Agent:      not running"),
+    }
+    println!(
+        "Telemetry:  {}",
+        if summary.telemetry_enabled {
+            "on"
+        } else {
+            "off"
+        }
+    );
+    println!(
+        "Default account: {}",
+        summary.default_account.as_deref().unwrap_or("(not set)")
+    );
+    for slow in &summary.slow_accounts {
+        println!(
+            "Slow account: {} takes {:.1}s to resolve; {}",
+            slow.account_id,
+            slow.median.as_secs_f64(),
+            slow.suggestion
+        );
+    }
+
+    Ok(())
+}
+
+pub fn handle_cache_action(action: CacheAction) -> Result<()> {
+    debug!("Handling cache action: {action:?}");
+
+    match action {
+        CacheAction::Clear { account, secure } => {
+            if let Some(account_id) = account {
+                match remove_cache_for_account(&account_id, secure) {
+                    Ok(CacheRemoval::Removed) => {
+                        println!("Cleared cache for account {account_id}");
+                    }
+                    Ok(CacheRemoval::NotFound) => {
+                        println!("No cache found for account {account_id}");
+                    }
+                    Err(err) => {
+                        eprintln!("Warning: Failed to clear cache for account {account_id}: {err}");
+                    }
+                }
+            } else {
+                clear_all_caches(secure)?;
+                match crate::template_history::history_dir()
+                    .and_then(|dir| crate::template_history::forget_content(&dir, secure))
+                {
+                    Ok(0) => {}
+                    Ok(forgotten) => {
+                        println!("Dropped stored render content for {forgotten} template(s).");
+                    }
+                    Err(err) => {
+                        eprintln!("Warning: Failed to drop stored render content: {err}");
+                    }
+                }
+                #[cfg(any(target_os = "macos", target_os = "windows"))]
+                {
+                    if let Err(err) = delete_key() {
+                        eprintln!("Warning: Failed to delete cache key from Keychain: {err}");
+                    }
+                }
+            }
+        }
+        CacheAction::List => cache_list()?,
+        CacheAction::Stats => cache_stats()?,
+        CacheAction::Status { porcelain } => cache_status(porcelain)?,
+        CacheAction::Refresh { account, ttl } => {
+            if let Err(err) = cache_refresh(&account, &ttl) {
+                notify(
+                    "op-loader background cache refresh failed",
+                    &format!("Account {account}: {err}"),
+                );
+                return Err(err);
+            }
+        }
+        CacheAction::Warm {
+            ttl,
+            cache_lock_wait,
+        } => cache_warm(&ttl, &cache_lock_wait)?,
+    }
+
+    Ok(())
+}
+
+fn cache_list() -> Result<()> {
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let dir = cache_dir()?;
+    let files = list_cache_files(&dir)?;
+
+    if files.is_empty() {
+        println!("No cache files in {}", dir.display());
+        return Ok(());
+    }
+
+    let now = std::time::SystemTime::now();
+    for file in &files {
+        let name = file
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let age = now.duration_since(file.modified).unwrap_or_default();
+        println!(
+            "{name}  {}  modified {} ago",
+            format_size(file.size),
+            format_age(age)
+        );
+    }
+
+    let total: u64 = files.iter().map(|file| file.size).sum();
+    match config.cache.max_size.as_deref() {
+        Some(max_size) => println!(
+            "Total: {} (max {})",
+            format_size(total),
+            format_size(parse_size(max_size)?)
+        ),
+        None => println!("Total: {}", format_size(total)),
+    }
+
+    Ok(())
+}
+
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+fn track_cache_event(account_id: &str, event: CacheEvent) {
+    crate::telemetry::record_cache_lookup(event == CacheEvent::Hit);
+    if let Err(err) = cache_dir().and_then(|dir| record_cache_event(&dir, account_id, event)) {
+        debug!("Failed to record cache stats: {err}");
+    }
+}
+
+fn cache_stats() -> Result<()> {
+    let stats = load_cache_stats(&cache_dir()?)?;
+    if stats.is_empty() {
+        println!("No cache lookups recorded yet.");
+        return Ok(());
+    }
+
+    let width = stats.keys().map(String::len).max().unwrap_or(0).max(7);
+    println!(
+        "{:<width$}  {:>6}  {:>6}  {:>7}  {:>5}  {:>8}",
+        "ACCOUNT", "HITS", "MISSES", "EXPIRED", "STALE", "HIT RATE"
+    );
+    for (account_id, entry) in &stats {
+        #[allow(clippy::cast_precision_loss)]
+        let hit_rate = if entry.lookups() == 0 {
+            0.0
+        } else {
+            entry.hits as f64 * 100.0 / entry.lookups() as f64
+        };
+        println!(
+            "{account_id:<width$}  {:>6}  {:>6}  {:>7}  {:>5}  {hit_rate:>7.1}%",
+            entry.hits, entry.misses, entry.expired, entry.stale
+        );
+    }
+
+    Ok(())
+}
+
+/// One row per cache file, oldest first: kind, sanitized account id, size in
+/// bytes, and age in seconds. Files that don't follow the naming scheme are
+/// reported with kind `unknown` and an empty account.
+pub fn cache_status_rows(
+    files: &[crate::cache::CacheFileInfo],
+    now: std::time::SystemTime,
+) -> Vec<(&'static str, String, u64, u64)> {
+    files
+        .iter()
+        .map(|file| {
+            let name = file
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let (kind, account) = CacheKind::parse_file_name(&name)
+                .map_or(("unknown", String::new()), |(kind, account)| {
+                    (kind.label(), account.to_string())
+                });
+            let age = now.duration_since(file.modified).unwrap_or_default();
+            (kind, account, file.size, age.as_secs())
+        })
+        .collect()
+}
+
+fn cache_status(porcelain: bool) -> Result<()> {
+    let files = list_cache_files(&cache_dir()?)?;
+    let rows = cache_status_rows(&files, std::time::SystemTime::now());
+
+    if porcelain {
+        print!(
+            "{}",
+            porcelain::records(rows.iter().map(|(kind, account, size, age)| {
+                vec![
+                    (*kind).to_string(),
+                    account.clone(),
+                    size.to_string(),
+                    age.to_string(),
+                ]
+            }))
+        );
+        return Ok(());
+    }
+
+    if rows.is_empty() {
+        println!("No caches.");
+        return Ok(());
+    }
+
+    let width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0).max(7);
+    println!(
+        "{:<13}  {:<width$}  {:>9}  {:>5}",
+        "KIND", "ACCOUNT", "SIZE", "AGE"
+    );
+    for (kind, account, size, age) in &rows {
+        println!(
+            "{kind:<13}  {account:<width$}  {:>9}  {:>5}",
+            format_size(*size),
+            format_age(Duration::from_secs(*age))
+        );
+    }
+    let total: u64 = rows.iter().map(|row| row.2).sum();
+    println!("Total: {}", format_size(total));
+
+    Ok(())
+}
+
+pub fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m", secs / 60),
+        3600..86400 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
+/// Evicts the oldest cache files when `cache.max_size` is configured and
+/// exceeded, keeping the entry just written for `account_id`.
+/// Applies `cache.max_size` after a write, never evicting the files in
+/// `written`.
+fn enforce_cache_max_size(written: &[PathBuf]) -> Result<()> {
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let Some(max_size) = config.cache.max_size.as_deref() else {
+        return Ok(());
+    };
+
+    let max_bytes = parse_size(max_size).context("Invalid cache.max_size")?;
+    for path in evict_to_size(&cache_dir()?, max_bytes, written)? {
+        info!("Evicted cache file {}", path.display());
+    }
+
+    Ok(())
+}
+
+fn cache_warm(ttl: &str, cache_lock_wait: &str) -> Result<()> {
+    if cfg!(not(any(target_os = "macos", target_os = "windows"))) {
+        anyhow::bail!("Cache is only supported on macOS and Windows.");
+    }
+
+    let ttl = parse_duration(ttl)?.context("Cache TTL cannot be empty")?;
+    let cache_lock_wait =
+        parse_duration(cache_lock_wait)?.unwrap_or_else(|| Duration::from_secs(5));
+
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    if config.inject_vars.is_empty() {
+        println!("No environment variables configured. Nothing to warm.");
+        return Ok(());
+    }
+
+    let vars_by_account = group_vars_by_account(&config.inject_vars);
+
+    // Resolve even where the cache is still valid, so every account gets a
+    // full TTL from now. An entry is only replaced once its account resolves,
+    // so a failed warm leaves the existing caches in place.
+    let vars_by_account_len = vars_by_account.len();
+    let mut failed = 0;
+    for (account_id, result) in resolve_vars_by_account(
+        &SubprocessOpClient,
+        vars_by_account,
+        Some(ttl),
+        CacheRead::Overwrite,
+        cache_lock_wait,
+    )? {
+        // Warming is an explicit retry, so its result replaces any cool-down.
+        record_refresh_result(&account_id, result.is_ok());
+        match result {
+            Ok(resolved) => {
+                println!(
+                    "Warmed cache for account {account_id} ({} vars)",
+                    resolved.len()
+                );
+            }
+            Err(err) => {
+                eprintln!("Failed to warm cache for account {account_id}: {err}");
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        notify(
+            "op-loader cache warm failed",
+            &format!("Failed to warm caches for {failed} account(s)"),
+        );
+        anyhow::bail!("Failed to warm caches for {failed} account(s)");
+    }
+
+    notify(
+        "op-loader cache warm finished",
+        &format!("Warmed caches for {vars_by_account_len} account(s)"),
+    );
+    Ok(())
+}
+
+fn clear_all_caches(secure: bool) -> Result<()> {
+    let dir = cache_dir()?;
+    if !dir.exists() {
+        println!("No cache directory found.");
+        return Ok(());
+    }
+
+    let mut removed = 0usize;
+    let mut failed = 0usize;
+    let mut saw_file = false;
+    for entry in std::fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read cache directory: {}", dir.display()))?
+    {
+        let entry = entry?;
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        match remove_cache_file(&path, secure) {
+            Ok(()) => removed += 1,
+            Err(err) => {
+                failed += 1;
+                eprintln!("Warning: Failed to remove {}: {err}", path.display());
+            }
+        }
+        saw_file = true;
+    }
+
+    if !saw_file {
+        println!("No cache files found.");
+        return Ok(());
+    }
+
+    println!(
+        "Cleared {removed} cache file(s).{suffix}",
+        suffix = if failed > 0 { " (some failures)" } else { "" }
+    );
+    Ok(())
+}
+
+fn template_add(path: &str, no_scan: bool) -> Result<()> {
+    info!("Adding template for: {path}");
+
+    let target_path = crate::template_link::target_path(path)?;
+    let target_key = target_path.to_string_lossy().to_string();
+
+    if !target_path.exists() {
+        anyhow::bail!("File does not exist: {}", target_path.display());
+    }
+
+    let mut config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+
+    if config.templated_files.contains_key(&target_key) {
+        anyhow::bail!(
+            "File is already managed as a template: {}",
+            target_path.display()
+        );
+    }
+
+    let original_content =
+        std::fs::read_to_string(&target_path).context("Failed to read source file")?;
+    let content = if no_scan {
+        original_content
+    } else {
+        replace_found_secrets(&config, &target_path, original_content)?
+    };
+
+    let template_path = store_template(&mut config, &target_path, &content)?;
+
+    println!("Added template for: {}", target_path.display());
+    println!("Template stored at: {}", template_path.display());
+    println!("\nAdd {{VAR_NAME}} placeholders to the template file.");
+    println!("Use `op-loader template list` to see configured variables.");
+
+    Ok(())
+}
+
+/// Opens the template for `path` in the user's editor and, once it's
+/// closed, warns about placeholders that don't match a configured var.
+fn template_edit(path: &str) -> Result<()> {
+    let target_path = crate::template_link::target_path(path)?;
+    let target_key = target_path.to_string_lossy().to_string();
+
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let template = config.templated_files.get(&target_key).with_context(|| {
+        format!(
+            "File is not managed as a template: {}",
+            target_path.display()
+        )
+    })?;
+    let templates_dir = get_templates_dir()?;
+    let template_path = templates_dir.join(&template.template_name);
+    if !template_path.exists() {
+        anyhow::bail!("Template file not found: {}", template_path.display());
+    }
+
+    let editor = editor_command();
+    let (program, args) = editor
+        .split_first()
+        .context("No editor configured; set $EDITOR")?;
+    let status = std::process::Command::new(program)
+        .args(args)
+        .arg(&template_path)
+        .status()
+        .with_context(|| format!("Failed to start editor `{program}`"))?;
+    if !status.success() {
+        anyhow::bail!("Editor `{program}` exited with {status}");
+    }
+
+    let content = std::fs::read_to_string(&template_path)
+        .with_context(|| format!("Failed to read template: {}", template_path.display()))?;
+    let content = match crate::template_engine::expand_includes(&content, &templates_dir) {
+        Ok(expanded) => expanded,
+        Err(err) => {
+            eprintln!("Warning: {err:#}");
+            content
+        }
+    };
+    let warnings = placeholder_warnings(&config, template, &content);
+    if warnings.is_empty() {
+        println!("Saved {}", template_path.display());
+    } else {
+        for warning in &warnings {
+            eprintln!("Warning: {warning}");
+        }
+    }
+    Ok(())
+}
+
+/// `$VISUAL`, then `$EDITOR`, then `vi`, split into the program and any
+/// arguments it was given (e.g. `code --wait`).
+fn editor_command() -> Vec<String> {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string())
+        .split_whitespace()
+        .map(String::from)
+        .collect()
+}
+
+/// A warning for each placeholder in `content` that isn't a configured var,
+/// with the closest var name when it looks like a typo, or that the
+/// template's `vars` list leaves out.
+fn placeholder_warnings(
+    config: &OpLoadConfig,
+    template: &TemplatedFile,
+    content: &str,
+) -> Vec<String> {
+    crate::template_engine::placeholder_names(content)
+        .into_iter()
+        .filter_map(|name| {
+            if config.inject_vars.contains_key(&name) {
+                return (!template.allows(&name))
+                    .then(|| format!("{{{{{name}}}}} isn't in this template's `vars` list"));
+            }
+            let known = config.inject_vars.keys().map(String::as_str);
+            Some(match crate::template_engine::closest(&name, known) {
+                Some(suggestion) => format!(
+                    "{{{{{name}}}}} isn't a configured variable. Did you mean {{{{{suggestion}}}}}?"
+                ),
+                None => format!("{{{{{name}}}}} isn't a configured variable"),
+            })
+        })
+        .collect()
+}
+
+/// Looks for plaintext secrets in a file being added as a template: values
+/// of configured vars and values that look like tokens. On a terminal, asks
+/// which to replace with `{{VAR}}` placeholders; otherwise only warns.
+fn replace_found_secrets(
+    config: &OpLoadConfig,
+    target_path: &Path,
+    content: String,
+) -> Result<String> {
+    let mut resolved_vars = std::collections::HashMap::new();
+    if !config.inject_vars.is_empty() {
+        match resolve_vars_per_account(&SubprocessOpClient, config) {
+            Ok(results) => {
+                for (account_id, result) in results {
+                    match result {
+                        Ok(resolved) => resolved_vars.extend(resolved),
+                        Err(err) => eprintln!(
+                            "Warning: Failed to resolve secrets for account {account_id}: {err}"
+                        ),
+                    }
+                }
+            }
+            Err(err) => eprintln!("Warning: Failed to resolve secrets: {err:#}"),
+        }
+    }
+    let (_, matched) = templatize_content(&content, &resolved_vars);
+    let suspects: Vec<crate::secret_scan::Suspect> = crate::secret_scan::find_suspects(&content)
+        .into_iter()
+        .filter(|suspect| {
+            !resolved_vars.values().any(|value| {
+                value.len() >= crate::secret_scan::MIN_SECRET_LEN
+                    && suspect.value.contains(value.as_str())
+            })
+        })
+        .collect();
+    if matched.is_empty() && suspects.is_empty() {
+        return Ok(content);
+    }
+
+    if !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        for name in &matched {
+            eprintln!(
+                "Warning: {} contains the value of {name}",
+                target_path.display()
+            );
+        }
+        for suspect in &suspects {
+            eprintln!(
+                "Warning: {}:{} looks like a {}: {}",
+                target_path.display(),
+                suspect.line,
+                suspect.label,
+                crate::secret_scan::preview(&suspect.value)
+            );
+        }
+        eprintln!(
+            "Replace them with placeholders using `op-loader template edit {}`.",
+            target_path.display()
+        );
+        return Ok(content);
+    }
+
+    let mut replacements = std::collections::HashMap::new();
+    for name in matched {
+        let answer = prompt(&format!(
+            "Replace the value of {name} with {{{{{name}}}}}? [Y/n] "
+        ))?;
+        if !answer.eq_ignore_ascii_case("n") {
+            replacements.insert(name.clone(), resolved_vars[&name].clone());
+        }
+    }
+    for suspect in suspects {
+        let answer = prompt(&format!(
+            "Line {} has what looks like a {} ({}). Placeholder name [{}], or - to keep it: ",
+            suspect.line,
+            suspect.label,
+            crate::secret_scan::preview(&suspect.value),
+            suspect.suggested_var
+        ))?;
+        let name = match answer.as_str() {
+            "-" => continue,
+            "" => suspect.suggested_var,
+            name => name.to_string(),
+        };
+        if !config.inject_vars.contains_key(&name) {
+            println!(
+                "Map {name} to a 1Password reference before rendering, or it stays as {{{{{name}}}}}."
+            );
+        }
+        replacements.insert(name, suspect.value);
+    }
+
+    Ok(templatize_content(&content, &replacements).0)
+}
+
+/// Asks `question` on the terminal and returns the trimmed answer.
+fn prompt(question: &str) -> Result<String> {
+    use std::io::Write;
+    print!("{question}");
+    std::io::stdout()
+        .flush()
+        .context("Failed to write prompt")?;
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .context("Failed to read answer")?;
+    Ok(answer.trim().to_string())
+}
+
+/// Narrows `config` to the templates for `paths`, or leaves every template
+/// if none are given.
+fn retain_templates(config: &mut OpLoadConfig, paths: &[String]) -> Result<()> {
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let mut selected = Vec::with_capacity(paths.len());
+    for path in paths {
+        let target_key = crate::template_link::target_path(path)?
+            .to_string_lossy()
+            .to_string();
+        if !config.templated_files.contains_key(&target_key) {
+            anyhow::bail!("Not a managed template: {target_key}");
+        }
+        selected.push(target_key);
+    }
+    config
+        .templated_files
+        .retain(|target, _| selected.contains(target));
+    Ok(())
+}
+
+/// Drops the vars that none of `config`'s templates refer to and may use.
+fn retain_template_vars(config: &mut OpLoadConfig, templates_dir: &Path) {
+    let templates: Vec<(TemplatedFile, String)> = config
+        .templated_files
+        .values()
+        .filter_map(|template| {
+            crate::template_engine::read_for_references(
+                &templates_dir.join(&template.template_name),
+                templates_dir,
+            )
+            .map(|content| (template.clone(), content))
+        })
+        .collect();
+    config.inject_vars.retain(|name, _| {
+        templates.iter().any(|(template, content)| {
+            template.allows(name) && crate::template_engine::references(content, name)
+        })
+    });
+}
+
+/// Resolves the vars that `config`'s templates refer to, and no others.
+/// Accounts that fail are reported and left out.
+fn resolve_template_vars(
+    config: &mut OpLoadConfig,
+) -> Result<(ResolvedVarsByAccount, Vec<String>)> {
+    retain_template_vars(config, &get_templates_dir()?);
+
+    let mut resolved_vars_by_account = std::collections::HashMap::new();
+    let mut failed_accounts = Vec::new();
+    for (account_id, result) in resolve_vars_per_account(&SubprocessOpClient, config)? {
+        match result {
+            Ok(resolved) => {
+                resolved_vars_by_account.insert(account_id, resolved);
+            }
+            Err(err) => {
+                eprintln!("Failed to resolve secrets for account {account_id}: {err}");
+                failed_accounts.push(account_id);
+            }
+        }
+    }
+    Ok((resolved_vars_by_account, failed_accounts))
+}
+
+/// Renders the templates for `paths`, or every template if none are given,
+/// resolving only the vars those templates refer to.
+fn template_render(paths: &[String], force: bool) -> Result<()> {
+    let mut config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    retain_templates(&mut config, paths)?;
+    let (resolved_vars_by_account, _) = resolve_template_vars(&mut config)?;
+
+    let rendered = render_templates(
+        &SubprocessOpClient,
+        &config,
+        &resolved_vars_by_account,
+        ManualEdits::for_render(force),
+    )?;
+    println!(
+        "Rendered {rendered}/{} template(s)",
+        config.templated_files.len()
+    );
+    Ok(())
+}
+
+/// Prints what one template renders to, without writing its file.
+fn template_render_stdout(paths: &[String], mask: bool) -> Result<()> {
+    if paths.len() != 1 {
+        anyhow::bail!("--stdout prints one template; give the path of the file to render");
+    }
+    let mut config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    retain_templates(&mut config, paths)?;
+    let (resolved_vars_by_account, failed_accounts) = resolve_template_vars(&mut config)?;
+    if !failed_accounts.is_empty() {
+        anyhow::bail!(
+            "Can't render without the secrets of {}",
+            failed_accounts.join(", ")
+        );
+    }
+    let resolved_vars = flatten_resolved_vars(&resolved_vars_by_account);
+
+    let (target_path, template_config) = config
+        .templated_files
+        .iter()
+        .next()
+        .context("No template selected")?;
+    let rendered = render_target(
+        &SubprocessOpClient,
+        &config,
+        &get_templates_dir()?,
+        target_path,
+        template_config,
+        &resolved_vars,
+    )?
+    .with_context(|| format!("Couldn't render {target_path}"))?;
+
+    if mask {
+        print!("{}", mask_values(&rendered, resolved_vars.values()));
+    } else {
+        print!("{rendered}");
+    }
+    Ok(())
+}
+
+/// `text` with every occurrence of the non-empty `values` replaced by
+/// `********`, longest first so one value inside another isn't left half
+/// shown.
+fn mask_values<'a>(text: &str, values: impl IntoIterator<Item = &'a String>) -> String {
+    let mut values: Vec<&String> = values
+        .into_iter()
+        .filter(|value| !value.is_empty())
+        .collect();
+    values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+    values.into_iter().fold(text.to_string(), |masked, value| {
+        masked.replace(value.as_str(), "********")
+    })
+}
+
+fn template_restore(path: &str, list: bool, backup: usize) -> Result<()> {
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let target = crate::template_link::target_path(path)?;
+    let target_key = target.to_string_lossy().to_string();
+    let template = config
+        .templated_files
+        .get(&target_key)
+        .with_context(|| format!("Not a managed template: {target_key}"))?;
+    let dir = crate::template_backup::backups_dir(&template.template_name)?;
+    let backups = crate::template_backup::list_backups(&dir)?;
+
+    if list {
+        if backups.is_empty() {
+            println!("No backups of {target_key}");
+        }
+        for (n, backup) in backups.iter().enumerate() {
+            println!("{:>3}  {}", n + 1, backup.display());
+        }
+        return Ok(());
+    }
+
+    let chosen = backup
+        .checked_sub(1)
+        .and_then(|idx| backups.get(idx))
+        .with_context(|| {
+            format!(
+                "No backup #{backup} of {target_key} ({} kept)",
+                backups.len()
+            )
+        })?;
+    let contents = std::fs::read(chosen)
+        .with_context(|| format!("Failed to read backup {}", chosen.display()))?;
+    crate::sync_guard::ensure_allowed(&target, &config.allowed_sync_paths)?;
+    // The file being replaced gets a backup of its own, so a restore can be
+    // undone with another one.
+    crate::template_backup::back_up(
+        &target,
+        &dir,
+        config.templates.backups().max(backups.len() + 1),
+        std::time::SystemTime::now(),
+    )?;
+    crate::sync_guard::write(&target, &contents, &config.allowed_sync_paths)?;
+
+    println!("Restored {target_key} from {}", chosen.display());
+    Ok(())
+}
+
+/// Renders the templates for `paths` (or all of them), then re-renders the
+/// ones whose template changes, and all of them when the config changes.
+/// Secrets are resolved again only when the config changes or a template
+/// starts using a var that wasn't resolved yet.
+fn template_watch(paths: &[String]) -> Result<()> {
+    let templates_dir = get_templates_dir()?;
+    std::fs::create_dir_all(&templates_dir).with_context(|| {
+        format!(
+            "Failed to create templates directory: {}",
+            templates_dir.display()
+        )
+    })?;
+    let config_file = confy::get_configuration_file_path("op_loader", None)
+        .context("Failed to get config file path")?;
+    let watcher = crate::template_watch::SourceWatcher::new(&templates_dir, &config_file)?;
+
+    let load = || -> Result<OpLoadConfig> {
+        let mut config: OpLoadConfig =
+            confy::load("op_loader", None).context("Failed to load configuration")?;
+        retain_templates(&mut config, paths)?;
+        Ok(config)
+    };
+    let mut config = load()?;
+    let mut resolved = resolve_template_vars(&mut config.clone())?.0;
+    let count = render_templates(&SubprocessOpClient, &config, &resolved, ManualEdits::Skip)?;
+    println!("Rendered {count} template(s).");
+    println!(
+        "Watching {} and {} for changes. Press Ctrl+C to stop.",
+        templates_dir.display(),
+        config_file.display()
+    );
+
+    loop {
+        let changed = watcher.next_changes()?;
+        let (config_changed, template_names) =
+            crate::template_watch::classify(&changed, &templates_dir, &config_file);
+
+        let targets = if config_changed {
+            match load() {
+                Ok(reloaded) => config = reloaded,
+                Err(err) => {
+                    eprintln!("Warning: Keeping the previous configuration: {err:#}");
+                    continue;
+                }
+            }
+            let mut targets: Vec<String> = config.templated_files.keys().cloned().collect();
+            targets.sort();
+            targets
+        } else {
+            crate::template_watch::affected_targets(&config, &templates_dir, &template_names)
+        };
+        if targets.is_empty() {
+            continue;
+        }
+
+        let mut changed_config = config.clone();
+        changed_config
+            .templated_files
+            .retain(|target, _| targets.contains(target));
+        let mut needed = changed_config.clone();
+        retain_template_vars(&mut needed, &templates_dir);
+        let flat = flatten_resolved_vars(&resolved);
+        if config_changed
+            || needed
+                .inject_vars
+                .keys()
+                .any(|name| !flat.contains_key(name))
+        {
+            match resolve_template_vars(&mut config.clone()) {
+                Ok((reresolved, _)) => resolved = reresolved,
+                Err(err) => {
+                    eprintln!("Error: {err:#}");
+                    continue;
+                }
+            }
+        }
+
+        match render_templates(
+            &SubprocessOpClient,
+            &changed_config,
+            &resolved,
+            ManualEdits::Skip,
+        ) {
+            Ok(count) => println!(
+                "Rendered {count} of {}: {}",
+                targets.len(),
+                targets.join(", ")
+            ),
+            Err(err) => eprintln!("Error: {err:#}"),
+        }
+    }
+}
+
+/// How a rendered file compares with what its template renders to now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TemplateDrift {
+    UpToDate,
+    Drifted,
+    Missing,
+}
+
+fn template_drift(target: &Path, rendered: &str) -> TemplateDrift {
+    match std::fs::read(target) {
+        Ok(contents) if contents == rendered.as_bytes() => TemplateDrift::UpToDate,
+        Ok(_) => TemplateDrift::Drifted,
+        Err(_) => TemplateDrift::Missing,
+    }
+}
+
+/// Renders templates in memory and fails, listing them, if any target file
+/// differs from what its template renders to.
+fn template_check(paths: &[String]) -> Result<()> {
+    let mut config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    retain_templates(&mut config, paths)?;
+    let (resolved_vars_by_account, failed_accounts) = resolve_template_vars(&mut config)?;
+    if !failed_accounts.is_empty() {
+        anyhow::bail!(
+            "Can't check templates without the secrets of {}",
+            failed_accounts.join(", ")
+        );
+    }
+    let resolved_vars = flatten_resolved_vars(&resolved_vars_by_account);
+    let templates_dir = get_templates_dir()?;
+
+    let mut targets: Vec<(&String, &TemplatedFile)> = config.templated_files.iter().collect();
+    targets.sort_by(|a, b| a.0.cmp(b.0));
+    let mut out_of_date = 0;
+    let mut unchecked = 0;
+    for (target_path, template_config) in targets {
+        let Some(rendered) = render_target(
+            &SubprocessOpClient,
+            &config,
+            &templates_dir,
+            target_path,
+            template_config,
+            &resolved_vars,
+        )?
+        else {
+            unchecked += 1;
+            continue;
+        };
+        match template_drift(Path::new(target_path), &rendered) {
+            TemplateDrift::UpToDate => {}
+            TemplateDrift::Drifted => {
+                println!("drifted: {target_path}");
+                out_of_date += 1;
+            }
+            TemplateDrift::Missing => {
+                println!("missing: {target_path}");
+                out_of_date += 1;
+            }
+        }
+    }
+
+    if out_of_date > 0 {
+        anyhow::bail!(
+            "{out_of_date} of {} rendered file(s) out of date; run `op-loader template render`",
+            config.templated_files.len()
+        );
+    }
+    if unchecked > 0 {
+        anyhow::bail!("{unchecked} template(s) couldn't be rendered to check");
+    }
+    println!(
+        "All {} rendered file(s) are up to date",
+        config.templated_files.len()
+    );
+    Ok(())
+}
+
+fn template_adopt(path: &str, cache_ttl: Option<&str>, cache_lock_wait: &str) -> Result<()> {
+    info!("Adopting template for: {path}");
+
+    let target_path = crate::template_link::target_path(path)?;
+    let target_key = target_path.to_string_lossy().to_string();
+
+    if !target_path.exists() {
+        anyhow::bail!("File does not exist: {}", target_path.display());
+    }
+
+    let mut config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+
+    if config.templated_files.contains_key(&target_key) {
+        anyhow::bail!(
+            "File is already managed as a template: {}",
+            target_path.display()
+        );
+    }
+
+    if config.inject_vars.is_empty() {
+        anyhow::bail!("No environment variables configured. Use the TUI to add mappings.");
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    if cache_ttl.is_some() {
+        anyhow::bail!("Cache is only supported on macOS and Windows.");
+    }
+
+    let cache_ttl = cache_ttl.map(parse_duration).transpose()?.unwrap_or(None);
+    let cache_lock_wait =
+        parse_duration(cache_lock_wait)?.unwrap_or_else(|| Duration::from_secs(5));
+
+    let vars_by_account = group_vars_by_account(&config.inject_vars);
+    let mut resolved_vars = std::collections::HashMap::new();
+    for (account_id, result) in resolve_vars_by_account(
+        &SubprocessOpClient,
+        vars_by_account,
+        cache_ttl,
+        CacheRead::Use,
+        cache_lock_wait,
+    )? {
+        match result {
+            Ok(resolved) => resolved_vars.extend(resolved),
+            Err(err) => {
+                eprintln!("Warning: Failed to resolve secrets for account {account_id}: {err}");
+            }
+        }
+    }
+
+    let original_content =
+        std::fs::read_to_string(&target_path).context("Failed to read source file")?;
+
+    let (templated_content, replaced) = templatize_content(&original_content, &resolved_vars);
+    if replaced.is_empty() {
+        anyhow::bail!(
+            "No configured secret values found in {}. Use `op-loader template add` instead.",
+            target_path.display()
+        );
+    }
+
+    let template_path = store_template(&mut config, &target_path, &templated_content)?;
+
+    println!("Adopted template for: {}", target_path.display());
+    println!("Template stored at: {}", template_path.display());
+    println!("\nReplaced values with placeholders:");
+    for var_name in &replaced {
+        println!("  {{{{{var_name}}}}}");
+    }
+    let skipped = short_values(&resolved_vars);
+    if !skipped.is_empty() {
+        println!(
+            "\nLeft values shorter than {} characters as they are; add placeholders for these by hand if needed:",
+            crate::secret_scan::MIN_SECRET_LEN
+        );
+        for var_name in skipped {
+            println!("  {var_name}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Replace every occurrence of a resolved secret value with its `{{VAR}}`
+/// placeholder. Matches are found in the original content and replaced in
+/// one pass, longer values first, so a secret that contains another isn't
+/// split apart and placeholders already put in are never matched again.
+/// Values shorter than `MIN_SECRET_LEN` (see [`short_values`]) are left
+/// alone. Returns the names of replaced vars.
+fn templatize_content(
+    content: &str,
+    resolved_vars: &std::collections::HashMap<String, String>,
+) -> (String, Vec<String>) {
+    let mut candidates: Vec<(&String, &String)> = resolved_vars
+        .iter()
+        .filter(|(_, value)| value.len() >= crate::secret_scan::MIN_SECRET_LEN)
+        .collect();
+    candidates.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(b.0)));
+
+    // (start, end, var_name) of each match kept, never overlapping.
+    let mut spans: Vec<(usize, usize, &String)> = Vec::new();
+    for (var_name, value) in candidates {
+        for (start, _) in content.match_indices(value.as_str()) {
+            let end = start + value.len();
+            if spans.iter().all(|(s, e, _)| end <= *s || start >= *e) {
+                spans.push((start, end, var_name));
+            }
+        }
+    }
+    spans.sort_unstable_by_key(|(start, _, _)| *start);
+
+    let mut templated = String::with_capacity(content.len());
+    let mut replaced = Vec::new();
+    let mut copied_to = 0;
+    for (start, end, var_name) in spans {
+        templated.push_str(&content[copied_to..start]);
+        templated.push_str(&format!("{{{{{var_name}}}}}"));
+        copied_to = end;
+        if !replaced.contains(var_name) {
+            replaced.push(var_name.clone());
+        }
+    }
+    templated.push_str(&content[copied_to..]);
+    replaced.sort();
+
+    (templated, replaced)
+}
+
+/// Names of vars whose values `templatize_content` skips as too short to
+/// tell apart from ordinary text, in name order.
+fn short_values(resolved_vars: &std::collections::HashMap<String, String>) -> Vec<&String> {
+    let mut names: Vec<&String> = resolved_vars
+        .iter()
+        .filter(|(_, value)| !value.is_empty() && value.len() < crate::secret_scan::MIN_SECRET_LEN)
+        .map(|(name, _)| name)
+        .collect();
+    names.sort();
+    names
+}
+
+/// The `# op-loader:` line put at the top of a template, listing the vars
+/// it can use.
+fn available_vars_comment<'a>(names: impl IntoIterator<Item = &'a String>) -> String {
+    let mut var_names: Vec<String> = names.into_iter().map(|k| format!("{{{{{k}}}}}")).collect();
+    var_names.sort();
+
+    if var_names.is_empty() {
+        "# op-loader: No variables configured yet. Use the TUI to add variables.\n".to_string()
+    } else {
+        format!(
+            "# op-loader: Available variables: {}\n",
+            var_names.join(", ")
+        )
+    }
+}
+
+/// Write `content` (prefixed with the available-variables hint) into the
+/// templates directory and register `target_path` in the config.
+fn store_template(config: &mut OpLoadConfig, target_path: &Path, content: &str) -> Result<PathBuf> {
+    let templates_dir = get_templates_dir()?;
+    std::fs::create_dir_all(&templates_dir).with_context(|| {
+        format!(
+            "Failed to create templates directory: {}",
+            templates_dir.display()
+        )
+    })?;
+
+    if let Err(err) = crate::sync_guard::ensure_allowed(target_path, &config.allowed_sync_paths) {
+        eprintln!("Warning: {err:#} Until then, rendering it is skipped.");
+    }
+
+    let template_name = config.templates.naming.template_name(target_path);
+    let target_key = target_path.to_string_lossy().to_string();
+    if let Some((other, _)) = config
+        .templated_files
+        .iter()
+        .find(|(other, t)| **other != target_key && t.template_name == template_name)
+    {
+        anyhow::bail!(
+            "{other} already uses the template {template_name}. Run `op-loader template migrate-names` first."
+        );
+    }
+    let template_path = templates_dir.join(&template_name);
+    if let Some(parent) = template_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    let vars_comment = available_vars_comment(config.inject_vars.keys());
+    let template_content = format!("{vars_comment}{content}");
+    std::fs::write(&template_path, &template_content)
+        .with_context(|| format!("Failed to write template to {}", template_path.display()))?;
+
+    config.templated_files.insert(
+        target_key,
+        TemplatedFile {
+            template_name,
+            vars: None,
+            symlink: false,
+        },
+    );
+    confy::store("op_loader", None, &*config).context("Failed to save configuration")?;
+
+    Ok(template_path)
+}
+
+/// Managed templates sorted by target, with whether the template file exists.
+fn template_rows(config: &OpLoadConfig, templates_dir: &Path) -> Vec<(String, PathBuf, bool)> {
+    let mut rows: Vec<(String, PathBuf, bool)> = config
+        .templated_files
+        .iter()
+        .map(|(target_path, template_config)| {
+            let template_path = templates_dir.join(&template_config.template_name);
+            let exists = template_path.exists();
+            (target_path.clone(), template_path, exists)
+        })
+        .collect();
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+    rows
+}
+
+fn template_list_porcelain(config: &OpLoadConfig, templates_dir: &Path) -> String {
+    porcelain::records(template_rows(config, templates_dir).into_iter().map(
+        |(target, template_path, exists)| {
+            vec![
+                target,
+                template_path.to_string_lossy().to_string(),
+                if exists { "ok" } else { "missing" }.to_string(),
+            ]
+        },
+    ))
+}
+
+fn template_list(porcelain: bool) -> Result<()> {
+    info!("Listing templates");
+
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let templates_dir = get_templates_dir()?;
+
+    if porcelain {
+        print!("{}", template_list_porcelain(&config, &templates_dir));
+        return Ok(());
+    }
+
+    if config.templated_files.is_empty() {
+        println!("No template files configured.");
+        println!("\nAdd a template with: op-loader template add <path>");
+        return Ok(());
+    }
+
+    println!("Managed template files:\n");
+    for (target_path, template_path, exists) in template_rows(&config, &templates_dir) {
+        let status = if exists { "✓" } else { "✗ (missing)" };
+        println!("  {status} {target_path}");
+        println!("    └─ {}", template_path.display());
+        if let Some(vars) = config
+            .templated_files
+            .get(&target_path)
+            .and_then(|template| template.vars.as_ref())
+        {
+            println!("       vars: {}", vars.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets which vars the template for `path` may use; `None` allows all of
+/// them. The template's `# op-loader:` hint is updated to match.
+fn template_scope(path: &str, vars: Option<Vec<String>>) -> Result<()> {
+    let target_path = crate::template_link::target_path(path)?;
+    let target_key = target_path.to_string_lossy().to_string();
+
+    let mut config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let template_path = get_templates_dir()?
+        .join(&set_template_scope(&mut config, &target_key, vars)?.template_name);
+    confy::store("op_loader", None, &config).context("Failed to save configuration")?;
+
+    let template = &config.templated_files[&target_key];
+    if template_path.exists() {
+        let content = std::fs::read_to_string(&template_path)
+            .with_context(|| format!("Failed to read template: {}", template_path.display()))?;
+        let allowed = config
+            .inject_vars
+            .keys()
+            .filter(|name| template.allows(name));
+        std::fs::write(
+            &template_path,
+            replace_vars_comment(&content, &available_vars_comment(allowed)),
+        )
+        .with_context(|| format!("Failed to write template to {}", template_path.display()))?;
+    }
+
+    match &template.vars {
+        Some(vars) => println!("{} can use: {}", target_path.display(), vars.join(", ")),
+        None => println!("{} can use every variable", target_path.display()),
+    }
+    Ok(())
+}
+
+/// Updates the `vars` allowlist of the template for `target_key`, checking
+/// that each name is a configured var.
+fn set_template_scope<'a>(
+    config: &'a mut OpLoadConfig,
+    target_key: &str,
+    vars: Option<Vec<String>>,
+) -> Result<&'a TemplatedFile> {
+    if let Some(unknown) = vars
+        .iter()
+        .flatten()
+        .find(|name| !config.inject_vars.contains_key(*name))
+    {
+        anyhow::bail!("No variable named {unknown} is configured");
+    }
+
+    let template = config
+        .templated_files
+        .get_mut(target_key)
+        .with_context(|| format!("File is not managed as a template: {target_key}"))?;
+    template.vars = vars.map(|mut vars| {
+        vars.sort();
+        vars.dedup();
+        vars
+    });
+    Ok(template)
+}
+
+/// `content` with its first `# op-loader:` variables line replaced by
+/// `comment`, or `comment` added on top if it has none.
+fn replace_vars_comment(content: &str, comment: &str) -> String {
+    let is_vars_line = |line: &str| {
+        line.starts_with("# op-loader: Available variables:")
+            || line.starts_with("# op-loader: No variables configured")
+    };
+    let mut replaced = false;
+    let mut out = String::with_capacity(content.len() + comment.len());
+    for line in content.split_inclusive('\n') {
+        if !replaced && is_vars_line(line) {
+            out.push_str(comment);
+            replaced = true;
+        } else {
+            out.push_str(line);
+        }
+    }
+    if replaced {
+        out
+    } else {
+        format!("{comment}{content}")
+    }
+}
+
+/// Switches the template for `path` to linked mode: the file there now is
+/// saved so `template unlink` can put it back, and the template is rendered
+/// into the state directory with the file replaced by a link to it.
+fn template_link(path: &str) -> Result<()> {
+    let target_path = crate::template_link::target_path(path)?;
+    let target_key = target_path.to_string_lossy().to_string();
+
+    let mut config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let template = config
+        .templated_files
+        .get_mut(&target_key)
+        .with_context(|| {
+            format!(
+                "File is not managed as a template: {}",
+                target_path.display()
+            )
+        })?;
+    if template.symlink {
+        println!("{} is already linked", target_path.display());
+        return Ok(());
+    }
+    if !cfg!(unix) {
+        anyhow::bail!("Linked templates are only supported on Unix systems");
+    }
+    if target_path.is_symlink() {
+        anyhow::bail!(
+            "{} is already a symlink. Replace it with a regular file first.",
+            target_path.display()
+        );
+    }
+
+    if target_path.exists() {
+        crate::template_link::save_original(
+            &target_path,
+            &crate::template_link::original_path(&template.template_name)?,
+        )?;
+    }
+    template.symlink = true;
+    let rendered_path = crate::template_link::rendered_path(&template.template_name)?;
+    confy::store("op_loader", None, &config).context("Failed to save configuration")?;
+
+    template_render(&[path.to_string()], false)?;
+    if target_path.is_symlink() {
+        println!(
+            "{} now links to {}",
+            target_path.display(),
+            rendered_path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Turns a linked template back into one rendered in place, putting back
+/// the file that was there before it was linked.
+fn template_unlink(path: &str) -> Result<()> {
+    let target_path = crate::template_link::target_path(path)?;
+    let target_key = target_path.to_string_lossy().to_string();
+
+    let mut config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let template = config
+        .templated_files
+        .get_mut(&target_key)
+        .with_context(|| {
+            format!(
+                "File is not managed as a template: {}",
+                target_path.display()
+            )
+        })?;
+    if !template.symlink {
+        anyhow::bail!("{} isn't linked", target_path.display());
+    }
+
+    let restored = unlink_target(&target_path, &template.template_name)?;
+    template.symlink = false;
+    confy::store("op_loader", None, &config).context("Failed to save configuration")?;
+
+    if restored {
+        println!("Restored {}", target_path.display());
+    } else {
+        println!(
+            "Removed the link at {} (there was no file before it was linked)",
+            target_path.display()
+        );
+    }
+    Ok(())
+}
+
+fn unlink_target(target_path: &Path, template_name: &str) -> Result<bool> {
+    crate::template_link::unlink(
+        target_path,
+        &crate::template_link::rendered_dir()?,
+        &crate::template_link::rendered_path(template_name)?,
+        &crate::template_link::original_path(template_name)?,
+    )
+}
+
+fn template_remove(path: &str) -> Result<()> {
+    info!("Removing template for: {path}");
+
+    let target_path = crate::template_link::target_path(path)?;
+    let target_key = target_path.to_string_lossy().to_string();
+
+    let mut config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+
+    let template_config = config
+        .templated_files
+        .remove(&target_key)
+        .with_context(|| {
+            format!(
+                "File is not managed as a template: {}",
+                target_path.display()
+            )
+        })?;
+
+    if template_config.symlink
+        && let Err(err) = unlink_target(&target_path, &template_config.template_name)
+    {
+        eprintln!("Warning: {err:#}");
+    }
+
+    let templates_dir = get_templates_dir()?;
+    let template_path = templates_dir.join(&template_config.template_name);
+
+    if template_path.exists() {
+        std::fs::remove_file(&template_path)
+            .with_context(|| format!("Failed to delete template: {}", template_path.display()))?;
+        println!("Removed template: {}", template_path.display());
+    } else {
+        println!(
+            "Removed config for: {} (template file was already missing)",
+            target_path.display()
+        );
+    }
+
+    confy::store("op_loader", None, &config).context("Failed to save configuration")?;
+
+    Ok(())
+}
+
+fn template_migrate_names(dry_run: bool) -> Result<()> {
+    let mut config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let naming = config.templates.naming;
+    let renames = plan_renames(&config, naming);
+    if renames.is_empty() {
+        println!(
+            "All template names already follow the `{}` naming.",
+            naming.as_str()
+        );
+        return Ok(());
+    }
+
+    for rename in &renames {
+        println!("{}: {} -> {}", rename.target, rename.from, rename.to);
+        if !rename.shared_with.is_empty() {
+            println!(
+                "  Warning: {} was shared with {}; each gets a copy, so check it matches this file",
+                rename.from,
+                rename.shared_with.join(", ")
+            );
+        }
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    let templates_dir = get_templates_dir()?;
+    for rename in &renames {
+        copy_template(&templates_dir, rename)?;
+        if let Some(template) = config.templated_files.get_mut(&rename.target) {
+            if template.symlink {
+                crate::template_link::rename(&rename.from, &rename.to)?;
+            }
+            template.template_name.clone_from(&rename.to);
+        }
+    }
+    confy::store("op_loader", None, &config).context("Failed to save configuration")?;
+
+    // Old files are only removed once nothing points at them, since a shared
+    // name is copied once per target.
+    for rename in &renames {
+        if config
+            .templated_files
+            .values()
+            .any(|t| t.template_name == rename.from)
+        {
+            continue;
+        }
+        for path in [
+            templates_dir.join(&rename.from),
+            history_path(&rename.from)?,
+        ] {
+            if let Err(err) = std::fs::remove_file(&path)
+                && err.kind() != std::io::ErrorKind::NotFound
+            {
+                eprintln!("Warning: Failed to remove {}: {err}", path.display());
+            }
+        }
+        let backups = crate::template_backup::backups_dir(&rename.from)?;
+        if let Err(err) = std::fs::remove_dir_all(&backups)
+            && err.kind() != std::io::ErrorKind::NotFound
+        {
+            eprintln!("Warning: Failed to remove {}: {err}", backups.display());
+        }
+    }
+
+    println!("Renamed {} template(s).", renames.len());
+    Ok(())
+}
+
+/// Copies a template, its backups, and its render history to the new name. Encrypted
+/// history content is bound to the template name, so it's re-sealed; records
+/// that can't be re-sealed keep only their hash.
+fn copy_template(templates_dir: &Path, rename: &TemplateRename) -> Result<()> {
+    let from = templates_dir.join(&rename.from);
+    let to = templates_dir.join(&rename.to);
+    if to.exists() {
+        anyhow::bail!(
+            "Cannot rename {} to {}: the destination already exists",
+            from.display(),
+            to.display()
+        );
+    }
+    if from.exists() {
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        std::fs::copy(&from, &to)
+            .with_context(|| format!("Failed to copy {} to {}", from.display(), to.display()))?;
+    }
+
+    let backups_to = crate::template_backup::backups_dir(&rename.to)?;
+    for backup in
+        crate::template_backup::list_backups(&crate::template_backup::backups_dir(&rename.from)?)?
+    {
+        std::fs::create_dir_all(&backups_to)
+            .with_context(|| format!("Failed to create directory: {}", backups_to.display()))?;
+        if let Some(name) = backup.file_name() {
+            std::fs::copy(&backup, backups_to.join(name))
+                .with_context(|| format!("Failed to copy backup {}", backup.display()))?;
+        }
+    }
+
+    let mut history = load_history(&history_path(&rename.from)?)?;
+    if history.is_empty() {
+        return Ok(());
+    }
+    for record in &mut history {
+        record.content = record.content.take().and_then(|sealed| {
+            open_history_content(&rename.from, &sealed)
+                .and_then(|content| seal_history_content(&rename.to, &content))
+                .ok()
+        });
+    }
+    save_history(&history_path(&rename.to)?, &history)
+}
+
+fn record_render(config: &OpLoadConfig, template_name: &str, rendered: &str) -> Result<()> {
+    let content = history_content(config, template_name, rendered);
+    let path = history_path(template_name)?;
+    let mut history = load_history(&path)?;
+    if push_record(
+        &mut history,
+        RenderRecord::new(rendered, content),
+        config.template_history.keep(),
+    ) {
+        save_history(&path, &history)?;
+    }
+    Ok(())
+}
+
+/// What to keep of a render in its history record: nothing unless
+/// `store_content` is set.
+fn history_content(config: &OpLoadConfig, template_name: &str, rendered: &str) -> Option<String> {
+    if !config.template_history.store_content {
+        return None;
+    }
+    match seal_history_content(template_name, rendered) {
+        Ok(sealed) => Some(sealed),
+        Err(err) => {
+            eprintln!("# Warning: Storing hash only for {template_name}: {err}");
+            None
+        }
+    }
+}
+
+/// Why an earlier render can't be shown or put back, and how to change that.
+const STORE_CONTENT_HINT: &str = "Add `store_content = true` under `[template_history]` in your config (`op-loader config path` shows where); renders from then on keep their content.";
+
+#[cfg_attr(not(any(target_os = "macos", target_os = "windows")), allow(dead_code))]
+fn history_aad(template_name: &str) -> Vec<u8> {
+    format!("op-loader:template_history:{template_name}").into_bytes()
+}
+
+/// Marks content stored as-is where there's no keychain to seal it with.
+#[cfg_attr(any(target_os = "macos", target_os = "windows"), allow(dead_code))]
+const PLAIN_HISTORY_PREFIX: &str = "plain:";
+
+/// Without a keychain, content is kept unencrypted; the history file is
+/// readable only by the current user, like the file it was rendered to.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn seal_history_content(_template_name: &str, content: &str) -> Result<String> {
+    Ok(format!("{PLAIN_HISTORY_PREFIX}{content}"))
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn seal_history_content(template_name: &str, content: &str) -> Result<String> {
+    assert_keychain_available()?;
+    let key = get_or_create_key()?;
+    seal_cache_payload(&key, &history_aad(template_name), content.as_bytes())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn open_history_content(_template_name: &str, sealed: &str) -> Result<String> {
+    sealed
+        .strip_prefix(PLAIN_HISTORY_PREFIX)
+        .map(String::from)
+        .context("Render history content was encrypted on macOS or Windows and can't be read here")
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn open_history_content(template_name: &str, sealed: &str) -> Result<String> {
+    assert_keychain_available()?;
+    let key = get_or_create_key()?;
+    let content = open_cache_payload(&key, &history_aad(template_name), sealed)?;
+    String::from_utf8(content).context("Render history content is not valid UTF-8")
+}
+
+fn managed_template_name(path: &str) -> Result<String> {
+    let target_path = crate::template_link::target_path(path)?;
+    let target_key = target_path.to_string_lossy().to_string();
+
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    config
+        .templated_files
+        .get(&target_key)
+        .map(|template| template.template_name.clone())
+        .with_context(|| {
+            format!(
+                "File is not managed as a template: {}",
+                target_path.display()
+            )
+        })
+}
+
+fn template_history(path: &str) -> Result<()> {
+    let template_name = managed_template_name(path)?;
+    let history = load_history(&history_path(&template_name)?)?;
+
+    if history.is_empty() {
+        println!("No renders recorded for {path} yet.");
+        return Ok(());
+    }
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    for (idx, record) in history.iter().enumerate() {
+        let age = Duration::from_secs(now.saturating_sub(record.rendered_at));
+        let label = if idx == 0 { "latest" } else { "" };
+        println!(
+            "{idx:>3}  {} ago  sha256:{}  {}{label}",
+            format_age(age),
+            &record.hash[..12],
+            if record.content.is_some() {
+                "[content] "
+            } else {
+                ""
+            },
+        );
+    }
+
+    Ok(())
+}
+
+fn template_diff(path: &str, against: &str) -> Result<()> {
+    let back = if against == "previous" {
+        1
+    } else {
+        against
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .with_context(|| {
+                format!("Invalid --against '{against}'. Use `previous` or a positive number.")
+            })?
+    };
+
+    let template_name = managed_template_name(path)?;
+    let history = load_history(&history_path(&template_name)?)?;
+    let Some((earlier_sealed, latest_sealed)) =
+        diff_records(&history, back).with_context(|| format!("Can't diff renders of {path}"))?
+    else {
+        println!("No changes.");
+        return Ok(());
+    };
+
+    let latest_content = open_history_content(&template_name, latest_sealed)?;
+    let earlier_content = open_history_content(&template_name, earlier_sealed)?;
+    for line in line_diff(&earlier_content, &latest_content) {
+        match line {
+            DiffLine::Same(text) => println!("  {text}"),
+            DiffLine::Removed(text) => println!("- {text}"),
+            DiffLine::Added(text) => println!("+ {text}"),
+        }
+    }
+
+    Ok(())
+}
+
+/// The stored content of the render `back` renders before the latest and
+/// of the latest, or `None` when they're the same.
+fn diff_records(history: &[RenderRecord], back: usize) -> Result<Option<(&str, &str)>> {
+    let (Some(latest), Some(earlier)) = (history.first(), history.get(back)) else {
+        anyhow::bail!("only {} render(s) recorded", history.len());
+    };
+    if latest.hash == earlier.hash {
+        return Ok(None);
+    }
+    let (Some(latest_sealed), Some(earlier_sealed)) = (&latest.content, &earlier.content) else {
+        anyhow::bail!(
+            "the renders differ (sha256:{} -> sha256:{}), but their content was not stored. {STORE_CONTENT_HINT}",
+            &earlier.hash[..12],
+            &latest.hash[..12]
+        );
+    };
+    Ok(Some((earlier_sealed, latest_sealed)))
+}
+
+/// The render `to` renders back from the latest, if its content was kept.
+fn rollback_record(history: &[RenderRecord], to: usize) -> Result<(&RenderRecord, &str)> {
+    if to == 0 {
+        anyhow::bail!("--to counts back from the latest render, starting at 1");
+    }
+    let record = history.get(to).with_context(|| {
+        format!(
+            "No render {to} renders back: {} render(s) recorded",
+            history.len()
+        )
+    })?;
+    let sealed = record.content.as_deref().with_context(|| {
+        format!(
+            "The content of render {to} (sha256:{}) was not stored, so it can't be rolled back to. {STORE_CONTENT_HINT}",
+            &record.hash[..12]
+        )
+    })?;
+    Ok((record, sealed))
+}
+
+/// Backs up the file at `target` into `backups_dir` (if backups are on) and
+/// replaces it with `content`. Targets in cloud-synced folders are refused
+/// before anything is touched.
+fn write_rollback(
+    config: &OpLoadConfig,
+    target: &Path,
+    backups_dir: &Path,
+    content: &str,
+) -> Result<()> {
+    crate::sync_guard::ensure_allowed(target, &config.allowed_sync_paths)?;
+    let keep = config.templates.backups();
+    if keep > 0 {
+        crate::template_backup::back_up(target, backups_dir, keep, std::time::SystemTime::now())?;
+    }
+    crate::sync_guard::write(target, content.as_bytes(), &config.allowed_sync_paths)
+}
+
+/// Writes an earlier render back to the target, backing up the current file
+/// first. The rollback is recorded as a render of its own, so it can be
+/// rolled back too.
+fn template_rollback(path: &str, to: usize) -> Result<()> {
+    let config: OpLoadConfig =
+        confy::load("op_loader", None).context("Failed to load configuration")?;
+    let target = crate::template_link::target_path(path)?;
+    let target_key = target.to_string_lossy().to_string();
+    let template = config
+        .templated_files
+        .get(&target_key)
+        .with_context(|| format!("Not a managed template: {target_key}"))?;
+
+    let history = load_history(&history_path(&template.template_name)?)?;
+    let (record, sealed) = rollback_record(&history, to)?;
+    let content = open_history_content(&template.template_name, sealed)?;
+
+    write_rollback(
+        &config,
+        &target,
+        &crate::template_backup::backups_dir(&template.template_name)?,
+        &content,
+    )?;
+    if let Err(err) = record_render(&config, &template.template_name, &content) {
+        eprintln!("Warning: Failed to record the rollback in render history: {err}");
+    }
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    println!(
+        "Rolled {target_key} back to the render from {} ago (sha256:{})",
+        format_age(Duration::from_secs(now.saturating_sub(record.rendered_at))),
+        &record.hash[..12]
+    );
+    println!("The next render replaces it again unless the variables it used are changed back.");
+    Ok(())
+}
+
+/// What a render does with a target that was edited by hand since it was
+/// last rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ManualEdits {
+    /// Leave it alone, with a warning.
+    Skip,
+    /// Replace it (after a backup, if backups are on).
+    Overwrite,
+    /// Ask whether to overwrite, skip, or merge with conflict markers.
+    Ask,
+}
+
+impl ManualEdits {
+    /// `--force` overwrites; otherwise ask on a terminal and skip elsewhere.
+    fn for_render(force: bool) -> Self {
+        if force {
+            Self::Overwrite
+        } else if std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+            Self::Ask
+        } else {
+            Self::Skip
+        }
+    }
+}
+
+/// Renders every managed template, sending a desktop notification if a
+/// render fails since it often runs from shell startup where the error
+/// scrolls by unnoticed.
+fn render_templates(
+    op: &dyn OpClient,
+    config: &OpLoadConfig,
+    resolved_vars_by_account: &std::collections::HashMap<
+        String,
+        std::collections::HashMap<String, String>,
+    >,
+    manual_edits: ManualEdits,
+) -> Result<usize> {
+    render_all_templates(op, config, resolved_vars_by_account, manual_edits).inspect_err(|err| {
+        notify("op-loader template render failed", &format!("{err:#}"));
+    })
+}
+
+fn render_all_templates(
+    op: &dyn OpClient,
+    config: &OpLoadConfig,
+    resolved_vars_by_account: &std::collections::HashMap<
+        String,
+        std::collections::HashMap<String, String>,
+    >,
+    manual_edits: ManualEdits,
+) -> Result<usize> {
+    let templates_dir = get_templates_dir()?;
+    let mut rendered_count = 0;
+    let resolved_vars = flatten_resolved_vars(resolved_vars_by_account);
+
+    for (target_path, template_config) in &config.templated_files {
+        let Some(rendered) = render_target(
+            op,
+            config,
+            &templates_dir,
+            target_path,
+            template_config,
+            &resolved_vars,
+        )?
+        else {
+            continue;
+        };
+
+        let target = PathBuf::from(target_path);
+        if let Err(err) = crate::sync_guard::ensure_allowed(&target, &config.allowed_sync_paths) {
+            eprintln!("# Warning: Skipping {target_path}: {err:#}");
+            continue;
+        }
+
+        if template_config.symlink {
+            match render_linked(&target, &template_config.template_name, &rendered) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(err) => {
+                    eprintln!("# Warning: Skipping {target_path}: {err:#}");
+                    continue;
+                }
+            }
+            info!("Rendered linked template: {target_path}");
+            rendered_count += 1;
+            if let Err(err) = record_render(config, &template_config.template_name, &rendered) {
+                eprintln!("# Warning: Failed to record render history for {target_path}: {err}");
+            }
+            continue;
+        }
+
+        let mut contents = rendered.clone();
+        if crate::template_history::edited_since(
+            &target,
+            crate::template_history::latest_hash(&template_config.template_name).as_deref(),
+        ) && std::fs::read(&target).is_ok_and(|current| current != rendered.as_bytes())
+        {
+            match manual_edit_choice(&target, &rendered, manual_edits)? {
+                Some(chosen) => contents = chosen,
+                None => continue,
+            }
+        }
+
+        let keep = config.templates.backups();
+        if keep > 0 && std::fs::read(&target).is_ok_and(|current| current != rendered.as_bytes()) {
+            let backed_up = crate::template_backup::backups_dir(&template_config.template_name)
+                .and_then(|dir| {
+                    crate::template_backup::back_up(
+                        &target,
+                        &dir,
+                        keep,
+                        std::time::SystemTime::now(),
+                    )
+                });
+            if let Err(err) = backed_up {
+                eprintln!("# Warning: Skipping {target_path}: couldn't back it up first: {err:#}");
+                continue;
+            }
+        }
+
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+
+        crate::atomic_file::write(&target, contents.as_bytes())
+            .with_context(|| format!("Failed to write to {target_path}"))?;
+
+        info!("Rendered template: {target_path}");
+        rendered_count += 1;
+
+        if let Err(err) = record_render(config, &template_config.template_name, &rendered) {
+            eprintln!("# Warning: Failed to record render history for {target_path}: {err}");
+        }
+    }
+
+    Ok(rendered_count)
+}
+
+/// What to write to a target edited by hand since its last render: the new
+/// render, the render merged with the edits, or `None` to leave it.
+fn manual_edit_choice(
+    target: &Path,
+    rendered: &str,
+    manual_edits: ManualEdits,
+) -> Result<Option<String>> {
+    let skip = || {
+        eprintln!(
+            "# Warning: Skipping {}: it was edited since the last render. Move the edits into the template, or run `op-loader template render --force` to overwrite them.",
+            target.display()
+        );
+        Ok(None)
+    };
+    match manual_edits {
+        ManualEdits::Skip => skip(),
+        ManualEdits::Overwrite => Ok(Some(rendered.to_string())),
+        ManualEdits::Ask => {
+            let answer = prompt(&format!(
+                "{} was edited since the last render. [o]verwrite, [m]erge with conflict markers, or [s]kip? [s] ",
+                target.display()
+            ))?;
+            match answer.to_ascii_lowercase().as_str() {
+                "o" | "overwrite" => Ok(Some(rendered.to_string())),
+                "m" | "merge" => {
+                    let current = std::fs::read_to_string(target)
+                        .with_context(|| format!("Failed to read {}", target.display()))?;
+                    Ok(Some(crate::template_history::conflict_markers(
+                        &current, rendered,
+                    )))
+                }
+                _ => skip(),
+            }
+        }
+    }
+}
+
+/// Writes a linked template's render into the state directory and points
+/// the target at it. Returns `false`, with a warning, when the render was
+/// edited through the link or something else has taken the target's place,
+/// so neither is overwritten.
+fn render_linked(target: &Path, template_name: &str, rendered: &str) -> Result<bool> {
+    use crate::template_link::{LinkState, link_state};
+
+    let rendered_dir = crate::template_link::rendered_dir()?;
+    let rendered_path = crate::template_link::rendered_path(template_name)?;
+    let last_hash = crate::template_history::latest_hash(template_name);
+    if crate::template_history::edited_since(&rendered_path, last_hash.as_deref()) {
+        eprintln!(
+            "# Warning: Skipping {}: {} was edited since the last render. Move the change into the template, then delete the file.",
+            target.display(),
+            rendered_path.display()
+        );
+        return Ok(false);
+    }
+    let original = crate::template_link::original_path(template_name)?;
+    let state = link_state(target, &rendered_dir, &original);
+    if state == LinkState::Other {
+        eprintln!(
+            "# Warning: Skipping {}: it isn't a link to its render anymore. Run `op-loader template link` again once it's out of the way.",
+            target.display()
+        );
+        return Ok(false);
+    }
+
+    if let Some(parent) = rendered_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    crate::atomic_file::write(&rendered_path, rendered.as_bytes())?;
+    crate::template_link::place_link(target, &rendered_path)?;
+    Ok(true)
+}
+
+fn flatten_resolved_vars(
+    resolved_vars_by_account: &ResolvedVarsByAccount,
+) -> std::collections::HashMap<String, String> {
+    resolved_vars_by_account
+        .values()
+        .flat_map(|vars| vars.iter().map(|(k, v)| (k.clone(), v.clone())))
+        .collect()
+}
+
+/// What the template for `target_path` renders to, without writing it.
+/// `None` when the template is missing or its op:// references couldn't be
+/// resolved; both are reported as warnings.
+fn render_target(
+    op: &dyn OpClient,
+    config: &OpLoadConfig,
+    templates_dir: &Path,
+    target_path: &str,
+    template_config: &TemplatedFile,
+    resolved_vars: &std::collections::HashMap<String, String>,
+) -> Result<Option<String>> {
+    let template_path = templates_dir.join(&template_config.template_name);
+
+    if !template_path.exists() {
+        eprintln!(
+            "# Warning: Template file not found for {}: {}",
+            target_path,
+            template_path.display()
+        );
+        return Ok(None);
+    }
+
+    debug!(
+        "Rendering template: {} -> {}",
+        template_path.display(),
+        target_path
+    );
+
+    let template_content =
+        std::fs::read_to_string(&template_path).context("Failed to read template file")?;
+    let template_content =
+        crate::template_engine::expand_includes(&template_content, templates_dir)
+            .with_context(|| format!("Failed to expand includes in {}", template_path.display()))?;
+
+    let mut out_of_scope: Vec<&str> = config
+        .inject_vars
+        .keys()
+        .filter(|name| {
+            !template_config.allows(name)
+                && crate::template_engine::references(&template_content, name)
+        })
+        .map(String::as_str)
+        .collect();
+    if !out_of_scope.is_empty() {
+        out_of_scope.sort_unstable();
+        eprintln!(
+            "# Warning: {target_path} refers to {}, which its `vars` list leaves out",
+            out_of_scope.join(", ")
+        );
+    }
+    let scoped_vars: std::collections::HashMap<String, String> = resolved_vars
+        .iter()
+        .filter(|(name, _)| template_config.allows(name))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+
+    match render_template(op, config, &template_path, &template_content, &scoped_vars) {
+        Ok(rendered) => Ok(Some(rendered)),
+        Err(err) if !crate::template_engine::op_references(&template_content).is_empty() => {
+            eprintln!("# Warning: Skipping {target_path}: {err:#}");
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Renders one template's text. Its `{{ op://... }}` references are
+/// resolved first with the default account, so only the references, never
+/// the rendered secrets, are sent to `op`.
+fn render_template(
+    op: &dyn OpClient,
+    config: &OpLoadConfig,
+    template_path: &Path,
+    template_content: &str,
+    resolved_vars: &std::collections::HashMap<String, String>,
+) -> Result<String> {
+    let references = crate::template_engine::op_references(template_content);
+    let resolved_references = if references.is_empty() {
+        std::collections::HashMap::new()
+    } else {
+        let account_id = config.default_account_id.as_deref().with_context(|| {
+            format!(
+                "{} has op:// references; set a default account in the TUI to resolve them",
+                template_path.display()
+            )
+        })?;
+        let blocks: Vec<(&str, &str)> = references
+            .iter()
+            .map(|reference| (reference.as_str(), reference.as_str()))
+            .collect();
+        resolve_vars_json(op, account_id, &inject_blocks(&blocks))
+            .and_then(|json| parse_cached_vars(&json))
+            .with_context(|| {
+                format!(
+                    "Failed to resolve op:// references in {}",
+                    template_path.display()
+                )
+            })?
+    };
+
+    match crate::template_engine::render(template_content, resolved_vars, &resolved_references) {
+        Ok(rendered) => Ok(rendered),
+        Err(err) if !crate::template_engine::has_tags(template_content) => {
+            eprintln!(
+                "# Warning: {} isn't a valid template ({err:#}); only plain {{{{VAR}}}} placeholders were substituted. Wrap literal braces in {{% raw %}}...{{% endraw %}}.",
+                template_path.display()
+            );
+            Ok(crate::template_engine::substitute(
+                template_content,
+                resolved_vars,
+                &resolved_references,
+            ))
+        }
+        Err(err) => Err(err.context(format!("Failed to render {}", template_path.display()))),
+    }
+}
+
+fn group_vars_by_account<'a>(
+    inject_vars: &'a std::collections::HashMap<String, InjectVarConfig>,
+) -> std::collections::BTreeMap<&'a str, Vec<(&'a str, &'a InjectVarConfig)>> {
+    let mut vars_by_account: std::collections::BTreeMap<
+        &'a str,
+        Vec<(&'a str, &'a InjectVarConfig)>,
+    > = std::collections::BTreeMap::new();
+
+    for (var_name, var_config) in inject_vars {
+        vars_by_account
+            .entry(var_config.account_id.as_str())
+            .or_default()
+            .push((var_name.as_str(), var_config));
+    }
+
+    // Sort so each account's `op inject` input, and therefore its cache
+    // config hash, doesn't depend on HashMap iteration order.
+    for vars in vars_by_account.values_mut() {
+        vars.sort_by_key(|(var_name, _)| *var_name);
+    }
+
+    vars_by_account
+}
+
+#[cfg(all(test, any(target_os = "macos", target_os = "windows")))]
+mod cache_tests {
+    use super::*;
+    use crate::cache::cache_path_for_account;
+    use assert_fs::TempDir;
+
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    fn write_cached_output_at(
+        cache_root: &std::path::Path,
+        account_id: &str,
+        kind: CacheKind,
+        entry: &CacheEntry,
+    ) -> Result<()> {
+        use std::fs::OpenOptions;
+        use std::io::Write;
+
+        std::fs::create_dir_all(cache_root).with_context(|| {
+            format!("Failed to create cache directory: {}", cache_root.display())
+        })?;
+        let path = cache_path_for_account(cache_root, account_id, kind);
+        let plaintext = serde_json::to_vec(entry)?;
+        let encrypted = super::encrypt_cache(account_id, kind, &plaintext)?;
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&path)
+            .with_context(|| {
+                format!("Failed to open cache file for writing: {}", path.display())
+            })?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mut perms = file.metadata()?.permissions();
+            perms.set_mode(0o600);
+            std::fs::set_permissions(&path, perms).with_context(|| {
+                format!("Failed to set cache file permissions: {}", path.display())
+            })?;
+        }
+
+        file.write_all(encrypted.as_bytes())
+            .with_context(|| format!("Failed to write cache file: {}", path.display()))?;
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    fn read_cached_output_at(
+        cache_root: &std::path::Path,
+        account_id: &str,
+        kind: CacheKind,
+        ttl: Duration,
+        config_hash: &str,
+    ) -> Result<CacheReadOutcome> {
+        let path = cache_path_for_account(cache_root, account_id, kind);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(CacheReadOutcome::Miss);
+            }
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed to read cache file: {}", path.display()));
+            }
+        };
+
+        let decrypted = super::decrypt_cache(account_id, kind, &contents)?;
+        let entry: CacheEntry = serde_json::from_slice(&decrypted)?;
+        if entry.config_hash != config_hash {
+            Ok(CacheReadOutcome::Stale)
+        } else if entry.is_fresh(ttl, std::time::SystemTime::now()) {
+            Ok(CacheReadOutcome::Hit(entry.payload))
+        } else {
+            Ok(CacheReadOutcome::Expired)
+        }
+    }
+
+    const TEST_INPUT: &str = "FOO: op://vault/item/field\n";
+
+    fn test_entry(output: &str) -> CacheEntry {
+        CacheEntry::new(
+            output.to_string(),
+            Duration::from_secs(60),
+            config_hash(TEST_INPUT),
+        )
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    fn clear_all_caches_at(cache_root: &std::path::Path) -> Result<()> {
+        if !cache_root.exists() {
+            return Ok(());
+        }
+
+        for entry in std::fs::read_dir(cache_root)
+            .with_context(|| format!("Failed to read cache directory: {}", cache_root.display()))?
+        {
+            let entry = entry?;
+            let path = entry.path();
+            if path.is_file() {
+                std::fs::remove_file(&path)
+                    .with_context(|| format!("Failed to remove cache file: {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    #[test]
+    fn cache_write_and_read_hit() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_root = temp_dir.path().join("op_loader");
+
+        let output = "{\"FOO\":\"bar\"}";
+        write_cached_output_at(
+            &cache_root,
+            "account-1",
+            CacheKind::ResolvedVars,
+            &test_entry(output),
+        )
+        .unwrap();
+        let result = read_cached_output_at(
+            &cache_root,
+            "account-1",
+            CacheKind::ResolvedVars,
+            Duration::from_secs(60),
+            &config_hash(TEST_INPUT),
+        )
+        .unwrap();
+
+        match result {
+            CacheReadOutcome::Hit(contents) => assert_eq!(contents, output),
+            _ => panic!("Expected cache hit"),
+        }
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    #[test]
+    fn cache_read_expired_returns_expired() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_root = temp_dir.path().join("op_loader");
+
+        // The file's mtime is fresh; expiry must come from the entry header.
+        let mut entry = test_entry("{\"TOKEN\":\"old\"}");
+        entry.created_at -= 120;
+        write_cached_output_at(&cache_root, "account-2", CacheKind::ResolvedVars, &entry).unwrap();
+
+        let result = read_cached_output_at(
+            &cache_root,
+            "account-2",
+            CacheKind::ResolvedVars,
+            Duration::from_secs(60),
+            &config_hash(TEST_INPUT),
+        )
+        .unwrap();
+
+        assert!(matches!(result, CacheReadOutcome::Expired));
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    #[test]
+    fn cache_read_missing_returns_miss() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_root = temp_dir.path().join("op_loader");
+
+        let result = read_cached_output_at(
+            &cache_root,
+            "missing-account",
+            CacheKind::ResolvedVars,
+            Duration::from_secs(60),
+            &config_hash(TEST_INPUT),
+        )
+        .unwrap();
+
+        assert!(matches!(result, CacheReadOutcome::Miss));
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    #[test]
+    fn cache_read_with_changed_mappings_returns_stale() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_root = temp_dir.path().join("op_loader");
+
+        write_cached_output_at(
+            &cache_root,
+            "account-3",
+            CacheKind::ResolvedVars,
+            &test_entry("{\"FOO\":\"bar\"}"),
+        )
+        .unwrap();
+
+        let changed_input = format!("{TEST_INPUT}BAR: op://vault/item/other\n");
+        let result = read_cached_output_at(
+            &cache_root,
+            "account-3",
+            CacheKind::ResolvedVars,
+            Duration::from_secs(60),
+            &config_hash(&changed_input),
+        )
+        .unwrap();
+
+        assert!(matches!(result, CacheReadOutcome::Stale));
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    #[test]
+    fn cache_clear_removes_all_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache_root = temp_dir.path().join("op_loader");
+
+        write_cached_output_at(
+            &cache_root,
+            "account-a",
+            CacheKind::ResolvedVars,
+            &test_entry("{\"A\":\"1\"}"),
+        )
+        .unwrap();
+        std::fs::write(cache_root.join("extra-file.txt"), "extra").unwrap();
+        std::fs::create_dir_all(cache_root.join("nested")).unwrap();
+
+        clear_all_caches_at(&cache_root).unwrap();
+
+        let remaining_files = std::fs::read_dir(cache_root)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .count();
+        assert_eq!(remaining_files, 0);
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+    use assert_fs::TempDir;
+
+    #[test]
+    fn config_get_default_account_id() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+
+        let config = OpLoadConfig {
+            default_account_id: Some("test-account-123".to_string()),
+            ..Default::default()
+        };
+        confy::store_path(&config_path, &config).unwrap();
+
+        let result = handle_config_action_with_path(
+            ConfigAction::Get {
+                key: "default_account_id".to_string(),
+            },
+            Some(&config_path),
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn config_with_cache_section_round_trips() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+
+        let mut config = OpLoadConfig {
+            allowed_sync_paths: vec!["~/Dropbox/private".to_string()],
+            ..Default::default()
+        };
+        config.cache.max_size = Some("10MB".to_string());
+        config.inject_vars.insert(
+            "TOKEN".to_string(),
+            InjectVarConfig {
+                account_id: "acct".to_string(),
+                op_reference: "op://v/i/f".to_string(),
+                prefix: None,
+            },
+        );
+        confy::store_path(&config_path, &config).unwrap();
+
+        let loaded: OpLoadConfig = confy::load_path(&config_path).unwrap();
+
+        assert_eq!(loaded.cache.max_size.as_deref(), Some("10MB"));
+        assert_eq!(loaded.allowed_sync_paths, config.allowed_sync_paths);
+        assert!(loaded.inject_vars.contains_key("TOKEN"));
+    }
+
+    #[test]
+    fn config_set_enables_telemetry() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        confy::store_path(&config_path, OpLoadConfig::default()).unwrap();
+
+        handle_config_action_with_path(
+            ConfigAction::Set {
+                key: "telemetry.enabled".to_string(),
+                value: "true".to_string(),
+            },
+            Some(&config_path),
+        )
+        .unwrap();
+        let result = handle_config_action_with_path(
+            ConfigAction::Set {
+                key: "telemetry.enabled".to_string(),
+                value: "yes please".to_string(),
+            },
+            Some(&config_path),
+        );
+
+        let loaded: OpLoadConfig = confy::load_path(&config_path).unwrap();
+        assert!(loaded.telemetry.enabled);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn upgrade_config_dry_run_leaves_file_untouched() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        let legacy = "default_account_id = \"acct\"\n\n[inject_vars]\nTOKEN = \"op://v/i/f\"\n";
+        std::fs::write(&config_path, legacy).unwrap();
+
+        upgrade_config_at(&config_path, true, 7).unwrap();
+        assert_eq!(std::fs::read_to_string(&config_path).unwrap(), legacy);
+        assert!(!backup_path(&config_path, 7).exists());
+
+        upgrade_config_at(&config_path, false, 7).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(backup_path(&config_path, 7)).unwrap(),
+            legacy
+        );
+        let upgraded: OpLoadConfig = confy::load_path(&config_path).unwrap();
+        assert_eq!(upgraded.inject_vars["TOKEN"].account_id, "acct");
+    }
+
+    #[test]
+    fn config_get_unknown_key() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+
+        let result = handle_config_action_with_path(
+            ConfigAction::Get {
+                key: "nonexistent_key".to_string(),
+            },
+            Some(&config_path),
+        );
+
+        assert!(result.is_err());
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("Unknown config key")
+        );
+    }
+
+    #[test]
+    fn config_path_shows_custom_path() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+
+        let result = handle_config_action_with_path(ConfigAction::Path, Some(&config_path));
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn config_get_when_file_does_not_exist_returns_not_set() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("nonexistent.toml");
+
+        let result = handle_config_action_with_path(
+            ConfigAction::Get {
+                key: "default_account_id".to_string(),
+            },
+            Some(&config_path),
+        );
+
+        assert!(result.is_ok());
+    }
+}
+
+#[cfg(test)]
+mod resolved_vars_tests {
+    use super::*;
+
+    #[test]
+    fn parses_resolved_vars_json() {
+        let json = r#"{"API_KEY":"abc123","URL":"https://example.com"}"#;
+
+        let parsed = parse_cached_vars(json).unwrap();
 
-    let target_path = expand_path(path)?;
-    let target_key = target_path.to_string_lossy().to_string();
+        assert_eq!(parsed.get("API_KEY"), Some(&"abc123".to_string()));
+        assert_eq!(parsed.get("URL"), Some(&"https://example.com".to_string()));
+    }
 
-    let mut config: OpLoadConfig =
-        confy::load("op_loader", None).context("Failed to load configuration")?;
+    #[test]
+    fn format_exports_escapes_single_quotes() {
+        let mut vars = std::collections::HashMap::new();
+        vars.insert("TOKEN".to_string(), "a'b".to_string());
 
-    let template_config = config
-        .templated_files
-        .remove(&target_key)
-        .with_context(|| {
-            format!(
-                "File is not managed as a template: {}",
-                target_path.display()
-            )
-        })?;
+        let output = format_exports(&vars, Shell::Posix);
 
-    let templates_dir = get_templates_dir()?;
-    let template_path = templates_dir.join(&template_config.template_name);
+        assert_eq!(output, "export TOKEN='a'\\''b'\n");
+    }
 
-    if template_path.exists() {
-        std::fs::remove_file(&template_path)
-            .with_context(|| format!("Failed to delete template: {}", template_path.display()))?;
-        println!("Removed template: {}", template_path.display());
-    } else {
-        println!(
-            "Removed config for: {} (template file was already missing)",
-            target_path.display()
+    #[test]
+    fn exports_under_prefixed_names() {
+        let mut config = OpLoadConfig::default();
+        config.inject_vars.insert(
+            "TOKEN".to_string(),
+            InjectVarConfig {
+                account_id: "acct".to_string(),
+                op_reference: "op://v/i/f".to_string(),
+                prefix: Some("PROD_".to_string()),
+            },
         );
+        let vars = std::collections::HashMap::from([("TOKEN".to_string(), "secret".to_string())]);
+
+        let output = format_exports(&with_prefixes(&config, &vars), Shell::Posix);
+
+        assert_eq!(output, "export PROD_TOKEN='secret'\n");
     }
 
-    confy::store("op_loader", None, &config).context("Failed to save configuration")?;
+    #[test]
+    fn format_exports_preserves_colons_and_newlines() {
+        let mut vars = std::collections::HashMap::new();
+        vars.insert("CONFIG".to_string(), "line1:ok\nline2".to_string());
 
-    Ok(())
-}
+        let output = format_exports(&vars, Shell::Posix);
 
-fn render_templates(
-    config: &OpLoadConfig,
-    resolved_vars_by_account: &std::collections::HashMap<
-        String,
-        std::collections::HashMap<String, String>,
-    >,
-) -> Result<()> {
-    let templates_dir = get_templates_dir()?;
+        assert_eq!(output, "export CONFIG='line1:ok\nline2'\n");
+    }
 
-    let resolved_vars: std::collections::HashMap<String, String> = resolved_vars_by_account
-        .values()
-        .flat_map(|vars| vars.iter().map(|(k, v)| (k.clone(), v.clone())))
-        .collect();
+    #[test]
+    fn groups_vars_by_account_in_name_order() {
+        let var = |account_id: &str, reference: &str| InjectVarConfig {
+            account_id: account_id.to_string(),
+            op_reference: reference.to_string(),
+            prefix: None,
+        };
+        let inject_vars = std::collections::HashMap::from([
+            ("ZETA".to_string(), var("acct-1", "op://v/z/f")),
+            ("ALPHA".to_string(), var("acct-1", "op://v/a/f")),
+            ("MID".to_string(), var("acct-1", "op://v/m/f")),
+            ("OTHER".to_string(), var("acct-2", "op://v/o/f")),
+        ]);
+
+        let grouped = group_vars_by_account(&inject_vars);
+
+        let names: Vec<&str> = grouped["acct-1"].iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["ALPHA", "MID", "ZETA"]);
+        assert_eq!(grouped["acct-2"].len(), 1);
+    }
 
-    for (target_path, template_config) in &config.templated_files {
-        let template_path = templates_dir.join(&template_config.template_name);
+    /// What `op inject` prints for `account_id`'s vars in `config`, with
+    /// each reference replaced by the given value.
+    fn injected(config: &OpLoadConfig, account_id: &str, values: &[(&str, &str)]) -> String {
+        let grouped = group_vars_by_account(&config.inject_vars);
+        let policy = crate::policy::Policy::default();
+        let (mut output, _) = account_inject_input(account_id, &grouped[account_id], &policy, None);
+        for (name, value) in values {
+            output = output.replace(&config.inject_vars[*name].op_reference, value);
+        }
+        output
+    }
 
-        if !template_path.exists() {
-            eprintln!(
-                "# Warning: Template file not found for {}: {}",
-                target_path,
-                template_path.display()
+    #[test]
+    fn resolves_each_account_with_op_inject() {
+        let mut config = OpLoadConfig::default();
+        for (name, account_id) in [
+            ("TOKEN", "acct-1"),
+            ("API_KEY", "acct-1"),
+            ("DB_URL", "acct-2"),
+            ("DB_PASSWORD", "acct-2"),
+        ] {
+            config.inject_vars.insert(
+                name.to_string(),
+                InjectVarConfig {
+                    account_id: account_id.to_string(),
+                    op_reference: format!("op://v/{name}/f"),
+                    prefix: None,
+                },
             );
-            continue;
         }
+        let op = crate::op_client::MockOpClient::default()
+            .succeed(
+                "inject --account acct-1",
+                &injected(
+                    &config,
+                    "acct-1",
+                    &[("API_KEY", "key"), ("TOKEN", "secret")],
+                ),
+            )
+            .fail("inject --account acct-2", "[ERROR] not currently signed in");
 
-        debug!(
-            "Rendering template: {} -> {}",
-            template_path.display(),
-            target_path
+        let results = resolve_vars_per_account(&op, &config).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, "acct-1");
+        assert_eq!(
+            results[0].1.as_ref().unwrap()["TOKEN"],
+            "secret".to_string()
         );
+        let err = results[1].1.as_ref().unwrap_err().to_string();
+        assert!(err.contains("not currently signed in"));
+    }
 
-        let template_content =
-            std::fs::read_to_string(&template_path).context("Failed to read template file")?;
+    #[test]
+    fn resolves_one_var_per_call_when_batching_is_slow() {
+        let mut config = OpLoadConfig::default();
+        for name in ["API_KEY", "TOKEN"] {
+            config.inject_vars.insert(
+                name.to_string(),
+                InjectVarConfig {
+                    account_id: "acct".to_string(),
+                    op_reference: format!("op://v/{name}/f"),
+                    prefix: None,
+                },
+            );
+        }
+        let grouped = group_vars_by_account(&config.inject_vars);
+        let (input, _) = account_inject_input(
+            "acct",
+            &grouped["acct"],
+            &crate::policy::Policy::default(),
+            None,
+        );
+        let op = crate::op_client::MockOpClient::default()
+            .succeed(
+                "inject --account acct",
+                &injected(&config, "acct", &[("API_KEY", "key"), ("TOKEN", "secret")]),
+            )
+            .succeed("read op://v/API_KEY/f --account acct --no-newline", "key")
+            .succeed("read op://v/TOKEN/f --account acct --no-newline", "secret");
+
+        // Threads finish in any order, and the version check isn't of interest.
+        let calls = || {
+            let mut calls = op.calls();
+            calls.retain(|call| call != "--version");
+            calls.sort();
+            calls
+        };
 
-        let mut rendered: String = template_content
-            .lines()
-            .filter(|line| !line.starts_with("# op-loader:"))
-            .collect::<Vec<_>>()
-            .join("\n");
+        let batched = resolve_account_json(&op, "acct", &input, Batching::Batched).unwrap();
+        assert_eq!(calls(), vec!["inject --account acct"]);
 
-        if template_content.ends_with('\n') && !rendered.ends_with('\n') {
-            rendered.push('\n');
-        }
+        let per_var = resolve_account_json(&op, "acct", &input, Batching::PerVar).unwrap();
+        assert_eq!(
+            calls(),
+            vec![
+                "inject --account acct",
+                "read op://v/API_KEY/f --account acct --no-newline",
+                "read op://v/TOKEN/f --account acct --no-newline",
+            ]
+        );
+        assert_eq!(
+            parse_cached_vars(&batched).unwrap(),
+            parse_cached_vars(&per_var).unwrap()
+        );
+    }
 
-        for (var_name, value) in &resolved_vars {
-            let placeholder = format!("{{{{{var_name}}}}}");
-            rendered = rendered.replace(&placeholder, value);
+    #[test]
+    fn keeps_multiline_values_from_op_inject_intact() {
+        let mut config = OpLoadConfig::default();
+        for name in ["CERT", "DSN", "EMPTY"] {
+            config.inject_vars.insert(
+                name.to_string(),
+                InjectVarConfig {
+                    account_id: "acct".to_string(),
+                    op_reference: format!("op://v/{name}/f"),
+                    prefix: None,
+                },
+            );
         }
+        let cert = "-----BEGIN CERTIFICATE-----\nMIIB: abc\n-----END CERTIFICATE-----\n";
+        let output = injected(
+            &config,
+            "acct",
+            &[
+                ("CERT", cert),
+                ("DSN", "postgres://u: p@h/db"),
+                ("EMPTY", ""),
+            ],
+        );
+        let op =
+            crate::op_client::MockOpClient::default().succeed("inject --account acct", &output);
 
-        let target = PathBuf::from(target_path);
-        if let Some(parent) = target.parent() {
-            std::fs::create_dir_all(parent)
-                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
-        }
+        let results = resolve_vars_per_account(&op, &config).unwrap();
 
-        std::fs::write(&target, &rendered)
-            .with_context(|| format!("Failed to write to {target_path}"))?;
+        let vars = results[0].1.as_ref().unwrap();
+        assert_eq!(vars["CERT"], cert);
+        assert_eq!(vars["DSN"], "postgres://u: p@h/db");
+        assert_eq!(vars["EMPTY"], "");
 
-        info!("Rendered template: {target_path}");
+        let truncated = &output[..output.rfind("--op-loader-").unwrap()];
+        let op =
+            crate::op_client::MockOpClient::default().succeed("inject --account acct", truncated);
+        let err = resolve_vars_per_account(&op, &config).unwrap().remove(0).1;
+        assert!(format!("{:#}", err.unwrap_err()).contains("ended before the last var"));
     }
 
-    Ok(())
-}
+    #[test]
+    fn resolves_a_lone_reference_with_op_read() {
+        let mut config = OpLoadConfig::default();
+        config.inject_vars.insert(
+            "CERT".to_string(),
+            InjectVarConfig {
+                account_id: "acct".to_string(),
+                op_reference: "op://v/cert/pem".to_string(),
+                prefix: None,
+            },
+        );
+        let op = crate::op_client::MockOpClient::default().succeed(
+            "read op://v/cert/pem --account acct --no-newline",
+            "line one\nline two",
+        );
 
-fn group_vars_by_account<'a>(
-    inject_vars: &'a std::collections::HashMap<String, InjectVarConfig>,
-) -> std::collections::BTreeMap<&'a str, Vec<(&'a str, &'a InjectVarConfig)>> {
-    let mut vars_by_account: std::collections::BTreeMap<
-        &'a str,
-        Vec<(&'a str, &'a InjectVarConfig)>,
-    > = std::collections::BTreeMap::new();
+        let results = resolve_vars_per_account(&op, &config).unwrap();
 
-    for (var_name, var_config) in inject_vars {
-        vars_by_account
-            .entry(var_config.account_id.as_str())
-            .or_default()
-            .push((var_name.as_str(), var_config));
+        assert_eq!(results[0].1.as_ref().unwrap()["CERT"], "line one\nline two");
+        assert!(!op.calls().iter().any(|call| call.starts_with("inject")));
+        assert_eq!(
+            var_test(&op, &config, "CERT").unwrap(),
+            "✓ op read op://v/cert/pem (CERT: ********, 17 chars)"
+        );
     }
 
-    vars_by_account
-}
+    #[test]
+    fn reports_drift_of_rendered_files() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("npmrc.tmpl"), "token={{TOKEN}}\n").unwrap();
+        let target = dir.path().join(".npmrc");
+        let template = TemplatedFile {
+            template_name: "npmrc.tmpl".to_string(),
+            vars: None,
+            symlink: false,
+        };
+        let vars = std::collections::HashMap::from([("TOKEN".to_string(), "abc".to_string())]);
+
+        let rendered = render_target(
+            &crate::op_client::MockOpClient::default(),
+            &OpLoadConfig::default(),
+            dir.path(),
+            &target.to_string_lossy(),
+            &template,
+            &vars,
+        )
+        .unwrap()
+        .unwrap();
 
-#[cfg(all(test, target_os = "macos"))]
-mod cache_tests {
-    use super::*;
-    use crate::cache::cache_path_for_account;
-    use assert_fs::TempDir;
-    use filetime::FileTime;
+        assert_eq!(rendered, "token=abc\n");
+        assert_eq!(template_drift(&target, &rendered), TemplateDrift::Missing);
+        std::fs::write(&target, "token=abc\n").unwrap();
+        assert_eq!(template_drift(&target, &rendered), TemplateDrift::UpToDate);
+        std::fs::write(&target, "token=old\n").unwrap();
+        assert_eq!(template_drift(&target, &rendered), TemplateDrift::Drifted);
+    }
 
-    #[cfg(target_os = "macos")]
-    fn write_cached_output_at(
-        cache_root: &std::path::Path,
-        account_id: &str,
-        kind: CacheKind,
-        output: &str,
-    ) -> Result<()> {
-        use std::fs::OpenOptions;
-        use std::io::Write;
+    #[test]
+    fn warns_about_unknown_placeholders() {
+        let mut config = OpLoadConfig::default();
+        for name in ["NPM_TOKEN", "DB_URL"] {
+            config.inject_vars.insert(
+                name.to_string(),
+                InjectVarConfig {
+                    account_id: "acct".to_string(),
+                    op_reference: format!("op://v/{name}/value"),
+                    prefix: None,
+                },
+            );
+        }
+        let template = TemplatedFile {
+            template_name: "npmrc.tmpl".to_string(),
+            vars: Some(vec!["NPM_TOKEN".to_string()]),
+            symlink: false,
+        };
 
-        std::fs::create_dir_all(cache_root).with_context(|| {
-            format!("Failed to create cache directory: {}", cache_root.display())
-        })?;
-        let path = cache_path_for_account(cache_root, account_id, kind);
-        let encrypted = super::encrypt_cache(output.as_bytes())?;
+        let warnings = placeholder_warnings(
+            &config,
+            &template,
+            "token={{NPM_TOKNE}}\ndb={{ DB_URL }}\nok={{NPM_TOKEN}} {{ op://v/i/f }} {{OTHER}}\n",
+        );
 
-        let mut file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(&path)
-            .with_context(|| {
-                format!("Failed to open cache file for writing: {}", path.display())
-            })?;
+        assert_eq!(
+            warnings,
+            [
+                "{{NPM_TOKNE}} isn't a configured variable. Did you mean {{NPM_TOKEN}}?",
+                "{{DB_URL}} isn't in this template's `vars` list",
+                "{{OTHER}} isn't a configured variable",
+            ]
+        );
+    }
 
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = file.metadata()?.permissions();
-            perms.set_mode(0o600);
-            std::fs::set_permissions(&path, perms).with_context(|| {
-                format!("Failed to set cache file permissions: {}", path.display())
-            })?;
-        }
+    #[test]
+    fn masks_values_in_printed_renders() {
+        let values = ["abc".to_string(), "abcdef".to_string(), String::new()];
 
-        file.write_all(encrypted.as_bytes())
-            .with_context(|| format!("Failed to write cache file: {}", path.display()))?;
-        Ok(())
+        assert_eq!(
+            mask_values("token=abcdef\nuser=abc\n", &values),
+            "token=********\nuser=********\n"
+        );
     }
 
-    #[cfg(target_os = "macos")]
-    fn read_cached_output_at(
-        cache_root: &std::path::Path,
-        account_id: &str,
-        kind: CacheKind,
-        ttl: Duration,
-    ) -> Result<CacheReadOutcome> {
-        let path = cache_path_for_account(cache_root, account_id, kind);
-        let metadata = match std::fs::metadata(&path) {
-            Ok(meta) => meta,
-            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
-                return Ok(CacheReadOutcome::Miss);
-            }
-            Err(err) => {
-                return Err(err)
-                    .with_context(|| format!("Failed to read cache metadata: {}", path.display()));
-            }
+    #[test]
+    fn diffs_explain_how_to_store_content() {
+        let mut config = OpLoadConfig::default();
+        let record = |config: &OpLoadConfig, rendered: &str| {
+            RenderRecord::new(rendered, history_content(config, "npmrc", rendered))
         };
-
-        let modified = metadata
-            .modified()
-            .with_context(|| format!("Failed to read cache mtime: {}", path.display()))?;
-
-        let age = modified
-            .elapsed()
-            .unwrap_or_else(|_| Duration::from_secs(0));
-        if age > ttl {
-            return Ok(CacheReadOutcome::Expired);
+        let history = [
+            record(&config, "token=new\n"),
+            record(&config, "token=old\n"),
+        ];
+
+        let err = diff_records(&history, 1).unwrap_err().to_string();
+
+        assert!(err.contains("store_content = true"), "{err}");
+        assert!(err.contains("[template_history]"), "{err}");
+        assert!(diff_records(&history, 2).is_err());
+
+        // Without a keychain, content is kept as-is in the private history file.
+        if cfg!(not(any(target_os = "macos", target_os = "windows"))) {
+            config.template_history.store_content = true;
+            let history = [
+                record(&config, "token=new\n"),
+                record(&config, "token=old\n"),
+            ];
+            let (earlier, latest) = diff_records(&history, 1).unwrap().unwrap();
+            assert_eq!(
+                open_history_content("npmrc", earlier).unwrap(),
+                "token=old\n"
+            );
+            assert_eq!(
+                open_history_content("npmrc", latest).unwrap(),
+                "token=new\n"
+            );
         }
+    }
 
-        let contents = std::fs::read_to_string(&path)
-            .with_context(|| format!("Failed to read cache file: {}", path.display()))?;
-        let decrypted = super::decrypt_cache(&contents)?;
-        let rendered = String::from_utf8_lossy(&decrypted).to_string();
-        Ok(CacheReadOutcome::Hit(rendered))
+    #[test]
+    fn rolls_back_only_to_stored_renders() {
+        let record = |hash: &str, content: Option<&str>| RenderRecord {
+            rendered_at: 0,
+            hash: hash.repeat(12),
+            content: content.map(String::from),
+        };
+        let history = [
+            record("a", Some("sealed-latest")),
+            record("b", None),
+            record("c", Some("sealed-older")),
+        ];
+
+        assert_eq!(rollback_record(&history, 2).unwrap().1, "sealed-older");
+        let err = rollback_record(&history, 1).unwrap_err().to_string();
+        assert!(err.contains("store_content = true"), "{err}");
+        assert!(rollback_record(&history, 3).is_err());
+        assert!(rollback_record(&history, 0).is_err());
     }
 
-    #[cfg(target_os = "macos")]
-    fn clear_all_caches_at(cache_root: &std::path::Path) -> Result<()> {
-        if !cache_root.exists() {
-            return Ok(());
-        }
+    #[test]
+    fn rollback_with_default_config_explains_how_to_store_content() {
+        let config = OpLoadConfig::default();
+        let history: Vec<RenderRecord> = ["new", "old"]
+            .iter()
+            .map(|rendered| RenderRecord {
+                rendered_at: 0,
+                hash: "f".repeat(64),
+                content: history_content(&config, "t", rendered),
+            })
+            .collect();
+
+        let err = rollback_record(&history, 1).unwrap_err().to_string();
+        assert!(err.contains("store_content = true"), "{err}");
+        assert!(err.contains("op-loader config path"), "{err}");
+    }
 
-        for entry in std::fs::read_dir(cache_root)
-            .with_context(|| format!("Failed to read cache directory: {}", cache_root.display()))?
-        {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_file() {
-                std::fs::remove_file(&path)
-                    .with_context(|| format!("Failed to remove cache file: {}", path.display()))?;
-            }
-        }
-        Ok(())
+    #[test]
+    fn refuses_rollback_into_cloud_synced_folder() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let synced = dir.path().join("Dropbox");
+        std::fs::create_dir(&synced).unwrap();
+        let target = synced.join(".npmrc");
+        std::fs::write(&target, "token=current\n").unwrap();
+        let backups = dir.path().join("backups");
+        let mut config = OpLoadConfig::default();
+
+        let err = write_rollback(&config, &target, &backups, "token=old\n").unwrap_err();
+
+        assert!(err.to_string().contains("inside a Dropbox folder"), "{err}");
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "token=current\n");
+        assert!(!backups.exists());
+
+        config.allowed_sync_paths = vec![synced.to_string_lossy().to_string()];
+        write_rollback(&config, &target, &backups, "token=old\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "token=old\n");
     }
 
-    #[cfg(target_os = "macos")]
     #[test]
-    fn cache_write_and_read_hit() {
-        let temp_dir = TempDir::new().unwrap();
-        let cache_root = temp_dir.path().join("op_loader");
+    fn scoped_templates_only_see_their_vars() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        std::fs::write(
+            dir.path().join("npmrc.tmpl"),
+            "token={{TOKEN}} db={{DB_URL}}\n",
+        )
+        .unwrap();
+        let mut config = OpLoadConfig::default();
+        for name in ["TOKEN", "DB_URL"] {
+            config.inject_vars.insert(
+                name.to_string(),
+                InjectVarConfig {
+                    account_id: "acct".to_string(),
+                    op_reference: format!("op://v/{name}/value"),
+                    prefix: None,
+                },
+            );
+        }
+        config.templated_files.insert(
+            "/home/me/.npmrc".to_string(),
+            TemplatedFile {
+                template_name: "npmrc.tmpl".to_string(),
+                vars: None,
+                symlink: false,
+            },
+        );
+        let vars = std::collections::HashMap::from([
+            ("TOKEN".to_string(), "abc".to_string()),
+            ("DB_URL".to_string(), "postgres://db".to_string()),
+        ]);
 
-        let output = "{\"FOO\":\"bar\"}";
-        write_cached_output_at(&cache_root, "account-1", CacheKind::ResolvedVars, output).unwrap();
-        let result = read_cached_output_at(
-            &cache_root,
-            "account-1",
-            CacheKind::ResolvedVars,
-            Duration::from_secs(60),
+        assert!(
+            set_template_scope(
+                &mut config,
+                "/home/me/.npmrc",
+                Some(vec!["NOPE".to_string()])
+            )
+            .is_err()
+        );
+        let template = set_template_scope(
+            &mut config,
+            "/home/me/.npmrc",
+            Some(vec!["TOKEN".to_string(), "TOKEN".to_string()]),
         )
+        .unwrap()
+        .clone();
+        assert_eq!(template.vars.as_deref(), Some(&["TOKEN".to_string()][..]));
+
+        let rendered = render_target(
+            &crate::op_client::MockOpClient::default(),
+            &config,
+            dir.path(),
+            "/home/me/.npmrc",
+            &template,
+            &vars,
+        )
+        .unwrap()
         .unwrap();
 
-        match result {
-            CacheReadOutcome::Hit(contents) => assert_eq!(contents, output),
-            _ => panic!("Expected cache hit"),
-        }
+        assert_eq!(rendered, "token=abc db={{DB_URL}}\n");
+        assert_eq!(
+            replace_vars_comment(
+                "# op-loader: Available variables: {{DB_URL}}, {{TOKEN}}\nx\n",
+                &available_vars_comment(template.vars.iter().flatten())
+            ),
+            "# op-loader: Available variables: {{TOKEN}}\nx\n"
+        );
     }
 
-    #[cfg(target_os = "macos")]
     #[test]
-    fn cache_read_expired_returns_expired() {
-        let temp_dir = TempDir::new().unwrap();
-        let cache_root = temp_dir.path().join("op_loader");
-
-        write_cached_output_at(
-            &cache_root,
-            "account-2",
-            CacheKind::ResolvedVars,
-            "{\"TOKEN\":\"old\"}",
+    fn resolves_op_references_in_templates_without_sending_secrets() {
+        let mut config = OpLoadConfig::default();
+        let template = "user={{USER}}\ntoken={{ op://Private/npm/token }}\n";
+        let vars = std::collections::HashMap::from([("USER".to_string(), "me".to_string())]);
+        let path = Path::new("npmrc.tmpl");
+
+        let err = render_template(
+            &crate::op_client::MockOpClient::default(),
+            &config,
+            path,
+            template,
+            &vars,
         )
-        .unwrap();
-        let cache_path = cache_path_for_account(&cache_root, "account-2", CacheKind::ResolvedVars);
-        let past = std::time::SystemTime::now() - Duration::from_secs(120);
-        filetime::set_file_mtime(&cache_path, FileTime::from_system_time(past)).unwrap();
+        .unwrap_err();
+        assert!(err.to_string().contains("set a default account"), "{err}");
 
-        let result = read_cached_output_at(
-            &cache_root,
-            "account-2",
-            CacheKind::ResolvedVars,
-            Duration::from_secs(60),
-        )
-        .unwrap();
+        config.default_account_id = Some("acct".to_string());
+        let op = crate::op_client::MockOpClient::default().succeed(
+            "read op://Private/npm/token --account acct --no-newline",
+            "npm_abc",
+        );
+
+        let rendered = render_template(&op, &config, path, template, &vars).unwrap();
+
+        assert_eq!(rendered, "user=me\ntoken=npm_abc\n");
+        let plain = render_template(&op, &config, path, "user={{USER}}\n", &vars).unwrap();
+        assert_eq!(plain, "user=me\n");
+        let calls: Vec<String> = op
+            .calls()
+            .into_iter()
+            .filter(|call| call != "--version")
+            .collect();
+        assert_eq!(
+            calls,
+            ["read op://Private/npm/token --account acct --no-newline"]
+        );
 
-        assert!(matches!(result, CacheReadOutcome::Expired));
+        // More than one reference goes through `op inject`, which only ever
+        // sees the references.
+        let (npm, gh) = ("op://Private/npm/token", "op://Private/gh/token");
+        let output = inject_blocks(&[(npm, npm), (gh, gh)])
+            .replace(&format!("\n{npm}\n"), "\nnpm_abc\n")
+            .replace(&format!("\n{gh}\n"), "\ngh_xyz\n");
+        let op =
+            crate::op_client::MockOpClient::default().succeed("inject --account acct", &output);
+        let template = format!("user={{{{USER}}}}\nnpm={{{{ {npm} }}}}\ngh={{{{ {gh} }}}}\n");
+
+        let rendered = render_template(&op, &config, path, &template, &vars).unwrap();
+
+        assert_eq!(rendered, "user=me\nnpm=npm_abc\ngh=gh_xyz\n");
     }
+}
+
+#[cfg(test)]
+mod cache_crypto_tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [7u8; 32];
 
-    #[cfg(target_os = "macos")]
     #[test]
-    fn cache_read_missing_returns_miss() {
-        let temp_dir = TempDir::new().unwrap();
-        let cache_root = temp_dir.path().join("op_loader");
+    fn seal_and_open_round_trip() {
+        let aad = cache_aad("account-1", CacheKind::ResolvedVars);
+        let sealed = seal_cache_payload(&KEY, &aad, b"{\"A\":\"1\"}").unwrap();
 
-        let result = read_cached_output_at(
-            &cache_root,
-            "missing-account",
-            CacheKind::ResolvedVars,
-            Duration::from_secs(60),
-        )
-        .unwrap();
+        let opened = open_cache_payload(&KEY, &aad, &sealed).unwrap();
 
-        assert!(matches!(result, CacheReadOutcome::Miss));
+        assert_eq!(opened, b"{\"A\":\"1\"}");
     }
 
-    #[cfg(target_os = "macos")]
     #[test]
-    fn cache_clear_removes_all_files() {
-        let temp_dir = TempDir::new().unwrap();
-        let cache_root = temp_dir.path().join("op_loader");
+    fn sealed_payload_does_not_contain_plaintext() {
+        let aad = cache_aad("account-1", CacheKind::ResolvedVars);
+        let sealed = seal_cache_payload(&KEY, &aad, b"super-secret-token").unwrap();
 
-        write_cached_output_at(
-            &cache_root,
-            "account-a",
-            CacheKind::ResolvedVars,
-            "{\"A\":\"1\"}",
+        let raw = base64::engine::general_purpose::STANDARD
+            .decode(&sealed)
+            .unwrap();
+
+        assert!(!raw.windows(18).any(|w| w == b"super-secret-token"));
+    }
+
+    #[test]
+    fn open_fails_for_a_different_account() {
+        let sealed = seal_cache_payload(
+            &KEY,
+            &cache_aad("account-1", CacheKind::ResolvedVars),
+            b"{\"A\":\"1\"}",
         )
         .unwrap();
-        std::fs::write(cache_root.join("extra-file.txt"), "extra").unwrap();
-        std::fs::create_dir_all(cache_root.join("nested")).unwrap();
 
-        clear_all_caches_at(&cache_root).unwrap();
+        let result = open_cache_payload(
+            &KEY,
+            &cache_aad("account-2", CacheKind::ResolvedVars),
+            &sealed,
+        );
 
-        let remaining_files = std::fs::read_dir(cache_root)
-            .unwrap()
-            .filter_map(|entry| entry.ok())
-            .filter(|entry| entry.path().is_file())
-            .count();
-        assert_eq!(remaining_files, 0);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn open_rejects_unknown_version() {
+        let aad = cache_aad("account-1", CacheKind::ResolvedVars);
+        let sealed = seal_cache_payload(&KEY, &aad, b"x").unwrap();
+        let mut raw = base64::engine::general_purpose::STANDARD
+            .decode(&sealed)
+            .unwrap();
+        raw[0] = 1;
+        let legacy = base64::engine::general_purpose::STANDARD.encode(raw);
+
+        let err = open_cache_payload(&KEY, &aad, &legacy).unwrap_err();
+
+        assert!(
+            err.to_string()
+                .contains("Unsupported cache payload version")
+        );
     }
 }
 
 #[cfg(test)]
-mod config_tests {
+mod bootstrap_tests {
     use super::*;
-    use assert_fs::TempDir;
 
-    #[test]
-    fn config_get_default_account_id() {
-        let temp_dir = TempDir::new().unwrap();
-        let config_path = temp_dir.path().join("config.toml");
+    fn account(account_uuid: &str) -> Account {
+        Account {
+            email: "user@example.com".to_string(),
+            user_uuid: "user-1".to_string(),
+            account_uuid: account_uuid.to_string(),
+        }
+    }
+
+    fn var(account_id: &str) -> InjectVarConfig {
+        InjectVarConfig {
+            account_id: account_id.to_string(),
+            op_reference: "op://vault/item/field".to_string(),
+            prefix: None,
+        }
+    }
 
+    #[test]
+    fn reports_accounts_not_added_to_op() {
         let config = OpLoadConfig {
-            default_account_id: Some("test-account-123".to_string()),
+            inject_vars: std::collections::HashMap::from([
+                ("A".to_string(), var("known")),
+                ("B".to_string(), var("unknown")),
+                ("C".to_string(), var("unknown")),
+            ]),
+            default_account_id: Some("other-default".to_string()),
             ..Default::default()
         };
-        confy::store_path(&config_path, &config).unwrap();
 
-        let result = handle_config_action_with_path(
-            ConfigAction::Get {
-                key: "default_account_id".to_string(),
-            },
-            Some(&config_path),
-        );
-        assert!(result.is_ok());
+        let missing = missing_accounts(&config, &[account("known")]);
+
+        assert_eq!(missing, vec!["other-default", "unknown"]);
     }
 
     #[test]
-    fn config_get_unknown_key() {
-        let temp_dir = TempDir::new().unwrap();
-        let config_path = temp_dir.path().join("config.toml");
-
-        let result = handle_config_action_with_path(
-            ConfigAction::Get {
-                key: "nonexistent_key".to_string(),
-            },
-            Some(&config_path),
-        );
+    fn no_missing_accounts_when_all_are_known() {
+        let config = OpLoadConfig {
+            inject_vars: std::collections::HashMap::from([("A".to_string(), var("known"))]),
+            ..Default::default()
+        };
 
-        assert!(result.is_err());
-        assert!(
-            result
-                .unwrap_err()
-                .to_string()
-                .contains("Unknown config key")
-        );
+        assert!(missing_accounts(&config, &[account("known")]).is_empty());
     }
 
     #[test]
-    fn config_path_shows_custom_path() {
-        let temp_dir = TempDir::new().unwrap();
-        let config_path = temp_dir.path().join("config.toml");
+    fn signs_in_only_when_the_session_is_gone() {
+        use crate::op_client::MockOpClient;
 
-        let result = handle_config_action_with_path(ConfigAction::Path, Some(&config_path));
+        let signed_in = MockOpClient::default().succeed("whoami --account acct", "me");
+        ensure_signed_in(&signed_in, "acct").unwrap();
+        assert_eq!(signed_in.calls(), ["whoami --account acct"]);
 
-        assert!(result.is_ok());
+        let signed_out = MockOpClient::default().succeed("signin --account acct", "");
+        ensure_signed_in(&signed_out, "acct").unwrap();
+        assert_eq!(
+            signed_out.calls(),
+            ["whoami --account acct", "signin --account acct"]
+        );
     }
 
     #[test]
-    fn config_get_when_file_does_not_exist_returns_not_set() {
-        let temp_dir = TempDir::new().unwrap();
-        let config_path = temp_dir.path().join("nonexistent.toml");
+    fn lists_accounts_and_items_through_the_op_client() {
+        use crate::op_client::MockOpClient;
 
-        let result = handle_config_action_with_path(
-            ConfigAction::Get {
-                key: "default_account_id".to_string(),
-            },
-            Some(&config_path),
-        );
+        let op = MockOpClient::default()
+            .succeed(
+                "account list --format json",
+                r#"[{"email":"user@example.com","user_uuid":"user-1","account_uuid":"acct"}]"#,
+            )
+            .fail(
+                "item list --vault Private --format json --account acct",
+                "[ERROR] You are not currently signed in.",
+            );
 
-        assert!(result.is_ok());
+        let accounts = list_op_accounts(&op).unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].account_uuid, "acct");
+        let err = list_op_items(&op, "Private", Some("acct"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("op item list failed"), "{err}");
     }
 }
 
 #[cfg(test)]
-mod resolved_vars_tests {
+mod unset_tests {
     use super::*;
 
     #[test]
-    fn parses_resolved_vars_json() {
-        let json = r#"{"API_KEY":"abc123","URL":"https://example.com"}"#;
-
-        let parsed = parse_cached_vars(json).unwrap();
+    fn format_unsets_empty_returns_empty_string() {
+        let output = format_unsets(&[], Shell::Posix);
 
-        assert_eq!(parsed.get("API_KEY"), Some(&"abc123".to_string()));
-        assert_eq!(parsed.get("URL"), Some(&"https://example.com".to_string()));
+        assert_eq!(output, "");
     }
 
     #[test]
-    fn format_exports_escapes_single_quotes() {
-        let mut vars = std::collections::HashMap::new();
-        vars.insert("TOKEN".to_string(), "a'b".to_string());
+    fn format_unsets_emits_unset_lines_in_order() {
+        let names = ["API_TOKEN".to_string(), "USER".to_string()];
 
-        let output = format_exports(&vars);
+        let output = format_unsets(&names, Shell::Posix);
 
-        assert_eq!(output, "export TOKEN='a'\\''b'\n");
+        assert_eq!(output, "unset API_TOKEN\nunset USER\n");
     }
 
     #[test]
-    fn format_exports_preserves_colons_and_newlines() {
-        let mut vars = std::collections::HashMap::new();
-        vars.insert("CONFIG".to_string(), "line1:ok\nline2".to_string());
+    fn unsets_prefixed_vars_under_their_exported_names() {
+        let var = |prefix: Option<&str>| InjectVarConfig {
+            account_id: "acct".to_string(),
+            op_reference: "op://v/i/f".to_string(),
+            prefix: prefix.map(String::from),
+        };
+        let mut config = OpLoadConfig::default();
+        config
+            .inject_vars
+            .insert("TOKEN".to_string(), var(Some("PROD_")));
+        config.inject_vars.insert("USER".to_string(), var(None));
 
-        let output = format_exports(&vars);
+        let output = format_unsets(&exported_names(&config), Shell::Posix);
 
-        assert_eq!(output, "export CONFIG='line1:ok\nline2'\n");
+        assert_eq!(output, "unset PROD_TOKEN\nunset USER\n");
     }
 }
 
 #[cfg(test)]
-mod unset_tests {
+mod scratch_tests {
     use super::*;
 
     #[test]
-    fn format_unsets_empty_returns_empty_string() {
-        let keys: Vec<&String> = Vec::new();
+    fn scratch_mappings_override_configured_ones() {
+        let mut config = OpLoadConfig {
+            default_account_id: Some("acct".to_string()),
+            ..OpLoadConfig::default()
+        };
+        config.inject_vars.insert(
+            "TOKEN".to_string(),
+            InjectVarConfig {
+                account_id: "acct".to_string(),
+                op_reference: "op://Work/old/password".to_string(),
+                prefix: None,
+            },
+        );
+        let scratch = ScratchVars {
+            mappings: vec![
+                "TOKEN=op://Work/new/password".to_string(),
+                "EXTRA=op://Work/item/field=with=equals".to_string(),
+            ],
+            account_id: None,
+        };
 
-        let output = format_unsets(keys);
+        assert_eq!(scratch.merge_into(&mut config, None).unwrap(), 2);
 
-        assert_eq!(output, "");
+        assert_eq!(
+            config.inject_vars["TOKEN"].op_reference,
+            "op://Work/new/password"
+        );
+        assert_eq!(
+            config.inject_vars["EXTRA"].op_reference,
+            "op://Work/item/field=with=equals"
+        );
+        assert_eq!(config.inject_vars["EXTRA"].account_id, "acct");
     }
 
     #[test]
-    fn format_unsets_emits_unset_lines_in_order() {
-        let var_a = "API_TOKEN".to_string();
-        let var_b = "USER".to_string();
-        let keys = vec![&var_a, &var_b];
+    fn rejects_malformed_scratch_mappings() {
+        assert!(parse_scratch_mapping("TOKEN").is_err());
+        assert!(parse_scratch_mapping("1TOKEN=op://v/i/f").is_err());
+        assert!(parse_scratch_mapping("TOKEN=plain").is_err());
+
+        let mut config = OpLoadConfig::default();
+        let scratch = ScratchVars {
+            mappings: vec!["TOKEN=op://v/i/f".to_string()],
+            account_id: None,
+        };
+        assert!(scratch.merge_into(&mut config, None).is_err());
+    }
+
+    #[test]
+    fn scratch_mappings_are_checked_against_policy() {
+        let policy = crate::policy::Policy {
+            allowed_vault_patterns: vec!["Team-*".to_string()],
+            forbidden_var_patterns: vec!["AWS_*".to_string()],
+            ..Default::default()
+        };
+        let merge = |mapping: &str| {
+            let mut config = OpLoadConfig {
+                default_account_id: Some("acct".to_string()),
+                ..OpLoadConfig::default()
+            };
+            let scratch = ScratchVars {
+                mappings: vec![mapping.to_string()],
+                account_id: None,
+            };
+            let result = scratch.merge_into(&mut config, Some(&policy));
+            (result, config.inject_vars.len())
+        };
 
-        let output = format_unsets(keys);
+        let (result, added) = merge("AWS_KEY=op://Team-Ops/aws/key");
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("forbidden pattern"), "{err}");
+        assert_eq!(added, 0);
 
-        assert_eq!(output, "unset API_TOKEN\nunset USER\n");
+        let (result, _) = merge("TOKEN=op://Personal/gh/token");
+        assert!(result.unwrap_err().to_string().contains("`Personal`"));
+
+        assert_eq!(merge("TOKEN=op://Team-Ops/gh/token").0.unwrap(), 1);
     }
 }
 
@@ -1406,28 +6486,106 @@ mod unset_tests {
 mod template_tests {
     use super::*;
 
-    mod path_to_template_name {
+    mod template_name {
         use super::*;
 
         #[test]
         fn extracts_filename_from_path() {
             let path = Path::new("/Users/foo/.npmrc");
-            let result = path_to_template_name(path);
-            assert_eq!(result, ".npmrc.tmpl");
+            let result = TemplateNaming::Hashed.template_name(path);
+            assert!(result.starts_with(".npmrc-"));
+            assert!(result.ends_with(".tmpl"));
         }
 
         #[test]
         fn handles_simple_filename() {
             let path = Path::new("myfile.txt");
-            let result = path_to_template_name(path);
-            assert_eq!(result, "myfile.txt.tmpl");
+            let result = TemplateNaming::Hashed.template_name(path);
+            assert!(result.starts_with("myfile.txt-"));
         }
 
         #[test]
         fn handles_nested_path() {
             let path = Path::new("/home/user/.config/app/settings.json");
-            let result = path_to_template_name(path);
-            assert_eq!(result, "settings.json.tmpl");
+            let result = TemplateNaming::Hashed.template_name(path);
+            assert!(result.starts_with("settings.json-"));
+        }
+    }
+
+    mod templatize_content {
+        use super::*;
+
+        fn vars(pairs: &[(&str, &str)]) -> std::collections::HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+                .collect()
+        }
+
+        #[test]
+        fn replaces_secret_values_with_placeholders() {
+            let content = "//registry.npmjs.org/:_authToken=npm_abc123\n";
+            let (result, replaced) =
+                templatize_content(content, &vars(&[("NPM_TOKEN", "npm_abc123")]));
+
+            assert_eq!(result, "//registry.npmjs.org/:_authToken={{NPM_TOKEN}}\n");
+            assert_eq!(replaced, vec!["NPM_TOKEN".to_string()]);
+        }
+
+        #[test]
+        fn prefers_longer_values_when_one_contains_another() {
+            let content = "short=abcdefgh\nlong=abcdefghijkl\n";
+            let (result, _) = templatize_content(
+                content,
+                &vars(&[("SHORT", "abcdefgh"), ("LONG", "abcdefghijkl")]),
+            );
+
+            assert_eq!(result, "short={{SHORT}}\nlong={{LONG}}\n");
+        }
+
+        #[test]
+        fn leaves_inserted_placeholders_alone() {
+            let content = "token=ghp_secret12\nuser=DEPLOY_TOKEN\n";
+            let (result, replaced) = templatize_content(
+                content,
+                &vars(&[
+                    ("GITHUB_DEPLOY_TOKEN", "ghp_secret12"),
+                    ("USER", "DEPLOY_TOKEN"),
+                ]),
+            );
+
+            assert_eq!(result, "token={{GITHUB_DEPLOY_TOKEN}}\nuser={{USER}}\n");
+            assert_eq!(
+                replaced,
+                vec!["GITHUB_DEPLOY_TOKEN".to_string(), "USER".to_string()]
+            );
+        }
+
+        #[test]
+        fn skips_empty_and_absent_values() {
+            let content = "token=xyz\n";
+            let (result, replaced) =
+                templatize_content(content, &vars(&[("EMPTY", ""), ("OTHER", "nope")]));
+
+            assert_eq!(result, content);
+            assert!(replaced.is_empty());
+        }
+
+        #[test]
+        fn leaves_short_values_alone() {
+            let content = "port=443\ndebug=true\ntoken=npm_abc123\n";
+            let resolved = vars(&[
+                ("PORT", "443"),
+                ("DEBUG", "true"),
+                ("NPM_TOKEN", "npm_abc123"),
+                ("EMPTY", ""),
+            ]);
+
+            let (result, replaced) = templatize_content(content, &resolved);
+
+            assert_eq!(result, "port=443\ndebug=true\ntoken={{NPM_TOKEN}}\n");
+            assert_eq!(replaced, vec!["NPM_TOKEN".to_string()]);
+            assert_eq!(short_values(&resolved), vec!["DEBUG", "PORT"]);
         }
     }
 
@@ -1462,22 +6620,8 @@ mod template_tests {
             template: &str,
             vars: &std::collections::HashMap<String, String>,
         ) -> String {
-            let mut rendered: String = template
-                .lines()
-                .filter(|line| !line.starts_with("# op-loader:"))
-                .collect::<Vec<_>>()
-                .join("\n");
-
-            if template.ends_with('\n') && !rendered.ends_with('\n') {
-                rendered.push('\n');
-            }
-
-            for (var_name, value) in vars {
-                let placeholder = format!("{{{{{}}}}}", var_name);
-                rendered = rendered.replace(&placeholder, value);
-            }
-
-            rendered
+            crate::template_engine::render(template, vars, &std::collections::HashMap::new())
+                .unwrap()
         }
 
         #[test]
@@ -1656,7 +6800,7 @@ mod lock_tests {
     }
 }
 
-#[cfg(all(test, target_os = "macos"))]
+#[cfg(all(test, any(target_os = "macos", target_os = "windows")))]
 mod concurrency_tests {
     use super::*;
     use crate::cache::cache_path_for_account;
@@ -1676,7 +6820,7 @@ mod concurrency_tests {
 
         std::fs::create_dir_all(cache_root).unwrap();
         let path = cache_path_for_account(cache_root, account_id, kind);
-        let encrypted = encrypt_cache(output.as_bytes()).unwrap();
+        let encrypted = encrypt_cache(account_id, kind, &test_entry_bytes(output)).unwrap();
         let mut file = OpenOptions::new()
             .create(true)
             .write(true)
@@ -1694,18 +6838,21 @@ mod concurrency_tests {
         ttl: Duration,
     ) -> Option<String> {
         let path = cache_path_for_account(cache_root, account_id, kind);
-        let metadata = match std::fs::metadata(&path) {
-            Ok(m) => m,
-            Err(_) => return None,
-        };
-        let modified = metadata.modified().ok()?;
-        let age = modified.elapsed().unwrap_or_default();
-        if age > ttl {
-            return None;
-        }
         let contents = std::fs::read_to_string(&path).ok()?;
-        let decrypted = decrypt_cache(&contents).ok()?;
-        Some(String::from_utf8_lossy(&decrypted).to_string())
+        let decrypted = decrypt_cache(account_id, kind, &contents).ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&decrypted).ok()?;
+        entry
+            .is_fresh(ttl, std::time::SystemTime::now())
+            .then_some(entry.payload)
+    }
+
+    fn test_entry_bytes(output: &str) -> Vec<u8> {
+        let entry = CacheEntry::new(
+            output.to_string(),
+            Duration::from_secs(60),
+            config_hash("KEY: op://vault/item/field\n"),
+        );
+        serde_json::to_vec(&entry).unwrap()
     }
 
     #[test]
@@ -1755,7 +6902,8 @@ mod concurrency_tests {
         let path = cache_path_for_account(&cache_root, account, CacheKind::ResolvedVars);
         let tmp_path = path.with_extension("cache.tmp");
 
-        let encrypted = encrypt_cache(updated.as_bytes()).unwrap();
+        let encrypted =
+            encrypt_cache(account, CacheKind::ResolvedVars, &test_entry_bytes(updated)).unwrap();
         std::fs::write(&tmp_path, &encrypted).unwrap();
         std::fs::rename(&tmp_path, &path).unwrap();
 
@@ -1845,3 +6993,187 @@ mod concurrency_tests {
         );
     }
 }
+
+#[cfg(test)]
+mod porcelain_tests {
+    use super::*;
+
+    fn config() -> OpLoadConfig {
+        let mut config = OpLoadConfig::default();
+        config.inject_vars.insert(
+            "TOKEN".to_string(),
+            InjectVarConfig {
+                account_id: "acct-1".to_string(),
+                op_reference: "op://Dev/API/token".to_string(),
+                prefix: None,
+            },
+        );
+        config.inject_vars.insert(
+            "DB_URL".to_string(),
+            InjectVarConfig {
+                account_id: "acct-2".to_string(),
+                op_reference: "op://Dev/DB/url".to_string(),
+                prefix: None,
+            },
+        );
+        config.templated_files.insert(
+            "/home/me/.npmrc".to_string(),
+            TemplatedFile {
+                template_name: "npmrc".to_string(),
+                vars: None,
+                symlink: false,
+            },
+        );
+        config.templated_files.insert(
+            "/home/me/.env".to_string(),
+            TemplatedFile {
+                template_name: "env".to_string(),
+                vars: None,
+                symlink: false,
+            },
+        );
+        config
+    }
+
+    #[test]
+    fn var_list_is_sorted_by_name() {
+        assert_eq!(
+            var_list_porcelain(&config()),
+            "DB_URL\tacct-2\top://Dev/DB/url\nTOKEN\tacct-1\top://Dev/API/token\n"
+        );
+    }
+
+    #[test]
+    fn template_list_reports_missing_templates() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        std::fs::write(dir.path().join("npmrc"), "").unwrap();
+
+        let output = template_list_porcelain(&config(), dir.path());
+
+        let root = dir.path().display();
+        assert_eq!(
+            output,
+            format!("/home/me/.env\t{root}/env\tmissing\n/home/me/.npmrc\t{root}/npmrc\tok\n")
+        );
+    }
+
+    #[test]
+    fn status_lists_every_key_in_order() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let files = [crate::cache::CacheFileInfo {
+            path: dir.path().join("op_inject_vars_acct-1.cache"),
+            size: 512,
+            modified: std::time::SystemTime::now(),
+        }];
+
+        let summary =
+            StatusSummary::collect(&config(), dir.path(), &files, false, None, Vec::new());
+
+        assert_eq!(
+            summary.porcelain(),
+            "vars\t2\naccounts\t2\ntemplates\t2\ntemplates_missing\t2\ncache_files\t1\n\
+             cache_bytes\t512\nagent_running\tno\ntelemetry_enabled\tno\ndefault_account\t\n\
+             agent_uptime_secs\t\nagent_accounts_warmed\t\nagent_last_refresh_secs\t\nagent_locked\t\n\
+             slow_accounts\t\n"
+        );
+    }
+
+    #[test]
+    fn status_reports_agent_health() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let health = crate::agent::AgentHealth {
+            uptime_secs: 3600,
+            accounts_warmed: vec!["acct-1".to_string(), "acct-2".to_string()],
+            last_refresh_secs: 60,
+            locked: true,
+        };
+        let slow = vec![crate::inject_timing::SlowAccount {
+            account_id: "acct-2".to_string(),
+            median: Duration::from_secs(6),
+            suggestion: crate::inject_timing::Suggestion::Agent,
+        }];
+
+        let summary = StatusSummary::collect(&config(), dir.path(), &[], true, Some(health), slow);
+
+        assert!(summary.porcelain().ends_with(
+            "agent_uptime_secs\t3600\nagent_accounts_warmed\t2\n\
+             agent_last_refresh_secs\t60\nagent_locked\tyes\nslow_accounts\tacct-2\n"
+        ));
+    }
+
+    #[test]
+    fn cache_status_rows_name_kind_and_account() {
+        let now = std::time::SystemTime::now();
+        let files = [crate::cache::CacheFileInfo {
+            path: PathBuf::from("/cache/op_vaults_acct-1.cache"),
+            size: 64,
+            modified: now - Duration::from_secs(90),
+        }];
+
+        assert_eq!(
+            cache_status_rows(&files, now),
+            vec![("vaults", "acct-1".to_string(), 64, 90)]
+        );
+    }
+}
+
+#[cfg(test)]
+mod managed_file_tests {
+    use super::*;
+    use crate::op_client::MockOpClient;
+
+    fn document(item_id: &str) -> ManagedFile {
+        ManagedFile {
+            account_id: "acct".to_string(),
+            vault_id: "vault".to_string(),
+            item_id: item_id.to_string(),
+            title: item_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn syncs_each_file_and_warns_about_failures() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let placed = dir.path().join("ca.pem");
+        let failed = dir.path().join("kubeconfig");
+        let mut config = OpLoadConfig::default();
+        config
+            .managed_files
+            .insert(placed.to_string_lossy().to_string(), document("ca"));
+        config
+            .managed_files
+            .insert(failed.to_string_lossy().to_string(), document("kube"));
+        let op = MockOpClient::default()
+            .succeed("document get ca --vault vault --account acct", "PEM")
+            .fail(
+                "document get kube --vault vault --account acct",
+                "[ERROR] \"kube\" isn't an item",
+            );
+
+        assert_eq!(sync_managed_files(&op, &config, None), 1);
+        assert_eq!(std::fs::read_to_string(&placed).unwrap(), "PEM");
+        assert!(!failed.exists());
+    }
+
+    #[test]
+    fn only_document_items_can_be_added() {
+        let op = MockOpClient::default()
+            .succeed(
+                "item get GitHub --vault Private --account acct --format json",
+                r#"{"id":"gh","title":"GitHub","category":"LOGIN","vault":{"id":"v1"}}"#,
+            )
+            .succeed(
+                "item get Kube --vault Private --account acct --format json",
+                r#"{"id":"k8s","title":"Kube","category":"DOCUMENT","vault":{"id":"v1"}}"#,
+            );
+
+        let err = document_item(&op, "acct", "Private", "GitHub")
+            .unwrap_err()
+            .to_string();
+        let file = document_item(&op, "acct", "Private", "Kube").unwrap();
+
+        assert!(err.contains("only document items"), "{err}");
+        assert_eq!(file.item_id, "k8s");
+        assert_eq!(file.vault_id, "v1");
+    }
+}
@@ -1,6 +1,44 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
 #[derive(Default)]
 pub struct CommandLog {
     pub entries: Vec<CommandLogEntry>,
+    /// Where entries are also appended, so they outlive the session.
+    file: Option<PathBuf>,
+    /// When the newest entry was logged, for its toast.
+    last_pushed_at: Option<Instant>,
+}
+
+/// How long the newest entry stays up as a toast.
+const TOAST_DURATION: Duration = Duration::from_secs(4);
+
+/// The log file is moved to `log.1` once it grows past this.
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Where per-machine history such as the command log is kept:
+/// `$XDG_STATE_HOME/op_loader`, or `~/.local/state/op_loader`.
+pub fn state_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_STATE_HOME") {
+        return Ok(PathBuf::from(dir).join("op_loader"));
+    }
+
+    #[cfg(target_os = "windows")]
+    if let Some(dir) = std::env::var_os("LOCALAPPDATA") {
+        return Ok(PathBuf::from(dir).join("op_loader").join("state"));
+    }
+
+    let home = std::env::var_os("HOME").context("HOME environment variable not set")?;
+    Ok(PathBuf::from(home)
+        .join(".local")
+        .join("state")
+        .join("op_loader"))
+}
+
+pub fn log_file_path(state_dir: &Path) -> PathBuf {
+    state_dir.join("log")
 }
 
 pub struct CommandLogEntry {
@@ -13,6 +51,19 @@ pub enum CommandStatus {
     Failed { stderr: String },
 }
 
+/// A failure with its full message, as shown in the error pane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorDetails {
+    pub source: String,
+    pub message: String,
+}
+
+impl ErrorDetails {
+    pub fn clipboard_text(&self) -> String {
+        format!("{}\n{}", self.source, self.message)
+    }
+}
+
 impl CommandLogEntry {
     pub fn display(&self) -> String {
         match &self.status {
@@ -26,27 +77,64 @@ impl CommandLogEntry {
             }
         }
     }
+
+    /// The entry as the full-screen log shows it: the command, then every
+    /// line of a failure's stderr, indented.
+    pub fn full_lines(&self) -> Vec<String> {
+        match &self.status {
+            CommandStatus::Success { .. } => vec![self.display()],
+            CommandStatus::Failed { stderr } => {
+                let mut lines = vec![format!("✗ {}", self.command)];
+                lines.extend(stderr.trim_end().lines().map(|line| format!("    {line}")));
+                lines
+            }
+        }
+    }
 }
 
 impl CommandLog {
+    /// Also appends every later entry to `path`. Entries carry commands and
+    /// `op`'s errors, never secret values, so the file needs no redaction.
+    pub fn persist_to(&mut self, path: PathBuf) {
+        self.file = Some(path);
+    }
+
     pub fn log_success(&mut self, command: impl Into<String>, item_count: Option<usize>) {
-        self.entries.push(CommandLogEntry {
+        self.push(CommandLogEntry {
             command: command.into(),
             status: CommandStatus::Success { item_count },
         });
-        self.trim();
     }
 
     pub fn log_failure(&mut self, command: impl Into<String>, stderr: impl Into<String>) {
-        self.entries.push(CommandLogEntry {
+        self.push(CommandLogEntry {
             command: command.into(),
             status: CommandStatus::Failed {
                 stderr: stderr.into(),
             },
         });
+    }
+
+    fn push(&mut self, entry: CommandLogEntry) {
+        if let Some(path) = &self.file
+            && let Err(err) = append_entry(path, &entry, SystemTime::now())
+        {
+            log::debug!("Failed to write the command log file: {err:#}");
+        }
+        self.entries.push(entry);
+        self.last_pushed_at = Some(Instant::now());
         self.trim();
     }
 
+    /// The newest entry while it's fresh enough to show as a toast.
+    pub fn toast(&self, now: Instant) -> Option<&CommandLogEntry> {
+        let pushed_at = self.last_pushed_at?;
+        if now.saturating_duration_since(pushed_at) >= TOAST_DURATION {
+            return None;
+        }
+        self.entries.last()
+    }
+
     fn trim(&mut self) {
         const MAX_ENTRIES: usize = 50;
         if self.entries.len() > MAX_ENTRIES {
@@ -54,8 +142,120 @@ impl CommandLog {
         }
     }
 
+    pub fn last_failure(&self) -> Option<ErrorDetails> {
+        self.entries
+            .iter()
+            .rev()
+            .find_map(|entry| match &entry.status {
+                CommandStatus::Failed { stderr } => Some(ErrorDetails {
+                    source: entry.command.clone(),
+                    message: stderr.trim_end().to_string(),
+                }),
+                CommandStatus::Success { .. } => None,
+            })
+    }
+
+    /// Every entry's [`CommandLogEntry::full_lines`], newest first.
+    pub fn full_lines(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .rev()
+            .flat_map(CommandLogEntry::full_lines)
+            .collect()
+    }
+
     pub fn recent(&self, n: usize) -> &[CommandLogEntry] {
         let start = self.entries.len().saturating_sub(n);
         &self.entries[start..]
     }
 }
+
+/// Appends `2024-02-29T12:00:00Z ✗ op item get x`, followed by the rest of
+/// the entry's lines as [`CommandLogEntry::full_lines`] gives them.
+fn append_entry(path: &Path, entry: &CommandLogEntry, now: SystemTime) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    if std::fs::metadata(path).is_ok_and(|metadata| metadata.len() > MAX_FILE_BYTES) {
+        std::fs::rename(path, path.with_extension("1"))
+            .with_context(|| format!("Failed to rotate {}", path.display()))?;
+    }
+
+    let timestamp = crate::timestamp::format_rfc3339(now);
+    let mut text = String::new();
+    for (idx, line) in entry.full_lines().iter().enumerate() {
+        if idx == 0 {
+            text.push_str(&format!("{timestamp} {line}\n"));
+        } else {
+            text.push_str(&format!("{:width$} {line}\n", "", width = timestamp.len()));
+        }
+    }
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .with_context(|| format!("Failed to append to {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_lines_keep_the_whole_stderr() {
+        let mut log = CommandLog::default();
+        log.log_success("op vault list", Some(2));
+        log.log_failure("op item get x", "[ERROR] first\nsecond\n");
+
+        assert_eq!(
+            log.full_lines(),
+            [
+                "✗ op item get x",
+                "    [ERROR] first",
+                "    second",
+                "✓ op vault list (2 items)",
+            ]
+        );
+        assert_eq!(log.recent(1)[0].display(), "✗ op item get x: [ERROR] first");
+    }
+
+    #[test]
+    fn toasts_the_newest_entry_for_a_few_seconds() {
+        let mut log = CommandLog::default();
+        assert!(log.toast(Instant::now()).is_none());
+
+        log.log_failure("op item get x", "[ERROR] gone");
+        let now = Instant::now();
+
+        assert_eq!(
+            log.toast(now).map(CommandLogEntry::display).as_deref(),
+            Some("✗ op item get x: [ERROR] gone")
+        );
+        assert!(log.toast(now + TOAST_DURATION).is_none());
+    }
+
+    #[test]
+    fn persisted_entries_are_timestamped() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = log_file_path(dir.path());
+        let entry = CommandLogEntry {
+            command: "op item get x".to_string(),
+            status: CommandStatus::Failed {
+                stderr: "first\nsecond".to_string(),
+            },
+        };
+
+        append_entry(&path, &entry, std::time::UNIX_EPOCH).unwrap();
+        append_entry(&path, &entry, std::time::UNIX_EPOCH).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let indent = " ".repeat("1970-01-01T00:00:00Z ".len());
+        let expected = format!(
+            "1970-01-01T00:00:00Z ✗ op item get x\n{indent}    first\n{indent}    second\n"
+        );
+        assert_eq!(contents, expected.repeat(2));
+    }
+}
@@ -0,0 +1,408 @@
+use anyhow::{Context, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::{
+    DefaultTerminal, Frame,
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, BorderType, Borders, Cell, Paragraph, Row, Table, TableState},
+};
+use std::path::Path;
+
+use crate::app::{InjectVarConfig, OpLoadConfig};
+use crate::policy::Policy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    Var,
+    Template,
+}
+
+impl EntryKind {
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Var => "var",
+            Self::Template => "template",
+        }
+    }
+}
+
+/// One entry from the imported config that differs from the local one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeEntry {
+    pub kind: EntryKind,
+    pub key: String,
+    pub mine: Option<String>,
+    pub theirs: String,
+    pub take_theirs: bool,
+}
+
+impl MergeEntry {
+    pub const fn is_conflict(&self) -> bool {
+        self.mine.is_some()
+    }
+
+    const fn choice_label(&self) -> &'static str {
+        match (self.take_theirs, self.is_conflict()) {
+            (true, true) => "theirs",
+            (true, false) => "add",
+            (false, true) => "mine",
+            (false, false) => "skip",
+        }
+    }
+}
+
+pub fn load_import(path: &Path) -> Result<OpLoadConfig> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+fn describe_var(var: &InjectVarConfig) -> String {
+    match &var.prefix {
+        Some(prefix) => format!("{} ({}, prefix {prefix})", var.op_reference, var.account_id),
+        None => format!("{} ({})", var.op_reference, var.account_id),
+    }
+}
+
+/// Entries in `theirs` that are missing from or differ in `mine`. New
+/// entries default to being added; conflicts default to keeping mine.
+pub fn plan_merge(mine: &OpLoadConfig, theirs: &OpLoadConfig) -> Vec<MergeEntry> {
+    let mut entries = Vec::new();
+
+    for (name, var) in &theirs.inject_vars {
+        let existing = mine.inject_vars.get(name).map(describe_var);
+        let incoming = describe_var(var);
+        if existing.as_ref() != Some(&incoming) {
+            entries.push(MergeEntry {
+                kind: EntryKind::Var,
+                key: name.clone(),
+                take_theirs: existing.is_none(),
+                mine: existing,
+                theirs: incoming,
+            });
+        }
+    }
+
+    for (path, template) in &theirs.templated_files {
+        let existing = mine
+            .templated_files
+            .get(path)
+            .map(|t| t.template_name.clone());
+        if existing.as_ref() != Some(&template.template_name) {
+            entries.push(MergeEntry {
+                kind: EntryKind::Template,
+                key: path.clone(),
+                take_theirs: existing.is_none(),
+                mine: existing,
+                theirs: template.template_name.clone(),
+            });
+        }
+    }
+
+    entries.sort_by(|a, b| {
+        b.is_conflict()
+            .cmp(&a.is_conflict())
+            .then_with(|| a.kind.label().cmp(b.kind.label()))
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    entries
+}
+
+/// Copies the chosen entries from `theirs` into `mine`. Returns how many
+/// were taken.
+pub fn apply_merge(
+    mine: &mut OpLoadConfig,
+    theirs: &OpLoadConfig,
+    entries: &[MergeEntry],
+) -> usize {
+    let mut taken = 0;
+    for entry in entries.iter().filter(|entry| entry.take_theirs) {
+        match entry.kind {
+            EntryKind::Var => {
+                if let Some(var) = theirs.inject_vars.get(&entry.key) {
+                    mine.inject_vars.insert(entry.key.clone(), var.clone());
+                    taken += 1;
+                }
+            }
+            EntryKind::Template => {
+                if let Some(template) = theirs.templated_files.get(&entry.key) {
+                    mine.templated_files
+                        .insert(entry.key.clone(), template.clone());
+                    taken += 1;
+                }
+            }
+        }
+    }
+    taken
+}
+
+/// Refuses a merged config that breaks `policy`, listing each violation,
+/// so an import can't bring in mappings the TUI would refuse to save.
+pub fn ensure_within_policy(policy: &Policy, merged: &OpLoadConfig) -> Result<()> {
+    let violations = policy.check_config(merged);
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    let listed: Vec<String> = violations.iter().map(ToString::to_string).collect();
+    anyhow::bail!(
+        "Import would break policy; config unchanged:\n{}",
+        listed.join("\n")
+    )
+}
+
+/// The merge screen's state. `finished` is `Some(true)` once the user
+/// applies the merge and `Some(false)` if they cancel.
+pub struct MergeScreen {
+    pub entries: Vec<MergeEntry>,
+    pub table_state: TableState,
+    pub finished: Option<bool>,
+}
+
+impl MergeScreen {
+    pub fn new(entries: Vec<MergeEntry>) -> Self {
+        let mut table_state = TableState::default();
+        if !entries.is_empty() {
+            table_state.select(Some(0));
+        }
+        Self {
+            entries,
+            table_state,
+            finished: None,
+        }
+    }
+
+    fn selected_mut(&mut self) -> Option<&mut MergeEntry> {
+        self.table_state
+            .selected()
+            .and_then(|idx| self.entries.get_mut(idx))
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) {
+        let len = self.entries.len();
+        match code {
+            KeyCode::Down | KeyCode::Char('j') if len > 0 => {
+                let idx = self.table_state.selected().map_or(0, |idx| (idx + 1) % len);
+                self.table_state.select(Some(idx));
+            }
+            KeyCode::Up | KeyCode::Char('k') if len > 0 => {
+                let idx = self
+                    .table_state
+                    .selected()
+                    .map_or(0, |idx| (idx + len - 1) % len);
+                self.table_state.select(Some(idx));
+            }
+            KeyCode::Char(' ') | KeyCode::Tab => {
+                if let Some(entry) = self.selected_mut() {
+                    entry.take_theirs = !entry.take_theirs;
+                }
+            }
+            KeyCode::Char('t') => {
+                if let Some(entry) = self.selected_mut() {
+                    entry.take_theirs = true;
+                }
+            }
+            KeyCode::Char('m' | 's') => {
+                if let Some(entry) = self.selected_mut() {
+                    entry.take_theirs = false;
+                }
+            }
+            KeyCode::Enter => self.finished = Some(true),
+            KeyCode::Esc | KeyCode::Char('q') => self.finished = Some(false),
+            _ => {}
+        }
+    }
+}
+
+/// Lets the user pick entries in a full-screen table. Returns the entries
+/// with their choices, or `None` if the import was cancelled.
+pub fn run_merge_screen(entries: Vec<MergeEntry>) -> Result<Option<Vec<MergeEntry>>> {
+    let mut screen = MergeScreen::new(entries);
+    ratatui::run(|terminal| run_screen(terminal, &mut screen))?;
+    Ok((screen.finished == Some(true)).then_some(screen.entries))
+}
+
+fn run_screen(terminal: &mut DefaultTerminal, screen: &mut MergeScreen) -> Result<()> {
+    while screen.finished.is_none() {
+        terminal.draw(|frame| render(frame, screen))?;
+        if let Event::Key(key) = event::read().context("Failed to read terminal event")?
+            && key.kind == KeyEventKind::Press
+        {
+            screen.handle_key(key.code);
+        }
+    }
+    Ok(())
+}
+
+fn render(frame: &mut Frame, screen: &mut MergeScreen) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Fill(1), Constraint::Length(1)])
+        .split(frame.area());
+
+    let rows = screen.entries.iter().map(|entry| {
+        let choice_style = if entry.take_theirs {
+            Style::default().fg(Color::Green)
+        } else {
+            Style::default().fg(Color::DarkGray)
+        };
+        let mine_style = if entry.take_theirs {
+            Style::default().fg(Color::DarkGray)
+        } else {
+            Style::default()
+        };
+        Row::new(vec![
+            Cell::from(entry.choice_label()).style(choice_style),
+            Cell::from(entry.kind.label()),
+            Cell::from(entry.key.as_str()),
+            Cell::from(entry.mine.as_deref().unwrap_or("-")).style(mine_style),
+            Cell::from(entry.theirs.as_str()),
+        ])
+    });
+
+    let conflicts = screen.entries.iter().filter(|e| e.is_conflict()).count();
+    let block = Block::default()
+        .title(format!(
+            " Import config: {} entries, {conflicts} conflicting ",
+            screen.entries.len()
+        ))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Cyan));
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(7),
+            Constraint::Length(9),
+            Constraint::Percentage(25),
+            Constraint::Percentage(35),
+            Constraint::Percentage(35),
+        ],
+    )
+    .header(
+        Row::new(vec!["USE", "KIND", "NAME", "MINE", "THEIRS"])
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+    )
+    .block(block)
+    .row_highlight_style(
+        Style::default()
+            .bg(Color::DarkGray)
+            .add_modifier(Modifier::BOLD),
+    )
+    .highlight_symbol("> ");
+
+    frame.render_stateful_widget(table, chunks[0], &mut screen.table_state);
+
+    let help = Paragraph::new(Line::from(
+        "[j/k] Move  [Space] Toggle  [t] Theirs  [m/s] Mine/Skip  [Enter] Apply  [Esc] Cancel ",
+    ))
+    .style(Style::default().fg(Color::DarkGray));
+    frame.render_widget(help, chunks[1]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::TemplatedFile;
+
+    fn var(reference: &str) -> InjectVarConfig {
+        InjectVarConfig {
+            account_id: "acct".to_string(),
+            op_reference: reference.to_string(),
+            prefix: None,
+        }
+    }
+
+    fn configs() -> (OpLoadConfig, OpLoadConfig) {
+        let mut mine = OpLoadConfig::default();
+        mine.inject_vars.insert("SAME".into(), var("op://v/same/f"));
+        mine.inject_vars
+            .insert("TOKEN".into(), var("op://v/mine/f"));
+
+        let mut theirs = OpLoadConfig::default();
+        theirs
+            .inject_vars
+            .insert("SAME".into(), var("op://v/same/f"));
+        theirs
+            .inject_vars
+            .insert("TOKEN".into(), var("op://v/theirs/f"));
+        theirs.inject_vars.insert("NEW".into(), var("op://v/new/f"));
+        theirs.templated_files.insert(
+            "/home/teammate/.npmrc".into(),
+            TemplatedFile {
+                template_name: "npmrc".into(),
+                vars: None,
+                symlink: false,
+            },
+        );
+        (mine, theirs)
+    }
+
+    #[test]
+    fn conflicts_come_first_and_keep_mine() {
+        let (mine, theirs) = configs();
+        let entries = plan_merge(&mine, &theirs);
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].key, "TOKEN");
+        assert!(entries[0].is_conflict());
+        assert!(!entries[0].take_theirs);
+        assert!(entries[1..].iter().all(|entry| entry.take_theirs));
+    }
+
+    #[test]
+    fn applies_only_chosen_entries() {
+        let (mut mine, theirs) = configs();
+        let mut screen = MergeScreen::new(plan_merge(&mine, &theirs));
+
+        screen.handle_key(KeyCode::Char('t'));
+        screen.handle_key(KeyCode::Down);
+        screen.handle_key(KeyCode::Char('s'));
+        screen.handle_key(KeyCode::Enter);
+
+        assert_eq!(screen.finished, Some(true));
+        let taken = apply_merge(&mut mine, &theirs, &screen.entries);
+        assert_eq!(taken, 2);
+        assert_eq!(mine.inject_vars["TOKEN"].op_reference, "op://v/theirs/f");
+        assert!(mine.inject_vars.contains_key("NEW"));
+        assert!(mine.templated_files.is_empty());
+    }
+
+    #[test]
+    fn prefix_changes_count_as_conflicts() {
+        let (mine, mut theirs) = configs();
+        theirs.inject_vars.insert(
+            "SAME".into(),
+            InjectVarConfig {
+                prefix: Some("PROD_".to_string()),
+                ..var("op://v/same/f")
+            },
+        );
+
+        let entries = plan_merge(&mine, &theirs);
+
+        let same = entries.iter().find(|entry| entry.key == "SAME").unwrap();
+        assert!(same.is_conflict());
+        assert!(same.theirs.contains("prefix PROD_"), "{}", same.theirs);
+    }
+
+    #[test]
+    fn refuses_imports_that_break_policy() {
+        let (mut mine, theirs) = configs();
+        let entries = plan_merge(&mine, &theirs);
+        apply_merge(&mut mine, &theirs, &entries);
+        let policy = Policy {
+            forbidden_var_patterns: vec!["NEW".to_string()],
+            ..Policy::default()
+        };
+
+        let err = ensure_within_policy(&policy, &mine)
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("NEW: variable name matches"), "{err}");
+        assert!(ensure_within_policy(&Policy::default(), &mine).is_ok());
+    }
+}
@@ -0,0 +1,135 @@
+use anyhow::{Context, Result, bail};
+use std::path::{Path, PathBuf};
+
+/// A rewrite of the raw config table from an older layout. Returns whether
+/// anything changed.
+struct Migration {
+    description: &'static str,
+    apply: fn(&mut toml::Table) -> Result<bool>,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    description: "Convert legacy `inject_vars` strings to { account_id, op_reference } tables using `default_account_id`",
+    apply: migrate_legacy_inject_vars,
+}];
+
+/// The result of running every migration over a config file.
+#[derive(Debug)]
+pub struct MigrationPlan {
+    pub original: String,
+    pub migrated: String,
+    pub applied: Vec<&'static str>,
+}
+
+impl MigrationPlan {
+    pub const fn is_empty(&self) -> bool {
+        self.applied.is_empty()
+    }
+}
+
+pub fn plan_migrations(original: &str) -> Result<MigrationPlan> {
+    let mut table: toml::Table = original.parse().context("Failed to parse config TOML")?;
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS {
+        if (migration.apply)(&mut table)? {
+            applied.push(migration.description);
+        }
+    }
+
+    let migrated = if applied.is_empty() {
+        original.to_string()
+    } else {
+        toml::to_string(&table).context("Failed to serialize migrated config")?
+    };
+
+    Ok(MigrationPlan {
+        original: original.to_string(),
+        migrated,
+        applied,
+    })
+}
+
+/// Where the pre-migration config is copied before it's rewritten.
+pub fn backup_path(config_path: &Path, timestamp: u64) -> PathBuf {
+    let mut name = config_path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".bak-{timestamp}"));
+    config_path.with_file_name(name)
+}
+
+/// Early versions mapped `NAME = "op://..."` with no account. Those mappings
+/// belong to the default account, which must be set to migrate them.
+fn migrate_legacy_inject_vars(table: &mut toml::Table) -> Result<bool> {
+    let Some(toml::Value::Table(inject_vars)) = table.get("inject_vars") else {
+        return Ok(false);
+    };
+    if !inject_vars.values().any(toml::Value::is_str) {
+        return Ok(false);
+    }
+
+    let Some(account_id) = table
+        .get("default_account_id")
+        .and_then(toml::Value::as_str)
+        .map(str::to_string)
+    else {
+        bail!(
+            "Legacy inject_vars entries need an account, but no default_account_id is set. Set one with `op-loader config set -k default_account_id <id>` and try again."
+        );
+    };
+
+    let Some(toml::Value::Table(inject_vars)) = table.get_mut("inject_vars") else {
+        return Ok(false);
+    };
+    for (_, value) in inject_vars.iter_mut() {
+        if let toml::Value::String(reference) = value {
+            let mut var = toml::Table::new();
+            var.insert("account_id".into(), account_id.clone().into());
+            var.insert("op_reference".into(), std::mem::take(reference).into());
+            *value = toml::Value::Table(var);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_legacy_inject_vars() {
+        let plan = plan_migrations(
+            "default_account_id = \"acct\"\n\n[inject_vars]\nTOKEN = \"op://v/i/f\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(plan.applied.len(), 1);
+        let config: crate::app::OpLoadConfig = toml::from_str(&plan.migrated).unwrap();
+        assert_eq!(config.inject_vars["TOKEN"].account_id, "acct");
+        assert_eq!(config.inject_vars["TOKEN"].op_reference, "op://v/i/f");
+    }
+
+    #[test]
+    fn current_config_is_left_alone() {
+        let original =
+            "[inject_vars.TOKEN]\naccount_id = \"acct\"\nop_reference = \"op://v/i/f\"\n";
+        let plan = plan_migrations(original).unwrap();
+
+        assert!(plan.is_empty());
+        assert_eq!(plan.migrated, original);
+    }
+
+    #[test]
+    fn legacy_vars_need_default_account() {
+        let err = plan_migrations("[inject_vars]\nTOKEN = \"op://v/i/f\"\n").unwrap_err();
+
+        assert!(err.to_string().contains("default_account_id"));
+    }
+
+    #[test]
+    fn backup_sits_next_to_config() {
+        assert_eq!(
+            backup_path(Path::new("/cfg/default-config.toml"), 42),
+            PathBuf::from("/cfg/default-config.toml.bak-42")
+        );
+    }
+}
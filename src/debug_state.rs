@@ -0,0 +1,283 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::app::{
+    App, ClipboardConfig, Modal, NotificationConfig, OpLoadConfig, TemplateHistoryConfig, TuiConfig,
+};
+
+/// A snapshot of op-loader's state for bug reports. It holds counts,
+/// selections, settings, and errors, but never secret values, `op://`
+/// references, or template contents.
+#[derive(Debug, Serialize)]
+pub struct StateSnapshot {
+    pub op_loader_version: &'static str,
+    pub os: &'static str,
+    pub generated_at: u64,
+    pub config: Option<ConfigSnapshot>,
+    pub caches: Vec<CacheSnapshot>,
+    /// Accounts whose background refreshes are backing off.
+    pub refresh_backoff_accounts: usize,
+    pub tui: Option<TuiSnapshot>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigSnapshot {
+    pub default_account_id: Option<String>,
+    pub vars_per_account: BTreeMap<String, usize>,
+    pub default_vaults: usize,
+    pub templates: usize,
+    pub allowed_sync_paths: usize,
+    pub cache_max_size: Option<String>,
+    pub notifications: NotificationConfig,
+    pub template_history: TemplateHistoryConfig,
+    pub tui: TuiConfig,
+    pub clipboard: ClipboardConfig,
+    pub telemetry_enabled: bool,
+    pub strict_permissions: bool,
+}
+
+impl ConfigSnapshot {
+    pub fn new(config: &OpLoadConfig) -> Self {
+        let mut vars_per_account = BTreeMap::new();
+        for var in config.inject_vars.values() {
+            *vars_per_account.entry(var.account_id.clone()).or_default() += 1;
+        }
+
+        Self {
+            default_account_id: config.default_account_id.clone(),
+            vars_per_account,
+            default_vaults: config.default_vault_per_account.len(),
+            templates: config.templated_files.len(),
+            allowed_sync_paths: config.allowed_sync_paths.len(),
+            cache_max_size: config.cache.max_size.clone(),
+            notifications: config.notifications.clone(),
+            template_history: config.template_history.clone(),
+            tui: config.tui.clone(),
+            clipboard: config.clipboard.clone(),
+            telemetry_enabled: config.telemetry.enabled,
+            strict_permissions: config.security.strict_permissions,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheSnapshot {
+    pub kind: &'static str,
+    pub account: String,
+    pub size_bytes: u64,
+    pub age_secs: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TuiSnapshot {
+    pub focused_panel: String,
+    pub modal: Option<&'static str>,
+    pub accounts: usize,
+    pub vaults: usize,
+    pub vault_items: usize,
+    pub filtered_items: usize,
+    pub managed_vars: usize,
+    pub recent_items: usize,
+    pub selected_account_idx: Option<usize>,
+    pub selected_vault_idx: Option<usize>,
+    pub selected_vault_item_idx: Option<usize>,
+    pub detail_fields: Option<usize>,
+    pub search_active: bool,
+    pub search_query_len: usize,
+    pub listings_loading: usize,
+    pub item_details_loading: bool,
+    pub secrets_hidden: bool,
+    pub value_revealed: bool,
+    pub clipboard_clear_pending: bool,
+    pub accounts_backing_off: usize,
+    pub error_message: Option<String>,
+    /// The command log lines currently on screen, newest last.
+    pub recent_commands: Vec<String>,
+}
+
+const RECENT_COMMANDS: usize = 20;
+
+/// Replaces each `op://vault/item/field` reference with `op://<redacted>`,
+/// since vault and item names can say more than a bug report should.
+fn redact_references(text: &str) -> String {
+    let mut parts = text.split("op://");
+    let mut redacted = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        redacted.push_str("op://<redacted>");
+        let end = part
+            .find(|c: char| c.is_whitespace() || matches!(c, '\'' | '"' | '`' | ')' | ',' | ':'))
+            .unwrap_or(part.len());
+        redacted.push_str(&part[end..]);
+    }
+    redacted
+}
+
+const fn modal_name(modal: &Modal) -> &'static str {
+    match modal {
+        Modal::EnvVar { .. } => "env_var",
+        Modal::VarCollision { .. } => "var_collision",
+        Modal::VarDeleteConfirm { .. } => "var_delete_confirm",
+        Modal::VaultCreate { .. } => "vault_create",
+        Modal::ItemCreate { .. } => "item_create",
+        Modal::FieldEdit { .. } => "field_edit",
+        Modal::PasswordGenerator { .. } => "password_generator",
+        Modal::VaultDetails { .. } => "vault_details",
+        Modal::ItemDeleteConfirm { .. } => "item_delete_confirm",
+        Modal::ErrorDetails { .. } => "error_details",
+        Modal::Help { .. } => "help",
+        Modal::CommandLog { .. } => "command_log",
+        Modal::SignIn { .. } => "signin",
+        Modal::PlaceFile { .. } => "place_file",
+        Modal::QuitConfirm { .. } => "quit_confirm",
+        Modal::EnvPreview { .. } => "env_preview",
+        Modal::BulkMap { .. } => "bulk_map",
+    }
+}
+
+impl TuiSnapshot {
+    pub fn new(app: &App) -> Self {
+        Self {
+            focused_panel: format!("{:?}", app.focused_panel),
+            modal: app.modal.as_ref().map(modal_name),
+            accounts: app.accounts.len(),
+            vaults: app.vaults.len(),
+            vault_items: app.vault_items.len(),
+            filtered_items: app.filtered_item_indices.len(),
+            managed_vars: app.managed_vars.len(),
+            recent_items: app.recent_items.items.len(),
+            selected_account_idx: app.selected_account_idx,
+            selected_vault_idx: app.selected_vault_idx,
+            selected_vault_item_idx: app.selected_vault_item_idx,
+            detail_fields: app
+                .selected_item_details
+                .as_ref()
+                .map(|details| details.fields.len()),
+            search_active: app.search_active,
+            search_query_len: app.search_query.chars().count(),
+            listings_loading: app.loading_listing_count(),
+            item_details_loading: app.item_details_loading.is_some(),
+            secrets_hidden: app.secrets_hidden,
+            value_revealed: app.revealed_field.is_some(),
+            clipboard_clear_pending: app.clipboard_clear.is_some(),
+            accounts_backing_off: app.account_backoff.failing_accounts(),
+            error_message: app.error_message.as_deref().map(redact_references),
+            recent_commands: app
+                .command_log
+                .recent(RECENT_COMMANDS)
+                .iter()
+                .map(|entry| redact_references(&entry.display()))
+                .collect(),
+        }
+    }
+}
+
+/// Collects the snapshot. `app` is only available from the TUI.
+pub fn collect(app: Option<&App>) -> StateSnapshot {
+    let config = match app.and_then(|app| app.config.as_ref()) {
+        Some(config) => Some(ConfigSnapshot::new(config)),
+        None => confy::load::<OpLoadConfig>("op_loader", None)
+            .ok()
+            .map(|config| ConfigSnapshot::new(&config)),
+    };
+
+    let now = SystemTime::now();
+    let cache_root = crate::cache::cache_dir().ok();
+    let caches = cache_root
+        .as_deref()
+        .and_then(|dir| crate::cache::list_cache_files(dir).ok())
+        .map(|files| {
+            crate::cli::cache_status_rows(&files, now)
+                .into_iter()
+                .map(|(kind, account, size_bytes, age_secs)| CacheSnapshot {
+                    kind,
+                    account,
+                    size_bytes,
+                    age_secs,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let refresh_backoff_accounts = cache_root.as_deref().map_or(0, |dir| {
+        crate::backoff::load_backoff(dir).failing_accounts()
+    });
+
+    StateSnapshot {
+        op_loader_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        generated_at: now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        config,
+        caches,
+        refresh_backoff_accounts,
+        tui: app.map(TuiSnapshot::new),
+    }
+}
+
+/// Where a snapshot goes when no path is given: the current directory, so
+/// it's easy to find and attach.
+pub fn default_path(dir: &Path, generated_at: u64) -> PathBuf {
+    dir.join(format!("op-loader-state-{generated_at}.json"))
+}
+
+pub fn write_snapshot(snapshot: &StateSnapshot, out: Option<PathBuf>) -> Result<PathBuf> {
+    let path = match out {
+        Some(path) => path,
+        None => default_path(
+            &std::env::current_dir().context("Failed to get current directory")?,
+            snapshot.generated_at,
+        ),
+    };
+    let json = serde_json::to_vec_pretty(snapshot).context("Failed to serialize state")?;
+    std::fs::write(&path, json)
+        .with_context(|| format!("Failed to write state to {}", path.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{InjectVarConfig, RevealedField};
+
+    #[test]
+    fn snapshot_leaves_out_references_and_values() {
+        let mut app = App::new();
+        let mut config = OpLoadConfig::default();
+        config.inject_vars.insert(
+            "TOKEN".to_string(),
+            InjectVarConfig {
+                account_id: "acct".to_string(),
+                op_reference: "op://Private/GitHub/token".to_string(),
+                prefix: None,
+            },
+        );
+        app.config = Some(config);
+        app.revealed_field = Some(RevealedField {
+            reference: "op://Private/GitHub/password".to_string(),
+            value: "hunter22".to_string(),
+            until: std::time::Instant::now(),
+        });
+        app.search_query = "github".to_string();
+        app.command_log.log_failure(
+            "op read --no-newline op://Private/GitHub/password",
+            "not signed in",
+        );
+
+        let snapshot = collect(Some(&app));
+        let json = serde_json::to_string(&snapshot).unwrap();
+
+        assert!(!json.contains("Private"));
+        assert!(!json.contains("hunter22"));
+        assert!(!json.contains("github"));
+        let config = snapshot.config.unwrap();
+        assert_eq!(config.vars_per_account["acct"], 1);
+        let tui = snapshot.tui.unwrap();
+        assert!(tui.value_revealed);
+        assert_eq!(tui.search_query_len, 6);
+        assert_eq!(
+            tui.recent_commands,
+            ["✗ op read --no-newline op://<redacted>: not signed in"]
+        );
+    }
+}
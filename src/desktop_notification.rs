@@ -0,0 +1,119 @@
+use std::process::{Command, Stdio};
+
+use crate::app::OpLoadConfig;
+
+/// Shows a desktop notification when `notifications.desktop` is enabled.
+/// Background tasks have no terminal to report to, so failures to notify are
+/// only logged.
+pub fn notify(title: &str, body: &str) {
+    let enabled = confy::load::<OpLoadConfig>("op_loader", None)
+        .is_ok_and(|config| config.notifications.desktop);
+    if !enabled {
+        return;
+    }
+
+    let Some((program, args)) = notifier_command(title, body) else {
+        log::debug!("Desktop notifications are not supported on this platform");
+        return;
+    };
+
+    let result = Command::new(program)
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match result {
+        Ok(status) if status.success() => {}
+        Ok(status) => log::debug!("`{program}` exited with {status}"),
+        Err(err) => log::debug!("Failed to run `{program}`: {err}"),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn notifier_command(title: &str, body: &str) -> Option<(&'static str, Vec<String>)> {
+    if on_path("terminal-notifier") {
+        Some(("terminal-notifier", terminal_notifier_args(title, body)))
+    } else {
+        Some(("osascript", osascript_args(title, body)))
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn notifier_command(title: &str, body: &str) -> Option<(&'static str, Vec<String>)> {
+    Some(("notify-send", notify_send_args(title, body)))
+}
+
+#[cfg(not(unix))]
+fn notifier_command(_title: &str, _body: &str) -> Option<(&'static str, Vec<String>)> {
+    None
+}
+
+#[cfg(target_os = "macos")]
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn terminal_notifier_args(title: &str, body: &str) -> Vec<String> {
+    vec![
+        "-title".to_string(),
+        title.to_string(),
+        "-message".to_string(),
+        body.to_string(),
+        "-group".to_string(),
+        "op-loader".to_string(),
+    ]
+}
+
+/// AppleScript string literals only need backslashes and quotes escaped.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn osascript_args(title: &str, body: &str) -> Vec<String> {
+    let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+    vec![
+        "-e".to_string(),
+        format!(
+            "display notification {} with title {}",
+            quote(body),
+            quote(title)
+        ),
+    ]
+}
+
+#[cfg_attr(any(target_os = "macos", not(unix)), allow(dead_code))]
+fn notify_send_args(title: &str, body: &str) -> Vec<String> {
+    vec![
+        "--app-name=op-loader".to_string(),
+        "--".to_string(),
+        title.to_string(),
+        body.to_string(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osascript_escapes_quotes_and_backslashes() {
+        let args = osascript_args("op-loader", r#"Failed: "op" said \n"#);
+
+        assert_eq!(
+            args,
+            vec![
+                "-e".to_string(),
+                r#"display notification "Failed: \"op\" said \\n" with title "op-loader""#
+                    .to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn notify_send_treats_text_as_arguments() {
+        let args = notify_send_args("-title", "--urgency=critical");
+
+        assert_eq!(args[1], "--");
+        assert_eq!(&args[2..], ["-title", "--urgency=critical"]);
+    }
+}
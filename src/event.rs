@@ -1,8 +1,10 @@
 use anyhow::{Context, Result};
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use ratatui::widgets::ListState;
 
-use crate::app::{App, FocusedPanel};
+use crate::action::Action;
+use crate::app::{App, BulkMapFocus, EnvVarField, FocusedPanel, ItemCreateFocus, Modal};
+use crate::text_input::pop_grapheme;
 
 enum NavAction {
     Up,
@@ -14,6 +16,7 @@ enum NavAction {
     PanelTwo,
     PanelFour,
     PanelVars,
+    PanelRecent,
 }
 
 impl NavAction {
@@ -28,6 +31,38 @@ impl NavAction {
             KeyCode::Char('2') => Some(Self::PanelTwo),
             KeyCode::Char('3') => Some(Self::PanelFour),
             KeyCode::Char('v' | 'V') => Some(Self::PanelVars),
+            KeyCode::Char('u' | 'U') => Some(Self::PanelRecent),
+            _ => None,
+        }
+    }
+}
+
+/// Moves through the focused list by more than one row. `g` on its own only
+/// arms [`App::pending_g`] for `gg`.
+enum PageAction {
+    First,
+    Last,
+    HalfPageDown,
+    HalfPageUp,
+    PageDown,
+    PageUp,
+}
+
+/// Lists don't know their rendered height, so pages are a fixed size.
+const PAGE_ROWS: isize = 20;
+
+impl PageAction {
+    fn from_key(key: KeyEvent, pending_g: bool) -> Option<Self> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('g') if pending_g && !ctrl => Some(Self::First),
+            KeyCode::Char('G') => Some(Self::Last),
+            KeyCode::Home => Some(Self::First),
+            KeyCode::End => Some(Self::Last),
+            KeyCode::Char('d') if ctrl => Some(Self::HalfPageDown),
+            KeyCode::Char('u') if ctrl => Some(Self::HalfPageUp),
+            KeyCode::PageDown => Some(Self::PageDown),
+            KeyCode::PageUp => Some(Self::PageUp),
             _ => None,
         }
     }
@@ -37,7 +72,11 @@ impl NavAction {
 enum VarsAction {
     Toggle,
     Copy,
+    Edit,
+    Test,
     Delete,
+    Preview,
+    Undo,
 }
 
 impl VarsAction {
@@ -45,7 +84,11 @@ impl VarsAction {
         match code {
             KeyCode::Char(' ') => Some(Self::Toggle),
             KeyCode::Char('c' | 'C') => Some(Self::Copy),
+            KeyCode::Char('e' | 'E') => Some(Self::Edit),
+            KeyCode::Char('t' | 'T') => Some(Self::Test),
             KeyCode::Char('d' | 'D') => Some(Self::Delete),
+            KeyCode::Char('p' | 'P') => Some(Self::Preview),
+            KeyCode::Char('u' | 'U') => Some(Self::Undo),
             _ => None,
         }
     }
@@ -73,11 +116,18 @@ fn handle_vars_action(app: &mut App, action: VarsAction) {
             }
 
             vars.sort();
-            let payload = vars.join(", ");
-
-            match copy_to_clipboard(&payload) {
-                Ok(()) => app.command_log.log_success("Vars copied", None),
-                Err(err) => app.command_log.log_failure("Vars copy", err.to_string()),
+            app.apply(Action::CopyVarNames { vars });
+        }
+        VarsAction::Edit => {
+            if let Some(var) = app.selected_managed_var() {
+                let var = var.clone();
+                app.open_var_edit_modal(&var);
+            }
+        }
+        VarsAction::Test => {
+            if let Some(var) = app.selected_managed_var() {
+                let var = var.clone();
+                app.apply(Action::TestVar { var });
             }
         }
         VarsAction::Delete => {
@@ -97,47 +147,224 @@ fn handle_vars_action(app: &mut App, action: VarsAction) {
             vars.sort();
             app.open_vars_delete_modal(vars);
         }
+        VarsAction::Preview => app.apply(Action::PreviewEnv),
+        VarsAction::Undo => app.apply(Action::UndoVarDeletion),
+    }
+}
+
+#[derive(Copy, Clone)]
+enum VaultsAction {
+    Create,
+    Details,
+}
+
+impl VaultsAction {
+    const fn from_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('n' | 'N') => Some(Self::Create),
+            KeyCode::Char('i' | 'I') => Some(Self::Details),
+            _ => None,
+        }
+    }
+}
+
+fn handle_vaults_action(app: &mut App, action: VaultsAction) {
+    match action {
+        VaultsAction::Create => {
+            if app.selected_account().is_none() {
+                app.command_log
+                    .log_failure("Vault create", "No account selected".to_string());
+                return;
+            }
+            app.open_vault_create_modal();
+        }
+        VaultsAction::Details => {
+            let Some(vault_id) = app
+                .vault_list_state
+                .selected()
+                .and_then(|idx| app.vaults.get(idx))
+                .map(|v| v.id.clone())
+            else {
+                app.command_log
+                    .log_failure("Vault details", "No vault selected".to_string());
+                return;
+            };
+
+            app.apply(Action::ShowVaultDetails { vault_id });
+        }
     }
 }
 
-fn copy_to_clipboard(value: &str) -> Result<()> {
-    use std::process::{Command, Stdio};
+#[derive(Copy, Clone)]
+enum ItemsAction {
+    Archive,
+    Delete,
+    CopyCommand,
+    Mark,
+    BulkMap,
+    Create,
+    PlaceFile,
+}
+
+impl ItemsAction {
+    const fn from_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('a' | 'A') => Some(Self::Archive),
+            KeyCode::Char('x' | 'X') => Some(Self::Delete),
+            KeyCode::Char('y' | 'Y') => Some(Self::CopyCommand),
+            KeyCode::Char(' ') => Some(Self::Mark),
+            KeyCode::Char('b' | 'B') => Some(Self::BulkMap),
+            KeyCode::Char('n' | 'N') => Some(Self::Create),
+            KeyCode::Char('p' | 'P') => Some(Self::PlaceFile),
+            _ => None,
+        }
+    }
+}
 
-    let mut child = Command::new("pbcopy")
-        .stdin(Stdio::piped())
-        .spawn()
-        .context("Failed to launch pbcopy")?;
+fn handle_items_action(app: &mut App, action: ItemsAction) {
+    let archive = match action {
+        ItemsAction::CopyCommand => {
+            copy_op_command(app);
+            return;
+        }
+        ItemsAction::Mark => {
+            if let Some(item_id) = app.selected_list_item().map(|item| item.id.clone()) {
+                app.toggle_item_mark(&item_id);
+            }
+            return;
+        }
+        ItemsAction::BulkMap => {
+            app.open_bulk_map_modal();
+            return;
+        }
+        ItemsAction::Create => {
+            if app.selected_vault().is_some() {
+                app.open_item_create_modal();
+            } else {
+                app.command_log
+                    .log_failure("Item create", "No vault selected".to_string());
+            }
+            return;
+        }
+        ItemsAction::PlaceFile => {
+            if let Err(err) = app.open_place_file_modal() {
+                app.command_log.log_failure("Place file", err.to_string());
+            }
+            return;
+        }
+        ItemsAction::Archive => true,
+        ItemsAction::Delete => false,
+    };
+
+    let Some(item) = app.selected_list_item().cloned() else {
+        app.command_log
+            .log_failure("Item delete", "No item selected".to_string());
+        return;
+    };
+
+    app.open_item_delete_modal(&item, archive);
+}
+
+#[derive(Copy, Clone)]
+enum DetailsAction {
+    CopyCommand,
+    CopyReference,
+    CopyValue,
+    Reveal,
+    ToggleNotes,
+    Edit,
+}
+
+impl DetailsAction {
+    const fn from_key(code: KeyCode) -> Option<Self> {
+        match code {
+            KeyCode::Char('y') => Some(Self::CopyCommand),
+            KeyCode::Char('Y') => Some(Self::CopyReference),
+            KeyCode::Char('c' | 'C') => Some(Self::CopyValue),
+            KeyCode::Char('r') => Some(Self::Reveal),
+            KeyCode::Char('n' | 'N') => Some(Self::ToggleNotes),
+            KeyCode::Char('e' | 'E') => Some(Self::Edit),
+            _ => None,
+        }
+    }
+}
 
-    if let Some(mut stdin) = child.stdin.take() {
-        use std::io::Write;
-        stdin
-            .write_all(value.as_bytes())
-            .context("Failed to write to pbcopy")?;
+fn handle_details_action(app: &mut App, action: DetailsAction) {
+    match action {
+        DetailsAction::CopyCommand => copy_op_command(app),
+        DetailsAction::CopyReference => match app.highlighted_detail_field() {
+            Some(field) => {
+                let reference = field.reference.clone();
+                app.apply(Action::CopyReference { reference });
+            }
+            None => app
+                .command_log
+                .log_failure("Reference copy", "No field selected".to_string()),
+        },
+        DetailsAction::CopyValue => match app.highlighted_detail_field() {
+            Some(field) => {
+                let reference = field.reference.clone();
+                app.apply(Action::CopyFieldValue { reference });
+            }
+            None => app
+                .command_log
+                .log_failure("Field value copy", "No field selected".to_string()),
+        },
+        DetailsAction::ToggleNotes => app.notes_expanded = !app.notes_expanded,
+        DetailsAction::Edit => app.open_field_edit_modal(),
+        DetailsAction::Reveal => match app.highlighted_detail_field() {
+            Some(field) => {
+                let reference = field.reference.clone();
+                app.apply(Action::ToggleReveal { reference });
+            }
+            None => app
+                .command_log
+                .log_failure("Field value reveal", "No field selected".to_string()),
+        },
     }
+}
 
-    let status = child.wait().context("Failed to wait for pbcopy")?;
-    if status.success() {
-        Ok(())
-    } else {
-        anyhow::bail!("pbcopy exited with status {status}")
+fn copy_op_command(app: &mut App) {
+    match app.op_command_for_selection() {
+        Some(command) => app.apply(Action::CopyOpCommand { command }),
+        None => app
+            .command_log
+            .log_failure("op command copy", "Nothing selected".to_string()),
     }
 }
 
+/// How often the event loop wakes without input, so background listing
+/// refreshes are drawn promptly.
+const EVENT_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
 pub fn handle_events(app: &mut App) -> Result<()> {
-    if let Event::Key(key) = event::read().context("Failed to read keyboard event")?
-        && key.kind == KeyEventKind::Press
-    {
-        handle_key_press(app, key);
+    if !event::poll(EVENT_POLL_INTERVAL).context("Failed to poll for events")? {
+        return Ok(());
+    }
+
+    match event::read().context("Failed to read terminal event")? {
+        Event::Key(key) if key.kind == KeyEventKind::Press => handle_key_press(app, key),
+        Event::FocusLost => app.on_focus_lost(),
+        Event::FocusGained => app.on_focus_gained(),
+        _ => {}
     }
     Ok(())
 }
 
 #[allow(clippy::too_many_lines)]
 fn handle_key_press(app: &mut App, key: KeyEvent) {
+    app.flash_pending = false;
+
     if let Some(modal) = app.modal.clone() {
         match modal {
-            crate::app::Modal::EnvVar { .. } => match key.code {
+            Modal::EnvVar {
+                editing, prefix, ..
+            } => match key.code {
                 KeyCode::Esc => app.close_modal(),
+                KeyCode::Tab | KeyCode::BackTab => app.toggle_env_var_modal_field(),
+                KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    app.cycle_env_var_prefix();
+                }
                 KeyCode::Enter => {
                     let env_var_name = app.modal_env_var_name().unwrap_or("").to_string();
                     if env_var_name.is_empty() {
@@ -147,10 +374,21 @@ fn handle_key_press(app: &mut App, key: KeyEvent) {
                     }
 
                     let op_reference = match app.modal_field_reference() {
-                        Some(reference) => reference.to_string(),
+                        Some(reference) => reference.trim().to_string(),
                         None => return,
                     };
 
+                    if let Some(edit) = editing {
+                        app.apply(Action::UpdateVar {
+                            original_name: edit.original_name,
+                            env_var_name,
+                            account_id: edit.account_id,
+                            op_reference,
+                            prefix,
+                        });
+                        return;
+                    }
+
                     let account_id = if let Some(account) = app.selected_account() {
                         account.account_uuid.clone()
                     } else {
@@ -158,50 +396,316 @@ fn handle_key_press(app: &mut App, key: KeyEvent) {
                         return;
                     };
 
-                    match app.save_op_item_config(&env_var_name, &account_id, &op_reference) {
-                        Ok(()) => {
-                            app.command_log
-                                .log_success(format!("Saved {env_var_name} to config"), None);
-                            app.load_managed_vars();
-                            if app.managed_vars_list_state.selected().is_none()
-                                && !app.managed_vars.is_empty()
-                            {
-                                app.managed_vars_list_state.select(Some(0));
-                            }
-                            app.close_modal();
+                    if app.confirm_var_collision(&env_var_name, &account_id, &op_reference) {
+                        return;
+                    }
+                    app.apply(Action::SaveVar {
+                        env_var_name,
+                        account_id,
+                        op_reference,
+                        prefix,
+                    });
+                }
+                KeyCode::Backspace => {
+                    if let Some((input, _)) = app.env_var_modal_input_mut() {
+                        pop_grapheme(input);
+                        app.error_message = None;
+                    }
+                }
+                KeyCode::Char(c) => match app.env_var_modal_input_mut() {
+                    Some((reference, EnvVarField::Reference)) => {
+                        reference.push(c);
+                        app.error_message = None;
+                    }
+                    Some((env_var_name, EnvVarField::Name)) => {
+                        if c.is_ascii_alphanumeric() || c == '_' {
+                            env_var_name.push(c.to_ascii_uppercase());
+                            app.error_message = None;
+                        } else {
+                            app.error_message =
+                                Some("Env var names may only use A-Z, 0-9, and _".to_string());
                         }
-                        Err(e) => app.error_message = Some(e.to_string()),
                     }
+                    None => {}
+                },
+                _ => {}
+            },
+            Modal::VarCollision {
+                env_var_name,
+                account_id,
+                op_reference,
+                prefix,
+                ..
+            } => match key.code {
+                KeyCode::Char('o' | 'O') => app.apply(Action::SaveVar {
+                    env_var_name,
+                    account_id,
+                    op_reference,
+                    prefix,
+                }),
+                KeyCode::Esc | KeyCode::Char('r' | 'R') => app.rename_colliding_var(),
+                _ => {}
+            },
+            Modal::VarDeleteConfirm { .. } => match key.code {
+                KeyCode::Esc | KeyCode::Char('n' | 'N') => app.close_modal(),
+                KeyCode::Char('y' | 'Y') => {
+                    if let Some(vars) = app.modal_vars_delete_targets() {
+                        let vars = vars.to_vec();
+                        app.apply(Action::RemoveVars { vars });
+                    }
+                }
+                _ => {}
+            },
+            Modal::VaultCreate {
+                name, description, ..
+            } => match key.code {
+                KeyCode::Esc => app.close_modal(),
+                KeyCode::Tab | KeyCode::BackTab => app.toggle_vault_create_field(),
+                KeyCode::Enter => {
+                    let name = name.trim().to_string();
+                    if name.is_empty() {
+                        app.error_message = Some("Vault name cannot be empty".to_string());
+                        return;
+                    }
+
+                    app.apply(Action::CreateVault {
+                        name,
+                        description: description.trim().to_string(),
+                    });
                 }
                 KeyCode::Backspace => {
-                    if let Some(env_var_name) = app.modal_env_var_name_mut() {
-                        env_var_name.pop();
+                    if let Some(input) = app.modal_vault_create_input_mut() {
+                        pop_grapheme(input);
                         app.error_message = None;
                     }
                 }
                 KeyCode::Char(c) => {
-                    if (c.is_ascii_alphanumeric() || c == '_')
-                        && let Some(env_var_name) = app.modal_env_var_name_mut()
-                    {
-                        env_var_name.push(c.to_ascii_uppercase());
+                    if let Some(input) = app.modal_vault_create_input_mut() {
+                        input.push(c);
+                        app.error_message = None;
+                    }
+                }
+                _ => {}
+            },
+            Modal::FieldEdit {
+                value, confirming, ..
+            } => match key.code {
+                KeyCode::Esc => app.close_modal(),
+                KeyCode::Char('y' | 'Y') if confirming => app.apply(Action::EditField),
+                KeyCode::Char('n' | 'N') if confirming => app.set_field_edit_confirming(false),
+                KeyCode::Enter if !confirming => {
+                    if value.is_empty() {
+                        app.error_message = Some("New value cannot be empty".to_string());
+                    } else {
+                        app.set_field_edit_confirming(true);
+                    }
+                }
+                KeyCode::Backspace if !confirming => {
+                    if let Some(Modal::FieldEdit { value, .. }) = app.modal.as_mut() {
+                        pop_grapheme(value);
+                        app.error_message = None;
+                    }
+                }
+                KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    app.open_password_generator();
+                }
+                KeyCode::Char(c) if !confirming => {
+                    if let Some(Modal::FieldEdit { value, .. }) = app.modal.as_mut() {
+                        value.push(c);
                         app.error_message = None;
                     }
                 }
                 _ => {}
             },
-            crate::app::Modal::VarDeleteConfirm { .. } => match key.code {
+            Modal::PasswordGenerator { .. } => match key.code {
+                KeyCode::Esc => app.close_password_generator(false),
+                KeyCode::Enter => app.close_password_generator(true),
+                KeyCode::Left | KeyCode::Char('-') => {
+                    app.update_password_recipe(|recipe| recipe.adjust_length(-1));
+                }
+                KeyCode::Right | KeyCode::Char('+' | '=') => {
+                    app.update_password_recipe(|recipe| recipe.adjust_length(1));
+                }
+                KeyCode::Char('d' | 'D') => {
+                    app.update_password_recipe(|recipe| recipe.digits = !recipe.digits);
+                }
+                KeyCode::Char('s' | 'S') => {
+                    app.update_password_recipe(|recipe| recipe.symbols = !recipe.symbols);
+                }
+                KeyCode::Char('r' | 'R') => app.update_password_recipe(|_| {}),
+                _ => {}
+            },
+            Modal::ItemCreate { focus, .. } => match key.code {
+                KeyCode::Esc => app.close_modal(),
+                KeyCode::Tab | KeyCode::Down => app.move_item_create_focus(true),
+                KeyCode::BackTab | KeyCode::Up => app.move_item_create_focus(false),
+                KeyCode::Left if focus == ItemCreateFocus::Category => {
+                    app.cycle_item_create_category(false);
+                }
+                KeyCode::Right if focus == ItemCreateFocus::Category => {
+                    app.cycle_item_create_category(true);
+                }
+                KeyCode::Enter => app.apply(Action::CreateItem),
+                KeyCode::Char('g') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    app.open_password_generator();
+                }
+                KeyCode::Backspace => {
+                    if let Some(input) = app.item_create_input_mut() {
+                        pop_grapheme(input);
+                        app.error_message = None;
+                    }
+                }
+                KeyCode::Char(c) => {
+                    if let Some(input) = app.item_create_input_mut() {
+                        input.push(c);
+                        app.error_message = None;
+                    }
+                }
+                _ => {}
+            },
+            Modal::ItemDeleteConfirm {
+                item_id,
+                archive,
+                confirmed_once,
+                affected_vars,
+                ..
+            } => match key.code {
                 KeyCode::Esc | KeyCode::Char('n' | 'N') => app.close_modal(),
                 KeyCode::Char('y' | 'Y') => {
-                    if let Some(vars) = app.modal_vars_delete_targets() {
-                        let vars = vars.to_vec();
-                        match app.remove_managed_vars(&vars) {
-                            Ok(()) => {
-                                app.command_log.log_success("Vars removed", None);
-                                app.close_modal();
-                            }
-                            Err(err) => app.error_message = Some(err.to_string()),
+                    // Permanent deletion needs a second confirmation.
+                    if !archive && !confirmed_once {
+                        if let Some(Modal::ItemDeleteConfirm { confirmed_once, .. }) =
+                            app.modal.as_mut()
+                        {
+                            *confirmed_once = true;
                         }
+                        return;
                     }
+
+                    app.apply(Action::DeleteItem {
+                        item_id,
+                        archive,
+                        affected_vars,
+                    });
+                }
+                _ => {}
+            },
+            Modal::ErrorDetails { details, .. } => match key.code {
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q' | 'Q' | 'e' | 'E') => {
+                    app.close_modal();
+                }
+                KeyCode::Down | KeyCode::Char('j' | 'J') => app.scroll_error_pane(1),
+                KeyCode::Up | KeyCode::Char('k' | 'K') => app.scroll_error_pane(-1),
+                KeyCode::PageDown => app.scroll_error_pane(10),
+                KeyCode::PageUp => app.scroll_error_pane(-10),
+                KeyCode::Char('y' | 'Y') => app.apply(Action::CopyErrorDetails {
+                    text: details.clipboard_text(),
+                }),
+                _ => {}
+            },
+            Modal::Help { .. } => match key.code {
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q' | 'Q' | '?') => {
+                    app.close_modal();
+                }
+                KeyCode::Down | KeyCode::Char('j' | 'J') => app.scroll_help(1),
+                KeyCode::Up | KeyCode::Char('k' | 'K') => app.scroll_help(-1),
+                KeyCode::PageDown => app.scroll_help(10),
+                KeyCode::PageUp => app.scroll_help(-10),
+                _ => {}
+            },
+            Modal::PlaceFile { file, path } => match key.code {
+                KeyCode::Esc => app.close_modal(),
+                KeyCode::Enter => app.apply(Action::PlaceFile { path, file }),
+                KeyCode::Backspace => {
+                    if let Some(path) = app.modal_place_file_path_mut() {
+                        pop_grapheme(path);
+                        app.error_message = None;
+                    }
+                }
+                KeyCode::Char(c) => {
+                    if let Some(path) = app.modal_place_file_path_mut() {
+                        path.push(c);
+                        app.error_message = None;
+                    }
+                }
+                _ => {}
+            },
+            Modal::QuitConfirm { .. } => match key.code {
+                KeyCode::Enter | KeyCode::Char('y' | 'Y' | 'q' | 'Q') => app.apply(Action::Quit),
+                KeyCode::Esc | KeyCode::Char('n' | 'N') => app.close_modal(),
+                _ => {}
+            },
+            Modal::SignIn { .. } => match key.code {
+                KeyCode::Enter => app.signin_requested = true,
+                KeyCode::Esc => app.cancel_signin(),
+                _ => {}
+            },
+            Modal::EnvPreview { .. } => match key.code {
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q' | 'Q' | 'p' | 'P') => {
+                    app.close_modal();
+                }
+                KeyCode::Char('r' | 'R') => app.toggle_env_preview_reveal(),
+                KeyCode::Down | KeyCode::Char('j' | 'J') => app.scroll_env_preview(1),
+                KeyCode::Up | KeyCode::Char('k' | 'K') => app.scroll_env_preview(-1),
+                KeyCode::PageDown => app.scroll_env_preview(10),
+                KeyCode::PageUp => app.scroll_env_preview(-10),
+                _ => {}
+            },
+            Modal::CommandLog { .. } => match key.code {
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q' | 'Q' | 'L') => {
+                    app.close_modal();
+                }
+                KeyCode::Down | KeyCode::Char('j' | 'J') => app.scroll_command_log_view(1),
+                KeyCode::Up | KeyCode::Char('k' | 'K') => app.scroll_command_log_view(-1),
+                KeyCode::PageDown => app.scroll_command_log_view(10),
+                KeyCode::PageUp => app.scroll_command_log_view(-10),
+                _ => {}
+            },
+            Modal::BulkMap { focus, .. } => match key.code {
+                KeyCode::Esc => app.close_modal(),
+                KeyCode::Tab | KeyCode::Down => app.move_bulk_map_focus(true),
+                KeyCode::BackTab | KeyCode::Up => app.move_bulk_map_focus(false),
+                KeyCode::Enter => {
+                    let vars = match app.bulk_map_vars() {
+                        Ok(vars) => vars,
+                        Err(err) => {
+                            app.error_message = Some(err.to_string());
+                            return;
+                        }
+                    };
+                    let Some(account_id) = app.selected_account().map(|a| a.account_uuid.clone())
+                    else {
+                        app.error_message = Some("No account selected".to_string());
+                        return;
+                    };
+                    app.apply(Action::SaveVars { account_id, vars });
+                }
+                KeyCode::Backspace => {
+                    if let Some(input) = app.bulk_map_input_mut() {
+                        pop_grapheme(input);
+                        app.error_message = None;
+                    }
+                }
+                KeyCode::Char(c) if focus == BulkMapFocus::Field => {
+                    if let Some(input) = app.bulk_map_input_mut() {
+                        input.push(c);
+                        app.error_message = None;
+                    }
+                }
+                KeyCode::Char(c) => {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        app.error_message =
+                            Some("Env var names may only use A-Z, 0-9, and _".to_string());
+                    } else if let Some(input) = app.bulk_map_input_mut() {
+                        input.push(c.to_ascii_uppercase());
+                        app.error_message = None;
+                    }
+                }
+                _ => {}
+            },
+            Modal::VaultDetails { .. } => match key.code {
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q' | 'Q' | 'i' | 'I') => {
+                    app.close_modal();
                 }
                 _ => {}
             },
@@ -219,9 +723,12 @@ fn handle_key_press(app: &mut App, key: KeyEvent) {
                 VaultItemListNav.on_select(app);
             }
             KeyCode::Backspace => {
-                app.search_query.pop();
+                pop_grapheme(&mut app.search_query);
                 app.update_filtered_items();
             }
+            KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                app.apply(Action::IndexItemFields);
+            }
             KeyCode::Char(c) => {
                 app.search_query.push(c);
                 app.update_filtered_items();
@@ -233,6 +740,26 @@ fn handle_key_press(app: &mut App, key: KeyEvent) {
         return;
     }
 
+    if let Some(input) = app.jump_input.as_mut() {
+        match key.code {
+            KeyCode::Char(c) if c.is_ascii_digit() => input.push(c),
+            KeyCode::Backspace if input.pop().is_none() => app.jump_input = None,
+            KeyCode::Enter => {
+                if let Some(row) = app.take_jump_row() {
+                    focused_nav(app.focused_panel).jump_to(app, row);
+                }
+            }
+            KeyCode::Esc => app.jump_input = None,
+            _ => {}
+        }
+        return;
+    }
+
+    if key.code == KeyCode::Char(':') {
+        app.jump_input = Some(String::new());
+        return;
+    }
+
     if key.code == KeyCode::Char('/')
         && (app.focused_panel == FocusedPanel::VaultItemList
             || app.focused_panel == FocusedPanel::VaultItemDetail)
@@ -241,13 +768,86 @@ fn handle_key_press(app: &mut App, key: KeyEvent) {
         return;
     }
 
+    // Before the panel actions, so Ctrl+D doesn't also count as `d`.
+    let pending_g = std::mem::take(&mut app.pending_g);
+    if let Some(action) = PageAction::from_key(key, pending_g) {
+        let nav = focused_nav(app.focused_panel);
+        match action {
+            PageAction::First => nav.jump_to(app, 0),
+            PageAction::Last => nav.jump_to(app, usize::MAX),
+            PageAction::HalfPageDown => nav.move_by(app, PAGE_ROWS / 2),
+            PageAction::HalfPageUp => nav.move_by(app, -PAGE_ROWS / 2),
+            PageAction::PageDown => nav.move_by(app, PAGE_ROWS),
+            PageAction::PageUp => nav.move_by(app, -PAGE_ROWS),
+        }
+        return;
+    }
+    if key.code == KeyCode::Char('g') && key.modifiers.is_empty() {
+        app.pending_g = true;
+        return;
+    }
+
+    // `u` only undoes a delete when there is one; otherwise it still
+    // focuses the Recent panel.
     if app.focused_panel == FocusedPanel::VarsList
         && let Some(action) = VarsAction::from_key(key.code)
+        && (!matches!(action, VarsAction::Undo) || !app.deleted_vars.is_empty())
     {
         handle_vars_action(app, action);
         return;
     }
 
+    if app.focused_panel == FocusedPanel::VaultItemList
+        && let Some(action) = ItemsAction::from_key(key.code)
+    {
+        handle_items_action(app, action);
+        return;
+    }
+
+    if app.focused_panel == FocusedPanel::VaultItemDetail
+        && let Some(action) = DetailsAction::from_key(key.code)
+    {
+        handle_details_action(app, action);
+        return;
+    }
+
+    if app.focused_panel == FocusedPanel::VaultList
+        && let Some(action) = VaultsAction::from_key(key.code)
+    {
+        handle_vaults_action(app, action);
+        return;
+    }
+
+    if matches!(key.code, KeyCode::Char('e' | 'E')) {
+        app.open_error_pane();
+        return;
+    }
+
+    if key.code == KeyCode::Char('?') {
+        app.open_help();
+        return;
+    }
+
+    if key.code == KeyCode::Char('l') {
+        app.command_log_size = app.command_log_size.next();
+        return;
+    }
+
+    if key.code == KeyCode::Char('L') {
+        app.open_command_log_view();
+        return;
+    }
+
+    if key.code == KeyCode::Char('R') {
+        app.apply(Action::RefreshListing);
+        return;
+    }
+
+    if key.code == KeyCode::F(12) {
+        app.apply(Action::DumpState);
+        return;
+    }
+
     // TODO: use `fn ensure_handle_action()` pattern?
     if key.code == KeyCode::Char('f') || key.code == KeyCode::Char('F') {
         match app.focused_panel {
@@ -258,16 +858,9 @@ fn handle_key_press(app: &mut App, key: KeyEvent) {
                     .and_then(|idx| app.accounts.get(idx))
                     .map(|a| a.account_uuid.clone())
                 {
-                    if let Err(e) = app.set_default_account(&selected_account_id) {
-                        app.command_log.log_failure(
-                            "Failed to save default account configuration",
-                            e.to_string(),
-                        );
-                    } else {
-                        app.command_log
-                            .log_success("Saved default account configuration", None);
-                        AccountListNav.on_select(app);
-                    }
+                    app.apply(Action::SetDefaultAccount {
+                        account_id: selected_account_id,
+                    });
                 }
             }
             FocusedPanel::VaultList => {
@@ -278,17 +871,10 @@ fn handle_key_press(app: &mut App, key: KeyEvent) {
                         .and_then(|idx| app.vaults.get(idx))
                         .map(|v| v.id.clone()),
                 ) {
-                    if let Err(e) = app.set_default_vault(&selected_account_id, &selected_vault_id)
-                    {
-                        app.command_log.log_failure(
-                            "Failed to save default vault configuration",
-                            e.to_string(),
-                        );
-                    } else {
-                        app.command_log
-                            .log_success("Saved default vault configuration", None);
-                        VaultListNav.on_select(app);
-                    }
+                    app.apply(Action::SetDefaultVault {
+                        account_id: selected_account_id,
+                        vault_id: selected_vault_id,
+                    });
                 }
             }
             _ => {}
@@ -297,26 +883,15 @@ fn handle_key_press(app: &mut App, key: KeyEvent) {
 
     if let Some(action) = NavAction::from_key(key.code) {
         match action {
-            NavAction::Quit => app.should_quit = true,
-            NavAction::PanelZero => app.focused_panel = FocusedPanel::AccountList,
-            NavAction::PanelOne => app.focused_panel = FocusedPanel::VaultList,
-            NavAction::PanelTwo => app.focused_panel = FocusedPanel::VaultItemList,
-            NavAction::PanelFour => app.focused_panel = FocusedPanel::VaultItemDetail,
-            NavAction::PanelVars => {
-                app.focused_panel = FocusedPanel::VarsList;
-                if app.managed_vars_list_state.selected().is_none() && !app.managed_vars.is_empty()
-                {
-                    app.managed_vars_list_state.select(Some(0));
-                }
-            }
+            NavAction::Quit => app.request_quit(),
+            NavAction::PanelZero => app.apply(Action::FocusPanel(FocusedPanel::AccountList)),
+            NavAction::PanelOne => app.apply(Action::FocusPanel(FocusedPanel::VaultList)),
+            NavAction::PanelTwo => app.apply(Action::FocusPanel(FocusedPanel::VaultItemList)),
+            NavAction::PanelFour => app.apply(Action::FocusPanel(FocusedPanel::VaultItemDetail)),
+            NavAction::PanelVars => app.apply(Action::FocusPanel(FocusedPanel::VarsList)),
+            NavAction::PanelRecent => app.apply(Action::FocusPanel(FocusedPanel::RecentList)),
             nav_action => {
-                let nav: &dyn ListNav = match app.focused_panel {
-                    FocusedPanel::AccountList => &AccountListNav,
-                    FocusedPanel::VaultList => &VaultListNav,
-                    FocusedPanel::VaultItemList => &VaultItemListNav,
-                    FocusedPanel::VaultItemDetail => &VaultItemDetailNav,
-                    FocusedPanel::VarsList => &VarsListNav,
-                };
+                let nav = focused_nav(app.focused_panel);
 
                 match nav_action {
                     NavAction::Up => nav.handle_up(app),
@@ -329,6 +904,17 @@ fn handle_key_press(app: &mut App, key: KeyEvent) {
     }
 }
 
+fn focused_nav(panel: FocusedPanel) -> &'static dyn ListNav {
+    match panel {
+        FocusedPanel::AccountList => &AccountListNav,
+        FocusedPanel::VaultList => &VaultListNav,
+        FocusedPanel::VaultItemList => &VaultItemListNav,
+        FocusedPanel::VaultItemDetail => &VaultItemDetailNav,
+        FocusedPanel::VarsList => &VarsListNav,
+        FocusedPanel::RecentList => &RecentListNav,
+    }
+}
+
 trait ListNav {
     fn len(&self, app: &App) -> usize;
 
@@ -362,6 +948,22 @@ trait ListNav {
         let idx = self.list_state(app).selected();
         self.set_selected_idx(app, idx);
     }
+
+    /// Highlights row `idx`, or the last row if the list is shorter.
+    fn jump_to(&self, app: &mut App, idx: usize) {
+        let len = self.len(app);
+        if len == 0 {
+            return;
+        }
+        self.list_state(app).select(Some(idx.min(len - 1)));
+    }
+
+    /// Moves the highlight `delta` rows, stopping at either end instead of
+    /// wrapping like [`ListNav::handle_up`] and [`ListNav::handle_down`].
+    fn move_by(&self, app: &mut App, delta: isize) {
+        let idx = self.list_state(app).selected().unwrap_or(0);
+        self.jump_to(app, idx.saturating_add_signed(delta));
+    }
 }
 
 struct AccountListNav;
@@ -380,36 +982,7 @@ impl ListNav for AccountListNav {
 
     fn on_select(&self, app: &mut App) {
         let idx = self.list_state(app).selected();
-        self.set_selected_idx(app, idx);
-
-        app.clear_search();
-        app.vault_items.clear();
-        app.filtered_item_indices.clear();
-        app.selected_item_details = None;
-
-        if let Err(e) = app.load_vaults() {
-            app.error_message = Some(e.to_string());
-        }
-
-        if let Some(vault_idx) = app
-            .selected_account()
-            .map(|a| a.account_uuid.clone())
-            .and_then(|account_id| {
-                app.config
-                    .as_ref()
-                    .and_then(|c| c.default_vault_per_account.get(&account_id))
-            })
-            .and_then(|vault_id| app.vaults.iter().position(|v| &v.id == vault_id))
-        {
-            app.selected_vault_idx = Some(vault_idx);
-            app.vault_list_state.select(Some(vault_idx));
-
-            if let Err(e) = app.load_vault_items() {
-                app.error_message = Some(e.to_string());
-            }
-        }
-
-        app.focused_panel = FocusedPanel::VaultList;
+        app.apply(Action::SelectAccount(idx));
     }
 }
 
@@ -429,15 +1002,7 @@ impl ListNav for VaultListNav {
 
     fn on_select(&self, app: &mut App) {
         let idx = self.list_state(app).selected();
-        self.set_selected_idx(app, idx);
-
-        app.clear_search();
-
-        if let Err(e) = app.load_vault_items() {
-            app.error_message = Some(e.to_string());
-        }
-
-        app.focused_panel = FocusedPanel::VaultItemList;
+        app.apply(Action::SelectVault(idx));
     }
 }
 
@@ -457,30 +1022,16 @@ impl ListNav for VaultItemListNav {
 
     fn on_select(&self, app: &mut App) {
         let list_idx = self.list_state(app).selected();
-        self.set_selected_idx(app, list_idx);
-
-        if let Some(list_idx) = list_idx
-            && let Some(&real_idx) = app.filtered_item_indices.get(list_idx)
-            && let Some(item) = app.vault_items.get(real_idx)
-        {
-            let item_id = item.id.clone();
-            if let Err(e) = app.load_item_details(&item_id) {
-                app.error_message = Some(e.to_string());
-            } else {
-                app.item_detail_list_state.select(Some(0));
-                app.selected_field_idx = None;
-                app.focused_panel = FocusedPanel::VaultItemDetail;
-            }
-        }
+        app.apply(Action::SelectItem(list_idx));
     }
 }
 
 struct VaultItemDetailNav;
 impl ListNav for VaultItemDetailNav {
     fn len(&self, app: &App) -> usize {
-        app.selected_item_details.as_ref().map_or(0, |d| {
-            d.fields.iter().filter(|f| f.label != "notesPlain").count()
-        })
+        app.selected_item_details
+            .as_ref()
+            .map_or(0, |d| d.detail_fields().len())
     }
 
     fn list_state<'a>(&self, app: &'a mut App) -> &'a mut ListState {
@@ -495,18 +1046,8 @@ impl ListNav for VaultItemDetailNav {
         let list_idx = self.list_state(app).selected();
         self.set_selected_idx(app, list_idx);
 
-        if let Some(idx) = list_idx
-            && let Some(details) = &app.selected_item_details
-        {
-            let field = details
-                .fields
-                .iter()
-                .filter(|f| f.label != "notesPlain")
-                .nth(idx);
-
-            if let Some(field) = field {
-                app.open_modal(field.reference.clone());
-            }
+        if let Some(idx) = list_idx {
+            app.open_modal_for_field(idx);
         }
     }
 }
@@ -530,3 +1071,25 @@ impl ListNav for VarsListNav {
         // No-op: cursor position is enough for vars actions.
     }
 }
+
+struct RecentListNav;
+
+impl ListNav for RecentListNav {
+    fn len(&self, app: &App) -> usize {
+        app.recent_items.items.len()
+    }
+
+    fn list_state<'a>(&self, app: &'a mut App) -> &'a mut ListState {
+        &mut app.recent_list_state
+    }
+
+    fn set_selected_idx(&self, app: &mut App, idx: Option<usize>) {
+        app.recent_list_state.select(idx);
+    }
+
+    fn on_select(&self, app: &mut App) {
+        if let Some(idx) = self.list_state(app).selected() {
+            app.apply(Action::OpenRecentItem(idx));
+        }
+    }
+}
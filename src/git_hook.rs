@@ -0,0 +1,236 @@
+use anyhow::{Context, Result, bail};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::cache::config_hash;
+use crate::secret_scan::MIN_SECRET_LEN;
+
+/// A file in the git index, with its path relative to the repo root.
+pub struct StagedFile {
+    pub path: PathBuf,
+    pub content: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Finding {
+    /// The file is a template target op-loader renders secrets into.
+    ManagedTarget { path: PathBuf },
+    /// The content matches a recorded render of a template.
+    RenderedOutput { path: PathBuf, template: String },
+    /// The content contains the resolved value of a managed variable.
+    SecretValue { path: PathBuf, var: String },
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ManagedTarget { path } => {
+                write!(f, "{}: rendered from a managed template", path.display())
+            }
+            Self::RenderedOutput { path, template } => write!(
+                f,
+                "{}: matches a previous render of template '{template}'",
+                path.display()
+            ),
+            Self::SecretValue { path, var } => {
+                write!(f, "{}: contains the value of {var}", path.display())
+            }
+        }
+    }
+}
+
+/// What staged files are compared against.
+#[derive(Default)]
+pub struct SecretIndex {
+    /// Absolute template targets.
+    pub targets: Vec<PathBuf>,
+    /// Hashes of recorded renders, mapped to their template name.
+    pub render_hashes: HashMap<String, String>,
+    /// Resolved values by variable name, when `--check-values` is used.
+    pub values: Vec<(String, String)>,
+}
+
+pub fn check_staged(repo_root: &Path, staged: &[StagedFile], index: &SecretIndex) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for file in staged {
+        let absolute = repo_root.join(&file.path);
+        let absolute = absolute.canonicalize().unwrap_or(absolute);
+        if index.targets.contains(&absolute) {
+            findings.push(Finding::ManagedTarget {
+                path: file.path.clone(),
+            });
+            continue;
+        }
+
+        let Ok(content) = std::str::from_utf8(&file.content) else {
+            continue;
+        };
+
+        if let Some(template) = index.render_hashes.get(&config_hash(content)) {
+            findings.push(Finding::RenderedOutput {
+                path: file.path.clone(),
+                template: template.clone(),
+            });
+            continue;
+        }
+
+        if let Some((var, _)) = index
+            .values
+            .iter()
+            .find(|(_, value)| value.len() >= MIN_SECRET_LEN && content.contains(value.as_str()))
+        {
+            findings.push(Finding::SecretValue {
+                path: file.path.clone(),
+                var: var.clone(),
+            });
+        }
+    }
+
+    findings
+}
+
+fn git(args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .with_context(|| format!("Failed to run `git {}`", args.join(" ")))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("`git {}` failed: {}", args.join(" "), stderr.trim());
+    }
+    Ok(output.stdout)
+}
+
+pub fn repo_root() -> Result<PathBuf> {
+    let stdout = git(&["rev-parse", "--show-toplevel"])?;
+    Ok(PathBuf::from(String::from_utf8_lossy(&stdout).trim()))
+}
+
+/// Added, copied, modified, and renamed files in the index, with their
+/// staged content.
+pub fn staged_files() -> Result<Vec<StagedFile>> {
+    let stdout = git(&[
+        "diff",
+        "--cached",
+        "--name-only",
+        "--diff-filter=ACMR",
+        "-z",
+    ])?;
+
+    stdout
+        .split(|&byte| byte == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| {
+            let name = String::from_utf8_lossy(name).to_string();
+            let content = git(&["show", &format!(":{name}")])?;
+            Ok(StagedFile {
+                path: PathBuf::from(name),
+                content,
+            })
+        })
+        .collect()
+}
+
+const HOOK_MARKER: &str = "# Installed by op-loader";
+
+pub fn hook_script(check_values: bool) -> String {
+    let flags = if check_values { " --check-values" } else { "" };
+    format!("#!/bin/sh\n{HOOK_MARKER}\nexec op-loader hook pre-commit{flags}\n")
+}
+
+/// Writes the pre-commit hook for the current repo. An existing hook is only
+/// replaced with `force`, or when op-loader installed it.
+pub fn install_hook(check_values: bool, force: bool) -> Result<PathBuf> {
+    let hooks_dir = PathBuf::from(
+        String::from_utf8_lossy(&git(&["rev-parse", "--git-path", "hooks"])?)
+            .trim()
+            .to_string(),
+    );
+    std::fs::create_dir_all(&hooks_dir)
+        .with_context(|| format!("Failed to create {}", hooks_dir.display()))?;
+
+    let path = hooks_dir.join("pre-commit");
+    if let Ok(existing) = std::fs::read_to_string(&path)
+        && !existing.contains(HOOK_MARKER)
+        && !force
+    {
+        bail!(
+            "{} already exists. Re-run with --force to replace it, or call `op-loader hook pre-commit` from it.",
+            path.display()
+        );
+    }
+
+    std::fs::write(&path, hook_script(check_values))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("Failed to make {} executable", path.display()))?;
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn staged(path: &str, content: &str) -> StagedFile {
+        StagedFile {
+            path: PathBuf::from(path),
+            content: content.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn flags_targets_renders_and_values() {
+        let repo = assert_fs::TempDir::new().unwrap();
+        let root = repo.path().canonicalize().unwrap();
+        std::fs::write(root.join(".npmrc"), "").unwrap();
+
+        let index = SecretIndex {
+            targets: vec![root.join(".npmrc")],
+            render_hashes: HashMap::from([(config_hash("TOKEN=abc\n"), "env".to_string())]),
+            values: vec![
+                ("API_KEY".to_string(), "sk-live-123456".to_string()),
+                ("PIN".to_string(), "1234".to_string()),
+            ],
+        };
+
+        let findings = check_staged(
+            &root,
+            &[
+                staged(".npmrc", "anything"),
+                staged("out.env", "TOKEN=abc\n"),
+                staged("config.py", "KEY = 'sk-live-123456'"),
+                staged("notes.txt", "call 1234"),
+            ],
+            &index,
+        );
+
+        assert_eq!(
+            findings,
+            vec![
+                Finding::ManagedTarget {
+                    path: PathBuf::from(".npmrc")
+                },
+                Finding::RenderedOutput {
+                    path: PathBuf::from("out.env"),
+                    template: "env".to_string()
+                },
+                Finding::SecretValue {
+                    path: PathBuf::from("config.py"),
+                    var: "API_KEY".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn hook_script_passes_flags() {
+        assert!(hook_script(true).ends_with("exec op-loader hook pre-commit --check-values\n"));
+        assert!(hook_script(false).contains(HOOK_MARKER));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::app::FocusedPanel;
+
+/// Keybindings shown in the `?` overlay, grouped by the panel they apply to.
+pub struct HelpSection {
+    pub title: &'static str,
+    pub panel: Option<FocusedPanel>,
+    pub bindings: &'static [(&'static str, &'static str)],
+}
+
+pub const HELP_SECTIONS: &[HelpSection] = &[
+    HelpSection {
+        title: "Global",
+        panel: None,
+        bindings: &[
+            ("0 1 2 3", "Focus Accounts, Vaults, Items, Details"),
+            ("v", "Focus managed vars"),
+            ("u", "Focus recently used items"),
+            ("j/k, ↑/↓", "Move through the focused list"),
+            ("gg / G", "Jump to the first / last row"),
+            ("Ctrl+D/U", "Move half a page down / up"),
+            (
+                ":<n> Enter",
+                "Jump to row n (row numbers show while typing)",
+            ),
+            ("PgDn/PgUp", "Move a page down / up"),
+            ("Enter", "Select"),
+            (
+                "R",
+                "Reload the focused list (accounts, vaults, items) from op",
+            ),
+            ("e", "Show the latest error"),
+            ("l", "Collapse / expand the command log"),
+            ("L", "Full-screen command log with complete errors"),
+            ("?", "Toggle this help"),
+            ("F12", "Write a redacted state snapshot for bug reports"),
+            ("q", "Quit"),
+        ],
+    },
+    HelpSection {
+        title: "[0] Accounts",
+        panel: Some(FocusedPanel::AccountList),
+        bindings: &[
+            (
+                "Enter",
+                "Select; also retries an account that is cooling down",
+            ),
+            ("f", "Favorite (open on startup)"),
+        ],
+    },
+    HelpSection {
+        title: "[1] Vaults",
+        panel: Some(FocusedPanel::VaultList),
+        bindings: &[
+            ("f", "Favorite (open on startup for this account)"),
+            ("n", "Create a vault"),
+            ("i", "Vault details and your permissions"),
+        ],
+    },
+    HelpSection {
+        title: "[2] Items",
+        panel: Some(FocusedPanel::VaultItemList),
+        bindings: &[
+            ("/", "Fuzzy search (Esc clears, Enter opens)"),
+            (
+                "Ctrl+F",
+                "While searching, fetch unopened items so their field labels match too",
+            ),
+            ("y", "Copy the `op item get` command"),
+            (
+                "n",
+                "Create an item in the selected vault (Ctrl+G generates a password)",
+            ),
+            ("Space", "Mark / unmark for bulk mapping"),
+            ("b", "Map a field of each marked item to env vars"),
+            ("p", "Place a document item's file on disk (mode 600)"),
+            ("a", "Archive the item"),
+            ("x", "Permanently delete the item"),
+        ],
+    },
+    HelpSection {
+        title: "[3] Details",
+        panel: Some(FocusedPanel::VaultItemDetail),
+        bindings: &[
+            ("Enter", "Map the field to an env var"),
+            ("c", "Copy the field's value (cleared after 30s)"),
+            ("r", "Reveal a concealed value for 10s (r again hides it)"),
+            (
+                "e",
+                "Change the field's value in 1Password (asks to confirm)",
+            ),
+            ("n", "Expand / collapse the item's notes"),
+            ("y", "Copy the `op read` command"),
+            ("Y", "Copy the field's full `op://` reference"),
+            ("/", "Search items"),
+        ],
+    },
+    HelpSection {
+        title: "[v] Managed vars",
+        panel: Some(FocusedPanel::VarsList),
+        bindings: &[
+            ("Space", "Select / deselect"),
+            ("c", "Copy names of the selected vars"),
+            ("e", "Edit the highlighted var's name or reference"),
+            ("t", "Test-resolve the highlighted var with `op read`"),
+            ("d", "Delete the selected vars"),
+            (
+                "u",
+                "Undo the last delete (focuses Recent when there's none)",
+            ),
+            (
+                "p",
+                "Preview what `op-loader env` would print (r to reveal)",
+            ),
+        ],
+    },
+    HelpSection {
+        title: "[u] Recent",
+        panel: Some(FocusedPanel::RecentList),
+        bindings: &[(
+            "Enter",
+            "Open the item in its account and vault, ready to map a field",
+        )],
+    },
+];
+
+/// Rendered height of the overlay: a title and bindings per section, with a
+/// blank line between sections.
+pub fn help_line_count() -> usize {
+    HELP_SECTIONS
+        .iter()
+        .map(|section| section.bindings.len() + 1)
+        .sum::<usize>()
+        + HELP_SECTIONS.len().saturating_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_panel_has_a_section() {
+        for panel in [
+            FocusedPanel::AccountList,
+            FocusedPanel::VaultList,
+            FocusedPanel::VaultItemList,
+            FocusedPanel::VaultItemDetail,
+            FocusedPanel::VarsList,
+            FocusedPanel::RecentList,
+        ] {
+            assert!(
+                HELP_SECTIONS
+                    .iter()
+                    .any(|section| section.panel == Some(panel)),
+                "{panel:?} has no help section"
+            );
+        }
+    }
+}
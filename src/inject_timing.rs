@@ -0,0 +1,301 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::cache::update_locked_json;
+
+/// Durations kept per account; older ones are dropped.
+const SAMPLES: usize = 10;
+/// Resolutions needed before an account is judged, so one cold start or a
+/// sign-in prompt doesn't flag it.
+const MIN_SAMPLES: usize = 3;
+/// Typical resolution time above which an account counts as slow.
+const SLOW: Duration = Duration::from_secs(2);
+/// Accounts with more vars than this are always batched, so splitting
+/// doesn't start dozens of `op` processes at once.
+const MAX_PER_VAR: usize = 8;
+
+/// How an account's vars are resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Batching {
+    /// One `op inject` call with every var.
+    Batched,
+    /// One `op inject` call per var, run in parallel.
+    PerVar,
+}
+
+/// How long resolving each account's vars took recently, in milliseconds,
+/// oldest first. Only account ids are kept, never references or values.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InjectTimings {
+    #[serde(default)]
+    accounts: BTreeMap<String, AccountTimings>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct AccountTimings {
+    #[serde(default)]
+    batched: Vec<u64>,
+    #[serde(default)]
+    per_var: Vec<u64>,
+}
+
+impl AccountTimings {
+    fn samples(&mut self, batching: Batching) -> &mut Vec<u64> {
+        match batching {
+            Batching::Batched => &mut self.batched,
+            Batching::PerVar => &mut self.per_var,
+        }
+    }
+}
+
+/// The middle of `samples`, once there are enough of them.
+fn median(samples: &[u64]) -> Option<Duration> {
+    if samples.len() < MIN_SAMPLES {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    Some(Duration::from_millis(sorted[sorted.len() / 2]))
+}
+
+/// An account whose `op inject` calls are consistently slow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowAccount {
+    pub account_id: String,
+    pub median: Duration,
+    pub suggestion: Suggestion,
+}
+
+/// What would keep a slow account off the shell's startup path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Suggestion {
+    /// `--cache-ttl` with this duration.
+    CacheTtl(&'static str),
+    /// `op-loader agent`, where the file cache isn't available.
+    Agent,
+}
+
+impl std::fmt::Display for Suggestion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CacheTtl(ttl) => write!(f, "pass `--cache-ttl {ttl}` to `env inject`"),
+            Self::Agent => write!(f, "run `op-loader agent` and use `env inject --from-agent`"),
+        }
+    }
+}
+
+/// The slower an account, the longer it's worth caching: a few seconds
+/// per shell adds up quickly, while a stale secret only matters once it's
+/// rotated.
+pub fn suggested_ttl(median: Duration) -> &'static str {
+    match median.as_secs() {
+        0..5 => "15m",
+        5..10 => "1h",
+        _ => "8h",
+    }
+}
+
+const fn cache_supported() -> bool {
+    cfg!(any(target_os = "macos", target_os = "windows"))
+}
+
+impl InjectTimings {
+    pub fn record(&mut self, account_id: &str, batching: Batching, elapsed: Duration) {
+        let samples = self
+            .accounts
+            .entry(account_id.to_string())
+            .or_default()
+            .samples(batching);
+        samples.push(u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX));
+        if samples.len() > SAMPLES {
+            samples.drain(..samples.len() - SAMPLES);
+        }
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for (account_id, timings) in &other.accounts {
+            for (batching, samples) in [
+                (Batching::Batched, &timings.batched),
+                (Batching::PerVar, &timings.per_var),
+            ] {
+                for millis in samples {
+                    self.record(account_id, batching, Duration::from_millis(*millis));
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// The middle of `account_id`'s recent durations with whichever
+    /// batching has been faster, once there are enough of them.
+    pub fn median(&self, account_id: &str) -> Option<Duration> {
+        let timings = self.accounts.get(account_id)?;
+        match (median(&timings.batched), median(&timings.per_var)) {
+            (Some(batched), Some(per_var)) => Some(batched.min(per_var)),
+            (batched, per_var) => batched.or(per_var),
+        }
+    }
+
+    /// Batches `var_count` vars unless that has been slow for the account,
+    /// then tries one call per var and keeps doing so only while it's
+    /// faster than batching was.
+    pub fn batching(&self, account_id: &str, var_count: usize) -> Batching {
+        if !(2..=MAX_PER_VAR).contains(&var_count) {
+            return Batching::Batched;
+        }
+        let Some(timings) = self.accounts.get(account_id) else {
+            return Batching::Batched;
+        };
+        match (median(&timings.batched), median(&timings.per_var)) {
+            (Some(batched), None) if batched >= SLOW => Batching::PerVar,
+            (Some(batched), Some(per_var)) if batched >= SLOW && per_var < batched => {
+                Batching::PerVar
+            }
+            _ => Batching::Batched,
+        }
+    }
+
+    /// Accounts that typically take longer than [`SLOW`], by account id.
+    pub fn slow_accounts(&self) -> Vec<SlowAccount> {
+        self.accounts
+            .keys()
+            .filter_map(|account_id| {
+                let median = self.median(account_id).filter(|median| *median >= SLOW)?;
+                Some(SlowAccount {
+                    account_id: account_id.clone(),
+                    median,
+                    suggestion: if cache_supported() {
+                        Suggestion::CacheTtl(suggested_ttl(median))
+                    } else {
+                        Suggestion::Agent
+                    },
+                })
+            })
+            .collect()
+    }
+}
+
+/// Durations from this process, written out by [`flush`] on exit.
+static PENDING: Mutex<InjectTimings> = Mutex::new(InjectTimings {
+    accounts: BTreeMap::new(),
+});
+
+/// Notes how long resolving `account_id`'s vars with `op` took.
+pub fn record(account_id: &str, batching: Batching, elapsed: Duration) {
+    if let Ok(mut pending) = PENDING.lock() {
+        pending.record(account_id, batching, elapsed);
+    }
+}
+
+/// Kept next to the cache files, without the `.cache` extension so
+/// eviction leaves them alone.
+pub fn timings_path(cache_root: &Path) -> PathBuf {
+    cache_root.join("inject_timings.json")
+}
+
+/// Adds this process's durations to the timings file.
+pub fn flush() {
+    let pending = PENDING
+        .lock()
+        .map(|mut pending| std::mem::take(&mut *pending))
+        .unwrap_or_default();
+    if pending.is_empty() {
+        return;
+    }
+    let result = crate::cache::cache_dir()
+        .and_then(|dir| update_timings(&dir, |timings| timings.merge(&pending)));
+    if let Err(err) = result {
+        log::debug!("Failed to record op inject timings: {err}");
+    }
+}
+
+pub fn load_timings(cache_root: &Path) -> InjectTimings {
+    std::fs::read_to_string(timings_path(cache_root))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+/// Rewrites the timings file under an exclusive lock, so shells exiting
+/// together don't drop each other's durations.
+pub fn update_timings(cache_root: &Path, update: impl FnOnce(&mut InjectTimings)) -> Result<()> {
+    update_locked_json(&timings_path(cache_root), "op inject timings", update)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_accounts_that_are_consistently_slow() {
+        let mut timings = InjectTimings::default();
+        for secs in [1, 6, 7] {
+            timings.record("slow", Batching::Batched, Duration::from_secs(secs));
+        }
+        for secs in [1, 1, 30] {
+            timings.record("fast", Batching::Batched, Duration::from_secs(secs));
+        }
+        timings.record("new", Batching::Batched, Duration::from_secs(20));
+
+        let slow = timings.slow_accounts();
+
+        assert_eq!(slow.len(), 1);
+        assert_eq!(slow[0].account_id, "slow");
+        assert_eq!(slow[0].median, Duration::from_secs(6));
+        assert_eq!(suggested_ttl(slow[0].median), "1h");
+    }
+
+    #[test]
+    fn keeps_only_recent_samples_across_flushes() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        for _ in 0..SAMPLES {
+            update_timings(dir.path(), |t| {
+                t.record("acct", Batching::Batched, Duration::from_secs(9))
+            })
+            .unwrap();
+        }
+        let mut recent = InjectTimings::default();
+        for _ in 0..MIN_SAMPLES * 2 {
+            recent.record("acct", Batching::Batched, Duration::from_millis(300));
+        }
+
+        update_timings(dir.path(), |t| t.merge(&recent)).unwrap();
+
+        let timings = load_timings(dir.path());
+        assert_eq!(timings.accounts["acct"].batched.len(), SAMPLES);
+        assert_eq!(timings.median("acct"), Some(Duration::from_millis(300)));
+        assert!(timings.slow_accounts().is_empty());
+    }
+
+    #[test]
+    fn switches_batching_by_recorded_latency() {
+        let mut timings = InjectTimings::default();
+        assert_eq!(timings.batching("acct", 4), Batching::Batched);
+
+        for _ in 0..MIN_SAMPLES {
+            timings.record("acct", Batching::Batched, Duration::from_secs(6));
+        }
+        assert_eq!(timings.batching("acct", 4), Batching::PerVar);
+        assert_eq!(timings.batching("acct", 1), Batching::Batched);
+        assert_eq!(timings.batching("acct", MAX_PER_VAR + 1), Batching::Batched);
+
+        for _ in 0..MIN_SAMPLES {
+            timings.record("acct", Batching::PerVar, Duration::from_secs(2));
+        }
+        assert_eq!(timings.batching("acct", 4), Batching::PerVar);
+        assert_eq!(timings.median("acct"), Some(Duration::from_secs(2)));
+
+        for _ in 0..SAMPLES {
+            timings.record("acct", Batching::PerVar, Duration::from_secs(9));
+        }
+        assert_eq!(timings.batching("acct", 4), Batching::Batched);
+    }
+}
@@ -0,0 +1,133 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Item metadata from `op item list`. Field values are never part of this
+/// output, so an inventory is safe to hand to auditors.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryItem {
+    pub id: String,
+    pub title: String,
+    pub category: String,
+    #[serde(default)]
+    pub vault: Option<InventoryVault>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryVault {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+pub fn parse_item_list(json: &[u8]) -> Result<Vec<InventoryItem>> {
+    let mut items: Vec<InventoryItem> =
+        serde_json::from_slice(json).context("Failed to parse item list JSON")?;
+    items.sort_by_key(|item| item.title.to_lowercase());
+    Ok(items)
+}
+
+pub fn export(items: &[InventoryItem], format: ExportFormat) -> Result<String> {
+    match format {
+        ExportFormat::Json => {
+            let mut json =
+                serde_json::to_string_pretty(items).context("Failed to serialize inventory")?;
+            json.push('\n');
+            Ok(json)
+        }
+        ExportFormat::Csv => Ok(to_csv(items)),
+    }
+}
+
+fn to_csv(items: &[InventoryItem]) -> String {
+    let mut output = String::from("id,title,category,vault,tags,created_at,updated_at\n");
+    for item in items {
+        let row = [
+            item.id.as_str(),
+            item.title.as_str(),
+            item.category.as_str(),
+            item.vault.as_ref().map_or("", |vault| vault.name.as_str()),
+            &item.tags.join(";"),
+            item.created_at.as_deref().unwrap_or_default(),
+            item.updated_at.as_deref().unwrap_or_default(),
+        ]
+        .map(csv_field)
+        .join(",");
+        output.push_str(&row);
+        output.push('\n');
+    }
+    output
+}
+
+/// Quotes a field per RFC 4180. Fields starting with a formula character are
+/// prefixed with `'` so spreadsheets don't evaluate item titles.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{value}")
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ITEM_LIST: &str = r#"[
+        {"id":"b1","title":"beta","category":"LOGIN","vault":{"id":"v","name":"Team"},
+         "tags":["prod","db"],"created_at":"2024-01-01T00:00:00Z","updated_at":"2024-02-01T00:00:00Z",
+         "additional_information":"user@example.com"},
+        {"id":"a1","title":"Alpha, \"main\"","category":"API_CREDENTIAL"}
+    ]"#;
+
+    #[test]
+    fn parses_and_sorts_by_title() {
+        let items = parse_item_list(ITEM_LIST.as_bytes()).unwrap();
+
+        assert_eq!(items[0].id, "a1");
+        assert_eq!(items[1].tags, vec!["prod", "db"]);
+    }
+
+    #[test]
+    fn csv_quotes_and_joins_tags() {
+        let items = parse_item_list(ITEM_LIST.as_bytes()).unwrap();
+
+        assert_eq!(
+            export(&items, ExportFormat::Csv).unwrap(),
+            "id,title,category,vault,tags,created_at,updated_at\n\
+             a1,\"Alpha, \"\"main\"\"\",API_CREDENTIAL,,,,\n\
+             b1,beta,LOGIN,Team,prod;db,2024-01-01T00:00:00Z,2024-02-01T00:00:00Z\n"
+        );
+    }
+
+    #[test]
+    fn csv_neutralizes_formulas() {
+        assert_eq!(csv_field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+    }
+
+    #[test]
+    fn json_export_omits_unlisted_fields() {
+        let items = parse_item_list(ITEM_LIST.as_bytes()).unwrap();
+        let json = export(&items, ExportFormat::Json).unwrap();
+
+        assert!(json.contains("\"updated_at\""));
+        assert!(!json.contains("user@example.com"));
+    }
+}
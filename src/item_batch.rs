@@ -0,0 +1,138 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::listing::run_op;
+use crate::op_client::OpClient;
+use crate::op_error::OpErrorKind;
+
+/// How many `op item get` calls a batch runs at once. Beyond a handful,
+/// 1Password starts rate limiting instead of answering sooner.
+pub const WORKERS: usize = 4;
+
+/// One item's `op item get --format json` output, or `op`'s stderr.
+#[derive(Debug)]
+pub struct ItemGet {
+    pub item_id: String,
+    pub result: Result<Vec<u8>, String>,
+}
+
+pub fn item_get_args(item_id: &str, account_id: &str, vault_id: &str) -> Vec<String> {
+    [
+        "item",
+        "get",
+        item_id,
+        "--account",
+        account_id,
+        "--vault",
+        vault_id,
+        "--format",
+        "json",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+/// Whether a failed get means the rest of the batch would fail the same way.
+fn stops_batch(stderr: &str) -> bool {
+    matches!(
+        crate::op_error::classify(stderr),
+        OpErrorKind::NotSignedIn | OpErrorKind::RateLimited | OpErrorKind::Network
+    )
+}
+
+/// Runs `op item get` for each of `item_ids`, `workers` at a time, and
+/// returns the results in the order given. Once a get fails because of the
+/// session or the connection, items not yet started are left out rather
+/// than failing one by one.
+pub fn get_items(
+    op: &dyn OpClient,
+    account_id: &str,
+    vault_id: &str,
+    item_ids: &[String],
+    workers: usize,
+) -> Vec<ItemGet> {
+    let next = AtomicUsize::new(0);
+    let stopped = AtomicBool::new(false);
+    let results: Mutex<Vec<(usize, ItemGet)>> = Mutex::new(Vec::with_capacity(item_ids.len()));
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers.clamp(1, item_ids.len().max(1)) {
+            scope.spawn(|| {
+                while !stopped.load(Ordering::Relaxed) {
+                    let idx = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item_id) = item_ids.get(idx) else {
+                        break;
+                    };
+                    let (result, _) = run_op(op, &item_get_args(item_id, account_id, vault_id));
+                    if let Err(stderr) = &result
+                        && stops_batch(stderr)
+                    {
+                        stopped.store(true, Ordering::Relaxed);
+                    }
+                    let get = ItemGet {
+                        item_id: item_id.clone(),
+                        result,
+                    };
+                    results
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner)
+                        .push((idx, get));
+                }
+            });
+        }
+    });
+
+    let mut results = results
+        .into_inner()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, get)| get).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op_client::MockOpClient;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| (*id).to_string()).collect()
+    }
+
+    #[test]
+    fn gets_every_item_in_order() {
+        let op = MockOpClient::default()
+            .succeed(
+                "item get a --account acct --vault v --format json",
+                "{\"id\":\"a\"}",
+            )
+            .succeed(
+                "item get b --account acct --vault v --format json",
+                "{\"id\":\"b\"}",
+            )
+            .fail(
+                "item get c --account acct --vault v --format json",
+                "[ERROR] \"c\" isn't an item in the \"v\" vault",
+            );
+
+        let gets = get_items(&op, "acct", "v", &ids(&["a", "b", "c"]), WORKERS);
+
+        let item_ids: Vec<&str> = gets.iter().map(|get| get.item_id.as_str()).collect();
+        assert_eq!(item_ids, ["a", "b", "c"]);
+        assert_eq!(gets[1].result.as_deref(), Ok(&b"{\"id\":\"b\"}"[..]));
+        assert!(gets[2].result.is_err());
+        assert_eq!(op.calls().len(), 3);
+    }
+
+    #[test]
+    fn stops_once_signed_out() {
+        let op = MockOpClient::default().fail(
+            "item get a --account acct --vault v --format json",
+            "[ERROR] You are not currently signed in",
+        );
+
+        let gets = get_items(&op, "acct", "v", &ids(&["a", "b", "c"]), 1);
+
+        assert_eq!(gets.len(), 1);
+        assert_eq!(op.calls().len(), 1);
+    }
+}
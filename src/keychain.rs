@@ -1,12 +1,15 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use rand_core::RngCore;
-use security_framework::os::macos::keychain::SecKeychain;
-use security_framework::passwords::{
-    delete_generic_password, get_generic_password, set_generic_password,
-};
 
-const SERVICE: &str = "op-loader cache key";
-const ACCOUNT: &str = "default";
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(target_os = "macos")]
+use macos as backend;
+
+#[cfg(target_os = "windows")]
+mod windows;
+#[cfg(target_os = "windows")]
+use windows as backend;
 
 pub fn get_or_create_key() -> Result<[u8; 32]> {
     if let Some(existing) = try_get_key()? {
@@ -16,26 +19,21 @@ pub fn get_or_create_key() -> Result<[u8; 32]> {
     let mut key = [0u8; 32];
     rand_core::OsRng.fill_bytes(&mut key);
 
-    set_generic_password(SERVICE, ACCOUNT, &key)
-        .context("Failed to store cache key in Keychain")?;
+    backend::store_secret(&key)?;
 
     Ok(key)
 }
 
 pub fn delete_key() -> Result<()> {
-    if get_generic_password(SERVICE, ACCOUNT).is_ok() {
-        delete_generic_password(SERVICE, ACCOUNT)
-            .context("Failed to delete cache key from Keychain")?;
-    }
-    Ok(())
+    backend::delete_secret()
 }
 
 fn try_get_key() -> Result<Option<[u8; 32]>> {
-    match get_generic_password(SERVICE, ACCOUNT) {
-        Ok(bytes) => {
+    match backend::read_secret()? {
+        Some(bytes) => {
             if bytes.len() != 32 {
                 anyhow::bail!(
-                    "Invalid Keychain cache key length: expected 32 bytes, got {}",
+                    "Invalid cache key length: expected 32 bytes, got {}",
                     bytes.len()
                 );
             }
@@ -43,11 +41,10 @@ fn try_get_key() -> Result<Option<[u8; 32]>> {
             key.copy_from_slice(&bytes);
             Ok(Some(key))
         }
-        Err(_) => Ok(None),
+        None => Ok(None),
     }
 }
 
 pub fn assert_keychain_available() -> Result<()> {
-    SecKeychain::default().context("Failed to access default Keychain")?;
-    Ok(())
+    backend::assert_available()
 }
@@ -0,0 +1,29 @@
+use anyhow::{Context, Result};
+use security_framework::os::macos::keychain::SecKeychain;
+use security_framework::passwords::{
+    delete_generic_password, get_generic_password, set_generic_password,
+};
+
+const SERVICE: &str = "op-loader cache key";
+const ACCOUNT: &str = "default";
+
+pub fn read_secret() -> Result<Option<Vec<u8>>> {
+    Ok(get_generic_password(SERVICE, ACCOUNT).ok())
+}
+
+pub fn store_secret(secret: &[u8]) -> Result<()> {
+    set_generic_password(SERVICE, ACCOUNT, secret).context("Failed to store cache key in Keychain")
+}
+
+pub fn delete_secret() -> Result<()> {
+    if get_generic_password(SERVICE, ACCOUNT).is_ok() {
+        delete_generic_password(SERVICE, ACCOUNT)
+            .context("Failed to delete cache key from Keychain")?;
+    }
+    Ok(())
+}
+
+pub fn assert_available() -> Result<()> {
+    SecKeychain::default().context("Failed to access default Keychain")?;
+    Ok(())
+}
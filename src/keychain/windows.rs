@@ -0,0 +1,91 @@
+use anyhow::Result;
+use windows_sys::Win32::Foundation::{ERROR_NOT_FOUND, GetLastError};
+use windows_sys::Win32::Security::Credentials::{
+    CRED_PERSIST_LOCAL_MACHINE, CRED_TYPE_GENERIC, CREDENTIALW, CredDeleteW, CredFree, CredReadW,
+    CredWriteW,
+};
+
+const TARGET: &str = "op-loader cache key";
+const USER: &str = "default";
+
+fn wide(value: &str) -> Vec<u16> {
+    value.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+pub fn read_secret() -> Result<Option<Vec<u8>>> {
+    let target = wide(TARGET);
+    let mut credential: *mut CREDENTIALW = std::ptr::null_mut();
+
+    // SAFETY: `target` is a NUL-terminated UTF-16 string that outlives the
+    // call, and `credential` is a valid out-pointer.
+    let ok = unsafe { CredReadW(target.as_ptr(), CRED_TYPE_GENERIC, 0, &raw mut credential) };
+    if ok == 0 {
+        // SAFETY: no preconditions; reads the calling thread's last error.
+        let err = unsafe { GetLastError() };
+        if err == ERROR_NOT_FOUND {
+            return Ok(None);
+        }
+        anyhow::bail!("Failed to read cache key from Credential Manager (error {err})");
+    }
+
+    // SAFETY: CredReadW succeeded, so `credential` points to a CREDENTIALW
+    // whose blob is `CredentialBlobSize` bytes long. We copy the bytes out
+    // before releasing the buffer with CredFree.
+    let secret = unsafe {
+        let blob = std::slice::from_raw_parts(
+            (*credential).CredentialBlob,
+            (*credential).CredentialBlobSize as usize,
+        )
+        .to_vec();
+        CredFree(credential.cast_const().cast());
+        blob
+    };
+
+    Ok(Some(secret))
+}
+
+pub fn store_secret(secret: &[u8]) -> Result<()> {
+    let mut target = wide(TARGET);
+    let mut user = wide(USER);
+    let mut blob = secret.to_vec();
+
+    // SAFETY: CREDENTIALW is a plain C struct for which all-zero is a valid
+    // (empty) value; the fields we rely on are set explicitly below.
+    let mut credential: CREDENTIALW = unsafe { std::mem::zeroed() };
+    credential.Type = CRED_TYPE_GENERIC;
+    credential.TargetName = target.as_mut_ptr();
+    credential.UserName = user.as_mut_ptr();
+    credential.CredentialBlob = blob.as_mut_ptr();
+    credential.CredentialBlobSize = u32::try_from(blob.len())?;
+    credential.Persist = CRED_PERSIST_LOCAL_MACHINE;
+
+    // SAFETY: every pointer in `credential` refers to a buffer that lives
+    // until the end of this function.
+    let ok = unsafe { CredWriteW(&raw const credential, 0) };
+    if ok == 0 {
+        // SAFETY: no preconditions; reads the calling thread's last error.
+        let err = unsafe { GetLastError() };
+        anyhow::bail!("Failed to store cache key in Credential Manager (error {err})");
+    }
+    Ok(())
+}
+
+pub fn delete_secret() -> Result<()> {
+    let target = wide(TARGET);
+
+    // SAFETY: `target` is a NUL-terminated UTF-16 string that outlives the call.
+    let ok = unsafe { CredDeleteW(target.as_ptr(), CRED_TYPE_GENERIC, 0) };
+    if ok == 0 {
+        // SAFETY: no preconditions; reads the calling thread's last error.
+        let err = unsafe { GetLastError() };
+        if err != ERROR_NOT_FOUND {
+            anyhow::bail!("Failed to delete cache key from Credential Manager (error {err})");
+        }
+    }
+    Ok(())
+}
+
+pub fn assert_available() -> Result<()> {
+    // Credential Manager is part of every supported Windows install.
+    Ok(())
+}
@@ -0,0 +1,186 @@
+use log::debug;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use crate::cache::{CacheKind, cache_file_for_account, remove_cache_file};
+use crate::cli::{read_listing_cache, write_listing_cache};
+use crate::op_client::{OpClient, SharedOpClient};
+
+/// An `op ... list` call whose JSON output is cached so the TUI can draw
+/// before `op` answers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListingRequest {
+    Accounts,
+    Vaults {
+        account_id: Option<String>,
+    },
+    Items {
+        account_id: String,
+        vault_id: String,
+    },
+}
+
+/// The outcome of refreshing a listing in the background.
+#[derive(Debug)]
+pub struct ListingRefresh {
+    pub request: ListingRequest,
+    pub result: Result<Vec<u8>, String>,
+    pub elapsed: Duration,
+}
+
+impl ListingRequest {
+    pub fn args(&self) -> Vec<String> {
+        let mut args: Vec<String> = match self {
+            Self::Accounts => vec!["account".into(), "list".into()],
+            Self::Vaults { account_id } => {
+                let mut args = vec!["vault".into(), "list".into()];
+                if let Some(account_id) = account_id {
+                    args.extend(["--account".into(), account_id.clone()]);
+                }
+                args
+            }
+            Self::Items {
+                account_id,
+                vault_id,
+            } => vec![
+                "item".into(),
+                "list".into(),
+                "--account".into(),
+                account_id.clone(),
+                "--vault".into(),
+                vault_id.clone(),
+            ],
+        };
+        args.extend(["--format".into(), "json".into()]);
+        args
+    }
+
+    /// The account the listing is for, when it names one.
+    pub fn account_id(&self) -> Option<&str> {
+        match self {
+            Self::Accounts => None,
+            Self::Vaults { account_id } => account_id.as_deref(),
+            Self::Items { account_id, .. } => Some(account_id),
+        }
+    }
+
+    pub fn command(&self) -> String {
+        format!("op {}", self.args().join(" "))
+    }
+
+    const fn kind(&self) -> CacheKind {
+        match self {
+            Self::Accounts => CacheKind::AccountList,
+            Self::Vaults { .. } => CacheKind::VaultList,
+            Self::Items { .. } => CacheKind::ItemList,
+        }
+    }
+
+    fn cache_key(&self) -> String {
+        match self {
+            Self::Accounts => "all".to_string(),
+            Self::Vaults { account_id } => account_id.clone().unwrap_or_else(|| "default".into()),
+            Self::Items {
+                account_id,
+                vault_id,
+            } => format!("{account_id}_{vault_id}"),
+        }
+    }
+
+    pub fn cached(&self) -> Option<Vec<u8>> {
+        read_listing_cache(&self.cache_key(), self.kind(), &self.command()).map(String::into_bytes)
+    }
+
+    pub fn store(&self, stdout: &[u8]) {
+        let payload = String::from_utf8_lossy(stdout).to_string();
+        if let Err(err) =
+            write_listing_cache(&self.cache_key(), self.kind(), &self.command(), payload)
+        {
+            debug!("Not caching `{}`: {err}", self.command());
+        }
+    }
+
+    /// Drops the cached listing after a change made through the TUI, so the
+    /// next load reflects it.
+    pub fn invalidate(&self) {
+        let result = cache_file_for_account(&self.cache_key(), self.kind()).and_then(|path| {
+            if path.exists() {
+                remove_cache_file(&path, false)
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(err) = result {
+            debug!("Failed to invalidate cached `{}`: {err}", self.command());
+        }
+    }
+}
+
+/// Re-runs `request` on a background thread, updating the cache and sending
+/// the result to `tx`.
+pub fn spawn_refresh(op: SharedOpClient, request: ListingRequest, tx: Sender<ListingRefresh>) {
+    std::thread::spawn(move || {
+        let (result, elapsed) = run_op(op.as_ref(), &request.args());
+        if let Ok(stdout) = &result {
+            request.store(stdout);
+        }
+        let _ = tx.send(ListingRefresh {
+            request,
+            result,
+            elapsed,
+        });
+    });
+}
+
+/// Runs `op` with `args`, returning its stdout (or stderr on failure) and how
+/// long it took. Meant for worker threads, so errors are plain strings.
+pub fn run_op(op: &dyn OpClient, args: &[String]) -> (Result<Vec<u8>, String>, Duration) {
+    let started = Instant::now();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match op.run(&args, None) {
+        Ok(output) if output.success => Ok(output.stdout),
+        Ok(output) => Err(output.stderr),
+        Err(err) => Err(format!("Failed to execute op command: {err}")),
+    };
+    (result, started.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_build_op_list_commands() {
+        assert_eq!(
+            ListingRequest::Accounts.command(),
+            "op account list --format json"
+        );
+        assert_eq!(
+            ListingRequest::Vaults { account_id: None }.command(),
+            "op vault list --format json"
+        );
+        assert_eq!(
+            ListingRequest::Items {
+                account_id: "acct".to_string(),
+                vault_id: "vault".to_string(),
+            }
+            .command(),
+            "op item list --account acct --vault vault --format json"
+        );
+    }
+
+    #[test]
+    fn requests_use_separate_cache_files() {
+        let vaults = ListingRequest::Vaults {
+            account_id: Some("acct".to_string()),
+        };
+        let items = ListingRequest::Items {
+            account_id: "acct".to_string(),
+            vault_id: "vault".to_string(),
+        };
+
+        assert_ne!(vaults.kind(), items.kind());
+        assert_eq!(vaults.cache_key(), "acct");
+        assert_eq!(items.cache_key(), "acct_vault");
+    }
+}
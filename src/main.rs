@@ -1,65 +1,124 @@
+mod action;
+mod agent;
 mod app;
+mod atomic_file;
+mod backoff;
 mod cache;
 mod cli;
 mod command_log;
+mod config_import;
+mod config_migration;
+mod debug_state;
+mod desktop_notification;
 mod event;
-#[cfg(target_os = "macos")]
+mod git_hook;
+mod help;
+mod inject_timing;
+mod inventory;
+mod item_batch;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
 mod keychain;
+mod listing;
+mod make_env;
+mod managed_files;
+mod op_client;
+mod op_error;
+mod op_version;
+mod output_sink;
+mod password_gen;
+mod permissions;
+mod policy;
+mod porcelain;
+mod recent_items;
+mod secret_scan;
+mod shell;
+mod signin;
+mod ssh_keys;
+mod sync_guard;
+mod telemetry;
+mod template_backup;
+mod template_engine;
+mod template_history;
+mod template_link;
+mod template_naming;
+mod template_watch;
+mod text_input;
+mod timestamp;
+mod totp;
 mod ui;
 
 use anyhow::Result;
 use clap::Parser;
 use ratatui::DefaultTerminal;
+use std::time::{Instant, SystemTime};
 
+use action::Action;
 use app::App;
 use cli::{Cli, Command};
 
-fn run_app(terminal: &mut DefaultTerminal) -> Result<()> {
-    let mut app = App::new();
+/// Turns on terminal focus reporting for as long as it's alive.
+struct FocusReporting;
 
-    app.load_config(None)?;
-    app.load_accounts()?;
+impl FocusReporting {
+    fn enable() -> Result<Self> {
+        crossterm::execute!(std::io::stdout(), crossterm::event::EnableFocusChange)?;
+        Ok(Self)
+    }
+}
 
-    if let Some(account_idx) = app
-        .config
-        .as_ref()
-        .and_then(|c| c.default_account_id.as_ref())
-        .and_then(|account_id| {
-            app.accounts
-                .iter()
-                .position(|a| &a.account_uuid == account_id)
-        })
-    {
-        app.selected_account_idx = Some(account_idx);
-        app.account_list_state.select(Some(account_idx));
-    } else if !app.accounts.is_empty() {
-        app.selected_account_idx = Some(0);
-        app.account_list_state.select(Some(0));
+impl Drop for FocusReporting {
+    fn drop(&mut self) {
+        let _ = crossterm::execute!(std::io::stdout(), crossterm::event::DisableFocusChange);
     }
+}
 
-    app.load_vaults()?;
+/// Hands the terminal to `op signin` so its prompts show, then takes it back.
+fn sign_in(terminal: &mut DefaultTerminal, account_id: Option<&str>) -> Result<()> {
+    ratatui::restore();
+    let result = signin::sign_in(&op_client::SubprocessOpClient, account_id);
+    crossterm::terminal::enable_raw_mode()?;
+    crossterm::execute!(std::io::stdout(), crossterm::terminal::EnterAlternateScreen)?;
+    terminal.clear()?;
+    result
+}
 
-    if let Some(vault_idx) = app
-        .selected_account()
-        .map(|a| a.account_uuid.clone())
-        .and_then(|account_id| {
-            app.config
-                .as_ref()
-                .and_then(|c| c.default_vault_per_account.get(&account_id))
-        })
-        .and_then(|vault_id| app.vaults.iter().position(|v| &v.id == vault_id))
-    {
-        app.selected_vault_idx = Some(vault_idx);
-        app.vault_list_state.select(Some(vault_idx));
-    }
+fn run_app(terminal: &mut DefaultTerminal) -> Result<()> {
+    let mut app = App::new();
+    let _focus_reporting = FocusReporting::enable()?;
 
-    if app.selected_account_idx.is_some() && app.selected_vault_idx.is_some() {
-        app.load_vault_items()?;
+    app.load_config(None)?;
+    app.load_recent_items();
+    app.detect_op_version();
+    if let Ok(state_dir) = command_log::state_dir() {
+        app.command_log
+            .persist_to(command_log::log_file_path(&state_dir));
     }
+    app.load_accounts()?;
 
     while !app.should_quit {
+        app.apply_background_results();
+        if app.clipboard_clear_due(Instant::now()) {
+            app.apply(Action::ClearClipboard);
+        }
+        app.remask_if_stale(Instant::now());
+        app.refresh_totp_if_due(SystemTime::now());
         terminal.draw(|frame| ui::render(frame, &mut app))?;
+        if std::mem::take(&mut app.bell_pending) {
+            use std::io::Write;
+            let mut stdout = std::io::stdout();
+            stdout.write_all(b"\x07")?;
+            stdout.flush()?;
+        }
         event::handle_events(&mut app)?;
+        if std::mem::take(&mut app.signin_requested) {
+            let result = sign_in(terminal, app.signin_account().as_deref());
+            app.finish_signin(result);
+        }
+    }
+
+    // Don't leave a copied secret behind after quitting.
+    if app.clipboard_clear.is_some() {
+        app.apply(Action::ClearClipboard);
     }
 
     Ok(())
@@ -72,11 +131,64 @@ fn main() -> Result<()> {
         .filter_level(args.verbosity.into())
         .init();
 
-    match args.command {
+    telemetry::record_command(args.command.as_ref().map_or("tui", Command::name));
+    // `fix-perms` and `doctor` report on permissions themselves. Otherwise
+    // only the TUI and `status` warn, so `env inject` at shell startup stays
+    // quiet and skips the walk unless strict mode has to tighten.
+    let reports_itself = matches!(
+        args.command,
+        Some(Command::FixPerms { .. } | Command::Doctor)
+    );
+    let warns = matches!(args.command, None | Some(Command::Status { .. }));
+    let strict = !reports_itself
+        && confy::load::<app::OpLoadConfig>("op_loader", None)
+            .is_ok_and(|config| config.security.strict_permissions);
+    if strict || warns {
+        permissions::check_at_startup(strict);
+    }
+    let result = run_command(args.command);
+    // Catches files the command created with the default umask.
+    if strict {
+        permissions::check_at_startup(strict);
+    }
+    telemetry::flush();
+    inject_timing::flush();
+    result
+}
+
+fn run_command(command: Option<Command>) -> Result<()> {
+    match command {
         Some(Command::Config { action }) => cli::handle_config_action(action)?,
         Some(Command::Env { action }) => cli::handle_env_action(action)?,
         Some(Command::Cache { action }) => cli::handle_cache_action(action)?,
         Some(Command::Template { action }) => cli::handle_template_action(action)?,
+        Some(Command::Item { action }) => cli::handle_item_action(action)?,
+        Some(Command::Bootstrap {
+            cache_ttl,
+            cache_lock_wait,
+        }) => cli::handle_bootstrap(cache_ttl.as_deref(), &cache_lock_wait)?,
+        Some(Command::Agent {
+            action,
+            idle_timeout,
+            cache_ttl,
+        }) => cli::handle_agent(action, &idle_timeout, &cache_ttl)?,
+        Some(Command::Telemetry { action }) => cli::handle_telemetry_action(action)?,
+        Some(Command::UpgradeConfig { dry_run }) => cli::handle_upgrade_config(dry_run)?,
+        Some(Command::Hook { action }) => cli::handle_hook_action(action)?,
+        Some(Command::Var { action }) => cli::handle_var_action(action)?,
+        Some(Command::File { action }) => cli::handle_file_action(action)?,
+        Some(Command::Ssh { action }) => cli::handle_ssh_action(action)?,
+        Some(Command::Status { porcelain }) => cli::handle_status(porcelain)?,
+        Some(Command::MakeEnv {
+            format,
+            export,
+            cache_ttl,
+            cache_lock_wait,
+            sink,
+        }) => cli::handle_make_env(format, export, cache_ttl.as_deref(), &cache_lock_wait, sink)?,
+        Some(Command::Doctor) => cli::handle_doctor()?,
+        Some(Command::FixPerms { dry_run }) => cli::handle_fix_perms(dry_run)?,
+        Some(Command::Debug { action }) => cli::handle_debug_action(action)?,
         None => ratatui::run(run_app)?,
     }
     Ok(())
@@ -0,0 +1,112 @@
+use anyhow::{Result, bail};
+
+/// Build tool syntax for `make-env`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MakeFormat {
+    /// GNU/BSD make include file (`NAME := value`)
+    #[default]
+    Make,
+    /// justfile assignments (`NAME := "value"`)
+    Just,
+}
+
+/// Renders `vars` (sorted by name) as assignments. With `export`, the
+/// variables are also exported to the environment of every recipe.
+pub fn format_assignments(
+    vars: &[(&str, &str)],
+    format: MakeFormat,
+    export: bool,
+) -> Result<String> {
+    let mut output = String::from("# Generated by op-loader make-env. Do not commit this file.\n");
+    for (name, value) in vars {
+        match format {
+            MakeFormat::Make => {
+                output.push_str(&make_assignment(name, value)?);
+                if export {
+                    output.push_str(&format!("export {name}\n"));
+                }
+            }
+            MakeFormat::Just => {
+                let prefix = if export { "export " } else { "" };
+                output.push_str(&format!("{prefix}{name} := {}\n", just_quote(value)));
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// `$` is doubled everywhere. A one-line `:=` assignment would also strip
+/// leading whitespace, treat `#` as a comment, and join a trailing `\` with
+/// the next line, so values like that go in a `define` block, which keeps its
+/// lines verbatim. `define NAME :=` needs make 3.82, which macOS doesn't
+/// ship, so the block is recursively expanded; `$$` still expands to `$`.
+fn make_assignment(name: &str, value: &str) -> Result<String> {
+    let escaped = value.replace('$', "$$");
+    let needs_block = value.contains(['\n', '\r', '#'])
+        || value.starts_with(char::is_whitespace)
+        || value.ends_with('\\');
+    if !needs_block {
+        return Ok(format!("{name} := {escaped}\n"));
+    }
+
+    if escaped
+        .lines()
+        .any(|line| matches!(line.trim(), "endef" | "define") || line.trim().starts_with("define "))
+    {
+        bail!(
+            "The value of {name} contains a `define` or `endef` line, which make can't represent"
+        );
+    }
+    Ok(format!("define {name}\n{escaped}\nendef\n"))
+}
+
+fn just_quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_make_values() {
+        let vars = [
+            ("PLAIN", "abc"),
+            ("DOLLAR", "pa$$w0rd"),
+            ("HASH", "a#b"),
+            ("MULTI", "line1\nline2"),
+        ];
+
+        let output = format_assignments(&vars, MakeFormat::Make, true).unwrap();
+
+        assert_eq!(
+            output,
+            "# Generated by op-loader make-env. Do not commit this file.\n\
+             PLAIN := abc\nexport PLAIN\n\
+             DOLLAR := pa$$$$w0rd\nexport DOLLAR\n\
+             define HASH\na#b\nendef\nexport HASH\n\
+             define MULTI\nline1\nline2\nendef\nexport MULTI\n"
+        );
+        assert!(format_assignments(&[("BAD", "x\nendef")], MakeFormat::Make, false).is_err());
+    }
+
+    #[test]
+    fn quotes_just_values() {
+        let output =
+            format_assignments(&[("TOKEN", "a\"b\\c\nd")], MakeFormat::Just, false).unwrap();
+
+        assert!(output.ends_with("TOKEN := \"a\\\"b\\\\c\\nd\"\n"));
+    }
+}
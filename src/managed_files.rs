@@ -0,0 +1,140 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::app::ManagedFile;
+use crate::op_client::OpClient;
+use crate::op_version::OpFeature;
+
+/// The `op document get` arguments that print `file`'s contents.
+pub fn document_get_args(file: &ManagedFile) -> [&str; 7] {
+    [
+        "document",
+        "get",
+        &file.item_id,
+        "--vault",
+        &file.vault_id,
+        "--account",
+        &file.account_id,
+    ]
+}
+
+/// Downloads a document item's file with `op document get`.
+pub fn fetch(op: &dyn OpClient, file: &ManagedFile) -> Result<Vec<u8>> {
+    crate::op_version::require(op.version(), OpFeature::Document)?;
+    let output = op
+        .run(&document_get_args(file), None)
+        .with_context(|| format!("Failed to run `op document get {}`", file.item_id))?;
+
+    if !output.success {
+        anyhow::bail!(
+            "op document get failed: {}",
+            crate::op_error::annotate(&output.stderr)
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// Writes `contents` to `target`, readable only by the current user. An
+/// existing file is tightened to 0600 too, not just new ones.
+pub fn place(target: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        if target.exists() {
+            std::fs::set_permissions(target, std::fs::Permissions::from_mode(0o600))
+                .with_context(|| format!("Failed to restrict {}", target.display()))?;
+        }
+    }
+
+    use std::io::Write;
+    options
+        .open(target)
+        .and_then(|mut file| file.write_all(contents))
+        .with_context(|| format!("Failed to write {}", target.display()))
+}
+
+/// Hex-encoded SHA-256 of a placed file, kept in the cache instead of the
+/// file itself.
+pub fn content_hash(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Whether `target` still holds what was placed there, going by its hash.
+pub fn is_current(target: &Path, expected_hash: &str) -> bool {
+    std::fs::read(target).is_ok_and(|contents| content_hash(&contents) == expected_hash)
+}
+
+/// What an account's documents cache entry is built from, so adding,
+/// removing, or repointing a file invalidates it.
+pub fn spec(files: &[(&str, &ManagedFile)]) -> String {
+    let mut lines: Vec<String> = files
+        .iter()
+        .map(|(target, file)| format!("{target}: {}/{}", file.vault_id, file.item_id))
+        .collect();
+    lines.sort();
+    lines.join("\n")
+}
+
+/// Target path to content hash, as stored in the documents cache.
+pub type PlacedHashes = BTreeMap<String, String>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op_client::MockOpClient;
+
+    fn document() -> ManagedFile {
+        ManagedFile {
+            account_id: "acct".to_string(),
+            vault_id: "vault".to_string(),
+            item_id: "kubeconfig".to_string(),
+            title: "Kubeconfig".to_string(),
+        }
+    }
+
+    #[test]
+    fn places_fetched_documents_owner_only() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let target = dir.path().join("kube").join("config");
+        let op = MockOpClient::default().succeed(
+            "document get kubeconfig --vault vault --account acct",
+            "apiVersion: v1\n",
+        );
+
+        let contents = fetch(&op, &document()).unwrap();
+        place(&target, &contents).unwrap();
+
+        assert_eq!(std::fs::read(&target).unwrap(), b"apiVersion: v1\n");
+        assert!(is_current(&target, &content_hash(b"apiVersion: v1\n")));
+        assert!(!is_current(&target, &content_hash(b"edited")));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&target).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode, 0o600);
+        }
+    }
+
+    #[test]
+    fn spec_ignores_order() {
+        let file = document();
+        let a = spec(&[("/a", &file), ("/b", &file)]);
+        let b = spec(&[("/b", &file), ("/a", &file)]);
+
+        assert_eq!(a, b);
+        assert_eq!(a, "/a: vault/kubeconfig\n/b: vault/kubeconfig");
+    }
+}
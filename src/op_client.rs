@@ -0,0 +1,168 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+use crate::op_version::OpVersion;
+
+/// What one `op` run produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpOutput {
+    pub success: bool,
+    pub stdout: Vec<u8>,
+    pub stderr: String,
+}
+
+/// Runs the 1Password CLI. The TUI and the CLI handlers call `op` through
+/// this, so tests can answer in its place without a signed-in `op`.
+pub trait OpClient: Send + Sync {
+    /// Runs `op` with `args`, writing `input` to its stdin if given. `Err`
+    /// means `op` couldn't be run at all; a failing `op` is an `OpOutput`
+    /// with `success` unset.
+    fn run(&self, args: &[&str], input: Option<&[u8]>) -> std::io::Result<OpOutput>;
+
+    /// Runs `op` with the terminal's stdin and stderr attached, so it can
+    /// prompt the user as `op signin` does. Only stdout is captured.
+    fn run_attached(&self, args: &[&str]) -> std::io::Result<OpOutput>;
+
+    /// The CLI's version, from `op --version`.
+    fn version(&self) -> Option<OpVersion> {
+        crate::op_version::probe(self)
+    }
+}
+
+/// An [`OpClient`] that can be handed to worker threads.
+pub type SharedOpClient = Arc<dyn OpClient>;
+
+/// The `op` binary on `PATH`.
+pub struct SubprocessOpClient;
+
+impl OpClient for SubprocessOpClient {
+    fn run(&self, args: &[&str], input: Option<&[u8]>) -> std::io::Result<OpOutput> {
+        let started = Instant::now();
+        let output = match input {
+            None => Command::new("op").args(args).output()?,
+            Some(input) => {
+                let mut child = Command::new("op")
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin.write_all(input)?;
+                }
+                child.wait_with_output()?
+            }
+        };
+        crate::telemetry::record_op_latency(started.elapsed());
+
+        Ok(OpOutput {
+            success: output.status.success(),
+            stdout: output.stdout,
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        })
+    }
+
+    /// Not counted in the latency telemetry: most of the time is spent
+    /// waiting on the user.
+    fn run_attached(&self, args: &[&str]) -> std::io::Result<OpOutput> {
+        let output = Command::new("op")
+            .args(args)
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .stdout(Stdio::piped())
+            .output()?;
+
+        Ok(OpOutput {
+            success: output.status.success(),
+            stdout: output.stdout,
+            stderr: String::new(),
+        })
+    }
+
+    /// Probed once per process; the binary doesn't change under us.
+    fn version(&self) -> Option<OpVersion> {
+        static VERSION: OnceLock<Option<OpVersion>> = OnceLock::new();
+        *VERSION.get_or_init(|| crate::op_version::probe(self))
+    }
+}
+
+/// Answers `op` calls from canned responses, keyed by the arguments joined
+/// with spaces, and records every call. Unknown calls fail like `op` would.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MockOpClient {
+    responses: std::sync::Mutex<std::collections::HashMap<String, OpOutput>>,
+    calls: std::sync::Mutex<Vec<String>>,
+}
+
+#[cfg(test)]
+impl MockOpClient {
+    pub fn succeed(self, args: &str, stdout: &str) -> Self {
+        self.respond(args, true, stdout.as_bytes().to_vec(), String::new())
+    }
+
+    pub fn fail(self, args: &str, stderr: &str) -> Self {
+        self.respond(args, false, Vec::new(), stderr.to_string())
+    }
+
+    fn respond(self, args: &str, success: bool, stdout: Vec<u8>, stderr: String) -> Self {
+        self.responses.lock().unwrap().insert(
+            args.to_string(),
+            OpOutput {
+                success,
+                stdout,
+                stderr,
+            },
+        );
+        self
+    }
+
+    /// The arguments of every call so far, joined with spaces.
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl OpClient for MockOpClient {
+    fn run(&self, args: &[&str], _input: Option<&[u8]>) -> std::io::Result<OpOutput> {
+        let key = args.join(" ");
+        self.calls.lock().unwrap().push(key.clone());
+        Ok(self
+            .responses
+            .lock()
+            .unwrap()
+            .get(&key)
+            .cloned()
+            .unwrap_or_else(|| OpOutput {
+                success: false,
+                stdout: Vec::new(),
+                stderr: format!("[ERROR] unexpected `op {key}`"),
+            }))
+    }
+
+    fn run_attached(&self, args: &[&str]) -> std::io::Result<OpOutput> {
+        self.run(args, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_answers_known_calls_and_records_them() {
+        let op = MockOpClient::default().succeed("whoami", "me");
+
+        let known = op.run(&["whoami"], None).unwrap();
+        let unknown = op.run(&["vault", "list"], None).unwrap();
+
+        assert!(known.success);
+        assert_eq!(known.stdout, b"me");
+        assert!(!unknown.success);
+        assert!(unknown.stderr.contains("op vault list"));
+        assert_eq!(op.calls(), ["whoami", "vault list"]);
+    }
+}
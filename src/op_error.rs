@@ -0,0 +1,143 @@
+/// What went wrong in a failed `op` call, going by its stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpErrorKind {
+    /// The session expired or the account was never signed in.
+    NotSignedIn,
+    VaultNotFound,
+    ItemNotFound,
+    RateLimited,
+    /// 1Password couldn't be reached.
+    Network,
+    Other,
+}
+
+const NOT_SIGNED_IN: &[&str] = &[
+    "not currently signed in",
+    "not signed in",
+    "session expired",
+    "sign in to create a new session",
+    "you are not signed in",
+];
+const RATE_LIMITED: &[&str] = &["too many requests", "rate limit", "(429)"];
+const NETWORK: &[&str] = &[
+    "connection refused",
+    "connection reset",
+    "network is unreachable",
+    "no such host",
+    "dial tcp",
+    "i/o timeout",
+    "tls handshake",
+    "could not connect",
+];
+const VAULT_NOT_FOUND: &[&str] = &["isn't a vault", "no vault found", "vault not found"];
+const ITEM_NOT_FOUND: &[&str] = &["isn't an item", "no item found", "item not found"];
+
+/// Sorts `op`'s stderr into an [`OpErrorKind`]. Earlier kinds win, so an
+/// expired session that also mentions a missing item counts as signed out.
+pub fn classify(stderr: &str) -> OpErrorKind {
+    let stderr = stderr.to_lowercase();
+    let matches = |patterns: &[&str]| patterns.iter().any(|pattern| stderr.contains(pattern));
+
+    if matches(NOT_SIGNED_IN) {
+        OpErrorKind::NotSignedIn
+    } else if matches(RATE_LIMITED) {
+        OpErrorKind::RateLimited
+    } else if matches(NETWORK) {
+        OpErrorKind::Network
+    } else if matches(ITEM_NOT_FOUND) {
+        OpErrorKind::ItemNotFound
+    } else if matches(VAULT_NOT_FOUND) {
+        OpErrorKind::VaultNotFound
+    } else {
+        OpErrorKind::Other
+    }
+}
+
+impl OpErrorKind {
+    /// A one-line explanation to show above `op`'s own message.
+    pub const fn hint(self) -> Option<&'static str> {
+        match self {
+            Self::NotSignedIn => Some("Not signed in to 1Password; sign in and try again"),
+            Self::VaultNotFound => {
+                Some("Vault not found; it may have been renamed, deleted, or unshared")
+            }
+            Self::ItemNotFound => {
+                Some("Item not found; it may have been renamed, moved, or deleted")
+            }
+            Self::RateLimited => {
+                Some("1Password is rate limiting requests; wait a minute and retry")
+            }
+            Self::Network => Some("Couldn't reach 1Password; check your network connection"),
+            Self::Other => None,
+        }
+    }
+
+    /// Whether the failure says something about the account or the
+    /// connection, so further calls for the account should pause. A missing
+    /// vault or item only concerns that one lookup.
+    pub const fn pauses_account(self) -> bool {
+        !matches!(self, Self::VaultNotFound | Self::ItemNotFound)
+    }
+}
+
+/// `stderr` with the hint for its kind as a first line, for the command
+/// log's one-line view and CLI warnings. Unrecognized errors are unchanged.
+pub fn annotate(stderr: &str) -> String {
+    let stderr = stderr.trim_end();
+    match classify(stderr).hint() {
+        Some(hint) => format!("{hint}\n{stderr}"),
+        None => stderr.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_common_op_errors() {
+        let cases = [
+            (
+                "[ERROR] 2024/01/01 12:00:00 You are not currently signed in. Please run `op signin --help` for instructions",
+                OpErrorKind::NotSignedIn,
+            ),
+            (
+                "[ERROR] 2024/01/01 12:00:00 \"GitHub\" isn't an item in the \"Private\" vault. Specify the item with its UUID, name, or domain.",
+                OpErrorKind::ItemNotFound,
+            ),
+            (
+                "[ERROR] 2024/01/01 12:00:00 \"Work\" isn't a vault in this account. Specify the vault with its ID or name.",
+                OpErrorKind::VaultNotFound,
+            ),
+            (
+                "[ERROR] 2024/01/01 12:00:00 Too many requests (429). Try again later.",
+                OpErrorKind::RateLimited,
+            ),
+            (
+                "[ERROR] 2024/01/01 12:00:00 dial tcp: lookup my.1password.com: no such host",
+                OpErrorKind::Network,
+            ),
+            (
+                "[ERROR] 2024/01/01 12:00:00 something else",
+                OpErrorKind::Other,
+            ),
+        ];
+
+        for (stderr, kind) in cases {
+            assert_eq!(classify(stderr), kind, "{stderr}");
+        }
+    }
+
+    #[test]
+    fn annotates_recognized_errors_only() {
+        let annotated = annotate("[ERROR] \"GitHub\" isn't an item in the \"Private\" vault\n");
+
+        assert_eq!(
+            annotated,
+            "Item not found; it may have been renamed, moved, or deleted\n[ERROR] \"GitHub\" isn't an item in the \"Private\" vault"
+        );
+        assert_eq!(annotate("[ERROR] odd\n"), "[ERROR] odd");
+        assert!(!OpErrorKind::ItemNotFound.pauses_account());
+        assert!(OpErrorKind::Network.pauses_account());
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+
+use anyhow::Result;
+
+use crate::op_client::OpClient;
+
+/// A 1Password CLI version as printed by `op --version`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OpVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl OpVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses `2.24.0`, `2.30.0-beta.01` and similar. Missing minor or patch
+    /// numbers count as zero.
+    pub fn parse(output: &str) -> Option<Self> {
+        let version = output.split_whitespace().next()?;
+        let version = version.strip_prefix('v').unwrap_or(version);
+        let release = version.split(['-', '+']).next()?;
+        let mut parts = release.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().map_or(Some(0), |part| part.parse().ok())?;
+        let patch = parts.next().map_or(Some(0), |part| part.parse().ok())?;
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl fmt::Display for OpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// `op` behavior that op-loader relies on and that older CLIs lack. CLI 1.x
+/// used a different command layout altogether (`op get item`), so everything
+/// here needs at least 2.0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpFeature {
+    /// `--format json` on `account list`, `vault list`, `item list` and `item get`.
+    JsonOutput,
+    /// `op inject`, used to resolve vars and render templates.
+    Inject,
+    /// `op read --no-newline`, used to test and copy single references.
+    Read,
+    /// `op item get --otp`.
+    Otp,
+    /// `op document get`, used to place managed files.
+    Document,
+}
+
+impl OpFeature {
+    pub const ALL: [Self; 5] = [
+        Self::JsonOutput,
+        Self::Inject,
+        Self::Read,
+        Self::Otp,
+        Self::Document,
+    ];
+
+    pub const fn min_version(self) -> OpVersion {
+        match self {
+            Self::JsonOutput | Self::Inject | Self::Read | Self::Otp | Self::Document => {
+                OpVersion::new(2, 0, 0)
+            }
+        }
+    }
+
+    pub const fn describe(self) -> &'static str {
+        match self {
+            Self::JsonOutput => "`--format json` listings",
+            Self::Inject => "`op inject`",
+            Self::Read => "`op read`",
+            Self::Otp => "one-time passwords (`op item get --otp`)",
+            Self::Document => "managed files (`op document get`)",
+        }
+    }
+}
+
+/// Runs `op --version`. `None` if `op` couldn't be run or printed something
+/// unexpected.
+pub fn probe<C: OpClient + ?Sized>(op: &C) -> Option<OpVersion> {
+    let output = op.run(&["--version"], None).ok()?;
+    if !output.success {
+        return None;
+    }
+    OpVersion::parse(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Fails with "op >= X required for Y" when `version` is known to be too old.
+/// An unknown version is let through; `op` then reports problems itself.
+pub fn require(version: Option<OpVersion>, feature: OpFeature) -> Result<()> {
+    let min = feature.min_version();
+    match version {
+        Some(version) if version < min => anyhow::bail!(
+            "op >= {}.{} required for {} (found {version}); update the 1Password CLI",
+            min.major,
+            min.minor,
+            feature.describe()
+        ),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::op_client::MockOpClient;
+
+    #[test]
+    fn parses_release_and_prerelease_versions() {
+        assert_eq!(OpVersion::parse("2.24.0\n"), Some(OpVersion::new(2, 24, 0)));
+        assert_eq!(
+            OpVersion::parse("2.30.0-beta.01"),
+            Some(OpVersion::new(2, 30, 0))
+        );
+        assert_eq!(OpVersion::parse("v1.12"), Some(OpVersion::new(1, 12, 0)));
+        assert_eq!(OpVersion::parse("not a version"), None);
+    }
+
+    #[test]
+    fn rejects_features_newer_than_the_cli() {
+        let old = OpVersion::parse("1.12.4");
+
+        let err = require(old, OpFeature::Inject).unwrap_err().to_string();
+
+        assert!(
+            err.starts_with("op >= 2.0 required for `op inject`"),
+            "{err}"
+        );
+        assert!(err.contains("found 1.12.4"));
+        assert!(require(OpVersion::parse("2.24.0"), OpFeature::Inject).is_ok());
+        assert!(require(None, OpFeature::Inject).is_ok());
+    }
+
+    #[test]
+    fn probes_op_version() {
+        let op = MockOpClient::default().succeed("--version", "2.24.0\n");
+
+        assert_eq!(probe(&op), Some(OpVersion::new(2, 24, 0)));
+        assert_eq!(probe(&MockOpClient::default()), None);
+    }
+}
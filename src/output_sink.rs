@@ -0,0 +1,218 @@
+use anyhow::{Context, Result, bail};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Where `env` commands write their shell statements.
+///
+/// Wrappers can route the export stream to a dedicated descriptor or fifo so
+/// it never mixes with other program output on stdout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputSink {
+    Stdout,
+    Fd(u32),
+    /// A fifo or file, refused inside a cloud-synced folder unless it's
+    /// under one of `allowed_sync_paths`.
+    Path {
+        path: PathBuf,
+        allowed_sync_paths: Vec<String>,
+    },
+}
+
+impl OutputSink {
+    pub fn new(fd: Option<u32>, out: Option<PathBuf>, allowed_sync_paths: Vec<String>) -> Self {
+        match (fd, out) {
+            (Some(fd), _) => Self::Fd(fd),
+            (None, Some(path)) => Self::Path {
+                path,
+                allowed_sync_paths,
+            },
+            (None, None) => Self::Stdout,
+        }
+    }
+
+    pub fn write(&self, output: &str) -> Result<()> {
+        match self {
+            Self::Stdout => {
+                print!("{output}");
+                Ok(())
+            }
+            Self::Fd(fd) => write_to_fd(*fd, output),
+            Self::Path {
+                path,
+                allowed_sync_paths,
+            } => {
+                crate::sync_guard::ensure_allowed(path, allowed_sync_paths)?;
+                write_to_path(path, output)
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn write_to_fd(fd: u32, output: &str) -> Result<()> {
+    if fd <= 2 {
+        bail!("--fd must not be stdin, stdout, or stderr (got {fd})");
+    }
+
+    let path = PathBuf::from(format!("/dev/fd/{fd}"));
+    let metadata =
+        std::fs::metadata(&path).with_context(|| format!("File descriptor {fd} is not open"))?;
+    if metadata.is_file() {
+        check_private(&path, &metadata)?;
+    }
+
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open file descriptor {fd} for writing"))?;
+    file.write_all(output.as_bytes())
+        .with_context(|| format!("Failed to write to file descriptor {fd}"))
+}
+
+#[cfg(not(unix))]
+fn write_to_fd(_fd: u32, _output: &str) -> Result<()> {
+    bail!("--fd is only supported on Unix-like systems; use --out instead.");
+}
+
+/// Writes to an existing fifo or regular file, or creates a private file.
+/// Symlinks are refused so the stream can't be redirected elsewhere.
+fn write_to_path(path: &Path, output: &str) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true);
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            bail!("Refusing to write to symlink: {}", path.display());
+        }
+        Ok(metadata) => {
+            check_private(path, &metadata)?;
+            if metadata.is_file() {
+                options.truncate(true);
+            } else if !is_fifo(&metadata) {
+                bail!(
+                    "Output target is not a regular file or fifo: {}",
+                    path.display()
+                );
+            }
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            options.create_new(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to inspect {}", path.display()));
+        }
+    }
+
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to open output target: {}", path.display()))?;
+    file.write_all(output.as_bytes())
+        .with_context(|| format!("Failed to write to output target: {}", path.display()))
+}
+
+#[cfg(unix)]
+fn is_fifo(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    metadata.file_type().is_fifo()
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_metadata: &std::fs::Metadata) -> bool {
+    false
+}
+
+/// Secrets must not land somewhere other users can read or write.
+#[cfg(unix)]
+fn check_private(path: &Path, metadata: &std::fs::Metadata) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        bail!(
+            "Output target {} is accessible by other users (mode {mode:o}); run `chmod 600` on it first",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_private(_path: &Path, _metadata: &std::fs::Metadata) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn sink(path: &Path) -> OutputSink {
+        OutputSink::new(None, Some(path.to_path_buf()), Vec::new())
+    }
+
+    #[test]
+    fn creates_private_file() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("exports");
+
+        sink(&path).write("export A='1'\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "export A='1'\n");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+    }
+
+    #[test]
+    fn refuses_world_readable_file() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("exports");
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let err = sink(&path).write("export A='1'\n").unwrap_err();
+
+        assert!(err.to_string().contains("accessible by other users"));
+    }
+
+    #[test]
+    fn refuses_symlink() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let target = dir.path().join("target");
+        let link = dir.path().join("link");
+        std::fs::write(&target, "").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        assert!(sink(&link).write("export A='1'\n").is_err());
+    }
+
+    #[test]
+    fn refuses_cloud_synced_folder() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let synced = dir.path().join("Dropbox");
+        std::fs::create_dir(&synced).unwrap();
+        let path = synced.join("exports");
+
+        let err = sink(&path).write("export A='1'\n").unwrap_err();
+
+        assert!(err.to_string().contains("inside a Dropbox folder"));
+        assert!(!path.exists());
+        OutputSink::new(
+            None,
+            Some(path.clone()),
+            vec![synced.to_string_lossy().to_string()],
+        )
+        .write("export A='1'\n")
+        .unwrap();
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn refuses_standard_descriptors() {
+        assert!(OutputSink::Fd(1).write("export A='1'\n").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use rand_core::RngCore;
+
+const LETTERS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+/// The symbols 1Password's generator uses.
+const SYMBOLS: &str = "!#$%&()*+,-./:;<=>?@[]^_{|}~";
+
+pub const MIN_LENGTH: usize = 8;
+pub const MAX_LENGTH: usize = 64;
+
+/// What `op item create --generate-password=<recipe>` calls a recipe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recipe {
+    pub length: usize,
+    pub digits: bool,
+    pub symbols: bool,
+}
+
+impl Default for Recipe {
+    fn default() -> Self {
+        Self {
+            length: 32,
+            digits: true,
+            symbols: true,
+        }
+    }
+}
+
+impl Recipe {
+    /// The equivalent `--generate-password` value, e.g. `letters,digits,32`.
+    pub fn op_recipe(self) -> String {
+        let mut parts = vec!["letters".to_string()];
+        if self.digits {
+            parts.push("digits".to_string());
+        }
+        if self.symbols {
+            parts.push("symbols".to_string());
+        }
+        parts.push(self.length.to_string());
+        parts.join(",")
+    }
+
+    pub fn adjust_length(&mut self, delta: isize) {
+        self.length = self
+            .length
+            .saturating_add_signed(delta)
+            .clamp(MIN_LENGTH, MAX_LENGTH);
+    }
+
+    fn classes(self) -> Vec<&'static str> {
+        let mut classes = vec![LETTERS];
+        if self.digits {
+            classes.push(DIGITS);
+        }
+        if self.symbols {
+            classes.push(SYMBOLS);
+        }
+        classes
+    }
+}
+
+/// A password from the OS random source with at least one character from
+/// every enabled class.
+pub fn generate(recipe: Recipe) -> String {
+    generate_with(recipe, &mut rand_core::OsRng)
+}
+
+fn generate_with(recipe: Recipe, rng: &mut impl RngCore) -> String {
+    let classes = recipe.classes();
+    let alphabet: Vec<char> = classes.iter().flat_map(|class| class.chars()).collect();
+    let length = recipe.length.max(classes.len());
+
+    let mut password: Vec<char> = classes
+        .iter()
+        .map(|class| {
+            let chars: Vec<char> = class.chars().collect();
+            chars[uniform(rng, chars.len())]
+        })
+        .collect();
+    while password.len() < length {
+        password.push(alphabet[uniform(rng, alphabet.len())]);
+    }
+
+    // Fisher-Yates, so the guaranteed characters aren't always up front.
+    for i in (1..password.len()).rev() {
+        password.swap(i, uniform(rng, i + 1));
+    }
+    password.into_iter().collect()
+}
+
+/// A uniform index below `bound`, rejecting the values that would bias a
+/// plain modulo.
+fn uniform(rng: &mut impl RngCore, bound: usize) -> usize {
+    let bound = u32::try_from(bound).expect("alphabet fits in u32");
+    let zone = u32::MAX - u32::MAX % bound;
+    loop {
+        let value = rng.next_u32();
+        if value < zone {
+            return (value % bound) as usize;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn includes_every_enabled_class() {
+        for _ in 0..50 {
+            let password = generate(Recipe {
+                length: MIN_LENGTH,
+                digits: true,
+                symbols: true,
+            });
+            assert_eq!(password.chars().count(), MIN_LENGTH);
+            assert!(password.chars().any(|c| LETTERS.contains(c)));
+            assert!(password.chars().any(|c| DIGITS.contains(c)));
+            assert!(password.chars().any(|c| SYMBOLS.contains(c)));
+        }
+
+        let letters_only = generate(Recipe {
+            length: 20,
+            digits: false,
+            symbols: false,
+        });
+        assert!(letters_only.chars().all(|c| LETTERS.contains(c)));
+    }
+
+    #[test]
+    fn describes_the_op_recipe() {
+        let mut recipe = Recipe {
+            length: 20,
+            digits: true,
+            symbols: false,
+        };
+        assert_eq!(recipe.op_recipe(), "letters,digits,20");
+
+        recipe.adjust_length(-100);
+        assert_eq!(recipe.length, MIN_LENGTH);
+        recipe.adjust_length(100);
+        assert_eq!(recipe.length, MAX_LENGTH);
+    }
+}
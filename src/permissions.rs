@@ -0,0 +1,223 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::path::PathBuf;
+
+/// A path that other users on the machine can read or write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionIssue {
+    pub path: PathBuf,
+    pub mode: u32,
+}
+
+impl PermissionIssue {
+    /// The mode with the group and other bits cleared; the owner's bits,
+    /// such as exec on a script, are kept as they are.
+    pub const fn expected_mode(&self) -> u32 {
+        self.mode & 0o700
+    }
+}
+
+impl fmt::Display for PermissionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is {:o} (expected {:o})",
+            self.path.display(),
+            self.mode,
+            self.expected_mode()
+        )
+    }
+}
+
+/// The config file, templates directory, cache directory, and the state
+/// directory holding the command log. Missing paths are skipped by the audit.
+pub fn audited_paths() -> Result<Vec<PathBuf>> {
+    let config_path = confy::get_configuration_file_path("op_loader", None)
+        .context("Failed to get config path")?;
+    let templates_dir = config_path
+        .parent()
+        .context("Config path has no parent directory")?
+        .join("templates");
+    Ok(vec![
+        config_path,
+        templates_dir,
+        crate::cache::cache_dir()?,
+        crate::command_log::state_dir()?,
+    ])
+}
+
+/// Walks `roots` and returns every file or directory that is group or world
+/// accessible. Symlinks are neither followed nor reported.
+#[cfg(unix)]
+pub fn audit(roots: &[PathBuf]) -> Result<Vec<PermissionIssue>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mut issues = Vec::new();
+    let mut pending: Vec<PathBuf> = roots.to_vec();
+    while let Some(path) = pending.pop() {
+        let Ok(metadata) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if metadata.file_type().is_symlink() {
+            continue;
+        }
+
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            issues.push(PermissionIssue {
+                path: path.clone(),
+                mode,
+            });
+        }
+
+        if metadata.is_dir() {
+            for entry in std::fs::read_dir(&path)
+                .with_context(|| format!("Failed to read directory: {}", path.display()))?
+            {
+                pending.push(entry?.path());
+            }
+        }
+    }
+
+    issues.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(issues)
+}
+
+/// Windows keeps these paths in per-user profile directories, and the audit
+/// only understands unix modes.
+#[cfg(not(unix))]
+pub fn audit(_roots: &[PathBuf]) -> Result<Vec<PermissionIssue>> {
+    Ok(Vec::new())
+}
+
+#[cfg(unix)]
+pub fn fix(issue: &PermissionIssue) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(
+        &issue.path,
+        std::fs::Permissions::from_mode(issue.expected_mode()),
+    )
+    .with_context(|| format!("Failed to set permissions on {}", issue.path.display()))
+}
+
+#[cfg(not(unix))]
+pub fn fix(_issue: &PermissionIssue) -> Result<()> {
+    Ok(())
+}
+
+/// Tightens every path the audit flags and returns what was changed.
+pub fn fix_all(roots: &[PathBuf]) -> Result<Vec<PermissionIssue>> {
+    let issues = audit(roots)?;
+    for issue in &issues {
+        fix(issue)?;
+    }
+    Ok(issues)
+}
+
+/// Runs before the TUI and `status`, and before and after every command
+/// with `security.strict_permissions`. In strict mode the paths are
+/// tightened on the spot; otherwise a one-line warning points at
+/// `fix-perms`. Audit failures never stop the command itself.
+pub fn check_at_startup(strict: bool) {
+    let result = audited_paths().and_then(|roots| {
+        if strict {
+            fix_all(&roots)
+        } else {
+            audit(&roots)
+        }
+    });
+
+    match result {
+        Ok(issues) if issues.is_empty() => {}
+        Ok(issues) if strict => {
+            for issue in &issues {
+                log::info!("Tightened permissions: {issue}");
+            }
+        }
+        Ok(issues) => eprintln!("{}", startup_warning(&issues)),
+        Err(err) => log::debug!("Permission audit failed: {err:#}"),
+    }
+}
+
+fn startup_warning(issues: &[PermissionIssue]) -> String {
+    let first = &issues[0];
+    match issues.len() {
+        1 => format!(
+            "warning: {} is readable by other users; run `op-loader fix-perms` to tighten it",
+            first.path.display()
+        ),
+        count => format!(
+            "warning: {count} op-loader paths (e.g. {}) are readable by other users; run `op-loader fix-perms` to tighten them",
+            first.path.display()
+        ),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    fn set_mode(path: &Path, mode: u32) {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn finds_and_fixes_loose_permissions() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let root = dir.path().join("templates");
+        std::fs::create_dir(&root).unwrap();
+        let loose = root.join("env.tmpl");
+        let tight = root.join("other.tmpl");
+        let script = root.join("hook.sh");
+        std::fs::write(&loose, "{{TOKEN}}").unwrap();
+        std::fs::write(&tight, "{{TOKEN}}").unwrap();
+        std::fs::write(&script, "#!/bin/sh").unwrap();
+        set_mode(&root, 0o755);
+        set_mode(&loose, 0o644);
+        set_mode(&tight, 0o600);
+        set_mode(&script, 0o755);
+        let roots = vec![root.clone(), dir.path().join("missing")];
+
+        let issues = audit(&roots).unwrap();
+        assert_eq!(
+            issues,
+            vec![
+                PermissionIssue {
+                    path: root.clone(),
+                    mode: 0o755,
+                },
+                PermissionIssue {
+                    path: loose.clone(),
+                    mode: 0o644,
+                },
+                PermissionIssue {
+                    path: script.clone(),
+                    mode: 0o755,
+                },
+            ]
+        );
+
+        assert_eq!(fix_all(&roots).unwrap().len(), 3);
+        assert!(audit(&roots).unwrap().is_empty());
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&loose), 0o600);
+        assert_eq!(mode(&script), 0o700);
+    }
+
+    #[test]
+    fn warning_names_the_fix() {
+        let issue = PermissionIssue {
+            path: PathBuf::from("/tmp/op_loader/default-config.toml"),
+            mode: 0o644,
+        };
+
+        assert_eq!(
+            issue.to_string(),
+            "/tmp/op_loader/default-config.toml is 644 (expected 600)"
+        );
+        assert!(startup_warning(&[issue]).contains("op-loader fix-perms"));
+    }
+}
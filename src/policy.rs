@@ -0,0 +1,252 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::app::{InjectVarConfig, OpLoadConfig};
+
+const POLICY_PATH_ENV: &str = "OP_LOADER_POLICY";
+
+/// Organization policy constraining which mappings op-loader accepts.
+///
+/// Patterns are globs where `*` matches any run of characters. Vault patterns
+/// are matched against the vault segment of the `op://vault/item/field`
+/// reference.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Policy {
+    #[serde(default)]
+    pub allowed_vault_patterns: Vec<String>,
+    #[serde(default)]
+    pub forbidden_var_patterns: Vec<String>,
+    #[serde(default)]
+    pub no_cache_prefixes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyViolation {
+    pub var_name: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.var_name, self.message)
+    }
+}
+
+impl Policy {
+    /// Checks a mapping under both its own name and the name it is exported
+    /// as, so a prefix can't get a var past the name patterns.
+    pub fn check_mapping(&self, var_name: &str, var: &InjectVarConfig) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        let op_reference = var.op_reference.as_str();
+
+        let exported = var.exported_name(var_name);
+        if let Some((name, pattern)) = [var_name, exported.as_str()].into_iter().find_map(|name| {
+            self.forbidden_var_patterns
+                .iter()
+                .find(|pattern| glob_match(pattern, name))
+                .map(|pattern| (name, pattern))
+        }) {
+            violations.push(PolicyViolation {
+                var_name: name.to_string(),
+                message: format!("variable name matches forbidden pattern `{pattern}`"),
+            });
+        }
+
+        if !self.allowed_vault_patterns.is_empty() {
+            let vault = reference_vault(op_reference).unwrap_or_default();
+            if !self
+                .allowed_vault_patterns
+                .iter()
+                .any(|pattern| glob_match(pattern, vault))
+            {
+                violations.push(PolicyViolation {
+                    var_name: var_name.to_string(),
+                    message: format!("vault `{vault}` is not in the allowed vault patterns"),
+                });
+            }
+        }
+
+        violations
+    }
+
+    pub fn check_config(&self, config: &OpLoadConfig) -> Vec<PolicyViolation> {
+        let mut var_names: Vec<&String> = config.inject_vars.keys().collect();
+        var_names.sort();
+
+        var_names
+            .into_iter()
+            .flat_map(|var_name| self.check_mapping(var_name, &config.inject_vars[var_name]))
+            .collect()
+    }
+
+    pub fn forbids_cache(&self, var_name: &str) -> bool {
+        self.no_cache_prefixes
+            .iter()
+            .any(|prefix| var_name.starts_with(prefix.as_str()))
+    }
+
+    /// Whether a configured mapping may not be cached, going by both its own
+    /// name and the name it is exported as.
+    pub fn forbids_caching(&self, var_name: &str, var: &InjectVarConfig) -> bool {
+        self.forbids_cache(var_name) || self.forbids_cache(&var.exported_name(var_name))
+    }
+}
+
+/// `$OP_LOADER_POLICY`, or `policy.toml` next to the config file.
+pub fn policy_path() -> Result<PathBuf> {
+    if let Some(path) = std::env::var_os(POLICY_PATH_ENV) {
+        return Ok(PathBuf::from(path));
+    }
+
+    let config_path = confy::get_configuration_file_path("op_loader", None)
+        .context("Failed to get config path")?;
+    let config_dir = config_path
+        .parent()
+        .context("Config path has no parent directory")?;
+    Ok(config_dir.join("policy.toml"))
+}
+
+/// Loads the policy file if one exists; no file means no constraints.
+pub fn load_policy() -> Result<Option<Policy>> {
+    let path = policy_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let policy = confy::load_path(&path)
+        .with_context(|| format!("Failed to load policy file: {}", path.display()))?;
+    Ok(Some(policy))
+}
+
+fn reference_vault(op_reference: &str) -> Option<&str> {
+    op_reference
+        .strip_prefix("op://")?
+        .split('/')
+        .next()
+        .filter(|vault| !vault.is_empty())
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == text;
+    };
+
+    let Some(mut remaining) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut parts: Vec<&str> = rest.split('*').collect();
+    let last = parts.pop().unwrap_or_default();
+
+    for part in parts {
+        match remaining.find(part) {
+            Some(idx) => remaining = &remaining[idx + part.len()..],
+            None => return false,
+        }
+    }
+
+    remaining.len() >= last.len() && remaining.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(op_reference: &str) -> InjectVarConfig {
+        InjectVarConfig {
+            account_id: "acct".to_string(),
+            op_reference: op_reference.to_string(),
+            prefix: None,
+        }
+    }
+
+    fn policy() -> Policy {
+        Policy {
+            allowed_vault_patterns: vec!["Team-*".to_string(), "Shared".to_string()],
+            forbidden_var_patterns: vec!["AWS_*_SECRET*".to_string()],
+            no_cache_prefixes: vec!["PROD_".to_string()],
+        }
+    }
+
+    #[test]
+    fn glob_matches_wildcards() {
+        assert!(glob_match("Team-*", "Team-Backend"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("AWS_*_SECRET*", "AWS_PROD_SECRET_KEY"));
+        assert!(glob_match("a*b*a", "aba"));
+        assert!(!glob_match("ab*ba", "aba"));
+        assert!(!glob_match("Team-*", "Personal"));
+        assert!(!glob_match("Shared", "Shared2"));
+    }
+
+    #[test]
+    fn accepts_allowed_mapping() {
+        assert!(
+            policy()
+                .check_mapping("GITHUB_TOKEN", &mapping("op://Team-Backend/GitHub/token"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn rejects_forbidden_var_and_disallowed_vault() {
+        let violations =
+            policy().check_mapping("AWS_PROD_SECRET_KEY", &mapping("op://Personal/AWS/secret"));
+
+        assert_eq!(violations.len(), 2);
+        assert!(violations[0].message.contains("forbidden pattern"));
+        assert!(violations[1].message.contains("`Personal`"));
+    }
+
+    #[test]
+    fn empty_policy_allows_everything() {
+        assert!(
+            Policy::default()
+                .check_mapping("ANY", &mapping("op://Anywhere/item/field"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn check_config_reports_violations_in_name_order() {
+        let config = OpLoadConfig {
+            inject_vars: std::collections::HashMap::from([
+                ("ZED".to_string(), mapping("op://Personal/z/f")),
+                ("ALPHA".to_string(), mapping("op://Private/a/f")),
+                ("OK".to_string(), mapping("op://Shared/o/f")),
+            ]),
+            ..Default::default()
+        };
+
+        let names: Vec<String> = policy()
+            .check_config(&config)
+            .into_iter()
+            .map(|v| v.var_name)
+            .collect();
+
+        assert_eq!(names, vec!["ALPHA", "ZED"]);
+    }
+
+    #[test]
+    fn no_cache_prefixes_match_var_names() {
+        assert!(policy().forbids_cache("PROD_DB_PASSWORD"));
+        assert!(!policy().forbids_cache("DEV_DB_PASSWORD"));
+    }
+
+    #[test]
+    fn checks_prefixed_vars_under_their_exported_names() {
+        let var = |prefix: &str| InjectVarConfig {
+            prefix: Some(prefix.to_string()),
+            ..mapping("op://Shared/aws/secret")
+        };
+
+        assert!(policy().forbids_caching("TOKEN", &var("PROD_")));
+        assert!(!policy().forbids_caching("TOKEN", &var("DEV_")));
+
+        let violations = policy().check_mapping("PROD_SECRET", &var("AWS_"));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].var_name, "AWS_PROD_SECRET");
+        assert!(violations[0].message.contains("forbidden pattern"));
+    }
+}
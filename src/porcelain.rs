@@ -0,0 +1,46 @@
+/// Joins fields into one `--porcelain` record. Fields are tab-separated, and
+/// tabs, newlines, and backslashes inside a field are escaped as `\t`, `\n`,
+/// and `\\`, so a record is always exactly one line.
+pub fn record<S: AsRef<str>>(fields: &[S]) -> String {
+    let mut line = String::new();
+    for (idx, field) in fields.iter().enumerate() {
+        if idx > 0 {
+            line.push('\t');
+        }
+        for ch in field.as_ref().chars() {
+            match ch {
+                '\\' => line.push_str("\\\\"),
+                '\t' => line.push_str("\\t"),
+                '\n' => line.push_str("\\n"),
+                '\r' => line.push_str("\\r"),
+                _ => line.push(ch),
+            }
+        }
+    }
+    line
+}
+
+/// Records joined with trailing newlines, ready to print.
+pub fn records<S: AsRef<str>>(rows: impl IntoIterator<Item = Vec<S>>) -> String {
+    rows.into_iter().map(|row| record(&row) + "\n").collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_tab_separated_and_escaped() {
+        assert_eq!(record(&["A", "b c", ""]), "A\tb c\t");
+        assert_eq!(
+            record(&["tab\there", "line\nbreak", "back\\slash"]),
+            "tab\\there\tline\\nbreak\tback\\\\slash"
+        );
+    }
+
+    #[test]
+    fn records_end_with_newlines() {
+        assert_eq!(records([vec!["a", "1"], vec!["b", "2"]]), "a\t1\nb\t2\n");
+        assert_eq!(records(Vec::<Vec<String>>::new()), "");
+    }
+}
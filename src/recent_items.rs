@@ -0,0 +1,105 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// How many items the Recent panel keeps when `tui.recent_items` isn't set.
+pub const DEFAULT_LIMIT: usize = 10;
+
+/// An item that was opened in the Details panel or had a var mapped from it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentItem {
+    pub account_id: String,
+    pub vault_id: String,
+    pub vault_name: String,
+    pub item_id: String,
+    pub title: String,
+    /// Unix seconds of the last open or mapping.
+    pub used_at: u64,
+}
+
+/// Recently used items, most recent first. Each item appears once.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentItems {
+    #[serde(default)]
+    pub items: Vec<RecentItem>,
+}
+
+impl RecentItems {
+    /// Moves `item` to the front, dropping whatever falls past `limit`.
+    pub fn record(&mut self, item: RecentItem, limit: usize) {
+        self.forget(&item.account_id, &item.item_id);
+        self.items.insert(0, item);
+        self.items.truncate(limit);
+    }
+
+    pub fn forget(&mut self, account_id: &str, item_id: &str) {
+        self.items
+            .retain(|recent| recent.account_id != account_id || recent.item_id != item_id);
+    }
+}
+
+/// The history is per machine, like the listing cache, so it lives next to it
+/// rather than in the config.
+pub fn recent_items_path(cache_root: &Path) -> PathBuf {
+    cache_root.join("recent_items.json")
+}
+
+pub fn load_recent_items(cache_root: &Path) -> RecentItems {
+    std::fs::read_to_string(recent_items_path(cache_root))
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_recent_items(cache_root: &Path, recent: &RecentItems) -> Result<()> {
+    std::fs::create_dir_all(cache_root)
+        .with_context(|| format!("Failed to create cache directory: {}", cache_root.display()))?;
+    let path = recent_items_path(cache_root);
+    let json = serde_json::to_vec_pretty(recent).context("Failed to serialize recent items")?;
+    std::fs::write(&path, json)
+        .with_context(|| format!("Failed to write recent items: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recent(item_id: &str) -> RecentItem {
+        RecentItem {
+            account_id: "acct".to_string(),
+            vault_id: "vault".to_string(),
+            vault_name: "Private".to_string(),
+            item_id: item_id.to_string(),
+            title: item_id.to_uppercase(),
+            used_at: 0,
+        }
+    }
+
+    #[test]
+    fn keeps_the_most_recent_first_without_duplicates() {
+        let mut recent_items = RecentItems::default();
+
+        for item_id in ["a", "b", "c", "a"] {
+            recent_items.record(recent(item_id), 3);
+        }
+        recent_items.record(recent("d"), 3);
+
+        let ids: Vec<&str> = recent_items
+            .items
+            .iter()
+            .map(|item| item.item_id.as_str())
+            .collect();
+        assert_eq!(ids, ["d", "a", "c"]);
+    }
+
+    #[test]
+    fn persists_across_loads() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let mut recent_items = RecentItems::default();
+        recent_items.record(recent("a"), DEFAULT_LIMIT);
+
+        save_recent_items(dir.path(), &recent_items).unwrap();
+
+        assert_eq!(load_recent_items(dir.path()), recent_items);
+    }
+}
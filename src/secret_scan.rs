@@ -0,0 +1,169 @@
+/// Token prefixes of well-known services, what they are, and the var name
+/// to suggest for them.
+const TOKEN_PREFIXES: &[(&str, &str, &str)] = &[
+    ("github_pat_", "GitHub token", "GITHUB_TOKEN"),
+    ("ghp_", "GitHub token", "GITHUB_TOKEN"),
+    ("gho_", "GitHub token", "GITHUB_TOKEN"),
+    ("ghs_", "GitHub token", "GITHUB_TOKEN"),
+    ("ghu_", "GitHub token", "GITHUB_TOKEN"),
+    ("glpat-", "GitLab token", "GITLAB_TOKEN"),
+    ("npm_", "npm token", "NPM_TOKEN"),
+    ("pypi-", "PyPI token", "PYPI_TOKEN"),
+    ("xoxb-", "Slack token", "SLACK_TOKEN"),
+    ("xoxp-", "Slack token", "SLACK_TOKEN"),
+    ("sk_live_", "Stripe key", "STRIPE_SECRET_KEY"),
+    ("AKIA", "AWS access key", "AWS_ACCESS_KEY_ID"),
+];
+
+/// Characters a token needs past its prefix before it counts, so words like
+/// `npm_config` don't.
+const MIN_TOKEN_BODY: usize = 16;
+
+/// Setting names that suggest the value next to them is a secret.
+const SECRET_KEYS: &[&str] = &["token", "secret", "password", "passwd", "apikey", "api_key"];
+
+/// Values shorter than this are too likely to be placeholders, examples, or
+/// ordinary text, such as `true` or `443`, to be treated as secrets.
+pub const MIN_SECRET_LEN: usize = 8;
+
+/// A value in a file that looks like a plaintext secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suspect {
+    /// 1-based line number of the first occurrence.
+    pub line: usize,
+    pub value: String,
+    pub label: &'static str,
+    pub suggested_var: String,
+}
+
+/// Values in `content` that look like secrets: tokens with a well-known
+/// prefix, and long values of settings named like `token` or `password`.
+/// Each value is reported once.
+pub fn find_suspects(content: &str) -> Vec<Suspect> {
+    let mut suspects: Vec<Suspect> = Vec::new();
+    for (idx, line) in content.lines().enumerate() {
+        let found = prefixed_tokens(line)
+            .into_iter()
+            .chain(secret_setting(line))
+            .map(|(value, label, suggested_var)| Suspect {
+                line: idx + 1,
+                value,
+                label,
+                suggested_var,
+            });
+        for suspect in found {
+            if !suspects.iter().any(|known| known.value == suspect.value) {
+                suspects.push(suspect);
+            }
+        }
+    }
+    suspects
+}
+
+fn prefixed_tokens(line: &str) -> Vec<(String, &'static str, String)> {
+    line.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        .filter_map(|word| {
+            TOKEN_PREFIXES
+                .iter()
+                .find(|(prefix, _, _)| {
+                    word.strip_prefix(prefix)
+                        .is_some_and(|body| body.len() >= MIN_TOKEN_BODY)
+                })
+                .map(|(_, label, var)| (word.to_string(), *label, (*var).to_string()))
+        })
+        .collect()
+}
+
+/// The value of a `key = value` or `key: value` line whose key names a
+/// secret, unless it's a placeholder or a reference.
+fn secret_setting(line: &str) -> Option<(String, &'static str, String)> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('#') || trimmed.starts_with(';') {
+        return None;
+    }
+    let (key, value) = trimmed
+        .split_once('=')
+        .or_else(|| trimmed.split_once(": "))?;
+    let name = key
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .rfind(|word| !word.is_empty())?;
+    let lowered = name.to_ascii_lowercase();
+    if !SECRET_KEYS.iter().any(|secret| lowered.contains(secret)) {
+        return None;
+    }
+
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+    let is_reference = value.contains("{{")
+        || value.starts_with('$')
+        || value.starts_with("op://")
+        || value.contains(char::is_whitespace);
+    if value.len() < MIN_SECRET_LEN || is_reference {
+        return None;
+    }
+    Some((value.to_string(), "secret setting", var_name_for(name)))
+}
+
+/// `_authToken` becomes `AUTH_TOKEN`, `api-key` becomes `API_KEY`.
+fn var_name_for(key: &str) -> String {
+    let mut name = String::with_capacity(key.len() + 4);
+    let mut previous_lower = false;
+    for c in key.chars() {
+        if c.is_ascii_uppercase() && previous_lower {
+            name.push('_');
+        }
+        previous_lower = c.is_ascii_lowercase() || c.is_ascii_digit();
+        name.push(if c.is_ascii_alphanumeric() {
+            c.to_ascii_uppercase()
+        } else {
+            '_'
+        });
+    }
+    name.trim_matches('_').to_string()
+}
+
+/// The first few characters of a secret, enough to recognise it.
+pub fn preview(value: &str) -> String {
+    let shown: String = value.chars().take(6).collect();
+    format!("{shown}… ({} chars)", value.chars().count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_prefixed_tokens_and_secret_settings() {
+        let content = "\
+//registry.npmjs.org/:_authToken=npm_abcdefghijklmnopqrstuvwxyz
+# password = hunter2hunter2
+email=me@example.com
+password: \"correct-horse-battery\"
+token={{GITHUB_TOKEN}}
+npm_config_cache=/tmp/npm
+export GH=ghp_0123456789abcdefghijklmnop
+";
+
+        let suspects = find_suspects(content);
+
+        let found: Vec<(usize, &str, &str)> = suspects
+            .iter()
+            .map(|suspect| {
+                (
+                    suspect.line,
+                    suspect.value.as_str(),
+                    suspect.suggested_var.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (1, "npm_abcdefghijklmnopqrstuvwxyz", "NPM_TOKEN"),
+                (4, "correct-horse-battery", "PASSWORD"),
+                (7, "ghp_0123456789abcdefghijklmnop", "GITHUB_TOKEN"),
+            ]
+        );
+        assert_eq!(var_name_for("_authToken"), "AUTH_TOKEN");
+        assert_eq!(preview("npm_abcdefgh"), "npm_ab… (12 chars)");
+    }
+}
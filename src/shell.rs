@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// The syntax `env inject` and `env unset` print statements in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    /// `export NAME='value'`, for bash, zsh, and other POSIX shells
+    #[default]
+    Posix,
+    /// `set -gx NAME 'value'`
+    Fish,
+    /// `$env:NAME = 'value'`
+    Powershell,
+}
+
+/// Somewhere `env inject` writes variables itself instead of printing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ApplyTarget {
+    /// Fish universal variables (`set -Ux`), shared by every fish session and
+    /// kept across restarts until the next inject or unset
+    FishUniversal,
+}
+
+fn posix_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Inside fish single quotes only `\\` and `\'` are escapes.
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn powershell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+impl Shell {
+    pub fn export(self, name: &str, value: &str) -> String {
+        match self {
+            Self::Posix => format!("export {name}={}\n", posix_quote(value)),
+            Self::Fish => format!("set -gx {name} {}\n", fish_quote(value)),
+            Self::Powershell => format!("$env:{name} = {}\n", powershell_quote(value)),
+        }
+    }
+
+    pub fn unset(self, name: &str) -> String {
+        match self {
+            Self::Posix => format!("unset {name}\n"),
+            Self::Fish => format!("set -e {name}\n"),
+            Self::Powershell => {
+                format!("Remove-Item -Path Env:{name} -ErrorAction SilentlyContinue\n")
+            }
+        }
+    }
+
+    /// Lines to add to the shell's startup file so every new session loads
+    /// the managed variables.
+    pub const fn profile_snippet(self) -> &'static str {
+        match self {
+            Self::Posix => {
+                "# op-loader: load managed secrets (add to ~/.bashrc or ~/.zshrc)\n\
+                 eval \"$(op-loader env inject)\"\n"
+            }
+            Self::Fish => {
+                "# op-loader: load managed secrets (add to ~/.config/fish/config.fish)\n\
+                 op-loader env inject --shell fish | source\n"
+            }
+            Self::Powershell => {
+                "# op-loader: load managed secrets (add to $PROFILE)\n\
+                 op-loader env inject --shell powershell | Out-String | Invoke-Expression\n"
+            }
+        }
+    }
+}
+
+pub fn fish_universal_script<'a>(vars: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    vars.into_iter()
+        .map(|(name, value)| format!("set -Ux {name} {}\n", fish_quote(value)))
+        .collect()
+}
+
+pub fn fish_universal_erase_script<'a>(names: impl IntoIterator<Item = &'a str>) -> String {
+    names
+        .into_iter()
+        .map(|name| format!("set -Ue {name}\n"))
+        .collect()
+}
+
+/// Runs `script` in fish. It goes over stdin so values never show up in the
+/// process list, and `--no-config` keeps a `config.fish` that calls
+/// op-loader from running again.
+pub fn run_fish(script: &str) -> Result<()> {
+    let mut child = Command::new("fish")
+        .arg("--no-config")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run fish. Is it installed and on your PATH?")?;
+
+    child
+        .stdin
+        .take()
+        .context("Failed to open fish stdin")?
+        .write_all(script.as_bytes())
+        .context("Failed to send variables to fish")?;
+
+    let output = child
+        .wait_with_output()
+        .context("Failed to wait for fish")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "fish exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_values_for_each_shell() {
+        let value = r"it's C:\tmp";
+
+        assert_eq!(
+            Shell::Posix.export("TOKEN", value),
+            "export TOKEN='it'\\''s C:\\tmp'\n"
+        );
+        assert_eq!(
+            Shell::Fish.export("TOKEN", value),
+            "set -gx TOKEN 'it\\'s C:\\\\tmp'\n"
+        );
+        assert_eq!(
+            Shell::Powershell.export("TOKEN", value),
+            "$env:TOKEN = 'it''s C:\\tmp'\n"
+        );
+        assert_eq!(Shell::Fish.unset("TOKEN"), "set -e TOKEN\n");
+    }
+
+    #[test]
+    fn builds_fish_universal_scripts() {
+        assert_eq!(
+            fish_universal_script([("A", "1"), ("B", "x'y")]),
+            "set -Ux A '1'\nset -Ux B 'x\\'y'\n"
+        );
+        assert_eq!(fish_universal_erase_script(["A"]), "set -Ue A\n");
+    }
+}
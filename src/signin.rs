@@ -0,0 +1,99 @@
+use anyhow::{Context, Result};
+
+use crate::op_client::OpClient;
+
+/// Whether `op` failed because the account needs signing in again, as
+/// opposed to a missing item or a network error.
+pub fn is_session_error(stderr: &str) -> bool {
+    crate::op_error::classify(stderr) == crate::op_error::OpErrorKind::NotSignedIn
+}
+
+/// The account named by `--account` in an `op` command line.
+pub fn account_from_args(args: &[&str]) -> Option<String> {
+    args.iter()
+        .position(|arg| *arg == "--account")
+        .and_then(|idx| args.get(idx + 1))
+        .map(|account| (*account).to_string())
+}
+
+/// Whether a password sign-in left an `OP_SESSION_*` token in the
+/// environment. Without one, `op` is unlocked by the 1Password app.
+pub fn has_session_token() -> bool {
+    std::env::vars_os().any(|(name, _)| {
+        name.to_str()
+            .is_some_and(|name| name.starts_with("OP_SESSION_"))
+    })
+}
+
+/// Runs `op signin` with the terminal attached, so a password prompt or the
+/// desktop app's biometric prompt can come up. Without the desktop app, `op`
+/// prints `export OP_SESSION_...` lines instead of keeping the session
+/// itself; those are applied to this process so later `op` calls use them.
+pub fn sign_in(op: &dyn OpClient, account_id: Option<&str>) -> Result<()> {
+    let mut args = vec!["signin"];
+    if let Some(account_id) = account_id {
+        args.extend(["--account", account_id]);
+    }
+    let output = op
+        .run_attached(&args)
+        .context("Failed to run `op signin`")?;
+    if !output.success {
+        anyhow::bail!("`op signin` failed");
+    }
+
+    for (name, value) in parse_session_exports(&String::from_utf8_lossy(&output.stdout)) {
+        // SAFETY: the TUI's worker threads only read the environment through
+        // `std::process::Command`, which takes the same lock as `set_var`.
+        unsafe { std::env::set_var(name, value) };
+    }
+    Ok(())
+}
+
+/// The `OP_SESSION_*` variables in `op signin` output such as
+/// `export OP_SESSION_my="token"`.
+fn parse_session_exports(stdout: &str) -> Vec<(String, String)> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let assignment = line.trim().strip_prefix("export ")?;
+            let (name, value) = assignment.split_once('=')?;
+            if !name.starts_with("OP_SESSION_") {
+                return None;
+            }
+            let value = value.trim().trim_matches('"');
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_expired_sessions() {
+        assert!(is_session_error(
+            "[ERROR] 2024/01/01 You are not currently signed in. Please run `op signin --help` for instructions"
+        ));
+        assert!(is_session_error(
+            "[ERROR] session expired, sign in to create a new session"
+        ));
+        assert!(!is_session_error(
+            "[ERROR] \"GitHub\" isn't an item in the \"Private\" vault"
+        ));
+    }
+
+    #[test]
+    fn parses_session_exports() {
+        let stdout = "export OP_SESSION_my=\"abc123\"\n# This command is meant to be used with your shell's eval function.\n";
+
+        assert_eq!(
+            parse_session_exports(stdout),
+            [("OP_SESSION_my".to_string(), "abc123".to_string())]
+        );
+        assert_eq!(
+            account_from_args(&["vault", "list", "--account", "acct"]),
+            Some("acct".to_string())
+        );
+    }
+}
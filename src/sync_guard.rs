@@ -0,0 +1,128 @@
+use anyhow::{Context, Result, bail};
+use std::path::{Component, Path, PathBuf};
+
+/// Returns the cloud sync provider whose folder contains `path`, if any.
+///
+/// Matching is done on path components so it covers both the legacy home
+/// directory roots (`~/Dropbox`, `~/OneDrive`, `~/Google Drive`) and the
+/// macOS File Provider roots under `~/Library/CloudStorage` and
+/// `~/Library/Mobile Documents`.
+pub fn cloud_sync_provider(path: &Path) -> Option<&'static str> {
+    path.components().find_map(|component| match component {
+        Component::Normal(name) => provider_for_dir(&name.to_string_lossy()),
+        _ => None,
+    })
+}
+
+pub fn is_allow_listed(path: &Path, allowed: &[PathBuf]) -> bool {
+    allowed.iter().any(|root| path.starts_with(root))
+}
+
+/// Refuses `target` when it's inside a cloud-synced folder that isn't in
+/// `allowed_sync_paths`, since whatever is written there is uploaded.
+/// Every command that writes secrets to a path outside op-loader's own
+/// directories checks here first.
+pub fn ensure_allowed(target: &Path, allowed_sync_paths: &[String]) -> Result<()> {
+    let Some(provider) = cloud_sync_provider(target) else {
+        return Ok(());
+    };
+    let allowed = allowed_sync_paths
+        .iter()
+        .map(|path| crate::cli::expand_path(path))
+        .collect::<Result<Vec<_>>>()?;
+    if is_allow_listed(target, &allowed) {
+        return Ok(());
+    }
+    bail!(
+        "{} is inside a {provider} folder. Add it to `allowed_sync_paths` in your config to write there.",
+        target.display()
+    )
+}
+
+/// Replaces `target` with `contents` like [`crate::atomic_file::write`],
+/// once [`ensure_allowed`] lets it through.
+pub fn write(target: &Path, contents: &[u8], allowed_sync_paths: &[String]) -> Result<()> {
+    ensure_allowed(target, allowed_sync_paths)?;
+    crate::atomic_file::write(target, contents)
+        .with_context(|| format!("Failed to write to {}", target.display()))
+}
+
+fn provider_for_dir(name: &str) -> Option<&'static str> {
+    if name == "Mobile Documents" || name == "iCloud Drive" || name == "iCloudDrive" {
+        Some("iCloud Drive")
+    } else if name == "Dropbox" || name.starts_with("Dropbox (") || name.starts_with("Dropbox-") {
+        Some("Dropbox")
+    } else if name.starts_with("OneDrive") {
+        Some("OneDrive")
+    } else if name == "Google Drive" || name.starts_with("GoogleDrive") {
+        Some("Google Drive")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_home_directory_sync_roots() {
+        assert_eq!(
+            cloud_sync_provider(Path::new("/Users/me/Dropbox/dotfiles/.npmrc")),
+            Some("Dropbox")
+        );
+        assert_eq!(
+            cloud_sync_provider(Path::new("/Users/me/Google Drive/.netrc")),
+            Some("Google Drive")
+        );
+        assert_eq!(
+            cloud_sync_provider(Path::new("C:/Users/me/OneDrive - Acme/config")),
+            Some("OneDrive")
+        );
+    }
+
+    #[test]
+    fn detects_macos_file_provider_roots() {
+        assert_eq!(
+            cloud_sync_provider(Path::new(
+                "/Users/me/Library/Mobile Documents/com~apple~CloudDocs/.env"
+            )),
+            Some("iCloud Drive")
+        );
+        assert_eq!(
+            cloud_sync_provider(Path::new(
+                "/Users/me/Library/CloudStorage/GoogleDrive-me@example.com/My Drive/.env"
+            )),
+            Some("Google Drive")
+        );
+        assert_eq!(
+            cloud_sync_provider(Path::new(
+                "/Users/me/Library/CloudStorage/OneDrive-Personal/.env"
+            )),
+            Some("OneDrive")
+        );
+    }
+
+    #[test]
+    fn ignores_regular_paths() {
+        assert_eq!(cloud_sync_provider(Path::new("/Users/me/.npmrc")), None);
+        assert_eq!(
+            cloud_sync_provider(Path::new("/home/me/projects/dropbox-client/.env")),
+            None
+        );
+    }
+
+    #[test]
+    fn allow_list_matches_nested_paths() {
+        let allowed = vec![PathBuf::from("/Users/me/Dropbox/private")];
+
+        assert!(is_allow_listed(
+            Path::new("/Users/me/Dropbox/private/.npmrc"),
+            &allowed
+        ));
+        assert!(!is_allow_listed(
+            Path::new("/Users/me/Dropbox/shared/.npmrc"),
+            &allowed
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::cache::update_locked_json;
+
+/// Anonymous usage counters. Only command names, cache hit/miss counts, and
+/// `op` latency buckets are kept: never account ids, references, variable
+/// names, or secrets.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TelemetryReport {
+    #[serde(default)]
+    pub commands: BTreeMap<String, u64>,
+    #[serde(default)]
+    pub cache_hits: u64,
+    #[serde(default)]
+    pub cache_misses: u64,
+    #[serde(default)]
+    pub op_latency: LatencyBuckets,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencyBuckets {
+    #[serde(default)]
+    pub under_100ms: u64,
+    #[serde(default)]
+    pub under_500ms: u64,
+    #[serde(default)]
+    pub under_1s: u64,
+    #[serde(default)]
+    pub under_5s: u64,
+    #[serde(default)]
+    pub over_5s: u64,
+}
+
+impl LatencyBuckets {
+    const fn record(&mut self, elapsed: Duration) {
+        let bucket = match elapsed.as_millis() {
+            0..100 => &mut self.under_100ms,
+            100..500 => &mut self.under_500ms,
+            500..1000 => &mut self.under_1s,
+            1000..5000 => &mut self.under_5s,
+            _ => &mut self.over_5s,
+        };
+        *bucket += 1;
+    }
+
+    const fn merge(&mut self, other: &Self) {
+        self.under_100ms += other.under_100ms;
+        self.under_500ms += other.under_500ms;
+        self.under_1s += other.under_1s;
+        self.under_5s += other.under_5s;
+        self.over_5s += other.over_5s;
+    }
+}
+
+impl TelemetryReport {
+    const fn new() -> Self {
+        Self {
+            commands: BTreeMap::new(),
+            cache_hits: 0,
+            cache_misses: 0,
+            op_latency: LatencyBuckets {
+                under_100ms: 0,
+                under_500ms: 0,
+                under_1s: 0,
+                under_5s: 0,
+                over_5s: 0,
+            },
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn merge(&mut self, other: &Self) {
+        for (command, count) in &other.commands {
+            *self.commands.entry(command.clone()).or_default() += count;
+        }
+        self.cache_hits += other.cache_hits;
+        self.cache_misses += other.cache_misses;
+        self.op_latency.merge(&other.op_latency);
+    }
+}
+
+/// Counters from this process, written out by [`flush`] on exit.
+static PENDING: Mutex<TelemetryReport> = Mutex::new(TelemetryReport::new());
+
+fn with_pending(update: impl FnOnce(&mut TelemetryReport)) {
+    if let Ok(mut pending) = PENDING.lock() {
+        update(&mut pending);
+    }
+}
+
+pub fn record_command(name: &str) {
+    with_pending(|report| *report.commands.entry(name.to_string()).or_default() += 1);
+}
+
+pub fn record_cache_lookup(hit: bool) {
+    with_pending(|report| {
+        if hit {
+            report.cache_hits += 1;
+        } else {
+            report.cache_misses += 1;
+        }
+    });
+}
+
+pub fn record_op_latency(elapsed: Duration) {
+    with_pending(|report| report.op_latency.record(elapsed));
+}
+
+/// Counters are kept next to the cache files, without the `.cache`
+/// extension so eviction leaves them alone.
+pub fn telemetry_path(cache_root: &Path) -> PathBuf {
+    cache_root.join("telemetry.json")
+}
+
+/// Adds this process's counters to the telemetry file, but only when
+/// `telemetry.enabled` is set. Otherwise they're dropped.
+pub fn flush() {
+    let pending = PENDING
+        .lock()
+        .map(|mut pending| std::mem::take(&mut *pending))
+        .unwrap_or_default();
+    if pending.is_empty() {
+        return;
+    }
+
+    let enabled = confy::load::<crate::app::OpLoadConfig>("op_loader", None)
+        .is_ok_and(|config| config.telemetry.enabled);
+    if !enabled {
+        return;
+    }
+
+    let result = crate::cache::cache_dir()
+        .and_then(|dir| update_report(&dir, |report| report.merge(&pending)));
+    if let Err(err) = result {
+        log::debug!("Failed to record telemetry: {err}");
+    }
+}
+
+pub fn load_report(cache_root: &Path) -> Result<TelemetryReport> {
+    let path = telemetry_path(cache_root);
+    match std::fs::read_to_string(&path) {
+        Ok(contents) if contents.trim().is_empty() => Ok(TelemetryReport::default()),
+        Ok(contents) => serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse telemetry: {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(TelemetryReport::default()),
+        Err(err) => {
+            Err(err).with_context(|| format!("Failed to read telemetry: {}", path.display()))
+        }
+    }
+}
+
+/// Rewrites the telemetry file under an exclusive lock, so processes exiting
+/// at the same time don't lose each other's counts.
+pub fn update_report(cache_root: &Path, update: impl FnOnce(&mut TelemetryReport)) -> Result<()> {
+    update_locked_json(&telemetry_path(cache_root), "telemetry", update)?;
+    Ok(())
+}
+
+/// Posts the report as JSON to `endpoint` with `curl`.
+pub fn send_report(endpoint: &str, report: &TelemetryReport) -> Result<()> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    if !endpoint.starts_with("https://") && !endpoint.starts_with("http://") {
+        bail!("telemetry.endpoint must be an http(s) URL, got '{endpoint}'");
+    }
+
+    let mut child = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--max-time",
+            "10",
+            "--header",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+            endpoint,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run curl")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let json = serde_json::to_vec(report).context("Failed to serialize telemetry")?;
+        stdin
+            .write_all(&json)
+            .context("Failed to write telemetry to curl")?;
+    }
+
+    let output = child
+        .wait_with_output()
+        .context("Failed to wait for curl")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("Failed to send telemetry: {}", stderr.trim());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latency_is_bucketed() {
+        let mut buckets = LatencyBuckets::default();
+        buckets.record(Duration::from_millis(50));
+        buckets.record(Duration::from_millis(700));
+        buckets.record(Duration::from_secs(9));
+
+        assert_eq!(buckets.under_100ms, 1);
+        assert_eq!(buckets.under_1s, 1);
+        assert_eq!(buckets.over_5s, 1);
+    }
+
+    #[test]
+    fn updates_accumulate_in_file() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let mut run = TelemetryReport::default();
+        run.commands.insert("env".to_string(), 1);
+        run.cache_hits = 2;
+
+        update_report(dir.path(), |report| report.merge(&run)).unwrap();
+        update_report(dir.path(), |report| report.merge(&run)).unwrap();
+
+        let report = load_report(dir.path()).unwrap();
+        assert_eq!(report.commands["env"], 2);
+        assert_eq!(report.cache_hits, 4);
+    }
+
+    #[test]
+    fn rejects_non_http_endpoints() {
+        let err = send_report("file:///etc/passwd", &TelemetryReport::default()).unwrap_err();
+
+        assert!(err.to_string().contains("http(s) URL"));
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::cache::cache_dir;
+
+/// Backups of a template's target, under the cache directory like render
+/// history, since they hold rendered secrets.
+pub fn backups_dir(template_name: &str) -> Result<PathBuf> {
+    Ok(cache_dir()?.join("template_backups").join(template_name))
+}
+
+/// `20240229T120000Z`: sortable, and without the colons Windows rejects in
+/// file names.
+fn backup_stem(now: SystemTime) -> String {
+    crate::timestamp::format_rfc3339(now).replace(['-', ':'], "")
+}
+
+/// Copies `target` into `dir` as `<timestamp>.bak`, readable only by the
+/// current user, then drops all but the newest `keep` backups. A missing
+/// target has nothing to back up.
+pub fn back_up(target: &Path, dir: &Path, keep: usize, now: SystemTime) -> Result<Option<PathBuf>> {
+    let contents = match std::fs::read(target) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", target.display()));
+        }
+    };
+
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+    let stem = backup_stem(now);
+    let mut path = dir.join(format!("{stem}.bak"));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{stem}-{n}.bak"));
+        n += 1;
+    }
+    crate::managed_files::place(&path, &contents)?;
+
+    for stale in list_backups(dir)?.into_iter().skip(keep.max(1)) {
+        std::fs::remove_file(&stale)
+            .with_context(|| format!("Failed to remove old backup {}", stale.display()))?;
+    }
+    Ok(Some(path))
+}
+
+/// Backups in `dir`, newest first. A missing directory has none.
+pub fn list_backups(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", dir.display()));
+        }
+    };
+
+    let mut backups: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "bak"))
+        .collect();
+    backups.sort_by_key(|path| std::cmp::Reverse(backup_order(path)));
+    Ok(backups)
+}
+
+/// Sorts `<stem>-10.bak` after `<stem>-9.bak` and `<stem>.bak` before both.
+fn backup_order(path: &Path) -> (String, u32) {
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match name.split_once('-') {
+        Some((stem, n)) => (stem.to_string(), n.parse().unwrap_or(0)),
+        None => (name, 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn keeps_the_newest_backups() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let target = dir.path().join(".npmrc");
+        let backups = dir.path().join("backups");
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+
+        assert_eq!(back_up(&target, &backups, 2, at(0)).unwrap(), None);
+        for (secs, contents) in [(0, "one"), (60, "two"), (60, "three")] {
+            std::fs::write(&target, contents).unwrap();
+            back_up(&target, &backups, 2, at(secs)).unwrap();
+        }
+
+        let kept = list_backups(&backups).unwrap();
+        let names: Vec<String> = kept
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, ["19700101T000100Z-2.bak", "19700101T000100Z.bak"]);
+        assert_eq!(std::fs::read_to_string(&kept[0]).unwrap(), "three");
+    }
+}
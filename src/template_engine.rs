@@ -0,0 +1,562 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Lines starting with this are notes for whoever edits the template and
+/// are left out of the rendered file.
+const COMMENT_PREFIX: &str = "# op-loader:";
+
+/// The template without its `# op-loader:` lines, keeping a trailing newline.
+fn strip_comments(template: &str) -> String {
+    let mut body: String = template
+        .lines()
+        .filter(|line| !line.starts_with(COMMENT_PREFIX))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if template.ends_with('\n') && !body.ends_with('\n') {
+        body.push('\n');
+    }
+    body
+}
+
+fn is_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The var name and fallback of a `{{NAME:-fallback}}` placeholder.
+fn split_default(inner: &str) -> Option<(&str, &str)> {
+    inner.split_once(":-").filter(|(name, _)| is_var_name(name))
+}
+
+/// Replaces each `{{...}}` in `body` for which `rewrite` returns something,
+/// given the text between the braces. Replacements aren't scanned again.
+fn rewrite_placeholders(body: &str, rewrite: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after
+            .find("}}")
+            .and_then(|end| Some((end, rewrite(&after[..end])?)))
+        {
+            Some((end, replacement)) => {
+                out.push_str(&replacement);
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Whether a placeholder holds an `op://` reference, resolved with `op`.
+fn is_op_reference(inner: &str) -> bool {
+    inner.trim().starts_with("op://")
+}
+
+/// The distinct `{{ op://... }}` references in the template, in order of
+/// first use, so they can be resolved before the template is rendered.
+/// `# op-loader:` lines don't count.
+pub fn op_references(template: &str) -> Vec<String> {
+    let mut references: Vec<String> = Vec::new();
+    for placeholder in strip_comments(template).split("{{").skip(1) {
+        let Some(inner) = placeholder.find("}}").map(|end| &placeholder[..end]) else {
+            continue;
+        };
+        let reference = inner.trim();
+        if is_op_reference(reference) && !references.iter().any(|known| known == reference) {
+            references.push(reference.to_string());
+        }
+    }
+    references
+}
+
+/// The engine variable holding the value of the `index`th resolved
+/// reference, named so it can't clash with a var.
+fn reference_var(index: usize) -> String {
+    format!("__op_reference_{index}")
+}
+
+/// Prepares plain placeholders for the engine: `{{NAME:-fallback}}` becomes
+/// the var or its fallback, and a resolved `{{ op://... }}` reference its
+/// engine variable, while unresolved references and `{{NAME}}` of a var
+/// that didn't resolve are wrapped in `{% raw %}`, so they stay in the
+/// output instead of failing the render.
+fn prepare_placeholders(
+    body: &str,
+    vars: &HashMap<String, String>,
+    references: &[(&String, &String)],
+) -> String {
+    rewrite_placeholders(body, |inner| {
+        if is_op_reference(inner) {
+            return Some(
+                match references
+                    .iter()
+                    .position(|(reference, _)| *reference == inner.trim())
+                {
+                    Some(index) => format!("{{{{ {} }}}}", reference_var(index)),
+                    None => format!("{{% raw %}}{{{{{inner}}}}}{{% endraw %}}"),
+                },
+            );
+        }
+        if let Some((name, fallback)) = split_default(inner) {
+            return Some(if vars.contains_key(name) {
+                format!("{{{{ {name} }}}}")
+            } else {
+                format!("{{% raw %}}{fallback}{{% endraw %}}")
+            });
+        }
+        (is_var_name(inner) && !vars.contains_key(inner))
+            .then(|| format!("{{% raw %}}{{{{{inner}}}}}{{% endraw %}}"))
+    })
+}
+
+/// The file name in an `{% include "name" %}` tag.
+fn include_name(tag: &str) -> Option<&str> {
+    let tag = tag
+        .trim()
+        .trim_start_matches('-')
+        .trim_end_matches('-')
+        .trim();
+    let name = tag.strip_prefix("include")?.trim_start();
+    let quote = name.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    name[1..].strip_suffix(quote)
+}
+
+/// Where an included file lives: inside `dir`, so includes can't reach
+/// other files on disk.
+fn include_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    let relative = Path::new(name);
+    if name.is_empty()
+        || !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        anyhow::bail!("Can't include \"{name}\": give a path inside the templates directory");
+    }
+    Ok(dir.join(relative))
+}
+
+/// Replaces each `{% include "name" %}` with the file `name` in `dir` (the
+/// templates directory), itself expanded, so fragments such as a proxy
+/// block can be shared between templates. An include that leads back to a
+/// file already being included is an error.
+pub fn expand_includes(template: &str, dir: &Path) -> Result<String> {
+    expand_includes_from(template, dir, &mut Vec::new())
+}
+
+fn expand_includes_from(template: &str, dir: &Path, stack: &mut Vec<String>) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{%") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("%}") else {
+            break;
+        };
+        let Some(name) = include_name(&after[..end]) else {
+            out.push_str(&rest[..start + 2 + end + 2]);
+            rest = &after[end + 2..];
+            continue;
+        };
+
+        if stack.iter().any(|included| included == name) {
+            anyhow::bail!("Include cycle: {} -> {name}", stack.join(" -> "));
+        }
+        let path = include_path(dir, name)?;
+        let fragment = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read included template {}", path.display()))?;
+        stack.push(name.to_string());
+        let expanded =
+            expand_includes_from(fragment.strip_suffix('\n').unwrap_or(&fragment), dir, stack)?;
+        stack.pop();
+
+        out.push_str(&rest[..start]);
+        out.push_str(&expanded);
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Reads a template with its includes expanded, to find the vars it uses.
+/// If the includes can't be expanded it's read as it is, leaving the error
+/// for rendering to report.
+pub fn read_for_references(path: &Path, dir: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(path).ok()?;
+    Some(expand_includes(&content, dir).unwrap_or(content))
+}
+
+/// Every file the template includes, directly or through other includes.
+/// Includes that can't be read are left out.
+pub fn included_files(template: &str, dir: &Path) -> Vec<String> {
+    let mut found: Vec<String> = Vec::new();
+    let mut pending = vec![template.to_string()];
+    while let Some(text) = pending.pop() {
+        for tag in text.split("{%").skip(1) {
+            let Some(name) = tag.find("%}").and_then(|end| include_name(&tag[..end])) else {
+                continue;
+            };
+            if found.iter().any(|known| known == name) {
+                continue;
+            }
+            found.push(name.to_string());
+            if let Ok(fragment) =
+                include_path(dir, name).and_then(|path| Ok(std::fs::read_to_string(path)?))
+            {
+                pending.push(fragment);
+            }
+        }
+    }
+    found
+}
+
+/// Whether the template uses `{% ... %}` tags or `{# ... #}` comments, as
+/// opposed to only `{{ ... }}` expressions. A comment has to open with
+/// whitespace, so shell syntax such as `${#arr[@]}` doesn't count.
+pub fn has_tags(template: &str) -> bool {
+    template.contains("{%")
+        || template.match_indices("{#").any(|(start, _)| {
+            template[start + 2..]
+                .chars()
+                .next()
+                .is_some_and(char::is_whitespace)
+        })
+}
+
+/// Renders a template with the Tera engine. Each resolved var is available
+/// by name and all of them as the `vars` map, so templates can use
+/// `{% if NAME %}`, `{{ NAME | default(value="...") }}` and
+/// `{% for name, value in vars %}`. `{{ op://... }}` references are replaced
+/// with their values from `references`. Plain `{{NAME}}` placeholders of
+/// vars and references that didn't resolve are left as they are.
+pub fn render(
+    template: &str,
+    vars: &HashMap<String, String>,
+    references: &HashMap<String, String>,
+) -> Result<String> {
+    let references: Vec<(&String, &String)> = references.iter().collect();
+    let body = prepare_placeholders(&strip_comments(template), vars, &references);
+
+    let mut context = tera::Context::new();
+    context.insert("vars", vars);
+    for (name, value) in vars {
+        context.insert(name, value);
+    }
+    for (index, (_, value)) in references.iter().enumerate() {
+        context.insert(reference_var(index), value);
+    }
+    tera::Tera::one_off(&body, &context, false)
+        .map_err(anyhow::Error::new)
+        .context("Failed to render template")
+}
+
+/// The pre-engine rendering: plain `{{NAME}}` and `{{NAME:-fallback}}`
+/// placeholders replaced, nothing else interpreted. Used for templates
+/// without tags that the engine can't parse, such as ones holding Go or
+/// Helm `{{ .Values }}` syntax.
+pub fn substitute(
+    template: &str,
+    vars: &HashMap<String, String>,
+    references: &HashMap<String, String>,
+) -> String {
+    rewrite_placeholders(&strip_comments(template), |inner| {
+        if is_op_reference(inner) {
+            return references.get(inner.trim()).cloned();
+        }
+        match split_default(inner) {
+            Some((name, fallback)) => {
+                Some(vars.get(name).map_or(fallback, String::as_str).to_string())
+            }
+            None => vars.get(inner).cloned(),
+        }
+    })
+}
+
+/// Whether the template refers to the var `name` in a `{{ ... }}` expression
+/// or a `{% ... %}` tag. `# op-loader:` lines don't count.
+pub fn references(template: &str, name: &str) -> bool {
+    let body = strip_comments(template);
+    let mut rest = body.as_str();
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        let close = match after.chars().next() {
+            Some('{') => "}}",
+            Some('%') => "%}",
+            _ => {
+                rest = after;
+                continue;
+            }
+        };
+        let inner = &after[1..];
+        let Some(end) = inner.find(close) else {
+            break;
+        };
+        if inner[..end]
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .any(|word| word == name)
+        {
+            return true;
+        }
+        rest = &inner[end + close.len()..];
+    }
+    false
+}
+
+/// Names a template binds itself with `{% for ... in %}` or `{% set %}`,
+/// plus the ones the engine provides, so they aren't mistaken for vars.
+fn local_names(body: &str) -> Vec<String> {
+    let mut names = vec!["vars".to_string(), "loop".to_string()];
+    for tag in body.split("{%").skip(1) {
+        let Some(end) = tag.find("%}") else {
+            continue;
+        };
+        let tag = tag[..end].trim().trim_start_matches('-').trim_start();
+        let bound = if let Some(rest) = tag.strip_prefix("for ") {
+            rest.split_once(" in ").map_or("", |(bound, _)| bound)
+        } else if let Some(rest) = tag
+            .strip_prefix("set_global ")
+            .or_else(|| tag.strip_prefix("set "))
+        {
+            rest.split_once('=').map_or("", |(bound, _)| bound)
+        } else {
+            ""
+        };
+        names.extend(
+            bound
+                .split(',')
+                .map(str::trim)
+                .filter(|name| is_var_name(name))
+                .map(String::from),
+        );
+    }
+    names
+}
+
+/// The var names the template's `{{ ... }}` placeholders start with, in
+/// order of first use. `op://` references, literals, and names the template
+/// binds itself are left out.
+pub fn placeholder_names(template: &str) -> Vec<String> {
+    let body = strip_comments(template);
+    let locals = local_names(&body);
+    let mut names: Vec<String> = Vec::new();
+    for placeholder in body.split("{{").skip(1) {
+        let Some(end) = placeholder.find("}}") else {
+            continue;
+        };
+        let inner = &placeholder[..end];
+        if is_op_reference(inner) {
+            continue;
+        }
+        let inner = inner.trim().trim_start_matches('-').trim_start();
+        let inner = split_default(inner).map_or(inner, |(name, _)| name);
+        let name: String = inner
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect();
+        if is_var_name(&name)
+            && !matches!(name.as_str(), "true" | "false" | "True" | "False")
+            && !locals.contains(&name)
+            && !names.contains(&name)
+        {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// The candidate closest to `name`, if it's within a typo or two.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().map(|c| c.to_ascii_uppercase()).collect();
+    let b: Vec<char> = b.chars().map(|c| c.to_ascii_uppercase()).collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| ((*name).to_string(), (*value).to_string()))
+            .collect()
+    }
+
+    fn render(template: &str, vars: &HashMap<String, String>) -> Result<String> {
+        super::render(template, vars, &HashMap::new())
+    }
+
+    #[test]
+    fn renders_conditionals_defaults_and_loops() {
+        let template = "{% if DEBUG %}debug=true\n{% endif %}region={{ REGION | default(value=\"us-east-1\") }}\n{% for host in HOSTS | split(pat=\",\") %}server {{ host }}\n{% endfor %}";
+
+        let rendered = render(template, &vars(&[("HOSTS", "a,b")])).unwrap();
+
+        assert_eq!(rendered, "region=us-east-1\nserver a\nserver b\n");
+    }
+
+    #[test]
+    fn keeps_plain_placeholders_compatible() {
+        let template =
+            "# op-loader: Available variables: {{TOKEN}}\ntoken={{TOKEN}} other={{UNKNOWN}}\n";
+
+        let rendered = render(template, &vars(&[("TOKEN", "a{{b}}<&>")])).unwrap();
+
+        assert_eq!(rendered, "token=a{{b}}<&> other={{UNKNOWN}}\n");
+    }
+
+    #[test]
+    fn falls_back_for_unresolved_vars() {
+        let template = "url={{DB_URL:-sqlite://local.db}} token={{TOKEN:-}} port={{PORT:-5432}}\n";
+
+        let vars = vars(&[("PORT", "6543")]);
+
+        assert_eq!(
+            render(template, &vars).unwrap(),
+            "url=sqlite://local.db token= port=6543\n"
+        );
+        assert_eq!(
+            substitute(template, &vars, &HashMap::new()),
+            "url=sqlite://local.db token= port=6543\n"
+        );
+        assert!(references(template, "DB_URL"));
+    }
+
+    #[test]
+    fn fills_in_resolved_op_references() {
+        let template = "# op-loader: {{ op://Private/old/token }}\ntoken={{ op://Private/npm/token }}\nuser={{USER}} again={{op://Private/npm/token}} other={{ op://Private/gh/token }}\n";
+        let references = vars(&[("op://Private/npm/token", "npm_{{abc}}")]);
+
+        assert_eq!(
+            op_references(template),
+            ["op://Private/npm/token", "op://Private/gh/token"]
+        );
+        assert!(op_references("user={{USER}} {{ not/op://x }}").is_empty());
+        let expected =
+            "token=npm_{{abc}}\nuser=me again=npm_{{abc}} other={{ op://Private/gh/token }}\n";
+        assert_eq!(
+            super::render(template, &vars(&[("USER", "me")]), &references).unwrap(),
+            expected
+        );
+        assert_eq!(
+            substitute(template, &vars(&[("USER", "me")]), &references),
+            expected
+        );
+    }
+
+    #[test]
+    fn shell_array_length_is_not_a_comment() {
+        let template = "count=${#arr[@]}\ntoken={{TOKEN}}\n";
+
+        assert!(!has_tags(template));
+        assert!(has_tags("{# note #}{{TOKEN}}"));
+        assert!(has_tags("{% if TOKEN %}x{% endif %}"));
+        assert_eq!(
+            substitute(template, &vars(&[("TOKEN", "abc")]), &HashMap::new()),
+            "count=${#arr[@]}\ntoken=abc\n"
+        );
+    }
+
+    #[test]
+    fn iterates_every_var() {
+        let template = "{% for name, value in vars %}{{ name }}={{ value }};{% endfor %}";
+
+        let rendered = render(template, &vars(&[("A", "1"), ("B", "2")])).unwrap();
+
+        assert_eq!(rendered, "A=1;B=2;");
+    }
+
+    #[test]
+    fn falls_back_for_foreign_syntax() {
+        let template = "image: {{ .Values.image }}\ntoken: {{TOKEN}}\n";
+
+        assert!(render(template, &vars(&[("TOKEN", "abc")])).is_err());
+        assert!(!has_tags(template));
+        assert_eq!(
+            substitute(template, &vars(&[("TOKEN", "abc")]), &HashMap::new()),
+            "image: {{ .Values.image }}\ntoken: abc\n"
+        );
+    }
+
+    #[test]
+    fn finds_references_in_expressions_and_tags() {
+        let template = "# op-loader: Available variables: {{UNUSED}}\n{% if DEBUG %}{{ TOKEN | upper }}{% endif %}\nNAME_2={{NAME_2}}\n";
+
+        assert!(references(template, "DEBUG"));
+        assert!(references(template, "TOKEN"));
+        assert!(references(template, "NAME_2"));
+        assert!(!references(template, "NAME"));
+        assert!(!references(template, "UNUSED"));
+    }
+
+    #[test]
+    fn lists_placeholder_names_for_validation() {
+        let template = "# op-loader: Available variables: {{UNUSED}}\n{{ NPM_TOKNE }} {{TOKEN:-x}} {{ op://v/i/f }}\n{% for host in HOSTS | split(pat=\",\") %}{{ host | upper }}{% endfor %}{% set region = \"eu\" %}{{ region }} {{ true }} {{ TOKEN }}\n";
+
+        assert_eq!(placeholder_names(template), ["NPM_TOKNE", "TOKEN"]);
+        assert_eq!(
+            closest("NPM_TOKNE", ["GITHUB_TOKEN", "NPM_TOKEN"]),
+            Some("NPM_TOKEN")
+        );
+        assert_eq!(closest("DATABASE", ["NPM_TOKEN"]), None);
+    }
+
+    #[test]
+    fn expands_includes_and_rejects_cycles() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("partials")).unwrap();
+        std::fs::write(
+            dir.path().join("partials/proxy"),
+            "proxy={{PROXY}}\n{% include \"partials/no_proxy\" %}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("partials/no_proxy"), "noproxy=localhost\n").unwrap();
+        let template = "registry=x\n{%- include 'partials/proxy' -%}\n";
+
+        let expanded = expand_includes(template, dir.path()).unwrap();
+
+        assert_eq!(expanded, "registry=x\nproxy={{PROXY}}\nnoproxy=localhost\n");
+        assert_eq!(
+            render(&expanded, &vars(&[("PROXY", "http://proxy:3128")])).unwrap(),
+            "registry=x\nproxy=http://proxy:3128\nnoproxy=localhost\n"
+        );
+        assert_eq!(
+            included_files(template, dir.path()),
+            ["partials/proxy", "partials/no_proxy"]
+        );
+
+        std::fs::write(dir.path().join("a"), "{% include \"b\" %}").unwrap();
+        std::fs::write(dir.path().join("b"), "{% include \"a\" %}").unwrap();
+        let err = expand_includes("{% include \"a\" %}", dir.path()).unwrap_err();
+        assert_eq!(err.to_string(), "Include cycle: a -> b -> a");
+        assert!(expand_includes("{% include \"../secrets\" %}", dir.path()).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cache::{cache_dir, config_hash, overwrite_with_zeros};
+
+/// One rendered output of a template. `content` holds the render when
+/// content history is enabled, encrypted on macOS and Windows and as plain
+/// text elsewhere; otherwise only the hash is kept.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderRecord {
+    pub rendered_at: u64,
+    pub hash: String,
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+impl RenderRecord {
+    pub fn new(rendered: &str, content: Option<String>) -> Self {
+        Self {
+            rendered_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            hash: config_hash(rendered),
+            content,
+        }
+    }
+}
+
+/// History lives under the cache directory rather than next to the
+/// templates, since the config directory is often synced via dotfiles.
+pub fn history_path(template_name: &str) -> Result<PathBuf> {
+    Ok(history_dir()?.join(format!("{template_name}.json")))
+}
+
+pub fn history_dir() -> Result<PathBuf> {
+    Ok(cache_dir()?.join("template_history"))
+}
+
+/// Drops the stored content from every history under `dir`, keeping the
+/// hashes that tell whether a rendered file was edited. Content is sealed
+/// with the cache key, so it goes when the cache does. With `secure`, the
+/// old files are overwritten with zeros first. Returns how many histories
+/// had content.
+pub fn forget_content(dir: &Path, secure: bool) -> Result<usize> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("Failed to read history directory: {}", dir.display()));
+        }
+    };
+
+    let mut forgotten = 0;
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let mut history = load_history(&path)?;
+        if history.iter().all(|record| record.content.is_none()) {
+            continue;
+        }
+        for record in &mut history {
+            record.content = None;
+        }
+        if secure {
+            overwrite_with_zeros(&path)?;
+        }
+        save_history(&path, &history)?;
+        forgotten += 1;
+    }
+    Ok(forgotten)
+}
+
+/// Records, newest first. A missing file is an empty history.
+pub fn load_history(path: &Path) -> Result<Vec<RenderRecord>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse render history: {}", path.display())),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => {
+            Err(err).with_context(|| format!("Failed to read render history: {}", path.display()))
+        }
+    }
+}
+
+/// Prepends `record` unless it matches the latest render, keeping at most
+/// `keep` records. Returns whether the history changed.
+pub fn push_record(history: &mut Vec<RenderRecord>, record: RenderRecord, keep: usize) -> bool {
+    if history
+        .first()
+        .is_some_and(|latest| latest.hash == record.hash)
+    {
+        return false;
+    }
+
+    history.insert(0, record);
+    history.truncate(keep.max(1));
+    true
+}
+
+pub fn save_history(path: &Path, history: &[RenderRecord]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+
+    let json = serde_json::to_string_pretty(history).context("Failed to serialize history")?;
+
+    // A new file is created owner-only; an existing one passes its mode on,
+    // so tighten it before the new records are written.
+    #[cfg(unix)]
+    if path.exists() {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).with_context(
+            || format!("Failed to set history file permissions: {}", path.display()),
+        )?;
+    }
+
+    crate::atomic_file::write(path, json.as_bytes())
+        .with_context(|| format!("Failed to write render history: {}", path.display()))
+}
+
+/// The hash of the latest recorded render of a template, if any.
+pub fn latest_hash(template_name: &str) -> Option<String> {
+    let path = history_path(template_name).ok()?;
+    load_history(&path)
+        .ok()?
+        .into_iter()
+        .next()
+        .map(|record| record.hash)
+}
+
+/// Whether the file at `path` was changed since op-loader last rendered it,
+/// going by the hash of the latest render. Without a recorded render there
+/// is nothing to compare against, so it isn't.
+pub fn edited_since(path: &Path, last_hash: Option<&str>) -> bool {
+    match (std::fs::read(path), last_hash) {
+        (Ok(contents), Some(hash)) => config_hash(&String::from_utf8_lossy(&contents)) != hash,
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Line diff based on the longest common subsequence.
+pub fn line_diff<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let (n, m) = (old_lines.len(), new_lines.len());
+
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_lines[i] == new_lines[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old_lines[i] == new_lines[j] {
+            diff.push(DiffLine::Same(old_lines[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(DiffLine::Removed(old_lines[i]));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new_lines[j]));
+            j += 1;
+        }
+    }
+    diff.extend(old_lines[i..].iter().map(|line| DiffLine::Removed(line)));
+    diff.extend(new_lines[j..].iter().map(|line| DiffLine::Added(line)));
+    diff
+}
+
+/// `current` and `rendered` combined, with each place they differ set out
+/// between git-style conflict markers for the user to resolve.
+pub fn conflict_markers(current: &str, rendered: &str) -> String {
+    let mut merged = String::with_capacity(current.len() + rendered.len());
+    let mut ours: Vec<&str> = Vec::new();
+    let mut theirs: Vec<&str> = Vec::new();
+    let flush = |merged: &mut String, ours: &mut Vec<&str>, theirs: &mut Vec<&str>| {
+        if ours.is_empty() && theirs.is_empty() {
+            return;
+        }
+        merged.push_str("<<<<<<< edited\n");
+        for line in ours.drain(..) {
+            merged.push_str(line);
+            merged.push('\n');
+        }
+        merged.push_str("=======\n");
+        for line in theirs.drain(..) {
+            merged.push_str(line);
+            merged.push('\n');
+        }
+        merged.push_str(">>>>>>> rendered\n");
+    };
+
+    for line in line_diff(current, rendered) {
+        match line {
+            DiffLine::Same(text) => {
+                flush(&mut merged, &mut ours, &mut theirs);
+                merged.push_str(text);
+                merged.push('\n');
+            }
+            DiffLine::Removed(text) => ours.push(text),
+            DiffLine::Added(text) => theirs.push(text),
+        }
+    }
+    flush(&mut merged, &mut ours, &mut theirs);
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_skips_unchanged_renders_and_trims() {
+        let mut history = Vec::new();
+
+        assert!(push_record(&mut history, RenderRecord::new("a", None), 2));
+        assert!(!push_record(&mut history, RenderRecord::new("a", None), 2));
+        assert!(push_record(&mut history, RenderRecord::new("b", None), 2));
+        assert!(push_record(&mut history, RenderRecord::new("c", None), 2));
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].hash, config_hash("c"));
+        assert_eq!(history[1].hash, config_hash("b"));
+    }
+
+    #[test]
+    fn forgetting_content_keeps_hashes() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let stored = dir.path().join("npmrc.json");
+        let hashes_only = dir.path().join("kube.json");
+        save_history(
+            &stored,
+            &[
+                RenderRecord::new("token=new", Some("sealed-new".to_string())),
+                RenderRecord::new("token=old", Some("sealed-old".to_string())),
+            ],
+        )
+        .unwrap();
+        save_history(&hashes_only, &[RenderRecord::new("a", None)]).unwrap();
+
+        assert_eq!(forget_content(dir.path(), true).unwrap(), 1);
+
+        let history = load_history(&stored).unwrap();
+        assert_eq!(history[0].hash, config_hash("token=new"));
+        assert!(history.iter().all(|record| record.content.is_none()));
+        assert_eq!(
+            forget_content(&dir.path().join("missing"), false).unwrap(),
+            0
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn history_files_are_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = assert_fs::TempDir::new().unwrap();
+        let fresh = dir.path().join("npmrc.json");
+        let loose = dir.path().join("kube.json");
+        std::fs::write(&loose, "[]").unwrap();
+        std::fs::set_permissions(&loose, std::fs::Permissions::from_mode(0o644)).unwrap();
+        let history = [RenderRecord::new(
+            "token=abc",
+            Some("plain:token=abc".to_string()),
+        )];
+
+        save_history(&fresh, &history).unwrap();
+        save_history(&loose, &history).unwrap();
+
+        for path in [fresh, loose] {
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode, 0o600, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn history_round_trips_through_disk() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join("nested").join("npmrc.json");
+        let history = vec![RenderRecord::new("token=abc", Some("sealed".to_string()))];
+
+        save_history(&path, &history).unwrap();
+
+        assert_eq!(load_history(&path).unwrap(), history);
+        assert!(
+            load_history(&dir.path().join("missing.json"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn diff_marks_changed_lines() {
+        let diff = line_diff("registry=x\ntoken=old\nend", "registry=x\ntoken=new\nend");
+
+        assert_eq!(
+            diff,
+            vec![
+                DiffLine::Same("registry=x"),
+                DiffLine::Removed("token=old"),
+                DiffLine::Added("token=new"),
+                DiffLine::Same("end"),
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_handles_added_and_removed_tails() {
+        assert_eq!(
+            line_diff("a", "a\nb"),
+            vec![DiffLine::Same("a"), DiffLine::Added("b")]
+        );
+        assert_eq!(
+            line_diff("a\nb", ""),
+            vec![DiffLine::Removed("a"), DiffLine::Removed("b")]
+        );
+    }
+
+    #[test]
+    fn marks_conflicts_between_edits_and_renders() {
+        let merged = conflict_markers("a\ntoken=old\nmine\nz\n", "a\ntoken=new\nz\n");
+
+        assert_eq!(
+            merged,
+            "a\n<<<<<<< edited\ntoken=old\nmine\n=======\ntoken=new\n>>>>>>> rendered\nz\n"
+        );
+
+        let dir = assert_fs::TempDir::new().unwrap();
+        let path = dir.path().join(".npmrc");
+        std::fs::write(&path, "token=abc\n").unwrap();
+        assert!(!edited_since(&path, Some(&config_hash("token=abc\n"))));
+        assert!(edited_since(&path, Some(&config_hash("token=old\n"))));
+        assert!(!edited_since(&path, None));
+    }
+}
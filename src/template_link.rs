@@ -0,0 +1,221 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::cli::{expand_home, expand_path};
+use crate::command_log::state_dir;
+
+/// Where renders of linked templates are written. The target path is a
+/// symlink into here, so the secrets live outside the user's dotfiles.
+pub fn rendered_dir() -> Result<PathBuf> {
+    Ok(state_dir()?.join("rendered"))
+}
+
+pub fn rendered_path(template_name: &str) -> Result<PathBuf> {
+    Ok(rendered_dir()?.join(template_name))
+}
+
+/// Whether `path` is a symlink into `rendered_dir`, i.e. a linked
+/// template's target.
+pub fn is_link(path: &Path) -> bool {
+    path.is_symlink()
+        && rendered_dir().is_ok_and(|dir| {
+            std::fs::read_link(path).is_ok_and(|points_to| points_to.starts_with(dir))
+        })
+}
+
+/// Expands a template's target path like `expand_path`, except that a
+/// linked target is known by its own path rather than its render's.
+pub fn target_path(path: &str) -> Result<PathBuf> {
+    let expanded = expand_home(path)?;
+    if is_link(&expanded)
+        && let Some(name) = expanded.file_name()
+    {
+        let parent = match expanded.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        return Ok(expand_path(&parent.to_string_lossy())?.join(name));
+    }
+    expand_path(path)
+}
+
+/// The file that was at the target before it was linked, put back by
+/// `template unlink`.
+pub fn original_path(template_name: &str) -> Result<PathBuf> {
+    Ok(state_dir()?.join("originals").join(template_name))
+}
+
+/// Copies the file at `target` to `original`, keeping its mode so
+/// `unlink` can put it back as it was.
+pub fn save_original(target: &Path, original: &Path) -> Result<()> {
+    if let Some(parent) = original.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    std::fs::copy(target, original)
+        .map(|_| ())
+        .with_context(|| format!("Failed to save {}", target.display()))
+}
+
+/// What's at a linked template's target path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkState {
+    /// A symlink into `rendered_dir`, to this render or one under an old
+    /// template name.
+    Linked,
+    /// Nothing yet.
+    Missing,
+    /// The file that was there when the template was linked, unchanged.
+    Original,
+    /// Something the user put there since; never replaced.
+    Other,
+}
+
+pub fn link_state(target: &Path, rendered_dir: &Path, original: &Path) -> LinkState {
+    if target.is_symlink() {
+        return match std::fs::read_link(target) {
+            Ok(points_to) if points_to.starts_with(rendered_dir) => LinkState::Linked,
+            _ => LinkState::Other,
+        };
+    }
+    match std::fs::read(target) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => LinkState::Missing,
+        Ok(contents) if std::fs::read(original).is_ok_and(|saved| saved == contents) => {
+            LinkState::Original
+        }
+        _ => LinkState::Other,
+    }
+}
+
+/// Points `target` at `rendered`, replacing a link or the original file
+/// already there.
+pub fn place_link(target: &Path, rendered: &Path) -> Result<()> {
+    if std::fs::read_link(target).is_ok_and(|points_to| points_to == rendered) {
+        return Ok(());
+    }
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+    }
+    match std::fs::remove_file(target) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            return Err(err).with_context(|| format!("Failed to replace {}", target.display()));
+        }
+        _ => {}
+    }
+    symlink(rendered, target)
+}
+
+#[cfg(unix)]
+fn symlink(rendered: &Path, target: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(rendered, target).with_context(|| {
+        format!(
+            "Failed to link {} to {}",
+            target.display(),
+            rendered.display()
+        )
+    })
+}
+
+#[cfg(not(unix))]
+fn symlink(_rendered: &Path, _target: &Path) -> Result<()> {
+    anyhow::bail!("Linked templates are only supported on Unix systems")
+}
+
+/// Replaces the link at `target` with the file that was there before it
+/// was linked, if there was one, and deletes the render. Returns whether an
+/// original file was put back.
+pub fn unlink(
+    target: &Path,
+    rendered_dir: &Path,
+    rendered: &Path,
+    original: &Path,
+) -> Result<bool> {
+    match link_state(target, rendered_dir, original) {
+        LinkState::Linked => std::fs::remove_file(target)
+            .with_context(|| format!("Failed to remove link {}", target.display()))?,
+        LinkState::Missing => {}
+        LinkState::Original | LinkState::Other => anyhow::bail!(
+            "{} isn't a link to its render anymore. Move it out of the way first.",
+            target.display()
+        ),
+    }
+
+    let restored = original.exists();
+    if restored {
+        std::fs::copy(original, target).with_context(|| {
+            format!(
+                "Failed to restore {} from {}",
+                target.display(),
+                original.display()
+            )
+        })?;
+        std::fs::remove_file(original)
+            .with_context(|| format!("Failed to remove {}", original.display()))?;
+    }
+    match std::fs::remove_file(rendered) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+            return Err(err).with_context(|| format!("Failed to remove {}", rendered.display()));
+        }
+        _ => {}
+    }
+    Ok(restored)
+}
+
+/// Moves a linked template's render and saved original along with a
+/// template rename.
+pub fn rename(from: &str, to: &str) -> Result<()> {
+    for (old, new) in [
+        (rendered_path(from)?, rendered_path(to)?),
+        (original_path(from)?, original_path(to)?),
+    ] {
+        if !old.exists() {
+            continue;
+        }
+        if let Some(parent) = new.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        std::fs::rename(&old, &new)
+            .with_context(|| format!("Failed to move {} to {}", old.display(), new.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_and_restores_the_original() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let rendered_dir = dir.path().join("rendered");
+        let rendered = rendered_dir.join("npmrc.tmpl");
+        let original = dir.path().join("originals").join("npmrc.tmpl");
+        let target = dir.path().join(".npmrc");
+        std::fs::write(&target, "token=old\n").unwrap();
+        save_original(&target, &original).unwrap();
+
+        assert_eq!(
+            link_state(&target, &rendered_dir, &original),
+            LinkState::Original
+        );
+        crate::managed_files::place(&rendered, b"token=abc\n").unwrap();
+        place_link(&target, &rendered).unwrap();
+
+        assert_eq!(
+            link_state(&target, &rendered_dir, &original),
+            LinkState::Linked
+        );
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "token=abc\n");
+
+        assert!(unlink(&target, &rendered_dir, &rendered, &original).unwrap());
+        assert!(!target.is_symlink());
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "token=old\n");
+        assert!(!rendered.exists() && !original.exists());
+        assert_eq!(
+            link_state(&target, &rendered_dir, &original),
+            LinkState::Other
+        );
+    }
+}
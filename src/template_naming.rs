@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Component, Path};
+
+use crate::app::OpLoadConfig;
+use crate::cache::config_hash;
+
+/// How a target path maps to a file name in the templates directory. Early
+/// versions used the target's file name alone, so `~/.config/a/config.toml`
+/// and `~/.config/b/config.toml` shared one template.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateNaming {
+    /// `config.toml-1a2b3c4d.tmpl`: the file name plus a hash of the full
+    /// target path.
+    #[default]
+    Hashed,
+    /// `home/.config/a/config.toml.tmpl`: the target's directories mirrored
+    /// under the templates directory.
+    Mirrored,
+}
+
+const HASH_LEN: usize = 8;
+
+impl TemplateNaming {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "hashed" => Some(Self::Hashed),
+            "mirrored" => Some(Self::Mirrored),
+            _ => None,
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Hashed => "hashed",
+            Self::Mirrored => "mirrored",
+        }
+    }
+
+    pub fn template_name(self, target: &Path) -> String {
+        match self {
+            Self::Hashed => {
+                let file_name = target.file_name().map_or_else(
+                    || "template".to_string(),
+                    |name| name.to_string_lossy().to_string(),
+                );
+                let hash = config_hash(&target.to_string_lossy());
+                format!("{file_name}-{}.tmpl", &hash[..HASH_LEN])
+            }
+            Self::Mirrored => mirrored_name(target, std::env::var_os("HOME").as_deref()),
+        }
+    }
+}
+
+/// Paths under `$HOME` go under `home/`, everything else under `root/`, so
+/// the two can't meet.
+fn mirrored_name(target: &Path, home: Option<&std::ffi::OsStr>) -> String {
+    let (top, relative) = match home
+        .map(Path::new)
+        .and_then(|home| target.strip_prefix(home).ok())
+    {
+        Some(relative) => ("home", relative),
+        None => ("root", target),
+    };
+
+    let mut parts = vec![top.to_string()];
+    for component in relative.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::Prefix(prefix) => parts.push(
+                prefix
+                    .as_os_str()
+                    .to_string_lossy()
+                    .trim_end_matches(':')
+                    .to_string(),
+            ),
+            Component::ParentDir => parts.push("__up__".to_string()),
+            Component::RootDir | Component::CurDir => {}
+        }
+    }
+    format!("{}.tmpl", parts.join("/"))
+}
+
+/// A managed template whose file name doesn't follow the configured naming.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateRename {
+    pub target: String,
+    pub from: String,
+    pub to: String,
+    /// Other targets whose entries point at the same template file. Only one
+    /// of them can have the content that was written last.
+    pub shared_with: Vec<String>,
+}
+
+/// The renames that bring every managed template in line with `naming`,
+/// sorted by target.
+pub fn plan_renames(config: &OpLoadConfig, naming: TemplateNaming) -> Vec<TemplateRename> {
+    let mut targets_by_name: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (target, template) in &config.templated_files {
+        targets_by_name
+            .entry(template.template_name.as_str())
+            .or_default()
+            .push(target.as_str());
+    }
+
+    let mut renames: Vec<TemplateRename> = config
+        .templated_files
+        .iter()
+        .filter_map(|(target, template)| {
+            let to = naming.template_name(Path::new(target));
+            if to == template.template_name {
+                return None;
+            }
+            let mut shared_with: Vec<String> = targets_by_name[template.template_name.as_str()]
+                .iter()
+                .filter(|other| **other != target.as_str())
+                .map(|other| (*other).to_string())
+                .collect();
+            shared_with.sort();
+            Some(TemplateRename {
+                target: target.clone(),
+                from: template.template_name.clone(),
+                to,
+                shared_with,
+            })
+        })
+        .collect();
+    renames.sort_by(|a, b| a.target.cmp(&b.target));
+    renames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::TemplatedFile;
+
+    #[test]
+    fn same_file_name_in_different_directories_gets_different_names() {
+        let a = Path::new("/home/me/.config/a/config.toml");
+        let b = Path::new("/home/me/.config/b/config.toml");
+
+        let hashed_a = TemplateNaming::Hashed.template_name(a);
+        assert!(hashed_a.starts_with("config.toml-"));
+        assert!(hashed_a.ends_with(".tmpl"));
+        assert_ne!(hashed_a, TemplateNaming::Hashed.template_name(b));
+        assert_eq!(hashed_a, TemplateNaming::Hashed.template_name(a));
+
+        let home = Some(std::ffi::OsStr::new("/home/me"));
+        assert_eq!(mirrored_name(a, home), "home/.config/a/config.toml.tmpl");
+        assert_eq!(
+            mirrored_name(Path::new("/etc/app/config.toml"), home),
+            "root/etc/app/config.toml.tmpl"
+        );
+    }
+
+    #[test]
+    fn plans_renames_for_colliding_legacy_names() {
+        let mut config = OpLoadConfig::default();
+        for target in ["/x/a/config.toml", "/x/b/config.toml"] {
+            config.templated_files.insert(
+                target.to_string(),
+                TemplatedFile {
+                    template_name: "config.toml.tmpl".to_string(),
+                    vars: None,
+                    symlink: false,
+                },
+            );
+        }
+        let current = TemplateNaming::Hashed.template_name(Path::new("/x/.npmrc"));
+        config.templated_files.insert(
+            "/x/.npmrc".to_string(),
+            TemplatedFile {
+                template_name: current,
+                vars: None,
+                symlink: false,
+            },
+        );
+
+        let renames = plan_renames(&config, TemplateNaming::Hashed);
+
+        assert_eq!(renames.len(), 2);
+        assert_eq!(renames[0].target, "/x/a/config.toml");
+        assert_eq!(renames[0].from, "config.toml.tmpl");
+        assert_eq!(renames[0].shared_with, ["/x/b/config.toml"]);
+        assert_ne!(renames[0].to, renames[1].to);
+    }
+}
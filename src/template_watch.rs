@@ -0,0 +1,150 @@
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::app::OpLoadConfig;
+
+/// Editors often save in several steps (write a temporary file, rename it,
+/// touch it), so changes arriving this close together are handled as one.
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// Watches the templates directory and the config file.
+pub struct SourceWatcher {
+    _watcher: notify::RecommendedWatcher,
+    rx: mpsc::Receiver<notify::Result<notify::Event>>,
+}
+
+impl SourceWatcher {
+    pub fn new(templates_dir: &Path, config_file: &Path) -> Result<Self> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(tx).context("Failed to start the file watcher")?;
+        watcher
+            .watch(templates_dir, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {}", templates_dir.display()))?;
+        // The directory rather than the file, so a config saved by replacing
+        // it is still seen.
+        if let Some(config_dir) = config_file.parent() {
+            watcher
+                .watch(config_dir, RecursiveMode::NonRecursive)
+                .with_context(|| format!("Failed to watch {}", config_dir.display()))?;
+        }
+        Ok(Self {
+            _watcher: watcher,
+            rx,
+        })
+    }
+
+    /// Blocks until something changes, then returns every path changed
+    /// until things settle.
+    pub fn next_changes(&self) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        collect(
+            self.rx.recv().context("The file watcher stopped")?,
+            &mut paths,
+        );
+        while let Ok(event) = self.rx.recv_timeout(SETTLE) {
+            collect(event, &mut paths);
+        }
+        paths.sort();
+        paths.dedup();
+        Ok(paths)
+    }
+}
+
+fn collect(event: notify::Result<notify::Event>, paths: &mut Vec<PathBuf>) {
+    match event {
+        Ok(event) if !matches!(event.kind, EventKind::Access(_)) => paths.extend(event.paths),
+        Ok(_) => {}
+        Err(err) => log::debug!("File watcher error: {err}"),
+    }
+}
+
+/// What a batch of changed paths touches: whether the config file changed,
+/// and the names of changed files in the templates directory.
+pub fn classify(
+    changed: &[PathBuf],
+    templates_dir: &Path,
+    config_file: &Path,
+) -> (bool, Vec<String>) {
+    let config_changed = changed.iter().any(|path| path == config_file);
+    let template_names = changed
+        .iter()
+        .filter_map(|path| path.strip_prefix(templates_dir).ok())
+        .map(|name| name.to_string_lossy().to_string())
+        .collect();
+    (config_changed, template_names)
+}
+
+/// Targets, sorted, of the templates named in `template_names` or that
+/// include one of them. Names that are neither, such as an editor's swap
+/// files, are ignored.
+pub fn affected_targets(
+    config: &OpLoadConfig,
+    templates_dir: &Path,
+    template_names: &[String],
+) -> Vec<String> {
+    let includes_changed = |template_name: &str| {
+        std::fs::read_to_string(templates_dir.join(template_name)).is_ok_and(|content| {
+            crate::template_engine::included_files(&content, templates_dir)
+                .iter()
+                .any(|included| template_names.contains(included))
+        })
+    };
+    let mut targets: Vec<String> = config
+        .templated_files
+        .iter()
+        .filter(|(_, template)| {
+            template_names.contains(&template.template_name)
+                || includes_changed(&template.template_name)
+        })
+        .map(|(target, _)| target.clone())
+        .collect();
+    targets.sort();
+    targets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::TemplatedFile;
+
+    #[test]
+    fn maps_changed_templates_to_their_targets() {
+        let mut config = OpLoadConfig::default();
+        for (target, name) in [
+            ("/home/me/.npmrc", "npmrc.tmpl"),
+            ("/home/me/.kube/config", "home/.kube/config.tmpl"),
+        ] {
+            config.templated_files.insert(
+                target.to_string(),
+                TemplatedFile {
+                    template_name: name.to_string(),
+                    vars: None,
+                    symlink: false,
+                },
+            );
+        }
+        let templates_dir = Path::new("/config/op_loader/templates");
+        let config_file = Path::new("/config/op_loader/default-config.toml");
+
+        let (config_changed, names) = classify(
+            &[
+                templates_dir.join("home/.kube/config.tmpl"),
+                templates_dir.join(".npmrc.tmpl.swp"),
+                PathBuf::from("/config/op_loader/other.toml"),
+            ],
+            templates_dir,
+            config_file,
+        );
+
+        assert!(!config_changed);
+        assert_eq!(
+            affected_targets(&config, templates_dir, &names),
+            ["/home/me/.kube/config"]
+        );
+        assert!(classify(&[config_file.to_path_buf()], templates_dir, config_file).0);
+    }
+}
@@ -0,0 +1,96 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+const CURSOR: &str = "█";
+
+/// Removes the last user-perceived character, so one Backspace deletes a
+/// whole emoji or accented letter rather than a single code point.
+pub fn pop_grapheme(input: &mut String) {
+    if let Some((idx, _)) = input.grapheme_indices(true).next_back() {
+        input.truncate(idx);
+    }
+}
+
+/// The end of `input` that fits in `width` terminal columns, followed by a
+/// block cursor. Wide characters (CJK, emoji) count as two columns, so the
+/// cursor stays visible however long the input gets.
+pub fn with_cursor(input: &str, width: u16) -> String {
+    let available = usize::from(width).saturating_sub(CURSOR.width());
+
+    let mut start = input.len();
+    let mut used = 0;
+    for (idx, grapheme) in input.grapheme_indices(true).rev() {
+        used += grapheme.width();
+        if used > available {
+            break;
+        }
+        start = idx;
+    }
+
+    format!("{}{CURSOR}", &input[start..])
+}
+
+/// `text` cut to `width` columns (ending in `…` when cut) and padded with
+/// spaces to exactly `width`, for lining up columns.
+pub fn fit_width(text: &str, width: usize) -> String {
+    let mut fitted = String::new();
+    let mut used = 0;
+    if text.width() <= width {
+        fitted.push_str(text);
+        used = text.width();
+    } else if width > 0 {
+        for grapheme in text.graphemes(true) {
+            if used + grapheme.width() > width - 1 {
+                break;
+            }
+            used += grapheme.width();
+            fitted.push_str(grapheme);
+        }
+        fitted.push('…');
+        used += 1;
+    }
+    fitted.push_str(&" ".repeat(width.saturating_sub(used)));
+    fitted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pop_removes_whole_graphemes() {
+        let mut input = "café👍🏽".to_string();
+
+        pop_grapheme(&mut input);
+        assert_eq!(input, "café");
+
+        let mut input = "cafe\u{301}".to_string();
+        pop_grapheme(&mut input);
+        assert_eq!(input, "caf");
+
+        let mut empty = String::new();
+        pop_grapheme(&mut empty);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn fit_width_pads_and_truncates_by_columns() {
+        assert_eq!(fit_width("abc", 5), "abc  ");
+        assert_eq!(fit_width("abcdef", 4), "abc…");
+        assert_eq!(fit_width("東京タワー", 6), "東京… ");
+        assert_eq!(fit_width("abc", 0), "");
+    }
+
+    #[test]
+    fn cursor_fits_short_input() {
+        assert_eq!(with_cursor("abc", 10), "abc█");
+    }
+
+    #[test]
+    fn long_wide_input_keeps_its_tail_visible() {
+        let rendered = with_cursor("東京タワー", 6);
+
+        assert_eq!(rendered, "ワー█");
+        assert!(rendered.width() <= 6);
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::SystemTime;
+
+use crate::cache::unix_secs;
+
+/// Authenticator apps rotate codes every 30 seconds unless the `otpauth://`
+/// URI says otherwise.
+pub const DEFAULT_PERIOD: u64 = 30;
+
+/// The rotation period from an `otpauth://totp/...?period=N` URI.
+pub fn period_from_uri(uri: &str) -> u64 {
+    uri.split_once('?')
+        .map(|(_, query)| query)
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .find_map(|pair| pair.strip_prefix("period="))
+        .and_then(|period| period.parse().ok())
+        .filter(|&period| period > 0)
+        .unwrap_or(DEFAULT_PERIOD)
+}
+
+/// Which rotation window `now` falls in; codes only change between windows.
+pub fn window(now: SystemTime, period: u64) -> u64 {
+    unix_secs(now) / period
+}
+
+pub fn seconds_remaining(now: SystemTime, period: u64) -> u64 {
+    period - unix_secs(now) % period
+}
+
+/// Splits a code in half for reading, e.g. `123 456`.
+pub fn format_code(code: &str) -> String {
+    let code = code.trim();
+    if code.len() < 6 || !code.is_ascii() {
+        return code.to_string();
+    }
+    let (first, second) = code.split_at(code.len() / 2);
+    format!("{first} {second}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn reads_period_from_uri() {
+        assert_eq!(
+            period_from_uri("otpauth://totp/GitHub:me?secret=ABC&period=60&digits=6"),
+            60
+        );
+        assert_eq!(period_from_uri("otpauth://totp/GitHub:me?secret=ABC"), 30);
+        assert_eq!(period_from_uri("otpauth://totp/x?period=0"), 30);
+    }
+
+    #[test]
+    fn counts_down_within_a_window() {
+        let now = UNIX_EPOCH + Duration::from_secs(3 * 30 + 12);
+
+        assert_eq!(window(now, 30), 3);
+        assert_eq!(seconds_remaining(now, 30), 18);
+        assert_eq!(format_code("123456\n"), "123 456");
+        assert_eq!(format_code("12345678"), "1234 5678");
+    }
+}
@@ -2,26 +2,98 @@ use ratatui::{
     Frame,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::Line,
+    text::{Line, Span},
     widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
 };
 
-use crate::app::{Account, App, FocusedPanel, ItemField, Vault};
-use crate::command_log::CommandLogEntry;
+use crate::app::{
+    Account, App, BulkMapFocus, BulkMapRow, DEFAULT_LEFT_PANE_PERCENT, EnvVarField, FocusedPanel,
+    ITEM_CREATE_CATEGORIES, InjectVarConfig, ItemCreateFocus, ItemField, SigninStatus, VarEdit,
+    Vault, VaultCreateField, VaultDetails, VaultItemDetails, category_label,
+};
+use crate::command_log::{CommandLogEntry, CommandStatus, ErrorDetails};
+use crate::help::{HELP_SECTIONS, help_line_count};
+use crate::password_gen::Recipe;
+use crate::recent_items::RecentItem;
+use crate::text_input::{fit_width, with_cursor};
+use unicode_width::UnicodeWidthStr;
+
+/// Narrower than this, the columns side by side leave the left panels
+/// unusable, so they move above the items instead.
+const STACKED_BELOW_WIDTH: u16 = 100;
+/// Shorter than this, the command log is hidden; toasts still show new
+/// entries and `L` the whole log.
+const COMMAND_LOG_MIN_HEIGHT: u16 = 30;
+
+struct PanelAreas {
+    accounts: Rect,
+    vaults: Rect,
+    recent: Rect,
+    vars: Rect,
+    command_log: Option<Rect>,
+    items: Rect,
+    details: Rect,
+    footer: Rect,
+}
 
-pub fn render(frame: &mut Frame, app: &mut App) {
+fn panel_areas(app: &App, area: Rect) -> PanelAreas {
+    if area.width < STACKED_BELOW_WIDTH {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(6),
+                Constraint::Length(6),
+                Constraint::Fill(2),
+                Constraint::Fill(1),
+                Constraint::Length(1),
+            ])
+            .split(area);
+        let halves = |row: Rect| {
+            Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(row)
+        };
+        let (top, middle) = (halves(rows[0]), halves(rows[1]));
+        return PanelAreas {
+            accounts: top[0],
+            vaults: top[1],
+            recent: middle[0],
+            vars: middle[1],
+            command_log: None,
+            items: rows[2],
+            details: rows[3],
+            footer: rows[4],
+        };
+    }
+
+    let left_percent = app
+        .config
+        .as_ref()
+        .map_or(DEFAULT_LEFT_PANE_PERCENT, |config| {
+            config.tui.left_pane_percent()
+        });
     let outer_layout = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
-        .split(frame.area());
+        .constraints([
+            Constraint::Percentage(left_percent),
+            Constraint::Percentage(100 - left_percent),
+        ])
+        .split(area);
 
+    let show_command_log = area.height >= COMMAND_LOG_MIN_HEIGHT;
     let left_pane_layout = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(5),
             Constraint::Min(8),
+            Constraint::Length(7),
             Constraint::Length(8),
-            Constraint::Length(8),
+            Constraint::Length(if show_command_log {
+                app.command_log_size.height()
+            } else {
+                0
+            }),
         ])
         .split(outer_layout[0]);
 
@@ -34,13 +106,37 @@ pub fn render(frame: &mut Frame, app: &mut App) {
         ])
         .split(outer_layout[1]);
 
-    render_list_panel(&AccountListPanel, frame, app, left_pane_layout[0]);
-    render_list_panel(&VaultListPanel, frame, app, left_pane_layout[1]);
-    render_list_panel(&VarsListPanel, frame, app, left_pane_layout[2]);
-    render_command_log(frame, app, left_pane_layout[3]);
-    render_vault_item_panel(frame, app, right_pane_layout[0]);
-    render_item_details_panel(frame, app, right_pane_layout[1]);
-    render_right_column_footer(frame, right_pane_layout[2]);
+    PanelAreas {
+        accounts: left_pane_layout[0],
+        vaults: left_pane_layout[1],
+        recent: left_pane_layout[2],
+        vars: left_pane_layout[3],
+        command_log: show_command_log.then_some(left_pane_layout[4]),
+        items: right_pane_layout[0],
+        details: right_pane_layout[1],
+        footer: right_pane_layout[2],
+    }
+}
+
+pub fn render(frame: &mut Frame, app: &mut App) {
+    let screen_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)])
+        .split(frame.area());
+    let areas = panel_areas(app, screen_layout[1]);
+
+    render_breadcrumb_bar(frame, app, screen_layout[0]);
+    render_list_panel(&AccountListPanel, frame, app, areas.accounts);
+    render_list_panel(&VaultListPanel, frame, app, areas.vaults);
+    render_list_panel(&RecentListPanel, frame, app, areas.recent);
+    render_list_panel(&VarsListPanel, frame, app, areas.vars);
+    if let Some(area) = areas.command_log {
+        render_command_log(frame, app, area);
+    }
+    render_vault_item_panel(frame, app, areas.items);
+    render_item_details_panel(frame, app, areas.details);
+    render_right_column_footer(frame, app, areas.footer);
+    render_toast(frame, app, areas.details);
 
     if app.modal.is_some() {
         render_modal(frame, app);
@@ -65,6 +161,15 @@ trait ListPanel {
         false
     }
 
+    fn is_loading(&self, _app: &App) -> bool {
+        false
+    }
+
+    /// Extra status shown after an entry, such as a cool-down countdown.
+    fn status_suffix(&self, _app: &App, _item: &Self::Item) -> Option<String> {
+        None
+    }
+
     fn selected_idx(&self, app: &App) -> Option<usize>;
     fn list_state<'a>(&self, app: &'a mut App) -> &'a mut ListState;
 
@@ -101,19 +206,33 @@ fn render_list_panel<P: ListPanel>(panel: &P, frame: &mut Frame, app: &mut App,
 }
 
 fn render_list_inner<P: ListPanel>(panel: &P, frame: &mut Frame, app: &mut App, area: Rect) {
+    if panel.is_loading(app) {
+        render_loading(frame, area);
+        return;
+    }
+
     let selected_idx = panel.selected_idx(app);
     let selected_color = panel.selected_color();
+    let is_focused = app.focused_panel == panel.focus_variant();
+    let panel_items = panel.items(app);
 
-    let items: Vec<ListItem> = panel
-        .items(app)
+    let items: Vec<ListItem> = panel_items
         .iter()
         .enumerate()
         .map(|(idx, item)| {
             let is_selected = selected_idx == Some(idx);
             let is_favorite = panel.is_favorite(app, item);
+            let number = app.row_number(idx, panel_items.len(), is_focused);
             let prefix = panel.selection_prefix(app, item, is_selected);
             let suffix = if is_favorite { " ★" } else { "" };
-            let content = format!("{}{}{}", prefix, panel.display_item(item), suffix);
+            let status = panel.status_suffix(app, item).unwrap_or_default();
+            let content = format!(
+                "{number}{}{}{}{}",
+                prefix,
+                panel.display_item(item),
+                suffix,
+                status
+            );
 
             ListItem::new(content).style(if is_selected {
                 Style::default().fg(selected_color)
@@ -139,6 +258,12 @@ fn render_vault_item_panel(frame: &mut Frame, app: &mut App, area: Rect) {
 
     let block = Block::default()
         .title(" [2] Items ")
+        .title_bottom(
+            Line::from(
+                " [n] New  [space] Mark  [b] Bulk map  [y] Copy op cmd  [a] Archive  [x] Delete ",
+            )
+            .right_aligned(),
+        )
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
         .border_style(if is_focused {
@@ -164,6 +289,12 @@ fn render_item_details_panel(frame: &mut Frame, app: &mut App, area: Rect) {
 
     let block = Block::default()
         .title(" [3] Details ")
+        .title_bottom(
+            Line::from(
+                " [c] Copy value  [r] Reveal  [e] Edit  [n] Notes  [y] Copy op read  [Y] Copy ref ",
+            )
+            .right_aligned(),
+        )
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
         .border_style(if is_focused {
@@ -178,8 +309,52 @@ fn render_item_details_panel(frame: &mut Frame, app: &mut App, area: Rect) {
     render_item_details(frame, app, inner);
 }
 
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Shown in place of a panel's content while `op` runs on a worker thread.
+fn render_loading(frame: &mut Frame, area: Rect) {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis());
+    let spinner = SPINNER_FRAMES[(millis / 100) as usize % SPINNER_FRAMES.len()];
+
+    let loading =
+        Paragraph::new(format!("{spinner} Loading…")).style(Style::default().fg(Color::DarkGray));
+    frame.render_widget(loading, area);
+}
+
+/// Wider category names (e.g. `Wireless Router`) are cut with `…`.
+const ITEM_CATEGORY_MAX_WIDTH: usize = 16;
+/// `format_age` output such as `59m` or `120d`.
+const ITEM_AGE_WIDTH: usize = 4;
+
 fn render_filtered_vault_items(frame: &mut Frame, app: &mut App, area: Rect) {
+    if app.vault_items_loading() {
+        render_loading(frame, area);
+        return;
+    }
+
     let selected_idx = app.selected_vault_item_idx;
+    let now = std::time::SystemTime::now();
+
+    // Columns: prefix (2), title, category, age. The highlight symbol takes 2.
+    let category_width = app
+        .filtered_item_indices
+        .iter()
+        .map(|&idx| {
+            category_label(&app.vault_items[idx].category)
+                .chars()
+                .count()
+        })
+        .max()
+        .unwrap_or(0)
+        .min(ITEM_CATEGORY_MAX_WIDTH);
+    let is_focused = app.focused_panel == FocusedPanel::VaultItemList;
+    let item_count = app.filtered_item_indices.len();
+    let number_width = app.row_number(0, item_count, is_focused).chars().count();
+    let title_width = usize::from(area.width)
+        .saturating_sub(number_width + 4 + 2 + category_width + 2 + ITEM_AGE_WIDTH)
+        .max(1);
 
     let items: Vec<ListItem> = app
         .filtered_item_indices
@@ -188,10 +363,43 @@ fn render_filtered_vault_items(frame: &mut Frame, app: &mut App, area: Rect) {
         .map(|(display_idx, &real_idx)| {
             let item = &app.vault_items[real_idx];
             let is_selected = selected_idx == Some(display_idx);
-            let prefix = if is_selected { "● " } else { "  " };
-            let content = format!("{}{}", prefix, item.title);
-
-            ListItem::new(content).style(if is_selected {
+            let prefix = if app.items_marked.contains(&item.id) {
+                "✓ "
+            } else if is_selected {
+                "● "
+            } else {
+                "  "
+            };
+            let age = item
+                .age(now)
+                .map(crate::cli::format_age)
+                .unwrap_or_default();
+            let mut spans = vec![
+                Span::styled(
+                    app.row_number(display_idx, item_count, is_focused),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(prefix),
+            ];
+            spans.extend(highlight_matches(
+                &item.title,
+                &fit_width(&item.title, title_width),
+                &app.title_match_positions(&item.title),
+            ));
+            spans.extend([
+                Span::raw("  "),
+                Span::styled(
+                    fit_width(&category_label(&item.category), category_width),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(
+                    format!("  {age:>ITEM_AGE_WIDTH$}"),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]);
+            let line = Line::from(spans);
+
+            ListItem::new(line).style(if is_selected {
                 Style::default().fg(Color::Cyan)
             } else {
                 Style::default()
@@ -210,11 +418,55 @@ fn render_filtered_vault_items(frame: &mut Frame, app: &mut App, area: Rect) {
     frame.render_stateful_widget(list, area, &mut app.vault_item_list_state);
 }
 
+/// `fitted`, a [`fit_width`] of `text`, with the chars at `positions` in
+/// `text` underlined in yellow. Only the part `fit_width` kept is marked.
+fn highlight_matches(text: &str, fitted: &str, positions: &[usize]) -> Vec<Span<'static>> {
+    if positions.is_empty() {
+        return vec![Span::raw(fitted.to_string())];
+    }
+    let kept = text
+        .chars()
+        .zip(fitted.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let matched_style = Style::default()
+        .fg(Color::Yellow)
+        .add_modifier(Modifier::UNDERLINED);
+
+    let mut spans = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (idx, c) in fitted.chars().enumerate() {
+        let matched = idx < kept && positions.contains(&idx);
+        if matched != run_matched && !run.is_empty() {
+            let text = std::mem::take(&mut run);
+            spans.push(if run_matched {
+                Span::styled(text, matched_style)
+            } else {
+                Span::raw(text)
+            });
+        }
+        run_matched = matched;
+        run.push(c);
+    }
+    spans.push(if run_matched {
+        Span::styled(run, matched_style)
+    } else {
+        Span::raw(run)
+    });
+    spans
+}
+
 fn render_search_box(frame: &mut Frame, app: &App, area: Rect) {
     let is_active = app.search_active;
 
+    let title = match app.field_index_loading {
+        Some(items) => format!(" [/] Search (indexing fields of {items} items…) "),
+        None if is_active => " [/] Search · Ctrl+F: search fields of all items ".to_string(),
+        None => " [/] Search ".to_string(),
+    };
     let block = Block::default()
-        .title(" [/] Search ")
+        .title(title)
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
         .border_style(if is_active {
@@ -233,7 +485,7 @@ fn render_search_box(frame: &mut Frame, app: &App, area: Rect) {
             "Press / to search".to_string()
         }
     } else if is_active {
-        format!("{}█", app.search_query)
+        with_cursor(&app.search_query, inner.width)
     } else {
         app.search_query.clone()
     };
@@ -249,32 +501,72 @@ fn render_search_box(frame: &mut Frame, app: &App, area: Rect) {
 }
 
 fn render_item_details(frame: &mut Frame, app: &mut App, area: Rect) {
+    if app.item_details_loading.is_some() {
+        render_loading(frame, area);
+        return;
+    }
+
     let Some(details) = &app.selected_item_details else {
         let empty = Paragraph::new("Select an item and press Enter");
         frame.render_widget(empty, area);
         return;
     };
 
-    let fields: Vec<&ItemField> = details
-        .fields
-        .iter()
-        .filter(|f| f.label != "notesPlain")
-        .collect();
+    let header = item_details_header(app, details);
+    let header_height = u16::try_from(header.len())
+        .unwrap_or(u16::MAX)
+        .min(area.height / 2);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(header_height), Constraint::Fill(1)])
+        .split(area);
 
+    // The highlight symbol takes 2 columns.
+    let row_width = usize::from(chunks[1].width).saturating_sub(2).max(1);
+    let fields = details.detail_fields();
     let items: Vec<ListItem> = fields
         .iter()
         .enumerate()
         .map(|(idx, f)| {
             let is_selected = app.selected_field_idx == Some(idx);
-            let value = if f.field_type == "CONCEALED" {
-                "********".to_string()
-            } else {
-                f.value.clone().unwrap_or_default()
-            };
+            let value = field_value_display(app, f);
             let prefix = if is_selected { "● " } else { "  " };
-            let content = format!("{}{}: {}\n    {}", prefix, f.label, value, f.reference);
 
-            ListItem::new(content).style(if is_selected {
+            let mut lines = Vec::new();
+            let starts_section = idx == 0 || fields[idx - 1].section_label() != f.section_label();
+            if let Some(section) = f.section_label().filter(|_| starts_section) {
+                lines.push(Line::styled(
+                    section.to_uppercase(),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
+            let number = app.row_number(
+                idx,
+                fields.len(),
+                app.focused_panel == FocusedPanel::VaultItemDetail,
+            );
+            let field_line = format!("{number}{}{}: {}", prefix, f.label, value);
+            let reference_line = format!("    {}", f.reference);
+            if is_selected {
+                // Wrap the selected row so long references can be read whole.
+                lines.extend(
+                    wrap_to_width(&field_line, row_width, 2)
+                        .into_iter()
+                        .map(Line::from),
+                );
+                lines.extend(
+                    wrap_to_width(&reference_line, row_width, 4)
+                        .into_iter()
+                        .map(Line::from),
+                );
+            } else {
+                lines.push(Line::from(field_line));
+                lines.push(Line::from(reference_line));
+            }
+
+            ListItem::new(lines).style(if is_selected {
                 Style::default().fg(Color::Cyan)
             } else {
                 Style::default()
@@ -282,6 +574,8 @@ fn render_item_details(frame: &mut Frame, app: &mut App, area: Rect) {
         })
         .collect();
 
+    frame.render_widget(Paragraph::new(header).wrap(Wrap { trim: false }), chunks[0]);
+
     let list = List::new(items)
         .highlight_style(
             Style::default()
@@ -290,14 +584,134 @@ fn render_item_details(frame: &mut Frame, app: &mut App, area: Rect) {
         )
         .highlight_symbol("> ");
 
-    frame.render_stateful_widget(list, area, &mut app.item_detail_list_state);
+    frame.render_stateful_widget(list, chunks[1], &mut app.item_detail_list_state);
+}
+
+/// Splits `text` into lines at most `width` columns wide, indenting the
+/// continuation lines by `indent`.
+fn wrap_to_width(text: &str, width: usize, indent: usize) -> Vec<String> {
+    use unicode_width::UnicodeWidthChar;
+
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let c_width = c.width().unwrap_or(0);
+        if used + c_width > width && used > indent {
+            lines.push(std::mem::take(&mut line));
+            line.push_str(&" ".repeat(indent));
+            used = indent;
+        }
+        line.push(c);
+        used += c_width;
+    }
+    lines.push(line);
+    lines
+}
+
+/// The item's URLs and notes, shown above its fields. Notes show their first
+/// line until expanded with `n`.
+fn item_details_header(app: &App, details: &VaultItemDetails) -> Vec<Line<'static>> {
+    let label_style = Style::default().fg(Color::DarkGray);
+    let mut lines = Vec::new();
+
+    for url in &details.urls {
+        let label = url
+            .label
+            .as_deref()
+            .filter(|l| !l.is_empty())
+            .unwrap_or("website");
+        let href_style = if url.primary {
+            Style::default().add_modifier(Modifier::UNDERLINED)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("{label}: "), label_style),
+            Span::styled(url.href.clone(), href_style),
+        ]));
+    }
+
+    if let Some(notes) = details.notes() {
+        if app.secrets_hidden {
+            lines.push(Line::from(vec![
+                Span::styled("notes: ", label_style),
+                Span::raw("********"),
+            ]));
+        } else if app.notes_expanded {
+            lines.push(Line::from(vec![
+                Span::styled("notes: ", label_style),
+                Span::styled("[n] collapse", label_style),
+            ]));
+            lines.extend(notes.lines().map(|line| Line::from(format!("  {line}"))));
+        } else {
+            let first = notes.lines().next().unwrap_or_default().to_string();
+            let mut spans = vec![Span::styled("notes: ", label_style), Span::raw(first)];
+            if notes.trim_end().lines().nth(1).is_some() {
+                spans.push(Span::styled(" … [n] expand", label_style));
+            }
+            lines.push(Line::from(spans));
+        }
+    }
+
+    if !lines.is_empty() {
+        lines.push(Line::default());
+    }
+    lines
+}
+
+/// Concealed fields are masked unless revealed with `r`; everything is
+/// masked while the terminal is unfocused. OTP fields show the current code
+/// rather than the `otpauth://` URI, which holds the seed.
+fn field_value_display(app: &App, field: &ItemField) -> String {
+    if field.is_otp() && !app.secrets_hidden {
+        totp_display(app)
+    } else if let Some(value) = app.revealed_value(field) {
+        value.to_string()
+    } else if field.field_type == "CONCEALED" || field.is_otp() || app.secrets_hidden {
+        "********".to_string()
+    } else {
+        field.value.clone().unwrap_or_default()
+    }
+}
+
+fn totp_display(app: &App) -> String {
+    let Some(totp) = app.totp.as_ref().filter(|totp| {
+        app.selected_item_details
+            .as_ref()
+            .is_some_and(|details| details.id == totp.item_id)
+    }) else {
+        return "********".to_string();
+    };
+
+    let now = std::time::SystemTime::now();
+    match app.current_totp_code(now) {
+        Some(code) => format!(
+            "{}  ({}s)",
+            crate::totp::format_code(code),
+            crate::totp::seconds_remaining(now, totp.period)
+        ),
+        None if totp.refreshing => "refreshing…".to_string(),
+        None if app.focused_panel == FocusedPanel::VaultItemDetail => "fetching…".to_string(),
+        None => "focus [3] to show the code".to_string(),
+    }
 }
 
 fn render_command_log(frame: &mut Frame, app: &App, area: Rect) {
     let block = Block::default()
-        .title(" Command Log ")
+        .title(if app.flash_pending {
+            " Command Log • done "
+        } else {
+            " Command Log "
+        })
+        .title_bottom(Line::from(" [l] Resize  [L] Full screen ").right_aligned())
         .borders(Borders::ALL)
-        .border_type(BorderType::Rounded);
+        .border_type(BorderType::Rounded)
+        .border_style(if app.flash_pending {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        });
 
     let visible_lines = area.height.saturating_sub(2) as usize;
 
@@ -314,8 +728,115 @@ fn render_command_log(frame: &mut Frame, app: &App, area: Rect) {
     frame.render_widget(paragraph, area);
 }
 
-fn render_right_column_footer(frame: &mut Frame, area: Rect) {
-    let text = "[Enter] Select  [k/Up] Up  [j/Down] Down  [q] Quit ";
+/// The newest command log entry, briefly, in the bottom-right corner of
+/// `area`. The full entry stays in the log.
+fn render_toast(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(entry) = app.command_log.toast(std::time::Instant::now()) else {
+        return;
+    };
+    let color = match entry.status {
+        CommandStatus::Success { .. } => Color::Green,
+        CommandStatus::Failed { .. } => Color::Red,
+    };
+    let text = entry.display();
+
+    let max_width = area.width.saturating_sub(2);
+    let width = u16::try_from(UnicodeWidthStr::width(text.as_str()) + 4)
+        .unwrap_or(u16::MAX)
+        .min(max_width);
+    let height = 3.min(area.height);
+    if width < 5 || height < 3 {
+        return;
+    }
+    let toast_area = Rect::new(
+        area.x + area.width - width - 1,
+        area.y + area.height - height,
+        width,
+        height,
+    );
+
+    frame.render_widget(Clear, toast_area);
+    let toast = Paragraph::new(text)
+        .style(Style::default().fg(color))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .border_style(Style::default().fg(color)),
+        );
+    frame.render_widget(toast, toast_area);
+}
+
+/// `a@example.com ▸ Private ▸ GitHub` on the left and whether `op` works for
+/// that account on the right.
+fn render_breadcrumb_bar(frame: &mut Frame, app: &App, area: Rect) {
+    let (status, status_color) = match app.signin_status(std::time::SystemTime::now()) {
+        SigninStatus::Loading => ("… loading accounts".to_string(), Color::DarkGray),
+        SigninStatus::SignedIn => match app.selected_whoami() {
+            Some(whoami) => (
+                format!(
+                    "● {} on {} · {}",
+                    whoami.email,
+                    whoami.url,
+                    whoami
+                        .unlock_method(crate::signin::has_session_token())
+                        .label()
+                ),
+                Color::Green,
+            ),
+            None => ("● signed in".to_string(), Color::Green),
+        },
+        SigninStatus::CoolingDown(remaining) => (
+            format!("⏳ op failing, retry in {}s", remaining.as_secs().max(1)),
+            Color::Yellow,
+        ),
+        SigninStatus::SignedOut => ("✗ not signed in".to_string(), Color::Red),
+    };
+    let status_width = u16::try_from(status.width()).unwrap_or(u16::MAX) + 1;
+
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(status_width)])
+        .split(area);
+
+    let mut spans = vec![Span::raw(" ")];
+    for (idx, part) in app.breadcrumb().into_iter().enumerate() {
+        if idx > 0 {
+            spans.push(Span::styled(" ▸ ", Style::default().fg(Color::DarkGray)));
+        }
+        spans.push(Span::styled(
+            part,
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+    }
+    frame.render_widget(Paragraph::new(Line::from(spans)), chunks[0]);
+
+    let status = Paragraph::new(status)
+        .style(Style::default().fg(status_color))
+        .alignment(Alignment::Right);
+    frame.render_widget(status, chunks[1]);
+}
+
+fn render_right_column_footer(frame: &mut Frame, app: &App, area: Rect) {
+    if let Some(input) = &app.jump_input {
+        let paragraph = Paragraph::new(format!(":{input}█  [Enter] Jump to row  [Esc] Cancel "))
+            .style(Style::default().fg(Color::Yellow));
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    if app.modal.is_none()
+        && let Some(error) = &app.error_message
+    {
+        let first_line = error.lines().next().unwrap_or_default();
+        let paragraph = Paragraph::new(format!("✗ {first_line}  [e] Details "))
+            .style(Style::default().fg(Color::Red))
+            .alignment(Alignment::Right);
+        frame.render_widget(paragraph, area);
+        return;
+    }
+
+    let text = "[Enter] Select  [k/Up] Up  [j/Down] Down  [e] Errors  [?] Help  [q] Quit ";
     let paragraph = Paragraph::new(text)
         .style(Style::default().fg(Color::DarkGray))
         .alignment(Alignment::Right);
@@ -330,6 +851,19 @@ fn render_modal(frame: &mut Frame, app: &App) {
     };
 
     match modal {
+        crate::app::Modal::EnvVar {
+            env_var_name,
+            field_reference,
+            editing: Some(edit),
+            prefix,
+        } => render_var_edit_modal(
+            frame,
+            app,
+            env_var_name,
+            field_reference,
+            edit,
+            prefix.as_deref(),
+        ),
         crate::app::Modal::EnvVar { .. } => {
             // Content: field info (5) + spacer (1) + input (3) + error (1) + help (1) = 11, plus border (2) = 13
             let modal_width = area.width * 60 / 100;
@@ -362,11 +896,7 @@ fn render_modal(frame: &mut Frame, app: &App) {
                 .split(inner);
 
             if let Some(field) = app.modal_selected_field() {
-                let value_display = if field.field_type == "CONCEALED" {
-                    "********".to_string()
-                } else {
-                    field.value.clone().unwrap_or_default()
-                };
+                let value_display = field_value_display(app, field);
 
                 let info_text = format!(
                     "Field: {}\nValue: {}\n\nReference:\n{}",
@@ -378,7 +908,10 @@ fn render_modal(frame: &mut Frame, app: &App) {
             }
 
             let input_block = Block::default()
-                .title(" Environment Variable Name ")
+                .title(format!(
+                    " Environment Variable Name (prefix: {}) ",
+                    app.modal_env_var_prefix().unwrap_or("none")
+                ))
                 .borders(Borders::ALL)
                 .border_type(BorderType::Rounded)
                 .border_style(Style::default().fg(Color::Cyan));
@@ -386,7 +919,7 @@ fn render_modal(frame: &mut Frame, app: &App) {
             let input_inner = input_block.inner(chunks[2]);
             frame.render_widget(input_block, chunks[2]);
 
-            let input_text = format!("{}█", app.modal_env_var_name().unwrap_or(""));
+            let input_text = with_cursor(app.modal_env_var_name().unwrap_or(""), input_inner.width);
             let input = Paragraph::new(input_text);
             frame.render_widget(input, input_inner);
 
@@ -397,11 +930,17 @@ fn render_modal(frame: &mut Frame, app: &App) {
                 frame.render_widget(error_text, chunks[3]);
             }
 
-            let help = Paragraph::new("Enter: Save  |  Esc: Cancel")
+            let help = Paragraph::new("Enter: Save  |  Ctrl+P: Prefix  |  Esc: Cancel")
                 .style(Style::default().fg(Color::DarkGray))
                 .alignment(Alignment::Center);
             frame.render_widget(help, chunks[4]);
         }
+        crate::app::Modal::VarCollision {
+            env_var_name,
+            op_reference,
+            existing,
+            ..
+        } => render_var_collision_modal(frame, app, env_var_name, op_reference, existing),
         crate::app::Modal::VarDeleteConfirm { vars } => {
             let modal_width = area.width * 60 / 100;
             let modal_height = 7_u16.min(area.height - 4);
@@ -448,7 +987,911 @@ fn render_modal(frame: &mut Frame, app: &App) {
                 .alignment(Alignment::Center);
             frame.render_widget(help, chunks[2]);
         }
+        crate::app::Modal::VaultCreate {
+            name,
+            description,
+            active_field,
+        } => render_vault_create_modal(frame, app, name, description, *active_field),
+        crate::app::Modal::VaultDetails { details } => render_vault_details_modal(frame, details),
+        crate::app::Modal::FieldEdit {
+            item_title,
+            field_label,
+            value,
+            confirming,
+            ..
+        } => render_field_edit_modal(frame, app, item_title, field_label, value, *confirming),
+        crate::app::Modal::PasswordGenerator {
+            recipe, password, ..
+        } => render_password_generator_modal(frame, app, *recipe, password),
+        crate::app::Modal::ItemCreate {
+            title,
+            category_idx,
+            fields,
+            focus,
+        } => render_item_create_modal(frame, app, title, *category_idx, fields, *focus),
+        crate::app::Modal::ErrorDetails { details, scroll } => {
+            render_error_modal(frame, details, *scroll);
+        }
+        crate::app::Modal::Help { scroll } => render_help_modal(frame, app, *scroll),
+        crate::app::Modal::CommandLog { scroll } => render_command_log_modal(frame, app, *scroll),
+        crate::app::Modal::EnvPreview {
+            vars,
+            warnings,
+            revealed,
+            scroll,
+        } => render_env_preview_modal(frame, vars, warnings, *revealed, *scroll),
+        crate::app::Modal::PlaceFile { file, path } => {
+            render_place_file_modal(frame, app, &file.title, path);
+        }
+        crate::app::Modal::QuitConfirm { reasons } => render_quit_confirm_modal(frame, reasons),
+        crate::app::Modal::SignIn { account_id, .. } => {
+            render_signin_modal(frame, app, account_id.as_deref());
+        }
+        crate::app::Modal::BulkMap { field, rows, focus } => {
+            render_bulk_map_modal(frame, app, field, rows, *focus)
+        }
+        crate::app::Modal::ItemDeleteConfirm {
+            item_title,
+            archive,
+            confirmed_once,
+            affected_vars,
+            ..
+        } => render_item_delete_modal(
+            frame,
+            app,
+            item_title,
+            *archive,
+            *confirmed_once,
+            affected_vars,
+        ),
+    }
+}
+
+fn render_item_delete_modal(
+    frame: &mut Frame,
+    app: &App,
+    item_title: &str,
+    archive: bool,
+    confirmed_once: bool,
+    affected_vars: &[String],
+) {
+    let title = if archive {
+        " Archive Item "
+    } else {
+        " Delete Item "
+    };
+    let inner = render_modal_frame(frame, title, 9);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+
+    let header_text = if archive {
+        format!("Archive \"{item_title}\"?")
+    } else if confirmed_once {
+        format!("Really delete \"{item_title}\"? This cannot be undone.")
+    } else {
+        format!("Permanently delete \"{item_title}\"?")
+    };
+    let header = Paragraph::new(header_text)
+        .style(Style::default().fg(if confirmed_once {
+            Color::Red
+        } else {
+            Color::Yellow
+        }))
+        .alignment(Alignment::Center);
+    frame.render_widget(header, chunks[0]);
+
+    let affected_text = if affected_vars.is_empty() {
+        "No managed vars reference this item.".to_string()
+    } else {
+        format!(
+            "These managed vars will have broken references:\n{}",
+            affected_vars.join(", ")
+        )
+    };
+    let affected = Paragraph::new(affected_text).wrap(Wrap { trim: false });
+    frame.render_widget(affected, chunks[1]);
+
+    if let Some(ref error) = app.error_message {
+        let error_text = Paragraph::new(error.as_str())
+            .style(Style::default().fg(Color::Red))
+            .alignment(Alignment::Center);
+        frame.render_widget(error_text, chunks[2]);
+    }
+
+    let help = Paragraph::new("Y: Confirm  |  N/Esc: Cancel")
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center);
+    frame.render_widget(help, chunks[3]);
+}
+
+fn render_signin_modal(frame: &mut Frame, app: &App, account_id: Option<&str>) {
+    let inner = render_modal_frame(frame, " Session Expired ", 7);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+
+    let account = account_id
+        .and_then(|account_id| app.accounts.iter().find(|a| a.account_uuid == account_id))
+        .map_or("your default account", |account| account.email.as_str());
+    let message = Paragraph::new(format!(
+        "op needs you to sign in to {account} again.\nSigning in shows op's prompt (or the 1Password app's), then retries what failed."
+    ))
+    .wrap(Wrap { trim: false });
+    frame.render_widget(message, chunks[0]);
+
+    if let Some(ref error) = app.error_message {
+        let error_text = Paragraph::new(error.lines().next().unwrap_or_default())
+            .style(Style::default().fg(Color::Red))
+            .alignment(Alignment::Center);
+        frame.render_widget(error_text, chunks[1]);
+    }
+
+    let help = Paragraph::new("Enter: Sign in  |  Esc: Cancel")
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center);
+    frame.render_widget(help, chunks[2]);
+}
+
+fn render_var_collision_modal(
+    frame: &mut Frame,
+    app: &App,
+    env_var_name: &str,
+    op_reference: &str,
+    existing: &InjectVarConfig,
+) {
+    let inner = render_modal_frame(frame, " Name Already Mapped ", 9);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+
+    let lines = vec![
+        Line::from(vec![
+            Span::styled(env_var_name, Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(" already maps:"),
+        ]),
+        Line::styled(
+            format!(
+                "  {} ({})",
+                existing.op_reference,
+                app.managed_var_account_label(existing)
+            ),
+            Style::default().fg(Color::DarkGray),
+        ),
+        Line::raw("Overwrite it with:"),
+        Line::styled(
+            format!("  {op_reference}"),
+            Style::default().fg(Color::Cyan),
+        ),
+    ];
+    frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), chunks[0]);
+
+    if let Some(ref error) = app.error_message {
+        let error_text = Paragraph::new(error.lines().next().unwrap_or_default())
+            .style(Style::default().fg(Color::Red))
+            .alignment(Alignment::Center);
+        frame.render_widget(error_text, chunks[1]);
+    }
+
+    let help = Paragraph::new("o: Overwrite  |  r/Esc: Rename")
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center);
+    frame.render_widget(help, chunks[2]);
+}
+
+fn render_place_file_modal(frame: &mut Frame, app: &App, title: &str, path: &str) {
+    // Content: item (1) + path (3) + error (1) + help (1), plus border (2)
+    let inner = render_modal_frame(frame, " Place Document ", 8);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // item
+            Constraint::Length(3), // path input
+            Constraint::Length(1), // error message
+            Constraint::Length(1), // help text
+        ])
+        .split(inner);
+
+    frame.render_widget(
+        Paragraph::new(format!("Document: {title} (written with mode 600)")),
+        chunks[0],
+    );
+    render_text_input(frame, chunks[1], " Path ", path, true);
+
+    if let Some(ref error) = app.error_message {
+        let error_text = Paragraph::new(error.as_str())
+            .style(Style::default().fg(Color::Red))
+            .alignment(Alignment::Center);
+        frame.render_widget(error_text, chunks[2]);
+    }
+
+    let help = Paragraph::new("Enter: Download and place  |  Esc: Cancel")
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center);
+    frame.render_widget(help, chunks[3]);
+}
+
+fn render_quit_confirm_modal(frame: &mut Frame, reasons: &[String]) {
+    // Content: heading (1) + reasons + help (1), plus border (2)
+    let reason_count = u16::try_from(reasons.len()).unwrap_or(u16::MAX);
+    let inner = render_modal_frame(frame, " Really Quit? ", reason_count.saturating_add(5));
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner);
+
+    let mut lines = vec![Line::raw("Quitting now loses:")];
+    lines.extend(
+        reasons
+            .iter()
+            .map(|reason| Line::raw(format!("  • {reason}"))),
+    );
+    frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), chunks[0]);
+
+    let help = Paragraph::new("y/q: Quit  |  n/Esc: Stay")
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center);
+    frame.render_widget(help, chunks[1]);
+}
+
+/// Clear a centered area of the given height, draw a titled modal border
+/// around it, and return the inner area for the modal's content.
+fn render_modal_frame(frame: &mut Frame, title: &str, height: u16) -> Rect {
+    let area = frame.area();
+    let modal_width = area.width * 60 / 100;
+    let modal_height = height.min(area.height.saturating_sub(4));
+    let modal_x = (area.width - modal_width) / 2;
+    let modal_y = (area.height - modal_height) / 2;
+
+    let modal_area = Rect::new(modal_x, modal_y, modal_width, modal_height);
+
+    frame.render_widget(Clear, modal_area);
+
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Yellow));
+
+    let inner = block.inner(modal_area);
+    frame.render_widget(block, modal_area);
+    inner
+}
+
+fn render_text_input(frame: &mut Frame, area: Rect, title: &str, value: &str, is_active: bool) {
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(if is_active {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default()
+        });
+
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let text = if is_active {
+        with_cursor(value, inner.width)
+    } else {
+        value.to_string()
+    };
+    frame.render_widget(Paragraph::new(text), inner);
+}
+
+fn render_vault_create_modal(
+    frame: &mut Frame,
+    app: &App,
+    name: &str,
+    description: &str,
+    active_field: VaultCreateField,
+) {
+    // Content: name (3) + description (3) + error (1) + help (1) = 8, plus border (2) = 10
+    let inner = render_modal_frame(frame, " Create Vault ", 10);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3), // name input
+            Constraint::Length(3), // description input
+            Constraint::Length(1), // error message
+            Constraint::Length(1), // help text
+        ])
+        .split(inner);
+
+    render_text_input(
+        frame,
+        chunks[0],
+        " Name ",
+        name,
+        active_field == VaultCreateField::Name,
+    );
+    render_text_input(
+        frame,
+        chunks[1],
+        " Description (optional) ",
+        description,
+        active_field == VaultCreateField::Description,
+    );
+
+    if let Some(ref error) = app.error_message {
+        let error_text = Paragraph::new(error.as_str())
+            .style(Style::default().fg(Color::Red))
+            .alignment(Alignment::Center);
+        frame.render_widget(error_text, chunks[2]);
+    }
+
+    let help = Paragraph::new("Tab: Switch Field  |  Enter: Create  |  Esc: Cancel")
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center);
+    frame.render_widget(help, chunks[3]);
+}
+
+fn render_password_generator_modal(frame: &mut Frame, app: &App, recipe: Recipe, password: &str) {
+    // Content: password (3) + options (3) + recipe (1) + help (1), plus border (2)
+    let inner = render_modal_frame(frame, " Generate Password ", 10);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3), // password
+            Constraint::Length(3), // options
+            Constraint::Length(1), // op recipe
+            Constraint::Length(1), // help text
+        ])
+        .split(inner);
+
+    let shown = if app.secrets_hidden {
+        "*".repeat(password.chars().count())
+    } else {
+        password.to_string()
+    };
+    render_text_input(frame, chunks[0], " Password ", &shown, false);
+
+    let check = |on: bool| if on { "[x]" } else { "[ ]" };
+    let options = Paragraph::new(vec![
+        Line::from(format!("Length: ◀ {} ▶", recipe.length)),
+        Line::from(format!(
+            "{} Digits    {} Symbols",
+            check(recipe.digits),
+            check(recipe.symbols)
+        )),
+    ]);
+    frame.render_widget(options, chunks[1]);
+
+    let op_recipe = Paragraph::new(format!("--generate-password={}", recipe.op_recipe()))
+        .style(Style::default().fg(Color::DarkGray));
+    frame.render_widget(op_recipe, chunks[2]);
+
+    let help = Paragraph::new(
+        "←→: Length  |  d: Digits  |  s: Symbols  |  r: Regenerate  |  Enter: Use  |  Esc: Back",
+    )
+    .style(Style::default().fg(Color::DarkGray))
+    .alignment(Alignment::Center);
+    frame.render_widget(help, chunks[3]);
+}
+
+fn render_field_edit_modal(
+    frame: &mut Frame,
+    app: &App,
+    item_title: &str,
+    field_label: &str,
+    value: &str,
+    confirming: bool,
+) {
+    // Content: field (1) + spacer (1) + input (3) + error/prompt (1) + help (1), plus border (2)
+    let inner = render_modal_frame(frame, " Edit Field Value ", 9);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // item and field
+            Constraint::Length(1), // spacer
+            Constraint::Length(3), // value input
+            Constraint::Length(1), // error message or confirmation
+            Constraint::Length(1), // help text
+        ])
+        .split(inner);
+
+    frame.render_widget(
+        Paragraph::new(format!("{item_title} › {field_label}")),
+        chunks[0],
+    );
+
+    let shown = if app.secrets_hidden || confirming {
+        "*".repeat(value.chars().count())
+    } else {
+        value.to_string()
+    };
+    render_text_input(frame, chunks[2], " New Value ", &shown, !confirming);
+
+    if confirming {
+        let prompt = Paragraph::new(format!("Replace the value of {field_label} in 1Password?"))
+            .style(Style::default().fg(Color::Yellow))
+            .alignment(Alignment::Center);
+        frame.render_widget(prompt, chunks[3]);
+    } else if let Some(ref error) = app.error_message {
+        let error_text = Paragraph::new(error.as_str())
+            .style(Style::default().fg(Color::Red))
+            .alignment(Alignment::Center);
+        frame.render_widget(error_text, chunks[3]);
+    }
+
+    let help = if confirming {
+        "Y: Save  |  N: Back  |  Esc: Cancel"
+    } else {
+        "Enter: Continue  |  Ctrl+G: Generate  |  Esc: Cancel"
+    };
+    let help = Paragraph::new(help)
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center);
+    frame.render_widget(help, chunks[4]);
+}
+
+fn render_item_create_modal(
+    frame: &mut Frame,
+    app: &App,
+    title: &str,
+    category_idx: usize,
+    fields: &[String],
+    focus: ItemCreateFocus,
+) {
+    // Content: title (3) + category (3) + fields (rows + 2) + error (1) + help (1), plus border (2)
+    let row_count = u16::try_from(fields.len()).unwrap_or(u16::MAX);
+    let inner = render_modal_frame(frame, " Create Item ", row_count.saturating_add(12));
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3), // title input
+            Constraint::Length(3), // category picker
+            Constraint::Min(3),    // field rows
+            Constraint::Length(1), // error message
+            Constraint::Length(1), // help text
+        ])
+        .split(inner);
+
+    render_text_input(
+        frame,
+        chunks[0],
+        " Title ",
+        title,
+        focus == ItemCreateFocus::Title,
+    );
+    let category = ITEM_CREATE_CATEGORIES
+        .get(category_idx)
+        .map_or("", |(name, _, _)| name);
+    let category_block = Block::default()
+        .title(" Category ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(if focus == ItemCreateFocus::Category {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default()
+        });
+    let category_inner = category_block.inner(chunks[1]);
+    frame.render_widget(category_block, chunks[1]);
+    frame.render_widget(Paragraph::new(format!("◀ {category} ▶")), category_inner);
+
+    let fields_block = Block::default()
+        .title(" Fields (label=value) ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(if matches!(focus, ItemCreateFocus::Field(_)) {
+            Style::default().fg(Color::Cyan)
+        } else {
+            Style::default()
+        });
+    let fields_inner = fields_block.inner(chunks[2]);
+    frame.render_widget(fields_block, chunks[2]);
+
+    let lines: Vec<Line> = fields
+        .iter()
+        .enumerate()
+        .map(|(idx, field)| {
+            let is_active = focus == ItemCreateFocus::Field(idx);
+            let text = if is_active && !app.secrets_hidden {
+                with_cursor(field, fields_inner.width.saturating_sub(2))
+            } else {
+                mask_field_value(field)
+            };
+            Line::from(vec![
+                Span::raw(if is_active { "> " } else { "  " }),
+                Span::styled(
+                    text,
+                    if is_active {
+                        Style::default().fg(Color::Cyan)
+                    } else {
+                        Style::default()
+                    },
+                ),
+            ])
+        })
+        .collect();
+    let active_row = match focus {
+        ItemCreateFocus::Field(idx) => u16::try_from(idx).unwrap_or(u16::MAX),
+        _ => 0,
+    };
+    let scroll = active_row.saturating_sub(fields_inner.height.saturating_sub(1));
+    frame.render_widget(Paragraph::new(lines).scroll((scroll, 0)), fields_inner);
+
+    if let Some(ref error) = app.error_message {
+        let error_text = Paragraph::new(error.as_str())
+            .style(Style::default().fg(Color::Red))
+            .alignment(Alignment::Center);
+        frame.render_widget(error_text, chunks[3]);
+    }
+
+    let help = Paragraph::new(
+        "Tab/↑↓: Move  |  ←→: Category  |  Ctrl+G: Generate  |  Enter: Create  |  Esc: Cancel",
+    )
+    .style(Style::default().fg(Color::DarkGray))
+    .alignment(Alignment::Center);
+    frame.render_widget(help, chunks[4]);
+}
+
+/// `password=********` for rows that aren't being edited.
+fn mask_field_value(field: &str) -> String {
+    match field.split_once('=') {
+        Some((label, value)) if !value.is_empty() => format!("{label}=********"),
+        _ => field.to_string(),
+    }
+}
+
+fn render_var_edit_modal(
+    frame: &mut Frame,
+    app: &App,
+    env_var_name: &str,
+    op_reference: &str,
+    edit: &VarEdit,
+    prefix: Option<&str>,
+) {
+    // Content: account (1) + spacer (1) + name (3) + reference (3) + error (1) + help (1), plus border (2)
+    let inner = render_modal_frame(frame, " Edit Managed Var ", 12);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1), // account
+            Constraint::Length(1), // spacer
+            Constraint::Length(3), // name input
+            Constraint::Length(3), // reference input
+            Constraint::Length(1), // error message
+            Constraint::Length(1), // help text
+        ])
+        .split(inner);
+
+    let account = Paragraph::new(format!(
+        "Account: {}  Prefix: {}",
+        edit.account_id,
+        prefix.unwrap_or("none")
+    ))
+    .style(Style::default().fg(Color::DarkGray));
+    frame.render_widget(account, chunks[0]);
+
+    render_text_input(
+        frame,
+        chunks[2],
+        " Environment Variable Name ",
+        env_var_name,
+        edit.active_field == EnvVarField::Name,
+    );
+    render_text_input(
+        frame,
+        chunks[3],
+        " Reference ",
+        op_reference,
+        edit.active_field == EnvVarField::Reference,
+    );
+
+    if let Some(ref error) = app.error_message {
+        let error_text = Paragraph::new(error.as_str())
+            .style(Style::default().fg(Color::Red))
+            .alignment(Alignment::Center);
+        frame.render_widget(error_text, chunks[4]);
+    }
+
+    let help =
+        Paragraph::new("Tab: Switch field  |  Ctrl+P: Prefix  |  Enter: Save  |  Esc: Cancel")
+            .style(Style::default().fg(Color::DarkGray))
+            .alignment(Alignment::Center);
+    frame.render_widget(help, chunks[5]);
+}
+
+fn render_bulk_map_modal(
+    frame: &mut Frame,
+    app: &App,
+    field: &str,
+    rows: &[BulkMapRow],
+    focus: BulkMapFocus,
+) {
+    // Content: field (3) + rows + error (1) + help (1), plus border (2)
+    let row_count = u16::try_from(rows.len()).unwrap_or(u16::MAX);
+    let inner = render_modal_frame(frame, " Map Marked Items ", row_count.saturating_add(7));
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(3), // field input
+            Constraint::Min(1),    // one row per item
+            Constraint::Length(1), // error message
+            Constraint::Length(1), // help text
+        ])
+        .split(inner);
+
+    render_text_input(
+        frame,
+        chunks[0],
+        " Field (same for every item) ",
+        field,
+        focus == BulkMapFocus::Field,
+    );
+
+    let title_width = rows
+        .iter()
+        .map(|row| row.item_title.chars().count())
+        .max()
+        .unwrap_or(0);
+    let name_width = chunks[1]
+        .width
+        .saturating_sub(u16::try_from(title_width + 7).unwrap_or(u16::MAX));
+    let lines: Vec<Line> = rows
+        .iter()
+        .enumerate()
+        .map(|(idx, row)| {
+            let is_active = focus == BulkMapFocus::Row(idx);
+            let name = if is_active {
+                with_cursor(&row.env_var_name, name_width)
+            } else {
+                row.env_var_name.clone()
+            };
+            Line::from(vec![
+                Span::raw(if is_active { "> " } else { "  " }),
+                Span::raw(format!("{:<title_width$}", row.item_title)),
+                Span::styled(" → ", Style::default().fg(Color::DarkGray)),
+                Span::styled(
+                    name,
+                    if is_active {
+                        Style::default().fg(Color::Cyan)
+                    } else {
+                        Style::default()
+                    },
+                ),
+            ])
+        })
+        .collect();
+    let active_row = match focus {
+        BulkMapFocus::Row(idx) => u16::try_from(idx).unwrap_or(u16::MAX),
+        BulkMapFocus::Field => 0,
+    };
+    let scroll = active_row.saturating_sub(chunks[1].height.saturating_sub(1));
+    frame.render_widget(Paragraph::new(lines).scroll((scroll, 0)), chunks[1]);
+
+    if let Some(ref error) = app.error_message {
+        let error_text = Paragraph::new(error.as_str())
+            .style(Style::default().fg(Color::Red))
+            .alignment(Alignment::Center);
+        frame.render_widget(error_text, chunks[2]);
+    }
+
+    let help = Paragraph::new("Tab/↑↓: Move  |  Enter: Save all  |  Esc: Cancel")
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center);
+    frame.render_widget(help, chunks[3]);
+}
+
+fn render_error_modal(frame: &mut Frame, details: &ErrorDetails, scroll: u16) {
+    let area = frame.area();
+    let modal_width = area.width * 80 / 100;
+    let modal_height = area.height * 70 / 100;
+    let modal_area = Rect::new(
+        (area.width - modal_width) / 2,
+        (area.height - modal_height) / 2,
+        modal_width,
+        modal_height,
+    );
+
+    frame.render_widget(Clear, modal_area);
+
+    let block = Block::default()
+        .title(format!(" Error: {} ", details.source))
+        .title_bottom(" [j/k] Scroll  [y] Copy  [Esc] Close ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Red));
+
+    let message = Paragraph::new(details.message.as_str())
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .scroll((scroll, 0));
+    frame.render_widget(message, modal_area);
+}
+
+/// The whole command log over the full screen, so multi-line `op` errors
+/// can be read in full.
+fn render_command_log_modal(frame: &mut Frame, app: &App, scroll: u16) {
+    let area = frame.area();
+    frame.render_widget(Clear, area);
+
+    let lines: Vec<Line> = app
+        .command_log
+        .full_lines()
+        .into_iter()
+        .map(|line| {
+            if line.starts_with('✗') {
+                Line::styled(line, Style::default().fg(Color::Red))
+            } else if line.starts_with(' ') {
+                Line::styled(line, Style::default().fg(Color::DarkGray))
+            } else {
+                Line::raw(line)
+            }
+        })
+        .collect();
+
+    let block = Block::default()
+        .title(" Command Log (newest first) ")
+        .title_bottom(" [j/k] Scroll  [Esc] Close ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Cyan));
+
+    let log = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .scroll((scroll, 0));
+    frame.render_widget(log, area);
+}
+
+fn render_env_preview_modal(
+    frame: &mut Frame,
+    vars: &[(String, String)],
+    warnings: &[String],
+    revealed: bool,
+    scroll: u16,
+) {
+    let area = frame.area();
+    frame.render_widget(Clear, area);
+
+    let lines: Vec<Line> = crate::app::env_preview_lines(vars, warnings, revealed)
+        .into_iter()
+        .map(|line| {
+            if line.starts_with('#') {
+                Line::styled(line, Style::default().fg(Color::Yellow))
+            } else {
+                Line::raw(line)
+            }
+        })
+        .collect();
+
+    let reveal_hint = if revealed { "Mask" } else { "Reveal" };
+    let block = Block::default()
+        .title(format!(" op-loader env preview ({} vars) ", vars.len()))
+        .title_bottom(format!(" [r] {reveal_hint}  [j/k] Scroll  [Esc] Close "))
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Cyan));
+
+    let preview = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim: false })
+        .scroll((scroll, 0));
+    frame.render_widget(preview, area);
+}
+
+fn render_help_modal(frame: &mut Frame, app: &App, scroll: u16) {
+    let area = frame.area();
+    let modal_width = area.width * 70 / 100;
+    let modal_height = area.height * 80 / 100;
+    let modal_area = Rect::new(
+        (area.width - modal_width) / 2,
+        (area.height - modal_height) / 2,
+        modal_width,
+        modal_height,
+    );
+
+    frame.render_widget(Clear, modal_area);
+
+    let mut lines: Vec<Line> = Vec::with_capacity(help_line_count());
+    for (idx, section) in HELP_SECTIONS.iter().enumerate() {
+        if idx > 0 {
+            lines.push(Line::default());
+        }
+
+        let is_current = section.panel == Some(app.focused_panel);
+        let title_style = if is_current {
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().add_modifier(Modifier::BOLD)
+        };
+        lines.push(Line::styled(section.title, title_style));
+
+        for (keys, description) in section.bindings {
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {keys:<10}"), Style::default().fg(Color::Yellow)),
+                Span::raw(*description),
+            ]));
+        }
     }
+
+    let block = Block::default()
+        .title(" Keybindings ")
+        .title_bottom(" [j/k] Scroll  [Esc] Close ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(Color::Cyan));
+
+    let help = Paragraph::new(lines).block(block).scroll((scroll, 0));
+    frame.render_widget(help, modal_area);
+}
+
+fn render_vault_details_modal(frame: &mut Frame, details: &VaultDetails) {
+    let unknown = || "(unknown)".to_string();
+    let permissions = details.permissions.as_ref().map_or_else(
+        || "(unavailable)".to_string(),
+        |perms| {
+            if perms.is_empty() {
+                "(none)".to_string()
+            } else {
+                perms.join(", ")
+            }
+        },
+    );
+
+    let text = format!(
+        "Name: {}\nID: {}\nType: {}\nItems: {}\nDescription: {}\nCreated: {}\nUpdated: {}\n\nYour permissions:\n{}",
+        details.name,
+        details.id,
+        details.vault_type.clone().unwrap_or_else(unknown),
+        details.items.map_or_else(unknown, |n| n.to_string()),
+        details
+            .description
+            .clone()
+            .filter(|d| !d.is_empty())
+            .unwrap_or_else(|| "(none)".to_string()),
+        details.created_at.clone().unwrap_or_else(unknown),
+        details.updated_at.clone().unwrap_or_else(unknown),
+        permissions,
+    );
+
+    let inner = render_modal_frame(frame, " Vault Details ", 16);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(inner);
+
+    let info = Paragraph::new(text).wrap(Wrap { trim: false });
+    frame.render_widget(info, chunks[0]);
+
+    let help = Paragraph::new("Esc: Close")
+        .style(Style::default().fg(Color::DarkGray))
+        .alignment(Alignment::Center);
+    frame.render_widget(help, chunks[1]);
 }
 
 struct AccountListPanel;
@@ -465,6 +1908,9 @@ impl ListPanel for AccountListPanel {
     fn focus_variant(&self) -> FocusedPanel {
         FocusedPanel::AccountList
     }
+    fn is_loading(&self, app: &App) -> bool {
+        app.accounts_loading()
+    }
     fn items<'a>(&self, app: &'a App) -> &'a [Account] {
         &app.accounts
     }
@@ -477,6 +1923,11 @@ impl ListPanel for AccountListPanel {
             .and_then(|c| c.default_account_id.as_ref())
             .is_some_and(|id| id == &item.account_uuid)
     }
+    fn status_suffix(&self, app: &App, item: &Self::Item) -> Option<String> {
+        app.account_backoff
+            .remaining(&item.account_uuid, std::time::SystemTime::now())
+            .map(|remaining| format!(" ⏳ retry in {}s", remaining.as_secs().max(1)))
+    }
     fn list_state<'a>(&self, app: &'a mut App) -> &'a mut ListState {
         &mut app.account_list_state
     }
@@ -497,11 +1948,14 @@ impl ListPanel for VaultListPanel {
         " [1] Vaults "
     }
     fn title_bottom(&self) -> Option<&str> {
-        Some(" [f] Favorite ")
+        Some(" [f] Favorite  [n] New  [i] Info ")
     }
     fn focus_variant(&self) -> FocusedPanel {
         FocusedPanel::VaultList
     }
+    fn is_loading(&self, app: &App) -> bool {
+        app.vaults_loading()
+    }
     fn items<'a>(&self, app: &'a App) -> &'a [Vault] {
         &app.vaults
     }
@@ -531,6 +1985,18 @@ impl ListPanel for VaultListPanel {
 
 struct VarsListPanel;
 
+/// References longer than this are cut with `…` in the Vars panel.
+const VAR_REFERENCE_WIDTH: usize = 40;
+
+fn truncate_end(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
 impl ListPanel for VarsListPanel {
     type Item = String;
 
@@ -539,7 +2005,7 @@ impl ListPanel for VarsListPanel {
     }
 
     fn title_bottom(&self) -> Option<&str> {
-        Some(" [Space] Select  [c] Copy Name  [d] Delete ")
+        Some(" [Space] Select  [c] Copy Name  [e] Edit  [t] Test  [d] Delete  [p] Preview ")
     }
 
     fn focus_variant(&self) -> FocusedPanel {
@@ -554,6 +2020,22 @@ impl ListPanel for VarsListPanel {
         item.clone()
     }
 
+    /// `  me@example.com  op://Work/Stripe/credential  [template]`, so it's
+    /// clear what a var is before deleting it.
+    fn status_suffix(&self, app: &App, item: &Self::Item) -> Option<String> {
+        let var = app.config.as_ref()?.inject_vars.get(item)?;
+        let template = if app.managed_vars_in_templates.contains(item) {
+            "  [template]"
+        } else {
+            ""
+        };
+        Some(format!(
+            "  {}  {}{template}",
+            app.managed_var_account_label(var),
+            truncate_end(&var.op_reference, VAR_REFERENCE_WIDTH)
+        ))
+    }
+
     fn list_state<'a>(&self, app: &'a mut App) -> &'a mut ListState {
         &mut app.managed_vars_list_state
     }
@@ -574,3 +2056,61 @@ impl ListPanel for VarsListPanel {
         }
     }
 }
+
+struct RecentListPanel;
+
+impl ListPanel for RecentListPanel {
+    type Item = RecentItem;
+
+    fn title(&self) -> &'static str {
+        " [u] Recent "
+    }
+
+    fn title_bottom(&self) -> Option<&str> {
+        Some(" [Enter] Open ")
+    }
+
+    fn focus_variant(&self) -> FocusedPanel {
+        FocusedPanel::RecentList
+    }
+
+    fn items<'a>(&self, app: &'a App) -> &'a [RecentItem] {
+        &app.recent_items.items
+    }
+
+    fn display_item(&self, item: &Self::Item) -> String {
+        item.title.clone()
+    }
+
+    /// `  Private  3d`: the vault and how long ago the item was used.
+    fn status_suffix(&self, _app: &App, item: &Self::Item) -> Option<String> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let age = std::time::Duration::from_secs(now.saturating_sub(item.used_at));
+        Some(format!(
+            "  {}  {}",
+            item.vault_name,
+            crate::cli::format_age(age)
+        ))
+    }
+
+    fn list_state<'a>(&self, app: &'a mut App) -> &'a mut ListState {
+        &mut app.recent_list_state
+    }
+
+    fn selected_color(&self) -> Color {
+        Color::Cyan
+    }
+
+    /// The entry for the item open in the Details panel.
+    fn selected_idx(&self, app: &App) -> Option<usize> {
+        let account_id = &app.selected_account()?.account_uuid;
+        let item_id = &app.selected_item_details.as_ref()?.id;
+        app.recent_items
+            .items
+            .iter()
+            .position(|recent| &recent.account_id == account_id && &recent.item_id == item_id)
+    }
+}